@@ -36,9 +36,21 @@ app = FastAPI(
 
 audit = AuditLogger(settings.audit_log_path)
 
+# forgeimages-cli exit codes (forgeimages_core::exit_code) with an HTTP
+# meaning of their own; anything else non-zero is a 500
+EXIT_VALIDATION_FAILED = 2
+CLI_ERROR_STATUS = {
+    3: 404,  # Template not found
+    4: 409,  # Template/engine version mismatch
+    5: 400,  # Invalid payload
+}
 
-def call_cli(command: list[str]) -> tuple[int, str]:
-    """Call the Rust CLI and return (exit_code, stdout)."""
+
+def call_cli(command: list[str], stdin: Optional[str] = None) -> tuple[int, str]:
+    """Call the Rust CLI and return (exit_code, stdout).
+
+    `stdin` feeds a `--payload -` argument.
+    """
     cli_path = settings.cli_path
     templates_dir = settings.templates_dir
 
@@ -51,6 +63,7 @@ def call_cli(command: list[str]) -> tuple[int, str]:
     try:
         result = subprocess.run(
             full_command,
+            input=stdin,
             capture_output=True,
             text=True,
             timeout=30,
@@ -90,7 +103,7 @@ async def health():
 @app.get("/templates", response_model=list[TemplateInfo])
 async def list_templates():
     """List all available templates."""
-    exit_code, output = call_cli(["templates"])
+    exit_code, output = call_cli(["templates", "--include-deprecated"])
 
     if exit_code != 0:
         raise HTTPException(status_code=500, detail="Failed to list templates")
@@ -151,7 +164,7 @@ async def validate_asset(
     )
 
     # Return 422 on validation failure
-    if exit_code == 2:  # Validation failure
+    if exit_code == EXIT_VALIDATION_FAILED:
         raise HTTPException(
             status_code=422,
             detail={
@@ -164,8 +177,8 @@ async def validate_asset(
 
     if exit_code != 0:
         raise HTTPException(
-            status_code=500,
-            detail=result.get("error", "Unknown error")
+            status_code=CLI_ERROR_STATUS.get(exit_code, 500),
+            detail=result.get("message", "Unknown error")
         )
 
     return ValidationResult(**result)
@@ -196,12 +209,12 @@ async def compile_asset(
     # Build payload
     payload = compile_request.model_dump()
 
-    # Call CLI
+    # Call CLI; the payload goes over stdin, since embedded sources can exceed ARG_MAX
     exit_code, output = call_cli([
         "compile",
         "--template", template_id,
-        "--payload", json.dumps(payload),
-    ])
+        "--payload", "-",
+    ], stdin=json.dumps(payload))
 
     try:
         result = json.loads(output)
@@ -220,7 +233,7 @@ async def compile_asset(
 
     # Determine success
     success = result.get("success", False)
-    error_message = result.get("error")
+    error_message = result.get("message")
 
     # Log the request
     audit.log_compile(
@@ -234,7 +247,7 @@ async def compile_asset(
     )
 
     # Return 422 on validation failure
-    if exit_code == 2:  # Validation/compilation failure
+    if exit_code == EXIT_VALIDATION_FAILED:
         raise HTTPException(
             status_code=422,
             detail={
@@ -246,7 +259,7 @@ async def compile_asset(
 
     if exit_code != 0:
         raise HTTPException(
-            status_code=500,
+            status_code=CLI_ERROR_STATUS.get(exit_code, 500),
             detail=error_message or "Unknown error"
         )
 
@@ -256,7 +269,7 @@ async def compile_asset(
 @app.get("/template/{template_id}")
 async def get_template(template_id: str):
     """Get details for a specific template."""
-    exit_code, output = call_cli(["templates"])
+    exit_code, output = call_cli(["templates", "--include-deprecated"])
 
     if exit_code != 0:
         raise HTTPException(status_code=500, detail="Failed to list templates")
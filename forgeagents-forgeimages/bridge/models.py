@@ -6,9 +6,13 @@ These models define the contract between Python and Rust.
 
 from datetime import datetime
 from typing import Optional
-from pydantic import BaseModel, Field, field_validator
+from pydantic import BaseModel, Field, field_serializer, field_validator
 import re
 
+# Largest integer JavaScript reads exactly; the engine writes larger seeds
+# as decimal strings and accepts either form
+MAX_SAFE_INTEGER = 2**53 - 1
+
 
 class AssetInput(BaseModel):
     """Input for asset validation/compilation."""
@@ -23,9 +27,25 @@ class CompileRequest(BaseModel):
     template_id: str = Field(..., min_length=1, max_length=64)
     asset_input: AssetInput
     source_data: Optional[str] = None  # Base64
-    seed: Optional[int] = Field(None, ge=0)
+    seed: Optional[int] = Field(None, ge=0, le=2**64 - 1)
     prompt: Optional[str] = Field(None, max_length=2000)
 
+    @field_validator('seed', mode='before')
+    @classmethod
+    def parse_seed(cls, v):
+        if isinstance(v, str):
+            if not v.isdigit() or not v.isascii():
+                raise ValueError('seed must be a decimal string')
+            return int(v)
+        return v
+
+    @field_serializer('seed')
+    def serialize_seed(self, v: Optional[int]):
+        # Same wire form as the engine, so the job hash survives a round trip
+        if v is not None and v > MAX_SAFE_INTEGER:
+            return str(v)
+        return v
+
     @field_validator('template_id')
     @classmethod
     def validate_template_id(cls, v: str) -> str:
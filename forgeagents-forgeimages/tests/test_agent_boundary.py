@@ -211,6 +211,33 @@ class TestCompileRequestValidation:
                 asset_input=AssetInput(width=1024, height=1024),
             )
 
+    def test_large_seed_round_trips_as_string(self):
+        """Seeds above 2^53-1 keep the engine's string form, so job hashes match."""
+        wire = '{"template_id":"pwa-icon","asset_input":{"width":1024,"height":1024},"seed":"18446744073709551615"}'
+        request = CompileRequest.model_validate_json(wire)
+        assert request.seed == 2**64 - 1
+        assert json.loads(json.dumps(request.model_dump()))["seed"] == "18446744073709551615"
+
+    def test_small_seed_accepts_either_form(self):
+        """Safe seeds are numbers whichever way they arrive."""
+        for seed in (42, "42"):
+            request = CompileRequest(
+                template_id="pwa-icon",
+                asset_input=AssetInput(width=1024, height=1024),
+                seed=seed,
+            )
+            assert request.model_dump()["seed"] == 42
+
+    def test_malformed_seed_rejected(self):
+        """Seeds must be non-negative decimal integers."""
+        for seed in ("-1", "0x10", "1.5", 2**64):
+            with pytest.raises(ValueError):
+                CompileRequest(
+                    template_id="pwa-icon",
+                    asset_input=AssetInput(width=1024, height=1024),
+                    seed=seed,
+                )
+
 
 class TestSkillInterface:
     """Test the skill interface."""
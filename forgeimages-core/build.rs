@@ -0,0 +1,57 @@
+//! Records the git commit being built as `FORGEIMAGES_GIT_COMMIT`, for
+//! `forgeimages-cli info`. Builds outside a git checkout, or without git,
+//! leave it unset.
+//!
+//! With the `grpc` feature it also generates the `forgeimages.v1`
+//! service's tonic server and client, over the messages in src/grpc.rs.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    output.status.success().then(|| text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// proto/forgeimages/v1/forgeimages.proto's service, method for method
+#[cfg(feature = "grpc")]
+fn generate_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("super::{}", input))
+            .output_type(format!("super::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+    };
+    let service = Service::builder()
+        .name("ForgeImages")
+        .package("forgeimages.v1")
+        .method(method("list_templates", "ListTemplates", "ListTemplatesRequest", "ListTemplatesResponse").build())
+        .method(method("validate_asset", "ValidateAsset", "ValidateAssetRequest", "ValidationResult").build())
+        .method(method("compile_asset", "CompileAsset", "CompileRequest", "CompiledAsset").build())
+        .method(method("compile_batch", "CompileBatch", "CompileBatchRequest", "CompileBatchItem").server_streaming().build())
+        .build();
+    Builder::new().compile(&[service]);
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    generate_service();
+    let Some(commit) = git(&["rev-parse", "HEAD"]) else { return };
+    println!("cargo:rustc-env=FORGEIMAGES_GIT_COMMIT={}", commit);
+
+    // Rebuild when HEAD moves: a checkout, or a commit on the current branch
+    let mut watched = vec!["HEAD".to_string(), "packed-refs".to_string()];
+    watched.extend(git(&["symbolic-ref", "-q", "HEAD"]));
+    for name in watched {
+        // A missing file would rerun this on every build
+        if let Some(path) = git(&["rev-parse", "--git-path", &name]).map(PathBuf::from).filter(|path| path.exists()) {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
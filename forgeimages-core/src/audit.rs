@@ -0,0 +1,164 @@
+//! Audit Log - Append-Only Compile Record
+//!
+//! Every compile attempt, successful or not, becomes one canonical-JSON line.
+//! Each line carries the sha256 of the previous line, so editing, removing or
+//! reordering entries breaks the chain and `verify_chain` reports where.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::hashing::{canonical_json, digests_equal, sha256_hex, HashingError};
+
+/// `prev_hash` of the first record in a log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Audit I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Audit serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Audit hashing error: {0}")]
+    Hashing(#[from] HashingError),
+
+    #[error("Audit chain broken at line {0}")]
+    ChainBroken(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    ValidationFailed,
+    Error,
+}
+
+/// One compile attempt, as handed to an `AuditSink`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: String,
+    pub template_id: String,
+    /// Unknown when the template could not be resolved
+    pub template_version: Option<String>,
+    pub engine_version: String,
+    pub job_hash: Option<String>,
+    pub outcome: AuditOutcome,
+    /// Violation rule codes only; messages stay out of the log
+    pub violations: Vec<String>,
+    pub manifest_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A logged line: the event plus the hash of the line before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+}
+
+/// Destination for audit events
+pub trait AuditSink: Send + Sync {
+    fn append(&self, event: &AuditEvent) -> Result<(), AuditError>;
+}
+
+struct ChainState {
+    file: File,
+    prev_hash: String,
+}
+
+/// JSONL audit file; each record is written and flushed as one line
+pub struct FileAuditSink {
+    path: PathBuf,
+    fsync: bool,
+    state: Mutex<ChainState>,
+}
+
+impl FileAuditSink {
+    /// Open (or create) a log, continuing the chain from its last line
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, AuditError> {
+        let path = path.into();
+        let prev_hash = match File::open(&path) {
+            Ok(file) => last_line_hash(file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => GENESIS_HASH.to_string(),
+            Err(e) => return Err(e.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            fsync: false,
+            state: Mutex::new(ChainState { file, prev_hash }),
+        })
+    }
+
+    /// fsync after every record (durable, slower)
+    pub fn with_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn append(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let record = AuditRecord {
+            event: event.clone(),
+            prev_hash: state.prev_hash.clone(),
+        };
+        let line = canonical_json(&record)?;
+
+        let mut buffer = line.clone().into_bytes();
+        buffer.push(b'\n');
+        state.file.write_all(&buffer)?;
+        state.file.flush()?;
+        if self.fsync {
+            state.file.sync_data()?;
+        }
+
+        state.prev_hash = sha256_hex(line.as_bytes());
+        Ok(())
+    }
+}
+
+fn last_line_hash(file: File) -> Result<String, AuditError> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.is_empty() {
+            prev_hash = sha256_hex(line.as_bytes());
+        }
+    }
+    Ok(prev_hash)
+}
+
+/// Check every record links to its predecessor; returns the record count
+pub fn verify_chain(path: &Path) -> Result<usize, AuditError> {
+    let file = File::open(path)?;
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut count = 0;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record: AuditRecord = serde_json::from_str(&line)
+            .map_err(|_| AuditError::ChainBroken(index + 1))?;
+        if !digests_equal(&record.prev_hash, &prev_hash) || canonical_json(&record)? != line {
+            return Err(AuditError::ChainBroken(index + 1));
+        }
+        prev_hash = sha256_hex(line.as_bytes());
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,235 @@
+//! Batch Compilation - Checkpointed Runs
+//!
+//! Each item compiles into `<output_root>/<job_hash>/`. With a checkpoint
+//! file, every finished item appends one canonical-JSON line. A restarted
+//! batch skips items whose latest line says compiled and whose directory
+//! still verifies (manifest plus every export hash). A line that does not
+//! parse is ignored, so a torn write costs one item, not the run.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{canonical_json, digests_equal};
+use crate::pipeline::{CompilationPipeline, CompileRequest, PipelineError};
+use crate::validation::ValidationResult;
+use crate::verify::verify_exports;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Compiled,
+    Failed,
+    /// Compiled by an earlier run; output re-verified, not recompiled
+    Skipped,
+}
+
+/// One checkpoint line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointEntry {
+    pub job_hash: String,
+    pub outcome: BatchOutcome,
+    pub manifest_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Append-only record of finished batch items
+pub struct BatchCheckpoint {
+    path: PathBuf,
+    file: File,
+    /// Latest readable entry per job hash
+    latest: HashMap<String, CheckpointEntry>,
+}
+
+impl BatchCheckpoint {
+    /// Open (or create) a checkpoint, loading the entries already in it
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        let mut latest = HashMap::new();
+        for line in content.split(|b| *b == b'\n') {
+            if let Ok(entry) = serde_json::from_slice::<CheckpointEntry>(line) {
+                latest.insert(entry.job_hash.clone(), entry);
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        // Terminate a torn last line so it cannot swallow the next entry
+        if content.last().is_some_and(|b| *b != b'\n') {
+            file.write_all(b"\n")?;
+        }
+        Ok(Self { path, file, latest })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Latest readable entry for a job hash
+    pub fn entry(&self, job_hash: &str) -> Option<&CheckpointEntry> {
+        self.latest.get(job_hash)
+    }
+
+    /// Manifest hash of a compiled item whose output in `dir` still verifies
+    fn verified(&self, job_hash: &str, dir: &Path) -> Option<String> {
+        let entry = self.entry(job_hash).filter(|e| e.outcome == BatchOutcome::Compiled)?;
+        let manifest_hash = verify_exports(dir).ok()?;
+        entry.manifest_hash.as_deref().is_some_and(|h| digests_equal(h, &manifest_hash)).then_some(manifest_hash)
+    }
+
+    /// Append one entry and flush it
+    pub fn record(&mut self, entry: CheckpointEntry) -> io::Result<()> {
+        let line = canonical_json(&entry).map_err(io::Error::other)?;
+        self.file.write_all(format!("{}\n", line).as_bytes())?;
+        self.file.flush()?;
+        self.latest.insert(entry.job_hash.clone(), entry);
+        Ok(())
+    }
+}
+
+/// Result for one request, in request order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchItem {
+    /// Unknown when the request could not be identified (e.g. unknown template)
+    pub job_hash: Option<String>,
+    pub outcome: BatchOutcome,
+    pub manifest_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchResult {
+    pub items: Vec<BatchItem>,
+}
+
+impl BatchResult {
+    pub fn count(&self, outcome: BatchOutcome) -> usize {
+        self.items.iter().filter(|i| i.outcome == outcome).count()
+    }
+}
+
+/// Progress `compile_batch_with` reports as it goes; `index` is the
+/// request's position
+#[derive(Debug)]
+pub enum BatchEvent<'a> {
+    /// `job_hash` is `None` when the request could not be identified
+    Started { index: usize, job_hash: Option<&'a str> },
+    /// The compiled asset's validation, or for a blocked compile the
+    /// violations that blocked it; skipped items have none
+    Validated { index: usize, validation: &'a ValidationResult },
+    Finished { index: usize, item: &'a BatchItem },
+}
+
+impl CompilationPipeline {
+    /// Compile every request into `<output_root>/<job_hash>/`.
+    ///
+    /// Item failures are recorded and the batch continues; only checkpoint
+    /// I/O errors and cancellation abort it. A cancelled item is neither
+    /// written nor checkpointed, so a rerun starts over at it.
+    pub fn compile_batch(
+        &self,
+        requests: &[CompileRequest],
+        output_root: &Path,
+        checkpoint: Option<&Path>,
+    ) -> Result<BatchResult, PipelineError> {
+        self.compile_batch_with(requests, output_root, checkpoint, |_| {})
+    }
+
+    /// `compile_batch`, calling `on_event` as each item starts, validates
+    /// and finishes
+    pub fn compile_batch_with(
+        &self,
+        requests: &[CompileRequest],
+        output_root: &Path,
+        checkpoint: Option<&Path>,
+        mut on_event: impl FnMut(BatchEvent<'_>),
+    ) -> Result<BatchResult, PipelineError> {
+        let checkpoint_error = |path: &Path, e: io::Error| {
+            PipelineError::OutputFailed(format!("checkpoint {}: {}", path.display(), e))
+        };
+        let mut checkpoint = checkpoint
+            .map(|path| BatchCheckpoint::open(path).map_err(|e| checkpoint_error(path, e)))
+            .transpose()?;
+        let mut result = BatchResult::default();
+
+        for (index, request) in requests.iter().enumerate() {
+            // Skipped items never reach the compile's own checkpoints
+            self.check_cancelled(None)?;
+            let job_hash = match self.job_hash(request) {
+                Ok(job_hash) => job_hash,
+                Err(e) => {
+                    on_event(BatchEvent::Started { index, job_hash: None });
+                    finish(&mut result, &mut on_event, index, BatchItem {
+                        job_hash: None,
+                        outcome: BatchOutcome::Failed,
+                        manifest_hash: None,
+                        error: Some(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+            on_event(BatchEvent::Started { index, job_hash: Some(&job_hash) });
+            let dir = output_root.join(&job_hash);
+
+            if let Some(manifest_hash) = checkpoint.as_ref().and_then(|c| c.verified(&job_hash, &dir)) {
+                tracing::info!(index, %job_hash, "checkpoint hit: output still verifies, skipped");
+                finish(&mut result, &mut on_event, index, BatchItem {
+                    job_hash: Some(job_hash),
+                    outcome: BatchOutcome::Skipped,
+                    manifest_hash: Some(manifest_hash),
+                    error: None,
+                });
+                continue;
+            }
+
+            let entry = match self.compile_to_dir(request, &dir) {
+                Ok(asset) => {
+                    on_event(BatchEvent::Validated { index, validation: &asset.validation });
+                    CheckpointEntry {
+                        job_hash,
+                        outcome: BatchOutcome::Compiled,
+                        manifest_hash: Some(asset.manifest_hash),
+                        error: None,
+                    }
+                }
+                Err(e @ PipelineError::Cancelled(..)) => return Err(e),
+                Err(e) => {
+                    if let PipelineError::ValidationFailed(_) = e {
+                        if let Ok(validation) = self.validate_asset(&request.template_id, &request.asset_input) {
+                            on_event(BatchEvent::Validated { index, validation: &validation });
+                        }
+                    }
+                    CheckpointEntry {
+                        job_hash,
+                        outcome: BatchOutcome::Failed,
+                        manifest_hash: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            if let Some(checkpoint) = checkpoint.as_mut() {
+                checkpoint.record(entry.clone()).map_err(|e| checkpoint_error(checkpoint.path(), e))?;
+            }
+            finish(&mut result, &mut on_event, index, BatchItem {
+                job_hash: Some(entry.job_hash),
+                outcome: entry.outcome,
+                manifest_hash: entry.manifest_hash,
+                error: entry.error,
+            });
+        }
+        Ok(result)
+    }
+}
+
+/// Report an item finished, then keep it
+fn finish(result: &mut BatchResult, on_event: &mut impl FnMut(BatchEvent<'_>), index: usize, item: BatchItem) {
+    on_event(BatchEvent::Finished { index, item: &item });
+    result.items.push(item);
+}
@@ -1,17 +1,103 @@
 //! ForgeImages CLI - Bridge interface for Python
 //!
-//! Commands: templates, validate, compile
-//! Outputs JSON to stdout
+//! Commands: templates, template, validate, compile, batch, verify,
+//! reproduce, extract, diff, hash, print, watch, hash-vectors, config, info,
+//! daemon, completions, with the `signing` feature sign and
+//! verify-signature, with the `server` feature serve, with the `grpc`
+//! feature serve-grpc, and with the `schema` feature schema
+//! Outputs JSON to stdout; `compile --output-dir` writes files and prints
+//! a summary instead, and `compile --dry-run` the plan, writing nothing.
+//! `validate --human` and `compile --human` print a report for terminals,
+//! colored unless `NO_COLOR` is set
+//!
+//! Logs go to stderr, never stdout: warnings by default, `-v` progress
+//! (templates loaded, exports rendered, cache hits), `-vv` validation rule
+//! timings and each template file, `-q` nothing; `--log-format json`
+//! writes one JSON object per line. Built with the `otel` feature, the
+//! pipeline's spans (`pipeline.compile`, `pipeline.validate`,
+//! `pipeline.render_export`, `pipeline.hash_manifest`) also go to an OTLP
+//! collector when `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+//!
+//! Flags fall back to environment variables (`FORGEIMAGES_TEMPLATES_DIR`,
+//! `FORGEIMAGES_OUTPUT_DIR`, `FORGEIMAGES_PROFILE`), then to
+//! `~/.config/forgeimages/config.toml` (or `--config`); `config show`
+//! prints the result
+//!
+//! `--pipeline-config <file>` (JSON or TOML, see
+//! `forgeimages_core::config`) sets up the pipeline every compiling
+//! command uses, `serve` and `serve-grpc` included; `--renderer` and
+//! `--timestamp` still apply on top. An invalid one is an `invalid_config`
+//! error before anything runs
+//!
+//! `--templates-dir` may be repeated: later directories overlay earlier
+//! ones, replacing a template only with a greater version, or the same
+//! version and content (`--allow-downgrade` lifts the first rule)
 //! Returns non-zero on validation failure
+//!
+//! `--renderer placeholder --timestamp <RFC 3339>` makes output repeatable
+//! byte for byte: the clock is fixed and manifest ids derive from it and
+//! the job hash (tests/golden/cli holds outputs produced this way)
+//!
+//! `--payload-format msgpack` reads `--payload`, `--requests` and
+//! `--request` as MessagePack, from stdin (`-`) or a file (`@path`); it
+//! needs the `msgpack` feature. They decode to the same values, and job
+//! hashes, as the JSON they stand for
+//!
+//! Exit codes, from `forgeimages_core::exit_code`: 0 success, 2 validation
+//! failure, 3 template not found, 4 template/engine version mismatch,
+//! 5 invalid payload (or unrecognized `--file`), 6 IO and output errors,
+//! 7 verification failure, 8 cancelled (`--timeout` or Ctrl-C), 9 valid
+//! but with warnings under `--fail-on-warning` (validate, compile, batch),
+//! 10 manifests differ (`diff`), 1 everything else, usage errors included;
+//! `template lint` and `template validate` exit 2 on any issue,
+//! `diff` exits 10 when the manifests differ, `batch` 1 when any item
+//! failed, `reproduce` 7 unless the recompile matches byte for byte,
+//! `extract` 7 when it refused an export whose bytes fail their hash, and
+//! `verify-signature` 7 when the signature does not check out
+//!
+//! `batch` and `watch` take `--output-format ndjson`: one `Event` per line,
+//! tagged by `type` (`item_started`, `validation`, `item_finished`,
+//! `template_error`, `summary`, `cancelled`), flushed as it happens
+//!
+//! `compile` and `batch` take `--timeout <seconds>`, and on Ctrl-C stop
+//! the same way: at the next export boundary, removing the partial output,
+//! with a last JSON line (an envelope, or for ndjson a `cancelled` event)
+//!
+//! Errors print one `ErrorEnvelope` to stdout and a line of context to
+//! stderr. Its `error_kind` is one of the library's `ErrorKind` names,
+//! shared with the server and the bindings, and the exit code is the
+//! kind's unless a command documents otherwise
 
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, SecondsFormat, Utc};
 
 use forgeimages_core::{
-    CompilationPipeline, CompileRequest,
+    AssetClass, BatchEvent, BatchOutcome, CancelReason, CancelToken, CompilationPipeline, PipelineBuilder, exit_code, CompiledAsset, ErrorEnvelope, ErrorKind, CompileRequest, PipelineError, Template, ValidationResult, ValidationViolation, ViolationSeverity,
+    canonical_json, parse_strict, ConfigError, PipelineConfig, HashAlgorithm, HashScheme, Renderer, ENGINE_VERSION, MIN_TEMPLATE_VERSION,
+    render::{Encoder, PlaceholderRenderer},
+    diff::{diff_manifests, ExportDiff, ManifestDiff},
+    reproduce::ReproduceError,
+    hashing::test_vectors,
+    output::{is_plain_file_name, MANIFEST_FILE},
+    sniff::sniff,
+    verify,
     validation::AssetInput,
-    templates::TemplateRegistry,
+    pipeline::PrintPlan,
+    print::{ColorSpace, PrintAuthority, PrintField, PrintSpec, ProfileRegistry, PROFILE_SUFFIX},
+    units::Length,
+    templates::{ExportFormat, LintIssue, RejectedTemplate, TemplateLoadError, TemplateRegistry},
 };
 
 #[derive(Parser)]
@@ -21,15 +107,746 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Path to templates directory
-    #[arg(short, long, default_value = "templates")]
-    templates_dir: PathBuf,
+    /// Path to templates directory; repeat to overlay directories in order
+    /// (default: `templates`, see `config show`)
+    #[arg(short, long)]
+    templates_dir: Vec<PathBuf>,
+
+    /// Config file to read instead of ~/.config/forgeimages/config.toml
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Let a later --templates-dir replace a template with a lower version
+    #[arg(long, global = true)]
+    allow_downgrade: bool,
+
+    /// Log to stderr: -v progress and cache hits, -vv rule timings, -vvv everything
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// No logs, not even warnings; `verify` prints no report either
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Pipeline settings (renderer, limits, encoding, keys) from a .json
+    /// or .toml file
+    #[arg(long, global = true)]
+    pipeline_config: Option<PathBuf>,
+
+    /// Backend that renders exports [default: --pipeline-config's, else placeholder]
+    #[arg(long, value_enum, global = true)]
+    renderer: Option<RendererChoice>,
+
+    /// Fixed clock (RFC 3339) for manifests, audit events and watch lines;
+    /// manifest ids derive from it, so reruns write identical bytes
+    #[arg(long, global = true, value_parser = parse_timestamp)]
+    timestamp: Option<DateTime<Utc>>,
+
+    /// Encoding of --payload, --requests and --request
+    #[arg(long, value_enum, global = true, default_value = "json")]
+    payload_format: PayloadFormat,
+}
+
+impl Cli {
+    /// `--pipeline-config`, validated, or the default config; `Err` is the
+    /// error envelope already printed
+    fn pipeline_config(&self) -> Result<PipelineConfig, ExitCode> {
+        let Some(path) = &self.pipeline_config else { return Ok(PipelineConfig::default()) };
+        PipelineConfig::load(path)
+            .and_then(|config| config.validate().map(|()| config))
+            .map_err(|e| pipeline_config_error(path, &e).emit())
+    }
+
+    /// A pipeline builder from `config`, with the renderer and clock the flags ask for
+    fn pipeline_builder(&self, config: &PipelineConfig, registry: TemplateRegistry) -> Result<PipelineBuilder, ExitCode> {
+        let builder = config.builder(registry).map_err(|e| {
+            let path = self.pipeline_config.as_deref().unwrap_or(Path::new("<default>"));
+            pipeline_config_error(path, &e).emit()
+        })?;
+        Ok(configure_pipeline(builder, self.renderer, self.timestamp))
+    }
+
+    /// The layered registry, or the error envelope already printed
+    fn load_templates(&self) -> Result<TemplateRegistry, ExitCode> {
+        TemplateRegistry::load_layered(&self.templates_dir, self.allow_downgrade).map_err(|e| {
+            let rejected = e.get_ref().and_then(|inner| inner.downcast_ref::<RejectedTemplate>());
+            // A template that would write outside the output directory fails
+            // validation like any unsafe request; other invalid templates are
+            // not an IO problem either
+            let (kind, code) = match rejected {
+                Some(RejectedTemplate { error: TemplateLoadError::UnsafeExportId(_), .. }) => {
+                    (ErrorKind::ValidationFailed, exit_code::VALIDATION_FAILED)
+                }
+                _ if e.kind() == std::io::ErrorKind::InvalidData => (ErrorKind::TemplatesUnavailable, exit_code::OTHER),
+                _ => (ErrorKind::TemplatesUnavailable, exit_code::IO_ERROR),
+            };
+            ErrorEnvelope::new(kind, format!("Failed to load templates: {}", e))
+                .detail("templates_dir", &self.templates_dir)
+                .with_exit_code(code).emit()
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum TemplatesAction {
+    /// Print one template as the engine enforces it (profiles and presets
+    /// applied), with the directory it was loaded from as `$source`
+    Show {
+        /// Template ID
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    /// Load every template in --templates-dir strictly and lint it
+    Lint {
+        /// Directory to lint; defaults to each top-level --templates-dir
+        #[arg(long)]
+        templates_dir: Option<PathBuf>,
+
+        /// Human-readable lines instead of JSON
+        #[arg(long)]
+        human: bool,
+    },
+
+    /// Load and lint one template file
+    Validate {
+        file: PathBuf,
+
+        /// Human-readable lines instead of JSON
+        #[arg(long)]
+        human: bool,
+    },
+
+    /// Scaffold a template file, or clone one from --templates-dir
+    New(NewTemplateArgs),
+}
+
+#[derive(Args)]
+struct NewTemplateArgs {
+    /// Id of the new template
+    #[arg(long)]
+    id: String,
+
+    /// Asset class (icon, cover, banner, logo); picks the default exports
+    #[arg(long, value_parser = parse_asset_class, required_unless_present = "from")]
+    class: Option<AssetClass>,
+
+    /// Aspect ratio as W:H; defaults to the canonical size in lowest terms
+    #[arg(long, value_parser = parse_ratio)]
+    aspect: Option<[u32; 2]>,
+
+    /// Canonical size as WIDTHxHEIGHT
+    #[arg(long, value_parser = parse_size, required_unless_present = "from")]
+    canonical: Option<[u32; 2]>,
+
+    /// Copy this template instead, under the new id at version 1.0.0
+    #[arg(long, conflicts_with_all = ["class", "aspect", "canonical"])]
+    from: Option<String>,
+
+    /// File to write, then lint; the template prints to stdout without one
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Overwrite --out if it exists
+    #[arg(long, requires = "out")]
+    force: bool,
+}
+
+fn parse_asset_class(arg: &str) -> Result<AssetClass, String> {
+    serde_json::from_value(serde_json::Value::from(arg))
+        .map_err(|_| format!("expected icon, cover, banner or logo, got {:?}", arg))
+}
+
+/// `W:H`
+fn parse_ratio(arg: &str) -> Result<[u32; 2], String> {
+    parse_pair(arg, ':').ok_or_else(|| format!("expected W:H, got {:?}", arg))
+}
+
+/// `WIDTHxHEIGHT`
+fn parse_size(arg: &str) -> Result<[u32; 2], String> {
+    parse_pair(arg, 'x').ok_or_else(|| format!("expected WIDTHxHEIGHT, got {:?}", arg))
+}
+
+fn parse_pair(arg: &str, separator: char) -> Option<[u32; 2]> {
+    let (a, b) = arg.split_once(separator)?;
+    let pair = [a.parse().ok()?, b.parse().ok()?];
+    (!pair.contains(&0)).then_some(pair)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListFormat {
+    Json,
+    Table,
+    Ids,
+}
+
+#[derive(Subcommand)]
+enum PrintAction {
+    /// Preview the print settings a compile would use, without compiling
+    Resolve(PrintResolveArgs),
+}
+
+/// User print overrides, each field optional; unset fields repeat the
+/// template's block, as if the user had copied them
+#[derive(Args)]
+struct PrintResolveArgs {
+    /// Template ID
+    #[arg(short, long)]
+    template: String,
+
+    #[arg(long)]
+    user_dpi: Option<u32>,
+
+    /// RGB, CMYK or GRAYSCALE
+    #[arg(long, value_parser = parse_color_space)]
+    user_colorspace: Option<ColorSpace>,
+
+    /// A length: `0.125in`, `3mm`, `9pt`
+    #[arg(long, value_parser = parse_length)]
+    user_bleed: Option<Length>,
+
+    /// Output profile name
+    #[arg(long)]
+    user_icc_profile: Option<String>,
+
+    #[arg(long, value_enum, default_value = "json")]
+    format: DiffFormat,
+}
+
+impl PrintResolveArgs {
+    /// The user spec the flags describe; `None` without any
+    fn user_spec(&self, template: &Template) -> Option<PrintSpec> {
+        if self.user_dpi.is_none() && self.user_colorspace.is_none() && self.user_bleed.is_none() && self.user_icc_profile.is_none() {
+            return None;
+        }
+        let base = template.print.as_ref().map_or_else(PrintSpec::default, |block| block.spec());
+        Some(PrintSpec {
+            authority: PrintAuthority::User,
+            dpi: self.user_dpi.unwrap_or(base.dpi),
+            color_space: self.user_colorspace.clone().unwrap_or(base.color_space),
+            bleed: self.user_bleed.unwrap_or(base.bleed),
+            icc_profile: self.user_icc_profile.clone(),
+        })
+    }
+}
+
+fn parse_color_space(arg: &str) -> Result<ColorSpace, String> {
+    serde_json::from_value(serde_json::Value::from(arg.to_uppercase()))
+        .map_err(|_| format!("expected RGB, CMYK or GRAYSCALE, got {:?}", arg))
+}
+
+fn parse_length(arg: &str) -> Result<Length, String> {
+    Length::parse(arg).map_err(|e| e.to_string())
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print each effective setting and where it came from
+    Show,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("input").required(true).args(["canonical", "job", "file"])))]
+struct HashArgs {
+    /// JSON file to print in canonical form with its digest; `-` reads stdin
+    #[arg(long, value_name = "FILE")]
+    canonical: Option<PathBuf>,
+
+    /// The job hash compile would record for --template and --payload
+    #[arg(long, requires_all = ["template", "payload"])]
+    job: bool,
+
+    /// Template ID, as compile's --template
+    #[arg(short, long, requires = "job")]
+    template: Option<String>,
+
+    /// JSON payload (CompileRequest); `-` reads stdin, `@path` reads a file
+    #[arg(short, long, requires = "job")]
+    payload: Option<String>,
+
+    /// File whose raw bytes to digest
+    #[arg(long)]
+    file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PayloadFormat {
+    Json,
+    /// Binary; from stdin (`-`) or a file (`@path`), never inline
+    Msgpack,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RendererChoice {
+    /// Minimal valid files per format (the only backend built in)
+    Placeholder,
+}
+
+impl RendererChoice {
+    fn name(self) -> &'static str {
+        match self {
+            RendererChoice::Placeholder => PlaceholderRenderer.name(),
+        }
+    }
+}
+
+/// `--pipeline-config` could not be read or would build a broken pipeline
+fn pipeline_config_error(path: &Path, error: &ConfigError) -> ErrorEnvelope {
+    let envelope = ErrorEnvelope::new(ErrorKind::InvalidConfig, format!("{}: {}", path.display(), error)).detail("file", path);
+    match error {
+        ConfigError::Invalid { field, .. } => envelope.detail("field", field),
+        _ => envelope,
+    }
+}
+
+fn configure_pipeline(builder: PipelineBuilder, renderer: Option<RendererChoice>, clock: Option<DateTime<Utc>>) -> PipelineBuilder {
+    let builder = match renderer {
+        Some(RendererChoice::Placeholder) => builder.renderer(PlaceholderRenderer),
+        None => builder,
+    };
+    match clock {
+        Some(now) => builder.clock(now),
+        None => builder,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WatchFormat {
+    Text,
+    Ndjson,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BatchFormat {
+    Json,
+    Ndjson,
+}
+
+/// One line of `--output-format ndjson`, written and flushed as it
+/// happens. This schema is a contract: fields may be added, never renamed
+/// or removed. `index` counts batch requests, or watch builds, from 0.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'a> {
+    ItemStarted {
+        index: usize,
+        template_id: &'a str,
+        /// Absent when the request could not be identified
+        #[serde(skip_serializing_if = "Option::is_none")]
+        job_hash: Option<&'a str>,
+    },
+    /// The item's `ValidationResult`; none for skipped or unidentified items
+    Validation {
+        index: usize,
+        #[serde(flatten)]
+        result: &'a ValidationResult,
+    },
+    ItemFinished {
+        index: usize,
+        outcome: BatchOutcome,
+        job_hash: Option<&'a str>,
+        manifest_hash: Option<&'a str>,
+        error: Option<&'a str>,
+    },
+    /// Watch could not load templates; the last good ones stay in use
+    TemplateError { message: &'a str },
+    /// Last line of a batch, or of a watch that reached --max-builds;
+    /// `failed_due_to_warnings` only for a batch run with --fail-on-warning
+    Summary {
+        total: usize,
+        compiled: usize,
+        failed: usize,
+        skipped: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failed_due_to_warnings: Option<bool>,
+    },
+    /// Last line of a batch stopped by --timeout or Ctrl-C; item `index`
+    /// was abandoned, and no item after it started
+    Cancelled {
+        index: usize,
+        reason: CancelReason,
+        /// The export that was rendering
+        export: Option<&'a str>,
+    },
+}
+
+impl Event<'_> {
+    fn emit(&self) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", serde_json::to_string(self).unwrap());
+        let _ = stdout.flush();
+    }
+}
+
+/// `info`: what the bridge may ask this build for. This schema is a
+/// contract: fields may be added, never renamed or removed, and every
+/// field is always present.
+#[derive(Serialize)]
+struct EngineInfo {
+    engine_version: &'static str,
+    min_template_version: &'static str,
+    /// Commit the binary was built from; null outside a git checkout
+    git_commit: Option<&'static str>,
+    hash_scheme: HashScheme,
+    /// Schemes manifests can be verified under, oldest first
+    hash_schemes: Vec<HashScheme>,
+    hash_algorithms: &'static [HashAlgorithm],
+    /// Cargo features compiled in
+    features: BTreeMap<&'static str, bool>,
+    renderer: &'static str,
+    export_formats: Vec<FormatInfo>,
+    /// Templates in --templates-dir, sorted; null when they fail to load
+    template_ids: Option<Vec<String>>,
+    templates_error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FormatInfo {
+    format: ExportFormat,
+    encoder: Encoder,
+}
+
+impl EngineInfo {
+    fn collect(cli: &Cli) -> Self {
+        let (template_ids, templates_error) = match TemplateRegistry::load_layered(&cli.templates_dir, cli.allow_downgrade) {
+            Ok(registry) => {
+                let mut ids: Vec<String> = registry.list().into_iter().map(|template| template.id.clone()).collect();
+                ids.sort();
+                (Some(ids), None)
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Self {
+            engine_version: ENGINE_VERSION,
+            min_template_version: MIN_TEMPLATE_VERSION,
+            git_commit: option_env!("FORGEIMAGES_GIT_COMMIT"),
+            hash_scheme: HashScheme::CURRENT,
+            hash_schemes: HashScheme::ALL.to_vec(),
+            hash_algorithms: HashAlgorithm::ALL,
+            features: BTreeMap::from([
+                ("signing", cfg!(feature = "signing")),
+                ("blake3", cfg!(feature = "blake3")),
+                ("server", cfg!(feature = "server")),
+                ("schema", cfg!(feature = "schema")),
+                ("icc", cfg!(feature = "icc")),
+                ("grpc", cfg!(feature = "grpc")),
+                ("msgpack", cfg!(feature = "msgpack")),
+                ("otel", cfg!(feature = "otel")),
+                ("s3", cfg!(feature = "s3")),
+                ("webhooks", cfg!(feature = "webhooks")),
+                ("test_hooks", cfg!(feature = "test-hooks")),
+            ]),
+            renderer: cli.renderer.unwrap_or(RendererChoice::Placeholder).name(),
+            export_formats: ExportFormat::ALL.into_iter()
+                .map(|format| FormatInfo { encoder: PlaceholderRenderer::encoder(&format), format })
+                .collect(),
+            template_ids,
+            templates_error,
+        }
+    }
+}
+
+/// Types `schema` prints, by their Rust names
+#[cfg(feature = "schema")]
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SchemaType {
+    #[value(name = "CompileRequest")]
+    CompileRequest,
+    #[value(name = "AssetInput")]
+    AssetInput,
+    #[value(name = "Template")]
+    Template,
+    #[value(name = "CompiledAsset")]
+    CompiledAsset,
+}
+
+#[cfg(feature = "schema")]
+impl SchemaType {
+    fn schema(self) -> schemars::Schema {
+        match self {
+            SchemaType::CompileRequest => schemars::schema_for!(CompileRequest),
+            SchemaType::AssetInput => schemars::schema_for!(AssetInput),
+            SchemaType::Template => schemars::schema_for!(Template),
+            SchemaType::CompiledAsset => schemars::schema_for!(CompiledAsset),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DiffFormat {
+    Json,
+    Human,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    #[arg(long, value_enum, default_value = "json")]
+    format: ListFormat,
+
+    /// Only templates of this asset class (icon, cover, banner, logo)
+    #[arg(long)]
+    asset_class: Option<String>,
+
+    /// Only templates carrying this tag; repeat to require several
+    #[arg(long)]
+    tag: Vec<String>,
+
+    /// List deprecated templates too
+    #[arg(long, conflicts_with = "only_deprecated")]
+    include_deprecated: bool,
+
+    /// List deprecated templates only
+    #[arg(long)]
+    only_deprecated: bool,
+
+    /// Full template bodies (json) or extra columns (table); ignored by ids
+    #[arg(long)]
+    detail: bool,
+}
+
+/// Request fields for a `--file` compile, instead of a JSON payload
+#[derive(Args)]
+struct RequestFlags {
+    #[arg(long, conflicts_with = "payload")]
+    seed: Option<u64>,
+
+    #[arg(long, conflicts_with = "payload")]
+    prompt: Option<String>,
+
+    /// Template parameter; repeat for several
+    #[arg(long = "param", value_name = "KEY=VALUE", value_parser = parse_param, conflicts_with = "payload")]
+    params: Vec<(String, String)>,
+
+    /// Print profile in --templates-dir to take the print spec from
+    #[arg(long, conflicts_with = "payload")]
+    profile: Option<String>,
+}
+
+impl RequestFlags {
+    fn any(&self) -> bool {
+        self.seed.is_some() || self.prompt.is_some() || !self.params.is_empty() || self.profile.is_some()
+    }
+
+    /// Set the flagged fields on a request built from a `--file`.
+    ///
+    /// Text slots are the only parameters templates declare, and they take
+    /// strings, so values are never coerced: `--param count=42` passes
+    /// `"42"`, just as a payload would have to.
+    fn apply(&self, request: &mut CompileRequest, templates_dirs: &[PathBuf]) -> Result<(), String> {
+        request.seed = self.seed;
+        request.prompt = self.prompt.clone();
+        for (key, value) in &self.params {
+            if request.params.insert(key.clone(), value.clone().into()).is_some() {
+                return Err(format!("--param {} is given twice", key));
+            }
+        }
+        if let Some(name) = &self.profile {
+            // Layered like templates: a later directory's profile wins
+            let mut profiles = ProfileRegistry::new();
+            for dir in templates_dirs {
+                let layer = ProfileRegistry::load_from_dir(dir).map_err(|e| e.to_string())?;
+                for name in layer.names() {
+                    profiles.register(layer.get(name).cloned().expect("listed by names"));
+                }
+            }
+            let profile = profiles.get(name).ok_or_else(|| {
+                let known: Vec<&str> = profiles.names().collect();
+                format!("unknown print profile {:?}; --templates-dir has: {}", name, known.join(", "))
+            })?;
+            let mut spec = PrintSpec::from_user(profile.dpi, profile.color_space.clone(), profile.bleed)
+                .map_err(|e| format!("print profile {}: {}", name, e))?;
+            spec.icc_profile = profile.icc_profile.clone();
+            request.print_spec = Some(spec);
+        }
+        Ok(())
+    }
+}
+
+/// Where a setting's value came from, highest precedence first
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Origin {
+    Flag,
+    Env,
+    ConfigFile,
+    Default,
+}
+
+#[derive(Serialize)]
+struct Setting<T> {
+    value: T,
+    source: Origin,
+}
+
+impl<T> Setting<T> {
+    /// The first value present in precedence order: flag, environment,
+    /// config file, built-in default
+    fn resolve(flag: Option<T>, env: Option<T>, file: Option<T>, default: T) -> Self {
+        let (value, source) = [(flag, Origin::Flag), (env, Origin::Env), (file, Origin::ConfigFile)]
+            .into_iter()
+            .find_map(|(value, source)| value.map(|value| (value, source)))
+            .unwrap_or((default, Origin::Default));
+        Self { value, source }
+    }
+}
+
+/// `config.toml`; relative paths are relative to the file
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    templates_dir: Option<PathList>,
+    output_dir: Option<PathBuf>,
+    profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "templates_dir must be a path or a list of paths")]
+enum PathList {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+/// Settings with defaults outside the flags, resolved once for every command
+#[derive(Serialize)]
+struct CliConfig {
+    /// Config file read, if there was one
+    config_file: Option<PathBuf>,
+    templates_dir: Setting<Vec<PathBuf>>,
+    /// Default `compile --output-dir`
+    output_dir: Setting<Option<PathBuf>>,
+    /// Default `compile --profile` for requests built from `--file`
+    profile: Setting<Option<String>>,
+}
+
+impl CliConfig {
+    fn resolve(cli: &Cli) -> Result<Self, ErrorEnvelope> {
+        let (config_file, file) = Self::read_file(cli.config.as_deref())?;
+        let env = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+        let (output_dir, profile) = match &cli.command {
+            Commands::Compile { output_dir, flags, .. } => (output_dir.clone(), flags.profile.clone()),
+            _ => (None, None),
+        };
+        Ok(Self {
+            config_file,
+            templates_dir: Setting::resolve(
+                Some(cli.templates_dir.clone()).filter(|dirs| !dirs.is_empty()),
+                env("FORGEIMAGES_TEMPLATES_DIR").map(|dirs| std::env::split_paths(&dirs).collect()),
+                file.templates_dir.map(|dirs| match dirs {
+                    PathList::One(dir) => vec![dir],
+                    PathList::Many(dirs) => dirs,
+                }),
+                vec![PathBuf::from("templates")],
+            ),
+            output_dir: Setting::resolve(
+                output_dir.map(Some),
+                env("FORGEIMAGES_OUTPUT_DIR").map(|dir| Some(dir.into())),
+                file.output_dir.map(Some),
+                None,
+            ),
+            profile: Setting::resolve(
+                profile.map(Some),
+                env("FORGEIMAGES_PROFILE").map(|name| Some(name.to_string_lossy().into_owned())),
+                file.profile.map(Some),
+                None,
+            ),
+        })
+    }
+
+    /// `--config`, else `$XDG_CONFIG_HOME/forgeimages/config.toml` (or
+    /// under `~/.config`) if it exists; only the named file must exist
+    fn read_file(named: Option<&Path>) -> Result<(Option<PathBuf>, ConfigFile), ErrorEnvelope> {
+        let path = match named {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let base = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+                    .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+                match base.map(|base| base.join("forgeimages/config.toml")) {
+                    Some(path) if path.exists() => path,
+                    _ => return Ok((None, ConfigFile::default())),
+                }
+            }
+        };
+        let invalid = |message: String| ErrorEnvelope::new(ErrorKind::InvalidConfig, format!("{}: {}", path.display(), message))
+            .detail("file", &path);
+        let text = fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+        let mut file: ConfigFile = toml::from_str(&text).map_err(|e| {
+            let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+            invalid(format!("line {}: {}", line, e.message()))
+        })?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        match &mut file.templates_dir {
+            Some(PathList::One(one)) => *one = dir.join(&*one),
+            Some(PathList::Many(many)) => many.iter_mut().for_each(|one| *one = dir.join(&*one)),
+            None => {}
+        }
+        if let Some(output_dir) = &mut file.output_dir {
+            *output_dir = dir.join(&*output_dir);
+        }
+        Ok((Some(path), file))
+    }
+
+    /// Fill in what the flags left unset
+    fn apply(&self, cli: &mut Cli) {
+        cli.templates_dir = self.templates_dir.value.clone();
+        if let Commands::Compile { output_dir, file, flags, .. } = &mut cli.command {
+            output_dir.clone_from(&self.output_dir.value);
+            // A payload carries its own print spec
+            if file.is_some() {
+                flags.profile.clone_from(&self.profile.value);
+            }
+        }
+    }
+}
+
+fn parse_param(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+fn parse_timestamp(arg: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(arg)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| format!("expected an RFC 3339 timestamp like 2026-01-01T00:00:00Z: {}", e))
+}
+
+fn parse_timeout(arg: &str) -> Result<Duration, String> {
+    arg.parse::<f64>().ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| format!("expected a positive number of seconds, got {:?}", arg))
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// List available templates
-    Templates,
+    /// List available templates, or show one
+    #[command(args_conflicts_with_subcommands = true)]
+    Templates {
+        #[command(subcommand)]
+        action: Option<TemplatesAction>,
+
+        #[command(flatten)]
+        list: ListArgs,
+    },
+
+    /// Check template files, for template authors and their CI
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
 
     /// Validate an asset
     Validate {
@@ -37,9 +854,22 @@ enum Commands {
         #[arg(short, long)]
         template: String,
 
-        /// JSON payload (AssetInput)
+        /// JSON payload (AssetInput); `-` reads stdin, `@path` reads a file
         #[arg(short, long)]
-        payload: String,
+        payload: Option<String>,
+
+        /// PNG, JPEG or SVG file to take the input from, instead of --payload
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// A report for terminals instead of JSON
+        #[arg(long)]
+        human: bool,
+
+        /// Exit 9 when the result is valid but has warnings; the result
+        /// itself, and any manifest, still record it as valid
+        #[arg(long)]
+        fail_on_warning: bool,
     },
 
     /// Compile an asset
@@ -48,101 +878,2365 @@ enum Commands {
         #[arg(short, long)]
         template: String,
 
-        /// JSON payload (CompileRequest)
+        /// JSON payload (CompileRequest); `-` reads stdin, `@path` reads a file
         #[arg(short, long)]
-        payload: String,
-    },
-}
+        payload: Option<String>,
 
-fn main() -> ExitCode {
-    let cli = Cli::parse();
+        /// PNG, JPEG or SVG file to take the input from, instead of --payload
+        #[arg(short, long)]
+        file: Option<PathBuf>,
 
-    // Load templates
-    let registry = match TemplateRegistry::load_from_dir(&cli.templates_dir) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!(r#"{{"error": "Failed to load templates: {}"}}"#, e);
-            return ExitCode::FAILURE;
-        }
-    };
+        /// Write exports and manifest.json here; stdout gets a summary
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
 
-    let pipeline = CompilationPipeline::new(registry);
+        /// Print the full manifest, exports included, to stdout
+        #[arg(long)]
+        stdout_manifest: bool,
 
-    match cli.command {
-        Commands::Templates => {
-            let templates: Vec<_> = pipeline.list_templates()
-                .iter()
-                .map(|t| serde_json::json!({
-                    "id": t.id,
-                    "name": t.name,
-                    "version": t.template_version,
-                    "asset_class": t.asset_class,
-                    "deprecated": t.deprecated,
-                }))
-                .collect();
+        /// Write into a non-empty output directory
+        #[arg(long)]
+        force: bool,
 
-            println!("{}", serde_json::to_string_pretty(&templates).unwrap());
-            ExitCode::SUCCESS
-        }
+        /// A validation report for terminals instead of JSON
+        #[arg(long, conflicts_with = "stdout_manifest")]
+        human: bool,
 
-        Commands::Validate { template, payload } => {
-            let input: AssetInput = match serde_json::from_str(&payload) {
-                Ok(i) => i,
-                Err(e) => {
-                    println!(r#"{{"valid": false, "error": "Invalid payload: {}"}}"#, e);
-                    return ExitCode::FAILURE;
-                }
-            };
+        /// Validate and print the CompilePlan (job hash, each export's
+        /// filename and size); render and write nothing
+        #[arg(long, conflicts_with_all = ["human", "stdout_manifest"])]
+        dry_run: bool,
 
-            match pipeline.validate_asset(&template, &input) {
-                Ok(result) => {
-                    println!("{}", serde_json::to_string_pretty(&result).unwrap());
-                    if result.valid {
-                        ExitCode::SUCCESS
-                    } else {
-                        ExitCode::from(2)  // Validation failure
-                    }
-                }
-                Err(e) => {
-                    println!(r#"{{"valid": false, "error": "{}"}}"#, e);
-                    ExitCode::FAILURE
-                }
-            }
-        }
+        /// Exit 9 when the result is valid but has warnings; the result
+        /// itself, and any manifest, still record it as valid
+        #[arg(long)]
+        fail_on_warning: bool,
 
-        Commands::Compile { template, payload } => {
-            let request: CompileRequest = match serde_json::from_str(&payload) {
-                Ok(r) => r,
-                Err(e) => {
-                    println!(r#"{{"success": false, "error": "Invalid payload: {}"}}"#, e);
-                    return ExitCode::FAILURE;
-                }
-            };
+        /// Give up after this many seconds, at the next export boundary
+        #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+        timeout: Option<Duration>,
 
-            // Ensure template_id matches
-            let request = CompileRequest {
-                template_id: template,
-                ..request
-            };
+        #[command(flatten)]
+        flags: RequestFlags,
+    },
 
-            match pipeline.compile_asset(&request) {
-                Ok(asset) => {
-                    let output = serde_json::json!({
-                        "success": true,
-                        "asset": asset,
-                    });
-                    println!("{}", serde_json::to_string_pretty(&output).unwrap());
-                    ExitCode::SUCCESS
-                }
-                Err(e) => {
-                    let output = serde_json::json!({
-                        "success": false,
-                        "error": e.to_string(),
-                    });
-                    println!("{}", serde_json::to_string(&output).unwrap());
-                    ExitCode::from(2)  // Compilation failure (validation)
-                }
-            }
-        }
-    }
+    /// Verify a written manifest, or an output directory and its files
+    Verify {
+        /// manifest.json to verify on its own
+        #[arg(short, long, conflicts_with = "dir", required_unless_present = "dir")]
+        manifest: Option<PathBuf>,
+
+        /// Output directory: its manifest.json plus every file beside it
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+
+        /// JSON payload (CompileRequest) to recompute the job hash from;
+        /// `-` reads stdin, `@path` reads a file
+        #[arg(short, long)]
+        request: Option<String>,
+
+        /// Ed25519 public key PEM file to check manifest.sig against
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+    },
+
+    /// Recompile a manifest from the request it records; exits 7 unless
+    /// every hash matches
+    Reproduce {
+        #[arg(short, long)]
+        manifest: PathBuf,
+
+        /// The source the manifest was compiled from
+        #[arg(short, long)]
+        source: Option<PathBuf>,
+
+        #[arg(long, value_enum, default_value = "json")]
+        format: DiffFormat,
+    },
+
+    /// Decode a manifest's embedded exports to files, checking each hash
+    Extract {
+        /// Manifest with `data_base64` exports, bare or as `compile` prints it
+        #[arg(short, long)]
+        manifest: PathBuf,
+
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Export id to extract; repeat for several, omit for all
+        #[arg(short, long = "export")]
+        exports: Vec<String>,
+
+        /// Write exports whose bytes do not match their recorded hash
+        #[arg(long)]
+        no_verify: bool,
+
+        /// Write into a non-empty output directory
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Recompile whenever the source or a template file changes
+    Watch {
+        /// Template ID
+        #[arg(short, long)]
+        template: String,
+
+        /// PNG, JPEG or SVG source file
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Holds the latest successful build; replaced whole on each success
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Replace a non-empty output directory
+        #[arg(long)]
+        force: bool,
+
+        /// Wait until files have been quiet this long before building
+        #[arg(long, default_value_t = 200)]
+        debounce_ms: u64,
+
+        /// How often to check for changes
+        #[arg(long, default_value_t = 250)]
+        poll_ms: u64,
+
+        /// Exit after this many results (template errors included)
+        #[arg(long)]
+        max_builds: Option<u32>,
+
+        /// `text` lines, or `ndjson` events
+        #[arg(long, value_enum, default_value = "text")]
+        output_format: WatchFormat,
+    },
+
+    /// Compile a list of requests, each into <output-dir>/<job_hash>/
+    Batch {
+        /// JSON array of CompileRequests; `-` reads stdin, `@path` reads a file
+        #[arg(short, long)]
+        requests: String,
+
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Record finished items here; a rerun skips items it records as
+        /// compiled whose output still verifies
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+
+        /// One `json` result at the end, or `ndjson` events as items run
+        #[arg(long, value_enum, default_value = "json")]
+        output_format: BatchFormat,
+
+        /// Exit 9 when the result is valid but has warnings; the result
+        /// itself, and any manifest, still record it as valid
+        #[arg(long)]
+        fail_on_warning: bool,
+
+        /// Give up on the whole batch after this many seconds
+        #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+        timeout: Option<Duration>,
+    },
+
+    /// Dump the hash scheme test vectors
+    HashVectors,
+
+    /// What this engine build supports, as JSON (see `EngineInfo`)
+    Info,
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the JSON Schema of a request, template or manifest type
+    #[cfg(feature = "schema")]
+    Schema {
+        #[arg(value_enum)]
+        r#type: SchemaType,
+    },
+
+    /// Inspect the settings flags, environment and config file resolve to
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Hash the way the engine does: canonical JSON, job hashes, raw files
+    Hash(HashArgs),
+
+    /// Print settings, resolved as compile resolves them
+    Print {
+        #[command(subcommand)]
+        action: PrintAction,
+    },
+
+    /// Compare two manifests; exits 10 when they differ
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+
+        #[arg(long, value_enum, default_value = "json")]
+        format: DiffFormat,
+    },
+
+    /// Sign a written manifest: writes manifest.sig beside it, and with
+    /// --embed a copy of the manifest carrying the signature block
+    #[cfg(feature = "signing")]
+    Sign {
+        #[arg(short, long)]
+        manifest: PathBuf,
+
+        /// PKCS#8 PEM Ed25519 private key
+        #[arg(short, long)]
+        key: PathBuf,
+
+        /// Name for the key in the signature block (default: its fingerprint)
+        #[arg(long)]
+        key_id: Option<String>,
+
+        /// Where to write the signature instead of manifest.sig beside the manifest
+        #[arg(long)]
+        sig: Option<PathBuf>,
+
+        /// Also write a copy of the manifest with the signature block embedded
+        #[arg(long)]
+        embed: Option<PathBuf>,
+    },
+
+    /// Check a manifest's signature; exits 7 unless it verifies
+    #[cfg(feature = "signing")]
+    VerifySignature {
+        #[arg(short, long)]
+        manifest: PathBuf,
+
+        /// Detached signature (default: the manifest's embedded block, else
+        /// manifest.sig beside it)
+        #[arg(long)]
+        sig: Option<PathBuf>,
+
+        /// SubjectPublicKeyInfo PEM Ed25519 public key
+        #[arg(long)]
+        public_key: PathBuf,
+    },
+
+    /// Answer newline-delimited JSON-RPC 2.0 on stdin/stdout until stdin closes
+    Daemon {
+        /// Requests handled at once; responses may arrive out of order
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+
+    /// Serve the HTTP API; prints the bound address, then runs until killed
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8085")]
+        addr: String,
+
+        /// Validations and compiles run at once; further requests wait
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Largest request body accepted, in bytes
+        #[arg(long, default_value_t = 64 * 1024 * 1024)]
+        max_body_bytes: usize,
+    },
+
+    /// Serve the gRPC API (`forgeimages.v1.ForgeImages`); prints the bound
+    /// address, then runs until killed
+    #[cfg(feature = "grpc")]
+    ServeGrpc {
+        #[arg(long, default_value = "127.0.0.1:8086")]
+        addr: String,
+
+        /// Validations and compiles run at once, across all calls; further
+        /// ones wait
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Largest message accepted or sent, in bytes
+        #[arg(long, default_value_t = 64 * 1024 * 1024)]
+        max_message_bytes: usize,
+    },
+}
+
+fn main() -> ExitCode {
+    // clap's own usage exit code, 2, would read as a validation failure
+    let mut cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() { ExitCode::from(exit_code::OTHER) } else { ExitCode::SUCCESS };
+        }
+    };
+    let _telemetry = init_logging(cli.verbose, cli.quiet, cli.log_format);
+    let config = match CliConfig::resolve(&cli) {
+        Ok(config) => config,
+        Err(e) => return e.emit(),
+    };
+    // Neither reads settings, so a broken config file can't hide them
+    if let Commands::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "forgeimages-cli", &mut std::io::stdout());
+        return ExitCode::SUCCESS;
+    }
+    #[cfg(feature = "schema")]
+    if let Commands::Schema { r#type } = cli.command {
+        println!("{}", serde_json::to_string_pretty(&r#type.schema()).unwrap());
+        return ExitCode::SUCCESS;
+    }
+    if let Commands::Config { action: ConfigAction::Show } = cli.command {
+        println!("{}", serde_json::to_string_pretty(&config).unwrap());
+        return ExitCode::SUCCESS;
+    }
+    config.apply(&mut cli);
+
+    if let Commands::Info = cli.command {
+        println!("{}", serde_json::to_string_pretty(&EngineInfo::collect(&cli)).unwrap());
+        return ExitCode::SUCCESS;
+    }
+
+    // Needs no templates; don't let a broken templates dir hide the contract
+    if let Commands::HashVectors = cli.command {
+        println!("{}", serde_json::to_string_pretty(&test_vectors()).unwrap());
+        return ExitCode::SUCCESS;
+    }
+    if let Commands::Diff { a, b, format } = &cli.command {
+        return diff(a, b, *format);
+    }
+    #[cfg(feature = "signing")]
+    if let Commands::Sign { manifest, key, key_id, sig, embed } = &cli.command {
+        return sign(manifest, key, key_id.as_deref(), sig.as_deref(), embed.as_deref());
+    }
+    #[cfg(feature = "signing")]
+    if let Commands::VerifySignature { manifest, sig, public_key } = &cli.command {
+        return verify_signature_command(manifest, sig.as_deref(), public_key);
+    }
+    if let Commands::Hash(args @ HashArgs { job: false, .. }) = &cli.command {
+        return hash_input(args);
+    }
+    if let Commands::Extract { manifest, output_dir, exports, no_verify, force } = &cli.command {
+        return extract(manifest, output_dir, exports, !*no_verify, *force);
+    }
+
+    let pipeline_config = match cli.pipeline_config() {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+
+    // Watch reloads templates itself and survives broken ones
+    if let Commands::Watch { template, file, output_dir, force, debounce_ms, poll_ms, max_builds, output_format } = &cli.command {
+        if let Err(e) = OutputDir::claim(output_dir, *force) {
+            return ErrorEnvelope::new(ErrorKind::OutputFailed, e).emit();
+        }
+        let watch = Watch {
+            templates_dirs: &cli.templates_dir,
+            allow_downgrade: cli.allow_downgrade,
+            pipeline_config: &pipeline_config,
+            renderer: cli.renderer,
+            clock: cli.timestamp,
+            template,
+            file,
+            output_dir,
+            debounce: Duration::from_millis(*debounce_ms),
+            poll: Duration::from_millis(*poll_ms),
+            format: *output_format,
+        };
+        watch.run(*max_builds);
+        return ExitCode::SUCCESS;
+    }
+
+    if let Commands::Template { action: TemplateAction::New(args) } = &cli.command {
+        return new_template(&cli, args);
+    }
+
+    // Lint reports broken templates instead of failing to load them
+    if let Commands::Template { action } = &cli.command {
+        let (reports, human) = match action {
+            TemplateAction::Lint { templates_dir, human } => {
+                let dirs = match templates_dir {
+                    Some(dir) => std::slice::from_ref(dir),
+                    None => cli.templates_dir.as_slice(),
+                };
+                (dirs.iter().flat_map(|dir| lint_dir(dir)).collect(), *human)
+            }
+            TemplateAction::Validate { file, human } => {
+                let dir = file.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                let report = match ProfileRegistry::load_from_dir(dir) {
+                    Ok(profiles) => lint_file(file, &profiles),
+                    Err(e) => FileReport::failed(dir, "print_profile", e.to_string()),
+                };
+                (vec![report], *human)
+            }
+            TemplateAction::New(_) => unreachable!("handled above"),
+        };
+        return print_lint(&reports, human);
+    }
+
+    let registry = match cli.load_templates() {
+        Ok(registry) => registry,
+        Err(code) => return code,
+    };
+
+    // Builds its own pipeline, configured like the original compile
+    if let Commands::Reproduce { manifest, source, format } = &cli.command {
+        return reproduce(registry, manifest, source.as_deref(), *format);
+    }
+
+    let mut builder = match cli.pipeline_builder(&pipeline_config, registry) {
+        Ok(builder) => builder,
+        Err(code) => return code,
+    };
+    if let Commands::Compile { timeout, .. } | Commands::Batch { timeout, .. } = &cli.command {
+        builder = builder.cancel_token(cancel_token(*timeout));
+    }
+    // Lets tests interrupt a compile part way through
+    #[cfg(feature = "test-hooks")]
+    if let Some(delay) = std::env::var("FORGEIMAGES_RENDER_DELAY_MS").ok().and_then(|ms| ms.parse().ok()) {
+        builder = builder.renderer(forgeimages_core::render::SlowRenderer { delay: Duration::from_millis(delay) });
+    }
+    let pipeline = builder.build();
+
+    let quiet = cli.quiet;
+    let format = cli.payload_format;
+    match cli.command {
+        Commands::Templates { action: Some(TemplatesAction::Show { id }), .. } => {
+            match pipeline.get_template(&id) {
+                Some(template) => {
+                    let mut shown = serde_json::to_value(&template).unwrap();
+                    // Which --templates-dir won, for layered directories
+                    shown["$source"] = serde_json::to_value(pipeline.registry().source_dir(&id)).unwrap();
+                    println!("{}", serde_json::to_string_pretty(&shown).unwrap());
+                    ExitCode::SUCCESS
+                }
+                None => ErrorEnvelope::from(&PipelineError::TemplateNotFound(id)).emit(),
+            }
+        }
+
+        Commands::Templates { action: None, list } => list_templates(&pipeline, &list),
+
+        Commands::Daemon { concurrency } => daemon(&pipeline, concurrency),
+
+        Commands::Print { action: PrintAction::Resolve(args) } => {
+            let user = pipeline.get_template(&args.template).and_then(|template| args.user_spec(&template));
+            match pipeline.print_plan(&args.template, user.as_ref()) {
+                Ok(plan) => {
+                    match args.format {
+                        DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&plan).unwrap()),
+                        DiffFormat::Human => print!("{}", print_plan_report(&plan, Style::detect())),
+                    }
+                    if plan.validation.valid { ExitCode::SUCCESS } else { ExitCode::from(exit_code::VALIDATION_FAILED) }
+                }
+                Err(e) => ErrorEnvelope::from(&e).emit(),
+            }
+        }
+
+        Commands::Batch { requests, output_dir, checkpoint, output_format, fail_on_warning, .. } => {
+            let requests: Vec<CompileRequest> = match parse_payload(&requests, format) {
+                Ok(requests) => requests,
+                Err(e) => {
+                    return ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid requests: {}", e)).emit();
+                }
+            };
+            batch(&pipeline, &requests, &output_dir, checkpoint.as_deref(), output_format, fail_on_warning)
+        }
+
+        Commands::Hash(HashArgs { template, payload, .. }) => {
+            let (Some(template), Some(payload)) = (template, payload) else {
+                unreachable!("clap requires --template and --payload with --job")
+            };
+            let request: CompileRequest = match parse_payload(&payload, format) {
+                Ok(request) => request,
+                Err(e) => {
+                    return ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)).emit();
+                }
+            };
+            // As compile does: --template wins over the payload's template_id
+            let request = CompileRequest { template_id: template, ..request };
+            match pipeline.job_hash(&request) {
+                Ok(job_hash) => {
+                    let hash = serde_json::json!({"template_id": request.template_id, "job_hash": job_hash});
+                    println!("{}", serde_json::to_string_pretty(&hash).unwrap());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => ErrorEnvelope::from(&e).emit(),
+            }
+        }
+
+        #[cfg(feature = "server")]
+        Commands::Serve { addr, concurrency, max_body_bytes } => {
+            let options = forgeimages_core::server::ServerOptions { concurrency, max_body_bytes };
+            serve(&addr, pipeline, options)
+        }
+
+        #[cfg(feature = "grpc")]
+        Commands::ServeGrpc { addr, concurrency, max_message_bytes } => {
+            let options = forgeimages_core::grpc::GrpcOptions { concurrency, max_message_bytes };
+            serve_grpc(&addr, pipeline, options)
+        }
+
+        #[cfg(feature = "signing")]
+        Commands::Sign { .. } | Commands::VerifySignature { .. } => unreachable!("handled before loading templates"),
+        #[cfg(feature = "schema")]
+        Commands::Schema { .. } => unreachable!("handled before loading templates"),
+
+        Commands::HashVectors
+        | Commands::Info
+        | Commands::Completions { .. }
+        | Commands::Config { .. }
+        | Commands::Diff { .. }
+        | Commands::Extract { .. }
+        | Commands::Template { .. }
+        | Commands::Watch { .. } => {
+            unreachable!("handled before loading templates")
+        }
+
+        Commands::Reproduce { .. } => unreachable!("handled before building the pipeline"),
+
+        Commands::Verify { manifest, dir, request, public_key } => {
+            let request: Option<CompileRequest> = match request.as_deref().map(|request| parse_payload(request, format)).transpose() {
+                Ok(r) => r,
+                Err(e) => {
+                    return ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid request: {}", e)).emit();
+                }
+            };
+            let public_key = match public_key.as_deref().map(read_public_key).transpose() {
+                Ok(key) => key,
+                Err(code) => return code,
+            };
+            let manifest_path = match (&manifest, &dir) {
+                (Some(path), _) => path.clone(),
+                (None, Some(dir)) => dir.join(MANIFEST_FILE),
+                (None, None) => unreachable!("clap requires --manifest or --dir"),
+            };
+
+            let checks = verify(&manifest_path, dir.as_deref(), request.as_ref(), public_key, &pipeline);
+            let ok = checks.iter().all(|check| check.status != CheckStatus::Fail);
+            // -q: rely on the exit code
+            if !quiet {
+                let report = serde_json::json!({
+                    "ok": ok,
+                    "manifest": manifest_path,
+                    "checks": checks,
+                });
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+            if ok {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(exit_code::VERIFICATION_FAILED)
+            }
+        }
+
+        Commands::Validate { template, payload, file, human, fail_on_warning } => {
+            let input: AssetInput = match read_input(payload, file) {
+                Ok(Input::Payload(payload)) => match parse_payload(&payload, format) {
+                    Ok(i) => i,
+                    Err(e) => {
+                        return ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)).emit();
+                    }
+                },
+                Ok(Input::File(source)) => source.input,
+                Err(e) => return e.emit(),
+            };
+
+            match pipeline.validate_asset(&template, &input) {
+                Ok(result) => {
+                    let gate = WarningGate::new(fail_on_warning, &result);
+                    if human {
+                        print!("{}{}", validation_report(&result, Style::detect()), gate.report_line());
+                    } else {
+                        gate.print_json(&result);
+                    }
+                    gate.exit_code()
+                }
+                Err(e) => ErrorEnvelope::from(&e).emit(),
+            }
+        }
+
+        Commands::Compile { template, payload, file, output_dir, stdout_manifest, force, human, dry_run, fail_on_warning, flags, .. } => {
+            if flags.any() && file.is_none() {
+                return ErrorEnvelope::new(ErrorKind::Usage, "--seed, --prompt, --param and --profile need --file")
+                    .emit();
+            }
+            let request: CompileRequest = match read_input(payload, file) {
+                Ok(Input::Payload(payload)) => match parse_payload(&payload, format) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        return ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)).emit();
+                    }
+                },
+                Ok(Input::File(source)) => {
+                    let mut request = source.request(&template);
+                    if let Err(e) = flags.apply(&mut request, &cli.templates_dir) {
+                        return ErrorEnvelope::new(ErrorKind::Usage, e).emit();
+                    }
+                    request
+                }
+                Err(e) => return e.emit(),
+            };
+
+            // Ensure template_id matches
+            let request = CompileRequest {
+                template_id: template,
+                ..request
+            };
+
+            // The exit code a compile would have, so CI can gate on it
+            if dry_run {
+                return match pipeline.compile_plan(&request) {
+                    Ok(plan) => {
+                        let gate = WarningGate::new(fail_on_warning, &plan.validation);
+                        gate.print_json(&plan);
+                        gate.exit_code()
+                    }
+                    Err(e) => ErrorEnvelope::from(&e).emit(),
+                };
+            }
+
+            let Some(dir) = output_dir else {
+                return match pipeline.compile_asset(&request) {
+                    Ok(asset) if human => print_compiled(&asset, None, fail_on_warning),
+                    Ok(asset) => print_manifest(&asset, fail_on_warning),
+                    Err(e) if human => print_compile_failure(&pipeline, &request, &e),
+                    Err(e) => pipeline.error_envelope(&request, &e).emit(),
+                };
+            };
+
+            let output = match OutputDir::claim(&dir, force) {
+                Ok(output) => output,
+                Err(e) => return ErrorEnvelope::new(ErrorKind::OutputFailed, e).emit(),
+            };
+            let asset = match pipeline.compile_to_dir(&request, &dir) {
+                Ok(asset) => asset,
+                Err(e) => {
+                    output.discard();
+                    if human {
+                        return print_compile_failure(&pipeline, &request, &e);
+                    }
+                    return pipeline.error_envelope(&request, &e).emit();
+                }
+            };
+            if stdout_manifest {
+                return print_manifest(&asset, fail_on_warning);
+            }
+            if human {
+                return print_compiled(&asset, Some(&dir), fail_on_warning);
+            }
+
+            let files: Vec<_> = asset.exports.iter()
+                .map(|export| serde_json::json!({
+                    "filename": export.filename,
+                    "size": fs::metadata(dir.join(&export.filename)).map(|m| m.len()).ok(),
+                    "hash": export.hash,
+                }))
+                .collect();
+            let summary = serde_json::json!({
+                "success": true,
+                "asset_id": asset.id,
+                "manifest_hash": asset.manifest_hash,
+                "manifest": dir.join(MANIFEST_FILE),
+                "files": files,
+            });
+            let gate = WarningGate::new(fail_on_warning, &asset.validation);
+            gate.print_json(&summary);
+            gate.exit_code()
+        }
+    }
+}
+
+/// Logs go to stderr, at every verbosity, so stdout stays the protocol
+fn init_logging(verbose: u8, quiet: bool, format: LogFormat) -> Telemetry {
+    use std::io::IsTerminal;
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::prelude::*;
+    let level = match verbose {
+        _ if quiet => LevelFilter::OFF,
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let logs = tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_target(false);
+    let logs = match format {
+        LogFormat::Text => logs.with_ansi(Style::detect_for(std::io::stderr().is_terminal()).color).boxed(),
+        LogFormat::Json => logs.json().boxed(),
+    };
+    let (spans, telemetry) = Telemetry::start();
+    tracing_subscriber::registry().with(spans).with(logs.with_filter(level)).init();
+    telemetry
+}
+
+type SpanLayer = Box<dyn tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync>;
+
+/// OTLP span export (the `otel` feature), on when `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set; the exporter reads its
+/// other `OTEL_*` variables itself. Flushes when dropped, at exit.
+#[cfg(feature = "otel")]
+struct Telemetry(Option<opentelemetry_sdk::trace::SdkTracerProvider>);
+
+#[cfg(feature = "otel")]
+impl Telemetry {
+    fn start() -> (Option<SpanLayer>, Self) {
+        use opentelemetry::trace::TracerProvider as _;
+        use tracing_subscriber::Layer as _;
+        let configured = ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+            .iter()
+            .any(|var| std::env::var_os(var).is_some());
+        if !configured {
+            return (None, Self(None));
+        }
+        let exporter = match opentelemetry_otlp::SpanExporter::builder().with_http().build() {
+            Ok(exporter) => exporter,
+            Err(e) => {
+                // Logging is not up yet
+                eprintln!("forgeimages-cli: OTLP export disabled: {}", e);
+                return (None, Self(None));
+            }
+        };
+        let mut resource = opentelemetry_sdk::Resource::builder();
+        if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name("forgeimages");
+        }
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource.build())
+            .build();
+        let layer = tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("forgeimages"))
+            .with_filter(tracing_subscriber::filter::LevelFilter::INFO)
+            .boxed();
+        (Some(layer), Self(Some(provider)))
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.0.take() {
+            let _ = provider.shutdown();
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+struct Telemetry;
+
+#[cfg(not(feature = "otel"))]
+impl Telemetry {
+    fn start() -> (Option<SpanLayer>, Self) {
+        (None, Self)
+    }
+}
+
+/// The token compile and batch stop on: `timeout` from now, or Ctrl-C
+fn cancel_token(timeout: Option<Duration>) -> CancelToken {
+    let token = timeout.map_or_else(CancelToken::new, CancelToken::with_timeout);
+    #[cfg(unix)]
+    {
+        let interrupted = token.clone();
+        // SAFETY: the action only stores to an atomic, which is async-signal-safe
+        let _ = unsafe {
+            signal_hook_registry::register(libc::SIGINT, move || interrupted.cancel(CancelReason::Interrupted))
+        };
+    }
+    token
+}
+
+#[cfg(feature = "server")]
+fn serve(addr: &str, pipeline: CompilationPipeline, options: forgeimages_core::server::ServerOptions) -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => return ErrorEnvelope::new(ErrorKind::Server, e.to_string()).with_exit_code(exit_code::OTHER).emit(),
+    };
+    runtime.block_on(async {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => return ErrorEnvelope::new(ErrorKind::Server, format!("{}: {}", addr, e)).emit(),
+        };
+        // With --addr 127.0.0.1:0 this is the only way to learn the port
+        let bound = listener.local_addr().map(|a| a.to_string()).unwrap_or_default();
+        println!("{}", serde_json::json!({"listening": bound}));
+        match forgeimages_core::server::serve(listener, std::sync::Arc::new(pipeline), options).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => ErrorEnvelope::new(ErrorKind::Server, e.to_string()).emit(),
+        }
+    })
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(addr: &str, pipeline: CompilationPipeline, options: forgeimages_core::grpc::GrpcOptions) -> ExitCode {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => return ErrorEnvelope::new(ErrorKind::Server, e.to_string()).with_exit_code(exit_code::OTHER).emit(),
+    };
+    runtime.block_on(async {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => return ErrorEnvelope::new(ErrorKind::Server, format!("{}: {}", addr, e)).emit(),
+        };
+        let bound = listener.local_addr().map(|a| a.to_string()).unwrap_or_default();
+        println!("{}", serde_json::json!({"listening": bound}));
+        match forgeimages_core::grpc::serve(listener, std::sync::Arc::new(pipeline), options).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => ErrorEnvelope::new(ErrorKind::Server, e.to_string()).emit(),
+        }
+    })
+}
+
+/// JSON-RPC error object; application errors use `-32000` and carry the
+/// CLI's `error_kind` and exit code in `data`
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<serde_json::Value>,
+}
+
+impl RpcError {
+    const PARSE_ERROR: i64 = -32700;
+    const INVALID_REQUEST: i64 = -32600;
+    const METHOD_NOT_FOUND: i64 = -32601;
+    const INVALID_PARAMS: i64 = -32602;
+    const APPLICATION: i64 = -32000;
+
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    fn params(e: impl std::fmt::Display) -> Self {
+        Self::new(Self::INVALID_PARAMS, format!("Invalid params: {}", e))
+    }
+}
+
+impl From<PipelineError> for RpcError {
+    fn from(error: PipelineError) -> Self {
+        let envelope = ErrorEnvelope::from(&error);
+        let data = serde_json::json!({
+            "error_kind": envelope.kind,
+            "exit_code": envelope.exit_code(),
+            "details": envelope.details,
+        });
+        Self { code: Self::APPLICATION, message: envelope.message, data: Some(data) }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ValidateParams {
+    template_id: String,
+    input: AssetInput,
+}
+
+#[derive(serde::Deserialize)]
+struct VerifyParams {
+    manifest: PathBuf,
+    #[serde(default)]
+    dir: Option<PathBuf>,
+    #[serde(default)]
+    request: Option<CompileRequest>,
+    /// Ed25519 public key PEM file
+    #[serde(default)]
+    public_key: Option<PathBuf>,
+}
+
+/// Serve JSON-RPC requests, one per line, with `concurrency` workers.
+///
+/// Each response is one line, written whole. Notifications (no `id`) are
+/// run but not answered. Lines that are not JSON-RPC get an error
+/// response with a null id; nothing a client sends ends the daemon
+/// except closing stdin.
+fn daemon(pipeline: &CompilationPipeline, concurrency: usize) -> ExitCode {
+    let (lines, queue) = std::sync::mpsc::sync_channel::<String>(concurrency.max(1));
+    let queue = std::sync::Mutex::new(queue);
+    let stdout = std::sync::Mutex::new(std::io::stdout());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.max(1) {
+            scope.spawn(|| loop {
+                let Ok(line) = queue.lock().unwrap().recv() else { return };
+                if let Some(response) = rpc_response(pipeline, &line) {
+                    let mut stdout = stdout.lock().unwrap();
+                    let _ = writeln!(stdout, "{}", response);
+                    let _ = stdout.flush();
+                }
+            });
+        }
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if !line.trim().is_empty() && lines.send(line).is_err() {
+                break;
+            }
+        }
+        drop(lines);
+    });
+    ExitCode::SUCCESS
+}
+
+/// The response line for one request line, or `None` for a notification
+fn rpc_response(pipeline: &CompilationPipeline, line: &str) -> Option<serde_json::Value> {
+    let respond = |id: serde_json::Value, outcome: Result<serde_json::Value, RpcError>| {
+        let mut response = serde_json::json!({"jsonrpc": "2.0", "id": id});
+        match outcome {
+            Ok(result) => response["result"] = result,
+            Err(e) => {
+                let mut error = serde_json::json!({"code": e.code, "message": e.message});
+                if let Some(data) = e.data {
+                    error["data"] = data;
+                }
+                response["error"] = error;
+            }
+        }
+        response
+    };
+
+    let request = match parse_strict(line) {
+        Ok(serde_json::Value::Object(request)) => request,
+        Ok(_) => return Some(respond(serde_json::Value::Null, Err(RpcError::new(RpcError::INVALID_REQUEST, "Invalid Request: not an object")))),
+        Err(e) => return Some(respond(serde_json::Value::Null, Err(RpcError::new(RpcError::PARSE_ERROR, format!("Parse error: {}", e))))),
+    };
+    let id = request.get("id").cloned();
+    let method = match (request.get("jsonrpc").and_then(|v| v.as_str()), request.get("method").and_then(|v| v.as_str())) {
+        (Some("2.0"), Some(method)) => method,
+        _ => {
+            let error = RpcError::new(RpcError::INVALID_REQUEST, "Invalid Request: needs jsonrpc \"2.0\" and a method");
+            return Some(respond(id.unwrap_or_default(), Err(error)));
+        }
+    };
+    let params = request.get("params").cloned().unwrap_or(serde_json::Value::Null);
+    let outcome = rpc_call(pipeline, method, params);
+    id.map(|id| respond(id, outcome))
+}
+
+fn rpc_call(pipeline: &CompilationPipeline, method: &str, params: serde_json::Value) -> Result<serde_json::Value, RpcError> {
+    let to_value = |value: Result<serde_json::Value, serde_json::Error>| value.map_err(|e| RpcError::from(PipelineError::from(e)));
+    match method {
+        "templates.list" => {
+            let mut templates = pipeline.list_templates();
+            templates.sort_by(|a, b| a.id.cmp(&b.id));
+            to_value(serde_json::to_value(templates))
+        }
+        "asset.validate" => {
+            let params: ValidateParams = serde_json::from_value(params).map_err(RpcError::params)?;
+            to_value(serde_json::to_value(pipeline.validate_asset(&params.template_id, &params.input)?))
+        }
+        "asset.compile" => {
+            let request: CompileRequest = serde_json::from_value(params).map_err(RpcError::params)?;
+            to_value(serde_json::to_value(pipeline.compile_asset(&request)?))
+        }
+        "manifest.verify" => {
+            let params: VerifyParams = serde_json::from_value(params).map_err(RpcError::params)?;
+            let public_key = params.public_key.as_deref().map(public_key_file).transpose().map_err(RpcError::params)?;
+            let checks = verify(&params.manifest, params.dir.as_deref(), params.request.as_ref(), public_key, pipeline);
+            let ok = checks.iter().all(|check| check.status != CheckStatus::Fail);
+            Ok(serde_json::json!({"ok": ok, "manifest": params.manifest, "checks": checks}))
+        }
+        "engine.info" => Ok(serde_json::json!({
+            "engine_version": forgeimages_core::ENGINE_VERSION,
+            "min_template_version": forgeimages_core::MIN_TEMPLATE_VERSION,
+            "hash_scheme": forgeimages_core::HashScheme::CURRENT.name(),
+            "template_count": pipeline.registry().list().len(),
+        })),
+        _ => Err(RpcError::new(RpcError::METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    }
+}
+
+/// Modification time and length of every file under a path
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+fn snapshot(path: &Path, into: &mut Snapshot) {
+    let Ok(metadata) = fs::metadata(path) else { return };
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            snapshot(&entry.path(), into);
+        }
+    } else {
+        into.insert(path.to_path_buf(), (metadata.modified().ok(), metadata.len()));
+    }
+}
+
+struct Watch<'a> {
+    templates_dirs: &'a [PathBuf],
+    allow_downgrade: bool,
+    pipeline_config: &'a PipelineConfig,
+    renderer: Option<RendererChoice>,
+    clock: Option<DateTime<Utc>>,
+    template: &'a str,
+    file: &'a Path,
+    output_dir: &'a Path,
+    debounce: Duration,
+    poll: Duration,
+    format: WatchFormat,
+}
+
+/// One watch build
+enum Build {
+    Compiled(Box<CompiledAsset>),
+    /// Blocked by validation; the violations, when the input could be validated
+    Invalid(String, Option<ValidationResult>),
+    Failed(String),
+}
+
+impl Watch<'_> {
+    /// Poll until `max_builds` results have been printed, or forever
+    fn run(&self, max_builds: Option<u32>) {
+        let mut pipeline: Option<CompilationPipeline> = None;
+        let mut seen: Option<(Snapshot, Snapshot)> = None;
+        let mut results = 0;
+        let (mut builds, mut compiled) = (0, 0);
+        while max_builds.is_none_or(|max| results < max) {
+            let current = self.settled();
+            if seen.as_ref() == Some(&current) {
+                std::thread::sleep(self.poll);
+                continue;
+            }
+            let templates_changed = seen.as_ref().is_none_or(|(templates, _)| *templates != current.0);
+            seen = Some(current);
+            let stamp = self.clock.unwrap_or_else(Utc::now).to_rfc3339_opts(SecondsFormat::Secs, true);
+
+            if templates_changed {
+                let builder = TemplateRegistry::load_layered(self.templates_dirs, self.allow_downgrade)
+                    .map_err(|e| e.to_string())
+                    .and_then(|registry| self.pipeline_config.builder(registry).map_err(|e| format!("pipeline config: {}", e)));
+                match builder {
+                    Ok(builder) => pipeline = Some(configure_pipeline(builder, self.renderer, self.clock).build()),
+                    Err(e) => {
+                        let kept = if pipeline.is_some() { "keeping the last good templates" } else { "no templates loaded yet" };
+                        let message = format!("{}; {}", e, kept);
+                        match self.format {
+                            WatchFormat::Text => println!("{} template error: {}", stamp, message),
+                            WatchFormat::Ndjson => Event::TemplateError { message: &message }.emit(),
+                        }
+                        results += 1;
+                        continue;
+                    }
+                }
+            }
+            let Some(pipeline) = &pipeline else { continue };
+            if self.format == WatchFormat::Ndjson {
+                Event::ItemStarted { index: builds, template_id: self.template, job_hash: None }.emit();
+            }
+            let build = self.build(pipeline);
+            compiled += matches!(build, Build::Compiled(_)) as usize;
+            match self.format {
+                WatchFormat::Text => println!("{} {}", stamp, build.line()),
+                WatchFormat::Ndjson => build.emit(builds),
+            }
+            builds += 1;
+            results += 1;
+        }
+        if self.format == WatchFormat::Ndjson {
+            Event::Summary { total: builds, compiled, failed: builds - compiled, skipped: 0, failed_due_to_warnings: None }.emit();
+        }
+    }
+
+    /// Templates and source, once neither has changed for `debounce`
+    fn settled(&self) -> (Snapshot, Snapshot) {
+        let take = || {
+            let (mut templates, mut source) = (Snapshot::new(), Snapshot::new());
+            for dir in self.templates_dirs {
+                snapshot(dir, &mut templates);
+            }
+            snapshot(self.file, &mut source);
+            (templates, source)
+        };
+        let mut current = take();
+        loop {
+            std::thread::sleep(self.debounce);
+            let again = take();
+            if again == current {
+                return current;
+            }
+            current = again;
+        }
+    }
+
+    /// Compile into a staging directory and swap it in only on success,
+    /// so the output directory always holds one complete build
+    fn build(&self, pipeline: &CompilationPipeline) -> Build {
+        let source = match read_input(None, Some(self.file.to_path_buf())) {
+            Ok(Input::File(source)) => source,
+            Ok(Input::Payload(_)) => unreachable!("no payload given"),
+            Err(e) => return Build::Failed(e.message),
+        };
+        let request = source.request(self.template);
+
+        let name = self.output_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let staging = self.output_dir.with_file_name(format!(".{}.partial", name));
+        let previous = self.output_dir.with_file_name(format!(".{}.previous", name));
+        let _ = fs::remove_dir_all(&staging);
+        let asset = match pipeline.compile_to_dir(&request, &staging) {
+            Ok(asset) => asset,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                return match e {
+                    PipelineError::ValidationFailed(message) => {
+                        Build::Invalid(message, pipeline.validate_asset(&request.template_id, &request.asset_input).ok())
+                    }
+                    e => Build::Failed(e.to_string()),
+                };
+            }
+        };
+        let _ = fs::remove_dir_all(&previous);
+        let swapped = match fs::rename(self.output_dir, &previous) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Build::Failed(format!("{}: {}", self.output_dir.display(), e)),
+        };
+        if let Err(e) = fs::rename(&staging, self.output_dir) {
+            if swapped {
+                let _ = fs::rename(&previous, self.output_dir);
+            }
+            return Build::Failed(format!("{}: {}", self.output_dir.display(), e));
+        }
+        let _ = fs::remove_dir_all(&previous);
+        Build::Compiled(Box::new(asset))
+    }
+}
+
+impl Build {
+    /// The text-format line, after the timestamp
+    fn line(&self) -> String {
+        match self {
+            Build::Compiled(asset) => {
+                let validity = if asset.validation.valid { "valid" } else { "invalid" };
+                format!("{}, {} violations, manifest {}", validity, asset.validation.violations.len(), asset.manifest_hash)
+            }
+            Build::Invalid(message, _) => format!("invalid: {}", message),
+            Build::Failed(message) => format!("error: {}", message),
+        }
+    }
+
+    /// Validation and item_finished events
+    fn emit(&self, index: usize) {
+        let validation = match self {
+            Build::Compiled(asset) => Some(&asset.validation),
+            Build::Invalid(_, validation) => validation.as_ref(),
+            Build::Failed(_) => None,
+        };
+        if let Some(result) = validation {
+            Event::Validation { index, result }.emit();
+        }
+        let (outcome, job_hash, manifest_hash, error) = match self {
+            Build::Compiled(asset) => (BatchOutcome::Compiled, Some(asset.job_hash.as_str()), Some(asset.manifest_hash.as_str()), None),
+            Build::Invalid(message, _) | Build::Failed(message) => (BatchOutcome::Failed, None, None, Some(message.as_str())),
+        };
+        Event::ItemFinished { index, outcome, job_hash, manifest_hash, error }.emit();
+    }
+}
+
+/// Lint findings for one file
+#[derive(Serialize)]
+struct FileReport {
+    file: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_id: Option<String>,
+    issues: Vec<LintIssue>,
+}
+
+impl FileReport {
+    fn failed(file: &Path, code: &'static str, message: String) -> Self {
+        let issue = LintIssue { code, severity: ViolationSeverity::Error, field: String::new(), message };
+        Self { file: file.to_path_buf(), template_id: None, issues: vec![issue] }
+    }
+}
+
+fn lint_file(path: &Path, profiles: &ProfileRegistry) -> FileReport {
+    match Template::from_file(path, profiles) {
+        Ok(template) => {
+            let mut issues = template.lint();
+            if path.file_stem().is_some_and(|stem| *stem != *template.id) {
+                issues.push(LintIssue {
+                    code: "id_filename_mismatch",
+                    severity: ViolationSeverity::Warning,
+                    field: "id".to_string(),
+                    message: format!("template {:?} is not in {}.json", template.id, template.id),
+                });
+            }
+            FileReport { file: path.to_path_buf(), template_id: Some(template.id), issues }
+        }
+        Err(e) => FileReport { file: path.to_path_buf(), template_id: None, issues: vec![e.to_issue()] },
+    }
+}
+
+/// Every template file in `dir`, in path order, plus ids used twice
+fn lint_dir(dir: &Path) -> Vec<FileReport> {
+    let profiles = match ProfileRegistry::load_from_dir(dir) {
+        Ok(profiles) => profiles,
+        Err(e) => return vec![FileReport::failed(dir, "print_profile", e.to_string())],
+    };
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|e| e == "json"))
+            .filter(|path| !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(PROFILE_SUFFIX)))
+            .collect(),
+        Err(e) => return vec![FileReport::failed(dir, "unreadable", e.to_string())],
+    };
+    paths.sort();
+
+    let mut seen: HashMap<String, PathBuf> = HashMap::new();
+    paths.iter()
+        .map(|path| {
+            let mut report = lint_file(path, &profiles);
+            if let Some(id) = &report.template_id {
+                match seen.get(id) {
+                    Some(first) => report.issues.push(LintIssue {
+                        code: "duplicate_template_id",
+                        severity: ViolationSeverity::Error,
+                        field: "id".to_string(),
+                        message: format!("template {:?} is also defined in {}", id, first.display()),
+                    }),
+                    None => {
+                        seen.insert(id.clone(), path.clone());
+                    }
+                }
+            }
+            report
+        })
+        .collect()
+}
+
+fn print_lint(reports: &[FileReport], human: bool) -> ExitCode {
+    let issues = reports.iter().flat_map(|r| &r.issues);
+    let errors = issues.clone().filter(|i| i.severity == ViolationSeverity::Error).count();
+    let warnings = issues.filter(|i| i.severity == ViolationSeverity::Warning).count();
+    if human {
+        for report in reports {
+            for issue in &report.issues {
+                let severity = if issue.severity == ViolationSeverity::Error { "error" } else { "warning" };
+                let field = if issue.field.is_empty() { String::new() } else { format!(" {}:", issue.field) };
+                println!("{}: {}[{}]{} {}", report.file.display(), severity, issue.code, field, issue.message);
+            }
+        }
+        println!("{} errors, {} warnings in {} files", errors, warnings, reports.len());
+    } else {
+        let report = serde_json::json!({
+            "ok": errors + warnings == 0,
+            "errors": errors,
+            "warnings": warnings,
+            "files": reports,
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+    if errors + warnings == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(exit_code::VALIDATION_FAILED)
+    }
+}
+
+/// ANSI styling for `--human` reports: on for terminals, off under
+/// `NO_COLOR`, and forced for pipes by `CLICOLOR_FORCE`
+#[derive(Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn detect() -> Self {
+        use std::io::IsTerminal;
+        Self::detect_for(std::io::stdout().is_terminal())
+    }
+
+    /// Color for a stream that is, or is not, a terminal
+    fn detect_for(terminal: bool) -> Self {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0");
+        Self { color: !set("NO_COLOR") && (set("CLICOLOR_FORCE") || terminal) }
+    }
+
+    fn paint(self, sgr: &str, text: &str) -> String {
+        if self.color { format!("\x1b[{}m{}\x1b[0m", sgr, text) } else { text.to_string() }
+    }
+}
+
+/// `1 error`, `2 warnings`
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// `--human` validation report: violations grouped by severity with rule,
+/// expected and actual in aligned columns and remediation bullets beneath,
+/// then a count line naming the warnings
+fn validation_report(result: &ValidationResult, style: Style) -> String {
+    let mut out = String::new();
+    let verdict = if result.valid { style.paint("1;32", "valid") } else { style.paint("1;31", "invalid") };
+    out.push_str(&format!("{} {}: {}\n", result.template_id, result.template_version, verdict));
+
+    let cell = |text: &Option<String>| text.clone().unwrap_or_else(|| "-".to_string());
+    let width = |header: &str, column: &dyn Fn(&ValidationViolation) -> String| {
+        result.violations.iter().map(|v| column(v).chars().count()).chain([header.len()]).max().unwrap_or(0)
+    };
+    let rule_width = width("RULE", &|v| v.rule.clone());
+    let expected_width = width("EXPECTED", &|v| cell(&v.expected));
+    let actual_width = width("ACTUAL", &|v| cell(&v.actual));
+    let row = |rule: String, rule_text: &str, expected: &str, actual: &str, message: &str| {
+        // Padding by the plain text keeps columns aligned when `rule` is painted
+        let pad = |text: &str, width: usize| " ".repeat(width - text.chars().count());
+        let line = format!(
+            "  {}{}  {}{}  {}{}  {}",
+            rule, pad(rule_text, rule_width), expected, pad(expected, expected_width), actual, pad(actual, actual_width), message,
+        );
+        format!("{}\n", line.trim_end())
+    };
+
+    let sections = [
+        (ViolationSeverity::Error, "Errors", "1;31"),
+        (ViolationSeverity::Warning, "Warnings", "1;33"),
+        (ViolationSeverity::Info, "Info", "1;34"),
+    ];
+    for (severity, title, sgr) in sections {
+        let violations: Vec<_> = result.violations.iter().filter(|v| v.severity == severity).collect();
+        if violations.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n{}\n", style.paint(sgr, &format!("{} ({})", title, violations.len()))));
+        out.push_str(&row(style.paint("2", "RULE"), "RULE", "EXPECTED", "ACTUAL", "MESSAGE"));
+        for violation in violations {
+            let (expected, actual) = (cell(&violation.expected), cell(&violation.actual));
+            out.push_str(&row(style.paint("1", &violation.rule), &violation.rule, &expected, &actual, &violation.message));
+            for step in &violation.remediation {
+                out.push_str(&format!("  {}  - {}\n", " ".repeat(rule_width), step));
+            }
+        }
+    }
+
+    let of = |severity: ViolationSeverity| result.violations.iter().filter(move |v| v.severity == severity);
+    let warnings: Vec<_> = of(ViolationSeverity::Warning).map(|v| v.rule.as_str()).collect();
+    out.push_str(&format!("\n{}, {}", count(of(ViolationSeverity::Error).count(), "error"), count(warnings.len(), "warning")));
+    if !warnings.is_empty() {
+        out.push_str(&format!(": {}", warnings.join(", ")));
+    }
+    out.push('\n');
+    out
+}
+
+/// `print resolve --format human`: the template-level spec field by field
+/// with its authority, a row per print export, then any validation findings
+fn print_plan_report(plan: &PrintPlan, style: Style) -> String {
+    let mut out = format!("{} {}\n", plan.template_id, plan.template_version);
+    let text = |value: serde_json::Value| match value {
+        serde_json::Value::String(text) => text,
+        serde_json::Value::Null => "-".to_string(),
+        value => value.to_string(),
+    };
+    if let Some(print) = &plan.print {
+        out.push_str(&format!("\n{}\n", style.paint("1", "Print")));
+        let rows: Vec<_> = PrintField::ALL.into_iter()
+            .map(|field| {
+                let authority = text(serde_json::to_value(print.provenance[field.name()]).unwrap_or_default());
+                let replaced = print.overrides.iter().find(|o| o.field == field.name())
+                    .map(|o| format!(" (template {})", text(o.template_value.clone())))
+                    .unwrap_or_default();
+                vec![field.name().to_string(), text(field.value(&print.spec)), format!("{}{}", authority, replaced)]
+            })
+            .collect();
+        out.push_str(&table(&["FIELD", "VALUE", "FROM"], &rows));
+    }
+    if !plan.exports.is_empty() {
+        out.push_str(&format!("\n{}\n", style.paint("1", "Exports")));
+        let size = |[w, h]: [u32; 2]| format!("{}x{}", w, h);
+        let rows: Vec<_> = plan.exports.iter()
+            .map(|export| vec![
+                export.id.clone(),
+                export.format.clone(),
+                export.print.spec.dpi.to_string(),
+                text(PrintField::ColorSpace.value(&export.print.spec)),
+                size(export.trim),
+                size(export.size),
+            ])
+            .collect();
+        out.push_str(&table(&["ID", "FORMAT", "DPI", "COLOR", "TRIM", "FILE"], &rows));
+    }
+    if !plan.validation.violations.is_empty() || !plan.validation.valid {
+        out.push('\n');
+        out.push_str(&validation_report(&plan.validation, style));
+    }
+    out
+}
+
+/// `compile --human` after a successful compile
+fn print_compiled(asset: &CompiledAsset, dir: Option<&Path>, fail_on_warning: bool) -> ExitCode {
+    print!("{}", validation_report(&asset.validation, Style::detect()));
+    match dir {
+        Some(dir) => println!("compiled {} into {}", count(asset.exports.len(), "export"), dir.display()),
+        None => println!("compiled {}", count(asset.exports.len(), "export")),
+    }
+    println!("manifest {}", asset.manifest_hash);
+    let gate = WarningGate::new(fail_on_warning, &asset.validation);
+    print!("{}", gate.report_line());
+    gate.exit_code()
+}
+
+/// `compile --human` after a failed compile: the report when validation
+/// blocked it, else the usual error envelope
+fn print_compile_failure(pipeline: &CompilationPipeline, request: &CompileRequest, error: &PipelineError) -> ExitCode {
+    let envelope = pipeline.error_envelope(request, error);
+    match &envelope.validation {
+        // Request-level rules (the source's own) only the envelope reports
+        Some(result) if !result.valid => {
+            print!("{}", validation_report(result, Style::detect()));
+            ExitCode::from(exit_code::VALIDATION_FAILED)
+        }
+        _ => envelope.emit(),
+    }
+}
+
+/// `hash --canonical` and `hash --file`, which need no templates
+fn hash_input(args: &HashArgs) -> ExitCode {
+    let algorithm = HashAlgorithm::Sha256;
+    let hash = if let Some(path) = &args.canonical {
+        let text = if path.as_os_str() == "-" {
+            payload_text("-").map(Cow::into_owned).map_err(|e| InputError::file(path, e, exit_code::INVALID_PAYLOAD))
+        } else {
+            fs::read_to_string(path).map_err(|e| InputError::file(path, e, exit_code::IO_ERROR))
+        };
+        let canonical = text.and_then(|text| {
+            let value = parse_strict(&text).map_err(|e| InputError::file(path, e, exit_code::INVALID_PAYLOAD))?;
+            canonical_json(&value).map_err(|e| InputError::file(path, e, exit_code::INVALID_PAYLOAD))
+        });
+        match canonical {
+            Ok(canonical) => serde_json::json!({"hash": algorithm.digest(canonical.as_bytes()), "canonical": canonical}),
+            Err(e) => return e.emit(),
+        }
+    } else if let Some(path) = &args.file {
+        match fs::File::open(path).and_then(|file| algorithm.digest_reader(file)) {
+            Ok(digest) => serde_json::json!({"file": path, "hash": digest}),
+            Err(e) => return InputError::file(path, e, exit_code::IO_ERROR).emit(),
+        }
+    } else {
+        unreachable!("--job needs templates")
+    };
+    println!("{}", serde_json::to_string_pretty(&hash).unwrap());
+    ExitCode::SUCCESS
+}
+
+/// `batch`: exits 1 when any item failed; the output says which
+fn batch(
+    pipeline: &CompilationPipeline,
+    requests: &[CompileRequest],
+    output_dir: &Path,
+    checkpoint: Option<&Path>,
+    format: BatchFormat,
+    fail_on_warning: bool,
+) -> ExitCode {
+    let mut current = 0;
+    // Items --fail-on-warning fails; skipped items were judged when they compiled
+    let mut warned = vec![];
+    let on_event = |event: BatchEvent<'_>| {
+        match event {
+            BatchEvent::Started { index, .. } => current = index,
+            BatchEvent::Validated { index, validation } if WarningGate::new(true, validation).failed => warned.push(index),
+            _ => {}
+        }
+        if format != BatchFormat::Ndjson {
+            return;
+        }
+        match event {
+            BatchEvent::Started { index, job_hash } => {
+                Event::ItemStarted { index, template_id: &requests[index].template_id, job_hash }.emit();
+            }
+            BatchEvent::Validated { index, validation } => Event::Validation { index, result: validation }.emit(),
+            BatchEvent::Finished { index, item } => Event::ItemFinished {
+                index,
+                outcome: item.outcome,
+                job_hash: item.job_hash.as_deref(),
+                manifest_hash: item.manifest_hash.as_deref(),
+                error: item.error.as_deref(),
+            }
+            .emit(),
+        }
+    };
+    let result = match pipeline.compile_batch_with(requests, output_dir, checkpoint, on_event) {
+        Ok(result) => result,
+        Err(PipelineError::Cancelled(reason, export)) if format == BatchFormat::Ndjson => {
+            Event::Cancelled { index: current, reason, export: export.as_deref() }.emit();
+            return ExitCode::from(exit_code::CANCELLED);
+        }
+        Err(e @ PipelineError::Cancelled(..)) => {
+            return ErrorEnvelope::from(&e).detail("index", current).emit();
+        }
+        Err(e) => return ErrorEnvelope::from(&e).emit(),
+    };
+    let (compiled, failed, skipped) = (
+        result.count(BatchOutcome::Compiled),
+        result.count(BatchOutcome::Failed),
+        result.count(BatchOutcome::Skipped),
+    );
+    warned.retain(|&index| result.items[index].outcome == BatchOutcome::Compiled);
+    let failed_on_warnings = fail_on_warning && !warned.is_empty();
+    match format {
+        BatchFormat::Ndjson => Event::Summary {
+            total: result.items.len(),
+            compiled,
+            failed,
+            skipped,
+            failed_due_to_warnings: fail_on_warning.then_some(failed_on_warnings),
+        }
+        .emit(),
+        BatchFormat::Json => {
+            let mut report = serde_json::json!({
+                "success": failed == 0 && !failed_on_warnings,
+                "compiled": compiled,
+                "failed": failed,
+                "skipped": skipped,
+                "items": result.items,
+            });
+            if fail_on_warning {
+                report["failed_due_to_warnings"] = failed_on_warnings.into();
+                report["warned"] = serde_json::to_value(&warned).unwrap();
+            }
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+    match (failed, failed_on_warnings) {
+        (0, false) => ExitCode::SUCCESS,
+        (0, true) => ExitCode::from(exit_code::FAILED_ON_WARNINGS),
+        _ => ExitCode::from(exit_code::OTHER),
+    }
+}
+
+/// `diff`: manifest `a` against manifest `b`
+fn diff(a: &Path, b: &Path, format: DiffFormat) -> ExitCode {
+    let read = |path: &Path| -> Result<CompiledAsset, InputError> {
+        let content = fs::read(path).map_err(|e| InputError::file(path, e, exit_code::IO_ERROR))?;
+        serde_json::from_slice(&content).map_err(|e| InputError::file(path, e, exit_code::INVALID_PAYLOAD))
+    };
+    let (a, b) = match (read(a), read(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => return e.emit(),
+    };
+    let diff = diff_manifests(&a, &b);
+    match format {
+        DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+        DiffFormat::Human => print_diff(&diff),
+    }
+    if diff.identical { ExitCode::SUCCESS } else { ExitCode::from(exit_code::MANIFESTS_DIFFER) }
+}
+
+/// A PEM key file, or the error envelope already printed
+#[cfg(feature = "signing")]
+fn read_key<K>(path: &Path, parse: fn(&str) -> Result<K, KeyError>) -> Result<K, ExitCode> {
+    let pem = fs::read_to_string(path).map_err(|e| InputError::file(path, e, exit_code::IO_ERROR).emit())?;
+    parse(&pem).map_err(|e| {
+        let kind = if matches!(e, KeyError::Encrypted) { ErrorKind::UnsupportedKey } else { ErrorKind::InvalidKey };
+        ErrorEnvelope::new(kind, format!("{}: {}", path.display(), e)).detail("file", path).emit()
+    })
+}
+
+/// `sign`: verify the manifest, then sign the hash it records
+#[cfg(feature = "signing")]
+fn sign(manifest: &Path, key: &Path, key_id: Option<&str>, sig: Option<&Path>, embed: Option<&Path>) -> ExitCode {
+    let key = match read_key(key, SigningKey::from_pkcs8_pem) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+    // A signature vouches for the manifest, so never sign one that fails its own hash
+    let manifest_hash = match verify::verify_manifest(manifest) {
+        Ok(hash) => hash,
+        Err(VerifyError::Io(e)) => return InputError::file(manifest, e, exit_code::IO_ERROR).emit(),
+        Err(e) => {
+            return ErrorEnvelope::new(ErrorKind::VerificationFailed, format!("{}: {}", manifest.display(), e))
+                .detail("file", manifest)
+                .emit();
+        }
+    };
+    let mut signature = signing::sign_hash(&manifest_hash, &key);
+    if let Some(key_id) = key_id {
+        signature.key_id = key_id.to_string();
+    }
+
+    let sig_path = sig.map_or_else(|| manifest.with_file_name(SIGNATURE_FILE), Path::to_path_buf);
+    let write = |path: &Path, content: String| fs::write(path, content)
+        .map_err(|e| ErrorEnvelope::new(ErrorKind::OutputFailed, format!("{}: {}", path.display(), e)).emit());
+    if let Err(code) = write(&sig_path, format!("{}\n", signature.signature_b64)) {
+        return code;
+    }
+    if let Some(embed) = embed {
+        // Read back as verified; `signature` sits outside the manifest hash
+        let mut copy: serde_json::Value = serde_json::from_slice(&fs::read(manifest).unwrap_or_default()).unwrap_or_default();
+        copy["signature"] = serde_json::to_value(&signature).unwrap();
+        if let Err(code) = write(embed, canonical_json(&copy).expect("manifest JSON was just parsed")) {
+            return code;
+        }
+    }
+
+    let report = serde_json::json!({
+        "manifest": manifest,
+        "signature_file": sig_path,
+        "embedded": embed,
+        "signature": signature,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    ExitCode::SUCCESS
+}
+
+/// `verify-signature`: `--sig`, else the embedded block, else manifest.sig
+#[cfg(feature = "signing")]
+fn verify_signature_command(manifest: &Path, sig: Option<&Path>, public_key: &Path) -> ExitCode {
+    let key = match read_key(public_key, VerifyingKey::from_public_key_pem) {
+        Ok(key) => key,
+        Err(code) => return code,
+    };
+    let embedded = sig.is_none() && fs::read(manifest).ok()
+        .and_then(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+        .is_some_and(|json| json.get("signature").is_some());
+    let (result, source) = if embedded {
+        (verify::verify_embedded_signature(manifest, &key).map(|block| Some(block.key_id)), manifest.to_path_buf())
+    } else {
+        let path = sig.map_or_else(|| manifest.with_file_name(SIGNATURE_FILE), Path::to_path_buf);
+        (verify::verify_signature_file(manifest, &path, &key).map(|()| None), path)
+    };
+
+    match result {
+        Ok(key_id) => {
+            let report = serde_json::json!({
+                "ok": true,
+                "manifest": manifest,
+                "signature": source,
+                "embedded": embedded,
+                "key_id": key_id,
+                "public_key_id": key.key_id(),
+            });
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(VerifyError::Io(e)) => InputError::file(manifest, e, exit_code::IO_ERROR).emit(),
+        Err(e) => ErrorEnvelope::new(ErrorKind::SignatureRejected, e.to_string())
+            .detail("manifest", manifest)
+            .detail("signature", source)
+            .emit(),
+    }
+}
+
+/// What `extract` did with one export
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Extraction {
+    /// Written, and its bytes match the recorded hash
+    Written,
+    /// Written under `--no-verify` although the bytes do not match
+    Unverified,
+    /// Not written: the bytes do not match the recorded hash
+    HashMismatch,
+    /// Not written: no usable bytes, or a filename that leaves the directory
+    Invalid,
+}
+
+#[derive(Serialize)]
+struct ExtractedFile<'a> {
+    id: &'a str,
+    filename: &'a str,
+    status: Extraction,
+    recorded_hash: &'a str,
+    /// Hash of the decoded bytes, under the recorded hash's algorithm
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// `extract`: exits 7 when an export was refused
+fn extract(manifest: &Path, output_dir: &Path, ids: &[String], verify: bool, force: bool) -> ExitCode {
+    let asset = match fs::read(manifest) {
+        // Either a bare manifest or compile's `{"asset": ..., "success": true}`
+        Ok(content) => serde_json::from_slice(&content).and_then(|mut json: serde_json::Value| match json.get_mut("asset") {
+            Some(asset) => serde_json::from_value::<CompiledAsset>(asset.take()),
+            None => serde_json::from_value(json),
+        }),
+        Err(e) => return InputError::file(manifest, e, exit_code::IO_ERROR).emit(),
+    };
+    let asset = match asset {
+        Ok(asset) => asset,
+        Err(e) => return InputError::file(manifest, e, exit_code::INVALID_PAYLOAD).emit(),
+    };
+    if let Some(unknown) = ids.iter().find(|id| !asset.exports.iter().any(|export| &export.id == *id)) {
+        let available: Vec<_> = asset.exports.iter().map(|export| export.id.as_str()).collect();
+        return ErrorEnvelope::new(ErrorKind::UnknownExport, format!("Manifest has no export {}", unknown))
+            .detail("available", available)
+            .emit();
+    }
+    let output = match OutputDir::claim(output_dir, force) {
+        Ok(output) => output,
+        Err(e) => return ErrorEnvelope::new(ErrorKind::OutputFailed, e).emit(),
+    };
+    if let Err(e) = fs::create_dir_all(output_dir) {
+        return ErrorEnvelope::new(ErrorKind::OutputFailed, format!("{}: {}", output_dir.display(), e)).emit();
+    }
+
+    let mut files = vec![];
+    for export in asset.exports.iter().filter(|export| ids.is_empty() || ids.contains(&export.id)) {
+        let mut file = ExtractedFile {
+            id: &export.id,
+            filename: &export.filename,
+            status: Extraction::Invalid,
+            recorded_hash: &export.hash,
+            hash: None,
+            detail: None,
+        };
+        // A bare file name, so a hostile manifest cannot write elsewhere
+        if !is_plain_file_name(&export.filename) {
+            file.detail = Some("filename is not a plain file name".to_string());
+            files.push(file);
+            continue;
+        }
+        if export.data_base64.is_empty() {
+            file.detail = Some("manifest carries no data for this export".to_string());
+            files.push(file);
+            continue;
+        }
+        let data = match base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &export.data_base64) {
+            Ok(data) => data,
+            Err(e) => {
+                file.detail = Some(format!("data_base64: {}", e));
+                files.push(file);
+                continue;
+            }
+        };
+        let algorithm = forgeimages_core::hashing::parse_digest(&export.hash).map(|(algorithm, _)| algorithm).ok();
+        file.hash = algorithm.map(|algorithm| algorithm.digest(&data));
+        let matches = file.hash.as_deref().is_some_and(|hash| forgeimages_core::hashing::digests_equal(&export.hash, hash));
+        file.status = match (matches, verify) {
+            (true, _) => Extraction::Written,
+            (false, false) => Extraction::Unverified,
+            (false, true) => Extraction::HashMismatch,
+        };
+        if !matches!(file.status, Extraction::HashMismatch) {
+            if let Err(e) = fs::write(output_dir.join(&export.filename), &data) {
+                output.discard();
+                return ErrorEnvelope::new(ErrorKind::OutputFailed, format!("{}: {}", export.filename, e)).emit();
+            }
+        }
+        files.push(file);
+    }
+
+    let refused = files.iter().filter(|file| matches!(file.status, Extraction::HashMismatch | Extraction::Invalid)).count();
+    let report = serde_json::json!({
+        "success": refused == 0,
+        "output_dir": output_dir,
+        "files": files,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    if refused == 0 { ExitCode::SUCCESS } else { ExitCode::from(exit_code::VERIFICATION_FAILED) }
+}
+
+fn reproduce(registry: TemplateRegistry, manifest: &Path, source: Option<&Path>, format: DiffFormat) -> ExitCode {
+    let recorded: CompiledAsset = match fs::read(manifest) {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(recorded) => recorded,
+            Err(e) => return InputError::file(manifest, e, exit_code::INVALID_PAYLOAD).emit(),
+        },
+        Err(e) => return InputError::file(manifest, e, exit_code::IO_ERROR).emit(),
+    };
+    let reproduction = match forgeimages_core::reproduce::reproduce(registry, &recorded, source) {
+        Ok(reproduction) => reproduction,
+        Err(ReproduceError::Pipeline(e)) => return ErrorEnvelope::from(&e).emit(),
+        Err(e @ ReproduceError::NoRequest) => {
+            return ErrorEnvelope::new(ErrorKind::NotReproducible, e.to_string()).detail("file", manifest).emit();
+        }
+        Err(e @ ReproduceError::SourceRequired) => return ErrorEnvelope::new(ErrorKind::Usage, e.to_string()).emit(),
+        Err(ReproduceError::Source(path, e)) => return InputError::file(&path, e, exit_code::IO_ERROR).emit(),
+    };
+    match format {
+        DiffFormat::Json => println!("{}", serde_json::to_string_pretty(&reproduction).unwrap()),
+        DiffFormat::Human => {
+            if let Some(change) = &reproduction.template_changed {
+                println!(
+                    "template {} changed since compile: recorded {} {}, loaded {} {}",
+                    change.template_id, change.recorded_version, change.recorded_hash, change.loaded_version, change.loaded_hash,
+                );
+            }
+            if let Some(change) = &reproduction.source_changed {
+                let recorded = change.recorded_hash.as_deref().unwrap_or("no source");
+                println!("source is not the one compiled: recorded {}, given {}", recorded, change.given_hash);
+            }
+            print_diff(&reproduction.diff);
+            if reproduction.reproduced {
+                println!("reproduced {}", reproduction.manifest_hash);
+            } else {
+                println!("not reproduced: manifest hash recorded {}, recompiled {}", reproduction.recorded_manifest_hash, reproduction.manifest_hash);
+            }
+        }
+    }
+    if reproduction.reproduced { ExitCode::SUCCESS } else { ExitCode::from(exit_code::VERIFICATION_FAILED) }
+}
+
+/// One line per difference, then a count
+fn print_diff(diff: &ManifestDiff) {
+    let mut lines = vec![];
+    for change in &diff.fields {
+        lines.push(format!("{}: {} -> {}", change.field, change.a, change.b));
+    }
+    for export in &diff.exports {
+        match export {
+            ExportDiff::OnlyInA { id } => lines.push(format!("exports[{}]: only in a", id)),
+            ExportDiff::OnlyInB { id } => lines.push(format!("exports[{}]: only in b", id)),
+            ExportDiff::Changed { id, changes } => lines.extend(changes.iter()
+                .map(|change| format!("exports[{}].{}: {} -> {}", id, change.field, change.a, change.b))),
+        }
+    }
+    if let Some([a, b]) = diff.validation.valid {
+        lines.push(format!("validation.valid: {} -> {}", a, b));
+    }
+    let violation = |v: &ValidationViolation| format!("{} ({:?}): {}", v.rule, v.severity, v.message);
+    lines.extend(diff.validation.removed.iter().map(|v| format!("validation: - {}", violation(v))));
+    lines.extend(diff.validation.added.iter().map(|v| format!("validation: + {}", violation(v))));
+    for line in &lines {
+        println!("{}", line);
+    }
+    if lines.is_empty() {
+        println!("identical");
+    } else {
+        println!("{} differences", lines.len());
+    }
+}
+
+/// `$schema` of scaffolded templates, as in the shipped ones
+const TEMPLATE_SCHEMA: &str = "https://forgeimages.dev/schemas/template-v1.json";
+
+/// `template new`: a builder template, or a clone starting a fresh
+/// version line; written files are linted like `template validate`
+fn new_template(cli: &Cli, args: &NewTemplateArgs) -> ExitCode {
+    let template = match (&args.from, &args.class, args.canonical) {
+        (Some(from), ..) => {
+            let registry = match cli.load_templates() {
+                Ok(registry) => registry,
+                Err(code) => return code,
+            };
+            let Some(source) = registry.get(from) else {
+                return ErrorEnvelope::from(&PipelineError::TemplateNotFound(from.clone())).emit();
+            };
+            Template {
+                id: args.id.clone(),
+                name: args.id.clone(),
+                template_version: "1.0.0".to_string(),
+                engine_min_version: forgeimages_core::ENGINE_VERSION.to_string(),
+                engine_max_version: None,
+                deprecated: false,
+                superseded_by: None,
+                ..source.clone()
+            }
+        }
+        (None, Some(class), Some(canonical)) => {
+            let builder = Template::builder(&args.id, class.clone(), canonical);
+            match args.aspect {
+                Some(aspect) => builder.aspect_ratio(aspect).build(),
+                None => builder.build(),
+            }
+        }
+        _ => unreachable!("clap requires --class and --canonical without --from"),
+    };
+
+    let mut value = serde_json::to_value(&template).unwrap();
+    // Print rules serialize only when changed; authors should see them anyway
+    value["validation"]["rules"]["print"] = serde_json::to_value(&template.validation.rules.print).unwrap();
+    value["$schema"] = serde_json::Value::from(TEMPLATE_SCHEMA);
+    let json = serde_json::to_string_pretty(&value).unwrap();
+
+    let Some(out) = &args.out else {
+        println!("{}", json);
+        return ExitCode::SUCCESS;
+    };
+    if out.exists() && !args.force {
+        return ErrorEnvelope::new(ErrorKind::OutputExists, format!("{} exists; pass --force to overwrite it", out.display()))
+            .detail("file", out)
+            .emit();
+    }
+    if let Err(e) = fs::write(out, json + "\n") {
+        return ErrorEnvelope::new(ErrorKind::OutputFailed, format!("{}: {}", out.display(), e))
+            .detail("file", out)
+            .emit();
+    }
+    let dir = out.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let report = match ProfileRegistry::load_from_dir(dir) {
+        Ok(profiles) => lint_file(out, &profiles),
+        Err(e) => FileReport::failed(dir, "print_profile", e.to_string()),
+    };
+    print_lint(&[report], false)
+}
+
+/// `templates` listing; every format is sorted by id
+fn list_templates(pipeline: &CompilationPipeline, args: &ListArgs) -> ExitCode {
+    let asset_class: Option<AssetClass> = match args.asset_class.as_deref()
+        .map(|class| serde_json::from_value(serde_json::Value::from(class)))
+        .transpose()
+    {
+        Ok(class) => class,
+        Err(_) => {
+            let class = args.asset_class.as_deref().unwrap_or_default();
+            return ErrorEnvelope::new(ErrorKind::UnknownAssetClass, format!("Unknown asset class: {}", class))
+                .emit();
+        }
+    };
+    let mut templates: Vec<Template> = pipeline.list_templates()
+        .into_iter()
+        .filter(|t| asset_class.as_ref().is_none_or(|class| t.asset_class == *class))
+        .filter(|t| args.tag.iter().all(|tag| t.tags.contains(tag)))
+        .filter(|t| match (args.include_deprecated, args.only_deprecated) {
+            (_, true) => t.deprecated,
+            (true, false) => true,
+            (false, false) => !t.deprecated,
+        })
+        .collect();
+    templates.sort_by(|a, b| a.id.cmp(&b.id));
+
+    match args.format {
+        ListFormat::Json if args.detail => {
+            println!("{}", serde_json::to_string_pretty(&templates).unwrap());
+        }
+        ListFormat::Json => {
+            let summaries: Vec<_> = templates.iter()
+                .map(|t| serde_json::json!({
+                    "id": t.id,
+                    "name": t.name,
+                    "version": t.template_version,
+                    "asset_class": t.asset_class,
+                    "deprecated": t.deprecated,
+                    "tags": t.tags,
+                }))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&summaries).unwrap());
+        }
+        ListFormat::Ids => {
+            for template in &templates {
+                println!("{}", template.id);
+            }
+        }
+        ListFormat::Table => {
+            let mut header = vec!["ID", "VERSION", "CLASS", "NAME", "TAGS", "DEPRECATED"];
+            if args.detail {
+                header.extend(["SIZE", "EXPORTS", "DESCRIPTION"]);
+            }
+            let rows: Vec<Vec<String>> = templates.iter()
+                .map(|t| {
+                    let class = serde_json::to_value(&t.asset_class).ok()
+                        .and_then(|v| v.as_str().map(str::to_string))
+                        .unwrap_or_default();
+                    let deprecated = match (&t.superseded_by, t.deprecated) {
+                        (Some(by), true) => format!("yes, use {}", by),
+                        (None, true) => "yes".to_string(),
+                        (_, false) => String::new(),
+                    };
+                    let mut row = vec![t.id.clone(), t.template_version.clone(), class, t.name.clone(), t.tags.join(","), deprecated];
+                    if args.detail {
+                        row.push(format!("{}x{}", t.canonical_size[0], t.canonical_size[1]));
+                        row.push(t.exports.len().to_string());
+                        row.push(t.description.clone());
+                    }
+                    row
+                })
+                .collect();
+            print!("{}", table(&header, &rows));
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Left-aligned columns two spaces apart, without trailing whitespace
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    std::iter::once(&header).chain(rows)
+        .map(|row| {
+            let line: Vec<String> = row.iter().zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            format!("{}\n", line.join("  ").trim_end())
+        })
+        .collect()
+}
+
+/// `print_manifest`'s output
+#[derive(Serialize)]
+struct ManifestOutput<'a> {
+    asset: &'a serde_json::value::RawValue,
+    /// Only under `--fail-on-warning`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed_due_to_warnings: Option<bool>,
+    success: bool,
+}
+
+/// The old compile output: the whole canonical manifest, base64 exports included
+fn print_manifest(asset: &CompiledAsset, fail_on_warning: bool) -> ExitCode {
+    // The canonical manifest is already built; don't serialize the exports again
+    let gate = WarningGate::new(fail_on_warning, &asset.validation);
+    match asset.canonical_json() {
+        Ok(manifest) => {
+            let output = ManifestOutput {
+                asset: serde_json::from_str(&manifest).expect("canonical JSON parses"),
+                failed_due_to_warnings: gate.enabled.then_some(gate.failed),
+                success: !gate.failed,
+            };
+            println!("{}", serde_json::to_string(&output).unwrap());
+            if gate.enabled { gate.exit_code() } else { ExitCode::SUCCESS }
+        }
+        Err(e) => {
+            let e: PipelineError = e.into();
+            ErrorEnvelope::from(&e).emit()
+        }
+    }
+}
+
+/// `--fail-on-warning` applied to a result: it fails one that is valid
+/// but still reports warnings, or errors a `warn` or `log` template let
+/// through, and leaves the result itself alone
+struct WarningGate {
+    enabled: bool,
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    failed: bool,
+}
+
+impl WarningGate {
+    fn new(enabled: bool, result: &ValidationResult) -> Self {
+        let count = |severity| result.violations.iter().filter(|v| v.severity == severity).count();
+        let (errors, warnings) = (count(ViolationSeverity::Error), count(ViolationSeverity::Warning));
+        let failed = enabled && result.valid && errors + warnings > 0;
+        Self { enabled, valid: result.valid, errors, warnings, failed }
+    }
+
+    /// Print `output` as JSON, with `failed_due_to_warnings` when the flag
+    /// is set, and `success` false when it failed the result
+    fn print_json(&self, output: &impl Serialize) {
+        if !self.enabled {
+            println!("{}", serde_json::to_string_pretty(output).unwrap());
+            return;
+        }
+        let mut json = serde_json::to_value(output).unwrap();
+        json["failed_due_to_warnings"] = self.failed.into();
+        if self.failed && json.get("success").is_some() {
+            json["success"] = false.into();
+        }
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+    }
+
+    /// The `--human` line saying why a valid result exits 9
+    fn report_line(&self) -> String {
+        if !self.failed {
+            return String::new();
+        }
+        let counts: Vec<_> = [(self.errors, "error"), (self.warnings, "warning")].into_iter()
+            .filter(|&(n, _)| n > 0)
+            .map(|(n, noun)| count(n, noun))
+            .collect();
+        format!("failed on {} (--fail-on-warning)\n", counts.join(" and "))
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        match (self.valid, self.failed) {
+            (false, _) => ExitCode::from(exit_code::VALIDATION_FAILED),
+            (true, true) => ExitCode::from(exit_code::FAILED_ON_WARNINGS),
+            (true, false) => ExitCode::SUCCESS,
+        }
+    }
+}
+
+/// Printing of the library's `ErrorEnvelope`, which every failing command
+/// reports instead of its normal output
+trait Emit {
+    fn emit(&self) -> ExitCode;
+}
+
+impl Emit for ErrorEnvelope {
+    /// JSON on stdout for the bridge, the message on stderr for whoever is watching
+    fn emit(&self) -> ExitCode {
+        println!("{}", serde_json::to_string(self).unwrap());
+        eprintln!("forgeimages-cli: {}: {}", self.kind, self.message);
+        ExitCode::from(self.exit_code())
+    }
+}
+
+/// An `--output-dir` this run may write into, and what to remove if it fails
+struct OutputDir {
+    path: PathBuf,
+    /// Created by this run, so removed whole on failure
+    created: bool,
+    /// Entries present before the run; `--force` leaves these alone on failure
+    existing: HashSet<OsString>,
+}
+
+impl OutputDir {
+    fn claim(path: &Path, force: bool) -> Result<Self, String> {
+        let created = !path.exists();
+        let existing: HashSet<OsString> = if created {
+            HashSet::new()
+        } else {
+            fs::read_dir(path)
+                .and_then(|entries| entries.map(|e| e.map(|e| e.file_name())).collect())
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+        };
+        if !existing.is_empty() && !force {
+            return Err(format!("{} is not empty; pass --force to write into it", path.display()));
+        }
+        Ok(Self { path: path.to_path_buf(), created, existing })
+    }
+
+    /// Remove partial output, best effort: the compile error is what gets reported
+    fn discard(self) {
+        if self.created {
+            let _ = fs::remove_dir_all(&self.path);
+            return;
+        }
+        let Ok(entries) = fs::read_dir(&self.path) else { return };
+        for entry in entries.flatten() {
+            if !self.existing.contains(&entry.file_name()) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+enum Input {
+    Payload(String),
+    File(SourceFile),
+}
+
+/// A `--file`, sniffed into the input a payload would have carried
+struct SourceFile {
+    input: AssetInput,
+    data: Vec<u8>,
+}
+
+impl SourceFile {
+    /// The request a payload embedding this file would carry
+    fn request(self, template_id: &str) -> CompileRequest {
+        CompileRequest {
+            template_id: template_id.to_string(),
+            asset_input: self.input,
+            source_data: Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &self.data)),
+            source_path: None,
+            seed: None,
+            prompt: None,
+            params: Default::default(),
+            print_spec: None,
+        }
+    }
+}
+
+struct InputError {
+    message: String,
+    file: Option<PathBuf>,
+    exit_code: u8,
+}
+
+impl InputError {
+    fn file(path: &Path, message: impl std::fmt::Display, exit_code: u8) -> Self {
+        Self { message: format!("{}: {}", path.display(), message), file: Some(path.to_path_buf()), exit_code }
+    }
+
+    fn emit(self) -> ExitCode {
+        let envelope = match self.file {
+            Some(file) => ErrorEnvelope::new(ErrorKind::InvalidFile, self.message).detail("file", file),
+            None => ErrorEnvelope::new(ErrorKind::Usage, self.message),
+        };
+        envelope.with_exit_code(self.exit_code).emit()
+    }
+}
+
+/// Exactly one of `--payload` and `--file`
+fn read_input(payload: Option<String>, file: Option<PathBuf>) -> Result<Input, InputError> {
+    let usage = |message: &str| InputError { message: message.to_string(), file: None, exit_code: exit_code::OTHER };
+    match (payload, file) {
+        (Some(payload), None) => Ok(Input::Payload(payload)),
+        (None, Some(path)) => {
+            let data = fs::read(&path).map_err(|e| InputError::file(&path, e, exit_code::IO_ERROR))?;
+            let sniffed = sniff(&data).map_err(|e| InputError::file(&path, e, exit_code::INVALID_PAYLOAD))?;
+            Ok(Input::File(SourceFile { input: sniffed.asset_input(), data }))
+        }
+        (Some(_), Some(_)) => Err(usage("--payload and --file are mutually exclusive")),
+        (None, None) => Err(usage("one of --payload or --file is required")),
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not run: missing input, or an earlier check failed
+    Skip,
+}
+
+#[derive(Serialize)]
+struct Check {
+    check: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+impl Check {
+    fn new(check: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { check, status, detail: detail.into() }
+    }
+
+    fn of<E: std::fmt::Display>(check: &'static str, result: Result<String, E>) -> Self {
+        match result {
+            Ok(detail) => Self::new(check, CheckStatus::Pass, detail),
+            Err(e) => Self::new(check, CheckStatus::Fail, e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "signing")]
+use forgeimages_core::{
+    output::SIGNATURE_FILE,
+    signing::{self, KeyError, SigningKey, VerifyingKey},
+    verify::VerifyError,
+};
+
+#[cfg(feature = "signing")]
+type PublicKey = VerifyingKey;
+
+#[cfg(not(feature = "signing"))]
+type PublicKey = std::convert::Infallible;
+
+/// `verify --public-key`, read like `verify-signature`'s
+#[cfg(feature = "signing")]
+fn read_public_key(path: &Path) -> Result<PublicKey, ExitCode> {
+    read_key(path, VerifyingKey::from_public_key_pem)
+}
+
+#[cfg(not(feature = "signing"))]
+fn read_public_key(_path: &Path) -> Result<PublicKey, ExitCode> {
+    Err(ErrorEnvelope::new(ErrorKind::InvalidKey, "--public-key needs a build with the signing feature").emit())
+}
+
+/// `manifest.verify`'s `public_key`: the daemon answers with an error
+/// rather than printing an envelope
+#[cfg(feature = "signing")]
+fn public_key_file(path: &Path) -> Result<PublicKey, String> {
+    let pem = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    VerifyingKey::from_public_key_pem(&pem).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(not(feature = "signing"))]
+fn public_key_file(_path: &Path) -> Result<PublicKey, String> {
+    Err("public_key needs a build with the signing feature".to_string())
+}
+
+/// Manifest hash, export files, job hash and signature, in that order.
+///
+/// Once the manifest fails nothing it records can be trusted, so the
+/// remaining checks are skipped rather than run against it.
+fn verify(
+    manifest_path: &Path,
+    dir: Option<&Path>,
+    request: Option<&CompileRequest>,
+    public_key: Option<PublicKey>,
+    pipeline: &CompilationPipeline,
+) -> Vec<Check> {
+    let manifest = Check::of("manifest_hash", verify::verify_manifest(manifest_path));
+    if manifest.status == CheckStatus::Fail {
+        let skipped = ["export_hashes", "job_hash", "signature"]
+            .map(|check| Check::new(check, CheckStatus::Skip, "manifest_hash failed"));
+        return std::iter::once(manifest).chain(skipped).collect();
+    }
+    let asset: CompiledAsset = match fs::read(manifest_path).map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_slice(&content).map_err(|e| e.to_string()))
+    {
+        Ok(asset) => asset,
+        Err(e) => return vec![manifest, Check::new("manifest", CheckStatus::Fail, e)],
+    };
+
+    let exports = match dir {
+        Some(dir) => match verify::verify_directory(dir) {
+            Ok(report) if report.is_ok() => Check::new("export_hashes", CheckStatus::Pass, format!("{} exports", asset.exports.len())),
+            Ok(report) => {
+                let mut problems: Vec<String> = report.mismatches.iter()
+                    .map(|m| format!("{} does not match {:?}", m.filename, m.source))
+                    .collect();
+                problems.extend(report.missing.iter().map(|name| format!("{} is missing", name)));
+                problems.extend(report.extra.iter().map(|name| format!("{} is not listed", name)));
+                Check::new("export_hashes", CheckStatus::Fail, problems.join("; "))
+            }
+            Err(e) => Check::new("export_hashes", CheckStatus::Fail, e.to_string()),
+        },
+        None => Check::new("export_hashes", CheckStatus::Skip, "pass --dir to check export files"),
+    };
+
+    // The digest's algorithm was checked with the manifest; its key fields must agree
+    let job_hash = match (asset.job_hash_keyed, &asset.job_hash_key_id, request) {
+        (true, None, _) => Check::new("job_hash", CheckStatus::Fail, "keyed job hash without job_hash_key_id"),
+        (false, Some(_), _) => Check::new("job_hash", CheckStatus::Fail, "job_hash_key_id on an unkeyed job hash"),
+        (_, _, Some(request)) => Check::of(
+            "job_hash",
+            verify::verify_job_hash(&asset, request, pipeline).map(|()| "recomputed from --request".to_string()),
+        ),
+        (_, _, None) => Check::new("job_hash", CheckStatus::Skip, "pass --request to recompute it"),
+    };
+
+    let signature = match (public_key, &asset.signer) {
+        (Some(key), _) => verify_signature(manifest_path, key),
+        (None, Some(signer)) => Check::new("signature", CheckStatus::Skip, format!("signed by {}; pass --public-key to check it", signer.key_id)),
+        (None, None) => Check::new("signature", CheckStatus::Skip, "manifest is not signed"),
+    };
+    vec![manifest, exports, job_hash, signature]
+}
+
+#[cfg(feature = "signing")]
+fn verify_signature(manifest_path: &Path, key: PublicKey) -> Check {
+    Check::of("signature", verify::verify_signature(manifest_path, &key).map(|()| format!("key {}", key.key_id())))
+}
+
+#[cfg(not(feature = "signing"))]
+fn verify_signature(_manifest_path: &Path, key: PublicKey) -> Check {
+    match key {}
+}
+
+/// A payload argument: `-` for stdin, `@path` for a file, else inline JSON.
+///
+/// JSON never starts with `@` and is never just `-`, so neither form is
+/// ambiguous. Only one argument per invocation can take stdin; no command
+/// accepts two payloads.
+fn payload_text(arg: &str) -> Result<Cow<'_, str>, String> {
+    let text = if arg == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map_err(|e| format!("stdin: {}", e))?;
+        if text.trim().is_empty() {
+            return Err("stdin is empty; pipe the JSON payload in".to_string());
+        }
+        text
+    } else if let Some(path) = arg.strip_prefix('@') {
+        fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?
+    } else {
+        return Ok(Cow::Borrowed(arg));
+    };
+    Ok(Cow::Owned(text))
+}
+
+/// A binary payload argument: `-` for stdin or `@path` for a file
+fn payload_bytes(arg: &str) -> Result<Vec<u8>, String> {
+    if arg == "-" {
+        let mut bytes = vec![];
+        std::io::stdin().read_to_end(&mut bytes).map_err(|e| format!("stdin: {}", e))?;
+        if bytes.is_empty() {
+            return Err("stdin is empty; pipe the MessagePack payload in".to_string());
+        }
+        Ok(bytes)
+    } else if let Some(path) = arg.strip_prefix('@') {
+        fs::read(path).map_err(|e| format!("{}: {}", path, e))
+    } else {
+        Err("a MessagePack payload must come from stdin (-) or a file (@path)".to_string())
+    }
+}
+
+/// Parse a `--payload`, rejecting duplicate keys
+fn parse_payload<T: DeserializeOwned>(payload: &str, format: PayloadFormat) -> Result<T, String> {
+    let value = match format {
+        PayloadFormat::Json => parse_strict(&payload_text(payload)?).map_err(|e| e.to_string())?,
+        PayloadFormat::Msgpack => parse_msgpack(&payload_bytes(payload)?)?,
+    };
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "msgpack")]
+fn parse_msgpack(bytes: &[u8]) -> Result<serde_json::Value, String> {
+    forgeimages_core::msgpack::parse_strict_msgpack(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn parse_msgpack(_bytes: &[u8]) -> Result<serde_json::Value, String> {
+    Err("--payload-format msgpack needs a build with the msgpack feature".to_string())
 }
@@ -0,0 +1,411 @@
+//! Bleed and Marks - Print Layout Around the Trim Box
+//!
+//! A print export's `ExportSpec.size` is its trim size. Bleed and marks grow
+//! the file around it; pixel math per export, from the resolved print spec:
+//! - bleed: twice the bleed length in pixels (`Length::to_pixels`) extra per
+//!   axis, the left/top side taking the smaller half when the total is odd
+//! - marks: a slug of `round(MARK_SLUG_INCHES * dpi)` pixels per side outside
+//!   the bleed, white paper carrying the block's `Marks` (crop marks on the
+//!   trim lines, registration crosses, color bars) in the band from
+//!   `markOffset` to `markOffset + markLength` out from the trim
+//!
+//! The artwork is drawn at trim size and extended into the bleed by
+//! mirroring its edges or by a solid fill color, per the print block.
+//!
+//! Guide proofs (`ExportSpec.guides`) outline the bleed edge, the trim and
+//! the safe area (`safeMargin` inside the trim) over the finished layout.
+
+use serde::{Deserialize, Serialize};
+
+use crate::background::parse_hex_color;
+use crate::print::{BleedFill, Marks, PrintSpec, TemplatePrint};
+use crate::raster::Raster;
+use crate::units::Length;
+
+/// Width of the slug that carries marks, per side
+pub const MARK_SLUG_INCHES: f64 = 0.25;
+
+pub(crate) const PAPER: [u8; 4] = [255, 255, 255, 255];
+/// Registration black
+pub(crate) const MARK: [u8; 4] = [0, 0, 0, 255];
+
+/// Color bar patches, left to right: solid process inks, their overprints,
+/// then a 50% gray
+pub const COLOR_BARS: [[u8; 4]; 8] = [
+    [0, 255, 255, 255],
+    [255, 0, 255, 255],
+    [255, 255, 0, 255],
+    [0, 0, 0, 255],
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [128, 128, 128, 255],
+];
+
+/// Guide outline colors: bleed edge, trim, safe area
+pub const GUIDE_BLEED: [u8; 4] = [0, 174, 239, 255];
+pub const GUIDE_TRIM: [u8; 4] = [236, 0, 140, 255];
+pub const GUIDE_SAFE: [u8; 4] = [0, 166, 81, 255];
+
+/// Where the trim box sits in an export file (recorded in the manifest)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TrimBox {
+    pub size: [u32; 2],
+    /// Top-left corner of the trim box within the file
+    pub offset: [u32; 2],
+}
+
+/// Pixel layout of one print export around its trim box
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrintLayout {
+    trim: [u32; 2],
+    /// Bleed left/top and right/bottom, per axis
+    bleed_before: [u32; 2],
+    bleed_after: [u32; 2],
+    slug: u32,
+    fill: Fill,
+    marks: Marks,
+    /// `marks.markOffset` and `marks.markLength` in pixels
+    mark_offset: u32,
+    mark_length: u32,
+    /// Mark line width
+    line: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Fill {
+    Mirror,
+    Color([u8; 4]),
+}
+
+impl PrintLayout {
+    /// Layout for an export of trim size `trim`; `None` when there is
+    /// neither bleed nor marks. `block` is the print block in effect
+    /// (the export's own, else the template's); its marks must fit the
+    /// resolved bleed plus the slug.
+    pub fn new(trim: [u32; 2], print: &PrintSpec, block: Option<&TemplatePrint>) -> Result<Option<Self>, String> {
+        let bleed = Length::from_micrometres(2 * print.bleed.micrometres()).to_pixels(print.dpi);
+        let marks = block.map_or_else(Marks::default, |b| b.marks);
+        marks.check_fits(print.bleed)?;
+        if bleed == 0 && marks.is_none() {
+            return Ok(None);
+        }
+        let fill = match block.map(|b| &b.bleed_fill) {
+            None | Some(BleedFill::Mirror) => Fill::Mirror,
+            Some(BleedFill::Color { color }) => Fill::Color(parse_hex_color(color)?),
+        };
+        Ok(Some(Self {
+            trim,
+            bleed_before: [bleed / 2; 2],
+            bleed_after: [bleed - bleed / 2; 2],
+            slug: if marks.any() { (MARK_SLUG_INCHES * print.dpi as f64).round() as u32 } else { 0 },
+            fill,
+            marks,
+            mark_offset: marks.mark_offset.to_pixels(print.dpi),
+            mark_length: marks.mark_length.to_pixels(print.dpi),
+            line: mark_line_width(print.dpi),
+        }))
+    }
+
+    /// The marks drawn, for the manifest; `None` without any
+    pub fn marks(&self) -> Option<Marks> {
+        self.marks.any().then_some(self.marks)
+    }
+
+    /// Full file size: trim, bleed and slug
+    pub fn size(&self) -> [u32; 2] {
+        [0, 1].map(|axis| self.trim[axis] + self.bleed_before[axis] + self.bleed_after[axis] + 2 * self.slug)
+    }
+
+    pub fn trim_box(&self) -> TrimBox {
+        TrimBox {
+            size: self.trim,
+            offset: [0, 1].map(|axis| self.slug + self.bleed_before[axis]),
+        }
+    }
+
+    /// Trim plus bleed, in the same form as `trim_box`
+    pub fn bleed_box(&self) -> TrimBox {
+        TrimBox {
+            size: [0, 1].map(|axis| self.trim[axis] + self.bleed_before[axis] + self.bleed_after[axis]),
+            offset: [self.slug; 2],
+        }
+    }
+
+    /// Place trim-size artwork in the full layout
+    pub fn compose(&self, art: &Raster) -> Raster {
+        assert_eq!([art.width, art.height], self.trim, "artwork is not at trim size");
+        let [width, height] = self.size();
+        let mut out = Raster::filled(width, height, PAPER);
+        let offset = self.trim_box().offset;
+        let bleed_end = [0, 1].map(|axis| offset[axis] + self.trim[axis] + self.bleed_after[axis]);
+
+        for y in self.slug..bleed_end[1] {
+            for x in self.slug..bleed_end[0] {
+                let u = x as i64 - offset[0] as i64;
+                let v = y as i64 - offset[1] as i64;
+                let inside = (0..art.width as i64).contains(&u) && (0..art.height as i64).contains(&v);
+                let pixel = match self.fill {
+                    _ if inside => art.get(u as u32, v as u32),
+                    Fill::Mirror => art.get(mirror(u, art.width), mirror(v, art.height)),
+                    Fill::Color(color) => color,
+                };
+                out.set(x, y, pixel);
+            }
+        }
+        if self.marks.any() {
+            self.draw_marks(&mut out);
+        }
+        out
+    }
+
+    fn draw_marks(&self, out: &mut Raster) {
+        let start = self.trim_box().offset;
+        let end = [0, 1].map(|axis| start[axis] + self.trim[axis]);
+        let (near, length, line) = (self.mark_offset, self.mark_length, self.line);
+        // The band before a trim edge at `edge` (first pixel, depth), clamped to the file
+        let before = |edge: u32| {
+            let first = edge.saturating_sub(near + length);
+            (first, edge.saturating_sub(near) - first)
+        };
+
+        if self.marks.crop {
+            // Each trim line continued across the band at both of its ends
+            let (top, depth) = before(start[1]);
+            for x in [start[0], end[0] - line] {
+                fill_rect(out, x, top, line, depth);
+                fill_rect(out, x, end[1] + near, line, length);
+            }
+            let (left, depth) = before(start[0]);
+            for y in [start[1], end[1] - line] {
+                fill_rect(out, left, y, depth, line);
+                fill_rect(out, end[0] + near, y, length, line);
+            }
+        }
+
+        let arm = length / 2;
+        let mid = [0, 1].map(|axis| start[axis] + self.trim[axis] / 2);
+        if self.marks.registration {
+            // Crosses centered in the band at each side's midpoint
+            let reach = near + arm;
+            let centers = [
+                (mid[0], start[1].saturating_sub(reach + 1)),
+                (mid[0], end[1] + reach),
+                (start[0].saturating_sub(reach + 1), mid[1]),
+                (end[0] + reach, mid[1]),
+            ];
+            for (cx, cy) in centers {
+                fill_rect(out, cx.saturating_sub(arm), cy.saturating_sub(line / 2), 2 * arm + 1, line);
+                fill_rect(out, cx.saturating_sub(line / 2), cy.saturating_sub(arm), line, 2 * arm + 1);
+            }
+        }
+
+        if self.marks.color_bars {
+            // Along the top band from one mark length past the left trim,
+            // stopping short of the registration cross or the right crop mark
+            let limit = if self.marks.registration { mid[0].saturating_sub(arm + length) } else { end[0] - length };
+            let (top, depth) = before(start[1]);
+            let mut x = start[0] + length;
+            for color in COLOR_BARS {
+                if x + length > limit {
+                    break;
+                }
+                fill_rect_with(out, x, top, length, depth, color);
+                x += length;
+            }
+        }
+    }
+}
+
+/// Bleed, trim and safe-area boxes of a guide proof, in file pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guides {
+    pub bleed: TrimBox,
+    pub trim: TrimBox,
+    pub safe: TrimBox,
+    /// Outline width
+    pub line: u32,
+}
+
+impl Guides {
+    /// Guides for an export of trim size `trim` laid out as `layout` (the
+    /// whole file is the trim without one); the safe area is `safeMargin`
+    /// inside the trim, collapsing to its center when the margin meets
+    pub fn new(trim: [u32; 2], layout: Option<&PrintLayout>, print: &PrintSpec, block: Option<&TemplatePrint>) -> Self {
+        let whole = TrimBox { size: trim, offset: [0, 0] };
+        let (bleed, trim) = layout.map_or((whole, whole), |layout| (layout.bleed_box(), layout.trim_box()));
+        let margin = block.and_then(|b| b.safe_margin).map_or(0, |margin| margin.to_pixels(print.dpi));
+        let inset = [0, 1].map(|axis| margin.min(trim.size[axis] / 2));
+        let safe = TrimBox {
+            size: [0, 1].map(|axis| trim.size[axis] - 2 * inset[axis]),
+            offset: [0, 1].map(|axis| trim.offset[axis] + inset[axis]),
+        };
+        Self { bleed, trim, safe, line: mark_line_width(print.dpi) }
+    }
+
+    /// Outline each box just inside its edge, trim on top
+    pub fn draw(&self, out: &mut Raster) {
+        for (area, color) in [(self.bleed, GUIDE_BLEED), (self.safe, GUIDE_SAFE), (self.trim, GUIDE_TRIM)] {
+            let [x, y] = area.offset;
+            let [w, h] = area.size;
+            let line = self.line.min(w).min(h);
+            fill_rect_with(out, x, y, w, line, color);
+            fill_rect_with(out, x, (y + h).saturating_sub(line), w, line, color);
+            fill_rect_with(out, x, y, line, h, color);
+            fill_rect_with(out, (x + w).saturating_sub(line), y, line, h, color);
+        }
+    }
+}
+
+/// Reflect an out-of-range coordinate back into `0..len` (edge pixel repeated
+/// once, as a mirror would show it); bleed wider than the art clamps
+fn mirror(i: i64, len: u32) -> u32 {
+    let len = len as i64;
+    let reflected = if i < 0 { -i - 1 } else if i >= len { 2 * len - i - 1 } else { i };
+    reflected.clamp(0, len - 1) as u32
+}
+
+/// Mark line width: about 1/300 in, at least one pixel
+pub(crate) fn mark_line_width(dpi: u32) -> u32 {
+    ((dpi + 150) / 300).max(1)
+}
+
+pub(crate) fn fill_rect(out: &mut Raster, x: u32, y: u32, w: u32, h: u32) {
+    fill_rect_with(out, x, y, w, h, MARK);
+}
+
+fn fill_rect_with(out: &mut Raster, x: u32, y: u32, w: u32, h: u32, color: [u8; 4]) {
+    for yy in y..(y + h).min(out.height) {
+        for xx in x..(x + w).min(out.width) {
+            out.set(xx, yy, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::TransparencyPolicy;
+    use crate::print::ColorSpace;
+
+    fn block(bleed_fill: BleedFill, marks: Marks) -> TemplatePrint {
+        TemplatePrint {
+            dpi: 300,
+            color_space: ColorSpace::Rgb,
+            bleed: Length::ZERO,
+            allow_user_print_overrides: true,
+            locked_fields: vec![],
+            allowed_user_color_spaces: vec![],
+            bleed_fill,
+            marks,
+            safe_margin: None,
+            icc_profile: None,
+            true_grayscale_vectors: false,
+            spot_colors: vec![],
+            pdf_standard: None,
+            transparency_policy: TransparencyPolicy::Flatten,
+            profile: None,
+        }
+    }
+
+    fn spec(dpi: u32, bleed_inches: f64) -> PrintSpec {
+        PrintSpec { dpi, bleed: Length::inches(bleed_inches), ..PrintSpec::default() }
+    }
+
+    #[test]
+    fn test_business_card_pixel_math() {
+        // 3.5 x 2 in at 300 dpi, 0.125 in bleed per side
+        let layout = PrintLayout::new([1050, 600], &spec(300, 0.125), None).unwrap().unwrap();
+        assert_eq!(layout.size(), [1125, 675]);
+        // 37.5 px per side: the left/top side rounds down
+        assert_eq!(layout.trim_box(), TrimBox { size: [1050, 600], offset: [37, 37] });
+
+        let with_marks = PrintLayout::new([1050, 600], &spec(300, 0.125), Some(&block(BleedFill::Mirror, Marks::legacy())))
+            .unwrap()
+            .unwrap();
+        assert_eq!(with_marks.size(), [1275, 825]);
+        assert_eq!(with_marks.trim_box().offset, [112, 112]);
+
+        assert_eq!(PrintLayout::new([1050, 600], &spec(300, 0.0), None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_mirror_reflects_edges() {
+        let mut art = Raster::new(3, 1);
+        for x in 0..3 {
+            art.set(x, 0, [x as u8 + 1, 0, 0, 255]);
+        }
+        // 2 px of bleed per side at 72 dpi: round(2 * 2/72 * 72) = 4 total
+        let layout = PrintLayout::new([3, 1], &spec(72, 2.0 / 72.0), None).unwrap().unwrap();
+        let out = layout.compose(&art);
+        let row: Vec<u8> = (0..out.width).map(|x| out.get(x, 2)[0]).collect();
+        assert_eq!(row, vec![2, 1, 1, 2, 3, 3, 2]);
+        let column: Vec<u8> = (0..out.height).map(|y| out.get(0, y)[0]).collect();
+        assert_eq!(column, vec![2; 5]);
+    }
+
+    #[test]
+    fn test_color_fill_and_marks_stay_out_of_the_bleed() {
+        let art = Raster::filled(30, 30, [0, 0, 255, 255]);
+        let fill = BleedFill::Color { color: "#00FF00".to_string() };
+        let layout = PrintLayout::new([30, 30], &spec(72, 0.125), Some(&block(fill, Marks::legacy()))).unwrap().unwrap();
+        let out = layout.compose(&art);
+        let TrimBox { offset, .. } = layout.trim_box();
+        // slug round(0.25 * 72) = 18, bleed 9 per side
+        assert_eq!((layout.slug, offset), (18, [27, 27]));
+        assert_eq!(out.get(18, 18), [0, 255, 0, 255]);
+        assert_eq!(out.get(27, 27), [0, 0, 255, 255]);
+
+        // Crop mark on the left trim line, 9 to 18 px above the trim
+        assert_eq!(out.get(27, 9), MARK);
+        assert_eq!(out.get(27, 17), MARK);
+        assert_eq!(out.get(27, 8), PAPER);
+        // Registration cross centered in the band
+        assert_eq!(out.get(27 + 15, 13), MARK);
+        // Corners of the slug are paper
+        assert_eq!(out.get(0, 0), PAPER);
+        // Nothing inside the bleed box is a mark
+        for y in 18..out.height - 18 {
+            for x in 18..out.width - 18 {
+                assert_ne!(out.get(x, y), MARK);
+            }
+        }
+    }
+
+    #[test]
+    fn test_color_bars_follow_the_left_crop_mark() {
+        let art = Raster::filled(30, 30, [0, 0, 255, 255]);
+        let marks = Marks { crop: true, color_bars: true, ..Marks::default() };
+        let layout = PrintLayout::new([30, 30], &spec(72, 0.125), Some(&block(BleedFill::Mirror, marks))).unwrap().unwrap();
+        let out = layout.compose(&art);
+        // 9 px patches from one mark length past the trim, up to the last
+        // that clears the right crop mark: only cyan fits in 30 px
+        assert_eq!(out.get(36, 9), COLOR_BARS[0]);
+        assert_eq!(out.get(44, 17), COLOR_BARS[0]);
+        assert_eq!(out.get(45, 9), PAPER);
+        // Registration crosses were not asked for
+        assert_eq!(out.get(13, 27 + 15), PAPER);
+        assert_eq!(layout.marks(), Some(marks));
+    }
+
+    #[test]
+    fn test_marks_must_fit_outside_the_trim() {
+        let marks = Marks { crop: true, mark_offset: Length::inches(0.2), mark_length: Length::inches(0.1), ..Marks::default() };
+        let e = PrintLayout::new([30, 30], &spec(72, 0.0), Some(&block(BleedFill::Mirror, marks))).unwrap_err();
+        assert_eq!(
+            e,
+            "marks need 7.62mm outside the trim (markOffset 5.08mm + markLength 2.54mm) \
+             but bleed 0mm and slug 6.35mm leave 6.35mm: 1.27mm short",
+        );
+        // A bleed wide enough makes room
+        assert!(PrintLayout::new([30, 30], &spec(72, 0.05), Some(&block(BleedFill::Mirror, marks))).is_ok());
+    }
+
+    #[test]
+    fn test_fill_color_must_parse() {
+        let fill = BleedFill::Color { color: "green".to_string() };
+        assert!(PrintLayout::new([10, 10], &spec(300, 0.125), Some(&block(fill, Marks::default()))).is_err());
+    }
+}
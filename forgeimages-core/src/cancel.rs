@@ -0,0 +1,87 @@
+//! Cancellation - Stopping a Compile Cleanly
+//!
+//! A `CancelToken` is shared by a pipeline (`PipelineBuilder::cancel_token`)
+//! and whatever may stop it: a deadline, a signal handler. The pipeline
+//! checks it after validation and after each export renders; a render in
+//! progress runs to the end first, renderers cannot be interrupted. A
+//! cancelled compile fails with `PipelineError::Cancelled`, naming the
+//! export that was rendering, and returns no asset, so nothing is written.
+//!
+//! Cancelling is one atomic store, safe from a signal handler.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelReason {
+    /// The token's deadline passed
+    TimedOut,
+    /// Cancelled from outside, e.g. on Ctrl-C
+    Interrupted,
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CancelReason::TimedOut => "timed out",
+            CancelReason::Interrupted => "interrupted",
+        })
+    }
+}
+
+const NOT_CANCELLED: u8 = 0;
+
+fn encode(reason: CancelReason) -> u8 {
+    match reason {
+        CancelReason::TimedOut => 1,
+        CancelReason::Interrupted => 2,
+    }
+}
+
+/// Clones share one state; the first reason given sticks
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    reason: AtomicU8,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that times out `timeout` from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let deadline = Instant::now().checked_add(timeout);
+        Self { inner: Arc::new(Inner { reason: AtomicU8::new(NOT_CANCELLED), deadline }) }
+    }
+
+    pub fn cancel(&self, reason: CancelReason) {
+        let _ = self.inner.reason.compare_exchange(NOT_CANCELLED, encode(reason), Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Why the token was cancelled, if it was or its deadline has passed
+    pub fn reason(&self) -> Option<CancelReason> {
+        match self.inner.reason.load(Ordering::SeqCst) {
+            1 => Some(CancelReason::TimedOut),
+            2 => Some(CancelReason::Interrupted),
+            _ => self.inner.deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+                .then_some(CancelReason::TimedOut),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+}
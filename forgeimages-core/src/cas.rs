@@ -0,0 +1,224 @@
+//! Content-Addressable Store - Exports Stored Once by Digest
+//!
+//! Objects are addressed by their prefixed digest (`HashRef`). The
+//! filesystem store lays them out as `objects/ab/cdef...` (first two hex
+//! digits, then the rest), writes through a temporary file and a rename, and
+//! re-hashes every object it reads.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::hashing::{digests_equal, hex, parse_digest, verify_hex, HashAlgorithm, HashingError};
+use crate::pipeline::CompiledAsset;
+
+#[derive(Debug, Error)]
+pub enum CasError {
+    #[error("CAS I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("CAS hashing error: {0}")]
+    Hashing(#[from] HashingError),
+
+    /// Stored bytes no longer hash to their address
+    #[error("Corrupt object: {0}")]
+    Corrupt(HashRef),
+
+    #[error("Export {0} does not match its recorded hash")]
+    ExportMismatch(String),
+}
+
+/// Address of a stored object: a prefixed digest with lowercase hex
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HashRef(String);
+
+impl HashRef {
+    /// Parse a stored digest (legacy unprefixed digests are sha256)
+    pub fn parse(digest: &str) -> Result<Self, HashingError> {
+        let (algorithm, hex_part) = parse_digest(digest)?;
+        let bytes = hex::decode(hex_part)
+            .filter(|b| !b.is_empty())
+            .ok_or_else(|| HashingError::InvalidDigest(digest.to_string()))?;
+        Ok(Self::from_bytes(algorithm, &bytes))
+    }
+
+    fn from_bytes(algorithm: HashAlgorithm, bytes: &[u8]) -> Self {
+        let mut digest = format!("{}:", algorithm.prefix());
+        hex::encode_to(bytes, &mut digest);
+        Self(digest)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        parse_digest(&self.0).map(|(algorithm, _)| algorithm).unwrap_or_default()
+    }
+
+    fn hex(&self) -> &str {
+        self.0.split_once(':').map_or(&self.0, |(_, hex)| hex)
+    }
+}
+
+impl fmt::Display for HashRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for HashRef {
+    type Error = HashingError;
+
+    fn try_from(digest: String) -> Result<Self, Self::Error> {
+        Self::parse(&digest)
+    }
+}
+
+impl From<HashRef> for String {
+    fn from(hash_ref: HashRef) -> Self {
+        hash_ref.0
+    }
+}
+
+/// Storage addressed by content digest
+pub trait CasStore: Send + Sync {
+    /// Store bytes (a no-op when already present) and return their address
+    fn put(&self, bytes: &[u8]) -> Result<HashRef, CasError>;
+
+    /// Bytes at an address, re-hashed before they are returned
+    fn get(&self, hash_ref: &HashRef) -> Result<Option<Vec<u8>>, CasError>;
+
+    fn contains(&self, hash_ref: &HashRef) -> Result<bool, CasError>;
+
+    /// Delete every object not in `reachable`; returns how many were removed
+    fn retain(&self, reachable: &[HashRef]) -> Result<usize, CasError>;
+}
+
+/// `CasStore` under a directory
+#[derive(Debug, Clone)]
+pub struct FsCasStore {
+    root: PathBuf,
+    algorithm: HashAlgorithm,
+}
+
+impl FsCasStore {
+    /// Store rooted at `root` (objects live in `root/objects`), addressing by sha256
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), algorithm: HashAlgorithm::Sha256 }
+    }
+
+    /// Address new objects with another algorithm
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn objects(&self) -> PathBuf {
+        self.root.join("objects")
+    }
+
+    /// `objects/ab/cdef...` for an address
+    pub fn object_path(&self, hash_ref: &HashRef) -> PathBuf {
+        let (fan_out, rest) = hash_ref.hex().split_at(2);
+        self.objects().join(fan_out).join(rest)
+    }
+}
+
+impl CasStore for FsCasStore {
+    fn put(&self, bytes: &[u8]) -> Result<HashRef, CasError> {
+        let mut hasher = self.algorithm.hasher();
+        hasher.update(bytes);
+        let hash_ref = HashRef::from_bytes(self.algorithm, &hasher.finalize_bytes());
+        let path = self.object_path(&hash_ref);
+        if path.exists() {
+            tracing::debug!(object = %hash_ref, "cas hit: object already stored");
+            return Ok(hash_ref);
+        }
+
+        let dir = path.parent().expect("object paths have a fan-out directory");
+        fs::create_dir_all(dir)?;
+        // Concurrent writers of the same object each rename a complete file
+        let temp = dir.join(format!(".tmp-{}", Uuid::new_v4()));
+        let written = fs::File::create(&temp)
+            .and_then(|mut file| file.write_all(bytes).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written?;
+        Ok(hash_ref)
+    }
+
+    fn get(&self, hash_ref: &HashRef) -> Result<Option<Vec<u8>>, CasError> {
+        let bytes = match fs::read(self.object_path(hash_ref)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        verify_hex(hash_ref.as_str(), &bytes).map_err(|_| CasError::Corrupt(hash_ref.clone()))?;
+        Ok(Some(bytes))
+    }
+
+    fn contains(&self, hash_ref: &HashRef) -> Result<bool, CasError> {
+        Ok(self.object_path(hash_ref).try_exists()?)
+    }
+
+    fn retain(&self, reachable: &[HashRef]) -> Result<usize, CasError> {
+        let keep: HashSet<PathBuf> = reachable.iter().map(|r| self.object_path(r)).collect();
+        let fan_outs = match fs::read_dir(self.objects()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut removed = 0;
+        for fan_out in fan_outs {
+            let fan_out = fan_out?.path();
+            if !fan_out.is_dir() {
+                continue;
+            }
+            for object in fs::read_dir(&fan_out)? {
+                let object = object?.path();
+                // Temporary files may belong to a put in progress
+                let temporary = object.file_name().is_some_and(|n| n.to_string_lossy().starts_with(".tmp-"));
+                if !temporary && !keep.contains(&object) {
+                    fs::remove_file(&object)?;
+                    removed += 1;
+                }
+            }
+            if fs::read_dir(&fan_out)?.next().is_none() {
+                fs::remove_dir(&fan_out)?;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Store an asset's exports, checking each lands at its recorded hash
+/// (so the store must address by the asset's `hash_algorithm`).
+///
+/// Returns the addresses in export order.
+pub fn put_exports(store: &dyn CasStore, asset: &CompiledAsset) -> Result<Vec<HashRef>, CasError> {
+    asset.exports.iter()
+        .map(|export| {
+            let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &export.data_base64)
+                .map_err(|_| CasError::ExportMismatch(export.id.clone()))?;
+            let hash_ref = store.put(&data)?;
+            if !digests_equal(hash_ref.as_str(), &export.hash) {
+                return Err(CasError::ExportMismatch(export.id.clone()));
+            }
+            Ok(hash_ref)
+        })
+        .collect()
+}
@@ -0,0 +1,424 @@
+//! Color Conversion - RGB to CMYK and Grayscale for Print Exports
+//!
+//! Pdf/Jpg/Tiff exports whose resolved print spec is CMYK are converted by
+//! the pipeline's `CmykConverter`, and the method (plus the profile, for
+//! ICC-based converters) is recorded on the export in the manifest.
+//!
+//! Grayscale exports carry Rec. 709 luma, `Y' = 0.2126 R' + 0.7152 G' +
+//! 0.0722 B'` on the gamma-encoded 8-bit values, in integer math rounded
+//! half up. Pixel formats are encoded single-channel (plus alpha for PNG);
+//! SVG exports get the same coefficients as a baked `feColorMatrix` filter.
+//!
+//! A converter must be a pure function of the pixel: the same RGB value
+//! converts to the same CMYK value on every run and platform. Transparency
+//! is flattened onto white paper before conversion.
+//!
+//! `NaiveCmyk` is the default. With the `icc` feature, `IccCmyk` converts
+//! through a registered CMYK output profile; set it with
+//! `PipelineBuilder::cmyk_converter`.
+//!
+//! Soft-proof exports round-trip RGB through the converter and back with
+//! `CmykConverter::to_rgb`, whose default simulates process inks on white
+//! paper (`simulate_press`), so out-of-gamut colors visibly dull.
+//!
+//! Spot colors from the print block (`TemplatePrint.spot_colors`) take
+//! precedence: a pixel exactly equal to a mapped RGB value gets the mapped
+//! ink values, everything else goes through the converter. Each export
+//! records the mappings it applied and how many pixels matched.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::background::parse_hex_color;
+#[cfg(feature = "icc")]
+use crate::icc::{IccColorSpace, IccError, IccProfile};
+use crate::raster::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ConversionMethod {
+    /// Device conversion with no color management (`NaiveCmyk`)
+    Naive,
+    /// Through an ICC output profile
+    Icc,
+    /// Grayscale pixels from `luma_709`
+    #[serde(rename = "rec709_luma")]
+    Rec709Luma,
+    /// SVG wrapped in a Rec. 709 luma filter; the vectors keep their colors
+    #[serde(rename = "rec709_luma_filter")]
+    Rec709LumaFilter,
+}
+
+/// How an export's CMYK pixels were produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ColorConversion {
+    pub method: ConversionMethod,
+    /// Digest of the ICC profile; `None` for naive conversion
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// RGB to CMYK conversion handed to renderers for CMYK print exports
+pub trait CmykConverter: Send + Sync {
+    /// What to record in the manifest for exports this converter produced
+    fn conversion(&self) -> ColorConversion;
+
+    /// Convert one opaque 8-bit RGB pixel to 8-bit CMYK (0 = no ink)
+    fn convert(&self, rgb: [u8; 3]) -> [u8; 4];
+
+    /// Appearance of printed CMYK as RGB, for soft proofs
+    fn to_rgb(&self, cmyk: [u8; 4]) -> [u8; 3] {
+        simulate_press(cmyk)
+    }
+}
+
+/// sRGB appearance of solid cyan, magenta, yellow and black process inks
+/// on white paper (SWOP-like)
+pub const PROCESS_INKS: [[u8; 3]; 4] = [[0, 174, 239], [236, 0, 140], [255, 242, 0], [35, 31, 32]];
+
+/// Printed appearance of `cmyk`: each ink filters the paper in proportion
+/// to its coverage, and the filters multiply. Only multiplication and
+/// addition, so results are identical on every platform.
+pub fn simulate_press(cmyk: [u8; 4]) -> [u8; 3] {
+    [0, 1, 2].map(|channel| {
+        let light = cmyk.iter().zip(PROCESS_INKS).fold(1.0f64, |light, (&coverage, ink)| {
+            light * (1.0 - coverage as f64 / 255.0 * (1.0 - ink[channel] as f64 / 255.0))
+        });
+        (light * 255.0).round() as u8
+    })
+}
+
+/// `raster` as it would print through `converter`: flattened onto white,
+/// converted to CMYK and back with `CmykConverter::to_rgb`. Opaque.
+pub fn soft_proof(raster: &Raster, converter: &dyn CmykConverter) -> Raster {
+    let mut out = Raster::new(raster.width, raster.height);
+    for (rgb, px) in flatten_on_white(raster).chunks_exact(3).zip(out.pixels.chunks_exact_mut(4)) {
+        let [r, g, b] = converter.to_rgb(converter.convert([rgb[0], rgb[1], rgb[2]]));
+        px.copy_from_slice(&[r, g, b, 255]);
+    }
+    out
+}
+
+/// Brand color with fixed ink values, e.g. a Pantone equivalent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SpotColorMapping {
+    pub name: String,
+    /// `#RRGGBB` the artwork uses for this color
+    pub rgb_hex: String,
+    /// 8-bit C, M, Y, K (0 = no ink), as `CmykConverter::convert` produces
+    pub cmyk: [u8; 4],
+}
+
+/// A print block's spot colors, parsed and checked
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpotColors {
+    entries: Vec<SpotColor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct SpotColor {
+    rgb: [u8; 3],
+    cmyk: [u8; 4],
+    name: String,
+}
+
+impl SpotColors {
+    /// Names must be non-empty and distinct, colors opaque and distinct
+    pub fn new(mappings: &[SpotColorMapping]) -> Result<Self, String> {
+        let mut entries: Vec<SpotColor> = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            if mapping.name.trim().is_empty() {
+                return Err("spot color name is empty".to_string());
+            }
+            let [r, g, b, a] = parse_hex_color(&mapping.rgb_hex).map_err(|e| format!("{}: {}", mapping.name, e))?;
+            if a != 255 {
+                return Err(format!("{}: spot colors must be opaque", mapping.name));
+            }
+            if let Some(other) = entries.iter().find(|e| e.name == mapping.name || e.rgb == [r, g, b]) {
+                return Err(format!("{} duplicates spot color {}", mapping.name, other.name));
+            }
+            entries.push(SpotColor { rgb: [r, g, b], cmyk: mapping.cmyk, name: mapping.name.clone() });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, rgb: [u8; 3]) -> bool {
+        self.entries.iter().any(|e| e.rgb == rgb)
+    }
+}
+
+/// A spot color as applied to one export, recorded in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SpotColorUsage {
+    pub name: String,
+    pub rgb_hex: String,
+    pub cmyk: [u8; 4],
+    /// Pixels converted to the mapped ink values
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub pixels: u64,
+}
+
+/// Maps spot colors exactly and hands every other pixel to `base`,
+/// counting the pixels each spot color matched
+pub struct SpotCmyk<'a> {
+    base: &'a dyn CmykConverter,
+    spots: &'a SpotColors,
+    counts: Vec<AtomicU64>,
+}
+
+impl<'a> SpotCmyk<'a> {
+    pub fn new(base: &'a dyn CmykConverter, spots: &'a SpotColors) -> Self {
+        Self { base, spots, counts: spots.entries.iter().map(|_| AtomicU64::new(0)).collect() }
+    }
+
+    /// Spot colors that matched at least one pixel so far, in mapping order
+    pub fn usage(&self) -> Vec<SpotColorUsage> {
+        self.spots.entries.iter().zip(&self.counts)
+            .map(|(spot, count)| (spot, count.load(Ordering::Relaxed)))
+            .filter(|&(_, pixels)| pixels > 0)
+            .map(|(spot, pixels)| SpotColorUsage {
+                name: spot.name.clone(),
+                rgb_hex: format!("#{:02X}{:02X}{:02X}", spot.rgb[0], spot.rgb[1], spot.rgb[2]),
+                cmyk: spot.cmyk,
+                pixels,
+            })
+            .collect()
+    }
+}
+
+impl CmykConverter for SpotCmyk<'_> {
+    fn conversion(&self) -> ColorConversion {
+        self.base.conversion()
+    }
+
+    fn convert(&self, rgb: [u8; 3]) -> [u8; 4] {
+        match self.spots.entries.iter().position(|spot| spot.rgb == rgb) {
+            Some(i) => {
+                self.counts[i].fetch_add(1, Ordering::Relaxed);
+                self.spots.entries[i].cmyk
+            }
+            None => self.base.convert(rgb),
+        }
+    }
+
+    fn to_rgb(&self, cmyk: [u8; 4]) -> [u8; 3] {
+        self.base.to_rgb(cmyk)
+    }
+}
+
+/// Device conversion: K from the brightest channel, C/M/Y from what remains.
+/// Integer math, rounded half up.
+pub struct NaiveCmyk;
+
+impl CmykConverter for NaiveCmyk {
+    fn conversion(&self) -> ColorConversion {
+        ColorConversion { method: ConversionMethod::Naive, profile: None }
+    }
+
+    fn convert(&self, [r, g, b]: [u8; 3]) -> [u8; 4] {
+        let max = r.max(g).max(b) as u32;
+        if max == 0 {
+            return [0, 0, 0, 255];
+        }
+        let ink = |c: u8| ((max - c as u32) * 255 + max / 2) / max;
+        [ink(r) as u8, ink(g) as u8, ink(b) as u8, (255 - max) as u8]
+    }
+}
+
+/// Conversion through a CMYK output profile (the `icc` feature): sRGB in,
+/// perceptual intent, the profile's tables evaluated by moxcms. moxcms is
+/// built without its SIMD paths, so a profile converts the same on every
+/// platform.
+#[cfg(feature = "icc")]
+pub struct IccCmyk {
+    profile: String,
+    to_cmyk: std::sync::Arc<moxcms::Transform8BitExecutor>,
+    to_rgb: std::sync::Arc<moxcms::Transform8BitExecutor>,
+}
+
+#[cfg(feature = "icc")]
+impl IccCmyk {
+    /// Transforms between sRGB and `profile`, which must be a CMYK profile
+    /// with tables both ways
+    pub fn new(profile: &IccProfile) -> Result<Self, IccError> {
+        use moxcms::{ColorProfile, Layout, TransformOptions};
+
+        let invalid = |reason: String| IccError { name: profile.name().to_string(), reason };
+        if profile.color_space() != IccColorSpace::Cmyk {
+            return Err(invalid(format!("is {:?}, not a CMYK output profile", profile.color_space())));
+        }
+        let cmyk = ColorProfile::new_from_slice(profile.data()).map_err(|e| invalid(e.to_string()))?;
+        let srgb = ColorProfile::new_srgb();
+        let options = TransformOptions::default();
+        let to_cmyk = srgb.create_transform_8bit(Layout::Rgb, &cmyk, Layout::Rgba, options)
+            .map_err(|e| invalid(format!("no sRGB to CMYK transform: {}", e)))?;
+        let to_rgb = cmyk.create_transform_8bit(Layout::Rgba, &srgb, Layout::Rgb, options)
+            .map_err(|e| invalid(format!("no CMYK to sRGB transform: {}", e)))?;
+        Ok(Self { profile: profile.sha256().to_string(), to_cmyk, to_rgb })
+    }
+}
+
+#[cfg(feature = "icc")]
+impl CmykConverter for IccCmyk {
+    fn conversion(&self) -> ColorConversion {
+        ColorConversion { method: ConversionMethod::Icc, profile: Some(self.profile.clone()) }
+    }
+
+    fn convert(&self, rgb: [u8; 3]) -> [u8; 4] {
+        let mut cmyk = [0; 4];
+        self.to_cmyk.transform(&rgb, &mut cmyk).expect("one pixel in, one out");
+        cmyk
+    }
+
+    /// The profile's own rendering of the inks, rather than `simulate_press`
+    fn to_rgb(&self, cmyk: [u8; 4]) -> [u8; 3] {
+        let mut rgb = [0; 3];
+        self.to_rgb.transform(&cmyk, &mut rgb).expect("one pixel in, one out");
+        rgb
+    }
+}
+
+/// Opaque RGB8 pixels of `raster` composited over white paper
+pub fn flatten_on_white(raster: &Raster) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raster.pixels.len() / 4 * 3);
+    for px in raster.pixels.chunks_exact(4) {
+        let a = px[3] as u32;
+        for &c in &px[..3] {
+            out.push(((c as u32 * a + 255 * (255 - a) + 127) / 255) as u8);
+        }
+    }
+    out
+}
+
+/// Rec. 709 luma of one 8-bit RGB pixel
+pub fn luma_709([r, g, b]: [u8; 3]) -> u8 {
+    ((2126 * r as u32 + 7152 * g as u32 + 722 * b as u32 + 5000) / 10000) as u8
+}
+
+/// Luma pixels of `raster`, flattened onto white first
+pub fn to_gray(raster: &Raster) -> Vec<u8> {
+    flatten_on_white(raster)
+        .chunks_exact(3)
+        .map(|rgb| luma_709([rgb[0], rgb[1], rgb[2]]))
+        .collect()
+}
+
+/// Interleaved luma + alpha pixels of `raster` (alpha kept as is)
+pub fn to_gray_alpha(raster: &Raster) -> Vec<u8> {
+    raster.pixels
+        .chunks_exact(4)
+        .flat_map(|px| [luma_709([px[0], px[1], px[2]]), px[3]])
+        .collect()
+}
+
+/// Interleaved CMYK8 pixels of `raster`, flattened onto white first
+pub fn to_cmyk(raster: &Raster, converter: &dyn CmykConverter) -> Vec<u8> {
+    flatten_on_white(raster)
+        .chunks_exact(3)
+        .flat_map(|rgb| converter.convert([rgb[0], rgb[1], rgb[2]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naive_swatches() {
+        let swatches: [([u8; 3], [u8; 4]); 10] = [
+            ([255, 255, 255], [0, 0, 0, 0]),
+            ([0, 0, 0], [0, 0, 0, 255]),
+            ([255, 0, 0], [0, 255, 255, 0]),
+            ([0, 255, 0], [255, 0, 255, 0]),
+            ([0, 0, 255], [255, 255, 0, 0]),
+            ([0, 255, 255], [255, 0, 0, 0]),
+            ([128, 128, 128], [0, 0, 0, 127]),
+            ([0, 128, 255], [255, 127, 0, 0]),
+            // Mid-tones, checked against the floating-point formula
+            ([255, 107, 53], [0, 148, 202, 0]),
+            ([64, 32, 16], [0, 128, 191, 191]),
+        ];
+        for (rgb, expected) in swatches {
+            let cmyk = NaiveCmyk.convert(rgb);
+            for (got, want) in cmyk.iter().zip(expected) {
+                assert!(got.abs_diff(want) <= 1, "{:?} -> {:?}, expected {:?}", rgb, cmyk, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_luma_swatches() {
+        let swatches = [
+            ([255, 255, 255], 255),
+            ([0, 0, 0], 0),
+            ([255, 0, 0], 54),
+            ([0, 255, 0], 182),
+            ([0, 0, 255], 18),
+            ([255, 107, 53], 135),
+            ([128, 128, 128], 128),
+        ];
+        for (rgb, expected) in swatches {
+            assert_eq!(luma_709(rgb), expected, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn test_spot_colors_map_exactly_and_count() {
+        let mapping = |name: &str, rgb_hex: &str, cmyk| SpotColorMapping { name: name.into(), rgb_hex: rgb_hex.into(), cmyk };
+        let spots = SpotColors::new(&[mapping("Brand Red", "#e4002b", [0, 250, 207, 10])]).unwrap();
+        let converter = SpotCmyk::new(&NaiveCmyk, &spots);
+
+        let mut raster = Raster::filled(3, 1, [228, 0, 43, 255]);
+        raster.set(2, 0, [228, 0, 44, 255]);
+        let cmyk = to_cmyk(&raster, &converter);
+        assert_eq!(&cmyk[..8], &[0, 250, 207, 10, 0, 250, 207, 10]);
+        assert_eq!(&cmyk[8..], &NaiveCmyk.convert([228, 0, 44]));
+        assert_eq!(
+            converter.usage(),
+            vec![SpotColorUsage { name: "Brand Red".into(), rgb_hex: "#E4002B".into(), cmyk: [0, 250, 207, 10], pixels: 2 }],
+        );
+
+        for (mappings, expected) in [
+            (vec![mapping("A", "#E4002B", [0; 4]), mapping("B", "#e4002b", [0; 4])], "B duplicates spot color A"),
+            (vec![mapping(" ", "#E4002B", [0; 4])], "name is empty"),
+            (vec![mapping("A", "#E4002B80", [0; 4])], "opaque"),
+        ] {
+            let err = SpotColors::new(&mappings).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_press_simulation() {
+        assert_eq!(simulate_press([0, 0, 0, 0]), [255, 255, 255]);
+        assert_eq!(simulate_press([0, 0, 0, 255]), [35, 31, 32]);
+        assert_eq!(simulate_press([255, 0, 0, 0]), [0, 174, 239]);
+        // Saturated RGB primaries fall outside the press gamut
+        assert_eq!(simulate_press(NaiveCmyk.convert([255, 0, 0])), [236, 0, 0]);
+        assert_eq!(simulate_press(NaiveCmyk.convert([0, 255, 0])), [0, 165, 0]);
+    }
+
+    #[test]
+    fn test_transparency_flattens_to_paper() {
+        let mut raster = Raster::new(2, 1);
+        raster.set(1, 0, [0, 0, 0, 255]);
+        assert_eq!(flatten_on_white(&raster), vec![255, 255, 255, 0, 0, 0]);
+        assert_eq!(to_cmyk(&raster, &NaiveCmyk), vec![0, 0, 0, 0, 0, 0, 0, 255]);
+        assert_eq!(to_gray(&raster), vec![255, 0]);
+        assert_eq!(to_gray_alpha(&raster), vec![0, 0, 0, 255]);
+    }
+}
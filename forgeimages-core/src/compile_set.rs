@@ -0,0 +1,173 @@
+//! Compile Sets - One Source, Many Templates
+//!
+//! A logo usually feeds the icon, favicon and social-card templates at once.
+//! The source is decoded a single time and shared; each template still goes
+//! through the full validated compile, and the set manifest ties the
+//! resulting manifest hashes together under one `set_hash`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::compute_manifest_hash_with;
+use crate::pipeline::{CompilationPipeline, CompileRequest, CompiledAsset, PipelineError};
+use crate::print::PrintSpec;
+use crate::validation::AssetInput;
+use crate::ENGINE_VERSION;
+
+/// Source shared by every template in a set, decoded once
+#[derive(Debug, Clone)]
+pub struct SourceArtifact {
+    pub input: AssetInput,
+    /// Base64 form, kept so job hashes match single-template compiles
+    encoded: Option<String>,
+    data: Option<Vec<u8>>,
+}
+
+impl SourceArtifact {
+    /// Dimensions only, no source bytes
+    pub fn new(input: AssetInput) -> Self {
+        Self { input, encoded: None, data: None }
+    }
+
+    pub fn from_bytes(input: AssetInput, data: Vec<u8>) -> Self {
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data);
+        Self { input, encoded: Some(encoded), data: Some(data) }
+    }
+
+    pub fn from_base64(input: AssetInput, encoded: String) -> Result<Self, PipelineError> {
+        let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &encoded)
+            .map_err(|e| PipelineError::InvalidSource(format!("source_data is not valid base64: {}", e)))?;
+        Ok(Self { input, encoded: Some(encoded), data: Some(data) })
+    }
+
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
+}
+
+/// Request fields shared by every template in a set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompileRequestCommon {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub print_spec: Option<PrintSpec>,
+    /// Stop at the first failing template; remaining ones are skipped
+    #[serde(default)]
+    pub fail_fast: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetEntryStatus {
+    Compiled,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetEntry {
+    pub template_id: String,
+    pub status: SetEntryStatus,
+    pub manifest_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Set-level manifest; `set_hash` covers every entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetManifest {
+    pub set_hash: String,
+    pub engine_version: String,
+    pub entries: Vec<SetEntry>,
+}
+
+/// Outcome for one template of a set
+#[derive(Debug)]
+pub struct SetMember {
+    pub template_id: String,
+    pub result: Result<CompiledAsset, PipelineError>,
+}
+
+#[derive(Debug)]
+pub struct CompileSetResult {
+    /// Attempted templates, in request order (skipped ones are absent)
+    pub results: Vec<SetMember>,
+    pub manifest: SetManifest,
+}
+
+impl CompileSetResult {
+    pub fn all_succeeded(&self) -> bool {
+        self.manifest.entries.iter().all(|e| e.status == SetEntryStatus::Compiled)
+    }
+}
+
+impl CompilationPipeline {
+    /// Compile one source against several templates
+    pub fn compile_set(
+        &self,
+        source: SourceArtifact,
+        template_ids: &[&str],
+        shared: CompileRequestCommon,
+    ) -> Result<CompileSetResult, PipelineError> {
+        let mut results = vec![];
+        let mut entries = vec![];
+        let mut failed = false;
+
+        for template_id in template_ids {
+            if failed && shared.fail_fast {
+                entries.push(SetEntry {
+                    template_id: template_id.to_string(),
+                    status: SetEntryStatus::Skipped,
+                    manifest_hash: None,
+                    error: None,
+                });
+                continue;
+            }
+
+            let request = CompileRequest {
+                template_id: template_id.to_string(),
+                asset_input: source.input.clone(),
+                source_data: source.encoded.clone(),
+                source_path: None,
+                seed: shared.seed,
+                prompt: shared.prompt.clone(),
+                params: shared.params.clone(),
+                print_spec: shared.print_spec.clone(),
+            };
+            let result = self.compile_audited(&request, source.data(), None);
+
+            entries.push(match &result {
+                Ok(asset) => SetEntry {
+                    template_id: template_id.to_string(),
+                    status: SetEntryStatus::Compiled,
+                    manifest_hash: Some(asset.manifest_hash.clone()),
+                    error: None,
+                },
+                Err(e) => {
+                    failed = true;
+                    SetEntry {
+                        template_id: template_id.to_string(),
+                        status: SetEntryStatus::Failed,
+                        manifest_hash: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            });
+            results.push(SetMember { template_id: template_id.to_string(), result });
+        }
+
+        let mut manifest = SetManifest {
+            set_hash: String::new(),  // Computed after
+            engine_version: ENGINE_VERSION.to_string(),
+            entries,
+        };
+        manifest.set_hash = compute_manifest_hash_with(self.hash_algorithm(), &manifest)?;
+
+        Ok(CompileSetResult { results, manifest })
+    }
+}
@@ -0,0 +1,296 @@
+//! Pipeline Config - Builder Settings as a File
+//!
+//! A `PipelineConfig` holds the settings a `PipelineBuilder` takes as plain
+//! data, so a deployment can keep them in a JSON or TOML file
+//! (`PipelineConfig::load`, chosen by extension). Every field has a
+//! default, an empty file is the default pipeline, and unknown fields are
+//! errors. `validate` rejects what would build a broken pipeline;
+//! `builder` validates, reads the files the config names (audit log, ICC
+//! profiles, job hash key, signing key) and returns the builder, which can
+//! still be adjusted before `build`.
+//!
+//! `CompilationPipeline::config` reports the effective config back, with
+//! inline secrets replaced by `REDACTED`. Its `hash` is recorded in every
+//! manifest as `config_hash`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::audit::FileAuditSink;
+use crate::encoding::EncodingProfile;
+use crate::hashing::{canonical_json, HashAlgorithm, HashingError, JobHashKey};
+use crate::icc::IccProfileStore;
+use crate::pipeline::{CompilationPipeline, PipelineBuilder, SandboxMode};
+use crate::render::{PlaceholderRenderer, Renderer, RetryPolicy};
+use crate::templates::TemplateRegistry;
+
+/// Renderers a config may name, by `Renderer::name`
+pub const RENDERERS: &[&str] = &["placeholder"];
+
+/// What `CompilationPipeline::config` shows instead of an inline secret
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Parse(String),
+
+    #[error("unrecognized extension; use .json or .toml")]
+    Format,
+
+    /// A setting that would build a broken pipeline, by its path in the file
+    #[error("{field}: {message}")]
+    Invalid { field: String, message: String },
+}
+
+impl ConfigError {
+    fn invalid(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Invalid { field: field.into(), message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    /// One of `RENDERERS`
+    pub renderer: String,
+    pub limits: Limits,
+    pub encoding: EncodingProfile,
+    pub hash_algorithm: HashAlgorithm,
+    pub normalize_source: bool,
+    pub fail_on_warnings: bool,
+    pub deduplicate_exports: bool,
+    pub emit_checksums: bool,
+    pub sandbox: SandboxMode,
+    /// Allow `source_path` requests under this directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_root: Option<PathBuf>,
+    /// Append-only audit log (see `audit`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<PathBuf>,
+    /// ICC profile files print specs may name, by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub icc_profiles: BTreeMap<String, PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_hash_key: Option<JobHashKeyConfig>,
+    pub signing: SigningSettings,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            renderer: PlaceholderRenderer.name().to_string(),
+            limits: Limits::default(),
+            encoding: EncodingProfile::default(),
+            hash_algorithm: HashAlgorithm::default(),
+            normalize_source: false,
+            fail_on_warnings: false,
+            deduplicate_exports: false,
+            emit_checksums: false,
+            sandbox: SandboxMode::Off,
+            source_root: None,
+            audit_log: None,
+            icc_profiles: BTreeMap::new(),
+            job_hash_key: None,
+            signing: SigningSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Rendered exports hashed and encoded at once
+    pub export_parallelism: usize,
+    /// Render attempts per export, the first included
+    pub render_attempts: u32,
+    /// Delay before the first render retry, in milliseconds
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { export_parallelism: 1, render_attempts: 1, retry_backoff_ms: 0 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobHashKeyConfig {
+    pub key_id: String,
+    pub secret: Secret,
+}
+
+/// Secret bytes, given inline or as a file whose bytes are used as is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Secret {
+    Value(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SigningSettings {
+    /// Sign manifests written by `compile_to_dir` (the `signing` feature)
+    pub enabled: bool,
+    /// PKCS#8 PEM private key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<PathBuf>,
+    /// Id recorded in signed manifests; defaults to the key's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+}
+
+impl PipelineConfig {
+    /// Read a `.json` or `.toml` config, resolving relative paths in it
+    /// against the file's directory
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)?;
+        let mut config = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&text)?,
+            Some("toml") => Self::from_toml(&text)?,
+            _ => return Err(ConfigError::Format),
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            config.resolve_paths(dir);
+        }
+        Ok(config)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|e| {
+            let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+            ConfigError::Parse(format!("line {}: {}", line, e.message()))
+        })
+    }
+
+    fn resolve_paths(&mut self, dir: &Path) {
+        let paths = self.source_root.iter_mut()
+            .chain(&mut self.audit_log)
+            .chain(self.icc_profiles.values_mut())
+            .chain(self.job_hash_key.as_mut().and_then(|key| match &mut key.secret {
+                Secret::File(path) => Some(path),
+                Secret::Value(_) => None,
+            }))
+            .chain(&mut self.signing.key_path);
+        for path in paths.filter(|p| p.is_relative()) {
+            *path = dir.join(&*path);
+        }
+    }
+
+    /// The first setting that would build a broken pipeline
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !RENDERERS.contains(&self.renderer.as_str()) {
+            return Err(ConfigError::invalid(
+                "renderer",
+                format!("unknown renderer {:?} (known: {})", self.renderer, RENDERERS.join(", ")),
+            ));
+        }
+        if self.limits.export_parallelism == 0 {
+            return Err(ConfigError::invalid("limits.export_parallelism", "must be at least 1"));
+        }
+        if self.limits.render_attempts == 0 {
+            return Err(ConfigError::invalid("limits.render_attempts", "must be at least 1"));
+        }
+        if let Some(key) = &self.job_hash_key {
+            if key.key_id.is_empty() {
+                return Err(ConfigError::invalid("job_hash_key.key_id", "must not be empty"));
+            }
+            if key.secret == Secret::Value(String::new()) {
+                return Err(ConfigError::invalid("job_hash_key.secret", "must not be empty"));
+            }
+        }
+        if self.signing.enabled {
+            if !cfg!(feature = "signing") {
+                return Err(ConfigError::invalid("signing.enabled", "this build has no signing support (the signing feature)"));
+            }
+            if self.signing.key_path.is_none() {
+                return Err(ConfigError::invalid("signing.key_path", "signing is enabled but no key path is set"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate, read the files this config names, and configure a builder
+    pub fn builder(&self, registry: TemplateRegistry) -> Result<PipelineBuilder, ConfigError> {
+        self.validate()?;
+        let mut builder = CompilationPipeline::builder(registry)
+            .encoding(self.encoding.clone())
+            .hash_algorithm(self.hash_algorithm)
+            .normalize_source(self.normalize_source)
+            .fail_on_warnings(self.fail_on_warnings)
+            .deduplicate_exports(self.deduplicate_exports)
+            .emit_checksums(self.emit_checksums)
+            .sandbox(self.sandbox)
+            .export_parallelism(self.limits.export_parallelism)
+            .retry_policy(RetryPolicy {
+                max_attempts: self.limits.render_attempts,
+                backoff: Duration::from_millis(self.limits.retry_backoff_ms),
+            });
+        builder = match self.renderer.as_str() {
+            "placeholder" => builder.renderer(PlaceholderRenderer),
+            _ => unreachable!("validated above"),
+        };
+        if let Some(root) = &self.source_root {
+            builder = builder.source_root(root);
+        }
+        if let Some(path) = &self.audit_log {
+            let sink = FileAuditSink::open(path).map_err(|e| ConfigError::invalid("audit_log", e.to_string()))?;
+            builder = builder.audit_sink(sink);
+        }
+        if !self.icc_profiles.is_empty() {
+            let mut store = IccProfileStore::new();
+            for (name, path) in &self.icc_profiles {
+                let field = format!("icc_profiles.{}", name);
+                let data = std::fs::read(path).map_err(|e| ConfigError::invalid(&field, format!("{}: {}", path.display(), e)))?;
+                store.register(name, data).map_err(|e| ConfigError::invalid(&field, e.reason))?;
+            }
+            builder = builder.icc_profiles(store);
+        }
+        if let Some(key) = &self.job_hash_key {
+            let secret = match &key.secret {
+                Secret::Value(value) => value.clone().into_bytes(),
+                Secret::File(path) => std::fs::read(path)
+                    .map_err(|e| ConfigError::invalid("job_hash_key.secret", format!("{}: {}", path.display(), e)))?,
+            };
+            builder = builder.job_hash_key(JobHashKey::new(&key.key_id, secret));
+        }
+        #[cfg(feature = "signing")]
+        if let (true, Some(path)) = (self.signing.enabled, &self.signing.key_path) {
+            use crate::signing::{SigningConfig, SigningKey};
+            let invalid = |message: String| ConfigError::invalid("signing.key_path", format!("{}: {}", path.display(), message));
+            let pem = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+            let key = SigningKey::from_pkcs8_pem(&pem).map_err(|e| invalid(e.to_string()))?;
+            let key_id = self.signing.key_id.clone().unwrap_or_else(|| key.verifying_key().key_id());
+            builder = builder.signing(SigningConfig { key, key_id });
+        }
+        Ok(builder.config(self.clone()))
+    }
+
+    /// The config with inline secrets replaced by `REDACTED`
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if let Some(JobHashKeyConfig { secret: secret @ Secret::Value(_), .. }) = &mut config.job_hash_key {
+            *secret = Secret::Value(REDACTED.to_string());
+        }
+        config
+    }
+
+    /// Digest of the canonical JSON of the redacted config: equal for
+    /// pipelines configured alike, whatever their secrets
+    pub fn hash(&self, algorithm: HashAlgorithm) -> Result<String, HashingError> {
+        Ok(algorithm.digest(canonical_json(&self.redacted())?.as_bytes()))
+    }
+}
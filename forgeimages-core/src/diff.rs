@@ -0,0 +1,107 @@
+//! Manifest Diff - What Changed Between Two Compiles
+//!
+//! Compares two manifests field by field, and their exports matched by id.
+//! Equal export hashes mean equal bytes, so an empty diff proves a rebuild
+//! (say, after a dependency bump) reproduced every file. `id`,
+//! `created_at` and `manifest_hash` belong to the run, not the output, and
+//! are not compared.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::pipeline::{CompiledAsset, ExportedFile};
+use crate::validation::ValidationDiff;
+
+/// One field that differs; `a` and `b` as serialized in each manifest
+/// (`null` for an export only one side has)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub a: Value,
+    pub b: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ExportDiff {
+    OnlyInA { id: String },
+    OnlyInB { id: String },
+    Changed { id: String, changes: Vec<FieldChange> },
+}
+
+impl ExportDiff {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::OnlyInA { id } | Self::OnlyInB { id } | Self::Changed { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestDiff {
+    pub identical: bool,
+    /// Top-level fields that differ
+    pub fields: Vec<FieldChange>,
+    /// Exports that differ, in `a`'s order, then those only `b` has
+    pub exports: Vec<ExportDiff>,
+    pub validation: ValidationDiff,
+}
+
+/// Compare manifest `a` with manifest `b`
+pub fn diff_manifests(a: &CompiledAsset, b: &CompiledAsset) -> ManifestDiff {
+    let mut fields = vec![];
+    let mut field = |name, x: Value, y: Value| {
+        if x != y {
+            fields.push(FieldChange { field: name, a: x, b: y });
+        }
+    };
+    field("template_id", value(&a.template_id), value(&b.template_id));
+    field("template_version", value(&a.template_version), value(&b.template_version));
+    field("template_hash", value(&a.template_hash), value(&b.template_hash));
+    field("engine_version", value(&a.engine_version), value(&b.engine_version));
+    field("hash_algorithm", value(&a.hash_algorithm), value(&b.hash_algorithm));
+    field("hash_scheme", value(&a.hash_scheme), value(&b.hash_scheme));
+    field("job_hash", value(&a.job_hash), value(&b.job_hash));
+    field("source_hash", value(&a.source_hash), value(&b.source_hash));
+    field("font_hash", value(&a.font_hash), value(&b.font_hash));
+    field("request", value(&a.request), value(&b.request));
+    field("signer", value(&a.signer), value(&b.signer));
+    field("encoding", value(&a.encoding), value(&b.encoding));
+    field("config_hash", value(&a.config_hash), value(&b.config_hash));
+    field("print", value(&a.print), value(&b.print));
+    field("exports_root", value(&a.exports_root), value(&b.exports_root));
+    field("export_errors", value(&a.export_errors), value(&b.export_errors));
+
+    let mut exports: Vec<ExportDiff> = a.exports.iter()
+        .filter_map(|x| match b.exports.iter().find(|y| y.id == x.id) {
+            None => Some(ExportDiff::OnlyInA { id: x.id.clone() }),
+            Some(y) => {
+                let changes = export_changes(x, y);
+                (!changes.is_empty()).then(|| ExportDiff::Changed { id: x.id.clone(), changes })
+            }
+        })
+        .collect();
+    exports.extend(b.exports.iter()
+        .filter(|y| !a.exports.iter().any(|x| x.id == y.id))
+        .map(|y| ExportDiff::OnlyInB { id: y.id.clone() }));
+
+    let validation = a.validation.diff(&b.validation);
+    ManifestDiff { identical: fields.is_empty() && exports.is_empty() && validation.is_empty(), fields, exports, validation }
+}
+
+fn value<T: Serialize>(field: &T) -> Value {
+    serde_json::to_value(field).unwrap_or_default()
+}
+
+fn export_changes(a: &ExportedFile, b: &ExportedFile) -> Vec<FieldChange> {
+    let pairs = [
+        ("hash", Value::from(a.hash.as_str()), Value::from(b.hash.as_str())),
+        ("size", Value::from(a.size.to_vec()), Value::from(b.size.to_vec())),
+        ("format", Value::from(a.format.as_str()), Value::from(b.format.as_str())),
+        ("filename", Value::from(a.filename.as_str()), Value::from(b.filename.as_str())),
+    ];
+    pairs.into_iter()
+        .filter(|(_, x, y)| x != y)
+        .map(|(field, a, b)| FieldChange { field, a, b })
+        .collect()
+}
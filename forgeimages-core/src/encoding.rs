@@ -0,0 +1,639 @@
+//! Encoding System - Pinned Output Encoders
+//!
+//! Law 4 (Deterministic Output) extends to the encoder: the same raster must
+//! produce the same bytes on every run and platform. The PNG encoder therefore
+//! has no heuristics at all:
+//! - one filter type applied to every scanline
+//! - a single final deflate block with fixed Huffman codes and greedy matching
+//! - chunks in fixed order: IHDR, iCCP (only with a profile), tEXt (only
+//!   when requested), IDAT, IEND
+//! - no tIME chunk, ever; embedded text comes from the manifest, not the clock
+//!
+//! TIFF output is equally fixed: little-endian baseline TIFF, one
+//! uncompressed strip, tags in ascending order and no DateTime tag.
+//!
+//! JPEG output is baseline at `JPEG_QUALITY` with the encoder's default
+//! tables, from `jpeg-encoder` built without its SIMD paths. CMYK JPEGs
+//! follow the Adobe convention (inverted samples, an `APP14` segment).
+//!
+//! ICC profiles go where each format expects them: PNG `iCCP`, TIFF tag
+//! 34675, JPEG `APP2` `ICC_PROFILE` segments.
+
+use serde::{Deserialize, Serialize};
+
+use crate::icc::IccProfile;
+use crate::raster::Raster;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Encoder parameters recorded in every manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EncodingProfile {
+    pub png_filter: PngFilter,
+    pub png_compression: PngCompression,
+}
+
+impl Default for EncodingProfile {
+    fn default() -> Self {
+        Self {
+            png_filter: PngFilter::Up,
+            png_compression: PngCompression::FixedHuffman,
+        }
+    }
+}
+
+/// Scanline filter applied uniformly to every row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl PngFilter {
+    fn type_byte(self) -> u8 {
+        match self {
+            PngFilter::None => 0,
+            PngFilter::Sub => 1,
+            PngFilter::Up => 2,
+            PngFilter::Average => 3,
+            PngFilter::Paeth => 4,
+        }
+    }
+}
+
+/// Deflate strategy for IDAT data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PngCompression {
+    /// Uncompressed deflate blocks
+    Stored,
+    /// Fixed Huffman codes with greedy LZ77 matching
+    FixedHuffman,
+}
+
+/// Key/value text embedded as tEXt chunks, in the given order
+pub type PngMetadata = Vec<(String, String)>;
+
+/// PNG pixel layout (8 bits per sample)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngColor {
+    Rgba,
+    /// Luma only, for opaque canvases (see `color::to_gray`)
+    Gray,
+    /// Luma plus alpha (see `color::to_gray_alpha`)
+    GrayAlpha,
+}
+
+impl PngColor {
+    fn color_type(self) -> u8 {
+        match self {
+            PngColor::Rgba => 6,
+            PngColor::Gray => 0,
+            PngColor::GrayAlpha => 4,
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PngColor::Rgba => 4,
+            PngColor::Gray => 1,
+            PngColor::GrayAlpha => 2,
+        }
+    }
+}
+
+/// Encode an RGBA raster as an 8-bit RGBA PNG, tagged with `icc` when given
+/// (the profile must be RGB; see `IccColorSpace::describes`)
+pub fn encode_png(raster: &Raster, profile: &EncodingProfile, metadata: &PngMetadata, icc: Option<&IccProfile>) -> Vec<u8> {
+    encode_png_pixels(raster.width, raster.height, PngColor::Rgba, &raster.pixels, profile, metadata, icc)
+}
+
+/// Encode interleaved 8-bit pixels in `color` layout; `icc` must describe them
+pub fn encode_png_pixels(
+    width: u32,
+    height: u32,
+    color: PngColor,
+    pixels: &[u8],
+    profile: &EncodingProfile,
+    metadata: &PngMetadata,
+    icc: Option<&IccProfile>,
+) -> Vec<u8> {
+    let stride = width as usize * color.bytes_per_pixel();
+    assert_eq!(pixels.len(), stride * height as usize, "pixel buffer size mismatch");
+    let mut out = Vec::new();
+    out.extend_from_slice(&PNG_SIGNATURE);
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8-bit, color type, deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[8, color.color_type(), 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &ihdr);
+
+    if let Some(icc) = icc {
+        // Profile name, null separator, compression method 0 (zlib)
+        let mut iccp = Vec::with_capacity(icc.name().len() + 2);
+        iccp.extend_from_slice(icc.name().as_bytes());
+        iccp.extend_from_slice(&[0, 0]);
+        iccp.extend_from_slice(&zlib_compress(icc.data(), profile.png_compression));
+        write_chunk(&mut out, b"iCCP", &iccp);
+    }
+
+    for (key, value) in metadata {
+        let mut text = Vec::with_capacity(key.len() + value.len() + 1);
+        text.extend_from_slice(key.as_bytes());
+        text.push(0);
+        text.extend_from_slice(value.as_bytes());
+        write_chunk(&mut out, b"tEXt", &text);
+    }
+
+    let filtered = filter_scanlines(pixels, stride, color.bytes_per_pixel(), profile.png_filter);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&filtered, profile.png_compression));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn filter_scanlines(pixels: &[u8], stride: usize, bpp: usize, filter: PngFilter) -> Vec<u8> {
+    let rows: Vec<&[u8]> = pixels.chunks_exact(stride.max(1)).collect();
+    let mut out = Vec::with_capacity(pixels.len() + rows.len());
+    let zero_row = vec![0u8; stride];
+
+    for (y, row) in rows.iter().enumerate() {
+        let prev = if y == 0 { &zero_row[..] } else { rows[y - 1] };
+        out.push(filter.type_byte());
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = prev[i];
+            let c = if i >= bpp { prev[i - bpp] } else { 0 };
+            let predicted = match filter {
+                PngFilter::None => 0,
+                PngFilter::Sub => a,
+                PngFilter::Up => b,
+                PngFilter::Average => ((a as u16 + b as u16) / 2) as u8,
+                PngFilter::Paeth => paeth(a, b, c),
+            };
+            out.push(row[i].wrapping_sub(predicted));
+        }
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// --- zlib / deflate ---
+
+pub(crate) fn zlib_compress(data: &[u8], compression: PngCompression) -> Vec<u8> {
+    // CMF: deflate, 32K window. FLG: fastest level, no dictionary, check bits.
+    let mut out = vec![0x78, 0x01];
+    match compression {
+        PngCompression::Stored => deflate_stored(data, &mut out),
+        PngCompression::FixedHuffman => deflate_fixed(data, &mut out),
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn deflate_stored(data: &[u8], out: &mut Vec<u8>) {
+    let mut chunks = data.chunks(u16::MAX as usize).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+        return;
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        out.push(if last { 0x01 } else { 0x00 });
+        let len = chunk.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self { out, bits: 0, count: 0 }
+    }
+
+    /// Write `count` bits, least significant first
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first
+    fn write_code(&mut self, code: u32, len: u32) {
+        let reversed = code.reverse_bits() >> (32 - len);
+        self.write(reversed, len);
+    }
+
+    fn finish(mut self) {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+            self.bits = 0;
+            self.count = 0;
+        }
+    }
+}
+
+fn write_literal(w: &mut BitWriter<'_>, symbol: u16) {
+    match symbol {
+        0..=143 => w.write_code(0x30 + symbol as u32, 8),
+        144..=255 => w.write_code(0x190 + (symbol as u32 - 144), 9),
+        256..=279 => w.write_code(symbol as u32 - 256, 7),
+        _ => w.write_code(0xC0 + (symbol as u32 - 280), 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter<'_>, length: usize, distance: usize) {
+    let li = LENGTH_BASE.iter().rposition(|&b| b as usize <= length).unwrap_or(0);
+    write_literal(w, 257 + li as u16);
+    w.write((length - LENGTH_BASE[li] as usize) as u32, LENGTH_EXTRA[li] as u32);
+
+    let di = DIST_BASE.iter().rposition(|&b| b as usize <= distance).unwrap_or(0);
+    w.write_code(di as u32, 5);
+    w.write((distance - DIST_BASE[di] as usize) as u32, DIST_EXTRA[di] as u32);
+}
+
+fn hash3(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn deflate_fixed(data: &[u8], out: &mut Vec<u8>) {
+    let mut w = BitWriter::new(out);
+    w.write(1, 1); // BFINAL
+    w.write(1, 2); // BTYPE = fixed Huffman
+
+    // Most recent position per 3-byte hash; greedy, single candidate.
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = 0;
+        let mut distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash3(data, i);
+            let candidate = head[h];
+            head[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let max = (data.len() - i).min(MAX_MATCH);
+                let mut len = 0;
+                while len < max && data[candidate + len] == data[i + len] {
+                    len += 1;
+                }
+                if len >= MIN_MATCH {
+                    best = len;
+                    distance = i - candidate;
+                }
+            }
+        }
+
+        if best > 0 {
+            write_match(&mut w, best, distance);
+            for j in i + 1..i + best {
+                if j + MIN_MATCH <= data.len() {
+                    head[hash3(data, j)] = j;
+                }
+            }
+            i += best;
+        } else {
+            write_literal(&mut w, data[i] as u16);
+            i += 1;
+        }
+    }
+
+    write_literal(&mut w, 256);
+    w.finish();
+}
+
+/// Pixel layout of TIFF output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiffPhotometric {
+    /// 8-bit luma (PhotometricInterpretation = BlackIsZero)
+    Gray,
+    /// Interleaved RGB8
+    Rgb,
+    /// Interleaved CMYK8 (PhotometricInterpretation = Separated, InkSet = CMYK)
+    Cmyk,
+}
+
+impl TiffPhotometric {
+    fn samples_per_pixel(self) -> u16 {
+        match self {
+            TiffPhotometric::Gray => 1,
+            TiffPhotometric::Rgb => 3,
+            TiffPhotometric::Cmyk => 4,
+        }
+    }
+}
+
+const TIFF_SHORT: u16 = 3;
+const TIFF_LONG: u16 = 4;
+const TIFF_RATIONAL: u16 = 5;
+const TIFF_UNDEFINED: u16 = 7;
+
+/// Encode interleaved 8-bit pixels as a single-strip uncompressed TIFF,
+/// tagged with `dpi` as its resolution and with `icc` when given (the
+/// profile must match `photometric`)
+pub fn encode_tiff(
+    width: u32,
+    height: u32,
+    photometric: TiffPhotometric,
+    pixels: &[u8],
+    dpi: u32,
+    icc: Option<&IccProfile>,
+) -> Vec<u8> {
+    let samples = photometric.samples_per_pixel();
+    assert_eq!(pixels.len(), width as usize * height as usize * samples as usize, "pixel buffer size mismatch");
+
+    let entry_count: u16 = match photometric {
+        TiffPhotometric::Gray | TiffPhotometric::Rgb => 13,
+        TiffPhotometric::Cmyk => 14,
+    } + icc.is_some() as u16;
+    // Header, IFD, then out-of-line values, then the strip; offsets stay even
+    let ifd_len = 2 + 12 * entry_count as u32 + 4;
+    let bits_offset = 8 + ifd_len;
+    // A single BitsPerSample value fits in the entry itself
+    let bits_len = if samples > 1 { 2 * samples as u32 } else { 0 };
+    let x_res_offset = bits_offset + bits_len;
+    let y_res_offset = x_res_offset + 8;
+    let icc_offset = y_res_offset + 8;
+    let icc_len = icc.map_or(0, |icc| icc.data().len() as u32);
+    let strip_offset = icc_offset + icc_len + icc_len % 2;
+
+    let mut out = Vec::with_capacity(strip_offset as usize + pixels.len());
+    out.extend_from_slice(b"II*\0");
+    out.extend_from_slice(&8u32.to_le_bytes());
+    out.extend_from_slice(&entry_count.to_le_bytes());
+    let mut entry = |tag: u16, kind: u16, count: u32, value: u32| {
+        out.extend_from_slice(&tag.to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&value.to_le_bytes());
+    };
+    entry(256, TIFF_LONG, 1, width);
+    entry(257, TIFF_LONG, 1, height);
+    entry(258, TIFF_SHORT, samples as u32, if samples > 1 { bits_offset } else { 8 });
+    entry(259, TIFF_SHORT, 1, 1); // No compression
+    entry(262, TIFF_SHORT, 1, match photometric {
+        TiffPhotometric::Gray => 1,
+        TiffPhotometric::Rgb => 2,
+        TiffPhotometric::Cmyk => 5,
+    });
+    entry(273, TIFF_LONG, 1, strip_offset);
+    entry(277, TIFF_SHORT, 1, samples as u32);
+    entry(278, TIFF_LONG, 1, height);
+    entry(279, TIFF_LONG, 1, pixels.len() as u32);
+    entry(282, TIFF_RATIONAL, 1, x_res_offset);
+    entry(283, TIFF_RATIONAL, 1, y_res_offset);
+    entry(284, TIFF_SHORT, 1, 1); // Chunky
+    entry(296, TIFF_SHORT, 1, 2); // Inches
+    if photometric == TiffPhotometric::Cmyk {
+        entry(332, TIFF_SHORT, 1, 1); // InkSet: CMYK
+    }
+    if icc.is_some() {
+        entry(34675, TIFF_UNDEFINED, icc_len, icc_offset); // ICC profile
+    }
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    if samples > 1 {
+        for _ in 0..samples {
+            out.extend_from_slice(&8u16.to_le_bytes());
+        }
+    }
+    for _ in 0..2 {
+        out.extend_from_slice(&dpi.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+    }
+    if let Some(icc) = icc {
+        out.extend_from_slice(icc.data());
+        if icc_len % 2 == 1 {
+            out.push(0);
+        }
+    }
+    debug_assert_eq!(out.len(), strip_offset as usize);
+    out.extend_from_slice(pixels);
+    out
+}
+
+/// Quality of every JPEG export; from 90 up chroma is not subsampled
+pub const JPEG_QUALITY: u8 = 90;
+
+/// JPEG pixel layout (8 bits per sample, no alpha)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JpegColor {
+    Rgb,
+    /// Luma only (see `color::to_gray`)
+    Luma,
+    Cmyk,
+}
+
+/// Encode interleaved 8-bit pixels in `color` layout as a baseline JPEG at
+/// `dpi`, tagged with `icc` when given (it must describe the pixels)
+pub fn encode_jpeg_pixels(
+    width: u32,
+    height: u32,
+    color: JpegColor,
+    pixels: &[u8],
+    dpi: u32,
+    icc: Option<&IccProfile>,
+) -> Result<Vec<u8>, String> {
+    let side = |n: u32| u16::try_from(n).map_err(|_| format!("{}x{} exceeds JPEG's 65535-pixel sides", width, height));
+    let (w, h) = (side(width)?, side(height)?);
+    let color_type = match color {
+        JpegColor::Rgb => jpeg_encoder::ColorType::Rgb,
+        JpegColor::Luma => jpeg_encoder::ColorType::Luma,
+        JpegColor::Cmyk => jpeg_encoder::ColorType::Cmyk,
+    };
+    let mut out = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut out, JPEG_QUALITY);
+    encoder.set_density(jpeg_encoder::PixelDensity::dpi(dpi.min(u16::MAX as u32) as u16));
+    if let Some(icc) = icc {
+        encoder.add_icc_profile(icc.data()).map_err(|e| format!("ICC profile {}: {}", icc.name(), e))?;
+    }
+    encoder.encode(pixels, w, h, color_type).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums_known_values() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_png_chunk_order_and_no_time() {
+        let raster = Raster::filled(3, 2, [255, 0, 0, 255]);
+        let metadata = vec![("Software".to_string(), "ForgeImages".to_string())];
+        let png = encode_png(&raster, &EncodingProfile::default(), &metadata, None);
+
+        let mut kinds = vec![];
+        let mut pos = 8;
+        while pos < png.len() {
+            let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            kinds.push(String::from_utf8(png[pos + 4..pos + 8].to_vec()).unwrap());
+            pos += 12 + len;
+        }
+        assert_eq!(kinds, vec!["IHDR", "tEXt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_tiff_layout() {
+        let tiff = encode_tiff(2, 1, TiffPhotometric::Cmyk, &[1, 2, 3, 4, 5, 6, 7, 8], 300, None);
+        let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+        assert_eq!(&tiff[..4], b"II*\0");
+
+        let ifd = u32_at(4) as usize;
+        let count = u16_at(ifd) as usize;
+        let tag = |wanted: u16| (0..count)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&pos| u16_at(pos) == wanted)
+            .map(|pos| u32_at(pos + 8))
+            .unwrap();
+        let tags: Vec<_> = (0..count).map(|i| u16_at(ifd + 2 + 12 * i)).collect();
+        assert!(tags.windows(2).all(|w| w[0] < w[1]), "tags out of order: {:?}", tags);
+
+        assert_eq!((tag(256), tag(257), tag(262), tag(277), tag(332)), (2, 1, 5, 4, 1));
+        let strip = tag(273) as usize;
+        assert_eq!(&tiff[strip..strip + tag(279) as usize], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let x_res = tag(282) as usize;
+        assert_eq!((u32_at(x_res), u32_at(x_res + 4)), (300, 1));
+    }
+
+    fn profile(len: usize) -> IccProfile {
+        let mut data = vec![7; len];
+        data[0..4].copy_from_slice(&(len as u32).to_be_bytes());
+        data[16..20].copy_from_slice(b"RGB ");
+        data[36..40].copy_from_slice(b"acsp");
+        IccProfile::new("test", data).unwrap()
+    }
+
+    #[test]
+    fn test_tiff_profile_keeps_the_strip_word_aligned() {
+        let icc = profile(129);
+        let tiff = encode_tiff(1, 1, TiffPhotometric::Rgb, &[1, 2, 3], 300, Some(&icc));
+        let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+        // Last two IFD entries: InkSet is absent, so ICC follows ResolutionUnit
+        let ifd = u32_at(4) as usize;
+        let entry = ifd + 2 + 12 * 13;
+        assert_eq!(&tiff[entry..entry + 4], &[0x73, 0x87, 7, 0]);
+        let offset = u32_at(entry + 8) as usize;
+        assert_eq!(&tiff[offset..offset + 129], icc.data());
+        assert_eq!(tiff.len() % 2, 1);
+        assert_eq!(&tiff[tiff.len() - 3..], &[1, 2, 3]);
+        assert_eq!((tiff.len() - 3) % 2, 0);
+    }
+
+    #[test]
+    fn test_jpeg_carries_the_whole_profile() {
+        // Longer than one APP2 segment holds, so it is split in two
+        let icc = profile(0xFFFF);
+        let pixels: Vec<u8> = (0..4 * 3 * 3).map(|i| (i * 20) as u8).collect();
+        let jpeg = encode_jpeg_pixels(4, 3, JpegColor::Rgb, &pixels, 300, Some(&icc)).unwrap();
+        let segments = jpeg.windows(16).filter(|w| w[..2] == [0xFF, 0xE2] && &w[4..] == b"ICC_PROFILE\0").count();
+        assert_eq!(segments, 2);
+
+        let mut decoder = jpeg_decoder::Decoder::new(&jpeg[..]);
+        let decoded = decoder.decode().unwrap();
+        let info = decoder.info().unwrap();
+        assert_eq!((info.width, info.height, info.pixel_format), (4, 3, jpeg_decoder::PixelFormat::RGB24));
+        assert_eq!(decoded.len(), pixels.len());
+        assert_eq!(decoder.icc_profile().as_deref(), Some(icc.data()));
+
+        assert!(encode_jpeg_pixels(70_000, 1, JpegColor::Luma, &[], 300, None).unwrap_err().contains("65535"));
+    }
+}
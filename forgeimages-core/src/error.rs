@@ -0,0 +1,407 @@
+//! Error Envelope - One Error Body for Every Front-End
+//!
+//! What the CLI prints, the server answers, and the FFI, WASM and Python
+//! bindings hand back when a call fails:
+//!
+//! ```json
+//! {"success":false,"error_kind":"template_not_found","message":"Template not found: banner",
+//!  "template_id":"banner","details":{"template_id":"banner"}}
+//! ```
+//!
+//! `template_id` and `validation` are present when known: a blocked
+//! compile carries the `ValidationResult` that blocked it. `details` holds
+//! whatever else the failure names (an export, a file, a bound).
+//!
+//! `ErrorKind` names are a contract with every consumer: renaming or
+//! removing one is a breaking change, and `test_kind_names_are_pinned`
+//! fails when it happens. Adding a kind is not breaking.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::exit_code;
+use crate::pipeline::{EngineBound, PipelineError};
+use crate::templates::TemplateArrayError;
+use crate::validation::ValidationResult;
+
+/// Why a call failed, as the `error_kind` of an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    // One per `PipelineError` variant
+    TemplateNotFound,
+    ValidationFailed,
+    EngineVersionMismatch,
+    CompilationError,
+    ExportFailed,
+    InvalidSource,
+    SourceNotAllowed,
+    OutputFailed,
+    AuditFailed,
+    SandboxViolation,
+    IccProfileNotFound,
+    SerializationError,
+    HashingError,
+    Cancelled,
+
+    // Failures before or around the pipeline
+    /// Bad arguments or flag combinations
+    Usage,
+    /// A request or input that does not parse as its type
+    InvalidPayload,
+    /// A file named by the caller that cannot be read or parsed
+    InvalidFile,
+    /// JSON that does not parse strictly (duplicate keys included)
+    InvalidJson,
+    /// A template or template set given inline (WASM, FFI, Node)
+    InvalidTemplate,
+    /// A null or non-UTF-8 FFI argument
+    InvalidArgument,
+    InvalidConfig,
+    /// A signing or public key that does not parse
+    InvalidKey,
+    /// An encrypted private key
+    UnsupportedKey,
+    TemplatesUnavailable,
+    OutputExists,
+    NotReproducible,
+    UnknownExport,
+    UnknownAssetClass,
+    VerificationFailed,
+    SignatureRejected,
+    UnsupportedMediaType,
+    Server,
+    /// The server's worker failed, or a panic was caught at the FFI boundary
+    Internal,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 33] = [
+        ErrorKind::TemplateNotFound,
+        ErrorKind::ValidationFailed,
+        ErrorKind::EngineVersionMismatch,
+        ErrorKind::CompilationError,
+        ErrorKind::ExportFailed,
+        ErrorKind::InvalidSource,
+        ErrorKind::SourceNotAllowed,
+        ErrorKind::OutputFailed,
+        ErrorKind::AuditFailed,
+        ErrorKind::SandboxViolation,
+        ErrorKind::IccProfileNotFound,
+        ErrorKind::SerializationError,
+        ErrorKind::HashingError,
+        ErrorKind::Cancelled,
+        ErrorKind::Usage,
+        ErrorKind::InvalidPayload,
+        ErrorKind::InvalidFile,
+        ErrorKind::InvalidJson,
+        ErrorKind::InvalidTemplate,
+        ErrorKind::InvalidArgument,
+        ErrorKind::InvalidConfig,
+        ErrorKind::InvalidKey,
+        ErrorKind::UnsupportedKey,
+        ErrorKind::TemplatesUnavailable,
+        ErrorKind::OutputExists,
+        ErrorKind::NotReproducible,
+        ErrorKind::UnknownExport,
+        ErrorKind::UnknownAssetClass,
+        ErrorKind::VerificationFailed,
+        ErrorKind::SignatureRejected,
+        ErrorKind::UnsupportedMediaType,
+        ErrorKind::Server,
+        ErrorKind::Internal,
+    ];
+
+    /// The snake_case name, as serialized
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::TemplateNotFound => "template_not_found",
+            ErrorKind::ValidationFailed => "validation_failed",
+            ErrorKind::EngineVersionMismatch => "engine_version_mismatch",
+            ErrorKind::CompilationError => "compilation_error",
+            ErrorKind::ExportFailed => "export_failed",
+            ErrorKind::InvalidSource => "invalid_source",
+            ErrorKind::SourceNotAllowed => "source_not_allowed",
+            ErrorKind::OutputFailed => "output_failed",
+            ErrorKind::AuditFailed => "audit_failed",
+            ErrorKind::SandboxViolation => "sandbox_violation",
+            ErrorKind::IccProfileNotFound => "icc_profile_not_found",
+            ErrorKind::SerializationError => "serialization_error",
+            ErrorKind::HashingError => "hashing_error",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Usage => "usage",
+            ErrorKind::InvalidPayload => "invalid_payload",
+            ErrorKind::InvalidFile => "invalid_file",
+            ErrorKind::InvalidJson => "invalid_json",
+            ErrorKind::InvalidTemplate => "invalid_template",
+            ErrorKind::InvalidArgument => "invalid_argument",
+            ErrorKind::InvalidConfig => "invalid_config",
+            ErrorKind::InvalidKey => "invalid_key",
+            ErrorKind::UnsupportedKey => "unsupported_key",
+            ErrorKind::TemplatesUnavailable => "templates_unavailable",
+            ErrorKind::OutputExists => "output_exists",
+            ErrorKind::NotReproducible => "not_reproducible",
+            ErrorKind::UnknownExport => "unknown_export",
+            ErrorKind::UnknownAssetClass => "unknown_asset_class",
+            ErrorKind::VerificationFailed => "verification_failed",
+            ErrorKind::SignatureRejected => "signature_rejected",
+            ErrorKind::UnsupportedMediaType => "unsupported_media_type",
+            ErrorKind::Server => "server",
+            ErrorKind::Internal => "internal",
+        }
+    }
+
+    /// The process exit code for this kind (see `exit_code`); a front-end
+    /// may pick another for one failure with `ErrorEnvelope::with_exit_code`
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::ValidationFailed => exit_code::VALIDATION_FAILED,
+            ErrorKind::TemplateNotFound => exit_code::TEMPLATE_NOT_FOUND,
+            ErrorKind::EngineVersionMismatch => exit_code::VERSION_MISMATCH,
+            ErrorKind::InvalidSource
+            | ErrorKind::SourceNotAllowed
+            | ErrorKind::InvalidPayload
+            | ErrorKind::InvalidFile
+            | ErrorKind::InvalidJson
+            | ErrorKind::UnsupportedMediaType => exit_code::INVALID_PAYLOAD,
+            ErrorKind::OutputFailed
+            | ErrorKind::AuditFailed
+            | ErrorKind::TemplatesUnavailable
+            | ErrorKind::OutputExists
+            | ErrorKind::Server => exit_code::IO_ERROR,
+            ErrorKind::VerificationFailed | ErrorKind::NotReproducible | ErrorKind::SignatureRejected => {
+                exit_code::VERIFICATION_FAILED
+            }
+            ErrorKind::Cancelled => exit_code::CANCELLED,
+            ErrorKind::CompilationError
+            | ErrorKind::ExportFailed
+            | ErrorKind::SandboxViolation
+            | ErrorKind::IccProfileNotFound
+            | ErrorKind::SerializationError
+            | ErrorKind::HashingError
+            | ErrorKind::Usage
+            | ErrorKind::InvalidTemplate
+            | ErrorKind::InvalidArgument
+            | ErrorKind::InvalidConfig
+            | ErrorKind::InvalidKey
+            | ErrorKind::UnsupportedKey
+            | ErrorKind::UnknownExport
+            | ErrorKind::UnknownAssetClass
+            | ErrorKind::Internal => exit_code::OTHER,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<&PipelineError> for ErrorKind {
+    fn from(error: &PipelineError) -> Self {
+        match error {
+            PipelineError::TemplateNotFound(_) => ErrorKind::TemplateNotFound,
+            PipelineError::ValidationFailed(_) => ErrorKind::ValidationFailed,
+            PipelineError::EngineVersionMismatch(..) => ErrorKind::EngineVersionMismatch,
+            PipelineError::CompilationError(_) => ErrorKind::CompilationError,
+            PipelineError::ExportFailed(..) => ErrorKind::ExportFailed,
+            PipelineError::InvalidSource(_) => ErrorKind::InvalidSource,
+            PipelineError::SourceNotAllowed(_) => ErrorKind::SourceNotAllowed,
+            PipelineError::OutputFailed(_) => ErrorKind::OutputFailed,
+            PipelineError::AuditFailed(_) => ErrorKind::AuditFailed,
+            PipelineError::SandboxViolation(_) => ErrorKind::SandboxViolation,
+            PipelineError::IccProfileNotFound(_) => ErrorKind::IccProfileNotFound,
+            PipelineError::SerializationError(_) => ErrorKind::SerializationError,
+            PipelineError::HashingError(_) => ErrorKind::HashingError,
+            PipelineError::Cancelled(..) => ErrorKind::Cancelled,
+        }
+    }
+}
+
+/// A failed call, as every front-end serializes it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ErrorEnvelope {
+    #[cfg_attr(feature = "ts", ts(type = "false"))]
+    success: Failure,
+    #[serde(rename = "error_kind")]
+    pub kind: ErrorKind,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<Box<ValidationResult>>,
+    #[serde(default)]
+    pub details: Map<String, Value>,
+    /// Exit code in place of the kind's; never serialized
+    #[serde(skip)]
+    exit_code: Option<u8>,
+}
+
+impl ErrorEnvelope {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            success: Failure,
+            kind,
+            message: message.into(),
+            template_id: None,
+            validation: None,
+            details: Map::new(),
+            exit_code: None,
+        }
+    }
+
+    /// Add `details[key]`
+    pub fn detail(mut self, key: &str, value: impl Serialize) -> Self {
+        self.details.insert(key.to_string(), serde_json::to_value(value).unwrap_or_default());
+        self
+    }
+
+    pub fn with_template_id(mut self, template_id: impl Into<String>) -> Self {
+        self.template_id = Some(template_id.into());
+        self
+    }
+
+    /// Attach the result behind a blocked compile
+    pub fn with_validation(mut self, validation: ValidationResult) -> Self {
+        self.validation = Some(Box::new(validation));
+        self
+    }
+
+    /// Exit with `code` instead of the kind's
+    pub fn with_exit_code(mut self, code: u8) -> Self {
+        self.exit_code = Some(code);
+        self
+    }
+
+    /// What a process reporting this envelope exits with
+    pub fn exit_code(&self) -> u8 {
+        self.exit_code.unwrap_or_else(|| self.kind.exit_code())
+    }
+}
+
+impl From<&PipelineError> for ErrorEnvelope {
+    fn from(error: &PipelineError) -> Self {
+        let envelope = Self::new(error.into(), error.to_string());
+        match error {
+            PipelineError::TemplateNotFound(id) => envelope.with_template_id(id).detail("template_id", id),
+            PipelineError::EngineVersionMismatch(bound, template_version, required, current) => envelope
+                .detail("bound", if *bound == EngineBound::TooOld { "too_old" } else { "too_new" })
+                .detail("template_version", template_version)
+                .detail("required", required)
+                .detail("current", current),
+            PipelineError::ExportFailed(export, _) => envelope.detail("export", export),
+            PipelineError::Cancelled(reason, export) => envelope.detail("reason", reason).detail("export", export),
+            _ => envelope,
+        }
+    }
+}
+
+impl From<PipelineError> for ErrorEnvelope {
+    fn from(error: PipelineError) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl From<&TemplateArrayError> for ErrorEnvelope {
+    fn from(error: &TemplateArrayError) -> Self {
+        let envelope = Self::new(ErrorKind::InvalidTemplate, error.to_string());
+        match error {
+            TemplateArrayError::Template(index, _) => envelope.detail("index", index),
+            _ => envelope,
+        }
+    }
+}
+
+impl fmt::Display for ErrorEnvelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for ErrorEnvelope {}
+
+/// `"success": false`, the field hosts tell envelopes from results by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Failure;
+
+impl Serialize for Failure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(false)
+    }
+}
+
+impl<'de> Deserialize<'de> for Failure {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match bool::deserialize(deserializer)? {
+            false => Ok(Failure),
+            true => Err(serde::de::Error::custom("an error envelope has \"success\": false")),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Failure {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Failure".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "const": false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelReason;
+
+    /// Renaming a kind breaks every consumer that branches on it: change
+    /// this list only with a breaking release
+    #[test]
+    fn test_kind_names_are_pinned() {
+        let names: Vec<String> = ErrorKind::ALL.iter()
+            .map(|kind| serde_json::to_value(kind).unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, [
+            "template_not_found", "validation_failed", "engine_version_mismatch", "compilation_error",
+            "export_failed", "invalid_source", "source_not_allowed", "output_failed", "audit_failed",
+            "sandbox_violation", "icc_profile_not_found", "serialization_error", "hashing_error", "cancelled",
+            "usage", "invalid_payload", "invalid_file", "invalid_json", "invalid_template", "invalid_argument",
+            "invalid_config", "invalid_key", "unsupported_key", "templates_unavailable", "output_exists",
+            "not_reproducible", "unknown_export", "unknown_asset_class", "verification_failed",
+            "signature_rejected", "unsupported_media_type", "server", "internal",
+        ]);
+        for kind in ErrorKind::ALL {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.name());
+            assert_eq!(serde_json::from_value::<ErrorKind>(kind.name().into()).unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn test_pipeline_errors_keep_their_wire_form() {
+        let envelope = ErrorEnvelope::from(&PipelineError::TemplateNotFound("banner".to_string()));
+        assert_eq!(
+            serde_json::to_string(&envelope).unwrap(),
+            concat!(
+                r#"{"success":false,"error_kind":"template_not_found","message":"Template not found: banner","#,
+                r#""template_id":"banner","details":{"template_id":"banner"}}"#,
+            ),
+        );
+        assert_eq!(envelope.exit_code(), 3);
+
+        let cancelled = ErrorEnvelope::from(&PipelineError::Cancelled(CancelReason::TimedOut, Some("icon".to_string())));
+        assert_eq!(cancelled.details["export"], "icon");
+        assert_eq!(cancelled.exit_code(), exit_code::CANCELLED);
+
+        let parsed: ErrorEnvelope = serde_json::from_str(&serde_json::to_string(&cancelled).unwrap()).unwrap();
+        assert_eq!(parsed.kind, ErrorKind::Cancelled);
+        assert!(serde_json::from_str::<ErrorEnvelope>(r#"{"success":true,"error_kind":"usage","message":""}"#).is_err());
+    }
+}
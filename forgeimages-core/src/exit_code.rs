@@ -0,0 +1,57 @@
+//! Process Exit Codes
+//!
+//! What `forgeimages-cli` exits with, by failure class. The Python bridge
+//! branches on these, so changing a code is a breaking change.
+
+use crate::error::ErrorKind;
+use crate::pipeline::PipelineError;
+
+pub const SUCCESS: u8 = 0;
+/// Anything without a class of its own, usage errors included
+pub const OTHER: u8 = 1;
+pub const VALIDATION_FAILED: u8 = 2;
+pub const TEMPLATE_NOT_FOUND: u8 = 3;
+/// The template's engine range excludes this engine
+pub const VERSION_MISMATCH: u8 = 4;
+/// The request or source could not be parsed or is not allowed
+pub const INVALID_PAYLOAD: u8 = 5;
+pub const IO_ERROR: u8 = 6;
+pub const VERIFICATION_FAILED: u8 = 7;
+/// Stopped by `--timeout` or Ctrl-C before it finished
+pub const CANCELLED: u8 = 8;
+/// Valid, but with warnings, under `--fail-on-warning`
+pub const FAILED_ON_WARNINGS: u8 = 9;
+/// `diff` compared two manifests that differ
+pub const MANIFESTS_DIFFER: u8 = 10;
+
+/// The exit code for a pipeline failure
+pub fn exit_code_for(error: &PipelineError) -> u8 {
+    ErrorKind::from(error).exit_code()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelReason;
+    use crate::pipeline::EngineBound;
+
+    #[test]
+    fn test_codes_are_pinned() {
+        let cases = [
+            (PipelineError::ValidationFailed(String::new()), 2),
+            (PipelineError::TemplateNotFound(String::new()), 3),
+            (PipelineError::EngineVersionMismatch(EngineBound::TooOld, String::new(), String::new(), String::new()), 4),
+            (PipelineError::InvalidSource(String::new()), 5),
+            (PipelineError::SourceNotAllowed(String::new()), 5),
+            (PipelineError::OutputFailed(String::new()), 6),
+            (PipelineError::AuditFailed(String::new()), 6),
+            (PipelineError::CompilationError(String::new()), 1),
+            (PipelineError::SandboxViolation(String::new()), 1),
+            (PipelineError::Cancelled(CancelReason::TimedOut, None), 8),
+        ];
+        for (error, code) in cases {
+            assert_eq!(exit_code_for(&error), code, "{:?}", error);
+        }
+        assert_eq!((FAILED_ON_WARNINGS, MANIFESTS_DIFFER), (9, 10));
+    }
+}
@@ -0,0 +1,458 @@
+//! Font Outlines - Minimal TrueType Reader
+//!
+//! Text slots are converted to paths at render time so output never depends
+//! on fonts installed on the rendering host. Only what that needs is read:
+//! character mapping (cmap formats 4 and 12), advances, and glyph outlines
+//! (simple and composite `glyf` glyphs). Path coordinates are written with
+//! fixed two-decimal precision so the resulting SVG is byte-stable.
+
+use std::fmt::Write;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum FontError {
+    #[error("Font data truncated")]
+    Truncated,
+
+    #[error("Font is missing required table '{0}'")]
+    MissingTable(&'static str),
+
+    #[error("Unsupported font: {0}")]
+    Unsupported(String),
+}
+
+/// A parsed TrueType font (borrowed tables are re-sliced on demand)
+#[derive(Debug, Clone)]
+pub struct Font {
+    data: Vec<u8>,
+    units_per_em: u16,
+    num_glyphs: u16,
+    long_loca: bool,
+    num_h_metrics: u16,
+    cmap: Subtable,
+    hmtx: (usize, usize),
+    loca: (usize, usize),
+    glyf: (usize, usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Subtable {
+    Format4(usize),
+    Format12(usize),
+}
+
+/// A template font after loading and hash verification
+#[derive(Debug, Clone)]
+pub struct LoadedFont {
+    /// Prefixed digest under the pipeline's hash algorithm
+    pub digest: String,
+    pub font: Font,
+}
+
+/// A point in font units
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+    on_curve: bool,
+}
+
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, FontError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(FontError::Truncated)
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Result<i16, FontError> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, FontError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(FontError::Truncated)
+}
+
+impl Font {
+    pub fn parse(data: Vec<u8>) -> Result<Self, FontError> {
+        let version = u32_at(&data, 0)?;
+        if version != 0x0001_0000 && version != u32::from_be_bytes(*b"true") {
+            return Err(FontError::Unsupported("only TrueType outlines are supported".into()));
+        }
+
+        let num_tables = u16_at(&data, 4)? as usize;
+        let mut tables = std::collections::HashMap::new();
+        for i in 0..num_tables {
+            let record = 12 + i * 16;
+            let tag = data.get(record..record + 4).ok_or(FontError::Truncated)?;
+            let offset = u32_at(&data, record + 8)? as usize;
+            let length = u32_at(&data, record + 12)? as usize;
+            if offset.checked_add(length).is_none_or(|end| end > data.len()) {
+                return Err(FontError::Truncated);
+            }
+            tables.insert(tag.to_vec(), (offset, length));
+        }
+        let table = |tag: &'static str| {
+            tables.get(tag.as_bytes()).copied().ok_or(FontError::MissingTable(tag))
+        };
+
+        let (head, _) = table("head")?;
+        let (maxp, _) = table("maxp")?;
+        let (hhea, _) = table("hhea")?;
+        let cmap = table("cmap")?;
+
+        let units_per_em = u16_at(&data, head + 18)?;
+        if units_per_em == 0 {
+            return Err(FontError::Unsupported("unitsPerEm is zero".into()));
+        }
+
+        Ok(Self {
+            units_per_em,
+            num_glyphs: u16_at(&data, maxp + 4)?,
+            long_loca: i16_at(&data, head + 50)? != 0,
+            num_h_metrics: u16_at(&data, hhea + 34)?,
+            cmap: find_cmap_subtable(&data, cmap.0)?,
+            hmtx: table("hmtx")?,
+            loca: table("loca")?,
+            glyf: table("glyf")?,
+            data,
+        })
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// Glyph id for a character (0 = .notdef when unmapped)
+    pub fn glyph_index(&self, c: char) -> u16 {
+        let code = c as u32;
+        let gid = match self.cmap {
+            Subtable::Format4(offset) => self.lookup_format4(offset, code),
+            Subtable::Format12(offset) => self.lookup_format12(offset, code),
+        };
+        gid.ok().flatten().filter(|&g| g < self.num_glyphs).unwrap_or(0)
+    }
+
+    /// Horizontal advance in font units
+    pub fn advance(&self, gid: u16) -> u16 {
+        let index = gid.min(self.num_h_metrics.saturating_sub(1)) as usize;
+        u16_at(&self.data, self.hmtx.0 + index * 4).unwrap_or(0)
+    }
+
+    /// Total advance of a string in font units
+    pub fn text_advance(&self, text: &str) -> u32 {
+        text.chars().map(|c| self.advance(self.glyph_index(c)) as u32).sum()
+    }
+
+    /// SVG path data for `text` with the baseline origin at (x, y)
+    pub fn text_to_path(&self, text: &str, font_size: f64, x: f64, y: f64) -> Result<String, FontError> {
+        let scale = font_size / self.units_per_em as f64;
+        let mut path = String::new();
+        let mut pen = 0.0;
+        for c in text.chars() {
+            let gid = self.glyph_index(c);
+            for contour in self.contours(gid, 0)? {
+                let mapped: Vec<Point> = contour.iter()
+                    .map(|p| Point {
+                        x: x + (pen + p.x) * scale,
+                        y: y - p.y * scale,
+                        on_curve: p.on_curve,
+                    })
+                    .collect();
+                append_contour(&mut path, &mapped);
+            }
+            pen += self.advance(gid) as f64;
+        }
+        Ok(path.trim_end().to_string())
+    }
+
+    fn glyph_range(&self, gid: u16) -> Result<Option<(usize, usize)>, FontError> {
+        if gid >= self.num_glyphs {
+            return Ok(None);
+        }
+        let (loca, _) = self.loca;
+        let (start, end) = if self.long_loca {
+            (
+                u32_at(&self.data, loca + gid as usize * 4)? as usize,
+                u32_at(&self.data, loca + gid as usize * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                u16_at(&self.data, loca + gid as usize * 2)? as usize * 2,
+                u16_at(&self.data, loca + gid as usize * 2 + 2)? as usize * 2,
+            )
+        };
+        if end <= start {
+            return Ok(None);
+        }
+        if end > self.glyf.1 {
+            return Err(FontError::Truncated);
+        }
+        Ok(Some((self.glyf.0 + start, self.glyf.0 + end)))
+    }
+
+    fn contours(&self, gid: u16, depth: u32) -> Result<Vec<Vec<Point>>, FontError> {
+        let Some((start, end)) = self.glyph_range(gid)? else {
+            return Ok(vec![]);
+        };
+        let glyph = &self.data[start..end];
+        let num_contours = i16_at(glyph, 0)?;
+        if num_contours >= 0 {
+            simple_glyph(glyph, num_contours as usize)
+        } else if depth >= MAX_COMPOSITE_DEPTH {
+            Err(FontError::Unsupported("composite glyph nesting too deep".into()))
+        } else {
+            self.composite_glyph(glyph, depth)
+        }
+    }
+
+    fn composite_glyph(&self, glyph: &[u8], depth: u32) -> Result<Vec<Vec<Point>>, FontError> {
+        const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+        const WE_HAVE_A_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+        const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+        let f2dot14 = |offset: usize| i16_at(glyph, offset).map(|v| v as f64 / 16384.0);
+
+        let mut contours = vec![];
+        let mut pos = 10;
+        loop {
+            let flags = u16_at(glyph, pos)?;
+            let component = u16_at(glyph, pos + 2)?;
+            pos += 4;
+            let (dx, dy) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                pos += 4;
+                (i16_at(glyph, pos - 4)? as f64, i16_at(glyph, pos - 2)? as f64)
+            } else {
+                pos += 2;
+                let b = glyph.get(pos - 2..pos).ok_or(FontError::Truncated)?;
+                (b[0] as i8 as f64, b[1] as i8 as f64)
+            };
+            let (mut a, mut b, mut c, mut d) = (1.0, 0.0, 0.0, 1.0);
+            if flags & WE_HAVE_A_SCALE != 0 {
+                a = f2dot14(pos)?;
+                d = a;
+                pos += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                a = f2dot14(pos)?;
+                d = f2dot14(pos + 2)?;
+                pos += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                a = f2dot14(pos)?;
+                b = f2dot14(pos + 2)?;
+                c = f2dot14(pos + 4)?;
+                d = f2dot14(pos + 6)?;
+                pos += 8;
+            }
+            if flags & ARGS_ARE_XY_VALUES == 0 {
+                return Err(FontError::Unsupported("point-matched composite glyphs".into()));
+            }
+
+            for contour in self.contours(component, depth + 1)? {
+                contours.push(contour.into_iter()
+                    .map(|p| Point {
+                        x: a * p.x + c * p.y + dx,
+                        y: b * p.x + d * p.y + dy,
+                        on_curve: p.on_curve,
+                    })
+                    .collect());
+            }
+
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+        Ok(contours)
+    }
+
+    fn lookup_format4(&self, offset: usize, code: u32) -> Result<Option<u16>, FontError> {
+        if code > 0xFFFF {
+            return Ok(None);
+        }
+        let data = &self.data;
+        let seg_count = u16_at(data, offset + 6)? as usize / 2;
+        let ends = offset + 14;
+        let starts = ends + seg_count * 2 + 2;
+        let deltas = starts + seg_count * 2;
+        let range_offsets = deltas + seg_count * 2;
+        for i in 0..seg_count {
+            let end = u16_at(data, ends + i * 2)? as u32;
+            if code > end {
+                continue;
+            }
+            let start = u16_at(data, starts + i * 2)? as u32;
+            if code < start {
+                return Ok(None);
+            }
+            let delta = u16_at(data, deltas + i * 2)?;
+            let range_offset = u16_at(data, range_offsets + i * 2)? as usize;
+            if range_offset == 0 {
+                return Ok(Some((code as u16).wrapping_add(delta)));
+            }
+            let glyph_offset = range_offsets + i * 2 + range_offset + (code - start) as usize * 2;
+            let gid = u16_at(data, glyph_offset)?;
+            return Ok((gid != 0).then(|| gid.wrapping_add(delta)));
+        }
+        Ok(None)
+    }
+
+    fn lookup_format12(&self, offset: usize, code: u32) -> Result<Option<u16>, FontError> {
+        let groups = u32_at(&self.data, offset + 12)? as usize;
+        for i in 0..groups {
+            let group = offset + 16 + i * 12;
+            let start = u32_at(&self.data, group)?;
+            let end = u32_at(&self.data, group + 4)?;
+            if (start..=end).contains(&code) {
+                let gid = u32_at(&self.data, group + 8)? + (code - start);
+                return Ok(u16::try_from(gid).ok());
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn find_cmap_subtable(data: &[u8], cmap: usize) -> Result<Subtable, FontError> {
+    let count = u16_at(data, cmap + 2)? as usize;
+    let mut best: Option<(u8, Subtable)> = None;
+    for i in 0..count {
+        let record = cmap + 4 + i * 8;
+        let platform = u16_at(data, record)?;
+        let encoding = u16_at(data, record + 2)?;
+        let offset = cmap + u32_at(data, record + 4)? as usize;
+        let format = u16_at(data, offset)?;
+        // Prefer full-Unicode format 12, then BMP format 4
+        let candidate = match (platform, encoding, format) {
+            (3, 10, 12) | (0, _, 12) => Some((2, Subtable::Format12(offset))),
+            (3, 1, 4) | (0, _, 4) => Some((1, Subtable::Format4(offset))),
+            _ => None,
+        };
+        if let Some((rank, subtable)) = candidate {
+            if best.is_none_or(|(r, _)| rank > r) {
+                best = Some((rank, subtable));
+            }
+        }
+    }
+    best.map(|(_, s)| s)
+        .ok_or_else(|| FontError::Unsupported("no Unicode cmap subtable".into()))
+}
+
+fn simple_glyph(glyph: &[u8], num_contours: usize) -> Result<Vec<Vec<Point>>, FontError> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    if num_contours == 0 {
+        return Ok(vec![]);
+    }
+    let mut end_points = Vec::with_capacity(num_contours);
+    for i in 0..num_contours {
+        end_points.push(u16_at(glyph, 10 + i * 2)? as usize);
+    }
+    let num_points = end_points.last().map_or(0, |&e| e + 1);
+    let instructions = u16_at(glyph, 10 + num_contours * 2)? as usize;
+    let mut pos = 12 + num_contours * 2 + instructions;
+
+    let byte = |pos: usize| glyph.get(pos).copied().ok_or(FontError::Truncated);
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = byte(pos)?;
+        pos += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let repeat = byte(pos)?;
+            pos += 1;
+            for _ in 0..repeat {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(num_points);
+
+    let mut read_coords = |short: u8, same: u8| -> Result<Vec<f64>, FontError> {
+        let mut value = 0i32;
+        let mut coords = Vec::with_capacity(num_points);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = byte(pos)? as i32;
+                pos += 1;
+                value += if flag & same != 0 { delta } else { -delta };
+            } else if flag & same == 0 {
+                value += i16_at(glyph, pos)? as i32;
+                pos += 2;
+            }
+            coords.push(value as f64);
+        }
+        Ok(coords)
+    };
+    let xs = read_coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut contours = Vec::with_capacity(num_contours);
+    let mut start = 0;
+    for end in end_points {
+        if end < start || end >= num_points {
+            return Err(FontError::Truncated);
+        }
+        contours.push((start..=end)
+            .map(|i| Point { x: xs[i], y: ys[i], on_curve: flags[i] & ON_CURVE != 0 })
+            .collect());
+        start = end + 1;
+    }
+    Ok(contours)
+}
+
+fn fmt_num(v: f64) -> String {
+    let s = format!("{:.2}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0, on_curve: true }
+}
+
+/// Append one closed quadratic contour, inserting implied on-curve midpoints
+fn append_contour(path: &mut String, points: &[Point]) {
+    if points.is_empty() {
+        return;
+    }
+    // Start on an on-curve point (or the midpoint of two off-curve points)
+    let first_on = points.iter().position(|p| p.on_curve);
+    let (start, rotated): (Point, Vec<Point>) = match first_on {
+        Some(i) => (points[i], points[i + 1..].iter().chain(&points[..i]).copied().collect()),
+        None => (midpoint(points[points.len() - 1], points[0]), points.to_vec()),
+    };
+
+    let _ = write!(path, "M{} {} ", fmt_num(start.x), fmt_num(start.y));
+    let mut control: Option<Point> = None;
+    for p in rotated {
+        match (control, p.on_curve) {
+            (None, true) => {
+                let _ = write!(path, "L{} {} ", fmt_num(p.x), fmt_num(p.y));
+            }
+            (None, false) => control = Some(p),
+            (Some(c), true) => {
+                let _ = write!(path, "Q{} {} {} {} ", fmt_num(c.x), fmt_num(c.y), fmt_num(p.x), fmt_num(p.y));
+                control = None;
+            }
+            (Some(c), false) => {
+                let mid = midpoint(c, p);
+                let _ = write!(path, "Q{} {} {} {} ", fmt_num(c.x), fmt_num(c.y), fmt_num(mid.x), fmt_num(mid.y));
+                control = Some(p);
+            }
+        }
+    }
+    if let Some(c) = control {
+        let _ = write!(path, "Q{} {} {} {} ", fmt_num(c.x), fmt_num(c.y), fmt_num(start.x), fmt_num(start.y));
+    }
+    path.push_str("Z ");
+}
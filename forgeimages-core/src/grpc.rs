@@ -0,0 +1,1226 @@
+//! gRPC API - The Pipeline Over Protocol Buffers
+//!
+//! The `forgeimages.v1.ForgeImages` service of
+//! proto/forgeimages/v1/forgeimages.proto: `ListTemplates`,
+//! `ValidateAsset`, `CompileAsset` and the server-streaming `CompileBatch`.
+//!
+//! `proto` holds its messages, written to match the file field for field,
+//! and the tonic server and client build.rs generates for them. Every
+//! message converts to and from the serde type it mirrors without loss:
+//! base64 in JSON (`source_data`, `data_base64`) is bytes here, lengths
+//! are micrometres, enumerations are their JSON names, and free-form JSON
+//! (`params`, print override values, export specs) is JSON text. A request
+//! therefore hashes to the same job hash whichever API it arrived by.
+//!
+//! Failed calls are a `Status` with a code from the envelope's exit code
+//! and the encoded `proto::Error` as its details (see `error_of`). As with
+//! the HTTP API, validation and compiles run on the blocking pool, at most
+//! `concurrency` at a time.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Semaphore};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Code, Request, Response, Status};
+
+use crate::color::{ColorConversion, SpotColorUsage};
+use crate::error::{ErrorEnvelope, ErrorKind};
+use crate::exit_code;
+use crate::hashing::parse_strict;
+use crate::icc::EmbeddedProfile;
+use crate::imposition::{CellRecord, ImpositionRecord};
+use crate::bleed::TrimBox;
+use crate::encoding::EncodingProfile;
+use crate::pipeline::{
+    CompilationPipeline, CompileRequest, CompiledAsset, ExportError, ExportedFile, PipelineError, RequestRecord, SignerInfo,
+};
+use crate::print::{Marks, PrintOverride, PrintSpec};
+use crate::sink::StoredObject;
+use crate::templates::{ScalingRecord, Template};
+use crate::units::Length;
+use crate::validation::{AssetInput, ValidationResult, ValidationViolation};
+
+/// The `forgeimages.v1` messages, and the generated `forge_images_server`
+/// and `forge_images_client`
+pub mod proto {
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct ListTemplatesRequest {}
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ListTemplatesResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub templates: Vec<TemplateSummary>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct TemplateSummary {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(string, tag = "3")]
+        pub description: String,
+        #[prost(string, tag = "4")]
+        pub template_version: String,
+        #[prost(bool, tag = "5")]
+        pub deprecated: bool,
+        #[prost(string, repeated, tag = "6")]
+        pub tags: Vec<String>,
+        #[prost(string, tag = "7")]
+        pub content_hash: String,
+        #[prost(string, tag = "8")]
+        pub template_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ValidateAssetRequest {
+        #[prost(string, tag = "1")]
+        pub template_id: String,
+        #[prost(message, optional, tag = "2")]
+        pub input: Option<AssetInput>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CompileBatchRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub requests: Vec<CompileRequest>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CompileBatchItem {
+        #[prost(uint32, tag = "1")]
+        pub index: u32,
+        #[prost(oneof = "compile_batch_item::Result", tags = "2, 3")]
+        pub result: Option<compile_batch_item::Result>,
+    }
+
+    pub mod compile_batch_item {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Result {
+            #[prost(message, boxed, tag = "2")]
+            Asset(Box<super::CompiledAsset>),
+            #[prost(message, tag = "3")]
+            Error(super::Error),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Error {
+        #[prost(string, tag = "1")]
+        pub error_kind: String,
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(string, optional, tag = "3")]
+        pub template_id: Option<String>,
+        #[prost(message, optional, tag = "4")]
+        pub validation: Option<ValidationResult>,
+        #[prost(string, tag = "5")]
+        pub details_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct AssetInput {
+        #[prost(uint32, tag = "1")]
+        pub width: u32,
+        #[prost(uint32, tag = "2")]
+        pub height: u32,
+        #[prost(uint32, optional, tag = "3")]
+        pub color_count: Option<u32>,
+        #[prost(string, optional, tag = "4")]
+        pub format: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CompileRequest {
+        #[prost(string, tag = "1")]
+        pub template_id: String,
+        #[prost(message, optional, tag = "2")]
+        pub asset_input: Option<AssetInput>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub source_data: Option<Vec<u8>>,
+        #[prost(string, optional, tag = "4")]
+        pub source_path: Option<String>,
+        #[prost(uint64, optional, tag = "5")]
+        pub seed: Option<u64>,
+        #[prost(string, optional, tag = "6")]
+        pub prompt: Option<String>,
+        #[prost(btree_map = "string, string", tag = "7")]
+        pub params: BTreeMap<String, String>,
+        #[prost(message, optional, tag = "8")]
+        pub print_spec: Option<PrintSpec>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ValidationResult {
+        #[prost(bool, tag = "1")]
+        pub valid: bool,
+        #[prost(message, repeated, tag = "2")]
+        pub violations: Vec<ValidationViolation>,
+        #[prost(string, tag = "3")]
+        pub template_id: String,
+        #[prost(string, tag = "4")]
+        pub template_version: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ValidationViolation {
+        #[prost(string, tag = "1")]
+        pub rule: String,
+        #[prost(string, tag = "2")]
+        pub severity: String,
+        #[prost(string, tag = "3")]
+        pub message: String,
+        #[prost(string, optional, tag = "4")]
+        pub expected: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub actual: Option<String>,
+        #[prost(string, repeated, tag = "6")]
+        pub remediation: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PrintSpec {
+        #[prost(string, tag = "1")]
+        pub authority: String,
+        #[prost(uint32, tag = "2")]
+        pub dpi: u32,
+        #[prost(string, tag = "3")]
+        pub color_space: String,
+        #[prost(int64, tag = "4")]
+        pub bleed_um: i64,
+        #[prost(string, optional, tag = "5")]
+        pub icc_profile: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CompiledAsset {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub template_id: String,
+        #[prost(string, tag = "3")]
+        pub template_version: String,
+        #[prost(string, tag = "4")]
+        pub template_hash: String,
+        #[prost(string, tag = "5")]
+        pub engine_version: String,
+        #[prost(message, optional, tag = "6")]
+        pub created_at: Option<::prost_types::Timestamp>,
+        #[prost(string, tag = "7")]
+        pub hash_algorithm: String,
+        #[prost(string, tag = "8")]
+        pub hash_scheme: String,
+        #[prost(string, tag = "9")]
+        pub manifest_hash: String,
+        #[prost(string, tag = "10")]
+        pub job_hash: String,
+        #[prost(bool, tag = "11")]
+        pub job_hash_keyed: bool,
+        #[prost(string, optional, tag = "12")]
+        pub job_hash_key_id: Option<String>,
+        #[prost(message, optional, tag = "13")]
+        pub validation: Option<ValidationResult>,
+        #[prost(uint32, tag = "14")]
+        pub warning_count: u32,
+        #[prost(bool, tag = "15")]
+        pub has_warnings: bool,
+        #[prost(message, optional, tag = "16")]
+        pub encoding: Option<EncodingProfile>,
+        #[prost(string, optional, tag = "17")]
+        pub font_hash: Option<String>,
+        #[prost(message, optional, tag = "18")]
+        pub signer: Option<SignerInfo>,
+        #[prost(string, optional, tag = "19")]
+        pub source_hash: Option<String>,
+        #[prost(string, optional, tag = "20")]
+        pub normalized_source_hash: Option<String>,
+        #[prost(message, optional, tag = "21")]
+        pub request: Option<RequestRecord>,
+        #[prost(message, optional, tag = "22")]
+        pub print: Option<PrintSpec>,
+        #[prost(message, repeated, tag = "23")]
+        pub print_overrides: Vec<PrintOverride>,
+        #[prost(string, optional, tag = "24")]
+        pub print_profile: Option<String>,
+        #[prost(string, optional, tag = "25")]
+        pub exports_root: Option<String>,
+        #[prost(message, repeated, tag = "26")]
+        pub exports: Vec<ExportedFile>,
+        #[prost(message, repeated, tag = "27")]
+        pub export_errors: Vec<ExportError>,
+        #[prost(message, repeated, tag = "28")]
+        pub stored_objects: Vec<StoredObject>,
+        #[prost(string, tag = "29")]
+        pub config_hash: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct EncodingProfile {
+        #[prost(string, tag = "1")]
+        pub png_filter: String,
+        #[prost(string, tag = "2")]
+        pub png_compression: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SignerInfo {
+        #[prost(string, tag = "1")]
+        pub key_id: String,
+        #[prost(string, tag = "2")]
+        pub algorithm: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct RequestRecord {
+        #[prost(message, optional, tag = "1")]
+        pub asset_input: Option<AssetInput>,
+        #[prost(string, optional, tag = "2")]
+        pub source: Option<String>,
+        #[prost(uint64, optional, tag = "3")]
+        pub seed: Option<u64>,
+        #[prost(string, optional, tag = "4")]
+        pub prompt: Option<String>,
+        #[prost(btree_map = "string, string", tag = "5")]
+        pub params: BTreeMap<String, String>,
+        #[prost(message, optional, tag = "6")]
+        pub print_spec: Option<PrintSpec>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct PrintOverride {
+        #[prost(string, tag = "1")]
+        pub field: String,
+        #[prost(string, tag = "2")]
+        pub template_value: String,
+        #[prost(string, tag = "3")]
+        pub user_value: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ExportedFile {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, tag = "2")]
+        pub filename: String,
+        #[prost(string, tag = "3")]
+        pub format: String,
+        #[prost(uint32, repeated, tag = "4")]
+        pub size: Vec<u32>,
+        #[prost(message, optional, tag = "5")]
+        pub trim: Option<TrimBox>,
+        #[prost(message, optional, tag = "6")]
+        pub marks: Option<Marks>,
+        #[prost(bytes = "vec", tag = "7")]
+        pub data: Vec<u8>,
+        #[prost(string, tag = "8")]
+        pub hash: String,
+        #[prost(message, optional, tag = "9")]
+        pub scaling: Option<ScalingRecord>,
+        #[prost(message, optional, tag = "10")]
+        pub print: Option<PrintSpec>,
+        #[prost(message, optional, tag = "11")]
+        pub color_conversion: Option<ColorConversion>,
+        #[prost(message, optional, tag = "12")]
+        pub icc_profile: Option<EmbeddedProfile>,
+        #[prost(message, repeated, tag = "13")]
+        pub spot_colors: Vec<SpotColorUsage>,
+        #[prost(string, optional, tag = "14")]
+        pub pdf_standard: Option<String>,
+        #[prost(bool, tag = "15")]
+        pub proof: bool,
+        #[prost(bool, tag = "16")]
+        pub deliverable: bool,
+        #[prost(message, optional, tag = "17")]
+        pub imposition: Option<ImpositionRecord>,
+        #[prost(string, optional, tag = "18")]
+        pub deduplicated_from: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct TrimBox {
+        #[prost(uint32, repeated, tag = "1")]
+        pub size: Vec<u32>,
+        #[prost(uint32, repeated, tag = "2")]
+        pub offset: Vec<u32>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Marks {
+        #[prost(bool, tag = "1")]
+        pub crop: bool,
+        #[prost(bool, tag = "2")]
+        pub registration: bool,
+        #[prost(bool, tag = "3")]
+        pub color_bars: bool,
+        #[prost(int64, tag = "4")]
+        pub mark_length_um: i64,
+        #[prost(int64, tag = "5")]
+        pub mark_offset_um: i64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ScalingRecord {
+        #[prost(string, tag = "1")]
+        pub policy: String,
+        #[prost(string, tag = "2")]
+        pub decision: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ColorConversion {
+        #[prost(string, tag = "1")]
+        pub method: String,
+        #[prost(string, optional, tag = "2")]
+        pub profile: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct EmbeddedProfile {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub sha256: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SpotColorUsage {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub rgb_hex: String,
+        #[prost(uint32, repeated, tag = "3")]
+        pub cmyk: Vec<u32>,
+        #[prost(uint64, tag = "4")]
+        pub pixels: u64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ImpositionRecord {
+        #[prost(double, repeated, tag = "1")]
+        pub sheet_mm: Vec<f64>,
+        #[prost(uint32, tag = "2")]
+        pub rows: u32,
+        #[prost(uint32, tag = "3")]
+        pub columns: u32,
+        #[prost(double, tag = "4")]
+        pub gutter_mm: f64,
+        #[prost(bool, tag = "5")]
+        pub crop_marks: bool,
+        #[prost(message, repeated, tag = "6")]
+        pub cells: Vec<CellRecord>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct CellRecord {
+        #[prost(uint32, tag = "1")]
+        pub row: u32,
+        #[prost(uint32, tag = "2")]
+        pub column: u32,
+        #[prost(double, tag = "3")]
+        pub x_pt: f64,
+        #[prost(double, tag = "4")]
+        pub y_pt: f64,
+        #[prost(double, tag = "5")]
+        pub width_pt: f64,
+        #[prost(double, tag = "6")]
+        pub height_pt: f64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ExportError {
+        #[prost(string, tag = "1")]
+        pub export_id: String,
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(string, tag = "3")]
+        pub spec_json: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StoredObject {
+        #[prost(string, tag = "1")]
+        pub export_id: String,
+        #[prost(string, tag = "2")]
+        pub key: String,
+        #[prost(string, optional, tag = "3")]
+        pub etag: Option<String>,
+        #[prost(string, tag = "4")]
+        pub sha256: String,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/forgeimages.v1.ForgeImages.rs"));
+}
+
+/// A message that does not map onto its serde type
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{field}: {message}")]
+pub struct ConversionError {
+    /// The message field, e.g. `exports.data`
+    pub field: String,
+    pub message: String,
+}
+
+impl ConversionError {
+    fn new(field: &str, message: impl ToString) -> Self {
+        Self { field: field.to_string(), message: message.to_string() }
+    }
+
+    /// The same error, one message further out
+    fn within(mut self, parent: &str) -> Self {
+        self.field = format!("{}.{}", parent, self.field);
+        self
+    }
+}
+
+impl From<ConversionError> for ErrorEnvelope {
+    fn from(error: ConversionError) -> Self {
+        ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", error)).detail("field", &error.field)
+    }
+}
+
+// --- Field helpers ---
+
+fn required<T>(value: Option<T>, field: &str) -> Result<T, ConversionError> {
+    value.ok_or_else(|| ConversionError::new(field, "missing"))
+}
+
+/// A unit enum's JSON name
+fn name_of(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        other => unreachable!("not a unit enum: {:?}", other),
+    }
+}
+
+fn parse_name<T: DeserializeOwned>(name: String, field: &str) -> Result<T, ConversionError> {
+    serde_json::from_value(Value::String(name)).map_err(|e| ConversionError::new(field, e))
+}
+
+fn to_json_text(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("manifest values serialize")
+}
+
+/// JSON text, read as strictly as a CLI payload
+fn from_json_text<T: DeserializeOwned>(text: &str, field: &str) -> Result<T, ConversionError> {
+    let value = parse_strict(text).map_err(|e| ConversionError::new(field, e))?;
+    serde_json::from_value(value).map_err(|e| ConversionError::new(field, e))
+}
+
+fn array<T, const N: usize>(values: Vec<T>, field: &str) -> Result<[T; N], ConversionError> {
+    let len = values.len();
+    values.try_into().map_err(|_| ConversionError::new(field, format!("expected {} values, got {}", N, len)))
+}
+
+fn decode_base64(text: &str, field: &str) -> Result<Vec<u8>, ConversionError> {
+    STANDARD.decode(text).map_err(|e| ConversionError::new(field, e))
+}
+
+fn params_to_proto(params: &BTreeMap<String, Value>) -> BTreeMap<String, String> {
+    params.iter().map(|(key, value)| (key.clone(), value.to_string())).collect()
+}
+
+fn params_from_proto(params: BTreeMap<String, String>) -> Result<BTreeMap<String, Value>, ConversionError> {
+    params.into_iter()
+        .map(|(key, text)| Ok((key.clone(), from_json_text(&text, &format!("params[{}]", key))?)))
+        .collect()
+}
+
+/// Convert each message, naming the failing one by its index
+fn convert_all<A, B>(values: Vec<A>, field: &str, convert: impl Fn(A) -> Result<B, ConversionError>) -> Result<Vec<B>, ConversionError> {
+    values.into_iter()
+        .enumerate()
+        .map(|(index, value)| convert(value).map_err(|e| e.within(&format!("{}[{}]", field, index))))
+        .collect()
+}
+
+fn timestamp(at: &DateTime<Utc>) -> prost_types::Timestamp {
+    // Leap seconds carry nanos past 1e9, which an i32 still holds
+    prost_types::Timestamp { seconds: at.timestamp(), nanos: at.timestamp_subsec_nanos() as i32 }
+}
+
+fn date_time(at: prost_types::Timestamp, field: &str) -> Result<DateTime<Utc>, ConversionError> {
+    u32::try_from(at.nanos).ok()
+        .and_then(|nanos| DateTime::from_timestamp(at.seconds, nanos))
+        .ok_or_else(|| ConversionError::new(field, "timestamp out of range"))
+}
+
+// --- Requests ---
+
+impl From<&AssetInput> for proto::AssetInput {
+    fn from(input: &AssetInput) -> Self {
+        Self { width: input.width, height: input.height, color_count: input.color_count, format: input.format.clone() }
+    }
+}
+
+impl From<proto::AssetInput> for AssetInput {
+    fn from(input: proto::AssetInput) -> Self {
+        Self { width: input.width, height: input.height, color_count: input.color_count, format: input.format }
+    }
+}
+
+impl From<&PrintSpec> for proto::PrintSpec {
+    fn from(spec: &PrintSpec) -> Self {
+        Self {
+            authority: name_of(&spec.authority),
+            dpi: spec.dpi,
+            color_space: name_of(&spec.color_space),
+            bleed_um: spec.bleed.micrometres(),
+            icc_profile: spec.icc_profile.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::PrintSpec> for PrintSpec {
+    type Error = ConversionError;
+
+    fn try_from(spec: proto::PrintSpec) -> Result<Self, ConversionError> {
+        Ok(Self {
+            authority: parse_name(spec.authority, "authority")?,
+            dpi: spec.dpi,
+            color_space: parse_name(spec.color_space, "color_space")?,
+            bleed: Length::from_micrometres(spec.bleed_um),
+            icc_profile: spec.icc_profile,
+        })
+    }
+}
+
+/// Fails when `source_data` is not base64 or `source_path` not UTF-8
+impl TryFrom<&CompileRequest> for proto::CompileRequest {
+    type Error = ConversionError;
+
+    fn try_from(request: &CompileRequest) -> Result<Self, ConversionError> {
+        let source_path = request.source_path.as_ref()
+            .map(|path| path.to_str().map(str::to_string).ok_or_else(|| ConversionError::new("source_path", "not UTF-8")))
+            .transpose()?;
+        Ok(Self {
+            template_id: request.template_id.clone(),
+            asset_input: Some((&request.asset_input).into()),
+            source_data: request.source_data.as_deref().map(|data| decode_base64(data, "source_data")).transpose()?,
+            source_path,
+            seed: request.seed,
+            prompt: request.prompt.clone(),
+            params: params_to_proto(&request.params),
+            print_spec: request.print_spec.as_ref().map(Into::into),
+        })
+    }
+}
+
+impl TryFrom<proto::CompileRequest> for CompileRequest {
+    type Error = ConversionError;
+
+    fn try_from(request: proto::CompileRequest) -> Result<Self, ConversionError> {
+        Ok(Self {
+            template_id: request.template_id,
+            asset_input: required(request.asset_input, "asset_input")?.into(),
+            source_data: request.source_data.map(|data| STANDARD.encode(data)),
+            source_path: request.source_path.map(PathBuf::from),
+            seed: request.seed,
+            prompt: request.prompt,
+            params: params_from_proto(request.params)?,
+            print_spec: request.print_spec.map(PrintSpec::try_from).transpose().map_err(|e| e.within("print_spec"))?,
+        })
+    }
+}
+
+// --- Results ---
+
+impl From<&ValidationViolation> for proto::ValidationViolation {
+    fn from(violation: &ValidationViolation) -> Self {
+        Self {
+            rule: violation.rule.clone(),
+            severity: name_of(&violation.severity),
+            message: violation.message.clone(),
+            expected: violation.expected.clone(),
+            actual: violation.actual.clone(),
+            remediation: violation.remediation.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::ValidationViolation> for ValidationViolation {
+    type Error = ConversionError;
+
+    fn try_from(violation: proto::ValidationViolation) -> Result<Self, ConversionError> {
+        Ok(Self {
+            rule: violation.rule,
+            severity: parse_name(violation.severity, "severity")?,
+            message: violation.message,
+            expected: violation.expected,
+            actual: violation.actual,
+            remediation: violation.remediation,
+        })
+    }
+}
+
+impl From<&ValidationResult> for proto::ValidationResult {
+    fn from(result: &ValidationResult) -> Self {
+        Self {
+            valid: result.valid,
+            violations: result.violations.iter().map(Into::into).collect(),
+            template_id: result.template_id.clone(),
+            template_version: result.template_version.clone(),
+        }
+    }
+}
+
+impl TryFrom<proto::ValidationResult> for ValidationResult {
+    type Error = ConversionError;
+
+    fn try_from(result: proto::ValidationResult) -> Result<Self, ConversionError> {
+        Ok(Self {
+            valid: result.valid,
+            violations: convert_all(result.violations, "violations", ValidationViolation::try_from)?,
+            template_id: result.template_id,
+            template_version: result.template_version,
+        })
+    }
+}
+
+impl From<&ErrorEnvelope> for proto::Error {
+    fn from(envelope: &ErrorEnvelope) -> Self {
+        Self {
+            error_kind: envelope.kind.name().to_string(),
+            message: envelope.message.clone(),
+            template_id: envelope.template_id.clone(),
+            validation: envelope.validation.as_deref().map(Into::into),
+            details_json: to_json_text(&envelope.details),
+        }
+    }
+}
+
+/// The envelope the error was made from; its exit code is the kind's
+impl TryFrom<proto::Error> for ErrorEnvelope {
+    type Error = ConversionError;
+
+    fn try_from(error: proto::Error) -> Result<Self, ConversionError> {
+        let mut envelope = ErrorEnvelope::new(parse_name(error.error_kind, "error_kind")?, error.message);
+        envelope.template_id = error.template_id;
+        if let Some(validation) = error.validation {
+            envelope = envelope.with_validation(validation.try_into().map_err(|e: ConversionError| e.within("validation"))?);
+        }
+        envelope.details = from_json_text(&error.details_json, "details_json")?;
+        Ok(envelope)
+    }
+}
+
+impl From<&RequestRecord> for proto::RequestRecord {
+    fn from(record: &RequestRecord) -> Self {
+        Self {
+            asset_input: Some((&record.asset_input).into()),
+            source: record.source.as_ref().map(name_of),
+            seed: record.seed,
+            prompt: record.prompt.clone(),
+            params: params_to_proto(&record.params),
+            print_spec: record.print_spec.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<proto::RequestRecord> for RequestRecord {
+    type Error = ConversionError;
+
+    fn try_from(record: proto::RequestRecord) -> Result<Self, ConversionError> {
+        Ok(Self {
+            asset_input: required(record.asset_input, "asset_input")?.into(),
+            source: record.source.map(|source| parse_name(source, "source")).transpose()?,
+            seed: record.seed,
+            prompt: record.prompt,
+            params: params_from_proto(record.params)?,
+            print_spec: record.print_spec.map(PrintSpec::try_from).transpose().map_err(|e| e.within("print_spec"))?,
+        })
+    }
+}
+
+impl From<&Marks> for proto::Marks {
+    fn from(marks: &Marks) -> Self {
+        Self {
+            crop: marks.crop,
+            registration: marks.registration,
+            color_bars: marks.color_bars,
+            mark_length_um: marks.mark_length.micrometres(),
+            mark_offset_um: marks.mark_offset.micrometres(),
+        }
+    }
+}
+
+impl From<proto::Marks> for Marks {
+    fn from(marks: proto::Marks) -> Self {
+        Self {
+            crop: marks.crop,
+            registration: marks.registration,
+            color_bars: marks.color_bars,
+            mark_length: Length::from_micrometres(marks.mark_length_um),
+            mark_offset: Length::from_micrometres(marks.mark_offset_um),
+        }
+    }
+}
+
+impl From<&SpotColorUsage> for proto::SpotColorUsage {
+    fn from(spot: &SpotColorUsage) -> Self {
+        Self {
+            name: spot.name.clone(),
+            rgb_hex: spot.rgb_hex.clone(),
+            cmyk: spot.cmyk.iter().map(|&ink| ink.into()).collect(),
+            pixels: spot.pixels,
+        }
+    }
+}
+
+impl TryFrom<proto::SpotColorUsage> for SpotColorUsage {
+    type Error = ConversionError;
+
+    fn try_from(spot: proto::SpotColorUsage) -> Result<Self, ConversionError> {
+        let cmyk: [u32; 4] = array(spot.cmyk, "cmyk")?;
+        let ink = |value: u32| u8::try_from(value).map_err(|_| ConversionError::new("cmyk", format!("{} is over 255", value)));
+        Ok(Self {
+            name: spot.name,
+            rgb_hex: spot.rgb_hex,
+            cmyk: [ink(cmyk[0])?, ink(cmyk[1])?, ink(cmyk[2])?, ink(cmyk[3])?],
+            pixels: spot.pixels,
+        })
+    }
+}
+
+impl From<&ImpositionRecord> for proto::ImpositionRecord {
+    fn from(record: &ImpositionRecord) -> Self {
+        Self {
+            sheet_mm: record.sheet_mm.to_vec(),
+            rows: record.rows,
+            columns: record.columns,
+            gutter_mm: record.gutter_mm,
+            crop_marks: record.crop_marks,
+            cells: record.cells.iter()
+                .map(|cell| proto::CellRecord {
+                    row: cell.row,
+                    column: cell.column,
+                    x_pt: cell.x_pt,
+                    y_pt: cell.y_pt,
+                    width_pt: cell.width_pt,
+                    height_pt: cell.height_pt,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::ImpositionRecord> for ImpositionRecord {
+    type Error = ConversionError;
+
+    fn try_from(record: proto::ImpositionRecord) -> Result<Self, ConversionError> {
+        Ok(Self {
+            sheet_mm: array(record.sheet_mm, "sheet_mm")?,
+            rows: record.rows,
+            columns: record.columns,
+            gutter_mm: record.gutter_mm,
+            crop_marks: record.crop_marks,
+            cells: record.cells.into_iter()
+                .map(|cell| CellRecord {
+                    row: cell.row,
+                    column: cell.column,
+                    x_pt: cell.x_pt,
+                    y_pt: cell.y_pt,
+                    width_pt: cell.width_pt,
+                    height_pt: cell.height_pt,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Fails when `data_base64` is not base64
+impl TryFrom<&ExportedFile> for proto::ExportedFile {
+    type Error = ConversionError;
+
+    fn try_from(export: &ExportedFile) -> Result<Self, ConversionError> {
+        Ok(Self {
+            id: export.id.clone(),
+            filename: export.filename.clone(),
+            format: export.format.clone(),
+            size: export.size.to_vec(),
+            trim: export.trim.map(|trim| proto::TrimBox { size: trim.size.to_vec(), offset: trim.offset.to_vec() }),
+            marks: export.marks.as_ref().map(Into::into),
+            data: decode_base64(&export.data_base64, "data_base64")?,
+            hash: export.hash.clone(),
+            scaling: Some(proto::ScalingRecord {
+                policy: name_of(&export.scaling.policy),
+                decision: name_of(&export.scaling.decision),
+            }),
+            print: export.print.as_ref().map(Into::into),
+            color_conversion: export.color_conversion.as_ref().map(|conversion| proto::ColorConversion {
+                method: name_of(&conversion.method),
+                profile: conversion.profile.clone(),
+            }),
+            icc_profile: export.icc_profile.as_ref().map(|profile| proto::EmbeddedProfile {
+                name: profile.name.clone(),
+                sha256: profile.sha256.clone(),
+            }),
+            spot_colors: export.spot_colors.iter().map(Into::into).collect(),
+            pdf_standard: export.pdf_standard.as_ref().map(name_of),
+            proof: export.proof,
+            deliverable: export.deliverable,
+            imposition: export.imposition.as_ref().map(Into::into),
+            deduplicated_from: export.deduplicated_from.clone(),
+        })
+    }
+}
+
+impl TryFrom<proto::ExportedFile> for ExportedFile {
+    type Error = ConversionError;
+
+    fn try_from(export: proto::ExportedFile) -> Result<Self, ConversionError> {
+        let trim = export.trim
+            .map(|trim| Ok::<_, ConversionError>(TrimBox { size: array(trim.size, "size")?, offset: array(trim.offset, "offset")? }))
+            .transpose()
+            .map_err(|e| e.within("trim"))?;
+        let scaling = required(export.scaling, "scaling")?;
+        let color_conversion = export.color_conversion
+            .map(|conversion| {
+                Ok::<_, ConversionError>(ColorConversion {
+                    method: parse_name(conversion.method, "method")?,
+                    profile: conversion.profile,
+                })
+            })
+            .transpose()
+            .map_err(|e| e.within("color_conversion"))?;
+        Ok(Self {
+            id: export.id,
+            filename: export.filename,
+            format: export.format,
+            size: array(export.size, "size")?,
+            trim,
+            marks: export.marks.map(Into::into),
+            data_base64: STANDARD.encode(export.data),
+            hash: export.hash,
+            scaling: ScalingRecord {
+                policy: parse_name(scaling.policy, "scaling.policy")?,
+                decision: parse_name(scaling.decision, "scaling.decision")?,
+            },
+            print: export.print.map(PrintSpec::try_from).transpose().map_err(|e| e.within("print"))?,
+            color_conversion,
+            icc_profile: export.icc_profile.map(|profile| EmbeddedProfile { name: profile.name, sha256: profile.sha256 }),
+            spot_colors: convert_all(export.spot_colors, "spot_colors", SpotColorUsage::try_from)?,
+            pdf_standard: export.pdf_standard.map(|standard| parse_name(standard, "pdf_standard")).transpose()?,
+            proof: export.proof,
+            deliverable: export.deliverable,
+            imposition: export.imposition.map(ImpositionRecord::try_from).transpose().map_err(|e| e.within("imposition"))?,
+            deduplicated_from: export.deduplicated_from,
+        })
+    }
+}
+
+/// Fails only when an export's `data_base64` is not base64
+impl TryFrom<&CompiledAsset> for proto::CompiledAsset {
+    type Error = ConversionError;
+
+    fn try_from(asset: &CompiledAsset) -> Result<Self, ConversionError> {
+        Ok(Self {
+            id: asset.id.clone(),
+            template_id: asset.template_id.clone(),
+            template_version: asset.template_version.clone(),
+            template_hash: asset.template_hash.clone(),
+            engine_version: asset.engine_version.clone(),
+            created_at: Some(timestamp(&asset.created_at)),
+            hash_algorithm: name_of(&asset.hash_algorithm),
+            hash_scheme: name_of(&asset.hash_scheme),
+            manifest_hash: asset.manifest_hash.clone(),
+            job_hash: asset.job_hash.clone(),
+            job_hash_keyed: asset.job_hash_keyed,
+            job_hash_key_id: asset.job_hash_key_id.clone(),
+            validation: Some((&asset.validation).into()),
+            warning_count: asset.warning_count,
+            has_warnings: asset.has_warnings,
+            encoding: Some(proto::EncodingProfile {
+                png_filter: name_of(&asset.encoding.png_filter),
+                png_compression: name_of(&asset.encoding.png_compression),
+            }),
+            font_hash: asset.font_hash.clone(),
+            signer: asset.signer.as_ref().map(|signer| proto::SignerInfo {
+                key_id: signer.key_id.clone(),
+                algorithm: signer.algorithm.clone(),
+            }),
+            source_hash: asset.source_hash.clone(),
+            normalized_source_hash: asset.normalized_source_hash.clone(),
+            request: asset.request.as_ref().map(Into::into),
+            print: Some((&asset.print).into()),
+            print_overrides: asset.print_overrides.iter()
+                .map(|overridden| proto::PrintOverride {
+                    field: overridden.field.clone(),
+                    template_value: overridden.template_value.to_string(),
+                    user_value: overridden.user_value.to_string(),
+                })
+                .collect(),
+            print_profile: asset.print_profile.clone(),
+            exports_root: asset.exports_root.clone(),
+            exports: asset.exports.iter()
+                .enumerate()
+                .map(|(index, export)| export.try_into().map_err(|e: ConversionError| e.within(&format!("exports[{}]", index))))
+                .collect::<Result<_, _>>()?,
+            export_errors: asset.export_errors.iter()
+                .map(|error| proto::ExportError {
+                    export_id: error.export_id.clone(),
+                    message: error.message.clone(),
+                    spec_json: to_json_text(&error.spec),
+                })
+                .collect(),
+            stored_objects: asset.stored_objects.iter()
+                .map(|stored| proto::StoredObject {
+                    export_id: stored.export_id.clone(),
+                    key: stored.key.clone(),
+                    etag: stored.etag.clone(),
+                    sha256: stored.sha256.clone(),
+                })
+                .collect(),
+                   config_hash: asset.config_hash.clone(),
+        })
+    }
+}
+
+/// The manifest again; `metrics` and `canonical`, never serialized, are
+/// left at their defaults
+impl TryFrom<proto::CompiledAsset> for CompiledAsset {
+    type Error = ConversionError;
+
+    fn try_from(asset: proto::CompiledAsset) -> Result<Self, ConversionError> {
+        let encoding = required(asset.encoding, "encoding")?;
+        let print_override = |overridden: proto::PrintOverride| {
+            Ok(PrintOverride {
+                template_value: from_json_text(&overridden.template_value, "template_value")?,
+                user_value: from_json_text(&overridden.user_value, "user_value")?,
+                field: overridden.field,
+            })
+        };
+        let export_error = |error: proto::ExportError| {
+            Ok(ExportError { spec: from_json_text(&error.spec_json, "spec_json")?, export_id: error.export_id, message: error.message })
+        };
+        Ok(Self {
+            id: asset.id,
+            template_id: asset.template_id,
+            template_version: asset.template_version,
+            template_hash: asset.template_hash,
+            engine_version: asset.engine_version,
+            created_at: date_time(required(asset.created_at, "created_at")?, "created_at")?,
+            hash_algorithm: parse_name(asset.hash_algorithm, "hash_algorithm")?,
+            hash_scheme: parse_name(asset.hash_scheme, "hash_scheme")?,
+            manifest_hash: asset.manifest_hash,
+            job_hash: asset.job_hash,
+            job_hash_keyed: asset.job_hash_keyed,
+            job_hash_key_id: asset.job_hash_key_id,
+            validation: required(asset.validation, "validation")?.try_into().map_err(|e: ConversionError| e.within("validation"))?,
+            warning_count: asset.warning_count,
+            has_warnings: asset.has_warnings,
+            encoding: EncodingProfile {
+                png_filter: parse_name(encoding.png_filter, "encoding.png_filter")?,
+                png_compression: parse_name(encoding.png_compression, "encoding.png_compression")?,
+            },
+            config_hash: asset.config_hash,
+            font_hash: asset.font_hash,
+            signer: asset.signer.map(|signer| SignerInfo { key_id: signer.key_id, algorithm: signer.algorithm }),
+            source_hash: asset.source_hash,
+            normalized_source_hash: asset.normalized_source_hash,
+            request: asset.request.map(RequestRecord::try_from).transpose().map_err(|e| e.within("request"))?,
+            print: required(asset.print, "print")?.try_into().map_err(|e: ConversionError| e.within("print"))?,
+            print_overrides: convert_all(asset.print_overrides, "print_overrides", print_override)?,
+            print_profile: asset.print_profile,
+            exports_root: asset.exports_root,
+            exports: convert_all(asset.exports, "exports", ExportedFile::try_from)?,
+            export_errors: convert_all(asset.export_errors, "export_errors", export_error)?,
+            stored_objects: asset.stored_objects.into_iter()
+                .map(|stored| StoredObject { export_id: stored.export_id, key: stored.key, etag: stored.etag, sha256: stored.sha256 })
+                .collect(),
+            metrics: Default::default(),
+            canonical: Default::default(),
+        })
+    }
+}
+
+impl TryFrom<&Template> for proto::TemplateSummary {
+    type Error = ErrorEnvelope;
+
+    fn try_from(template: &Template) -> Result<Self, ErrorEnvelope> {
+        Ok(Self {
+            id: template.id.clone(),
+            name: template.name.clone(),
+            description: template.description.clone(),
+            template_version: template.template_version.clone(),
+            deprecated: template.deprecated,
+            tags: template.tags.clone(),
+            content_hash: template.content_hash().map_err(|e| ErrorEnvelope::from(PipelineError::from(e)))?,
+            template_json: serde_json::to_string(template).map_err(|e| ErrorEnvelope::from(PipelineError::from(e)))?,
+        })
+    }
+}
+
+// --- Service ---
+
+/// The `Status` for an envelope: a code from its exit code, the encoded
+/// `proto::Error` as details
+pub fn status_of(envelope: &ErrorEnvelope) -> Status {
+    let code = match envelope.exit_code() {
+        exit_code::VALIDATION_FAILED | exit_code::VERSION_MISMATCH | exit_code::FAILED_ON_WARNINGS => Code::FailedPrecondition,
+        exit_code::TEMPLATE_NOT_FOUND => Code::NotFound,
+        exit_code::INVALID_PAYLOAD => Code::InvalidArgument,
+        exit_code::CANCELLED => Code::Cancelled,
+        _ => Code::Internal,
+    };
+    Status::with_details(code, envelope.message.clone(), proto::Error::from(envelope).encode_to_vec().into())
+}
+
+/// The `proto::Error` a failed call carries, if the status came from this
+/// service
+pub fn error_of(status: &Status) -> Option<proto::Error> {
+    proto::Error::decode(status.details()).ok().filter(|error| !error.error_kind.is_empty())
+}
+
+/// Limits every server is started with
+#[derive(Debug, Clone, Copy)]
+pub struct GrpcOptions {
+    /// Validations and compiles in flight at once, across all calls
+    pub concurrency: usize,
+    /// Largest message accepted or sent, in bytes
+    pub max_message_bytes: usize,
+}
+
+/// `forgeimages.v1.ForgeImages` over a shared pipeline
+pub struct ForgeImagesService {
+    pipeline: Arc<CompilationPipeline>,
+    slots: Arc<Semaphore>,
+}
+
+/// The service, ready to add to a tonic server
+pub fn service(pipeline: Arc<CompilationPipeline>, options: GrpcOptions) -> proto::forge_images_server::ForgeImagesServer<ForgeImagesService> {
+    let service = ForgeImagesService { pipeline, slots: Arc::new(Semaphore::new(options.concurrency.max(1))) };
+    proto::forge_images_server::ForgeImagesServer::new(service)
+        .max_decoding_message_size(options.max_message_bytes)
+        .max_encoding_message_size(options.max_message_bytes)
+}
+
+/// Serve until the listener fails
+pub async fn serve(
+    listener: TcpListener,
+    pipeline: Arc<CompilationPipeline>,
+    options: GrpcOptions,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(service(pipeline, options))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+/// Run pipeline work on the blocking pool once a slot is free
+async fn run<T: Send + 'static>(
+    pipeline: &Arc<CompilationPipeline>,
+    slots: &Arc<Semaphore>,
+    work: impl FnOnce(&CompilationPipeline) -> T + Send + 'static,
+) -> Result<T, ErrorEnvelope> {
+    let internal = |e: String| ErrorEnvelope::new(ErrorKind::Internal, e);
+    let slot = slots.clone().acquire_owned().await.map_err(|e| internal(e.to_string()))?;
+    let pipeline = pipeline.clone();
+    tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        work(&pipeline)
+    })
+    .await
+    .map_err(|e| internal(e.to_string()))
+}
+
+/// One compile, as a message or the envelope of its failure
+async fn compile(
+    pipeline: &Arc<CompilationPipeline>,
+    slots: &Arc<Semaphore>,
+    request: proto::CompileRequest,
+) -> Result<proto::CompiledAsset, ErrorEnvelope> {
+    let template_id = request.template_id.clone();
+    let request = CompileRequest::try_from(request).map_err(|e| ErrorEnvelope::from(e).with_template_id(template_id))?;
+    run(pipeline, slots, move |pipeline| {
+        let asset = pipeline.compile_asset(&request).map_err(|e| pipeline.error_envelope(&request, &e))?;
+        proto::CompiledAsset::try_from(&asset).map_err(|e| ErrorEnvelope::new(ErrorKind::SerializationError, e.to_string()))
+    })
+    .await?
+}
+
+type CompileBatchStream = Pin<Box<dyn tokio_stream::Stream<Item = Result<proto::CompileBatchItem, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl proto::forge_images_server::ForgeImages for ForgeImagesService {
+    /// Every template, sorted by id
+    async fn list_templates(
+        &self,
+        _request: Request<proto::ListTemplatesRequest>,
+    ) -> Result<Response<proto::ListTemplatesResponse>, Status> {
+        let mut templates = self.pipeline.list_templates();
+        templates.sort_by(|a, b| a.id.cmp(&b.id));
+        let templates = templates.iter().map(proto::TemplateSummary::try_from).collect::<Result<_, _>>();
+        Ok(Response::new(proto::ListTemplatesResponse { templates: templates.map_err(|e| status_of(&e))? }))
+    }
+
+    async fn validate_asset(
+        &self,
+        request: Request<proto::ValidateAssetRequest>,
+    ) -> Result<Response<proto::ValidationResult>, Status> {
+        let request = request.into_inner();
+        let template_id = request.template_id;
+        let input: AssetInput = required(request.input, "input").map_err(|e| status_of(&e.into()))?.into();
+        let result = run(&self.pipeline, &self.slots, move |pipeline| pipeline.validate_asset(&template_id, &input))
+            .await
+            .map_err(|e| status_of(&e))?
+            .map_err(|e| status_of(&e.into()))?;
+        Ok(Response::new((&result).into()))
+    }
+
+    async fn compile_asset(
+        &self,
+        request: Request<proto::CompileRequest>,
+    ) -> Result<Response<proto::CompiledAsset>, Status> {
+        compile(&self.pipeline, &self.slots, request.into_inner())
+            .await
+            .map(Response::new)
+            .map_err(|e| status_of(&e))
+    }
+
+    type CompileBatchStream = CompileBatchStream;
+
+    /// Compiles run side by side, within the service's slots; items are
+    /// sent in request order
+    async fn compile_batch(
+        &self,
+        request: Request<proto::CompileBatchRequest>,
+    ) -> Result<Response<CompileBatchStream>, Status> {
+        let mut compiles: Vec<_> = request.into_inner().requests.into_iter()
+            .map(|request| {
+                let (pipeline, slots) = (self.pipeline.clone(), self.slots.clone());
+                tokio::spawn(async move { compile(&pipeline, &slots, request).await })
+            })
+            .collect();
+        let (items, stream) = mpsc::channel(4);
+        tokio::spawn(async move {
+            for (index, compile) in compiles.iter_mut().enumerate() {
+                let result = match compile.await {
+                    Ok(Ok(asset)) => proto::compile_batch_item::Result::Asset(Box::new(asset)),
+                    Ok(Err(envelope)) => proto::compile_batch_item::Result::Error((&envelope).into()),
+                    Err(e) => proto::compile_batch_item::Result::Error((&ErrorEnvelope::new(ErrorKind::Internal, e.to_string())).into()),
+                };
+                let item = proto::CompileBatchItem { index: index as u32, result: Some(result) };
+                if items.send(Ok(item)).await.is_err() {
+                    // The client went away; compiles still waiting for a slot need not run
+                    compiles.iter().for_each(|compile| compile.abort());
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(stream))))
+    }
+}
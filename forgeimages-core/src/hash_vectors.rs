@@ -0,0 +1,236 @@
+//! Hash Test Vectors - The Canonicalization Contract
+//!
+//! Fixed inputs with the canonical text and digests this engine produces for
+//! them, so other implementations (the Python bridge, third-party verifiers)
+//! can check themselves against it. The vectors are public contract: an
+//! expected value never changes under an existing `HashScheme`; changing one
+//! means adding a scheme. `tests/hash_vectors.rs` pins them.
+
+use serde::Serialize;
+
+use crate::hashing::HashScheme;
+
+/// One input and what hashing it must produce
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HashTestVector {
+    pub description: &'static str,
+    /// Scheme the expected values belong to
+    pub scheme: HashScheme,
+    /// JSON text as a verifier would receive it
+    pub input: &'static str,
+    /// `scheme.canonicalize` of the parsed input
+    pub canonical: &'static str,
+    /// sha256 of `canonical`, without domain separation
+    pub sha256: &'static str,
+    /// For manifests: the sha256 manifest hash (see `ManifestHashView`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_hash: Option<&'static str>,
+}
+
+/// (description, input, canonical, sha256, manifest_hash), all `fi-hash-1`
+type Vector = (&'static str, &'static str, &'static str, &'static str, Option<&'static str>);
+
+const FI_HASH_1: &[Vector] = &[
+    (
+        "empty object",
+        r#"{}"#,
+        r#"{}"#,
+        "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+        None,
+    ),
+    (
+        "empty containers at every depth",
+        r#"{"a":[],"b":{},"c":[{},[]],"d":{"e":{}}}"#,
+        r#"{"a":[],"b":{},"c":[{},[]],"d":{"e":{}}}"#,
+        "sha256:3e1886990c93ae54b8f8b564b36acd535625e9934920abcb52588bc64fe719ec",
+        None,
+    ),
+    (
+        "literals",
+        r#"{"t":true,"f":false,"n":null}"#,
+        r#"{"f":false,"n":null,"t":true}"#,
+        "sha256:22e00dc2f7b01420f940fbdbfbdf34fa0667cc6500186495023ba37722cbd05e",
+        None,
+    ),
+    (
+        "keys sort by code point",
+        r#"{"b":1,"a":2,"aa":3,"A":4,"":5,"a b":6}"#,
+        r#"{"":5,"A":4,"a":2,"a b":6,"aa":3,"b":1}"#,
+        "sha256:9dd87c118e454a79716244539d80cffad2c9bd0103489190ad1923f4b104125d",
+        None,
+    ),
+    (
+        "unicode keys",
+        r#"{"z":1,"é":2,"e":3,"日本":4,"😀":5}"#,
+        r#"{"e":3,"z":1,"é":2,"日本":4,"😀":5}"#,
+        "sha256:425cabeda6df060bd4eb22b64de62e1219acf4471ee6160614023de1204b0e1b",
+        None,
+    ),
+    (
+        "keys sort by UTF-8 bytes, not UTF-16 code units (RFC 8785 orders these two the other way)",
+        r#"{"😀":1,"｡":2}"#,
+        r#"{"｡":2,"😀":1}"#,
+        "sha256:d1d83ba6bff40585e1333a6ebe5e3eaf5edbc7ade11fc9578bb15a93583600f6",
+        None,
+    ),
+    (
+        "string escapes",
+        r#"{"s":"line\nquote\"tab\tback\\slash/ctrl\u0001\u001fdel\u007f\u00e9\u2028"}"#,
+        "{\"s\":\"line\\nquote\\\"tab\\tback\\\\slash/ctrl\\u0001\\u001fdel\u{7f}é\u{2028}\"}",
+        "sha256:e290f1063e1e56bd6263e5a644594ee97102936984d02807b39edfdb6c14755d",
+        None,
+    ),
+    (
+        "nested arrays",
+        r#"[[1,[2,[3,[]]]],{"a":[{"b":[null,true,false]}]},[[[[[]]]]]]"#,
+        r#"[[1,[2,[3,[]]]],{"a":[{"b":[null,true,false]}]},[[[[[]]]]]]"#,
+        "sha256:f817f94d420176a44bf4bf935c8b59c57330a6fb627e7e29e2b0779ad1725f2c",
+        None,
+    ),
+    (
+        "integers",
+        r#"[0,-1,42,9007199254740991,-9007199254740991]"#,
+        r#"[0,-1,42,9007199254740991,-9007199254740991]"#,
+        "sha256:4817afe8a2030d41925db08abb474e5defec15139e046f0086f53357ba4c6eb7",
+        None,
+    ),
+    (
+        "floats",
+        r#"[0.0,-0.0,1.0,-1.5,0.1,0.30000000000000004,1e21,1e20,1e-6,1e-7,123.456e5,5e-324,1.7976931348623157e308,-2.5e-8]"#,
+        r#"[0,0,1,-1.5,0.1,0.30000000000000004,1e+21,100000000000000000000,0.000001,1e-7,12345600,5e-324,1.7976931348623157e+308,-2.5e-8]"#,
+        "sha256:af974bee057ef010dd2c41eb2bbda5d5c5a3ae50bf5e56e8da06f30823f0665e",
+        None,
+    ),
+    (
+        "synthetic manifest: manifest_hash excludes itself and exports[].deduplicated_from",
+        r#"{
+  "id": "00000000-0000-4000-8000-000000000001",
+  "template_id": "test-icon",
+  "template_version": "1.0.0",
+  "template_hash": "sha256:abababababababababababababababababababababababababababababababab",
+  "engine_version": "1.0.0",
+  "created_at": "2026-01-01T00:00:00Z",
+  "hash_algorithm": "sha256",
+  "hash_scheme": "fi-hash-1",
+  "manifest_hash": "sha256:7f089629d6f846a4f0bcc5a6e6e669cf81d46287102cbaf9caf6378000628f36",
+  "job_hash": "sha256:cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd",
+  "validation": {
+    "valid": true,
+    "violations": [
+      {
+        "rule": "color_count",
+        "severity": "warning",
+        "message": "Uses 20 colors (max 16)",
+        "expected": "16",
+        "actual": "20",
+        "remediation": [
+          "Reduce the palette"
+        ]
+      }
+    ],
+    "template_id": "test-icon",
+    "template_version": "1.0.0"
+  },
+  "warning_count": 1,
+  "has_warnings": true,
+  "encoding": {
+    "png_compression": "fixed_huffman",
+    "png_filter": "up"
+  },
+  "font_hash": null,
+  "signer": null,
+  "source_hash": "sha256:efefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefef",
+  "normalized_source_hash": null,
+  "print": {
+    "authority": "system",
+    "bleed_inches": 0.125,
+    "color_space": "RGB",
+    "dpi": 300
+  },
+  "exports_root": "sha256:0101010101010101010101010101010101010101010101010101010101010101",
+  "exports": [
+    {
+      "id": "master",
+      "filename": "master.svg",
+      "format": "svg",
+      "size": [
+        1024,
+        1024
+      ],
+      "hash": "sha256:2323232323232323232323232323232323232323232323232323232323232323",
+      "data_base64": "PHN2Zy8+",
+      "scaling": {
+        "policy": "allow",
+        "decision": "vector_source"
+      }
+    },
+    {
+      "id": "icon-512",
+      "filename": "icon-512.png",
+      "format": "png",
+      "size": [
+        512,
+        512
+      ],
+      "hash": "sha256:4545454545454545454545454545454545454545454545454545454545454545",
+      "data_base64": "iVBORw0KGgo=",
+      "scaling": {
+        "policy": "allow",
+        "decision": "vector_source"
+      }
+    },
+    {
+      "id": "icon-512-copy",
+      "filename": "icon-512-copy.png",
+      "format": "png",
+      "size": [
+        512,
+        512
+      ],
+      "hash": "sha256:4545454545454545454545454545454545454545454545454545454545454545",
+      "data_base64": "iVBORw0KGgo=",
+      "scaling": {
+        "policy": "allow",
+        "decision": "vector_source"
+      },
+      "deduplicated_from": "icon-512"
+    }
+  ],
+  "export_errors": [
+    {
+      "export_id": "favicon",
+      "message": "ICO encoder unavailable",
+      "spec": {
+        "id": "favicon",
+        "description": "Browser favicon",
+        "size": [
+          32,
+          32
+        ],
+        "format": "ico",
+        "required": false,
+        "scalingPolicy": null
+      }
+    }
+  ]
+}"#,
+        r#"{"created_at":"2026-01-01T00:00:00Z","encoding":{"png_compression":"fixed_huffman","png_filter":"up"},"engine_version":"1.0.0","export_errors":[{"export_id":"favicon","message":"ICO encoder unavailable","spec":{"description":"Browser favicon","format":"ico","id":"favicon","required":false,"scalingPolicy":null,"size":[32,32]}}],"exports":[{"data_base64":"PHN2Zy8+","filename":"master.svg","format":"svg","hash":"sha256:2323232323232323232323232323232323232323232323232323232323232323","id":"master","scaling":{"decision":"vector_source","policy":"allow"},"size":[1024,1024]},{"data_base64":"iVBORw0KGgo=","filename":"icon-512.png","format":"png","hash":"sha256:4545454545454545454545454545454545454545454545454545454545454545","id":"icon-512","scaling":{"decision":"vector_source","policy":"allow"},"size":[512,512]},{"data_base64":"iVBORw0KGgo=","deduplicated_from":"icon-512","filename":"icon-512-copy.png","format":"png","hash":"sha256:4545454545454545454545454545454545454545454545454545454545454545","id":"icon-512-copy","scaling":{"decision":"vector_source","policy":"allow"},"size":[512,512]}],"exports_root":"sha256:0101010101010101010101010101010101010101010101010101010101010101","font_hash":null,"has_warnings":true,"hash_algorithm":"sha256","hash_scheme":"fi-hash-1","id":"00000000-0000-4000-8000-000000000001","job_hash":"sha256:cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd","manifest_hash":"sha256:7f089629d6f846a4f0bcc5a6e6e669cf81d46287102cbaf9caf6378000628f36","normalized_source_hash":null,"print":{"authority":"system","bleed_inches":0.125,"color_space":"RGB","dpi":300},"signer":null,"source_hash":"sha256:efefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefef","template_hash":"sha256:abababababababababababababababababababababababababababababababab","template_id":"test-icon","template_version":"1.0.0","validation":{"template_id":"test-icon","template_version":"1.0.0","valid":true,"violations":[{"actual":"20","expected":"16","message":"Uses 20 colors (max 16)","remediation":["Reduce the palette"],"rule":"color_count","severity":"warning"}]},"warning_count":1}"#,
+        "sha256:100486c9331a416a012a65fd31859b14c961fb99fc609e485a9de90f77d842e7",
+        Some("sha256:7f089629d6f846a4f0bcc5a6e6e669cf81d46287102cbaf9caf6378000628f36"),
+    ),
+];
+
+/// The fixed vector set, in a stable order
+pub fn test_vectors() -> Vec<HashTestVector> {
+    FI_HASH_1
+        .iter()
+        .map(|&(description, input, canonical, sha256, manifest_hash)| HashTestVector {
+            description,
+            scheme: HashScheme::V1,
+            input,
+            canonical,
+            sha256,
+            manifest_hash,
+        })
+        .collect()
+}
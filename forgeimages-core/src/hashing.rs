@@ -1,70 +1,1195 @@
 //! Hashing System - SHA-256 for Manifests
 //!
 //! Provides deterministic, reproducible hashes for legal defensibility.
+//!
+//! Stored digests name their algorithm: `sha256:<hex>` or `blake3:<hex>`.
+//! Unprefixed digests from older manifests are read as sha256.
+
+use std::fmt;
+use std::io::{self, Read, Write};
 
+use hmac::{Hmac, Mac};
 use sha2::{Sha256, Digest};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, to_string};
+use thiserror::Error;
+
+pub use crate::hash_vectors::{test_vectors, HashTestVector};
+pub use crate::ledger::{Ledger, LedgerEntry, LedgerError, LedgerSink};
+pub use crate::merkle::{exports_root, merkle_proof, verify_merkle_proof, MerkleProof, MerkleStep, Side};
 
 /// Compute SHA-256 hash of bytes, return hex string
 pub fn sha256_hex(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
+    let mut hasher = Hasher::new();
     hasher.update(data);
-    let result = hasher.finalize();
-    hex::encode(result)
+    hasher.finalize_hex()
+}
+
+/// SHA-256 of everything a reader yields, without buffering it whole
+pub fn sha256_hex_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Hasher::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize_hex())
+}
+
+/// Digest algorithm for everything a manifest records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Every algorithm this build can compute
+    pub const ALL: &'static [HashAlgorithm] = &[
+        HashAlgorithm::Sha256,
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3,
+    ];
+
+    /// Prefix used in stored digests
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        Hasher::with_algorithm(*self)
+    }
+
+    /// Prefixed digest of `data`
+    pub fn digest(&self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize_digest()
+    }
+
+    /// Prefixed digest of everything a reader yields
+    pub fn digest_reader(&self, mut reader: impl Read) -> io::Result<String> {
+        let mut hasher = self.hasher();
+        io::copy(&mut reader, &mut hasher)?;
+        Ok(hasher.finalize_digest())
+    }
+}
+
+/// Split a stored digest into its algorithm and hex; unprefixed is sha256
+pub fn parse_digest(digest: &str) -> Result<(HashAlgorithm, &str), HashingError> {
+    match digest.split_once(':') {
+        Some((prefix, hex)) => HashAlgorithm::from_prefix(prefix)
+            .map(|algorithm| (algorithm, hex))
+            .ok_or_else(|| HashingError::UnknownAlgorithm(prefix.to_string())),
+        None => Ok((HashAlgorithm::Sha256, digest)),
+    }
+}
+
+/// A digest that did not match; both sides truncated for logging
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Hash mismatch: expected {expected}, got {actual}")]
+pub struct HashMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
+impl HashMismatch {
+    /// Hex characters kept after the prefix
+    const SHOWN: usize = 12;
+
+    fn new(expected: &str, actual: &str) -> Self {
+        Self { expected: Self::truncate(expected), actual: Self::truncate(actual) }
+    }
+
+    fn truncate(digest: &str) -> String {
+        let hex_start = digest.find(':').map_or(0, |i| i + 1);
+        match digest.char_indices().nth(hex_start + Self::SHOWN) {
+            Some((end, _)) => format!("{}…", &digest[..end]),
+            None => digest.to_string(),
+        }
+    }
+}
+
+/// Compare without an early exit, so timing does not reveal the matching prefix
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Check that two stored digests name the same hash: same algorithm (legacy
+/// unprefixed is sha256), hex compared case-insensitively in constant time
+pub fn verify_digest(expected: &str, actual: &str) -> Result<(), HashMismatch> {
+    let bytes = |digest| parse_digest(digest).ok().and_then(|(alg, hex_part)| Some((alg, hex::decode(hex_part)?)));
+    match (bytes(expected), bytes(actual)) {
+        (Some((alg_e, hex_e)), Some((alg_a, hex_a))) if alg_e == alg_a && constant_time_eq(&hex_e, &hex_a) => Ok(()),
+        _ => Err(HashMismatch::new(expected, actual)),
+    }
+}
+
+/// Check that `data` hashes to `expected` under the digest's own algorithm.
+///
+/// An unknown prefix cannot match; the mismatch then reports the sha256 of `data`.
+pub fn verify_hex(expected: &str, data: &[u8]) -> Result<(), HashMismatch> {
+    let algorithm = parse_digest(expected).map(|(alg, _)| alg).unwrap_or_default();
+    verify_digest(expected, &algorithm.digest(data))
+}
+
+/// Whether two stored digests name the same hash (legacy form included)
+pub fn digests_equal(a: &str, b: &str) -> bool {
+    verify_digest(a, b).is_ok()
+}
+
+/// Whether `data` hashes to a stored digest, under the digest's own algorithm
+pub fn digest_matches(digest: &str, data: &[u8]) -> Result<bool, HashingError> {
+    parse_digest(digest)?;
+    Ok(verify_hex(digest, data).is_ok())
+}
+
+#[derive(Clone)]
+enum HasherState {
+    Sha256(Sha256),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// Incremental hasher (SHA-256 unless told otherwise); also an `io::Write`
+/// sink for `io::copy`
+#[derive(Clone)]
+pub struct Hasher(HasherState);
+
+impl Hasher {
+    pub fn new() -> Self {
+        Self::with_algorithm(HashAlgorithm::Sha256)
+    }
+
+    pub fn with_algorithm(algorithm: HashAlgorithm) -> Self {
+        Self(match algorithm {
+            HashAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+        })
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self.0 {
+            HasherState::Sha256(_) => HashAlgorithm::Sha256,
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(_) => HashAlgorithm::Blake3,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.0 {
+            HasherState::Sha256(hasher) => hasher.update(data),
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Raw digest bytes
+    pub(crate) fn finalize_bytes(self) -> Vec<u8> {
+        match self.0 {
+            HasherState::Sha256(hasher) => hasher.finalize().to_vec(),
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        }
+    }
+
+    /// Bare hex digest, as `sha256sum` prints it
+    pub fn finalize_hex(self) -> String {
+        hex::encode(self.finalize_bytes())
+    }
+
+    /// Algorithm-prefixed digest, as manifests store it
+    pub fn finalize_digest(self) -> String {
+        let mut digest = format!("{}:", self.algorithm().prefix());
+        hex::encode_to(&self.finalize_bytes(), &mut digest);
+        digest
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Hasher").field(&self.algorithm()).finish()
+    }
+}
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer that hashes exactly the bytes its inner writer accepts
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_algorithm(inner, HashAlgorithm::Sha256)
+    }
+
+    pub fn with_algorithm(inner: W, algorithm: HashAlgorithm) -> Self {
+        Self { inner, hasher: Hasher::with_algorithm(algorithm) }
+    }
+
+    /// The inner writer (unflushed) and the hash of everything written
+    pub fn finalize_hex(self) -> (W, String) {
+        (self.inner, self.hasher.finalize_hex())
+    }
+
+    /// The inner writer (unflushed) and the prefixed digest of everything written
+    pub fn finalize_digest(self) -> (W, String) {
+        (self.inner, self.hasher.finalize_digest())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum HashingError {
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// NaN or infinity at a JSON Pointer (RFC 6901) path; JSON cannot
+    /// represent it and serde_json would silently write `null`
+    #[error("Non-finite number at {0:?}")]
+    NonFiniteNumber(String),
+
+    /// Integer beyond `MAX_SAFE_INTEGER` that JavaScript would read as a
+    /// different number, at a JSON Pointer path
+    #[error("Integer at {0:?} exceeds 2^53-1, which JavaScript verifiers cannot represent; pass it as a decimal string")]
+    UnsafeInteger(String),
+
+    #[error("Unknown hash algorithm: {0}")]
+    UnknownAlgorithm(String),
+
+    #[error("Malformed digest: {0}")]
+    InvalidDigest(String),
+
+    #[error("Digests use different algorithms: {0}")]
+    MixedAlgorithms(String),
+
+    #[error("No export with id {0:?}")]
+    UnknownLeaf(String),
+}
+
+#[derive(Debug, Error)]
+pub enum StrictJsonError {
+    #[error("Invalid JSON: {0}")]
+    Syntax(String),
+
+    /// Key, then the JSON Pointer (RFC 6901) path of its second occurrence
+    #[error("Duplicate key {0:?} at {1:?}")]
+    DuplicateKey(String, String),
+}
+
+/// Parse JSON, rejecting any object that repeats a key.
+///
+/// `serde_json::from_str` keeps the last of duplicate keys, so the value
+/// hashed would not be what the author wrote.
+pub fn parse_strict(json: &str) -> Result<Value, StrictJsonError> {
+    let duplicate = std::cell::RefCell::new(None);
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let parsed = serde::de::DeserializeSeed::deserialize(strict::Seed { path: vec![], duplicate: &duplicate }, &mut deserializer)
+        .and_then(|value| deserializer.end().map(|_| value));
+    match (parsed, duplicate.into_inner()) {
+        (_, Some((key, path))) => Err(StrictJsonError::DuplicateKey(key, path)),
+        (Ok(value), None) => Ok(value),
+        (Err(e), None) => Err(StrictJsonError::Syntax(e.to_string())),
+    }
 }
 
 /// Convert to canonical JSON (sorted keys, no whitespace)
-pub fn canonical_json<T: Serialize>(value: &T) -> Result<String, serde_json::Error> {
+///
+/// Numbers use the ECMAScript `Number.prototype.toString` form (as in JCS,
+/// RFC 8785): shortest round-trip digits, no trailing `.0`, plain notation
+/// for exponents -7 < e < 21 and `1e+21` style beyond. `-0.0` becomes `0`.
+/// Integers beyond `MAX_SAFE_INTEGER` in magnitude are rejected unless
+/// JavaScript prints the same digits for them (as for `100000000000000000`,
+/// the canonical form of `1e17`): a verifier there could not reproduce the
+/// hash. Values that may be that large belong in strings (see `json_u64`).
+pub fn canonical_json<T: Serialize>(value: &T) -> Result<String, HashingError> {
+    check_numbers(value)?;
     let v: Value = serde_json::to_value(value)?;
-    let sorted = sort_value(&v);
-    to_string(&sorted)
+    let mut out = String::new();
+    write_canonical(&v, &mut out)?;
+    Ok(out)
 }
 
-fn sort_value(v: &Value) -> Value {
+/// `canonical_json` of an object, plus the byte offset where the value of
+/// top-level key `slot` starts (if present), for splicing it afterwards
+pub(crate) fn canonical_object_with_slot(
+    object: &serde_json::Map<String, Value>,
+    slot: &str,
+) -> Result<(String, Option<usize>), HashingError> {
+    check_numbers(object)?;
+    let mut sorted: Vec<_> = object.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    let mut out = String::from("{");
+    let mut offset = None;
+    for (i, (k, v)) in sorted.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&to_string(k)?);
+        out.push(':');
+        if k == slot {
+            offset = Some(out.len());
+        }
+        write_canonical(v, &mut out)?;
+    }
+    out.push('}');
+    Ok((out, offset))
+}
+
+/// Reject numbers canonical JSON cannot carry faithfully
+fn check_numbers<T: ?Sized + Serialize>(value: &T) -> Result<(), HashingError> {
+    // Custom serializer errors are left for serde_json to report
+    if let Err(found) = value.serialize(finite::Check) {
+        match (found.path(), found.problem) {
+            (Some(path), finite::Problem::NonFinite) => return Err(HashingError::NonFiniteNumber(path)),
+            (Some(path), finite::Problem::UnsafeInteger) => return Err(HashingError::UnsafeInteger(path)),
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_canonical(v: &Value, out: &mut String) -> Result<(), serde_json::Error> {
     match v {
         Value::Object(map) => {
             let mut sorted: Vec<_> = map.iter().collect();
             sorted.sort_by(|a, b| a.0.cmp(b.0));
-            let sorted_map: serde_json::Map<String, Value> = sorted
-                .into_iter()
-                .map(|(k, v)| (k.clone(), sort_value(v)))
-                .collect();
-            Value::Object(sorted_map)
+            out.push('{');
+            for (i, (k, v)) in sorted.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&to_string(k)?);
+                out.push(':');
+                write_canonical(v, out)?;
+            }
+            out.push('}');
         }
         Value::Array(arr) => {
-            Value::Array(arr.iter().map(sort_value).collect())
+            out.push('[');
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out)?;
+            }
+            out.push(']');
+        }
+        Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+            (Some(u), _, _) => out.push_str(&u.to_string()),
+            (_, Some(i), _) => out.push_str(&i.to_string()),
+            (_, _, Some(f)) => out.push_str(&format_f64(f)),
+            _ => out.push_str(&n.to_string()),
+        },
+        _ => out.push_str(&to_string(v)?),
+    }
+    Ok(())
+}
+
+/// ECMAScript Number-to-String for a finite f64
+fn format_f64(f: f64) -> String {
+    if f == 0.0 {
+        return "0".to_string();
+    }
+    // `{:e}` gives the shortest round-trip digits: d[.ddd]e<exp>
+    let sci = format!("{:e}", f.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exp.parse::<i32>().unwrap_or(0) + 1;
+
+    let body = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let sign = if n - 1 < 0 { '-' } else { '+' };
+        match digits.split_at(1) {
+            (first, "") => format!("{}e{}{}", first, sign, (n - 1).abs()),
+            (first, rest) => format!("{}.{}e{}{}", first, rest, sign, (n - 1).abs()),
+        }
+    };
+    if f < 0.0 { format!("-{}", body) } else { body }
+}
+
+/// Prefix of every job hash preimage from scheme `fi-hash-1` on
+pub const JOB_HASH_DOMAIN: &[u8] = b"forgeimages:job:v1\0";
+
+/// Prefix of every manifest hash preimage from scheme `fi-hash-1` on
+pub const MANIFEST_HASH_DOMAIN: &[u8] = b"forgeimages:manifest:v1\0";
+
+/// How job and manifest hash preimages are built.
+///
+/// Manifests record theirs as `hash_scheme`; one without it predates
+/// schemes and is `fi-hash-0`. The digest algorithm is independent of the
+/// scheme (see `HashAlgorithm`). Never change a released scheme; add one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum HashScheme {
+    /// Sorted keys with serde_json's own number text (`1.0`, `1e21`, NaN as
+    /// `null`); no domain separation
+    #[default]
+    #[serde(rename = "fi-hash-0")]
+    V0,
+    /// `canonical_json` (JCS number text, non-finite rejected), with
+    /// `JOB_HASH_DOMAIN` / `MANIFEST_HASH_DOMAIN` prepended
+    #[serde(rename = "fi-hash-1")]
+    V1,
+}
+
+impl HashScheme {
+    /// Scheme for new compiles
+    pub const CURRENT: HashScheme = HashScheme::V1;
+
+    /// Every scheme manifests can be verified under, oldest first
+    pub const ALL: [HashScheme; 2] = [HashScheme::V0, HashScheme::V1];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashScheme::V0 => "fi-hash-0",
+            HashScheme::V1 => "fi-hash-1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fi-hash-0" => Some(HashScheme::V0),
+            "fi-hash-1" => Some(HashScheme::V1),
+            _ => None,
+        }
+    }
+
+    fn domains(self) -> (&'static [u8], &'static [u8]) {
+        match self {
+            HashScheme::V0 => (b"", b""),
+            HashScheme::V1 => (JOB_HASH_DOMAIN, MANIFEST_HASH_DOMAIN),
         }
-        _ => v.clone()
+    }
+
+    /// Canonical text of `value` under this scheme
+    pub fn canonicalize<T: Serialize>(self, value: &T) -> Result<String, HashingError> {
+        match self {
+            // serde_json's map is key-ordered, so plain `to_string` sorts
+            HashScheme::V0 => Ok(to_string(&serde_json::to_value(value)?)?),
+            HashScheme::V1 => canonical_json(value),
+        }
+    }
+
+    /// Manifest hash under this scheme (prefixed digest)
+    pub fn manifest_hash<T: Serialize>(self, algorithm: HashAlgorithm, manifest: &T) -> Result<String, HashingError> {
+        Ok(self.manifest_hash_of_canonical(algorithm, &self.canonicalize(manifest)?))
+    }
+
+    /// Manifest hash of text already canonicalized under this scheme
+    pub(crate) fn manifest_hash_of_canonical(self, algorithm: HashAlgorithm, canonical: &str) -> String {
+        let mut hasher = algorithm.hasher();
+        hasher.update(self.domains().1);
+        hasher.update(canonical.as_bytes());
+        hasher.finalize_digest()
+    }
+
+    /// Job hash under this scheme (prefixed digest)
+    pub fn job_hash(
+        self,
+        algorithm: HashAlgorithm,
+        template_id: &str,
+        template_version: &str,
+        payload: &impl Serialize,
+        engine_version: &str,
+    ) -> Result<String, HashingError> {
+        let combined = self.job_preimage(template_id, template_version, payload, engine_version)?;
+        let mut hasher = algorithm.hasher();
+        hasher.update(self.domains().0);
+        hasher.update(combined.as_bytes());
+        Ok(hasher.finalize_digest())
+    }
+
+    /// HMAC-SHA256 of the job hash preimage under `key` (`sha256:` prefixed,
+    /// whatever the manifest's algorithm)
+    pub fn keyed_job_hash(
+        self,
+        key: &JobHashKey,
+        template_id: &str,
+        template_version: &str,
+        payload: &impl Serialize,
+        engine_version: &str,
+    ) -> Result<String, HashingError> {
+        let combined = self.job_preimage(template_id, template_version, payload, engine_version)?;
+        let mac = hmac_sha256(&key.secret).chain_update(self.domains().0).chain_update(combined).finalize();
+        let mut digest = format!("{}:", HashAlgorithm::Sha256.prefix());
+        hex::encode_to(&mac.into_bytes(), &mut digest);
+        Ok(digest)
+    }
+
+    fn job_preimage(
+        self,
+        template_id: &str,
+        template_version: &str,
+        payload: &impl Serialize,
+        engine_version: &str,
+    ) -> Result<String, HashingError> {
+        let canonical_payload = self.canonicalize(payload)?;
+        Ok(format!("{}:{}:{}:{}", template_id, template_version, canonical_payload, engine_version))
     }
 }
 
-/// Compute manifest hash for an asset
-pub fn compute_manifest_hash<T: Serialize>(manifest: &T) -> Result<String, serde_json::Error> {
-    let canonical = canonical_json(manifest)?;
-    Ok(sha256_hex(canonical.as_bytes()))
+/// Secret for keyed job hashes.
+///
+/// Plain job hashes are digests of guessable inputs, so anyone who sees one
+/// can confirm a guess at what was compiled. Keyed job hashes are
+/// HMAC-SHA256 under this secret; manifests record only `key_id`.
+#[derive(Clone)]
+pub struct JobHashKey {
+    key_id: String,
+    secret: Vec<u8>,
+}
+
+impl JobHashKey {
+    pub fn new(key_id: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self { key_id: key_id.into(), secret: secret.into() }
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
+
+impl fmt::Debug for JobHashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobHashKey").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104) keyed with `key`, ready for `update`
+pub(crate) fn hmac_sha256(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// Compute manifest hash for an asset (current scheme, sha256)
+pub fn compute_manifest_hash<T: Serialize>(manifest: &T) -> Result<String, HashingError> {
+    compute_manifest_hash_with(HashAlgorithm::Sha256, manifest)
+}
+
+/// Manifest hash under a chosen algorithm (current scheme, prefixed digest)
+pub fn compute_manifest_hash_with<T: Serialize>(
+    algorithm: HashAlgorithm,
+    manifest: &T,
+) -> Result<String, HashingError> {
+    HashScheme::CURRENT.manifest_hash(algorithm, manifest)
 }
 
 /// Compute job hash for audit logging
-/// job_hash = sha256(template_id + template_version + canonical_payload + engine_version)
+/// job_hash = sha256(domain + template_id + template_version + canonical_payload + engine_version)
 pub fn compute_job_hash(
     template_id: &str,
     template_version: &str,
     payload: &impl Serialize,
     engine_version: &str,
-) -> Result<String, serde_json::Error> {
-    let canonical_payload = canonical_json(payload)?;
-    let combined = format!(
-        "{}:{}:{}:{}",
-        template_id, template_version, canonical_payload, engine_version
-    );
-    Ok(sha256_hex(combined.as_bytes()))
+) -> Result<String, HashingError> {
+    compute_job_hash_with(HashAlgorithm::Sha256, template_id, template_version, payload, engine_version)
+}
+
+/// Job hash under a chosen algorithm (current scheme, prefixed digest)
+pub fn compute_job_hash_with(
+    algorithm: HashAlgorithm,
+    template_id: &str,
+    template_version: &str,
+    payload: &impl Serialize,
+    engine_version: &str,
+) -> Result<String, HashingError> {
+    HashScheme::CURRENT.job_hash(algorithm, template_id, template_version, payload, engine_version)
+}
+
+/// Largest integer every JSON consumer reads exactly (2^53 - 1, JavaScript's
+/// `Number.MAX_SAFE_INTEGER`)
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Serde for `Option<u64>` fields that may exceed `MAX_SAFE_INTEGER`.
+///
+/// Values up to it are written as numbers, larger ones as decimal strings;
+/// either form is read back, so a value means the same whichever way a
+/// client sent it.
+pub mod json_u64 {
+    use std::fmt;
+
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    use super::MAX_SAFE_INTEGER;
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(v) if *v > MAX_SAFE_INTEGER => serializer.serialize_str(&v.to_string()),
+            Some(v) => serializer.serialize_u64(*v),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "anyOf": [
+                { "type": "integer", "minimum": 0 },
+                { "type": "string", "pattern": "^[0-9]+$" },
+                { "type": "null" },
+            ]
+        })
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an unsigned integer, as a number or a decimal string")
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(U64Visitor).map(Some)
+        }
+    }
+
+    struct U64Visitor;
+
+    impl Visitor<'_> for U64Visitor {
+        type Value = u64;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an unsigned integer, as a number or a decimal string")
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<u64, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<u64, E> {
+            u64::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+            // Plain decimal only: no sign, no whitespace
+            if v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(E::invalid_value(de::Unexpected::Str(v), &self));
+            }
+            v.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+        }
+    }
+}
+
+/// `Value` deserialization that tracks its path and stops at a repeated key
+pub(crate) mod strict {
+    use std::cell::RefCell;
+    use std::fmt;
+
+    use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+    use serde_json::{Map, Number, Value};
+
+    pub(crate) struct Seed<'a> {
+        pub(crate) path: Vec<String>,
+        /// Set to (key, pointer) on the first duplicate
+        pub(crate) duplicate: &'a RefCell<Option<(String, String)>>,
+    }
+
+    impl Seed<'_> {
+        fn under(&self, segment: String) -> Self {
+            let mut path = self.path.clone();
+            path.push(segment);
+            Seed { path, duplicate: self.duplicate }
+        }
+    }
+
+    fn pointer(path: &[String]) -> String {
+        path.iter().map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1"))).collect()
+    }
+
+    impl<'de> DeserializeSeed<'de> for Seed<'_> {
+        type Value = Value;
+
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for Seed<'_> {
+        type Value = Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a JSON value")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+            Ok(Value::Bool(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+            Ok(Value::Number(v.into()))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+            Ok(Value::Number(v.into()))
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+            Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+            Ok(Value::String(v.to_string()))
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<Value, E> {
+            Ok(Value::String(v))
+        }
+
+        /// Binary formats' byte strings (MessagePack `bin`) read as the
+        /// base64 string JSON carries bytes as
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+            Ok(Value::String(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, v)))
+        }
+
+        fn visit_unit<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_none<E>(self) -> Result<Value, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+            let mut values = vec![];
+            while let Some(value) = seq.next_element_seed(self.under(values.len().to_string()))? {
+                values.push(value);
+            }
+            Ok(Value::Array(values))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+            let mut object = Map::new();
+            // Keys must be strings, whatever format the map comes from
+            while let Some(key) = map.next_key::<String>()? {
+                let seed = self.under(key.clone());
+                if object.contains_key(&key) {
+                    *self.duplicate.borrow_mut() = Some((key.clone(), pointer(&seed.path)));
+                    return Err(de::Error::custom(format!("duplicate key {:?}", key)));
+                }
+                let value = map.next_value_seed(seed)?;
+                object.insert(key, value);
+            }
+            Ok(Value::Object(object))
+        }
+    }
+}
+
+/// Walks a value as serde would serialize it, failing on the first
+/// non-finite float with the path to it
+mod finite {
+    use std::fmt;
+
+    use serde::ser::{self, Serialize};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(super) enum Problem {
+        NonFinite,
+        /// Beyond `MAX_SAFE_INTEGER` in magnitude
+        UnsafeInteger,
+    }
+
+    #[derive(Debug)]
+    pub(super) struct Found {
+        /// Innermost segment first; `None` for a custom serializer error
+        segments: Option<Vec<String>>,
+        pub(super) problem: Problem,
+    }
+
+    impl Found {
+        fn under(mut self, segment: impl Into<String>) -> Self {
+            if let Some(segments) = &mut self.segments {
+                segments.push(segment.into());
+            }
+            self
+        }
+
+        /// JSON Pointer to the offending number
+        pub(super) fn path(&self) -> Option<String> {
+            let segments = self.segments.as_ref()?;
+            Some(segments.iter().rev()
+                .map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1")))
+                .collect())
+        }
+    }
+
+    impl fmt::Display for Found {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.problem {
+                Problem::NonFinite => write!(f, "non-finite number at {:?}", self.path()),
+                Problem::UnsafeInteger => write!(f, "unsafe integer at {:?}", self.path()),
+            }
+        }
+    }
+
+    impl std::error::Error for Found {}
+
+    impl ser::Error for Found {
+        fn custom<T: fmt::Display>(_msg: T) -> Self {
+            Self { segments: None, problem: Problem::NonFinite }
+        }
+    }
+
+    type Checked = Result<(), Found>;
+
+    fn check_float(finite: bool) -> Checked {
+        if finite { Ok(()) } else { Err(Found { segments: Some(vec![]), problem: Problem::NonFinite }) }
+    }
+
+    /// Safe integers, and larger ones JavaScript prints with the same
+    /// digits (the integral floats canonical JSON writes, like `1e17`)
+    fn check_integer(magnitude: u128) -> Checked {
+        let as_f64 = magnitude as f64;
+        let reproducible = magnitude <= super::MAX_SAFE_INTEGER as u128
+            || (as_f64 as u128 == magnitude && super::format_f64(as_f64) == magnitude.to_string());
+        if reproducible {
+            Ok(())
+        } else {
+            Err(Found { segments: Some(vec![]), problem: Problem::UnsafeInteger })
+        }
+    }
+
+    fn key_text<T: ?Sized + Serialize>(key: &T) -> String {
+        match serde_json::to_value(key) {
+            Ok(serde_json::Value::String(s)) => s,
+            Ok(other) => other.to_string(),
+            Err(_) => "?".to_string(),
+        }
+    }
+
+    pub(super) struct Check;
+
+    /// Sequence, tuple, map or struct body; `variant` wraps enum variants
+    pub(super) struct Compound {
+        index: usize,
+        key: Option<String>,
+        variant: Option<&'static str>,
+    }
+
+    impl Compound {
+        fn new(variant: Option<&'static str>) -> Self {
+            Self { index: 0, key: None, variant }
+        }
+
+        fn wrap(&self, found: Found, segment: String) -> Found {
+            let found = found.under(segment);
+            match self.variant {
+                Some(variant) => found.under(variant),
+                None => found,
+            }
+        }
+
+        fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked {
+            let index = self.index;
+            self.index += 1;
+            value.serialize(Check).map_err(|e| self.wrap(e, index.to_string()))
+        }
+
+        fn field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Checked {
+            value.serialize(Check).map_err(|e| self.wrap(e, key.to_string()))
+        }
+    }
+
+    macro_rules! accept {
+        ($($method:ident($ty:ty)),* $(,)?) => {
+            $(fn $method(self, _v: $ty) -> Checked { Ok(()) })*
+        };
+    }
+
+    impl ser::Serializer for Check {
+        type Ok = ();
+        type Error = Found;
+        type SerializeSeq = Compound;
+        type SerializeTuple = Compound;
+        type SerializeTupleStruct = Compound;
+        type SerializeTupleVariant = Compound;
+        type SerializeMap = Compound;
+        type SerializeStruct = Compound;
+        type SerializeStructVariant = Compound;
+
+        accept!(
+            serialize_bool(bool), serialize_i8(i8), serialize_i16(i16), serialize_i32(i32),
+            serialize_u8(u8), serialize_u16(u16), serialize_u32(u32), serialize_char(char),
+            serialize_str(&str), serialize_bytes(&[u8]), serialize_unit_struct(&'static str),
+        );
+
+        fn serialize_i64(self, v: i64) -> Checked { check_integer(v.unsigned_abs() as u128) }
+        fn serialize_i128(self, v: i128) -> Checked { check_integer(v.unsigned_abs()) }
+        fn serialize_u64(self, v: u64) -> Checked { check_integer(v as u128) }
+        fn serialize_u128(self, v: u128) -> Checked { check_integer(v) }
+
+        fn serialize_f32(self, v: f32) -> Checked { check_float(v.is_finite()) }
+        fn serialize_f64(self, v: f64) -> Checked { check_float(v.is_finite()) }
+        fn serialize_none(self) -> Checked { Ok(()) }
+        fn serialize_unit(self) -> Checked { Ok(()) }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Checked {
+            value.serialize(Check)
+        }
+
+        fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Checked {
+            Ok(())
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Checked {
+            value.serialize(Check)
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Checked {
+            value.serialize(Check).map_err(|e| e.under(variant))
+        }
+
+        fn serialize_seq(self, _len: Option<usize>) -> Result<Compound, Found> { Ok(Compound::new(None)) }
+        fn serialize_tuple(self, _len: usize) -> Result<Compound, Found> { Ok(Compound::new(None)) }
+        fn serialize_map(self, _len: Option<usize>) -> Result<Compound, Found> { Ok(Compound::new(None)) }
+
+        fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound, Found> {
+            Ok(Compound::new(None))
+        }
+
+        fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound, Found> {
+            Ok(Compound::new(None))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Compound, Found> {
+            Ok(Compound::new(Some(variant)))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Compound, Found> {
+            Ok(Compound::new(Some(variant)))
+        }
+    }
+
+    impl ser::SerializeSeq for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked { self.element(value) }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeTuple for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked { self.element(value) }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeTupleStruct for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked { self.element(value) }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeTupleVariant for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked { self.element(value) }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeStruct for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Checked {
+            self.field(key, value)
+        }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeStructVariant for Compound {
+        type Ok = ();
+        type Error = Found;
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Checked {
+            self.field(key, value)
+        }
+        fn end(self) -> Checked { Ok(()) }
+    }
+
+    impl ser::SerializeMap for Compound {
+        type Ok = ();
+        type Error = Found;
+
+        fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Checked {
+            self.key = Some(key_text(key));
+            Ok(())
+        }
+
+        fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Checked {
+            let key = self.key.take().unwrap_or_default();
+            value.serialize(Check).map_err(|e| self.wrap(e, key))
+        }
+
+        // Keys are only rendered into text when a value fails
+        fn serialize_entry<K: ?Sized + Serialize, V: ?Sized + Serialize>(&mut self, key: &K, value: &V) -> Checked {
+            value.serialize(Check).map_err(|e| self.wrap(e, key_text(key)))
+        }
+
+        fn end(self) -> Checked { Ok(()) }
+    }
 }
 
 // We need hex encoding
-mod hex {
-    pub fn encode(bytes: impl AsRef<[u8]>) -> String {
-        bytes.as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+/// Lowercase hex without per-byte allocation; decoding accepts either case
+pub(crate) mod hex {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    pub(crate) fn encode(bytes: impl AsRef<[u8]>) -> String {
+        let bytes = bytes.as_ref();
+        let mut out = String::with_capacity(bytes.len() * 2);
+        encode_to(bytes, &mut out);
+        out
+    }
+
+    /// Append the hex of `bytes` to `out`
+    pub(crate) fn encode_to(bytes: &[u8], out: &mut String) {
+        out.reserve(bytes.len() * 2);
+        for &b in bytes {
+            out.push(DIGITS[(b >> 4) as usize] as char);
+            out.push(DIGITS[(b & 0x0f) as usize] as char);
+        }
+    }
+
+    fn nibble(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    /// Bytes of an even-length hex string; `None` on any other character
+    pub(crate) fn decode(hex: &str) -> Option<Vec<u8>> {
+        let hex = hex.as_bytes();
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        hex.chunks_exact(2)
+            .map(|pair| Some(nibble(pair[0])? << 4 | nibble(pair[1])?))
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn reference(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+
+        #[test]
+        fn test_round_trip_matrix() {
+            let large: Vec<u8> = (0..1 << 20).map(|i: u32| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+            let every_byte: Vec<u8> = (0..=255).collect();
+            for bytes in [&[][..], &[0x00], &[0xff], &[0x5a], &every_byte, &large] {
+                let hex = encode(bytes);
+                assert_eq!(hex, reference(bytes), "len {}", bytes.len());
+                assert_eq!(decode(&hex).as_deref(), Some(bytes));
+                assert_eq!(decode(&hex.to_uppercase()).as_deref(), Some(bytes));
+            }
+        }
+
+        #[test]
+        fn test_encode_to_appends() {
+            let mut out = "sha256:".to_string();
+            encode_to(&[0xde, 0xad], &mut out);
+            assert_eq!(out, "sha256:dead");
+        }
+
+        #[test]
+        fn test_decode_rejects_malformed() {
+            for bad in ["0", "abc", "zz", "+f", "0x", "é0", " 0"] {
+                assert_eq!(decode(bad), None, "{:?}", bad);
+            }
+        }
     }
 }
 
@@ -73,6 +1198,119 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    /// Official BLAKE3 test vectors (input byte i is i % 251), checked
+    /// through `Hasher` in one call and in 7-byte pieces
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_blake3_vectors() {
+        let cases = [
+            (0, "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"),
+            (1, "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"),
+            (1023, "10108970eeda3eb932baac1428c7a2163b0e924c9a9e25b35bba72b28f70bd11"),
+            (1024, "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"),
+            (1025, "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"),
+            (2048, "e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a"),
+            (2049, "5f4d72f40d7a5f82b15ca2b2e44b1de3c2ef86c426c95c1af0b6879522563030"),
+            (3072, "b98cb0ff3623be03326b373de6b9095218513e64f1ee2edd2525c7ad1e5cffd2"),
+        ];
+        for (len, expected) in cases {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut whole = Hasher::with_algorithm(HashAlgorithm::Blake3);
+            whole.update(&data);
+            let mut pieces = Hasher::with_algorithm(HashAlgorithm::Blake3);
+            for chunk in data.chunks(7) {
+                pieces.update(chunk);
+            }
+            assert_eq!(whole.finalize_digest(), format!("blake3:{}", expected), "len {}", len);
+            assert_eq!(pieces.finalize_hex(), expected, "len {}", len);
+        }
+    }
+
+    #[test]
+    fn test_unsafe_integers_are_rejected_with_their_path() {
+        for (value, path) in [
+            (json!({"a": [1, MAX_SAFE_INTEGER + 2]}), "/a/1"),
+            (json!({"n": i64::MIN}), "/n"),
+            (json!([1u64 << 60]), "/0"),
+            (json!(u64::MAX), ""),
+        ] {
+            match canonical_json(&value) {
+                Err(HashingError::UnsafeInteger(p)) => assert_eq!(p, path, "{}", value),
+                other => panic!("{}: {:?}", value, other),
+            }
+        }
+        let max = MAX_SAFE_INTEGER as i64;
+        assert_eq!(canonical_json(&json!([max, -max])).unwrap(), "[9007199254740991,-9007199254740991]");
+        // Integral floats canonicalize to digits that re-parse as integers
+        let reparsed: Value = serde_json::from_str(&canonical_json(&1e17).unwrap()).unwrap();
+        assert_eq!(canonical_json(&reparsed).unwrap(), "100000000000000000");
+    }
+
+    #[test]
+    fn test_json_u64_accepts_numbers_and_strings() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Seeded {
+            #[serde(default, with = "json_u64")]
+            seed: Option<u64>,
+        }
+        for (json, seed) in [
+            (r#"{"seed":42}"#, Some(42)),
+            (r#"{"seed":"42"}"#, Some(42)),
+            (r#"{"seed":18446744073709551615}"#, Some(u64::MAX)),
+            (r#"{"seed":"18446744073709551615"}"#, Some(u64::MAX)),
+            (r#"{"seed":null}"#, None),
+            ("{}", None),
+        ] {
+            assert_eq!(serde_json::from_str::<Seeded>(json).unwrap(), Seeded { seed }, "{}", json);
+        }
+        for bad in [r#"{"seed":-1}"#, r#"{"seed":"-1"}"#, r#"{"seed":" 1"}"#, r#"{"seed":"18446744073709551616"}"#, r#"{"seed":1.5}"#] {
+            assert!(serde_json::from_str::<Seeded>(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(serde_json::to_string(&Seeded { seed: Some(MAX_SAFE_INTEGER) }).unwrap(), r#"{"seed":9007199254740991}"#);
+        assert_eq!(serde_json::to_string(&Seeded { seed: Some(MAX_SAFE_INTEGER + 1) }).unwrap(), r#"{"seed":"9007199254740992"}"#);
+    }
+
+    #[test]
+    fn test_parse_strict_matches_serde_json_without_duplicates() {
+        let json = r#"{"a":[1,-2,3.5,1e300,18446744073709551615,null,true,"s"],"b":{"c":{}},"d":[]}"#;
+        assert_eq!(parse_strict(json).unwrap(), serde_json::from_str::<Value>(json).unwrap());
+        assert!(matches!(parse_strict("{} x"), Err(StrictJsonError::Syntax(_))));
+        assert!(matches!(parse_strict(r#"{"a":}"#), Err(StrictJsonError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_parse_strict_names_duplicate_and_path() {
+        for (json, key, path) in [
+            (r#"{"a":1,"a":2}"#, "a", "/a"),
+            (r#"{"x":{"y":[{},{"k":1,"k":1}]}}"#, "k", "/x/y/1/k"),
+            (r#"[{"a/b":{"~":0,"~":0}}]"#, "~", "/0/a~1b/~0"),
+        ] {
+            match parse_strict(json) {
+                Err(StrictJsonError::DuplicateKey(k, p)) => assert_eq!((k.as_str(), p.as_str()), (key, path), "{}", json),
+                other => panic!("{}: {:?}", json, other),
+            }
+        }
+        // Same key in sibling objects is fine
+        parse_strict(r#"[{"a":1},{"a":1}]"#).unwrap();
+    }
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        let cases: [(&[u8], &[u8], &str); 3] = [
+            // Test case 1
+            (&[0x0b; 20], b"Hi There", "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            // Test case 2
+            (b"Jefe", b"what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+            // Test case 6: key longer than the block size
+            (&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+        ];
+        for (key, data, expected) in cases {
+            assert_eq!(hex::encode(hmac_sha256(key).chain_update(data).finalize().into_bytes()), expected);
+            assert!(hmac_sha256(key).chain_update(data).verify_slice(&hex::decode(expected).unwrap()).is_ok());
+        }
+    }
+
     #[test]
     fn test_canonical_json_sorted() {
         let obj = json!({"z": 1, "a": 2, "m": 3});
@@ -80,6 +1318,103 @@ mod tests {
         assert_eq!(canonical, r#"{"a":2,"m":3,"z":1}"#);
     }
 
+    #[test]
+    fn test_float_formatting() {
+        let cases = [
+            (0.0, "0"), (-0.0, "0"), (1.0, "1"), (300.0, "300"), (-1.5, "-1.5"),
+            (0.1, "0.1"), (123.456, "123.456"), (0.000001, "0.000001"), (1e-7, "1e-7"),
+            (1e20, "100000000000000000000"), (1e21, "1e+21"), (1.5e300, "1.5e+300"),
+            (-2.5e-10, "-2.5e-10"), (5e-324, "5e-324"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(canonical_json(&value).unwrap(), expected, "{:e}", value);
+        }
+    }
+
+    #[test]
+    fn test_non_finite_numbers_are_rejected_with_path() {
+        #[derive(Serialize)]
+        struct Inner { values: Vec<f64> }
+        #[derive(Serialize)]
+        struct Outer { name: &'static str, inner: std::collections::BTreeMap<&'static str, Inner> }
+
+        let value = Outer {
+            name: "x",
+            inner: [("a/b", Inner { values: vec![1.0, f64::NAN] })].into_iter().collect(),
+        };
+        match canonical_json(&value) {
+            Err(HashingError::NonFiniteNumber(path)) => assert_eq!(path, "/inner/a~1b/values/1"),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(matches!(compute_manifest_hash(&f64::INFINITY), Err(HashingError::NonFiniteNumber(p)) if p.is_empty()));
+    }
+
+    /// xorshift64*, enough to spread inputs over the f64 bit space
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn random_value(state: &mut u64, depth: u32) -> Value {
+        match next(state) % if depth > 2 { 4 } else { 6 } {
+            0 => {
+                let f = f64::from_bits(next(state));
+                if f.is_finite() { json!(f) } else { json!(-0.0) }
+            }
+            1 => json!((next(state) as i64) % (MAX_SAFE_INTEGER as i64 + 1)),
+            2 => json!(format!("s{}", next(state) % 100)),
+            3 => json!((next(state) % 1_000_000) as f64 / 1000.0),
+            4 => Value::Array((0..next(state) % 4).map(|_| random_value(state, depth + 1)).collect()),
+            _ => Value::Object((0..next(state) % 4)
+                .map(|i| (format!("k{}", (next(state) % 10) * 10 + i), random_value(state, depth + 1)))
+                .collect()),
+        }
+    }
+
+    #[test]
+    fn test_canonicalization_is_a_fixed_point() {
+        let mut state = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..2000 {
+            let value = random_value(&mut state, 0);
+            let first = canonical_json(&value).unwrap();
+            let reparsed: Value = serde_json::from_str(&first).unwrap();
+            let second = canonical_json(&reparsed).unwrap();
+            assert_eq!(first, second);
+            assert_eq!(canonical_json(&serde_json::from_str::<Value>(&second).unwrap()).unwrap(), second);
+        }
+    }
+
+    /// Yields `len` pseudo-random bytes at most `chunk` at a time
+    struct ChunkedReader { remaining: usize, chunk: usize, state: u64 }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.chunk).min(self.remaining);
+            for byte in &mut buf[..n] {
+                *byte = next(&mut self.state) as u8;
+            }
+            self.remaining -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_streaming_hash_matches_one_shot() {
+        let reader = || ChunkedReader { remaining: 3 * 1024 * 1024 + 17, chunk: 4093, state: 7 };
+        let mut whole = vec![];
+        reader().read_to_end(&mut whole).unwrap();
+
+        assert_eq!(sha256_hex_reader(reader()).unwrap(), sha256_hex(&whole));
+
+        let mut writer = HashingWriter::new(Vec::new());
+        io::copy(&mut reader(), &mut writer).unwrap();
+        let (copied, hash) = writer.finalize_hex();
+        assert_eq!(copied, whole);
+        assert_eq!(hash, sha256_hex(&whole));
+    }
+
     #[test]
     fn test_hash_deterministic() {
         let data = b"test data";
@@ -98,4 +1433,44 @@ mod tests {
         let h2 = compute_manifest_hash(&manifest).unwrap();
         assert_eq!(h1, h2);
     }
+
+    #[test]
+    fn test_verify_hex_accepts_any_case_and_legacy_form() {
+        let digest = HashAlgorithm::Sha256.digest(b"forge");
+        let bare = sha256_hex(b"forge");
+        assert_eq!(verify_hex(&digest, b"forge"), Ok(()));
+        assert_eq!(verify_hex(&digest.to_uppercase().replacen("SHA256", "sha256", 1), b"forge"), Ok(()));
+        assert_eq!(verify_hex(&bare, b"forge"), Ok(()));
+        assert_eq!(verify_digest(&bare, &digest), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_hex_mismatch_is_truncated() {
+        let digest = HashAlgorithm::Sha256.digest(b"forge");
+        let err = verify_hex(&digest, b"other").unwrap_err();
+        assert_eq!(err.expected, format!("{}…", &digest[..19]));
+        assert!(err.actual.starts_with("sha256:") && err.actual.ends_with('…'));
+        assert_eq!(err.actual.chars().count(), 20);
+
+        assert!(verify_hex("md5:abcd", b"forge").is_err());
+        assert!(verify_hex("sha256:not-hex", b"forge").is_err());
+        assert!(verify_digest(&digest, &digest[..digest.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_scheme_vectors() {
+        let manifest = json!({"b": "x", "a": 1});
+        let cases = [
+            (HashScheme::V0, "ecf9e98ec0641e23113ff3ce8bdc78d0ddd249886517fd4a7f68cc83d4e65667", "f75a061f34f92a8e074b13ebc5c5cf6c972337d14f3ffe7f62e98a11088ced15"),
+            (HashScheme::V1, "005f80a402b4c6bb2f9bd95a4760a54b69dcb6341689ac4a0be2ef0ac2339f30", "b1f8b8d0b30b4524108ab35336f0cb0a70883f8b4635b82e6f09a6eb777bfc4b"),
+        ];
+        for (scheme, manifest_hex, job_hex) in cases {
+            let manifest_hash = scheme.manifest_hash(HashAlgorithm::Sha256, &manifest).unwrap();
+            let job_hash = scheme.job_hash(HashAlgorithm::Sha256, "t", "1.0.0", &manifest, "2.0.0").unwrap();
+            assert_eq!(manifest_hash, format!("sha256:{}", manifest_hex), "{:?}", scheme);
+            assert_eq!(job_hash, format!("sha256:{}", job_hex), "{:?}", scheme);
+        }
+        assert_eq!(compute_manifest_hash(&manifest).unwrap(), format!("sha256:{}", cases[1].1));
+        assert_eq!(serde_json::to_value(HashScheme::CURRENT).unwrap(), "fi-hash-1");
+    }
 }
@@ -0,0 +1,192 @@
+//! ICC Profiles - Registered Output Profiles for Print Deliverables
+//!
+//! Print specs name an output profile (`PrintSpec.icc_profile`); the bytes
+//! come only from the pipeline's `IccProfileStore`. A name that is not
+//! registered fails the compile: there is no silent fallback to sRGB.
+//!
+//! Registration checks the 128-byte profile header (declared size, `acsp`
+//! signature, data color space) so a profile is never embedded into a file
+//! whose pixels it cannot describe.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::hashing::HashAlgorithm;
+use crate::print::ColorSpace;
+
+const HEADER_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("ICC profile {name}: {reason}")]
+pub struct IccError {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Data color space from the profile header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IccColorSpace {
+    Rgb,
+    Cmyk,
+    Gray,
+}
+
+impl IccColorSpace {
+    /// Whether pixels in `space` can be tagged with a profile of this kind
+    pub fn describes(self, space: &ColorSpace) -> bool {
+        matches!(
+            (self, space),
+            (IccColorSpace::Rgb, ColorSpace::Rgb)
+                | (IccColorSpace::Cmyk, ColorSpace::Cmyk)
+                | (IccColorSpace::Gray, ColorSpace::Grayscale)
+        )
+    }
+}
+
+/// A registered profile: its bytes and their sha256 digest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    name: String,
+    data: Vec<u8>,
+    sha256: String,
+    color_space: IccColorSpace,
+}
+
+impl IccProfile {
+    /// Check the header and digest the bytes. Names are 1-79 printable ASCII
+    /// characters without leading or trailing spaces (the PNG `iCCP` rule).
+    pub fn new(name: impl Into<String>, data: Vec<u8>) -> Result<Self, IccError> {
+        let name = name.into();
+        let invalid = |reason: &str| IccError { name: name.clone(), reason: reason.to_string() };
+
+        let printable = name.bytes().all(|b| (0x20..0x7F).contains(&b));
+        if name.is_empty() || name.len() > 79 || !printable || name.trim() != name {
+            return Err(invalid("name must be 1-79 printable ASCII characters without outer spaces"));
+        }
+        if data.len() < HEADER_LEN {
+            return Err(invalid("shorter than the 128-byte header"));
+        }
+        if u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize != data.len() {
+            return Err(invalid("header size does not match the data length"));
+        }
+        if &data[36..40] != b"acsp" {
+            return Err(invalid("missing 'acsp' signature"));
+        }
+        let color_space = match &data[16..20] {
+            b"RGB " => IccColorSpace::Rgb,
+            b"CMYK" => IccColorSpace::Cmyk,
+            b"GRAY" => IccColorSpace::Gray,
+            _ => return Err(invalid("data color space must be RGB, CMYK or GRAY")),
+        };
+        let sha256 = HashAlgorithm::Sha256.digest(&data);
+        Ok(Self { name, data, sha256, color_space })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// `sha256:`-prefixed digest of the profile bytes
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    pub fn color_space(&self) -> IccColorSpace {
+        self.color_space
+    }
+
+    /// What the manifest records for an export carrying this profile
+    pub fn embedded(&self) -> EmbeddedProfile {
+        EmbeddedProfile { name: self.name.clone(), sha256: self.sha256.clone() }
+    }
+}
+
+/// Profile embedded in an export file (recorded in the manifest)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EmbeddedProfile {
+    pub name: String,
+    pub sha256: String,
+}
+
+/// Profiles print specs may reference, by name
+#[derive(Debug, Clone, Default)]
+pub struct IccProfileStore {
+    profiles: BTreeMap<String, IccProfile>,
+}
+
+impl IccProfileStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `data` under `name`, replacing any profile of that name
+    pub fn register(&mut self, name: impl Into<String>, data: Vec<u8>) -> Result<&IccProfile, IccError> {
+        let profile = IccProfile::new(name, data)?;
+        let name = profile.name.clone();
+        self.profiles.insert(name.clone(), profile);
+        Ok(&self.profiles[&name])
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IccProfile> {
+        self.profiles.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_bytes(space: &[u8; 4]) -> Vec<u8> {
+        let mut data = vec![0; 132];
+        data[0..4].copy_from_slice(&132u32.to_be_bytes());
+        data[16..20].copy_from_slice(space);
+        data[36..40].copy_from_slice(b"acsp");
+        data
+    }
+
+    #[test]
+    fn test_register_digests_and_classifies() {
+        let mut store = IccProfileStore::new();
+        let data = profile_bytes(b"CMYK");
+        let profile = store.register("Coated FOGRA39", data.clone()).unwrap();
+        assert_eq!(profile.sha256(), HashAlgorithm::Sha256.digest(&data));
+        assert_eq!(profile.color_space(), IccColorSpace::Cmyk);
+        assert!(profile.color_space().describes(&ColorSpace::Cmyk));
+        assert!(!profile.color_space().describes(&ColorSpace::Rgb));
+        assert_eq!(store.names().collect::<Vec<_>>(), vec!["Coated FOGRA39"]);
+    }
+
+    #[test]
+    fn test_malformed_profiles_are_rejected() {
+        let mut short = profile_bytes(b"RGB ");
+        short.truncate(100);
+        let mut wrong_size = profile_bytes(b"RGB ");
+        wrong_size.push(0);
+        let mut unsigned = profile_bytes(b"RGB ");
+        unsigned[36] = b'x';
+        let cases = [
+            ("ok", short, "128-byte"),
+            ("ok", wrong_size, "size"),
+            ("ok", unsigned, "acsp"),
+            ("ok", profile_bytes(b"Lab "), "color space"),
+            (" padded", profile_bytes(b"RGB "), "name"),
+            ("", profile_bytes(b"RGB "), "name"),
+        ];
+        for (name, data, expected) in cases {
+            let e = IccProfile::new(name, data).unwrap_err();
+            assert!(e.to_string().contains(expected), "{}", e);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Imposition - N-up Print Sheets
+//!
+//! An export with an `imposition` block is a press sheet: its single-unit
+//! artwork (trim size `ExportSpec.size`) repeats in a `rows` x `columns`
+//! grid, `gutter` apart, centered on the sheet. Pixel math at the export's
+//! resolved dpi, the sheet by `print::raster_dimensions`, lengths by
+//! `Length::to_pixels`:
+//! - each cell carries the export's bleed; into a gutter it reaches at most
+//!   the gutter's midline (the left/top cell taking the larger half when the
+//!   gutter is odd), on the grid's outer edges in full
+//! - crop marks continue every cell's trim lines into a band of
+//!   `MARK_SLUG_INCHES` outside the outer bleed
+//!
+//! The grid, outer bleed and mark band must fit the sheet; otherwise the
+//! compile fails with the shortfall. The manifest records the parameters
+//! and each cell's trim box in points (1/72 in) from the sheet's top-left.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bleed::{fill_rect, mark_line_width, PrintLayout, MARK_SLUG_INCHES, PAPER};
+use crate::print::presets::{self, PhysicalSize};
+use crate::print::{self, Marks, PrintSpec, TemplatePrint};
+use crate::raster::Raster;
+use crate::units::{self, Length};
+
+/// Upper bound on rows and columns
+pub const MAX_CELLS_PER_AXIS: u32 = 100;
+
+/// `imposition` block on a print export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Imposition {
+    pub sheet: Sheet,
+    pub rows: u32,
+    pub columns: u32,
+    /// Written as `gutterMm`, a number of millimetres; also reads a length
+    /// string under either name
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    #[serde(default, rename = "gutterMm", alias = "gutter", with = "units::millimetres")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "units::legacy_schema"))]
+    pub gutter: Length,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub crop_marks: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Sheet size: a `print::presets` name or explicit lengths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum Sheet {
+    Preset(String),
+    Size(PhysicalSize),
+}
+
+impl Sheet {
+    pub fn size(&self) -> Result<PhysicalSize, String> {
+        match self {
+            Sheet::Preset(name) => presets::get(name).ok_or_else(|| format!("unknown sheet preset '{}'", name)),
+            Sheet::Size(size) => Ok(*size),
+        }
+    }
+}
+
+impl Imposition {
+    /// Checks that hold at any dpi; fit is checked by `SheetLayout::new`
+    pub fn validate(&self) -> Result<(), String> {
+        let size = self.sheet.size()?;
+        if size.width <= Length::ZERO || size.height <= Length::ZERO {
+            return Err("sheet size must be positive".to_string());
+        }
+        if !(1..=MAX_CELLS_PER_AXIS).contains(&self.rows) || !(1..=MAX_CELLS_PER_AXIS).contains(&self.columns) {
+            return Err(format!("rows and columns must be between 1 and {}", MAX_CELLS_PER_AXIS));
+        }
+        if self.gutter < Length::ZERO {
+            return Err("gutter must be zero or positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Imposition as recorded on an export in the manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ImpositionRecord {
+    pub sheet_mm: [f64; 2],
+    pub rows: u32,
+    pub columns: u32,
+    pub gutter_mm: f64,
+    pub crop_marks: bool,
+    /// Row-major, top-left first
+    pub cells: Vec<CellRecord>,
+}
+
+/// One cell's trim box, in points from the sheet's top-left
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CellRecord {
+    pub row: u32,
+    pub column: u32,
+    pub x_pt: f64,
+    pub y_pt: f64,
+    pub width_pt: f64,
+    pub height_pt: f64,
+}
+
+/// Pixel layout of one imposed sheet
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SheetLayout {
+    sheet: [u32; 2],
+    trim: [u32; 2],
+    dpi: u32,
+    /// Columns, rows
+    grid: [u32; 2],
+    gutter: u32,
+    /// Trim box of the top-left cell
+    origin: [u32; 2],
+    /// Bleed around one cell (never marks)
+    cell: Option<PrintLayout>,
+    bleed_before: [u32; 2],
+    bleed_after: [u32; 2],
+    crop_marks: bool,
+    slug: u32,
+    line: u32,
+}
+
+impl SheetLayout {
+    /// Lay out cells of trim size `trim`; `block` is the print block in
+    /// effect. Errors name the shortfall when the grid does not fit.
+    pub fn new(
+        trim: [u32; 2],
+        print: &PrintSpec,
+        block: Option<&TemplatePrint>,
+        imposition: &Imposition,
+    ) -> Result<Self, String> {
+        imposition.validate()?;
+        let dpi = print.dpi;
+        let sheet = print::raster_dimensions(imposition.sheet.size()?, dpi).map_err(|e| format!("sheet {}", e))?;
+        let gutter = imposition.gutter.to_pixels(dpi);
+
+        // Single-export marks do not apply per cell
+        let bleed_block = block.map(|b| TemplatePrint { marks: Marks::default(), ..b.clone() });
+        let cell = PrintLayout::new(trim, print, bleed_block.as_ref())?;
+        let (bleed_before, bleed_after) = match &cell {
+            Some(cell) => {
+                let before = cell.trim_box().offset;
+                let size = cell.size();
+                (before, [0, 1].map(|axis| size[axis] - trim[axis] - before[axis]))
+            }
+            None => ([0; 2], [0; 2]),
+        };
+        let slug = if imposition.crop_marks { (MARK_SLUG_INCHES * dpi as f64).round() as u32 } else { 0 };
+
+        let grid = [imposition.columns, imposition.rows];
+        let grid_size = [0, 1].map(|axis| grid[axis] as u64 * trim[axis] as u64 + (grid[axis] as u64 - 1) * gutter as u64);
+        let needed = [0, 1].map(|axis| grid_size[axis] + (bleed_before[axis] + bleed_after[axis] + 2 * slug) as u64);
+        if needed[0] > sheet[0] as u64 || needed[1] > sheet[1] as u64 {
+            let short = [0, 1].map(|axis| needed[axis].saturating_sub(sheet[axis] as u64));
+            let short_mm = short.map(|px| Length::from_pixels(px.min(u32::MAX as u64) as u32, dpi).as_mm());
+            return Err(format!(
+                "{}x{} grid needs {}x{} px but the sheet is {}x{} px at {} dpi (short by {}x{} px, {:.2}x{:.2} mm)",
+                imposition.columns, imposition.rows, needed[0], needed[1], sheet[0], sheet[1], dpi,
+                short[0], short[1], short_mm[0], short_mm[1],
+            ));
+        }
+        let origin = [0, 1].map(|axis| (sheet[axis] - needed[axis] as u32) / 2 + slug + bleed_before[axis]);
+
+        Ok(Self {
+            sheet,
+            trim,
+            dpi,
+            grid,
+            gutter,
+            origin,
+            cell,
+            bleed_before,
+            bleed_after,
+            crop_marks: imposition.crop_marks,
+            slug,
+            line: mark_line_width(dpi),
+        })
+    }
+
+    /// Full sheet size in pixels
+    pub fn size(&self) -> [u32; 2] {
+        self.sheet
+    }
+
+    /// Top-left of the trim box of the cell at (`column`, `row`)
+    pub fn cell_offset(&self, column: u32, row: u32) -> [u32; 2] {
+        let index = [column, row];
+        [0, 1].map(|axis| self.origin[axis] + index[axis] * (self.trim[axis] + self.gutter))
+    }
+
+    /// Trim-size artwork tiled onto the sheet
+    pub fn compose(&self, art: &Raster) -> Raster {
+        assert_eq!([art.width, art.height], self.trim, "artwork is not at trim size");
+        let unit = match &self.cell {
+            Some(cell) => cell.compose(art),
+            None => art.clone(),
+        };
+        let mut out = Raster::filled(self.sheet[0], self.sheet[1], PAPER);
+        for row in 0..self.grid[1] {
+            for column in 0..self.grid[0] {
+                let index = [column, row];
+                let at = self.cell_offset(column, row);
+                // Bleed reach per side: in full on outer edges, to the midline in gutters
+                let before = [0, 1].map(|axis| match index[axis] {
+                    0 => self.bleed_before[axis],
+                    _ => self.bleed_before[axis].min(self.gutter / 2),
+                });
+                let after = [0, 1].map(|axis| match index[axis] + 1 == self.grid[axis] {
+                    true => self.bleed_after[axis],
+                    false => self.bleed_after[axis].min(self.gutter - self.gutter / 2),
+                });
+                for y in at[1] - before[1]..at[1] + self.trim[1] + after[1] {
+                    for x in at[0] - before[0]..at[0] + self.trim[0] + after[0] {
+                        let u = x + self.bleed_before[0] - at[0];
+                        let v = y + self.bleed_before[1] - at[1];
+                        out.set(x, y, unit.get(u, v));
+                    }
+                }
+            }
+        }
+        if self.crop_marks {
+            self.draw_marks(&mut out);
+        }
+        out
+    }
+
+    fn draw_marks(&self, out: &mut Raster) {
+        let line = self.line;
+        let reach = self.slug - self.slug / 4;
+        let last = self.cell_offset(self.grid[0] - 1, self.grid[1] - 1);
+        // Outer edges of the bleed box around the whole grid
+        let top = self.origin[1] - self.bleed_before[1];
+        let bottom = last[1] + self.trim[1] + self.bleed_after[1];
+        let left = self.origin[0] - self.bleed_before[0];
+        let right = last[0] + self.trim[0] + self.bleed_after[0];
+
+        for column in 0..self.grid[0] {
+            let x = self.cell_offset(column, 0)[0];
+            for x in [x, x + self.trim[0] - line] {
+                fill_rect(out, x, top - self.slug, line, reach);
+                fill_rect(out, x, bottom + self.slug / 4, line, reach);
+            }
+        }
+        for row in 0..self.grid[1] {
+            let y = self.cell_offset(0, row)[1];
+            for y in [y, y + self.trim[1] - line] {
+                fill_rect(out, left - self.slug, y, reach, line);
+                fill_rect(out, right + self.slug / 4, y, reach, line);
+            }
+        }
+    }
+
+    /// Manifest record for `imposition` laid out as `self`
+    pub fn record(&self, imposition: &Imposition) -> ImpositionRecord {
+        let points = |px: u32| px as f64 * 72.0 / self.dpi as f64;
+        let sheet = imposition.sheet.size().expect("validated when laid out");
+        let cells = (0..self.grid[1])
+            .flat_map(|row| (0..self.grid[0]).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let [x, y] = self.cell_offset(column, row);
+                CellRecord {
+                    row,
+                    column,
+                    x_pt: points(x),
+                    y_pt: points(y),
+                    width_pt: points(self.trim[0]),
+                    height_pt: points(self.trim[1]),
+                }
+            })
+            .collect();
+        ImpositionRecord {
+            sheet_mm: [sheet.width.as_mm(), sheet.height.as_mm()],
+            rows: imposition.rows,
+            columns: imposition.columns,
+            gutter_mm: imposition.gutter.as_mm(),
+            crop_marks: imposition.crop_marks,
+            cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bleed::MARK;
+
+    fn spec(dpi: u32, bleed_inches: f64) -> PrintSpec {
+        PrintSpec { dpi, bleed: Length::inches(bleed_inches), ..PrintSpec::default() }
+    }
+
+    fn imposition(sheet: PhysicalSize, rows: u32, columns: u32, gutter: Length) -> Imposition {
+        Imposition { sheet: Sheet::Size(sheet), rows, columns, gutter, crop_marks: false }
+    }
+
+    #[test]
+    fn test_cells_are_centered_with_gutters() {
+        // 1 in = 10 px at 10 dpi: a 10x4 in sheet, 2x1 grid of 3x2 in cells, 1 in gutter
+        let sheet = PhysicalSize::inches(10.0, 4.0);
+        let layout = SheetLayout::new([30, 20], &spec(10, 0.0), None, &imposition(sheet, 1, 2, Length::inches(1.0))).unwrap();
+        assert_eq!(layout.size(), [100, 40]);
+        assert_eq!(layout.cell_offset(0, 0), [15, 10]);
+        assert_eq!(layout.cell_offset(1, 0), [55, 10]);
+    }
+
+    #[test]
+    fn test_bleed_meets_at_the_gutter_midline() {
+        // 2 px bleed per side, 3 px gutter: the left cell reaches 2, the right 1
+        let mut art = Raster::filled(4, 2, [10, 0, 0, 255]);
+        art.set(0, 0, [20, 0, 0, 255]);
+        let sheet = PhysicalSize::new(Length::points(15.0), Length::points(6.0));
+        let layout = SheetLayout::new([4, 2], &spec(72, 2.0 / 72.0), None, &imposition(sheet, 1, 2, Length::points(3.0))).unwrap();
+        assert_eq!(layout.size(), [15, 6]);
+        let out = layout.compose(&art);
+        let [x0, _] = layout.cell_offset(0, 0);
+        let [x1, _] = layout.cell_offset(1, 0);
+        assert_eq!(x1 - x0, 4 + 3);
+        let y = layout.cell_offset(0, 0)[1] + 1;
+        let red = |x: u32| out.get(x, y)[0];
+        // Left cell's right bleed covers the first two gutter pixels, right cell's left bleed the third
+        assert_eq!((red(x0 + 4), red(x0 + 5)), (10, 10));
+        assert_eq!(red(x1 - 1), 10);
+        // The right cell's mirrored edge repeats its own left column (art x=0)
+        assert_eq!(out.get(x1 - 1, layout.cell_offset(1, 0)[1]), [20, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_shortfall_is_reported() {
+        let sheet = PhysicalSize::inches(5.0, 4.0);
+        let err = SheetLayout::new([30, 20], &spec(10, 0.0), None, &imposition(sheet, 1, 2, Length::inches(1.0))).unwrap_err();
+        assert!(err.contains("needs 70x20 px but the sheet is 50x40 px at 10 dpi (short by 20x0 px, 50.80x0.00 mm)"), "{}", err);
+    }
+
+    #[test]
+    fn test_crop_marks_sit_outside_the_bleed() {
+        let sheet = PhysicalSize::inches(4.0, 4.0);
+        let imposition = Imposition { crop_marks: true, ..imposition(sheet, 1, 1, Length::ZERO) };
+        let layout = SheetLayout::new([40, 40], &spec(20, 0.25), None, &imposition).unwrap();
+        let out = layout.compose(&Raster::filled(40, 40, [0, 0, 255, 255]));
+        let [x, y] = layout.cell_offset(0, 0);
+        // 5 px bleed, 5 px slug: marks in [y - 10, y - 6)
+        assert_eq!(out.get(x, y - 10), MARK);
+        assert_eq!(out.get(x, y - 7), MARK);
+        assert_eq!(out.get(x, y - 6), PAPER);
+        assert_eq!(out.get(x, y - 5), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_record_in_points() {
+        let sheet = PhysicalSize::inches(10.0, 4.0);
+        let imposition = imposition(sheet, 1, 2, Length::inches(1.0));
+        let layout = SheetLayout::new([30, 20], &spec(10, 0.0), None, &imposition).unwrap();
+        let record = layout.record(&imposition);
+        assert_eq!(record.cells.len(), 2);
+        let cell = &record.cells[1];
+        assert_eq!((cell.row, cell.column), (0, 1));
+        assert_eq!((cell.x_pt, cell.y_pt, cell.width_pt, cell.height_pt), (396.0, 72.0, 216.0, 144.0));
+    }
+}
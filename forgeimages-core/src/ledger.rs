@@ -0,0 +1,199 @@
+//! Job Ledger - Hash-Chained Record of Compiled Jobs
+//!
+//! Where the audit log records every attempt, the ledger records only
+//! compiles that produced a manifest: `{seq, prev_entry_hash, job_hash,
+//! manifest_hash, timestamp}`. Each entry's hash covers all of those fields
+//! and becomes the next entry's `prev_entry_hash`, and `seq` counts up from
+//! zero, so modifying, removing or reordering any entry breaks
+//! `verify_chain`. Dropping entries from the end is only detectable against
+//! a `head_hash` kept elsewhere.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::audit::{AuditError, AuditEvent, AuditSink, GENESIS_HASH};
+use crate::hashing::{canonical_json, verify_digest, HashAlgorithm, HashingError};
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("Ledger I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Ledger hashing error: {0}")]
+    Hashing(#[from] HashingError),
+
+    #[error("Malformed ledger line {0}: {1}")]
+    Malformed(usize, String),
+
+    #[error("Ledger chain broken at entry {0}")]
+    ChainBroken(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub prev_entry_hash: String,
+    pub job_hash: String,
+    pub manifest_hash: String,
+    pub timestamp: String,
+    /// sha256 over every other field, in canonical JSON
+    pub entry_hash: String,
+}
+
+/// The hashed part of an entry
+#[derive(Serialize)]
+struct EntryBody<'a> {
+    seq: u64,
+    prev_entry_hash: &'a str,
+    job_hash: &'a str,
+    manifest_hash: &'a str,
+    timestamp: &'a str,
+}
+
+impl LedgerEntry {
+    fn compute_hash(&self) -> Result<String, HashingError> {
+        let body = EntryBody {
+            seq: self.seq,
+            prev_entry_hash: &self.prev_entry_hash,
+            job_hash: &self.job_hash,
+            manifest_hash: &self.manifest_hash,
+            timestamp: &self.timestamp,
+        };
+        Ok(HashAlgorithm::Sha256.digest(canonical_json(&body)?.as_bytes()))
+    }
+}
+
+/// Append-only, in-memory ledger
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Hash of the last entry (`GENESIS_HASH` when empty)
+    pub fn head_hash(&self) -> &str {
+        self.entries.last().map_or(GENESIS_HASH, |e| &e.entry_hash)
+    }
+
+    /// Link a new entry to the current head
+    pub fn append(&mut self, job_hash: &str, manifest_hash: &str, timestamp: &str) -> Result<&LedgerEntry, HashingError> {
+        let mut entry = LedgerEntry {
+            seq: self.entries.len() as u64,
+            prev_entry_hash: self.head_hash().to_string(),
+            job_hash: job_hash.to_string(),
+            manifest_hash: manifest_hash.to_string(),
+            timestamp: timestamp.to_string(),
+            entry_hash: String::new(),
+        };
+        entry.entry_hash = entry.compute_hash()?;
+        self.entries.push(entry);
+        Ok(&self.entries[self.entries.len() - 1])
+    }
+
+    /// Check sequence numbers, links and entry hashes; reports the first bad entry
+    pub fn verify_chain(&self) -> Result<(), LedgerError> {
+        let mut prev_hash = GENESIS_HASH;
+        for (index, entry) in self.entries.iter().enumerate() {
+            let linked = entry.seq == index as u64 && verify_digest(prev_hash, &entry.prev_entry_hash).is_ok();
+            if !linked || verify_digest(&entry.entry_hash, &entry.compute_hash()?).is_err() {
+                return Err(LedgerError::ChainBroken(index));
+            }
+            prev_hash = &entry.entry_hash;
+        }
+        Ok(())
+    }
+
+    /// Read JSONL (one canonical entry per line) without verifying the chain
+    pub fn read_jsonl(reader: impl BufRead) -> Result<Self, LedgerError> {
+        let mut entries = vec![];
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| LedgerError::Malformed(index + 1, e.to_string()))?;
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn write_jsonl(&self, mut writer: impl Write) -> Result<(), LedgerError> {
+        for entry in &self.entries {
+            writeln!(writer, "{}", canonical_json(entry)?)?;
+        }
+        Ok(())
+    }
+}
+
+struct SinkState {
+    file: File,
+    ledger: Ledger,
+}
+
+/// Audit sink that appends each compile with a manifest to a JSONL ledger
+pub struct LedgerSink {
+    path: PathBuf,
+    state: Mutex<SinkState>,
+}
+
+impl LedgerSink {
+    /// Open (or create) a ledger file; an existing chain must verify
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, LedgerError> {
+        let path = path.into();
+        let ledger = match File::open(&path) {
+            Ok(file) => Ledger::read_jsonl(BufReader::new(file))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ledger::new(),
+            Err(e) => return Err(e.into()),
+        };
+        ledger.verify_chain()?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, state: Mutex::new(SinkState { file, ledger }) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Copy of the ledger as written so far
+    pub fn ledger(&self) -> Ledger {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).ledger.clone()
+    }
+}
+
+impl AuditSink for LedgerSink {
+    fn append(&self, event: &AuditEvent) -> Result<(), AuditError> {
+        // Failed attempts belong to the audit log, not the ledger
+        let (Some(job_hash), Some(manifest_hash)) = (&event.job_hash, &event.manifest_hash) else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let line = canonical_json(state.ledger.append(job_hash, manifest_hash, &event.timestamp)?)?;
+        let written = state.file.write_all(format!("{}\n", line).as_bytes()).and_then(|_| state.file.flush());
+        if written.is_err() {
+            // Keep memory in step with the file
+            state.ledger.entries.pop();
+        }
+        Ok(written?)
+    }
+}
@@ -11,14 +11,66 @@
 pub mod templates;
 pub mod validation;
 pub mod hashing;
+pub mod hash_vectors;
+pub mod ledger;
+pub mod merkle;
 pub mod print;
+pub mod pdf;
+pub mod units;
+pub mod bleed;
+pub mod imposition;
 pub mod pipeline;
+pub mod config;
+pub mod render;
+pub mod raster;
+pub mod encoding;
+pub mod color;
+pub mod icc;
+pub mod background;
+pub mod font;
+pub mod svg;
+pub mod svg_normalize;
+pub mod svg_coverage;
+pub mod sniff;
+pub mod audit;
+pub mod compile_set;
+pub mod batch;
+pub mod cancel;
+pub mod cas;
+pub mod output;
+pub mod sink;
+pub mod notify;
+pub mod verify;
+pub mod diff;
+pub mod reproduce;
+pub mod exit_code;
+pub mod error;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+#[cfg(feature = "signing")]
+pub mod signing;
 
-pub use templates::{Template, TemplateId, ExportSpec, AssetClass};
+pub use templates::{Template, TemplateBuilder, TemplateId, ExportSpec, AssetClass};
 pub use validation::{ValidationResult, ValidationRule, ValidationViolation, ViolationSeverity};
-pub use hashing::{compute_manifest_hash, compute_job_hash, canonical_json};
-pub use print::PrintAuthority;
-pub use pipeline::{CompilationPipeline, CompiledAsset, CompileRequest, PipelineError};
+pub use hashing::{compute_manifest_hash, compute_job_hash, canonical_json, HashAlgorithm, HashMismatch, HashScheme, HashingError, JobHashKey, StrictJsonError, parse_strict};
+pub use print::{PrintAuthority, PrintSpec};
+pub use pipeline::{CompilationPipeline, CompiledAsset, CompileRequest, EngineBound, ExportError, JobHashInput, ManifestHashView, ManifestStamp, PipelineBuilder, PipelineError, SandboxMode};
+pub use config::{ConfigError, PipelineConfig};
+pub use compile_set::{CompileRequestCommon, CompileSetResult, SourceArtifact};
+pub use batch::{BatchCheckpoint, BatchEvent, BatchOutcome, BatchResult};
+pub use cancel::{CancelReason, CancelToken};
+pub use render::{Renderer, RenderError, RenderJob, RetryPolicy};
+pub use encoding::EncodingProfile;
+pub use exit_code::exit_code_for;
+pub use error::{ErrorEnvelope, ErrorKind};
 
 pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const MIN_TEMPLATE_VERSION: &str = "1.0.0";
@@ -0,0 +1,158 @@
+//! Merkle Tree - Selective Disclosure of Exports
+//!
+//! Proves one export belonged to a compile without revealing the others.
+//!
+//! Construction:
+//! - Leaves are the exports' recorded digests, ordered by export id (byte
+//!   order; ties keep manifest order).
+//! - Leaf node = H(0x00 || leaf digest bytes); parent = H(0x01 || left || right),
+//!   so a leaf can never pose as an inner node.
+//! - A level with an odd number of nodes pairs its last node with itself.
+//! - H is the algorithm the leaf digests name; the root is a prefixed digest.
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashing::{constant_time_eq, hex, parse_digest, HashAlgorithm, HashingError};
+use crate::pipeline::ExportedFile;
+
+const LEAF_TAG: u8 = 0x00;
+const NODE_TAG: u8 = 0x01;
+
+/// Which side of the running hash a sibling sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleStep {
+    /// Prefixed digest of the sibling node
+    pub sibling: String,
+    pub side: Side,
+}
+
+/// Path from one export's leaf to the root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub export_id: String,
+    /// Position among the id-ordered leaves
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    pub steps: Vec<MerkleStep>,
+}
+
+fn node(algorithm: HashAlgorithm, tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[tag]);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize_bytes()
+}
+
+fn prefixed(algorithm: HashAlgorithm, bytes: &[u8]) -> String {
+    let mut digest = format!("{}:", algorithm.prefix());
+    hex::encode_to(bytes, &mut digest);
+    digest
+}
+
+/// Decode a prefixed digest, requiring the tree's algorithm
+fn digest_bytes(algorithm: Option<HashAlgorithm>, digest: &str) -> Result<(HashAlgorithm, Vec<u8>), HashingError> {
+    let (found, hex_part) = parse_digest(digest)?;
+    if algorithm.is_some_and(|a| a != found) {
+        return Err(HashingError::MixedAlgorithms(digest.to_string()));
+    }
+    let bytes = hex::decode(hex_part).ok_or_else(|| HashingError::InvalidDigest(digest.to_string()))?;
+    Ok((found, bytes))
+}
+
+/// `(export id, digest)` pairs in tree order (stable sort by id)
+fn ordered_leaves(exports: &[ExportedFile]) -> Vec<(&str, &str)> {
+    let mut leaves: Vec<_> = exports.iter().map(|e| (e.id.as_str(), e.hash.as_str())).collect();
+    leaves.sort_by(|a, b| a.0.cmp(b.0));
+    leaves
+}
+
+/// Root over `(export id, digest)` pairs in any order
+pub(crate) fn root_of(mut leaves: Vec<(&str, &str)>) -> Result<Option<String>, HashingError> {
+    leaves.sort_by(|a, b| a.0.cmp(b.0));
+    let digests: Vec<&str> = leaves.into_iter().map(|(_, d)| d).collect();
+    if digests.is_empty() {
+        return Ok(None);
+    }
+    let (algorithm, mut level) = leaf_level(&digests)?;
+    while level.len() > 1 {
+        level = next_level(algorithm, &level);
+    }
+    Ok(Some(prefixed(algorithm, &level[0])))
+}
+
+/// Leaf nodes for the ordered digests
+fn leaf_level(digests: &[&str]) -> Result<(HashAlgorithm, Vec<Vec<u8>>), HashingError> {
+    let mut algorithm = None;
+    let mut level = Vec::with_capacity(digests.len());
+    for digest in digests {
+        let (found, bytes) = digest_bytes(algorithm, digest)?;
+        algorithm = Some(found);
+        level.push(node(found, LEAF_TAG, &[&bytes]));
+    }
+    Ok((algorithm.unwrap_or_default(), level))
+}
+
+fn next_level(algorithm: HashAlgorithm, level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level.chunks(2)
+        .map(|pair| {
+            let right = pair.get(1).unwrap_or(&pair[0]);
+            node(algorithm, NODE_TAG, &[&pair[0], right])
+        })
+        .collect()
+}
+
+/// Root over the exports' digests; `None` when there are no exports
+pub fn exports_root(exports: &[ExportedFile]) -> Result<Option<String>, HashingError> {
+    root_of(exports.iter().map(|e| (e.id.as_str(), e.hash.as_str())).collect())
+}
+
+/// Proof that `export_id` is one of the leaves under `exports_root(exports)`
+pub fn merkle_proof(exports: &[ExportedFile], export_id: &str) -> Result<MerkleProof, HashingError> {
+    let leaves = ordered_leaves(exports);
+    let leaf_index = leaves.iter()
+        .position(|(id, _)| *id == export_id)
+        .ok_or_else(|| HashingError::UnknownLeaf(export_id.to_string()))?;
+    let digests: Vec<&str> = leaves.iter().map(|(_, d)| *d).collect();
+    let (algorithm, mut level) = leaf_level(&digests)?;
+
+    let mut steps = vec![];
+    let mut index = leaf_index;
+    while level.len() > 1 {
+        let (sibling, side) = if index % 2 == 0 {
+            (level.get(index + 1).unwrap_or(&level[index]), Side::Right)
+        } else {
+            (&level[index - 1], Side::Left)
+        };
+        steps.push(MerkleStep { sibling: prefixed(algorithm, sibling), side });
+        level = next_level(algorithm, &level);
+        index /= 2;
+    }
+    Ok(MerkleProof { export_id: export_id.to_string(), leaf_index, leaf_count: leaves.len(), steps })
+}
+
+/// Whether `proof` leads from the export digest `leaf_hash` to `root`
+pub fn verify_merkle_proof(root: &str, proof: &MerkleProof, leaf_hash: &str) -> bool {
+    let verify = || -> Result<bool, HashingError> {
+        let (algorithm, leaf) = digest_bytes(None, leaf_hash)?;
+        let (_, root) = digest_bytes(Some(algorithm), root)?;
+        let mut current = node(algorithm, LEAF_TAG, &[&leaf]);
+        for step in &proof.steps {
+            let (_, sibling) = digest_bytes(Some(algorithm), &step.sibling)?;
+            current = match step.side {
+                Side::Left => node(algorithm, NODE_TAG, &[&sibling, &current]),
+                Side::Right => node(algorithm, NODE_TAG, &[&current, &sibling]),
+            };
+        }
+        Ok(constant_time_eq(&current, &root))
+    };
+    verify().unwrap_or(false)
+}
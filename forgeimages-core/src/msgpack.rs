@@ -0,0 +1,111 @@
+//! MessagePack - A Smaller Wire Format for the Same Values
+//!
+//! Requests may arrive as MessagePack instead of JSON, so a large source
+//! travels as raw `bin` bytes rather than base64 text. A MessagePack body
+//! decodes to the very value its JSON form would parse to: maps need
+//! string keys, none repeated (as `parse_strict`), and `bin` reads as the
+//! base64 string JSON would carry. Everything downstream, job hashes
+//! included, sees only that value, so a request hashes the same whichever
+//! format it came in.
+//!
+//! Encoding writes the JSON value too: objects as maps keyed by field
+//! name, bytes still as their base64 strings.
+
+use std::cell::RefCell;
+use std::io::Cursor;
+
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::hashing::strict::Seed;
+use crate::pipeline::{CompiledAsset, CompileRequest};
+
+#[derive(Debug, Error)]
+pub enum MsgpackError {
+    #[error("Invalid MessagePack: {0}")]
+    Syntax(String),
+
+    /// Key, then the JSON Pointer (RFC 6901) path of its second occurrence
+    #[error("Duplicate key {0:?} at {1:?}")]
+    DuplicateKey(String, String),
+
+    /// Well-formed, but not the type asked for
+    #[error("{0}")]
+    Shape(#[from] serde_json::Error),
+
+    #[error("MessagePack encoding failed: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+}
+
+/// Decode MessagePack to the JSON value it stands for, rejecting repeated
+/// keys and trailing bytes
+pub fn parse_strict_msgpack(bytes: &[u8]) -> Result<Value, MsgpackError> {
+    let duplicate = RefCell::new(None);
+    let mut deserializer = rmp_serde::Deserializer::new(Cursor::new(bytes));
+    let parsed = Seed { path: vec![], duplicate: &duplicate }.deserialize(&mut deserializer);
+    match (parsed, duplicate.into_inner()) {
+        (_, Some((key, path))) => Err(MsgpackError::DuplicateKey(key, path)),
+        (Ok(_), None) if deserializer.position() < bytes.len() as u64 => {
+            Err(MsgpackError::Syntax(format!("trailing bytes after offset {}", deserializer.position())))
+        }
+        (Ok(value), None) => Ok(value),
+        (Err(e), None) => Err(MsgpackError::Syntax(e.to_string())),
+    }
+}
+
+/// Decode MessagePack as `T`, via its JSON value
+pub fn from_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MsgpackError> {
+    Ok(serde_json::from_value(parse_strict_msgpack(bytes)?)?)
+}
+
+/// Encode `value` as MessagePack, as its JSON value
+pub fn to_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, MsgpackError> {
+    Ok(rmp_serde::to_vec_named(&serde_json::to_value(value)?)?)
+}
+
+impl CompileRequest {
+    /// A request sent as MessagePack; `source_data` may be `bin`
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, MsgpackError> {
+        from_msgpack(bytes)
+    }
+}
+
+impl CompiledAsset {
+    /// The manifest as MessagePack; it decodes to the same value as
+    /// `canonical_json`, so its `manifest_hash` still verifies
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, MsgpackError> {
+        to_msgpack(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashing::parse_strict;
+
+    /// `{"a": <bin 0x01 0x02>}`
+    const BIN_MAP: &[u8] = &[0x81, 0xa1, b'a', 0xc4, 0x02, 0x01, 0x02];
+
+    #[test]
+    fn test_values_match_their_json_form() {
+        let json = r#"{"a":[1,-2,3.5,18446744073709551615,null,true,"s"],"b":{"c":{}},"d":[]}"#;
+        let value = parse_strict(json).unwrap();
+        assert_eq!(parse_strict_msgpack(&to_msgpack(&value).unwrap()).unwrap(), value);
+        assert_eq!(parse_strict_msgpack(BIN_MAP).unwrap(), serde_json::json!({"a": "AQI="}));
+    }
+
+    #[test]
+    fn test_rejects_what_json_would() {
+        // {"a": 1, "a": 2}
+        match parse_strict_msgpack(&[0x82, 0xa1, b'a', 0x01, 0xa1, b'a', 0x02]) {
+            Err(MsgpackError::DuplicateKey(key, path)) => assert_eq!((key.as_str(), path.as_str()), ("a", "/a")),
+            other => panic!("{:?}", other),
+        }
+        // {1: 2}: keys must be strings
+        assert!(matches!(parse_strict_msgpack(&[0x81, 0x01, 0x02]), Err(MsgpackError::Syntax(_))));
+        assert!(matches!(parse_strict_msgpack(&[BIN_MAP, &[0xc0]].concat()), Err(MsgpackError::Syntax(_))));
+        assert!(matches!(parse_strict_msgpack(&BIN_MAP[..4]), Err(MsgpackError::Syntax(_))));
+    }
+}
@@ -0,0 +1,58 @@
+//! Compile Notifications - Push Instead of Poll
+//!
+//! A pipeline built with a `Notifier` hands it the `AuditEvent` of every
+//! compile attempt, successful or not, once the attempt is over (after the
+//! audit sink, so an audit failure is reported as the compile's error).
+//! Notifying cannot fail a compile: `notify` returns nothing, and an
+//! implementation that can fail logs and moves on.
+//!
+//! Webhook bodies (the `webhooks` feature) are the event's canonical JSON,
+//! signed with HMAC-SHA256 under a shared secret; receivers check the
+//! `SIGNATURE_HEADER` with `verify_signature`.
+
+use hmac::Mac;
+
+use crate::audit::AuditEvent;
+use crate::hashing::{hex, hmac_sha256};
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`
+pub const SIGNATURE_HEADER: &str = "X-ForgeImages-Signature";
+
+/// Receiver of compile outcomes
+pub trait Notifier: Send + Sync {
+    /// Called once per compile attempt; must not block for long
+    fn notify(&self, event: &AuditEvent);
+}
+
+/// `SIGNATURE_HEADER` value for `body` under `secret`
+pub fn signature(secret: &[u8], body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(hmac_sha256(secret).chain_update(body).finalize().into_bytes()))
+}
+
+/// Whether `header` is the signature of `body` under `secret`, compared in
+/// constant time
+pub fn verify_signature(secret: &[u8], body: &[u8], header: &str) -> bool {
+    let header = header.trim();
+    match (header.get(..7), header.get(7..).and_then(hex::decode)) {
+        (Some(prefix), Some(tag)) if prefix.eq_ignore_ascii_case("sha256=") => {
+            hmac_sha256(secret).chain_update(body).verify_slice(&tag).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signatures_verify_only_their_body() {
+        let header = signature(b"secret", br#"{"outcome":"success"}"#);
+        assert!(header.starts_with("sha256=") && header.len() == 7 + 64);
+        assert!(verify_signature(b"secret", br#"{"outcome":"success"}"#, &header));
+        assert!(verify_signature(b"secret", br#"{"outcome":"success"}"#, &header.to_uppercase().replace("SHA256", "sha256")));
+        assert!(!verify_signature(b"secret", br#"{"outcome":"error"}"#, &header));
+        assert!(!verify_signature(b"other", br#"{"outcome":"success"}"#, &header));
+        assert!(!verify_signature(b"secret", br#"{"outcome":"success"}"#, &header[7..]));
+    }
+}
@@ -0,0 +1,118 @@
+//! Directory Output - Exports Plus Sidecar Manifest
+//!
+//! Layout: one file per export, `manifest.json` (canonical JSON),
+//! `manifest.sha256` (`sha256sum -c` compatible, over manifest.json) and,
+//! when the pipeline signs, `manifest.sig` (base64 signature over the
+//! manifest hash).
+//!
+//! With `emit_checksums`, every export also gets a `{filename}.sha256`
+//! sidecar and the directory a `checksums.txt` covering the exports and
+//! manifest.json, all `sha256sum -c` compatible whatever the manifest's
+//! hash algorithm.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Component, Path};
+
+use crate::hashing::{parse_digest, sha256_hex, sha256_hex_reader, verify_digest, HashAlgorithm, HashingWriter};
+use crate::pipeline::{CompiledAsset, PipelineError};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const MANIFEST_HASH_FILE: &str = "manifest.sha256";
+pub const SIGNATURE_FILE: &str = "manifest.sig";
+pub const CHECKSUMS_FILE: &str = "checksums.txt";
+
+/// Whether `name` is a bare file name: one normal path component, with
+/// no separator of any platform, so it cannot name a file outside the
+/// directory it is joined onto
+pub fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        && !name.contains(['/', '\\'])
+}
+
+/// Name of the `sha256sum -c` sidecar for an export file
+pub fn sidecar_name(filename: &str) -> String {
+    format!("{}.sha256", filename)
+}
+
+/// One `sha256sum` line
+fn checksum_line(hex: &str, filename: &str) -> String {
+    format!("{}  {}\n", hex, filename)
+}
+
+/// Write exports and sidecars; `signature` is written only when present
+pub(crate) fn write_dir(
+    asset: &CompiledAsset,
+    dir: &Path,
+    signature: Option<&[u8]>,
+    emit_checksums: bool,
+) -> Result<(), PipelineError> {
+    let io = |e: std::io::Error| PipelineError::OutputFailed(format!("{}: {}", dir.display(), e));
+
+    // Checked before anything is written, so a hostile name leaves no partial output
+    if let Some(export) = asset.exports.iter().find(|export| !is_plain_file_name(&export.filename)) {
+        return Err(PipelineError::ValidationFailed(format!(
+            "export {} filename {:?} is not a plain file name", export.id, export.filename,
+        )));
+    }
+    fs::create_dir_all(dir).map_err(io)?;
+    let mut checksums = vec![];
+    for export in &asset.exports {
+        // Decode straight to disk, hashing what lands there
+        let mut decoder = base64::read::DecoderReader::new(
+            export.data_base64.as_bytes(),
+            &base64::engine::general_purpose::STANDARD,
+        );
+        let (algorithm, _) = parse_digest(&export.hash)?;
+        let mut writer = HashingWriter::with_algorithm(File::create(dir.join(&export.filename)).map_err(io)?, algorithm);
+        std::io::copy(&mut decoder, &mut writer)
+            .map_err(|e| PipelineError::OutputFailed(format!("{}: {}", export.filename, e)))?;
+        let (mut file, hash) = writer.finalize_digest();
+        file.flush().map_err(io)?;
+        verify_digest(&export.hash, &hash)
+            .map_err(|e| PipelineError::OutputFailed(format!("{}: written bytes do not match export hash: {}", export.filename, e)))?;
+
+        let sidecar = dir.join(sidecar_name(&export.filename));
+        if emit_checksums {
+            let sha256 = if algorithm == HashAlgorithm::Sha256 {
+                parse_digest(&hash)?.1.to_string()
+            } else {
+                // sha256sum only knows sha256
+                sha256_hex_reader(File::open(dir.join(&export.filename)).map_err(io)?).map_err(io)?
+            };
+            let line = checksum_line(&sha256, &export.filename);
+            fs::write(&sidecar, &line).map_err(io)?;
+            checksums.push((export.filename.clone(), line));
+        } else if sidecar.exists() {
+            fs::remove_file(&sidecar).map_err(io)?;
+        }
+    }
+
+    let manifest = asset.canonical_json()?;
+    fs::write(dir.join(MANIFEST_FILE), manifest.as_bytes()).map_err(io)?;
+    let manifest_line = checksum_line(&sha256_hex(manifest.as_bytes()), MANIFEST_FILE);
+    fs::write(dir.join(MANIFEST_HASH_FILE), &manifest_line).map_err(io)?;
+
+    let checksums_path = dir.join(CHECKSUMS_FILE);
+    if emit_checksums {
+        checksums.push((MANIFEST_FILE.to_string(), manifest_line));
+        checksums.sort();
+        let content: String = checksums.into_iter().map(|(_, line)| line).collect();
+        fs::write(&checksums_path, content).map_err(io)?;
+    } else if checksums_path.exists() {
+        fs::remove_file(&checksums_path).map_err(io)?;
+    }
+
+    let signature_path = dir.join(SIGNATURE_FILE);
+    match signature {
+        Some(signature) => {
+            let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, signature);
+            fs::write(&signature_path, format!("{}\n", encoded)).map_err(io)?;
+        }
+        // Never leave a stale signature from an earlier run next to a new manifest
+        None if signature_path.exists() => fs::remove_file(&signature_path).map_err(io)?,
+        None => {}
+    }
+    Ok(())
+}
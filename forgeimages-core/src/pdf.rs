@@ -0,0 +1,396 @@
+//! PDF - PDF/X Print Exports
+//!
+//! Print vendors take PDF/X, not plain PDF. A print block may name a
+//! `PdfStandard`; its Pdf exports are then written by `encode_pdf` as one
+//! page holding one opaque image, with:
+//! - an `/OutputIntents` entry (`/S /GTS_PDFX`) whose `/DestOutputProfile`
+//!   is the export's ICC profile
+//! - `/TrimBox` (and `/BleedBox` around bleed) on the page
+//! - `/GTS_PDFXVersion` in the Info dictionary and the XMP packet, with
+//!   `/Trapped /False`
+//!
+//! Identifiers are deterministic (Law 4): dates are fixed at the epoch and
+//! the trailer `/ID` and XMP document id come from a digest of the page.
+//!
+//! The pipeline refuses exports that cannot conform before rendering
+//! (`unmet_requirements`) and checks what the renderer returned afterwards
+//! (`check_conformance`), so a non-conformant file is never emitted.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::bleed::TrimBox;
+use crate::icc::IccProfile;
+use crate::print::ColorSpace;
+
+/// Fixed creation and modification date of every PDF/X export
+pub const PDF_DATE: &str = "D:19700101000000Z";
+const XMP_DATE: &str = "1970-01-01T00:00:00Z";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum PdfStandard {
+    /// PDF/X-1a:2001: CMYK or gray only, no transparency, PDF 1.3
+    X1a,
+    /// PDF/X-4: color-managed RGB allowed, PDF 1.6
+    X4,
+}
+
+impl PdfStandard {
+    /// Name used in error messages
+    pub fn label(self) -> &'static str {
+        match self {
+            PdfStandard::X1a => "PDF/X-1a",
+            PdfStandard::X4 => "PDF/X-4",
+        }
+    }
+
+    /// `/GTS_PDFXVersion` value
+    pub fn version(self) -> &'static str {
+        match self {
+            PdfStandard::X1a => "PDF/X-1:2001",
+            PdfStandard::X4 => "PDF/X-4",
+        }
+    }
+
+    /// `/GTS_PDFXConformance` value; only PDF/X-1a has one
+    pub fn conformance(self) -> Option<&'static str> {
+        match self {
+            PdfStandard::X1a => Some("PDF/X-1a:2001"),
+            PdfStandard::X4 => None,
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            PdfStandard::X1a => "%PDF-1.3",
+            PdfStandard::X4 => "%PDF-1.6",
+        }
+    }
+}
+
+/// What to do with partly transparent pixels in a PDF/X export. PDF/X-4
+/// permits live transparency, but `encode_pdf` writes one opaque image, so
+/// the policy applies under both standards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TransparencyPolicy {
+    /// Composite onto white paper
+    #[default]
+    Flatten,
+    /// Fail the export
+    Error,
+}
+
+impl TransparencyPolicy {
+    pub fn is_flatten(&self) -> bool {
+        matches!(self, TransparencyPolicy::Flatten)
+    }
+}
+
+/// Standard and transparency policy of one Pdf export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PdfConformance {
+    pub standard: PdfStandard,
+    pub transparency: TransparencyPolicy,
+}
+
+/// Requirements an export cannot meet before anything is rendered: PDF/X
+/// needs an output intent profile, and PDF/X-1a has no RGB
+pub fn unmet_requirements(standard: PdfStandard, color_space: &ColorSpace, icc: Option<&IccProfile>) -> Vec<String> {
+    let mut unmet = vec![];
+    if icc.is_none() {
+        unmet.push("an output intent ICC profile (iccProfile)".to_string());
+    }
+    if standard == PdfStandard::X1a && *color_space == ColorSpace::Rgb {
+        unmet.push("CMYK or grayscale color (colorSpace is RGB)".to_string());
+    }
+    unmet
+}
+
+/// Device color of the page image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfColor {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+impl PdfColor {
+    pub fn components(self) -> usize {
+        match self {
+            PdfColor::Gray => 1,
+            PdfColor::Rgb => 3,
+            PdfColor::Cmyk => 4,
+        }
+    }
+
+    fn device(self) -> &'static str {
+        match self {
+            PdfColor::Gray => "/DeviceGray",
+            PdfColor::Rgb => "/DeviceRGB",
+            PdfColor::Cmyk => "/DeviceCMYK",
+        }
+    }
+}
+
+/// One page image and where it sits on the press
+pub struct PdfPage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub color: PdfColor,
+    /// Interleaved 8-bit samples, rows top to bottom
+    pub pixels: &'a [u8],
+    pub dpi: u32,
+    /// Trim and bleed boxes within the image; both default to the whole page
+    pub trim: Option<TrimBox>,
+    pub bleed: Option<TrimBox>,
+    /// `/Title` and `dc:title`
+    pub title: &'a str,
+}
+
+/// Write `page` as a PDF/X file with `icc` as its output intent
+pub fn encode_pdf(page: &PdfPage<'_>, standard: PdfStandard, icc: &IccProfile) -> Vec<u8> {
+    assert_eq!(
+        page.pixels.len(),
+        page.width as usize * page.height as usize * page.color.components(),
+        "pixel buffer size mismatch",
+    );
+    let id = document_id(page, standard, icc);
+    let id_hex: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    let title = pdf_text(page.title);
+
+    let media = [0, 0, page.width, page.height];
+    let to_box = |b: Option<TrimBox>| match b {
+        Some(b) => {
+            // Image rows run top to bottom; PDF y runs bottom to top
+            let bottom = page.height - b.offset[1] - b.size[1];
+            [b.offset[0], bottom, b.offset[0] + b.size[0], bottom + b.size[1]]
+        }
+        None => media,
+    };
+    let rect = |b: [u32; 4]| {
+        let points = b.map(|px| number(px as f64 * 72.0 / page.dpi.max(1) as f64));
+        format!("[{} {} {} {}]", points[0], points[1], points[2], points[3])
+    };
+    let bleed_box = match page.bleed {
+        Some(bleed) => format!(" /BleedBox {}", rect(to_box(Some(bleed)))),
+        None => String::new(),
+    };
+    let [_, _, width_pt, height_pt] = media.map(|px| number(px as f64 * 72.0 / page.dpi.max(1) as f64));
+
+    let mut info = format!(
+        "<< /Title ({}) /Producer (ForgeImages) /CreationDate ({}) /ModDate ({}) /Trapped /False /GTS_PDFXVersion ({})",
+        title, PDF_DATE, PDF_DATE, standard.version(),
+    );
+    if let Some(conformance) = standard.conformance() {
+        info.push_str(&format!(" /GTS_PDFXConformance ({})", conformance));
+    }
+    info.push_str(" >>");
+
+    let mut pdf = PdfWriter::new(standard.header());
+    pdf.object(b"<< /Type /Catalog /Pages 2 0 R /Metadata 3 0 R /OutputIntents [4 0 R] >>");
+    pdf.object(b"<< /Type /Pages /Kids [6 0 R] /Count 1 >>");
+    pdf.stream("/Type /Metadata /Subtype /XML", xmp(page.title, standard, &id_hex).as_bytes());
+    pdf.object(format!(
+        "<< /Type /OutputIntent /S /GTS_PDFX /OutputConditionIdentifier ({name}) /Info ({name}) /RegistryName (http://www.color.org) /DestOutputProfile 5 0 R >>",
+        name = pdf_text(icc.name()),
+    ).as_bytes());
+    pdf.stream(&format!("/N {}", icc_components(icc)), icc.data());
+    pdf.object(format!(
+        "<< /Type /Page /Parent 2 0 R /MediaBox {} /TrimBox {}{} /Resources << /XObject << /Im0 7 0 R >> >> /Contents 8 0 R >>",
+        rect(media), rect(to_box(page.trim)), bleed_box,
+    ).as_bytes());
+    pdf.stream(
+        &format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace {} /BitsPerComponent 8",
+            page.width, page.height, page.color.device(),
+        ),
+        page.pixels,
+    );
+    pdf.stream("", format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", width_pt, height_pt).as_bytes());
+    pdf.object(info.as_bytes());
+    pdf.finish(&format!("/Root 1 0 R /Info 9 0 R /ID [<{id}> <{id}>]", id = id_hex))
+}
+
+/// Structural checks on finished bytes: whatever produced them, a file
+/// missing any of these is not the PDF/X it claims to be
+pub fn check_conformance(bytes: &[u8], standard: PdfStandard) -> Vec<String> {
+    let contains = |needle: &str| bytes.windows(needle.len()).any(|window| window == needle.as_bytes());
+    let mut unmet = vec![];
+    if !bytes.starts_with(b"%PDF-") {
+        unmet.push("a PDF file".to_string());
+        return unmet;
+    }
+    let mut require = |needle: &str, what: &str| {
+        if !contains(needle) {
+            unmet.push(what.to_string());
+        }
+    };
+    require("/OutputIntents", "an output intent (/OutputIntents)");
+    require("/S /GTS_PDFX", "a PDF/X output intent (/S /GTS_PDFX)");
+    require("/DestOutputProfile", "an output profile (/DestOutputProfile)");
+    require(&format!("/GTS_PDFXVersion ({})", standard.version()), "the version identifier (/GTS_PDFXVersion)");
+    if let Some(conformance) = standard.conformance() {
+        require(&format!("/GTS_PDFXConformance ({})", conformance), "the conformance identifier (/GTS_PDFXConformance)");
+    }
+    require("/Trapped /False", "a trapping key (/Trapped)");
+    require("/TrimBox", "a trim box (/TrimBox)");
+    require("/ID [", "a document id (/ID)");
+    if standard == PdfStandard::X1a {
+        for (needle, what) in [("/SMask", "soft masks"), ("/DeviceRGB", "RGB color"), ("/Transparency", "transparency groups")] {
+            if contains(needle) {
+                unmet.push(format!("no {} ({})", what, needle));
+            }
+        }
+    }
+    unmet
+}
+
+fn icc_components(icc: &IccProfile) -> usize {
+    match icc.color_space() {
+        crate::icc::IccColorSpace::Gray => 1,
+        crate::icc::IccColorSpace::Rgb => 3,
+        crate::icc::IccColorSpace::Cmyk => 4,
+    }
+}
+
+/// First 16 bytes of a digest over everything the page shows
+fn document_id(page: &PdfPage<'_>, standard: PdfStandard, icc: &IccProfile) -> [u8; 16] {
+    let mut hasher = Sha256::new();
+    hasher.update(standard.version().as_bytes());
+    hasher.update([0]);
+    hasher.update(page.title.as_bytes());
+    hasher.update([0]);
+    for value in [page.width, page.height, page.dpi, page.color.components() as u32] {
+        hasher.update(value.to_be_bytes());
+    }
+    for b in [page.trim, page.bleed].into_iter().flatten() {
+        for value in b.offset.into_iter().chain(b.size) {
+            hasher.update(value.to_be_bytes());
+        }
+    }
+    hasher.update(icc.sha256().as_bytes());
+    hasher.update(page.pixels);
+    let digest = hasher.finalize();
+    let mut id = [0; 16];
+    id.copy_from_slice(&digest[..16]);
+    id
+}
+
+fn xmp(title: &str, standard: PdfStandard, id_hex: &str) -> String {
+    let uuid = format!(
+        "{}-{}-{}-{}-{}",
+        &id_hex[..8], &id_hex[8..12], &id_hex[12..16], &id_hex[16..20], &id_hex[20..],
+    );
+    let conformance = standard.conformance()
+        .map(|c| format!("\n   <pdfx:GTS_PDFXConformance>{}</pdfx:GTS_PDFXConformance>", c))
+        .unwrap_or_default();
+    format!(
+        r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/"
+    xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+    xmlns:pdfx="http://ns.adobe.com/pdfx/1.3/"
+    xmlns:pdfxid="http://www.npes.org/pdfx/ns/id/">
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+   <xmp:CreateDate>{date}</xmp:CreateDate>
+   <xmp:ModifyDate>{date}</xmp:ModifyDate>
+   <xmp:MetadataDate>{date}</xmp:MetadataDate>
+   <xmpMM:DocumentID>uuid:{uuid}</xmpMM:DocumentID>
+   <xmpMM:InstanceID>uuid:{uuid}</xmpMM:InstanceID>
+   <pdf:Producer>ForgeImages</pdf:Producer>
+   <pdf:Trapped>False</pdf:Trapped>
+   <pdfx:GTS_PDFXVersion>{version}</pdfx:GTS_PDFXVersion>{conformance}
+   <pdfxid:GTS_PDFXVersion>{version}</pdfxid:GTS_PDFXVersion>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="r"?>"#,
+        title = xml_text(title),
+        date = XMP_DATE,
+        uuid = uuid,
+        version = standard.version(),
+        conformance = conformance,
+    )
+}
+
+/// Printable ASCII literal string contents; anything else becomes `?`
+fn pdf_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+fn xml_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Up to four decimals, trailing zeros dropped
+fn number(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text.is_empty() { "0".to_string() } else { text.to_string() }
+}
+
+/// Objects numbered from 1 in the order written, then the xref table
+struct PdfWriter {
+    out: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new(header: &str) -> Self {
+        let mut out = Vec::new();
+        out.extend_from_slice(header.as_bytes());
+        // Binary marker comment, as the spec recommends for files with binary streams
+        out.extend_from_slice(b"\n%\xE2\xE3\xCF\xD3\n");
+        Self { out, offsets: vec![] }
+    }
+
+    fn object(&mut self, body: &[u8]) {
+        self.offsets.push(self.out.len());
+        self.out.extend_from_slice(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+        self.out.extend_from_slice(body);
+        self.out.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, dict: &str, data: &[u8]) {
+        let separator = if dict.is_empty() { "" } else { " " };
+        let mut body = format!("<< {}{}/Length {} >>\nstream\n", dict, separator, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.object(&body);
+    }
+
+    fn finish(mut self, trailer: &str) -> Vec<u8> {
+        let xref = self.out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} {} >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1, trailer, xref,
+        ));
+        self.out.extend_from_slice(table.as_bytes());
+        self.out
+    }
+}
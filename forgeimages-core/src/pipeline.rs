@@ -2,14 +2,44 @@
 //!
 //! CRITICAL: compile_asset MUST call validate internally. No bypass.
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Instant;
+
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use crate::templates::{Template, TemplateRegistry, ExportSpec};
-use crate::validation::{Validator, ValidationResult, AssetInput};
-use crate::hashing::{compute_manifest_hash, compute_job_hash};
+use crate::templates::{Template, TemplateRegistry, ExportSpec, ExportFormat, ScalingRecord};
+use crate::validation::{Validator, ValidationResult, ValidationViolation, ViolationSeverity, AssetInput, RequestContext, raster_source_size};
+use crate::render::{Renderer, RenderError, RenderJob, PlaceholderRenderer, RetryPolicy};
+use crate::raster::Raster;
+use crate::background;
+use crate::font::{Font, LoadedFont};
+use crate::svg;
+use crate::encoding::{EncodingProfile, PngMetadata};
+use crate::color::{CmykConverter, ColorConversion, ConversionMethod, NaiveCmyk, SpotCmyk, SpotColorUsage, SpotColors};
+use crate::bleed::{Guides, PrintLayout, TrimBox};
+use crate::imposition::{ImpositionRecord, SheetLayout};
+use crate::icc::{EmbeddedProfile, IccProfile, IccProfileStore};
+use crate::pdf::{self, PdfConformance, PdfStandard};
+use crate::print::{self, ColorSpace, Marks, PrintAuthority, PrintOverride, PrintSpec};
+use crate::units::Length;
+use crate::audit::{AuditEvent, AuditOutcome, AuditSink};
+use crate::cancel::{CancelReason, CancelToken};
+use crate::config::{JobHashKeyConfig, Limits, PipelineConfig, Secret, SigningSettings, REDACTED};
+use crate::error::{ErrorEnvelope, ErrorKind};
+use crate::output;
+use crate::notify::Notifier;
+use crate::sink::{self, ExportSink, StoredExports, StoredObject};
+#[cfg(feature = "signing")]
+use crate::signing::{self, SigningConfig};
+use crate::hashing::{compute_job_hash_with, exports_root, parse_digest, verify_digest, HashAlgorithm, HashScheme, HashingError, HashingWriter, JobHashKey};
 use crate::ENGINE_VERSION;
 
 #[cfg(feature = "test-hooks")]
@@ -36,73 +66,873 @@ pub enum PipelineError {
     #[error("Validation failed: {0}")]
     ValidationFailed(String),
 
-    #[error("Template version {0} requires engine >= {1}, current is {2}")]
-    EngineVersionMismatch(String, String, String),
+    #[error("Template version {1} requires engine {2}, current is {3}: {}", .0.remediation())]
+    EngineVersionMismatch(EngineBound, String, String, String),
 
     #[error("Compilation error: {0}")]
     CompilationError(String),
 
+    #[error("Required export {0} failed: {1}")]
+    ExportFailed(String, String),
+
+    #[error("Invalid source: {0}")]
+    InvalidSource(String),
+
+    #[error("Source path not allowed: {0}")]
+    SourceNotAllowed(String),
+
+    #[error("Output error: {0}")]
+    OutputFailed(String),
+
+    #[error("Audit log error: {0}")]
+    AuditFailed(String),
+
+    #[error("Sandbox violation: {0}")]
+    SandboxViolation(String),
+
+    #[error("ICC profile not registered: {0}")]
+    IccProfileNotFound(String),
+
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("Hashing error: {0}")]
+    HashingError(#[from] HashingError),
+
+    /// The pipeline's `CancelToken` fired; the export is the one that was
+    /// rendering, if any
+    #[error("Compile {0}{}", .1.as_ref().map(|id| format!(" while rendering export {}", id)).unwrap_or_default())]
+    Cancelled(CancelReason, Option<String>),
+}
+
+impl PipelineError {
+    /// Stable snake_case name of the variant: the `ErrorKind` front-ends
+    /// print; renaming one breaks their consumers
+    pub fn kind(&self) -> &'static str {
+        ErrorKind::from(self).name()
+    }
+}
+
+/// What a compile may touch beyond its declared inputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxMode {
+    #[default]
+    Off,
+    /// No `source_path` reads, only `sandbox_safe` renderers, and template
+    /// assets only from the registry's in-memory store. Templates are
+    /// never fetched remotely, sandboxed or not.
+    Enforced,
+}
+
+/// Which end of a template's engine range the running engine falls outside
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineBound {
+    /// Below `engine_min_version`
+    TooOld,
+    /// At or above `engine_max_version`
+    TooNew,
+}
+
+impl EngineBound {
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            EngineBound::TooOld => "this template targets a newer engine; upgrade the engine",
+            EngineBound::TooNew => "this template targets an older engine; use a newer template version",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(optional_fields = nullable))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CompileRequest {
     pub template_id: String,
     pub asset_input: AssetInput,
     #[serde(default)]
     pub source_data: Option<String>,  // Base64 encoded source
-    #[serde(default)]
+    /// Source file under the pipeline's source root; exclusive with source_data.
+    /// Only the file's hash enters the job hash, never the path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
+    /// A number or a decimal string; written as a string above 2^53 - 1
+    #[cfg_attr(feature = "ts", ts(type = "number | string | null", optional))]
+    #[serde(default, with = "crate::hashing::json_u64")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "crate::hashing::json_u64::schema"))]
     pub seed: Option<u64>,
     #[serde(default)]
     pub prompt: Option<String>,
+    /// Template parameters, e.g. text slot values keyed by slot id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, serde_json::Value>,
+    /// User print override; resolved against the template's print block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print_spec: Option<PrintSpec>,
+}
+
+/// What a compile was asked for, as its manifest records it. The source
+/// is identified by the manifest's `source_hash`, never stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RequestRecord {
+    pub asset_input: AssetInput,
+    /// How the source was supplied; absent without a source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceKind>,
+    #[cfg_attr(feature = "ts", ts(type = "number | string | null"))]
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::hashing::json_u64")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "crate::hashing::json_u64::schema"))]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print_spec: Option<PrintSpec>,
+}
+
+/// `source_data` or `source_path`; they identify a source differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    Inline,
+    Path,
+}
+
+impl RequestRecord {
+    pub fn of(request: &CompileRequest) -> Self {
+        let source = match (&request.source_data, &request.source_path) {
+            (_, Some(_)) => Some(SourceKind::Path),
+            (Some(_), None) => Some(SourceKind::Inline),
+            (None, None) => None,
+        };
+        Self {
+            asset_input: request.asset_input.clone(),
+            source,
+            seed: request.seed,
+            prompt: request.prompt.clone(),
+            params: request.params.clone(),
+            print_spec: request.print_spec.clone(),
+        }
+    }
+
+    /// The request again, with `source` supplied the recorded way: inline
+    /// as base64, or as a path under the pipeline's source root
+    pub fn request(&self, template_id: &str, source: Option<RecordedSource<'_>>) -> CompileRequest {
+        let (source_data, source_path) = match source {
+            Some(RecordedSource::Inline(bytes)) => {
+                (Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)), None)
+            }
+            Some(RecordedSource::Path(path)) => (None, Some(path.to_path_buf())),
+            None => (None, None),
+        };
+        CompileRequest {
+            template_id: template_id.to_string(),
+            asset_input: self.asset_input.clone(),
+            source_data,
+            source_path,
+            seed: self.seed,
+            prompt: self.prompt.clone(),
+            params: self.params.clone(),
+            print_spec: self.print_spec.clone(),
+        }
+    }
+}
+
+/// A source handed back to `RequestRecord::request`
+#[derive(Debug, Clone, Copy)]
+pub enum RecordedSource<'a> {
+    Inline(&'a [u8]),
+    /// Relative to the pipeline's source root
+    Path(&'a Path),
+}
+
+/// What a compile would print with, resolved without rendering
+/// (`CompilationPipeline::print_plan`)
+#[derive(Debug, Clone, Serialize)]
+pub struct PrintPlan {
+    pub template_id: String,
+    pub template_version: String,
+    /// `print_override` findings under the template's failure mode; when
+    /// not valid a compile stops here, and so does the plan
+    pub validation: ValidationResult,
+    /// The template-level spec (the manifest's `print`)
+    pub print: Option<PlannedPrint>,
+    /// Print-format exports, in template order
+    pub exports: Vec<PlannedExport>,
+}
+
+/// An effective spec, where each field came from, and what the user replaced
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedPrint {
+    pub spec: PrintSpec,
+    /// Authority per field, by `PrintField::name`
+    pub provenance: BTreeMap<&'static str, PrintAuthority>,
+    pub overrides: Vec<PrintOverride>,
+}
+
+impl PlannedPrint {
+    fn of(resolved: print::Resolved) -> Self {
+        let provenance = print::PrintField::ALL.into_iter()
+            .map(|field| (field.name(), resolved.authority(field)))
+            .collect();
+        Self { spec: resolved.spec, provenance, overrides: resolved.overrides }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedExport {
+    pub id: String,
+    pub format: String,
+    pub print: PlannedPrint,
+    /// The export's size before bleed and marks
+    pub trim: [u32; 2],
+    /// Pixel size of the file: trim plus bleed and marks, or the press sheet
+    pub size: [u32; 2],
+}
+
+/// What a compile would write, resolved without rendering
+/// (`CompilationPipeline::compile_plan`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CompilePlan {
+    pub template_id: String,
+    pub template_version: String,
+    /// The job hash the manifest would record
+    pub job_hash: String,
+    pub source_hash: Option<String>,
+    /// Every rule, as compile runs them; when not valid a compile stops
+    /// here, and so does the plan
+    pub validation: ValidationResult,
+    /// The template-level spec (the manifest's `print`)
+    pub print: Option<PlannedPrint>,
+    /// Every export, in template order
+    pub exports: Vec<PlannedFile>,
+}
+
+/// One file a compile would write, sized as its manifest entry would be
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFile {
+    pub id: String,
+    pub filename: String,
+    pub format: String,
+    /// Pixel size of the file: bleed and marks, or the press sheet, included
+    pub size: [u32; 2],
+    /// Trim box within the file, when bleed or marks enlarge it
+    pub trim: Option<TrimBox>,
+    /// Whether a failed render fails the compile, rather than becoming a warning
+    pub required: bool,
+    /// False for soft proofs and guide previews
+    pub deliverable: bool,
+    pub scaling: ScalingRecord,
+    pub color_space: ColorSpace,
+    /// Effective print spec; Pdf/Jpg/Tiff only
+    pub print: Option<PrintSpec>,
+    pub pdf_standard: Option<PdfStandard>,
+}
+
+/// The run-specific fields of a manifest, fixed so a recompile can
+/// reproduce its hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestStamp {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub config_hash: String,
+}
+
+impl ManifestStamp {
+    pub fn of(asset: &CompiledAsset) -> Self {
+        Self { id: asset.id.clone(), created_at: asset.created_at, config_hash: asset.config_hash.clone() }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CompiledAsset {
     pub id: String,
     pub template_id: String,
     pub template_version: String,
+    /// `Template::content_hash` of the template as compiled; empty in older manifests
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub template_hash: String,
     pub engine_version: String,
     pub created_at: DateTime<Utc>,
+    /// Algorithm behind every digest in this manifest
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// How job and manifest hashes were built; absent means `fi-hash-0`
+    #[serde(default)]
+    pub hash_scheme: HashScheme,
     pub manifest_hash: String,
     pub job_hash: String,
+    /// `job_hash` is HMAC-SHA256 under the key named by `job_hash_key_id`
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub job_hash_keyed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_hash_key_id: Option<String>,
     pub validation: ValidationResult,
+    /// Warning violations in `validation`, surfaced so callers can't miss them
+    pub warning_count: u32,
+    pub has_warnings: bool,
+    pub encoding: EncodingProfile,
+    /// `PipelineConfig::hash` of the compiling pipeline's effective config;
+    /// empty in older manifests
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub config_hash: String,
+    /// Digest of the template font used to outline text slots
+    #[serde(default)]
+    pub font_hash: Option<String>,
+    /// Key that signs this manifest; the signature itself is never hashed
+    #[serde(default)]
+    pub signer: Option<SignerInfo>,
+    /// Digest of the decoded source bytes
+    #[serde(default)]
+    pub source_hash: Option<String>,
+    /// Digest of the normalized SVG, when the pipeline normalizes sources
+    #[serde(default)]
+    pub normalized_source_hash: Option<String>,
+    /// The request compiled, minus its source bytes; absent in older manifests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<RequestRecord>,
+    /// Effective print spec, including the authority it came from
+    pub print: PrintSpec,
+    /// Fields of `print` the user's spec supplied, with the template values
+    /// they replaced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub print_overrides: Vec<PrintOverride>,
+    /// Print profile the template block was built from; `print` already
+    /// holds its effective values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print_profile: Option<String>,
+    /// Merkle root over the export digests (see `merkle`); `None` without exports
+    #[serde(default)]
+    pub exports_root: Option<String>,
     pub exports: Vec<ExportedFile>,
+    /// Optional exports that failed to render or store (covered by the manifest hash)
+    #[serde(default)]
+    pub export_errors: Vec<ExportError>,
+    /// Where the pipeline's `ExportSink` stored each export (see `sink`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stored_objects: Vec<StoredObject>,
+    /// Runtime facts about this compile; not part of the manifest
+    #[serde(skip)]
+    pub metrics: CompileMetrics,
+    /// Canonical manifest text kept from hashing; never serialized
+    #[serde(skip)]
+    pub canonical: CanonicalDocument,
+}
+
+impl CompiledAsset {
+    /// The manifest as canonical JSON, as written to manifest.json.
+    ///
+    /// A freshly compiled asset returns the text produced while hashing it;
+    /// a deserialized one, or one whose `manifest_hash` has since changed,
+    /// is serialized again. After editing other fields, clear `canonical`
+    /// (or call `hashing::canonical_json`).
+    pub fn canonical_json(&self) -> Result<Cow<'_, str>, HashingError> {
+        match self.canonical.text_for(&self.manifest_hash) {
+            Some(text) => Ok(Cow::Borrowed(text)),
+            None => Ok(Cow::Owned(crate::hashing::canonical_json(self)?)),
+        }
+    }
+}
+
+/// Canonical JSON of a compiled manifest, kept from hashing it.
+///
+/// Hashing a fresh asset canonicalizes nearly all of it, base64 exports
+/// included; keeping that text saves writers a second pass. It is never
+/// serialized, so a deserialized asset has none, and verification always
+/// recomputes from the manifest it reads.
+#[derive(Clone, Default)]
+pub struct CanonicalDocument(Option<CachedCanonical>);
+
+#[derive(Clone)]
+struct CachedCanonical {
+    /// The manifest hash the text was built with
+    manifest_hash: String,
+    text: Arc<str>,
+}
+
+impl CanonicalDocument {
+    pub fn is_cached(&self) -> bool {
+        self.0.is_some()
+    }
+
+    fn text_for(&self, manifest_hash: &str) -> Option<&str> {
+        self.0.as_ref()
+            .filter(|cached| cached.manifest_hash == manifest_hash)
+            .map(|cached| &*cached.text)
+    }
+
+    /// Hash a fresh asset (`manifest_hash` still empty) under `scheme`,
+    /// keeping the canonical text when the hashed view is the whole manifest
+    fn hash(asset: &CompiledAsset, scheme: HashScheme, algorithm: HashAlgorithm) -> Result<(String, Self), HashingError> {
+        let value = serde_json::to_value(asset)?;
+        let spliceable = scheme == HashScheme::V1
+            && asset.manifest_hash.is_empty()
+            && ManifestHashView::covers_all_but_hash(&value);
+        let Some(object) = value.as_object().filter(|_| spliceable) else {
+            return Ok((ManifestHashView::from_json(&value).hash(scheme, algorithm)?, Self::default()));
+        };
+
+        // With nothing else excluded, the hashed text is the manifest with
+        // `"manifest_hash":""`; splice the hash into that slot
+        let (hashed, slot) = crate::hashing::canonical_object_with_slot(object, "manifest_hash")?;
+        let manifest_hash = scheme.manifest_hash_of_canonical(algorithm, &hashed);
+        let slot = slot.expect("serialized assets have a manifest_hash");
+        let mut text = String::with_capacity(hashed.len() + manifest_hash.len());
+        text.push_str(&hashed[..slot + 1]);
+        text.push_str(&manifest_hash);
+        text.push_str(&hashed[slot + 1..]);
+        let cached = CachedCanonical { manifest_hash: manifest_hash.clone(), text: text.into() };
+        Ok((manifest_hash, Self(Some(cached))))
+    }
+}
+
+impl fmt::Debug for CanonicalDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(cached) => write!(f, "CanonicalDocument({} bytes)", cached.text.len()),
+            None => f.write_str("CanonicalDocument(None)"),
+        }
+    }
+}
+
+/// Per-compile runtime metrics, kept out of the manifest and its hash
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileMetrics {
+    pub exports: Vec<ExportMetrics>,
+    /// Exports whose bytes were reused instead of rendered
+    pub deduplicated: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportMetrics {
+    pub export_id: String,
+    /// Render attempts, including retries of transient failures
+    /// (0 when the bytes were reused from an identical export)
+    pub attempts: u32,
+}
+
+impl CompileMetrics {
+    pub fn attempts(&self, export_id: &str) -> Option<u32> {
+        self.exports.iter().find(|e| e.export_id == export_id).map(|e| e.attempts)
+    }
+
+    /// Exports that invoked the renderer at least once
+    pub fn rendered(&self) -> u32 {
+        self.exports.iter().filter(|e| e.attempts > 0).count() as u32
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ExportedFile {
     pub id: String,
     pub filename: String,
     pub format: String,
+    /// File dimensions, bleed and marks included (see `trim`)
     pub size: [u32; 2],
+    /// Trim box within the file, when bleed or marks enlarge it past the spec size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim: Option<TrimBox>,
+    /// Printer's marks drawn outside the trim
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marks: Option<Marks>,
     pub data_base64: String,
     pub hash: String,
+    /// Effective scaling policy and whether this export upscaled the source
+    pub scaling: ScalingRecord,
+    /// Print spec the export was rendered for; Pdf/Jpg/Tiff only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print: Option<PrintSpec>,
+    /// How CMYK pixels were produced; set for CMYK print exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_conversion: Option<ColorConversion>,
+    /// ICC profile embedded in the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<EmbeddedProfile>,
+    /// Spot colors mapped during CMYK conversion, with the pixels each matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spot_colors: Vec<SpotColorUsage>,
+    /// PDF/X standard the file conforms to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pdf_standard: Option<PdfStandard>,
+    /// Soft-proof or guide preview (`ExportSpec::is_proof`)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub proof: bool,
+    /// Whether the file is meant for delivery; false for proofs
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub deliverable: bool,
+    /// N-up layout, when the file is an imposed press sheet (`size` is the sheet's)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imposition: Option<ImpositionRecord>,
+    /// Export whose render produced these bytes, when deduplication reused it
+    /// (not covered by the manifest hash)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deduplicated_from: Option<String>,
+}
+
+/// Signing key id and algorithm recorded in a signed manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SignerInfo {
+    pub key_id: String,
+    pub algorithm: String,
+}
+
+/// Failure of an optional export, recorded instead of aborting the compile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExportError {
+    pub export_id: String,
+    pub message: String,
+    pub spec: ExportSpec,
 }
 
 /// The compilation pipeline - single entry point for all asset operations
+///
+/// `Send + Sync`: share one instance behind an `Arc`. The registry can be
+/// swapped while compiles run; each compile uses the registry it started with.
 pub struct CompilationPipeline {
-    registry: TemplateRegistry,
+    registry: RwLock<Arc<TemplateRegistry>>,
     validator: Validator,
+    renderer: Box<dyn Renderer>,
+    encoding: EncodingProfile,
+    audit: Option<Box<dyn AuditSink>>,
+    sink: Option<Box<dyn ExportSink>>,
+    notifier: Option<Box<dyn Notifier>>,
+    retry: RetryPolicy,
+    source_root: Option<PathBuf>,
+    normalize_source: bool,
+    fail_on_warnings: bool,
+    deduplicate_exports: bool,
+    export_parallelism: usize,
+    emit_checksums: bool,
+    sandbox: SandboxMode,
+    hash_algorithm: HashAlgorithm,
+    job_hash_key: Option<JobHashKey>,
+    cmyk: Box<dyn CmykConverter>,
+    icc_profiles: IccProfileStore,
+    cancel: Option<CancelToken>,
+    clock: Option<DateTime<Utc>>,
+    #[cfg(feature = "signing")]
+    signing: Option<SigningConfig>,
+    /// The `PipelineConfig` built from, for the file paths it names
+    config: PipelineConfig,
+}
+
+/// Builder for pipelines that need more than the default configuration
+pub struct PipelineBuilder {
+    registry: TemplateRegistry,
+    renderer: Box<dyn Renderer>,
+    encoding: EncodingProfile,
+    audit: Option<Box<dyn AuditSink>>,
+    sink: Option<Box<dyn ExportSink>>,
+    notifier: Option<Box<dyn Notifier>>,
+    retry: RetryPolicy,
+    source_root: Option<PathBuf>,
+    normalize_source: bool,
+    fail_on_warnings: bool,
+    deduplicate_exports: bool,
+    export_parallelism: usize,
+    emit_checksums: bool,
+    sandbox: SandboxMode,
+    hash_algorithm: HashAlgorithm,
+    job_hash_key: Option<JobHashKey>,
+    cmyk: Box<dyn CmykConverter>,
+    icc_profiles: IccProfileStore,
+    cancel: Option<CancelToken>,
+    clock: Option<DateTime<Utc>>,
+    #[cfg(feature = "signing")]
+    signing: Option<SigningConfig>,
+    /// The `PipelineConfig` built from, for the file paths it names
+    config: PipelineConfig,
+}
+
+impl PipelineBuilder {
+    /// Replace the default placeholder renderer
+    pub fn renderer(mut self, renderer: impl Renderer + 'static) -> Self {
+        self.renderer = Box::new(renderer);
+        self
+    }
+
+    /// Override the pinned encoder parameters (recorded in every manifest)
+    pub fn encoding(mut self, encoding: EncodingProfile) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Canonicalize SVG sources before hashing and rendering
+    pub fn normalize_source(mut self, normalize: bool) -> Self {
+        self.normalize_source = normalize;
+        self
+    }
+
+    /// Reject compiles that would succeed with warnings (for CI)
+    pub fn fail_on_warnings(mut self, fail: bool) -> Self {
+        self.fail_on_warnings = fail;
+        self
+    }
+
+    /// Render identical exports (same format, size, background and print
+    /// spec) once and reuse the bytes; the renderer must not depend on export ids
+    pub fn deduplicate_exports(mut self, deduplicate: bool) -> Self {
+        self.deduplicate_exports = deduplicate;
+        self
+    }
+
+    /// Hash and base64-encode up to `threads` rendered exports at once
+    /// (default 1). Renders stay serial; at most `threads` rendered exports
+    /// are held in memory before they are encoded.
+    pub fn export_parallelism(mut self, threads: usize) -> Self {
+        self.export_parallelism = threads.max(1);
+        self
+    }
+
+    /// Have `compile_to_dir` write a `.sha256` sidecar per export and a
+    /// `checksums.txt` (see `output`)
+    pub fn emit_checksums(mut self, emit: bool) -> Self {
+        self.emit_checksums = emit;
+        self
+    }
+
+    /// Confine compiles to their declared inputs (for untrusted templates)
+    pub fn sandbox(mut self, mode: SandboxMode) -> Self {
+        self.sandbox = mode;
+        self
+    }
+
+    /// Algorithm for every digest the pipeline records (default sha256)
+    pub fn hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = algorithm;
+        self
+    }
+
+    /// Key job hashes with HMAC-SHA256 (for deployments where tenants can
+    /// see each other's job hashes)
+    pub fn job_hash_key(mut self, key: JobHashKey) -> Self {
+        self.job_hash_key = Some(key);
+        self
+    }
+
+    /// Replace the naive device conversion used for CMYK print exports
+    pub fn cmyk_converter(mut self, converter: impl CmykConverter + 'static) -> Self {
+        self.cmyk = Box::new(converter);
+        self
+    }
+
+    /// Output profiles print specs may name (none by default)
+    pub fn icc_profiles(mut self, store: IccProfileStore) -> Self {
+        self.icc_profiles = store;
+        self
+    }
+
+    /// Retry renders that fail with retryable errors
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Allow `source_path` requests, confined to files under `root`
+    pub fn source_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.source_root = Some(root.into());
+        self
+    }
+
+    /// Sign manifests written by `compile_to_dir`
+    #[cfg(feature = "signing")]
+    pub fn signing(mut self, config: SigningConfig) -> Self {
+        self.signing = Some(config);
+        self
+    }
+
+    /// Stop compiles at the next checkpoint once `token` is cancelled
+    /// (see `cancel`)
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Stamp every manifest and audit event with `now` instead of the
+    /// current time, and derive manifest ids from it and the job hash, so
+    /// repeated runs write identical bytes (for tests and golden files)
+    pub fn clock(mut self, now: DateTime<Utc>) -> Self {
+        self.clock = Some(now);
+        self
+    }
+
+    /// Record every compile attempt, including blocked ones
+    pub fn audit_sink(mut self, sink: impl AuditSink + 'static) -> Self {
+        self.audit = Some(Box::new(sink));
+        self
+    }
+
+    /// Store each compile's exports in `sink` and record them in the
+    /// manifest's `stored_objects` (see `sink`)
+    pub fn export_sink(mut self, sink: impl ExportSink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Tell `notifier` how every compile attempt ended (see `notify`)
+    pub fn notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifier = Some(Box::new(notifier));
+        self
+    }
+
+    /// Remember the `PipelineConfig` this builder was configured from
+    pub(crate) fn config(mut self, config: PipelineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn build(self) -> CompilationPipeline {
+        CompilationPipeline {
+            registry: RwLock::new(Arc::new(self.registry)),
+            validator: Validator::new().fail_on_warnings(self.fail_on_warnings),
+            renderer: self.renderer,
+            encoding: self.encoding,
+            audit: self.audit,
+            sink: self.sink,
+            notifier: self.notifier,
+            retry: self.retry,
+            source_root: self.source_root,
+            normalize_source: self.normalize_source,
+            fail_on_warnings: self.fail_on_warnings,
+            deduplicate_exports: self.deduplicate_exports,
+            export_parallelism: self.export_parallelism,
+            emit_checksums: self.emit_checksums,
+            sandbox: self.sandbox,
+            hash_algorithm: self.hash_algorithm,
+            job_hash_key: self.job_hash_key,
+            cmyk: self.cmyk,
+            icc_profiles: self.icc_profiles,
+            cancel: self.cancel,
+            clock: self.clock,
+            #[cfg(feature = "signing")]
+            signing: self.signing,
+            config: self.config,
+        }
+    }
 }
 
 impl CompilationPipeline {
     pub fn new(registry: TemplateRegistry) -> Self {
-        Self {
+        Self::builder(registry).build()
+    }
+
+    pub fn builder(registry: TemplateRegistry) -> PipelineBuilder {
+        PipelineBuilder {
             registry,
-            validator: Validator::new(),
+            renderer: Box::new(PlaceholderRenderer),
+            encoding: EncodingProfile::default(),
+            audit: None,
+            sink: None,
+            notifier: None,
+            retry: RetryPolicy::default(),
+            source_root: None,
+            normalize_source: false,
+            fail_on_warnings: false,
+            deduplicate_exports: false,
+            export_parallelism: 1,
+            emit_checksums: false,
+            sandbox: SandboxMode::Off,
+            hash_algorithm: HashAlgorithm::Sha256,
+            job_hash_key: None,
+            cmyk: Box::new(NaiveCmyk),
+            icc_profiles: IccProfileStore::new(),
+            cancel: None,
+            clock: None,
+            #[cfg(feature = "signing")]
+            signing: None,
+            config: PipelineConfig::default(),
         }
     }
 
+    /// The pipeline's clock: fixed by `PipelineBuilder::clock`, else the current time
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.unwrap_or_else(Utc::now)
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Id of the key job hashes are keyed with, if any
+    pub fn job_hash_key_id(&self) -> Option<&str> {
+        self.job_hash_key.as_ref().map(JobHashKey::key_id)
+    }
+
+    /// The effective config, inline secrets redacted (for diagnostics).
+    /// Files are reported as the `PipelineConfig` built from named them;
+    /// an audit sink or ICC profiles given to the builder directly have
+    /// no path to report.
+    pub fn config(&self) -> PipelineConfig {
+        let given = &self.config;
+        let job_hash_key = self.job_hash_key.as_ref().map(|key| JobHashKeyConfig {
+            key_id: key.key_id().to_string(),
+            secret: given.job_hash_key.as_ref()
+                .filter(|given| given.key_id == key.key_id())
+                .map_or_else(|| Secret::Value(REDACTED.to_string()), |given| given.secret.clone()),
+        });
+        #[cfg(feature = "signing")]
+        let signing = SigningSettings {
+            enabled: self.signing.is_some(),
+            key_path: given.signing.key_path.clone().filter(|_| self.signing.is_some()),
+            key_id: self.signing.as_ref().map(|signing| signing.key_id.clone()),
+        };
+        #[cfg(not(feature = "signing"))]
+        let signing = SigningSettings::default();
+        PipelineConfig {
+            renderer: self.renderer.name().to_string(),
+            limits: Limits {
+                export_parallelism: self.export_parallelism,
+                render_attempts: self.retry.max_attempts,
+                retry_backoff_ms: self.retry.backoff.as_millis() as u64,
+            },
+            encoding: self.encoding.clone(),
+            hash_algorithm: self.hash_algorithm,
+            normalize_source: self.normalize_source,
+            fail_on_warnings: self.fail_on_warnings,
+            deduplicate_exports: self.deduplicate_exports,
+            emit_checksums: self.emit_checksums,
+            sandbox: self.sandbox,
+            source_root: self.source_root.clone(),
+            audit_log: given.audit_log.clone().filter(|_| self.audit.is_some()),
+            icc_profiles: given.icc_profiles.iter()
+                .filter(|(name, _)| self.icc_profiles.get(name).is_some())
+                .map(|(name, path)| (name.clone(), path.clone()))
+                .collect(),
+            job_hash_key,
+            signing,
+        }.redacted()
+    }
+
+    /// Snapshot of the current template registry
+    pub fn registry(&self) -> Arc<TemplateRegistry> {
+        Arc::clone(&self.registry.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Hot-reload templates; compiles already running keep their snapshot
+    pub fn replace_registry(&self, registry: TemplateRegistry) {
+        *self.registry.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(registry);
+    }
+
     /// List all available templates
-    pub fn list_templates(&self) -> Vec<&Template> {
-        self.registry.list()
+    pub fn list_templates(&self) -> Vec<Template> {
+        self.registry().list().into_iter().cloned().collect()
     }
 
     /// Get a specific template
-    pub fn get_template(&self, id: &str) -> Option<&Template> {
-        self.registry.get(id)
+    pub fn get_template(&self, id: &str) -> Option<Template> {
+        self.registry().get(id).cloned()
     }
 
     /// Validate an asset against a template
@@ -113,145 +943,1441 @@ impl CompilationPipeline {
         template_id: &str,
         input: &AssetInput,
     ) -> Result<ValidationResult, PipelineError> {
+        let span = tracing::info_span!("pipeline.validate", template_id, valid = tracing::field::Empty).entered();
+        let registry = self.registry();
+        let template = self.validation_template(&registry, template_id)?;
+        let result = self.validator.validate(input, template);
+        span.record("valid", result.valid);
+        Ok(result)
+    }
+
+    /// Validate a full compile request (input rules plus request-level rules)
+    fn validate_request(
+        &self,
+        registry: &TemplateRegistry,
+        ctx: &RequestContext<'_>,
+    ) -> Result<ValidationResult, PipelineError> {
+        let template_id = ctx.request.template_id.as_str();
+        let span = tracing::info_span!("pipeline.validate", template_id, valid = tracing::field::Empty).entered();
+        let template = self.validation_template(registry, template_id)?;
+        let result = self.validator.validate_request(ctx, template);
+        span.record("valid", result.valid);
+        Ok(result)
+    }
+
+    fn validation_template<'r>(
+        &self,
+        registry: &'r TemplateRegistry,
+        template_id: &str,
+    ) -> Result<&'r Template, PipelineError> {
         #[cfg(feature = "test-hooks")]
         VALIDATION_CALL_COUNT.fetch_add(1, Ordering::SeqCst);
 
-        let template = self.registry.get(template_id)
+        let template = registry.get(template_id)
             .ok_or_else(|| PipelineError::TemplateNotFound(template_id.to_string()))?;
 
         // Check engine version compatibility
         self.check_engine_version(template)?;
 
-        Ok(self.validator.validate(input, template))
+        Ok(template)
     }
 
     /// Compile an asset
     ///
-    /// CRITICAL: This ALWAYS calls validate_asset internally. No bypass possible.
+    /// CRITICAL: This ALWAYS validates internally (the same rules as
+    /// validate_asset, plus request-level rules). No bypass possible.
     pub fn compile_asset(&self, request: &CompileRequest) -> Result<CompiledAsset, PipelineError> {
-        let template = self.registry.get(&request.template_id)
+        self.compile_audited(request, None, None)
+    }
+
+    /// The envelope for a failed compile of `request`: its template id and,
+    /// when validation blocked it, the violations `validate_asset` reports
+    /// (request-level rules, the source's own among them, are only in the
+    /// message)
+    pub fn error_envelope(&self, request: &CompileRequest, error: &PipelineError) -> ErrorEnvelope {
+        let envelope = ErrorEnvelope::from(error).with_template_id(&request.template_id);
+        match error {
+            PipelineError::ValidationFailed(_) => match self.validate_asset(&request.template_id, &request.asset_input) {
+                Ok(validation) => envelope.with_validation(validation),
+                Err(_) => envelope,
+            },
+            _ => envelope,
+        }
+    }
+
+    /// `compile_asset` under a fixed id and timestamp, as when reproducing
+    /// an earlier manifest
+    pub fn compile_asset_stamped(&self, request: &CompileRequest, stamp: &ManifestStamp) -> Result<CompiledAsset, PipelineError> {
+        self.compile_audited(request, None, Some(stamp))
+    }
+
+    /// Compile with an optional pre-decoded source (shared across a set)
+    pub(crate) fn compile_audited(
+        &self,
+        request: &CompileRequest,
+        decoded_source: Option<&[u8]>,
+        stamp: Option<&ManifestStamp>,
+    ) -> Result<CompiledAsset, PipelineError> {
+        // Identifiers and outcomes only: never source bytes or prompts
+        let span = tracing::info_span!(
+            "pipeline.compile",
+            template_id = %request.template_id,
+            outcome = tracing::field::Empty,
+        ).entered();
+        let mut event = AuditEvent {
+            timestamp: self.now().to_rfc3339(),
+            template_id: request.template_id.clone(),
+            template_version: None,
+            engine_version: ENGINE_VERSION.to_string(),
+            job_hash: None,
+            outcome: AuditOutcome::Error,
+            violations: vec![],
+            manifest_hash: None,
+            error: None,
+        };
+        let started = Instant::now();
+        let result = self.compile(request, decoded_source, stamp, &mut event);
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        span.record("outcome", result.as_ref().map_or_else(PipelineError::kind, |_| "compiled"));
+        match &result {
+            Ok(asset) => tracing::info!(
+                template_id = %asset.template_id,
+                job_hash = %asset.job_hash,
+                manifest_hash = %asset.manifest_hash,
+                exports = asset.exports.len(),
+                elapsed_ms,
+                "compiled",
+            ),
+            Err(e) => tracing::info!(template_id = %request.template_id, kind = e.kind(), error = %e, elapsed_ms, "compile failed"),
+        }
+
+        if self.audit.is_none() && self.notifier.is_none() {
+            return result;
+        }
+        match &result {
+            Ok(asset) => {
+                event.outcome = AuditOutcome::Success;
+                event.violations = asset.validation.violations.iter().map(|v| v.rule.clone()).collect();
+                event.manifest_hash = Some(asset.manifest_hash.clone());
+            }
+            Err(e) => {
+                if matches!(e, PipelineError::ValidationFailed(_)) {
+                    event.outcome = AuditOutcome::ValidationFailed;
+                }
+                event.error = Some(e.to_string());
+            }
+        }
+        let logged = self.audit.as_ref().map_or(Ok(()), |sink| sink.append(&event));
+        // A compile that already failed reports its own error, not the log's
+        let result = match (result, logged) {
+            (Ok(_), Err(e)) => Err(PipelineError::AuditFailed(e.to_string())),
+            (result, _) => result,
+        };
+        if let Some(notifier) = &self.notifier {
+            if let Err(e @ PipelineError::AuditFailed(_)) = &result {
+                event.outcome = AuditOutcome::Error;
+                event.error = Some(e.to_string());
+            }
+            notifier.notify(&event);
+        }
+        result
+    }
+
+    /// The print settings a compile of `template_id` with the user spec
+    /// `user` would use, resolved by the same code as `compile_asset`.
+    /// Layout errors (marks or impositions that no longer fit) are errors
+    /// here as they would be there.
+    pub fn print_plan(&self, template_id: &str, user: Option<&PrintSpec>) -> Result<PrintPlan, PipelineError> {
+        let registry = self.registry();
+        let template = self.validation_template(&registry, template_id)?;
+        let request = CompileRequest {
+            template_id: template_id.to_string(),
+            asset_input: AssetInput {
+                width: template.canonical_size[0],
+                height: template.canonical_size[1],
+                color_count: None,
+                format: None,
+            },
+            source_data: None,
+            source_path: None,
+            seed: None,
+            prompt: None,
+            params: BTreeMap::new(),
+            print_spec: user.cloned(),
+        };
+        let validation = self.validator.validate_print_override(
+            &RequestContext { request: &request, source: None, font: None },
+            template,
+        );
+        let mut plan = PrintPlan {
+            template_id: template.id.clone(),
+            template_version: template.template_version.clone(),
+            validation,
+            print: None,
+            exports: vec![],
+        };
+        if !plan.validation.valid {
+            return Ok(plan);
+        }
+
+        let ResolvedPrints { block, exports } = resolve_prints(template, user)?;
+        let export_prints: Vec<_> = exports.iter().map(|resolved| resolved.as_ref().map(|r| r.spec.clone())).collect();
+        let (layouts, sheets) = export_layouts(template, &export_prints)?;
+        plan.print = Some(PlannedPrint::of(block));
+        for (((spec, resolved), layout), sheet) in template.exports.iter().zip(exports).zip(layouts).zip(sheets) {
+            let Some(resolved) = resolved else { continue };
+            let size = match (&layout, &sheet) {
+                (_, Some(sheet)) => sheet.size(),
+                (Some(layout), None) => layout.size(),
+                (None, None) => spec.size,
+            };
+            plan.exports.push(PlannedExport {
+                id: spec.id.clone(),
+                format: format!("{:?}", spec.format).to_lowercase(),
+                print: PlannedPrint::of(resolved),
+                trim: spec.size,
+                size,
+            });
+        }
+        Ok(plan)
+    }
+
+    /// What a compile of `request` would write, found by the same code as
+    /// `compile_asset` up to rendering: validation, the job hash, and each
+    /// export's filename, pixel size and print settings. Nothing is
+    /// rendered. Errors a compile raises before rendering are errors here.
+    pub fn compile_plan(&self, request: &CompileRequest) -> Result<CompilePlan, PipelineError> {
+        let registry = self.registry();
+        let template = registry.get(&request.template_id)
             .ok_or_else(|| PipelineError::TemplateNotFound(request.template_id.clone()))?;
+        self.check_sandbox(&registry, template, request)?;
+        let Identified { source, source_hash, job_hash, .. } = self.identify(template, request, None)?;
+        let font = load_font(&registry, template, self.hash_algorithm);
+        let validation = self.validate_request(&registry, &RequestContext {
+            request,
+            source: source.as_deref(),
+            font: font.as_ref(),
+        })?;
+        let mut plan = CompilePlan {
+            template_id: template.id.clone(),
+            template_version: template.template_version.clone(),
+            job_hash,
+            source_hash,
+            validation,
+            print: None,
+            exports: vec![],
+        };
+        if !plan.validation.valid {
+            return Ok(plan);
+        }
+
+        let ResolvedPrints { block, exports: resolved_exports } = resolve_prints(template, request.print_spec.as_ref())?;
+        let print = block.spec.clone();
+        let export_prints: Vec<_> = resolved_exports.into_iter().map(|resolved| resolved.map(|r| r.spec)).collect();
+        let (export_layouts, export_sheets) = export_layouts(template, &export_prints)?;
+        let export_colors = export_color_spaces(template, &print, &export_prints)?;
+        let export_profiles = self.export_profiles(template, &print, &export_prints, &export_colors)?;
+        export_spot_colors(template, &export_colors)?;
+        let export_pdfs = export_pdf_conformance(template, &export_colors, &export_profiles)?;
+        let source_size = raster_source_size(&request.asset_input, source.as_deref());
+        fill_text_slots(template, request, source)?;
+
+        plan.print = Some(PlannedPrint::of(block));
+        let exports = template.exports.iter()
+            .zip(export_prints)
+            .zip(export_layouts)
+            .zip(export_sheets)
+            .zip(export_colors)
+            .zip(export_pdfs);
+        for (((((spec, print), layout), sheet), color_space), pdf) in exports {
+            // As `finish_exports` sizes the manifest entry
+            let mut size = spec.size;
+            let trim = layout.as_ref().map(|layout| {
+                size = layout.size();
+                layout.trim_box()
+            });
+            if let (Some(sheet), Some(_)) = (&sheet, &spec.imposition) {
+                size = sheet.size();
+            }
+            plan.exports.push(PlannedFile {
+                id: spec.id.clone(),
+                filename: format!("{}.{}", spec.id, format_extension(&spec.format)),
+                format: format!("{:?}", spec.format).to_lowercase(),
+                size,
+                trim,
+                required: spec.required && !spec.is_proof(),
+                deliverable: !spec.is_proof(),
+                scaling: spec.scaling(template.scaling_policy, source_size),
+                color_space,
+                print,
+                pdf_standard: pdf.map(|pdf| pdf.standard),
+            });
+        }
+        Ok(plan)
+    }
+
+    /// Job hash a compile of `request` would record, without compiling
+    pub fn job_hash(&self, request: &CompileRequest) -> Result<String, PipelineError> {
+        let registry = self.registry();
+        let template = registry.get(&request.template_id)
+            .ok_or_else(|| PipelineError::TemplateNotFound(request.template_id.clone()))?;
+        self.check_sandbox(&registry, template, request)?;
+        Ok(self.identify(template, request, None)?.job_hash)
+    }
+
+    /// Load (and optionally normalize) the source and derive the job hash
+    fn identify(
+        &self,
+        template: &Template,
+        request: &CompileRequest,
+        decoded_source: Option<&[u8]>,
+    ) -> Result<Identified, PipelineError> {
+        let source = match decoded_source {
+            Some(bytes) => Some(bytes.to_vec()),
+            None => self.load_source(request)?,
+        };
+        let source_hash = source.as_deref().map(|s| self.hash_algorithm.digest(s));
+        let (source, normalized_source_hash) = match source {
+            Some(bytes) if self.normalize_source && svg::looks_like_svg(&bytes) => {
+                let normalized = normalize_svg(bytes)?;
+                let hash = self.hash_algorithm.digest(&normalized);
+                (Some(normalized), Some(hash))
+            }
+            source => (source, None),
+        };
+
+        // Path and normalized sources are identified by content, not by request bytes
+        let content_hash = normalized_source_hash.as_deref()
+            .or(source_hash.as_deref().filter(|_| request.source_path.is_some()));
+        let input = JobHashInput::new(request, template, content_hash);
+        let job_hash = match &self.job_hash_key {
+            Some(key) => HashScheme::CURRENT.keyed_job_hash(
+                key, &request.template_id, &template.template_version, &input, ENGINE_VERSION,
+            )?,
+            None => compute_job_hash_with(
+                self.hash_algorithm, &request.template_id, &template.template_version, &input, ENGINE_VERSION,
+            )?,
+        };
+        Ok(Identified { source, source_hash, normalized_source_hash, job_hash })
+    }
+
+    /// Compile, noting audit-relevant facts in `event` as they become known
+    fn compile(
+        &self,
+        request: &CompileRequest,
+        decoded_source: Option<&[u8]>,
+        stamp: Option<&ManifestStamp>,
+        event: &mut AuditEvent,
+    ) -> Result<CompiledAsset, PipelineError> {
+        let registry = self.registry();
+        let template = registry.get(&request.template_id)
+            .ok_or_else(|| PipelineError::TemplateNotFound(request.template_id.clone()))?;
+        event.template_version = Some(template.template_version.clone());
+        // Loaded templates were checked already; registered ones may not have been
+        template.validate_export_ids().map_err(|e| PipelineError::ValidationFailed(e.to_string()))?;
+        self.check_sandbox(&registry, template, request)?;
+
+        let Identified { source, source_hash, normalized_source_hash, job_hash } =
+            self.identify(template, request, decoded_source)?;
+        event.job_hash = Some(job_hash.clone());
+
+        let font = load_font(&registry, template, self.hash_algorithm);
 
         // MANDATORY: Validation is always called. This is non-negotiable.
-        let validation = self.validate_asset(&request.template_id, &request.asset_input)?;
+        let validation = self.validate_request(&registry, &RequestContext {
+            request,
+            source: source.as_deref(),
+            font: font.as_ref(),
+        })?;
 
         // If validation failed with errors, reject compilation
         if !validation.valid {
+            event.violations = validation.violations.iter().map(|v| v.rule.clone()).collect();
             let messages: Vec<_> = validation.violations.iter()
                 .map(|v| format!("{}: {}", v.rule, v.message))
                 .collect();
             return Err(PipelineError::ValidationFailed(messages.join("; ")));
         }
+        self.check_cancelled(None)?;
+
+        let ResolvedPrints { block, exports: resolved_exports } = resolve_prints(template, request.print_spec.as_ref())?;
+        let print_overrides = block.overrides;
+        let print = block.spec;
+        let export_prints: Vec<_> = resolved_exports.into_iter().map(|resolved| resolved.map(|r| r.spec)).collect();
+        let (export_layouts, export_sheets) = export_layouts(template, &export_prints)?;
+        let export_colors = export_color_spaces(template, &print, &export_prints)?;
+        let export_profiles = self.export_profiles(template, &print, &export_prints, &export_colors)?;
+        let export_spots = export_spot_colors(template, &export_colors)?;
+        let export_pdfs = export_pdf_conformance(template, &export_colors, &export_profiles)?;
+        let export_guides = export_guides(template, &export_prints, &export_layouts);
+
+        let font = font.and_then(Result::ok);
+        let prepared = Prepared {
+            template,
+            request,
+            source_size: raster_source_size(&request.asset_input, source.as_deref()),
+            source: fill_text_slots(template, request, source)?,
+            font: font.as_ref().map(|f| &f.font),
+            metadata: png_metadata(template, &job_hash),
+            print,
+            export_prints,
+            export_layouts,
+            export_sheets,
+            export_colors,
+            export_profiles,
+            export_spots,
+            export_pdfs,
+            export_guides,
+        };
+
+        // Generate exports; only required export failures abort here
+        let RenderedExports { exports, errors: mut export_errors, metrics } = self.generate_exports(&prepared)?;
+        let print = prepared.print;
+        let mut validation = validation;
+        for error in &export_errors {
+            validation.violations.push(export_warning("export_failed", "render", error));
+        }
 
-        // Generate exports (simulated for now)
-        let exports = self.generate_exports(template, request)?;
+        // Optional export failures are warnings too
+        if self.fail_on_warnings && validation.warning_count() > 0 {
+            return Err(warnings_failure(&validation, event));
+        }
 
         // Build manifest
-        let asset_id = Uuid::new_v4().to_string();
-        let created_at = Utc::now();
+        let (asset_id, created_at) = match (stamp, self.clock) {
+            (Some(stamp), _) => (stamp.id.clone(), stamp.created_at),
+            (None, Some(now)) => (clocked_id(&job_hash, now), now),
+            (None, None) => (Uuid::new_v4().to_string(), Utc::now()),
+        };
+        let template_hash = template.content_hash_with(self.hash_algorithm)?;
+        let config_hash = match stamp {
+            Some(stamp) => stamp.config_hash.clone(),
+            None => self.config().hash(self.hash_algorithm)?,
+        };
 
-        let job_hash = compute_job_hash(
-            &request.template_id,
-            &template.template_version,
-            request,
-            ENGINE_VERSION,
-        )?;
+        // Stored before hashing, so the manifest hash covers where they went
+        let (exports, stored_objects) = match &self.sink {
+            Some(sink) => {
+                let StoredExports { exports, objects, errors } = sink::store_exports(
+                    sink.as_ref(), template, &asset_id, exports, |export| self.check_cancelled(Some(export)),
+                )?;
+                for error in &errors {
+                    validation.violations.push(export_warning("export_store_failed", "store", error));
+                }
+                if self.fail_on_warnings && !errors.is_empty() {
+                    sink::discard(sink.as_ref(), &objects);
+                    return Err(warnings_failure(&validation, event));
+                }
+                export_errors.extend(errors);
+                (exports, objects)
+            }
+            None => (exports, vec![]),
+        };
 
         let mut asset = CompiledAsset {
             id: asset_id,
             template_id: request.template_id.clone(),
             template_version: template.template_version.clone(),
+            template_hash,
             engine_version: ENGINE_VERSION.to_string(),
             created_at,
+            hash_algorithm: self.hash_algorithm,
+            hash_scheme: HashScheme::CURRENT,
             manifest_hash: String::new(),  // Computed after
             job_hash,
+            job_hash_keyed: self.job_hash_key.is_some(),
+            job_hash_key_id: self.job_hash_key.as_ref().map(|key| key.key_id().to_string()),
+            warning_count: validation.warning_count(),
+            has_warnings: validation.warning_count() > 0,
             validation,
+            encoding: self.encoding.clone(),
+            config_hash,
+            font_hash: font.map(|f| f.digest),
+            signer: self.signer_info(),
+            source_hash,
+            normalized_source_hash,
+            request: Some(RequestRecord::of(request)),
+            print,
+            print_overrides,
+            print_profile: template.print.as_ref().and_then(|p| p.profile.clone()),
+            exports_root: exports_root(&exports)?,
             exports,
+            export_errors,
+            stored_objects,
+            metrics: CompileMetrics::default(),
+            canonical: CanonicalDocument::default(),
         };
 
         // Compute manifest hash (includes everything)
-        asset.manifest_hash = compute_manifest_hash(&asset)?;
+        let hashed = tracing::info_span!("pipeline.hash_manifest", algorithm = self.hash_algorithm.prefix())
+            .in_scope(|| CanonicalDocument::hash(&asset, HashScheme::CURRENT, self.hash_algorithm));
+        let (manifest_hash, canonical) = match (hashed, &self.sink) {
+            (Ok(hashed), _) => hashed,
+            (Err(e), Some(sink)) => {
+                sink::discard(sink.as_ref(), &asset.stored_objects);
+                return Err(e.into());
+            }
+            (Err(e), None) => return Err(e.into()),
+        };
+        asset.manifest_hash = manifest_hash;
+        asset.canonical = canonical;
+        asset.metrics = metrics;
+
+        Ok(asset)
+    }
 
+    /// Compile and write exports plus sidecar manifest files into `dir`
+    pub fn compile_to_dir(&self, request: &CompileRequest, dir: &Path) -> Result<CompiledAsset, PipelineError> {
+        let asset = self.compile_asset(request)?;
+        // Signed strictly after the manifest hash is final
+        let signature = self.sign_manifest(&asset);
+        output::write_dir(&asset, dir, signature.as_ref().map(|s| &s[..]), self.emit_checksums)?;
         Ok(asset)
     }
 
+    #[cfg(feature = "signing")]
+    fn signer_info(&self) -> Option<SignerInfo> {
+        self.signing.as_ref().map(|config| SignerInfo {
+            key_id: config.key_id.clone(),
+            algorithm: signing::ALGORITHM.to_string(),
+        })
+    }
+
+    #[cfg(not(feature = "signing"))]
+    fn signer_info(&self) -> Option<SignerInfo> {
+        None
+    }
+
+    #[cfg(feature = "signing")]
+    fn sign_manifest(&self, asset: &CompiledAsset) -> Option<[u8; 64]> {
+        let config = self.signing.as_ref()?;
+        Some(config.key.sign(asset.manifest_hash.as_bytes()))
+    }
+
+    #[cfg(not(feature = "signing"))]
+    fn sign_manifest(&self, _asset: &CompiledAsset) -> Option<[u8; 64]> {
+        None
+    }
+
+    /// Profile to embed in each template export, in template order: the one
+    /// its print spec names, or for Png the template-level one when it
+    /// describes the PNG's pixels. Unregistered names and profiles that
+    /// cannot describe a print export's pixels are errors.
+    fn export_profiles(
+        &self,
+        template: &Template,
+        print: &PrintSpec,
+        export_prints: &[Option<PrintSpec>],
+        export_colors: &[ColorSpace],
+    ) -> Result<Vec<Option<&IccProfile>>, PipelineError> {
+        let lookup = |name: &Option<String>| -> Result<Option<&IccProfile>, PipelineError> {
+            name.as_deref()
+                .map(|name| self.icc_profiles.get(name).ok_or_else(|| PipelineError::IccProfileNotFound(name.to_string())))
+                .transpose()
+        };
+        let template_profile = lookup(&print.icc_profile)?;
+        template.exports.iter().zip(export_prints).zip(export_colors)
+            .map(|((spec, export_print), color)| match (export_print, &spec.format) {
+                (Some(export_print), _) => {
+                    let profile = lookup(&export_print.icc_profile)?;
+                    match profile {
+                        Some(profile) if !profile.color_space().describes(&export_print.color_space) => {
+                            Err(PipelineError::ValidationFailed(format!(
+                                "print: exports[{}]: ICC profile '{}' is {:?}, export is {:?}",
+                                spec.id, profile.name(), profile.color_space(), export_print.color_space,
+                            )))
+                        }
+                        _ => Ok(profile),
+                    }
+                }
+                (None, ExportFormat::Png) => Ok(template_profile.filter(|p| p.color_space().describes(color))),
+                (None, _) => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Refuse any access a sandboxed compile could make beyond its inputs
+    fn check_sandbox(
+        &self,
+        registry: &TemplateRegistry,
+        template: &Template,
+        request: &CompileRequest,
+    ) -> Result<(), PipelineError> {
+        if self.sandbox == SandboxMode::Off {
+            return Ok(());
+        }
+        if let Some(path) = &request.source_path {
+            return Err(PipelineError::SandboxViolation(format!("source_path read: {}", path.display())));
+        }
+        if !self.renderer.sandbox_safe() {
+            return Err(PipelineError::SandboxViolation(format!(
+                "renderer {} is not sandbox-safe", self.renderer.name()
+            )));
+        }
+        if let Some(font) = template.font.as_ref().filter(|f| registry.registered_asset(&f.path).is_none()) {
+            return Err(PipelineError::SandboxViolation(format!(
+                "template asset read from disk: {}", font.path
+            )));
+        }
+        Ok(())
+    }
+
+    /// A checkpoint: `Cancelled` once the token has fired, blaming
+    /// `export` when one was just rendering
+    pub(crate) fn check_cancelled(&self, export: Option<&str>) -> Result<(), PipelineError> {
+        match self.cancel.as_ref().and_then(CancelToken::reason) {
+            Some(reason) => Err(PipelineError::Cancelled(reason, export.map(str::to_string))),
+            None => Ok(()),
+        }
+    }
+
     fn check_engine_version(&self, template: &Template) -> Result<(), PipelineError> {
-        let engine_ver = semver::Version::parse(ENGINE_VERSION)
-            .map_err(|_| PipelineError::CompilationError("Invalid engine version".into()))?;
-        let min_ver = semver::Version::parse(&template.engine_min_version)
-            .map_err(|_| PipelineError::CompilationError("Invalid template min version".into()))?;
-
-        if engine_ver < min_ver {
-            return Err(PipelineError::EngineVersionMismatch(
-                template.template_version.clone(),
-                template.engine_min_version.clone(),
-                ENGINE_VERSION.to_string(),
+        check_engine_range(ENGINE_VERSION, template)
+    }
+
+    /// Decode inline source data or read `source_path` under the source root
+    fn load_source(&self, request: &CompileRequest) -> Result<Option<Vec<u8>>, PipelineError> {
+        let Some(relative) = &request.source_path else {
+            return decode_source(request);
+        };
+        if request.source_data.is_some() {
+            return Err(PipelineError::InvalidSource(
+                "source_data and source_path are mutually exclusive".into(),
             ));
         }
-
-        Ok(())
+        let root = self.source_root.as_ref().ok_or_else(|| {
+            PipelineError::SourceNotAllowed("pipeline has no source root configured".into())
+        })?;
+        let path = resolve_source_path(root, relative)?;
+        std::fs::read(&path)
+            .map(Some)
+            .map_err(|e| PipelineError::InvalidSource(format!("{}: {}", relative.display(), e)))
     }
 
+
     fn generate_exports(
         &self,
-        template: &Template,
-        request: &CompileRequest,
-    ) -> Result<Vec<ExportedFile>, PipelineError> {
+        prepared: &Prepared<'_>,
+    ) -> Result<RenderedExports, PipelineError> {
+        let Prepared { template, request, .. } = *prepared;
         let mut exports = vec![];
+        let mut errors = vec![];
+        let mut metrics = CompileMetrics::default();
+        let mut rendered_once: BTreeMap<RenderKey, Rendered> = BTreeMap::new();
+        // Rendered but not yet hashed, in template order
+        let mut pending: Vec<PendingExport<'_>> = vec![];
 
-        for spec in &template.exports {
-            // Generate placeholder data (in real impl, this would render the asset)
-            let data = self.render_export(spec, request)?;
-            let hash = crate::hashing::sha256_hex(&data);
+        let prints = prepared.export_prints.iter()
+            .zip(&prepared.export_layouts)
+            .zip(&prepared.export_sheets)
+            .zip(&prepared.export_colors)
+            .zip(&prepared.export_profiles)
+            .zip(&prepared.export_spots)
+            .zip(&prepared.export_pdfs)
+            .zip(&prepared.export_guides);
+        let total = template.exports.len();
+        for (n, (spec, (((((((print, layout), sheet), color), &icc), spots), &pdf), &guides))) in template.exports.iter().zip(prints).enumerate() {
+            let key = (
+                format_extension(&spec.format),
+                spec.size,
+                print.as_ref().map(print_key),
+                layout.clone(),
+                sheet.clone(),
+                spots.clone(),
+                spec.soft_proof,
+                pdf,
+                guides,
+            );
+            if let Some((source_id, data, spot_usage)) = rendered_once.get(&key).filter(|_| self.deduplicate_exports) {
+                tracing::info!(export = %spec.id, n = n + 1, of = total, from = %source_id, "reused identical render");
+                metrics.exports.push(ExportMetrics { export_id: spec.id.clone(), attempts: 0 });
+                metrics.deduplicated += 1;
+                pending.push(PendingExport {
+                    spec,
+                    data: Arc::clone(data),
+                    print,
+                    layout,
+                    sheet,
+                    color,
+                    icc,
+                    spots: spot_usage.clone(),
+                    pdf,
+                    deduplicated_from: Some(source_id.clone()),
+                });
+            } else {
+                let _span = tracing::info_span!(
+                    "pipeline.render_export",
+                    export_id = %spec.id,
+                    format = format_extension(&spec.format),
+                    size = %format_args!("{}x{}", spec.size[0], spec.size[1]),
+                ).entered();
+                let started = Instant::now();
+                let (rendered, attempts) = self.retry.run(|| background_for(template, spec, request).and_then(|background| {
+                    // Fresh per attempt, so pixel counts cover the bytes kept
+                    let cmyk = SpotCmyk::new(&*self.cmyk, spots);
+                    let job = RenderJob {
+                        template,
+                        spec,
+                        request,
+                        encoding: &self.encoding,
+                        metadata: &prepared.metadata,
+                        background: background.as_ref(),
+                        source: prepared.source.as_deref(),
+                        font: prepared.font,
+                        print: print.as_ref(),
+                        cmyk: (*color == ColorSpace::Cmyk).then_some(&cmyk as &dyn CmykConverter),
+                        proof: spec.soft_proof.then_some(&cmyk as &dyn CmykConverter),
+                        layout: layout.as_ref(),
+                        sheet: sheet.as_ref(),
+                        icc_profile: icc,
+                        grayscale: *color == ColorSpace::Grayscale,
+                        pdf,
+                        guides,
+                    };
+                    self.renderer.render(&job).map(|data| (data, cmyk.usage()))
+                }));
+                // Whatever the renderer wrote, a PDF/X export must be one
+                let rendered = rendered.and_then(|(data, usage)| match pdf {
+                    Some(pdf) => {
+                        let unmet = pdf::check_conformance(&data, pdf.standard);
+                        if unmet.is_empty() {
+                            Ok((data, usage))
+                        } else {
+                            Err(RenderError::new(format!("{} requirements not met: {}", pdf.standard.label(), unmet.join("; "))))
+                        }
+                    }
+                    None => Ok((data, usage)),
+                });
+                metrics.exports.push(ExportMetrics { export_id: spec.id.clone(), attempts });
+                tracing::info!(
+                    export = %spec.id,
+                    n = n + 1,
+                    of = total,
+                    attempts,
+                    ok = rendered.is_ok(),
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "rendered export",
+                );
+                self.check_cancelled(Some(&spec.id))?;
+                let (data, spot_usage) = match rendered {
+                    Ok((data, spot_usage)) => (Arc::new(data), spot_usage),
+                    Err(e) if spec.required && !spec.is_proof() => {
+                        return Err(PipelineError::ExportFailed(spec.id.clone(), e.message));
+                    }
+                    Err(e) => {
+                        errors.push(ExportError {
+                            export_id: spec.id.clone(),
+                            message: e.message,
+                            spec: spec.clone(),
+                        });
+                        continue;
+                    }
+                };
+                if self.deduplicate_exports {
+                    rendered_once.entry(key)
+                        .or_insert_with(|| (spec.id.clone(), Arc::clone(&data), spot_usage.clone()));
+                }
+                pending.push(PendingExport {
+                    spec,
+                    data,
+                    print,
+                    layout,
+                    sheet,
+                    color,
+                    icc,
+                    spots: spot_usage,
+                    pdf,
+                    deduplicated_from: None,
+                });
+            }
 
-            exports.push(ExportedFile {
-                id: spec.id.clone(),
-                filename: format!("{}.{}", spec.id, format_extension(&spec.format)),
-                format: format!("{:?}", spec.format).to_lowercase(),
-                size: spec.size,
-                data_base64: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data),
-                hash,
-            });
+            if pending.len() >= self.export_parallelism {
+                exports.extend(self.finish_exports(template, prepared.source_size, pending.drain(..)));
+            }
         }
+        exports.extend(self.finish_exports(template, prepared.source_size, pending.drain(..)));
 
-        Ok(exports)
+        Ok(RenderedExports { exports, errors, metrics })
     }
 
-    fn render_export(
+    /// Hash and encode a batch of rendered exports, one thread per export
+    /// when the batch has more than one; results keep the batch order
+    fn finish_exports<'a>(
         &self,
-        spec: &ExportSpec,
-        _request: &CompileRequest,
-    ) -> Result<Vec<u8>, PipelineError> {
-        // Placeholder: In real implementation, this would:
-        // 1. Take the SVG master
-        // 2. Render to the target format at target size
-        // For now, return a minimal valid placeholder
-        match spec.format {
-            crate::templates::ExportFormat::Svg => {
-                Ok(format!(
-                    r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}"></svg>"#,
-                    spec.size[0], spec.size[1]
-                ).into_bytes())
+        template: &Template,
+        source_size: Option<[u32; 2]>,
+        batch: impl ExactSizeIterator<Item = PendingExport<'a>>,
+    ) -> Vec<ExportedFile> {
+        let finish = |pending: PendingExport<'_>| {
+            let mut export = exported_file(template, pending.spec, source_size, &pending.data, self.hash_algorithm);
+            if let Some(layout) = pending.layout {
+                export.size = layout.size();
+                export.trim = Some(layout.trim_box());
+                export.marks = layout.marks();
+            }
+            if let (Some(sheet), Some(imposition)) = (pending.sheet, &pending.spec.imposition) {
+                export.size = sheet.size();
+                export.imposition = Some(sheet.record(imposition));
+            }
+            export.print = pending.print.clone();
+            export.color_conversion = match (pending.color, &pending.spec.format) {
+                (ColorSpace::Rgb, _) => None,
+                (ColorSpace::Cmyk, _) => Some(self.cmyk.conversion()),
+                (ColorSpace::Grayscale, ExportFormat::Svg) => {
+                    Some(ColorConversion { method: ConversionMethod::Rec709LumaFilter, profile: None })
+                }
+                (ColorSpace::Grayscale, _) => Some(ColorConversion { method: ConversionMethod::Rec709Luma, profile: None }),
+            };
+            export.icc_profile = pending.icc.map(IccProfile::embedded);
+            export.spot_colors = pending.spots;
+            export.pdf_standard = pending.pdf.map(|pdf| pdf.standard);
+            export.proof = pending.spec.is_proof();
+            export.deliverable = !pending.spec.is_proof();
+            export.deduplicated_from = pending.deduplicated_from;
+            export
+        };
+        if batch.len() <= 1 {
+            return batch.map(finish).collect();
+        }
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = batch.map(|pending| scope.spawn(move || finish(pending))).collect();
+            handles.into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        })
+    }
+}
+
+/// A rendered export waiting to be hashed and encoded
+struct PendingExport<'a> {
+    spec: &'a ExportSpec,
+    /// Shared with `rendered_once` when deduplicating
+    data: Arc<Vec<u8>>,
+    print: &'a Option<PrintSpec>,
+    layout: &'a Option<PrintLayout>,
+    sheet: &'a Option<SheetLayout>,
+    color: &'a ColorSpace,
+    icc: Option<&'a IccProfile>,
+    /// Spot colors the render applied
+    spots: Vec<SpotColorUsage>,
+    pdf: Option<PdfConformance>,
+    deduplicated_from: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn default_true() -> bool { true }
+
+/// A manifest id for a pipeline with a fixed clock: a version 4 UUID whose
+/// "random" bits are the SHA-256 of the job hash and time, so two jobs at
+/// the same time still get different ids
+fn clocked_id(job_hash: &str, now: DateTime<Utc>) -> String {
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::new().chain_update(job_hash).chain_update("\n").chain_update(now.to_rfc3339()).finalize();
+    let bytes: [u8; 16] = digest[..16].try_into().expect("SHA-256 is 32 bytes");
+    uuid::Builder::from_random_bytes(bytes).into_uuid().to_string()
+}
+
+/// The warning for an optional export that failed to `stage` (render, store)
+fn export_warning(rule: &str, stage: &str, error: &ExportError) -> ValidationViolation {
+    ValidationViolation {
+        rule: rule.to_string(),
+        severity: ViolationSeverity::Warning,
+        message: format!("Optional export {} failed to {}", error.export_id, stage),
+        expected: None,
+        actual: Some(error.message.clone()),
+        remediation: vec!["Inspect export_errors in the manifest".to_string()],
+    }
+}
+
+/// The error for a compile whose warnings `fail_on_warnings` refuses,
+/// recording their rules on the audit `event`
+fn warnings_failure(validation: &ValidationResult, event: &mut AuditEvent) -> PipelineError {
+    event.violations = validation.violations.iter().map(|v| v.rule.clone()).collect();
+    PipelineError::ValidationFailed(format!(
+        "{} warning(s) with fail_on_warnings set: {}",
+        validation.warning_count(),
+        validation.violations.iter().map(|v| v.rule.as_str()).collect::<Vec<_>>().join(", "),
+    ))
+}
+
+/// Source and identity of a compile, before validation
+struct Identified {
+    source: Option<Vec<u8>>,
+    source_hash: Option<String>,
+    normalized_source_hash: Option<String>,
+    job_hash: String,
+}
+
+/// Format extension, size, resolved print spec, print layout, sheet layout,
+/// spot colors, whether the export is a soft proof, its PDF/X
+/// conformance and its guides. Within one compile
+/// the background is a function of format and size, so exports with equal
+/// keys render identical bytes.
+type RenderKey = (
+    &'static str,
+    [u32; 2],
+    Option<PrintKey>,
+    Option<PrintLayout>,
+    Option<SheetLayout>,
+    SpotColors,
+    bool,
+    Option<PdfConformance>,
+    Option<Guides>,
+);
+
+/// First export rendered for a key: its id, bytes and spot color usage
+type Rendered = (String, Arc<Vec<u8>>, Vec<SpotColorUsage>);
+
+/// `PrintSpec` as an ordered key
+type PrintKey = (PrintAuthority, u32, ColorSpace, Length, Option<String>);
+
+fn print_key(spec: &PrintSpec) -> PrintKey {
+    (spec.authority, spec.dpi, spec.color_space.clone(), spec.bleed, spec.icc_profile.clone())
+}
+
+/// Print settings of a compile: the template-level resolution and each
+/// print export's, in template order (`None` for non-print exports)
+struct ResolvedPrints {
+    block: print::Resolved,
+    exports: Vec<Option<print::Resolved>>,
+}
+
+/// Resolve `user` against `template` as a compile does.
+///
+/// Validation already rejected bad overrides. Under warn/log policies
+/// refused fields keep the template's value, but bounds always hold and a
+/// template closed to overrides refuses the request outright.
+fn resolve_prints(template: &Template, user: Option<&PrintSpec>) -> Result<ResolvedPrints, PipelineError> {
+    if let Some(user) = user {
+        PrintSpec::check_bounds(user.dpi, user.bleed)
+            .map_err(|e| PipelineError::ValidationFailed(format!("print_override: {}", e)))?;
+        let closed = print::closed_blocks(template);
+        if !closed.is_empty() {
+            return Err(PipelineError::ValidationFailed(format!(
+                "print_override: Template does not allow user print overrides ({})",
+                closed.join(", "),
+            )));
+        }
+    }
+    Ok(ResolvedPrints {
+        block: print::resolve_block(user, template.print.as_ref()),
+        exports: template.exports.iter().map(|spec| print::resolve_export(user, template.print.as_ref(), spec)).collect(),
+    })
+}
+
+/// Per-export bleed layouts and imposed sheets, in template order
+type ExportLayouts = (Vec<Option<PrintLayout>>, Vec<Option<SheetLayout>>);
+
+/// Bleed and marks, or the imposed sheet, of each template export. Imposed
+/// exports take their bleed per cell, so they have no single-unit layout.
+fn export_layouts(template: &Template, export_prints: &[Option<PrintSpec>]) -> Result<ExportLayouts, PipelineError> {
+    let mut layouts = vec![];
+    let mut sheets = vec![];
+    for (spec, print) in template.exports.iter().zip(export_prints) {
+        let invalid = |e: String| PipelineError::ValidationFailed(format!("print: exports[{}]: {}", spec.id, e));
+        let block = spec.print.as_ref().or(template.print.as_ref());
+        match (print, &spec.imposition) {
+            (Some(print), Some(imposition)) => {
+                let sheet = SheetLayout::new(spec.size, print, block, imposition)
+                    .map_err(|e| invalid(format!("imposition: {}", e)))?;
+                layouts.push(None);
+                sheets.push(Some(sheet));
             }
-            crate::templates::ExportFormat::Png => {
-                // Minimal 1x1 transparent PNG
-                Ok(vec![
-                    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A,
-                    0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
-                    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
-                    0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
-                    0x89, 0x00, 0x00, 0x00, 0x0A, 0x49, 0x44, 0x41,
-                    0x54, 0x78, 0x9C, 0x63, 0x00, 0x01, 0x00, 0x00,
-                    0x05, 0x00, 0x01, 0x0D, 0x0A, 0x2D, 0xB4, 0x00,
-                    0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE,
-                    0x42, 0x60, 0x82
-                ])
+            (None, Some(_)) => return Err(invalid("imposition: export has no print spec".to_string())),
+            (Some(print), None) => {
+                layouts.push(PrintLayout::new(spec.size, print, block).map_err(invalid)?);
+                sheets.push(None);
             }
-            _ => {
-                Ok(b"placeholder".to_vec())
+            (None, None) => {
+                layouts.push(None);
+                sheets.push(None);
             }
         }
     }
+    Ok((layouts, sheets))
+}
+
+/// Guides of each guide proof export, drawn around its layout (or its
+/// whole file when it has neither bleed nor marks)
+fn export_guides(template: &Template, export_prints: &[Option<PrintSpec>], export_layouts: &[Option<PrintLayout>]) -> Vec<Option<Guides>> {
+    template.exports.iter().zip(export_prints).zip(export_layouts)
+        .map(|((spec, print), layout)| {
+            let print = print.as_ref().filter(|_| spec.guides)?;
+            let block = spec.print.as_ref().or(template.print.as_ref());
+            Some(Guides::new(spec.size, layout.as_ref(), print, block))
+        })
+        .collect()
+}
+
+/// Spot colors of each template export: the print block's for CMYK
+/// exports and soft proofs, none otherwise
+fn export_spot_colors(template: &Template, export_colors: &[ColorSpace]) -> Result<Vec<SpotColors>, PipelineError> {
+    template.exports.iter().zip(export_colors)
+        .map(|(spec, color)| {
+            let block = spec.print.as_ref().or(template.print.as_ref());
+            match (color == &ColorSpace::Cmyk || spec.soft_proof, block) {
+                (true, Some(block)) => SpotColors::new(&block.spot_colors).map_err(|e| {
+                    PipelineError::ValidationFailed(format!("print: exports[{}]: spotColors: {}", spec.id, e))
+                }),
+                _ => Ok(SpotColors::default()),
+            }
+        })
+        .collect()
+}
+
+/// PDF/X conformance of each Pdf export whose print block names a
+/// standard. Exports that cannot conform fail here, with every unmet
+/// requirement listed, rather than render a non-conformant file.
+fn export_pdf_conformance(
+    template: &Template,
+    export_colors: &[ColorSpace],
+    export_profiles: &[Option<&IccProfile>],
+) -> Result<Vec<Option<PdfConformance>>, PipelineError> {
+    template.exports.iter().zip(export_colors).zip(export_profiles)
+        .map(|((spec, color), &icc)| {
+            let block = spec.print.as_ref().or(template.print.as_ref());
+            let Some((standard, block)) = block.and_then(|b| b.pdf_standard.map(|s| (s, b))) else {
+                return Ok(None);
+            };
+            if spec.format != ExportFormat::Pdf {
+                return Ok(None);
+            }
+            let unmet = pdf::unmet_requirements(standard, color, icc);
+            if !unmet.is_empty() {
+                return Err(PipelineError::ValidationFailed(format!(
+                    "print: exports[{}]: {} requirements not met: {}",
+                    spec.id, standard.label(), unmet.join("; "),
+                )));
+            }
+            Ok(Some(PdfConformance { standard, transparency: block.transparency_policy }))
+        })
+        .collect()
+}
+
+/// Color space of each template export: its print spec's for print
+/// formats; Png and Svg follow the template-level spec into Grayscale
+/// (refused for Svg under `trueGrayscaleVectors`); everything else,
+/// soft proofs included, is RGB
+fn export_color_spaces(
+    template: &Template,
+    print: &PrintSpec,
+    export_prints: &[Option<PrintSpec>],
+) -> Result<Vec<ColorSpace>, PipelineError> {
+    let grayscale = print.color_space == ColorSpace::Grayscale;
+    let strict = template.print.as_ref().is_some_and(|p| p.true_grayscale_vectors);
+    template.exports.iter().zip(export_prints)
+        .map(|(spec, export_print)| match (export_print, &spec.format) {
+            (Some(export_print), _) => Ok(export_print.color_space.clone()),
+            (None, _) if spec.soft_proof => Ok(ColorSpace::Rgb),
+            (None, ExportFormat::Svg) if grayscale && strict => Err(PipelineError::ValidationFailed(format!(
+                "print: exports[{}]: SVG cannot be converted to true grayscale vectors (trueGrayscaleVectors)",
+                spec.id,
+            ))),
+            (None, ExportFormat::Png | ExportFormat::Svg) if grayscale => Ok(ColorSpace::Grayscale),
+            (None, _) => Ok(ColorSpace::Rgb),
+        })
+        .collect()
+}
+
+fn exported_file(
+    template: &Template,
+    spec: &ExportSpec,
+    source_size: Option<[u32; 2]>,
+    data: &[u8],
+    algorithm: HashAlgorithm,
+) -> ExportedFile {
+    // Hash and base64-encode in one pass over the rendered bytes
+    let mut writer = HashingWriter::with_algorithm(
+        base64::write::EncoderStringWriter::new(&base64::engine::general_purpose::STANDARD),
+        algorithm,
+    );
+    writer.write_all(data).expect("writing to a String cannot fail");
+    let (encoder, hash) = writer.finalize_digest();
+
+    ExportedFile {
+        id: spec.id.clone(),
+        filename: format!("{}.{}", spec.id, format_extension(&spec.format)),
+        format: format!("{:?}", spec.format).to_lowercase(),
+        size: spec.size,
+        trim: None,
+        marks: None,
+        data_base64: encoder.into_inner(),
+        hash,
+        scaling: spec.scaling(template.scaling_policy, source_size),
+        print: None,
+        color_conversion: None,
+        icc_profile: None,
+        spot_colors: vec![],
+        pdf_standard: None,
+        proof: false,
+        deliverable: true,
+        imposition: None,
+        deduplicated_from: None,
+    }
+}
+
+/// The part of a manifest its hash covers.
+///
+/// Built from a `CompiledAsset` or from manifest JSON, it never holds
+/// `manifest_hash` (the hash itself), `signature`, `metrics`, or
+/// `exports[].deduplicated_from` (how bytes were produced, not what they
+/// are). Hashing and verification both go through it, so neither depends
+/// on the order fields are filled in. Unknown fields in JSON are kept, so
+/// manifests from newer versions still verify.
+///
+/// Verifying a manifest read from JSON:
+///
+/// ```
+/// use forgeimages_core::{hashing::{digests_equal, parse_digest}, pipeline::ManifestHashView, HashScheme};
+///
+/// let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/manifest-fi-hash-1.json");
+/// let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+///
+/// let recorded = manifest["manifest_hash"].as_str().unwrap();
+/// let scheme = manifest["hash_scheme"].as_str().and_then(HashScheme::from_name).unwrap_or_default();
+/// let (algorithm, _) = parse_digest(recorded).unwrap();
+///
+/// let computed = ManifestHashView::from_json(&manifest).hash(scheme, algorithm).unwrap();
+/// assert!(digests_equal(&computed, recorded));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestHashView(serde_json::Map<String, serde_json::Value>);
+
+impl ManifestHashView {
+    /// Top-level fields outside the hash
+    const EXCLUDED: [&'static str; 3] = ["manifest_hash", "signature", "metrics"];
+    /// Per-export fields outside the hash
+    const EXCLUDED_EXPORT: [&'static str; 1] = ["deduplicated_from"];
+
+    pub fn of(asset: &CompiledAsset) -> Result<Self, HashingError> {
+        Ok(Self::from_json(&serde_json::to_value(asset)?))
+    }
+
+    /// View of manifest JSON; a non-object is viewed as empty
+    pub fn from_json(manifest: &serde_json::Value) -> Self {
+        let mut fields = manifest.as_object().cloned().unwrap_or_default();
+        for field in Self::EXCLUDED {
+            fields.remove(field);
+        }
+        if let Some(exports) = fields.get_mut("exports").and_then(serde_json::Value::as_array_mut) {
+            for export in exports.iter_mut().filter_map(serde_json::Value::as_object_mut) {
+                for field in Self::EXCLUDED_EXPORT {
+                    export.remove(field);
+                }
+            }
+        }
+        Self(fields)
+    }
+
+    /// Whether the view of `manifest` would differ from it only by
+    /// `manifest_hash` (so hashing it whole, hash blanked, is the same)
+    fn covers_all_but_hash(manifest: &serde_json::Value) -> bool {
+        let Some(fields) = manifest.as_object() else { return false };
+        let excluded_export_field = fields.get("exports")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(serde_json::Value::as_object)
+            .any(|export| Self::EXCLUDED_EXPORT.iter().any(|f| export.contains_key(*f)));
+        fields.get("manifest_hash").and_then(serde_json::Value::as_str) == Some("")
+            && !Self::EXCLUDED.iter().any(|f| *f != "manifest_hash" && fields.contains_key(*f))
+            && !excluded_export_field
+    }
+
+    pub fn hash(&self, scheme: HashScheme, algorithm: HashAlgorithm) -> Result<String, HashingError> {
+        match scheme {
+            // These schemes hashed the manifest with its hash field blanked
+            HashScheme::V0 | HashScheme::V1 => {
+                let mut fields = self.0.clone();
+                fields.insert("manifest_hash".to_string(), String::new().into());
+                scheme.manifest_hash(algorithm, &fields)
+            }
+        }
+    }
+}
+
+struct RenderedExports {
+    exports: Vec<ExportedFile>,
+    errors: Vec<ExportError>,
+    metrics: CompileMetrics,
+}
+
+/// Per-compile inputs resolved before rendering
+struct Prepared<'a> {
+    template: &'a Template,
+    request: &'a CompileRequest,
+    /// Decoded source, with text slots already filled
+    source: Option<Vec<u8>>,
+    /// Raster source dimensions; `None` for SVG sources
+    source_size: Option<[u32; 2]>,
+    font: Option<&'a Font>,
+    metadata: PngMetadata,
+    print: PrintSpec,
+    /// `print::resolve_export` for each template export, in template order
+    export_prints: Vec<Option<PrintSpec>>,
+    /// Bleed and marks for each template export, in template order
+    export_layouts: Vec<Option<PrintLayout>>,
+    /// Press sheet for each imposed template export, in template order
+    export_sheets: Vec<Option<SheetLayout>>,
+    /// Color space each template export is encoded in, in template order
+    export_colors: Vec<ColorSpace>,
+    /// ICC profile for each template export, in template order
+    export_profiles: Vec<Option<&'a IccProfile>>,
+    /// Spot colors for each template export, in template order
+    export_spots: Vec<SpotColors>,
+    /// PDF/X conformance for each template export, in template order
+    export_pdfs: Vec<Option<PdfConformance>>,
+    /// Guides for each guide proof export, in template order
+    export_guides: Vec<Option<Guides>>,
+}
+
+pub(crate) fn decode_source(request: &CompileRequest) -> Result<Option<Vec<u8>>, PipelineError> {
+    let Some(data) = &request.source_data else {
+        return Ok(None);
+    };
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, data)
+        .map(Some)
+        .map_err(|e| PipelineError::InvalidSource(format!("source_data is not valid base64: {}", e)))
+}
+
+/// Canonicalize and confine a source path to the root (symlinks included)
+fn resolve_source_path(root: &Path, relative: &Path) -> Result<PathBuf, PipelineError> {
+    let root = root.canonicalize()
+        .map_err(|e| PipelineError::SourceNotAllowed(format!("source root {}: {}", root.display(), e)))?;
+    let path = root.join(relative).canonicalize()
+        .map_err(|e| PipelineError::InvalidSource(format!("{}: {}", relative.display(), e)))?;
+    if !path.starts_with(&root) {
+        return Err(PipelineError::SourceNotAllowed(relative.display().to_string()));
+    }
+    Ok(path)
+}
+
+/// Version of the `JobHashInput` layout; bump on any change to its fields.
+/// 2: `print` is the resolved spec, no longer the request's override.
+pub const JOB_HASH_VERSION: u32 = 2;
+
+/// Exactly the request fields that identify a job.
+///
+/// Fields are listed here rather than taken from `CompileRequest`, so new
+/// request fields do not change existing job hashes. Absent optional fields
+/// are omitted, never written as `null`. The source enters either as the
+/// request's `source_data` or, for path and normalized sources, as its
+/// content digest (`source_hash`); never as a path.
+///
+/// Print intent enters as resolved (`print::resolve_block`): what the
+/// compile renders with, not what the request asked for. Overrides the
+/// template refuses, or that repeat its own values, leave the hash alone;
+/// authorities are not hashed, so the same spec from the template or the
+/// user hashes the same.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobHashInput<'a> {
+    pub job_hash_version: u32,
+    pub template_id: &'a str,
+    pub width: u32,
+    pub height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_data: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<&'a str>,
+    /// A decimal string above 2^53 - 1, so it stays canonical-safe
+    #[serde(skip_serializing_if = "Option::is_none", with = "crate::hashing::json_u64")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: &'a BTreeMap<String, serde_json::Value>,
+    /// Resolved template-level spec (the manifest's `print`)
+    pub print: JobPrintInput,
+    /// Resolved spec of each print export with its own block, by export id
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub export_prints: BTreeMap<&'a str, JobPrintInput>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobPrintInput {
+    pub dpi: u32,
+    pub color_space: ColorSpace,
+    /// Kept in inches so job hashes from before `Length` still match
+    pub bleed_inches: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<String>,
+}
+
+impl JobPrintInput {
+    pub fn of(spec: &PrintSpec) -> Self {
+        Self {
+            dpi: spec.dpi,
+            color_space: spec.color_space.clone(),
+            bleed_inches: spec.bleed.as_inches(),
+            icc_profile: spec.icc_profile.clone(),
+        }
+    }
+}
+
+impl<'a> JobHashInput<'a> {
+    /// With a `content_hash` the source is identified by that digest instead
+    /// of the request's `source_data`. `template` is the one the request
+    /// names; its print blocks resolve the request's print spec.
+    pub fn new(request: &'a CompileRequest, template: &'a Template, content_hash: Option<&'a str>) -> Self {
+        let user = request.print_spec.as_ref();
+        let export_prints = template.exports.iter()
+            .filter(|spec| spec.print.is_some())
+            .filter_map(|spec| {
+                let resolved = print::resolve_export(user, template.print.as_ref(), spec)?;
+                Some((spec.id.as_str(), JobPrintInput::of(&resolved.spec)))
+            })
+            .collect();
+        Self {
+            job_hash_version: JOB_HASH_VERSION,
+            template_id: &request.template_id,
+            width: request.asset_input.width,
+            height: request.asset_input.height,
+            color_count: request.asset_input.color_count,
+            format: request.asset_input.format.as_deref(),
+            source_data: request.source_data.as_deref().filter(|_| content_hash.is_none()),
+            source_hash: content_hash,
+            seed: request.seed,
+            prompt: request.prompt.as_deref(),
+            params: &request.params,
+            print: JobPrintInput::of(&print::resolve_block(user, template.print.as_ref()).spec),
+            export_prints,
+        }
+    }
+}
+
+fn normalize_svg(source: Vec<u8>) -> Result<Vec<u8>, PipelineError> {
+    let text = String::from_utf8(source)
+        .map_err(|_| PipelineError::InvalidSource("SVG source is not UTF-8".into()))?;
+    crate::svg_normalize::normalize(&text)
+        .map(String::into_bytes)
+        .map_err(|e| PipelineError::InvalidSource(e.to_string()))
+}
+
+/// Text slot values the request supplies, keyed by slot id
+pub(crate) fn slot_values(template: &Template, request: &CompileRequest) -> BTreeMap<String, String> {
+    template.text_slots.iter()
+        .filter_map(|slot| {
+            let value = request.params.get(&slot.id)?.as_str()?;
+            Some((slot.id.clone(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Substitute slot params into an SVG master (validation already checked them)
+fn fill_text_slots(
+    template: &Template,
+    request: &CompileRequest,
+    source: Option<Vec<u8>>,
+) -> Result<Option<Vec<u8>>, PipelineError> {
+    let values = slot_values(template, request);
+    let Some(source) = source else {
+        return Ok(None);
+    };
+    if values.is_empty() || !svg::looks_like_svg(&source) {
+        return Ok(Some(source));
+    }
+    let master = String::from_utf8(source)
+        .map_err(|_| PipelineError::InvalidSource("SVG master is not UTF-8".into()))?;
+    let filled = svg::fill_slots(&master, &values)
+        .map_err(|e| PipelineError::InvalidSource(e.to_string()))?;
+    Ok(Some(filled.into_bytes()))
+}
+
+/// Check `engine` against the template's `[engine_min_version, engine_max_version)`.
+///
+/// Pre-releases sort before their release (`2.0.0-rc.1 < 2.0.0`), so an
+/// engine `2.0.0-rc.1` is too old for a `2.0.0` minimum. For a release
+/// maximum the pre-release is ignored: `2.0.0-rc.1` already has 2.x
+/// semantics and is too new for a `2.0.0` maximum.
+fn check_engine_range(engine: &str, template: &Template) -> Result<(), PipelineError> {
+    let engine_ver = semver::Version::parse(engine)
+        .map_err(|_| PipelineError::CompilationError("Invalid engine version".into()))?;
+    let min_ver = semver::Version::parse(&template.engine_min_version)
+        .map_err(|_| PipelineError::CompilationError("Invalid template min version".into()))?;
+    let mismatch = |bound, required: String| PipelineError::EngineVersionMismatch(
+        bound,
+        template.template_version.clone(),
+        required,
+        engine.to_string(),
+    );
+
+    if engine_ver < min_ver {
+        return Err(mismatch(EngineBound::TooOld, format!(">= {}", min_ver)));
+    }
+
+    if let Some(max) = &template.engine_max_version {
+        let max_ver = semver::Version::parse(max)
+            .map_err(|_| PipelineError::CompilationError("Invalid template max version".into()))?;
+        let compared = if max_ver.pre.is_empty() {
+            semver::Version::new(engine_ver.major, engine_ver.minor, engine_ver.patch)
+        } else {
+            engine_ver.clone()
+        };
+        if compared >= max_ver {
+            return Err(mismatch(EngineBound::TooNew, format!("< {}", max_ver)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Load and verify the template font; errors become validation findings
+fn load_font(
+    registry: &TemplateRegistry,
+    template: &Template,
+    algorithm: HashAlgorithm,
+) -> Option<Result<LoadedFont, String>> {
+    let font_ref = template.font.as_ref()?;
+    let load = || {
+        let bytes = registry.read_template_asset(&template.id, &font_ref.path)
+            .map_err(|e| format!("{}: {}", font_ref.path, e))?;
+        // The template's digest is checked under its own algorithm
+        let (expected, _) = parse_digest(&font_ref.sha256).map_err(|e| format!("{}: {}", font_ref.path, e))?;
+        let actual = expected.digest(&bytes);
+        verify_digest(&font_ref.sha256, &actual).map_err(|e| format!("{}: {}", font_ref.path, e))?;
+        let digest = if expected == algorithm { actual } else { algorithm.digest(&bytes) };
+        let font = Font::parse(bytes).map_err(|e| format!("{}: {}", font_ref.path, e))?;
+        Ok(LoadedFont { digest, font })
+    };
+    Some(load())
+}
+
+/// Seeded background for one export; vector exports are left untouched
+fn background_for(
+    template: &Template,
+    spec: &ExportSpec,
+    request: &CompileRequest,
+) -> Result<Option<Raster>, RenderError> {
+    if template.background_generator.is_none() || spec.format == ExportFormat::Svg {
+        return Ok(None);
+    }
+    let seed = request.seed
+        .ok_or_else(|| RenderError::new("Background generator requires a seed"))?;
+    background::generate(&template.background_generator, seed, spec.size)
+        .map_err(RenderError::new)
+}
+
+/// Text chunks for raster exports - manifest values only, never the clock
+fn png_metadata(template: &Template, job_hash: &str) -> PngMetadata {
+    if !template.embed_metadata {
+        return vec![];
+    }
+    vec![
+        ("Software".to_string(), format!("ForgeImages {}", ENGINE_VERSION)),
+        ("forgeimages:template".to_string(), format!("{}@{}", template.id, template.template_version)),
+        ("forgeimages:job_hash".to_string(), job_hash.to_string()),
+    ]
 }
 
 fn format_extension(format: &crate::templates::ExportFormat) -> &'static str {
@@ -261,6 +2387,7 @@ fn format_extension(format: &crate::templates::ExportFormat) -> &'static str {
         crate::templates::ExportFormat::Ico => "ico",
         crate::templates::ExportFormat::Pdf => "pdf",
         crate::templates::ExportFormat::Jpg => "jpg",
+        crate::templates::ExportFormat::Tiff => "tiff",
     }
 }
 
@@ -269,3 +2396,151 @@ impl Default for CompilationPipeline {
         Self::new(TemplateRegistry::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::FailureMode;
+
+    fn warn_mode_pipeline() -> CompilationPipeline {
+        let mut template: Template = serde_json::from_str(include_str!("../templates/pwa-icon.json")).unwrap();
+        template.validation.failure_mode = FailureMode::Warn;
+        let mut registry = TemplateRegistry::new();
+        registry.register(template);
+        CompilationPipeline::new(registry)
+    }
+
+    fn request(color_count: Option<u32>) -> CompileRequest {
+        serde_json::from_value(serde_json::json!({
+            "template_id": "pwa-icon",
+            "asset_input": { "width": 1024, "height": 1024, "color_count": color_count },
+        })).unwrap()
+    }
+
+    fn ranged_template(min: &str, max: Option<&str>) -> Template {
+        let mut template: Template = serde_json::from_str(include_str!("../templates/pwa-icon.json")).unwrap();
+        template.engine_min_version = min.to_string();
+        template.engine_max_version = max.map(str::to_string);
+        template
+    }
+
+    fn bound(engine: &str, template: &Template) -> Option<EngineBound> {
+        match check_engine_range(engine, template) {
+            Ok(()) => None,
+            Err(PipelineError::EngineVersionMismatch(bound, ..)) => Some(bound),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_engine_range_is_half_open() {
+        let template = ranged_template("1.0.0", Some("2.0.0"));
+        assert_eq!(bound("0.9.9", &template), Some(EngineBound::TooOld));
+        assert_eq!(bound("1.0.0", &template), None);
+        assert_eq!(bound("1.99.0", &template), None);
+        assert_eq!(bound("2.0.0", &template), Some(EngineBound::TooNew));
+        assert_eq!(bound("3.1.0", &template), Some(EngineBound::TooNew));
+        assert_eq!(bound("9.0.0", &ranged_template("1.0.0", None)), None);
+    }
+
+    #[test]
+    fn test_engine_range_pre_releases() {
+        let template = ranged_template("1.0.0", Some("2.0.0"));
+        assert_eq!(bound("1.0.0-rc.1", &template), Some(EngineBound::TooOld));
+        assert_eq!(bound("1.5.0-beta", &template), None);
+        assert_eq!(bound("2.0.0-alpha", &template), Some(EngineBound::TooNew));
+
+        let template = ranged_template("1.0.0", Some("2.0.0-beta"));
+        assert_eq!(bound("2.0.0-alpha", &template), None);
+        assert_eq!(bound("2.0.0-beta", &template), Some(EngineBound::TooNew));
+        assert_eq!(bound("2.0.0", &template), Some(EngineBound::TooNew));
+    }
+
+    #[test]
+    fn test_engine_mismatch_remediation() {
+        let too_new = check_engine_range("2.0.0", &ranged_template("1.0.0", Some("2.0.0"))).unwrap_err();
+        assert!(too_new.to_string().contains("requires engine < 2.0.0"));
+        assert!(too_new.to_string().contains(EngineBound::TooNew.remediation()));
+        let too_old = check_engine_range("0.1.0", &ranged_template("1.0.0", None)).unwrap_err();
+        assert!(too_old.to_string().contains(EngineBound::TooOld.remediation()));
+    }
+
+    #[test]
+    fn test_warning_fields_match_validation() {
+        let pipeline = warn_mode_pipeline();
+        for (colors, expected) in [(None, 0), (Some(40), 1)] {
+            let asset = pipeline.compile_asset(&request(colors)).unwrap();
+            assert_eq!(asset.warning_count, expected);
+            assert_eq!(asset.warning_count, asset.validation.warning_count());
+            assert_eq!(asset.has_warnings, asset.validation.warning_count() > 0);
+        }
+    }
+
+    /// Every field recorded on a span or event, as (span name, field, value)
+    #[derive(Clone, Default)]
+    struct Recorded(Arc<std::sync::Mutex<Vec<(String, String, String)>>>);
+
+    struct FieldVisitor<'a>(&'a Recorded, &'a str);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+            self.0.0.lock().unwrap().push((self.1.to_string(), field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for Recorded
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, _: &tracing::span::Id, _: tracing_subscriber::layer::Context<'_, S>) {
+            attrs.record(&mut FieldVisitor(self, attrs.metadata().name()));
+        }
+
+        fn on_record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            values.record(&mut FieldVisitor(self, span.name()));
+        }
+
+        fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+            event.record(&mut FieldVisitor(self, "event"));
+        }
+    }
+
+    /// PII policy: what tracing exports (OTLP included) names templates,
+    /// exports and outcomes, never source bytes or prompts
+    #[test]
+    fn test_spans_never_record_sources_or_prompts() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        const PROMPT: &str = "a portrait of Jane Doe";
+        let source = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1024 1024"><title>private</title></svg>"#;
+        let source_data = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, source);
+        let mut request = request(None);
+        request.prompt = Some(PROMPT.to_string());
+        request.source_data = Some(source_data.clone());
+
+        let recorded = Recorded::default();
+        let pipeline = warn_mode_pipeline();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(recorded.clone()), || {
+            pipeline.validate_asset("pwa-icon", &request.asset_input).unwrap();
+            pipeline.compile_asset(&request).unwrap();
+        });
+
+        let recorded = recorded.0.lock().unwrap();
+        let spans: std::collections::BTreeSet<&str> = recorded.iter().map(|(span, ..)| span.as_str()).collect();
+        for span in ["pipeline.validate", "pipeline.compile", "pipeline.render_export", "pipeline.hash_manifest"] {
+            assert!(spans.contains(span), "{} missing from {:?}", span, spans);
+        }
+        let field = |span: &str, field: &str| recorded.iter()
+            .find(|(s, f, _)| s == span && f == field)
+            .map(|(.., value)| value.clone());
+        assert_eq!(field("pipeline.compile", "outcome").as_deref(), Some("\"compiled\""));
+        assert_eq!(field("pipeline.render_export", "size").as_deref(), Some("1024x1024"));
+        for (span, field, value) in recorded.iter() {
+            assert!(!field.contains("prompt") && !field.contains("source"), "{}.{}", span, field);
+            for secret in [PROMPT, &source_data, "private"] {
+                assert!(!value.contains(secret), "{}.{} = {}", span, field, value);
+            }
+        }
+    }
+}
@@ -1,15 +1,39 @@
 //! Print Authority System
 //!
 //! Defines the source of print specifications to prevent conditional sprawl.
+//!
+//! Every effective spec comes from `resolve`, field by field: User when the
+//! user supplied the field and the policy allows it, else Template, else
+//! System.
+//!
+//! A template print block may start from a named `PrintProfile` and set
+//! fields of its own on top; the profile is applied when the template
+//! loads, so a loaded block always carries the effective values.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::background::parse_hex_color;
+use crate::bleed::MARK_SLUG_INCHES;
+use crate::color::{SpotColorMapping, SpotColors};
+use crate::pdf::{PdfStandard, TransparencyPolicy};
+use self::presets::PhysicalSize;
+use crate::templates::{ExportSpec, Template};
+use crate::units::{self, Length};
 
 /// PrintAuthority determines where print specifications come from.
 /// This prevents if/else sprawl throughout the codebase.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum PrintAuthority {
     /// System defaults (fallback)
+    #[default]
     System,
     /// Template-defined specifications
     Template,
@@ -17,22 +41,31 @@ pub enum PrintAuthority {
     User,
 }
 
-impl Default for PrintAuthority {
-    fn default() -> Self {
-        Self::System
-    }
-}
-
 /// Print specifications for physical output
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PrintSpec {
+    /// Ignored on requests: a request-supplied spec is always User authority
+    #[serde(default)]
     pub authority: PrintAuthority,
     pub dpi: u32,
     pub color_space: ColorSpace,
-    pub bleed_inches: f64,
+    /// Written as `bleed_inches`, a number of inches; also reads a length
+    /// string under either name
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    #[serde(rename = "bleed_inches", alias = "bleed", with = "units::inches")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "units::legacy_schema"))]
+    pub bleed: Length,
+    /// Output profile registered in the pipeline's `IccProfileStore`.
+    /// On requests, `None` leaves the template's profile in place.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "UPPERCASE")]
 pub enum ColorSpace {
     Rgb,
@@ -46,35 +79,958 @@ impl Default for PrintSpec {
             authority: PrintAuthority::System,
             dpi: 300,
             color_space: ColorSpace::Rgb,
-            bleed_inches: 0.125,
+            bleed: Length::inches(0.125),
+            icc_profile: None,
         }
     }
 }
 
 impl PrintSpec {
     /// Create from template authority
-    pub fn from_template(dpi: u32, color_space: ColorSpace, bleed: f64) -> Self {
+    pub fn from_template(dpi: u32, color_space: ColorSpace, bleed: Length) -> Self {
         Self {
             authority: PrintAuthority::Template,
             dpi,
             color_space,
-            bleed_inches: bleed,
+            bleed,
+            icc_profile: None,
         }
     }
 
-    /// Create from user with validation
-    pub fn from_user(dpi: u32, color_space: ColorSpace, bleed: f64) -> Result<Self, &'static str> {
-        if dpi < 72 || dpi > 1200 {
+    /// Bounds every user and template print value must meet
+    pub fn check_bounds(dpi: u32, bleed: Length) -> Result<(), &'static str> {
+        if !(72..=1200).contains(&dpi) {
             return Err("DPI must be between 72 and 1200");
         }
-        if bleed < 0.0 || bleed > 1.0 {
+        if !(Length::ZERO..=Length::inches(1.0)).contains(&bleed) {
             return Err("Bleed must be between 0 and 1 inch");
         }
+        Ok(())
+    }
+
+    /// Create from user with validation
+    pub fn from_user(dpi: u32, color_space: ColorSpace, bleed: Length) -> Result<Self, &'static str> {
+        Self::check_bounds(dpi, bleed)?;
         Ok(Self {
             authority: PrintAuthority::User,
             dpi,
             color_space,
-            bleed_inches: bleed,
+            bleed,
+            icc_profile: None,
+        })
+    }
+}
+
+/// Template `print` block; also the per-export override on `ExportSpec`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatePrint {
+    pub dpi: u32,
+    pub color_space: ColorSpace,
+    /// Written as `bleedInches`, a number of inches; also reads a length
+    /// string under either name
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    #[serde(rename = "bleedInches", alias = "bleed", with = "units::inches")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "units::legacy_schema"))]
+    pub bleed: Length,
+    #[serde(default = "default_true")]
+    pub allow_user_print_overrides: bool,
+    /// Fields users may not override even when overrides are allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locked_fields: Vec<PrintField>,
+    /// Color spaces a user spec may ask for; any when empty. The block's
+    /// own `colorSpace` is not held to it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_user_color_spaces: Vec<ColorSpace>,
+    /// How artwork extends into the bleed (see `bleed`)
+    #[serde(default, skip_serializing_if = "BleedFill::is_mirror")]
+    pub bleed_fill: BleedFill,
+    /// Printer's marks in a slug outside the bleed (see `Marks`)
+    #[cfg_attr(feature = "ts", ts(type = "Marks | true"))]
+    #[serde(default, skip_serializing_if = "Marks::is_none", with = "legacy_marks")]
+    #[cfg_attr(feature = "schema", schemars(schema_with = "legacy_marks::schema"))]
+    pub marks: Marks,
+    /// Distance inside the trim that critical content must keep clear of;
+    /// checked by the `safe_margin` rule, drawn on guide proofs
+    #[cfg_attr(feature = "ts", ts(type = "string | null"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safe_margin: Option<Length>,
+    /// Output profile name (see `icc`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<String>,
+    /// When Grayscale, refuse SVG exports rather than bake a luma filter
+    /// over colored vectors (see `color`)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub true_grayscale_vectors: bool,
+    /// Exact RGB to CMYK mappings applied ahead of the converter (see `color`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spot_colors: Vec<SpotColorMapping>,
+    /// PDF/X standard the block's Pdf exports must conform to (see `pdf`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pdf_standard: Option<PdfStandard>,
+    /// Partly transparent pixels in PDF/X exports: flatten or fail
+    #[serde(default, skip_serializing_if = "TransparencyPolicy::is_flatten")]
+    pub transparency_policy: TransparencyPolicy,
+    /// `PrintProfile` the block was built from; its values are already
+    /// applied, so this only records where they came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+fn default_true() -> bool { true }
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BleedFill {
+    /// Reflect the artwork's edges outward
+    #[default]
+    Mirror,
+    /// Solid `#RRGGBB` / `#RRGGBBAA` fill
+    Color { color: String },
+}
+
+impl BleedFill {
+    pub fn is_mirror(&self) -> bool {
+        matches!(self, BleedFill::Mirror)
+    }
+}
+
+/// Printer's marks, drawn outside the trim box in a slug of
+/// `MARK_SLUG_INCHES` per side beyond the bleed. Each mark sits in the band
+/// `markOffset` to `markOffset + markLength` out from the trim:
+/// - crop: the trim lines continued across the band
+/// - registration: a cross centered in the band at each side's midpoint
+/// - color bars: `markLength` square patches along the top band
+///
+/// Templates written before the struct said `marks: true`, which reads as
+/// crop and registration marks at the default length and offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Marks {
+    #[serde(default)]
+    pub crop: bool,
+    #[serde(default)]
+    pub registration: bool,
+    #[serde(default)]
+    pub color_bars: bool,
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    #[serde(default = "Marks::default_length")]
+    pub mark_length: Length,
+    /// From the trim; marks closer than the bleed print over it
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    #[serde(default = "Marks::default_offset")]
+    pub mark_offset: Length,
+}
+
+impl Default for Marks {
+    fn default() -> Self {
+        Self {
+            crop: false,
+            registration: false,
+            color_bars: false,
+            mark_length: Self::default_length(),
+            mark_offset: Self::default_offset(),
+        }
+    }
+}
+
+impl Marks {
+    /// What `marks: true` means
+    pub fn legacy() -> Self {
+        Self { crop: true, registration: true, ..Self::default() }
+    }
+
+    fn default_length() -> Length {
+        Length::inches(0.125)
+    }
+
+    fn default_offset() -> Length {
+        Length::inches(0.125)
+    }
+
+    /// Whether any mark is drawn
+    pub fn any(&self) -> bool {
+        self.crop || self.registration || self.color_bars
+    }
+
+    pub fn is_none(&self) -> bool {
+        !self.any()
+    }
+
+    /// Lengths in range, and the marks inside the canvas a print export has
+    /// outside its trim: `bleed` plus the slug
+    pub fn check_fits(&self, bleed: Length) -> Result<(), String> {
+        if self.is_none() {
+            return Ok(());
+        }
+        if self.mark_length <= Length::ZERO {
+            return Err("marks.markLength must be positive".to_string());
+        }
+        if self.mark_offset < Length::ZERO {
+            return Err("marks.markOffset must be zero or positive".to_string());
+        }
+        let slug = Length::inches(MARK_SLUG_INCHES);
+        let needed = Length::from_micrometres(self.mark_offset.micrometres() + self.mark_length.micrometres());
+        let available = Length::from_micrometres(bleed.micrometres() + slug.micrometres());
+        if needed > available {
+            return Err(format!(
+                "marks need {} outside the trim (markOffset {} + markLength {}) but bleed {} and slug {} leave {}: {} short",
+                needed, self.mark_offset, self.mark_length, bleed, slug, available,
+                Length::from_micrometres(needed.micrometres() - available.micrometres()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `#[serde(with)]` for `marks`, which was a bool: `true` reads as
+/// `Marks::legacy` and is still written for it, so existing templates
+/// keep their hashes
+mod legacy_marks {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Marks;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Legacy {
+        Flag(bool),
+        Fields(Marks),
+    }
+
+    pub fn serialize<S: Serializer>(marks: &Marks, serializer: S) -> Result<S::Ok, S::Error> {
+        if *marks == Marks::legacy() {
+            return serializer.serialize_bool(true);
+        }
+        marks.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Marks, D::Error> {
+        Ok(match Legacy::deserialize(deserializer)? {
+            Legacy::Flag(true) => Marks::legacy(),
+            Legacy::Flag(false) => Marks::default(),
+            Legacy::Fields(marks) => marks,
         })
     }
+
+    #[cfg(feature = "schema")]
+    pub fn schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "anyOf": [{ "type": "boolean" }, generator.subschema_for::<Marks>()] })
+    }
+}
+
+impl TemplatePrint {
+    /// Check against the bounds `PrintSpec::from_user` enforces, the fill
+    /// color, the spot colors and the marks
+    pub fn validate(&self) -> Result<(), String> {
+        PrintSpec::check_bounds(self.dpi, self.bleed)?;
+        if let BleedFill::Color { color } = &self.bleed_fill {
+            parse_hex_color(color).map_err(|e| format!("bleedFill: {}", e))?;
+        }
+        SpotColors::new(&self.spot_colors).map_err(|e| format!("spotColors: {}", e))?;
+        if self.safe_margin.is_some_and(|margin| margin < Length::ZERO) {
+            return Err("safeMargin must be zero or positive".to_string());
+        }
+        self.marks.check_fits(self.bleed)
+    }
+
+    /// The block as a Template-authority spec
+    pub fn spec(&self) -> PrintSpec {
+        PrintSpec {
+            icc_profile: self.icc_profile.clone(),
+            ..PrintSpec::from_template(self.dpi, self.color_space.clone(), self.bleed)
+        }
+    }
+
+    pub fn override_policy(&self) -> OverridePolicy {
+        if !self.allow_user_print_overrides {
+            return OverridePolicy::NONE;
+        }
+        let allowed = |field| !self.locked_fields.contains(&field);
+        OverridePolicy {
+            dpi: allowed(PrintField::Dpi),
+            color_space: allowed(PrintField::ColorSpace),
+            bleed: allowed(PrintField::Bleed),
+            icc_profile: allowed(PrintField::IccProfile),
+            user_color_spaces: self.allowed_user_color_spaces.clone(),
+        }
+    }
+}
+
+/// One resolvable field of a `PrintSpec`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum PrintField {
+    Dpi,
+    ColorSpace,
+    #[serde(rename = "bleedInches", alias = "bleed")]
+    Bleed,
+    IccProfile,
+}
+
+impl PrintField {
+    pub const ALL: [PrintField; 4] =
+        [PrintField::Dpi, PrintField::ColorSpace, PrintField::Bleed, PrintField::IccProfile];
+
+    /// Name as it appears in manifests and requests
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrintField::Dpi => "dpi",
+            PrintField::ColorSpace => "color_space",
+            PrintField::Bleed => "bleed_inches",
+            PrintField::IccProfile => "icc_profile",
+        }
+    }
+
+    /// The field's value in `spec`, as a manifest writes it
+    pub fn value(&self, spec: &PrintSpec) -> serde_json::Value {
+        match self {
+            PrintField::Dpi => serde_json::json!(spec.dpi),
+            PrintField::ColorSpace => serde_json::json!(spec.color_space),
+            PrintField::Bleed => serde_json::json!(spec.bleed.as_inches()),
+            PrintField::IccProfile => serde_json::json!(spec.icc_profile),
+        }
+    }
+}
+
+/// A field a user spec supplied, next to the value it replaced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PrintOverride {
+    /// `PrintField::name`
+    pub field: String,
+    /// The template's value; the system default without a template block
+    pub template_value: serde_json::Value,
+    pub user_value: serde_json::Value,
+}
+
+/// Which fields a user spec may override
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverridePolicy {
+    pub dpi: bool,
+    pub color_space: bool,
+    pub bleed: bool,
+    pub icc_profile: bool,
+    /// Color spaces a user may ask for when `color_space` is allowed; any
+    /// when empty
+    pub user_color_spaces: Vec<ColorSpace>,
+}
+
+impl OverridePolicy {
+    pub const ALL: OverridePolicy =
+        OverridePolicy { dpi: true, color_space: true, bleed: true, icc_profile: true, user_color_spaces: Vec::new() };
+    pub const NONE: OverridePolicy =
+        OverridePolicy { dpi: false, color_space: false, bleed: false, icc_profile: false, user_color_spaces: Vec::new() };
+
+    pub fn allows(&self, field: PrintField) -> bool {
+        match field {
+            PrintField::Dpi => self.dpi,
+            PrintField::ColorSpace => self.color_space,
+            PrintField::Bleed => self.bleed,
+            PrintField::IccProfile => self.icc_profile,
+        }
+    }
+
+    /// Whether the value `user` gives `field` may stand: the field is
+    /// allowed and, for the color space, on the allowlist
+    pub fn admits(&self, user: &PrintSpec, field: PrintField) -> bool {
+        self.allows(field) && (field != PrintField::ColorSpace || self.admits_color_space(&user.color_space))
+    }
+
+    pub fn admits_color_space(&self, color_space: &ColorSpace) -> bool {
+        self.user_color_spaces.is_empty() || self.user_color_spaces.contains(color_space)
+    }
+}
+
+/// An effective spec and the authority behind each field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    /// `authority` is the highest authority among the fields
+    pub spec: PrintSpec,
+    pub dpi: PrintAuthority,
+    pub color_space: PrintAuthority,
+    pub bleed: PrintAuthority,
+    pub icc_profile: PrintAuthority,
+    /// Fields taken from the user, in `PrintField::ALL` order
+    pub overrides: Vec<PrintOverride>,
+}
+
+impl Resolved {
+    pub fn authority(&self, field: PrintField) -> PrintAuthority {
+        match field {
+            PrintField::Dpi => self.dpi,
+            PrintField::ColorSpace => self.color_space,
+            PrintField::Bleed => self.bleed,
+            PrintField::IccProfile => self.icc_profile,
+        }
+    }
+
+    /// Fields where `user` asked for a value the policy kept from it
+    pub fn refused(&self, user: &PrintSpec) -> Vec<PrintField> {
+        PrintField::ALL.into_iter()
+            .filter(|&field| supplies(user, field))
+            .filter(|&field| self.authority(field) != PrintAuthority::User && !same_field(field, user, &self.spec))
+            .collect()
+    }
+}
+
+fn same_field(field: PrintField, a: &PrintSpec, b: &PrintSpec) -> bool {
+    match field {
+        PrintField::Dpi => a.dpi == b.dpi,
+        PrintField::ColorSpace => a.color_space == b.color_space,
+        PrintField::Bleed => a.bleed == b.bleed,
+        PrintField::IccProfile => a.icc_profile == b.icc_profile,
+    }
+}
+
+/// Whether a user spec supplies `field`; an unset profile supplies nothing
+fn supplies(user: &PrintSpec, field: PrintField) -> bool {
+    field != PrintField::IccProfile || user.icc_profile.is_some()
+}
+
+/// Resolve the effective spec per field: User (when `policy` admits the
+/// user's value and the user supplied it) > Template > System.
+///
+/// Values are taken as given; user specs are bounds-checked by the caller
+/// (`PrintSpec::check_bounds`) and template blocks when loaded.
+pub fn resolve(
+    system: &PrintSpec,
+    template: Option<&PrintSpec>,
+    user: Option<&PrintSpec>,
+    policy: &OverridePolicy,
+) -> Resolved {
+    let pick = |field: PrintField| -> (PrintAuthority, &PrintSpec) {
+        match (user, template) {
+            (Some(user), _) if policy.admits(user, field) && supplies(user, field) => (PrintAuthority::User, user),
+            (_, Some(template)) => (PrintAuthority::Template, template),
+            _ => (PrintAuthority::System, system),
+        }
+    };
+    let (dpi, dpi_from) = pick(PrintField::Dpi);
+    let (color_space, color_space_from) = pick(PrintField::ColorSpace);
+    let (bleed, bleed_from) = pick(PrintField::Bleed);
+    let (icc_profile, icc_from) = pick(PrintField::IccProfile);
+    let overrides = match user {
+        Some(user) => PrintField::ALL.into_iter()
+            .filter(|&field| pick(field).0 == PrintAuthority::User)
+            .map(|field| PrintOverride {
+                field: field.name().to_string(),
+                template_value: field.value(template.unwrap_or(system)),
+                user_value: field.value(user),
+            })
+            .collect(),
+        None => vec![],
+    };
+    Resolved {
+        spec: PrintSpec {
+            authority: dpi.max(color_space).max(bleed).max(icc_profile),
+            dpi: dpi_from.dpi,
+            color_space: color_space_from.color_space.clone(),
+            bleed: bleed_from.bleed,
+            icc_profile: icc_from.icc_profile.clone(),
+        },
+        dpi,
+        color_space,
+        bleed,
+        icc_profile,
+        overrides,
+    }
+}
+
+/// `resolve` for one export; `None` for non-print formats. The export's own
+/// `print` block, when set, stands in for the template's.
+pub fn resolve_export(
+    user: Option<&PrintSpec>,
+    template: Option<&TemplatePrint>,
+    spec: &ExportSpec,
+) -> Option<Resolved> {
+    spec.format.is_print().then(|| resolve_block(user, spec.print.as_ref().or(template)))
+}
+
+/// Print blocks of `template` that refuse user specs outright
+/// (`allowUserPrintOverrides` off), as `print` or `exports[id].print`
+pub fn closed_blocks(template: &Template) -> Vec<String> {
+    let closed = |block: &Option<TemplatePrint>| block.as_ref().is_some_and(|b| !b.allow_user_print_overrides);
+    let template_block = closed(&template.print).then(|| "print".to_string());
+    let export_blocks = template.exports.iter()
+        .filter(|spec| spec.format.is_print() && closed(&spec.print))
+        .map(|spec| format!("exports[{}].print", spec.id));
+    template_block.into_iter().chain(export_blocks).collect()
+}
+
+/// `resolve` against a template print block (System defaults beneath it)
+pub fn resolve_block(user: Option<&PrintSpec>, block: Option<&TemplatePrint>) -> Resolved {
+    let policy = block.map_or(OverridePolicy::ALL, TemplatePrint::override_policy);
+    resolve(&PrintSpec::default(), block.map(TemplatePrint::spec).as_ref(), user, &policy)
+}
+
+/// Named print settings a template block can start from, loaded from
+/// `<name>.printprofile.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintProfile {
+    pub name: String,
+    pub dpi: u32,
+    pub color_space: ColorSpace,
+    /// Written as `bleedInches`, like the template block
+    #[serde(rename = "bleedInches", alias = "bleed", with = "units::inches")]
+    pub bleed: Length,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icc_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Marks::is_none", with = "legacy_marks")]
+    pub marks: Marks,
+    #[serde(default, skip_serializing_if = "BleedFill::is_mirror")]
+    pub bleed_fill: BleedFill,
+}
+
+/// File name suffix of print profiles
+pub const PROFILE_SUFFIX: &str = ".printprofile.json";
+
+impl PrintProfile {
+    /// A template block's JSON with this profile beneath it: fields the
+    /// block sets win, the rest come from the profile
+    pub fn apply(&self, block: &serde_json::Map<String, serde_json::Value>) -> serde_json::Value {
+        let serde_json::Value::Object(mut merged) = serde_json::json!(self) else {
+            unreachable!("profiles serialize as objects")
+        };
+        merged.remove("name");
+        // Either spelling of the bleed replaces the profile's
+        if block.contains_key("bleed") || block.contains_key("bleedInches") {
+            merged.remove("bleedInches");
+        }
+        merged.extend(block.clone());
+        serde_json::Value::Object(merged)
+    }
+}
+
+/// Print profiles by name
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRegistry {
+    profiles: BTreeMap<String, PrintProfile>,
+}
+
+impl ProfileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every `*.printprofile.json` in `dir`. Unlike templates, a profile
+    /// file that does not parse, or is out of bounds, is an error.
+    pub fn load_from_dir(dir: &Path) -> Result<Self, std::io::Error> {
+        let invalid = |path: &Path, e: String| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        };
+        let mut registry = Self::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(PROFILE_SUFFIX)) {
+                continue;
+            }
+            let profile: PrintProfile = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| invalid(&path, e.to_string()))?;
+            PrintSpec::check_bounds(profile.dpi, profile.bleed).map_err(|e| invalid(&path, e.to_string()))?;
+            if let BleedFill::Color { color } = &profile.bleed_fill {
+                parse_hex_color(color).map_err(|e| invalid(&path, format!("bleedFill: {}", e)))?;
+            }
+            profile.marks.check_fits(profile.bleed).map_err(|e| invalid(&path, e))?;
+            if registry.profiles.contains_key(&profile.name) {
+                return Err(invalid(&path, format!("duplicate print profile '{}'", profile.name)));
+            }
+            registry.register(profile);
+        }
+        Ok(registry)
+    }
+
+    /// Register `profile`, replacing any of the same name
+    pub fn register(&mut self, profile: PrintProfile) {
+        self.profiles.insert(profile.name.clone(), profile);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PrintProfile> {
+        self.profiles.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Apply the profiles named by a template's JSON, in place: its `print`
+    /// block and each export's. Errors name the block and the profile.
+    pub fn apply_to_template(&self, template: &mut serde_json::Value) -> Result<(), String> {
+        let apply = |at: String, block: &mut serde_json::Value| -> Result<(), String> {
+            let Some(fields) = block.as_object() else { return Ok(()) };
+            let Some(name) = fields.get("profile") else { return Ok(()) };
+            let name = name.as_str().ok_or_else(|| format!("{}.profile: expected a profile name", at))?;
+            let profile = self.get(name).ok_or_else(|| format!("{}: unknown print profile '{}'", at, name))?;
+            *block = profile.apply(fields);
+            Ok(())
+        };
+        if let Some(block) = template.get_mut("print") {
+            apply("print".to_string(), block)?;
+        }
+        if let Some(exports) = template.get_mut("exports").and_then(serde_json::Value::as_array_mut) {
+            for export in exports {
+                let id = export.get("id").and_then(serde_json::Value::as_str).unwrap_or("?").to_string();
+                if let Some(block) = export.get_mut("print") {
+                    apply(format!("exports[{}].print", id), block)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pixel dimensions of a physical size could not be computed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DimensionError {
+    #[error("{axis} {length} is negative")]
+    Negative { axis: &'static str, length: Length },
+    #[error("{axis} {length} at {dpi} dpi is more than {max} pixels", max = u32::MAX)]
+    TooLarge { axis: &'static str, length: Length, dpi: u32 },
+}
+
+/// Pixel size of `physical` at `dpi`, the one conversion export sizing,
+/// imposition and validation share.
+///
+/// Each side is `µm * dpi / 25400` rounded half away from zero; sides are
+/// never negative, so halves round up. The arithmetic is integer (128-bit,
+/// `Length::checked_pixels`), with no floating point, so a size lands on
+/// the same pixels on every platform. A negative side, or one past
+/// `u32::MAX` pixels, is an error rather than clamped or wrapped.
+pub fn raster_dimensions(physical: PhysicalSize, dpi: u32) -> Result<[u32; 2], DimensionError> {
+    let side = |axis: &'static str, length: Length| {
+        if length < Length::ZERO {
+            return Err(DimensionError::Negative { axis, length });
+        }
+        length.checked_pixels(dpi).ok_or(DimensionError::TooLarge { axis, length, dpi })
+    };
+    Ok([side("width", physical.width)?, side("height", physical.height)?])
+}
+
+/// Named physical sizes for print exports
+pub mod presets {
+    use serde::{Deserialize, Serialize};
+
+    use crate::units::{self, Length};
+
+    /// Physical dimensions, in the orientation the preset names them.
+    /// Written as `widthMm` / `heightMm` numbers; also reads length strings
+    /// under either name
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+    #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+    pub struct PhysicalSize {
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        #[serde(rename = "widthMm", alias = "width", with = "units::millimetres")]
+        #[cfg_attr(feature = "schema", schemars(schema_with = "units::legacy_schema"))]
+        pub width: Length,
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        #[serde(rename = "heightMm", alias = "height", with = "units::millimetres")]
+        #[cfg_attr(feature = "schema", schemars(schema_with = "units::legacy_schema"))]
+        pub height: Length,
+    }
+
+    impl PhysicalSize {
+        pub const fn new(width: Length, height: Length) -> Self {
+            Self { width, height }
+        }
+
+        pub fn mm(width: f64, height: f64) -> Self {
+            Self::new(Length::mm(width), Length::mm(height))
+        }
+
+        pub fn inches(width: f64, height: f64) -> Self {
+            Self::new(Length::inches(width), Length::inches(height))
+        }
+
+        /// Same size, long edge horizontal
+        pub fn landscape(self) -> Self {
+            Self::new(self.width.max(self.height), self.width.min(self.height))
+        }
+    }
+
+    /// (name, width × height in mm); imperial sizes are exact in mm
+    const PRESETS: &[(&str, f64, f64)] = &[
+        ("a1", 594.0, 841.0),
+        ("a2", 420.0, 594.0),
+        ("a3", 297.0, 420.0),
+        ("a4", 210.0, 297.0),
+        ("a5", 148.0, 210.0),
+        ("a6", 105.0, 148.0),
+        ("us-letter", 215.9, 279.4),
+        ("us-legal", 215.9, 355.6),
+        ("tabloid", 279.4, 431.8),
+        ("us-business-card", 88.9, 50.8),
+        ("eu-business-card", 85.0, 55.0),
+        ("poster-18x24", 457.2, 609.6),
+        ("poster-24x36", 609.6, 914.4),
+        ("poster-27x40", 685.8, 1016.0),
+    ];
+
+    /// Preset by name (lowercase, as templates spell it)
+    pub fn get(name: &str) -> Option<PhysicalSize> {
+        PRESETS.iter()
+            .find(|(preset, ..)| *preset == name)
+            .map(|&(_, width, height)| PhysicalSize::mm(width, height))
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        PRESETS.iter().map(|(name, ..)| *name)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_millimetre_fields_still_read() {
+            let size: PhysicalSize = serde_json::from_value(serde_json::json!({"widthMm": 320, "heightMm": 450.5})).unwrap();
+            assert_eq!(size, PhysicalSize::mm(320.0, 450.5));
+            let size: PhysicalSize = serde_json::from_value(serde_json::json!({"width": "11in", "height": "17in"})).unwrap();
+            assert_eq!(size, get("tabloid").unwrap());
+            assert_eq!(serde_json::to_value(size).unwrap(), serde_json::json!({"widthMm": 279.4, "heightMm": 431.8}));
+        }
+
+        #[test]
+        fn test_conversions() {
+            let card = PhysicalSize::inches(3.5, 2.0);
+            // Imperial sizes are whole micrometres, so they compare exactly
+            assert_eq!(card, get("us-business-card").unwrap());
+            assert_eq!((card.width.as_inches(), card.height.as_inches()), (3.5, 2.0));
+            assert_eq!(get("a4").unwrap().landscape(), PhysicalSize::mm(297.0, 210.0));
+            assert!(names().all(|name| get(name).is_some()));
+            assert_eq!(get("A4"), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template_print(allow: bool) -> TemplatePrint {
+        TemplatePrint {
+            dpi: 600,
+            color_space: ColorSpace::Cmyk,
+            bleed: Length::inches(0.25),
+            allow_user_print_overrides: allow,
+            locked_fields: vec![],
+            allowed_user_color_spaces: vec![],
+            bleed_fill: BleedFill::Mirror,
+            marks: Marks::default(),
+            safe_margin: None,
+            icc_profile: Some("press".to_string()),
+            true_grayscale_vectors: false,
+            spot_colors: vec![],
+            pdf_standard: None,
+            transparency_policy: TransparencyPolicy::Flatten,
+            profile: None,
+        }
+    }
+
+    fn user_spec() -> PrintSpec {
+        PrintSpec {
+            authority: PrintAuthority::User,
+            dpi: 150,
+            color_space: ColorSpace::Grayscale,
+            bleed: Length::inches(0.5),
+            icc_profile: Some("proof".to_string()),
+        }
+    }
+
+    fn value(spec: &PrintSpec, field: PrintField) -> String {
+        match field {
+            PrintField::Dpi => spec.dpi.to_string(),
+            PrintField::ColorSpace => format!("{:?}", spec.color_space),
+            PrintField::Bleed => spec.bleed.to_string(),
+            PrintField::IccProfile => format!("{:?}", spec.icc_profile),
+        }
+    }
+
+    #[test]
+    fn test_resolve_precedence_per_field() {
+        use PrintAuthority::*;
+
+        let system = PrintSpec::default();
+        let template = template_print(true).spec();
+        let user = user_spec();
+        // (template present, user present, user allowed for the field) -> winner
+        let cases = [
+            (false, false, true, System),
+            (false, false, false, System),
+            (true, false, true, Template),
+            (true, false, false, Template),
+            (false, true, true, User),
+            (false, true, false, System),
+            (true, true, true, User),
+            (true, true, false, Template),
+        ];
+        for field in PrintField::ALL {
+            for (has_template, has_user, allowed, expected) in cases {
+                let policy = OverridePolicy {
+                    dpi: field != PrintField::Dpi || allowed,
+                    color_space: field != PrintField::ColorSpace || allowed,
+                    bleed: field != PrintField::Bleed || allowed,
+                    icc_profile: field != PrintField::IccProfile || allowed,
+                    user_color_spaces: vec![],
+                };
+                let resolved = resolve(
+                    &system,
+                    Some(&template).filter(|_| has_template),
+                    Some(&user).filter(|_| has_user),
+                    &policy,
+                );
+                let case = format!("{:?} {:?}", field, (has_template, has_user, allowed));
+                assert_eq!(resolved.authority(field), expected, "{}", case);
+                let source = match expected { System => &system, Template => &template, User => &user };
+                assert_eq!(value(&resolved.spec, field), value(source, field), "{}", case);
+                // The other fields are unaffected by this one's policy
+                for other in PrintField::ALL.into_iter().filter(|&f| f != field) {
+                    let other_expected = match (has_user, has_template) {
+                        (true, _) => User,
+                        (false, true) => Template,
+                        (false, false) => System,
+                    };
+                    assert_eq!(resolved.authority(other), other_expected, "{} / {:?}", case, other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unset_user_profile_keeps_the_template_profile() {
+        let template = template_print(true).spec();
+        let user = PrintSpec { icc_profile: None, ..user_spec() };
+        let resolved = resolve(&PrintSpec::default(), Some(&template), Some(&user), &OverridePolicy::ALL);
+        assert_eq!(resolved.icc_profile, PrintAuthority::Template);
+        assert_eq!(resolved.spec.icc_profile.as_deref(), Some("press"));
+        assert_eq!(resolved.dpi, PrintAuthority::User);
+
+        // Nor is it refused when the field is locked
+        let locked = TemplatePrint { locked_fields: vec![PrintField::IccProfile], ..template_print(true) };
+        assert_eq!(resolve_block(Some(&user), Some(&locked)).refused(&user), vec![]);
+        assert_eq!(resolve_block(Some(&user_spec()), Some(&locked)).refused(&user_spec()), vec![PrintField::IccProfile]);
+    }
+
+    #[test]
+    fn test_overall_authority_is_the_highest_field_authority() {
+        let template = template_print(true).spec();
+        let dpi_only = OverridePolicy { dpi: true, ..OverridePolicy::NONE };
+        let resolved = resolve(&PrintSpec::default(), Some(&template), Some(&user_spec()), &dpi_only);
+        assert_eq!(resolved.spec.authority, PrintAuthority::User);
+        assert_eq!((resolved.spec.dpi, resolved.spec.color_space), (150, ColorSpace::Cmyk));
+
+        let resolved = resolve(&PrintSpec::default(), Some(&template), None, &OverridePolicy::ALL);
+        assert_eq!(resolved.spec, template);
+    }
+
+    #[test]
+    fn test_template_block_policy() {
+        assert_eq!(template_print(false).override_policy(), OverridePolicy::NONE);
+        assert_eq!(template_print(true).override_policy(), OverridePolicy::ALL);
+        let locked = TemplatePrint { locked_fields: vec![PrintField::ColorSpace], ..template_print(true) };
+        assert_eq!(locked.override_policy(), OverridePolicy { color_space: false, ..OverridePolicy::ALL });
+        // Locking is moot when overrides are off altogether
+        let locked = TemplatePrint { allow_user_print_overrides: false, ..locked };
+        assert_eq!(locked.override_policy(), OverridePolicy::NONE);
+    }
+
+    #[test]
+    fn test_allowed_user_color_spaces_hold_users_only() {
+        let block = TemplatePrint { allowed_user_color_spaces: vec![ColorSpace::Rgb], ..template_print(true) };
+
+        // The block's own CMYK is not held to the list
+        let resolved = resolve_block(None, Some(&block));
+        assert_eq!((resolved.color_space, resolved.spec.color_space), (PrintAuthority::Template, ColorSpace::Cmyk));
+
+        // A user asking for a listed space gets it
+        let user = PrintSpec { color_space: ColorSpace::Rgb, ..user_spec() };
+        let resolved = resolve_block(Some(&user), Some(&block));
+        assert_eq!((resolved.color_space, resolved.spec.color_space), (PrintAuthority::User, ColorSpace::Rgb));
+
+        // Anything else keeps the template's and is refused; other fields still apply
+        let resolved = resolve_block(Some(&user_spec()), Some(&block));
+        assert_eq!((resolved.color_space, resolved.spec.color_space.clone()), (PrintAuthority::Template, ColorSpace::Cmyk));
+        assert_eq!(resolved.spec.dpi, 150);
+        assert_eq!(resolved.refused(&user_spec()), vec![PrintField::ColorSpace]);
+
+        // Without the field every space is allowed
+        assert_eq!(resolve_block(Some(&user_spec()), Some(&template_print(true))).color_space, PrintAuthority::User);
+    }
+
+    #[test]
+    fn test_refused_fields_only_where_the_user_value_differs() {
+        let block = TemplatePrint { locked_fields: vec![PrintField::ColorSpace, PrintField::Bleed], ..template_print(true) };
+        let user = PrintSpec { bleed: Length::inches(0.25), ..user_spec() };
+        let resolved = resolve_block(Some(&user), Some(&block));
+        assert_eq!(resolved.spec.dpi, 150);
+        // Bleed is locked but matches; color space is locked and differs
+        assert_eq!(resolved.refused(&user), vec![PrintField::ColorSpace]);
+    }
+
+    #[test]
+    fn test_template_block_uses_user_bounds() {
+        assert!(template_print(true).validate().is_ok());
+        assert!(TemplatePrint { dpi: 2400, ..template_print(true) }.validate().is_err());
+        assert!(TemplatePrint { bleed: Length::inches(-0.1), ..template_print(true) }.validate().is_err());
+    }
+
+    #[test]
+    fn test_raster_dimensions_of_presets() {
+        let cases = [
+            ("a4", 300, [2480, 3508]),
+            ("a4", 72, [595, 842]),
+            ("us-letter", 300, [2550, 3300]),
+            ("us-business-card", 300, [1050, 600]),
+            ("eu-business-card", 300, [1004, 650]),
+            ("poster-24x36", 150, [3600, 5400]),
+        ];
+        for (name, dpi, expected) in cases {
+            assert_eq!(raster_dimensions(presets::get(name).unwrap(), dpi), Ok(expected), "{} at {} dpi", name, dpi);
+        }
+    }
+
+    #[test]
+    fn test_raster_dimensions_round_half_pixels_away_from_zero() {
+        let micrometres = |width, height| PhysicalSize::new(Length::from_micrometres(width), Length::from_micrometres(height));
+        // 0.5 in at 1 dpi is exactly half a pixel
+        assert_eq!(raster_dimensions(PhysicalSize::mm(12.7, 12.699), 1), Ok([1, 0]));
+        // 254 dpi: 100 µm a pixel, so 10.5 px at 1050 µm
+        assert_eq!(raster_dimensions(micrometres(1050, 1049), 254), Ok([11, 10]));
+        // 127 dpi: 200 µm a pixel, 1.5 px at 300 µm
+        assert_eq!(raster_dimensions(micrometres(300, 299), 127), Ok([2, 1]));
+        assert_eq!(raster_dimensions(micrometres(0, 1), 1200), Ok([0, 0]));
+    }
+
+    #[test]
+    fn test_raster_dimensions_refuse_instead_of_wrapping() {
+        // 4.29e9 px is the most a side can be
+        let max = Length::from_micrometres(u32::MAX as i64 * 25_400);
+        assert_eq!(raster_dimensions(PhysicalSize::new(max, max), 1), Ok([u32::MAX; 2]));
+        let past = Length::from_micrometres(u32::MAX as i64 * 25_400 + 12_700);
+        assert_eq!(
+            raster_dimensions(PhysicalSize::new(Length::mm(10.0), past), 1),
+            Err(DimensionError::TooLarge { axis: "height", length: past, dpi: 1 }),
+        );
+        let e = raster_dimensions(PhysicalSize::new(Length::from_micrometres(i64::MAX), Length::ZERO), u32::MAX).unwrap_err();
+        assert!(e.to_string().starts_with("width "), "{}", e);
+        assert!(e.to_string().ends_with("at 4294967295 dpi is more than 4294967295 pixels"), "{}", e);
+        assert_eq!(
+            raster_dimensions(PhysicalSize::mm(-1.0, 10.0), 300).unwrap_err().to_string(),
+            "width -1mm is negative",
+        );
+    }
 }
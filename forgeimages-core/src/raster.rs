@@ -0,0 +1,67 @@
+//! Raster Buffers - 8-bit RGBA pixel storage shared by renderers and encoders
+
+/// Straight (non-premultiplied) RGBA8 image, rows top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Fully transparent canvas
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::filled(width, height, [0, 0, 0, 0])
+    }
+
+    pub fn filled(width: u32, height: u32, rgba: [u8; 4]) -> Self {
+        let count = width as usize * height as usize;
+        let mut pixels = Vec::with_capacity(count * 4);
+        for _ in 0..count {
+            pixels.extend_from_slice(&rgba);
+        }
+        Self { width, height, pixels }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.offset(x, y);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        let i = self.offset(x, y);
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    /// Bytes of one row (width * 4)
+    pub fn row(&self, y: u32) -> &[u8] {
+        let stride = self.width as usize * 4;
+        let start = y as usize * stride;
+        &self.pixels[start..start + stride]
+    }
+
+    /// Composite `top` over this raster (straight alpha "over", integer math)
+    pub fn draw_over(&mut self, top: &Raster) {
+        assert_eq!((self.width, self.height), (top.width, top.height), "raster size mismatch");
+        for (dst, src) in self.pixels.chunks_exact_mut(4).zip(top.pixels.chunks_exact(4)) {
+            let sa = src[3] as u32;
+            if sa == 0 {
+                continue;
+            }
+            if sa == 255 {
+                dst.copy_from_slice(src);
+                continue;
+            }
+            let da = (dst[3] as u32 * (255 - sa) + 127) / 255;
+            let oa = sa + da;
+            for c in 0..3 {
+                dst[c] = ((src[c] as u32 * sa + dst[c] as u32 * da + oa / 2) / oa) as u8;
+            }
+            dst[3] = oa as u8;
+        }
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+}
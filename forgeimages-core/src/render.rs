@@ -0,0 +1,333 @@
+//! Render Stage - Export Production
+//!
+//! The pipeline never renders directly. It hands each export spec to a
+//! `Renderer`, so alternative backends can be swapped in without touching
+//! validation or manifest logic.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::bleed::{Guides, PrintLayout};
+use crate::color::{self, CmykConverter};
+use crate::encoding::{
+    encode_jpeg_pixels, encode_png, encode_png_pixels, encode_tiff, EncodingProfile, JpegColor, PngColor, PngMetadata,
+    TiffPhotometric,
+};
+use crate::font::Font;
+use crate::icc::IccProfile;
+use crate::imposition::SheetLayout;
+use crate::pdf::{self, PdfColor, PdfConformance, PdfPage, TransparencyPolicy};
+use crate::pipeline::CompileRequest;
+use crate::print::PrintSpec;
+use crate::raster::Raster;
+use crate::svg;
+use crate::templates::{ExportFormat, ExportSpec, Template};
+
+/// Error produced while rendering a single export
+#[derive(Debug, Clone, Error)]
+#[error("{message}")]
+pub struct RenderError {
+    pub message: String,
+    /// Transient failure (e.g. lost GPU context); the pipeline may retry it
+    pub retryable: bool,
+}
+
+impl RenderError {
+    /// Deterministic failure - never retried
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: false }
+    }
+
+    pub fn retryable(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: true }
+    }
+}
+
+/// How often to re-run a render that failed with a retryable error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts including the first (1 = no retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 1, backoff: Duration::ZERO }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `render` until it succeeds, fails deterministically, or attempts
+    /// run out. Returns the result and the number of attempts made.
+    pub fn run<T>(&self, mut render: impl FnMut() -> Result<T, RenderError>) -> (Result<T, RenderError>, u32) {
+        let mut attempt = 1;
+        loop {
+            match render() {
+                Err(e) if e.retryable && attempt < self.max_attempts => {
+                    std::thread::sleep(self.backoff.saturating_mul(1 << (attempt - 1).min(16)));
+                    attempt += 1;
+                }
+                result => return (result, attempt),
+            }
+        }
+    }
+}
+
+/// Everything a renderer needs to produce one export
+pub struct RenderJob<'a> {
+    pub template: &'a Template,
+    pub spec: &'a ExportSpec,
+    pub request: &'a CompileRequest,
+    pub encoding: &'a EncodingProfile,
+    /// PNG text entries, empty unless the template embeds metadata
+    pub metadata: &'a PngMetadata,
+    /// Generated background at the spec size, composited beneath the source
+    pub background: Option<&'a Raster>,
+    /// Decoded source with text slots filled (SVG master or raster bytes)
+    pub source: Option<&'a [u8]>,
+    /// Template font for outlining text slots
+    pub font: Option<&'a Font>,
+    /// Resolved print spec; set only for Pdf/Jpg/Tiff exports
+    pub print: Option<&'a PrintSpec>,
+    /// Set exactly when `print` is CMYK; renderers convert their RGB output
+    /// with it (the manifest records that they did). Spot colors are
+    /// already folded in: exact matches get the mapped ink values.
+    pub cmyk: Option<&'a dyn CmykConverter>,
+    /// Set for soft-proof Png exports: renderers pass their RGB output
+    /// through `color::soft_proof` with it before encoding
+    pub proof: Option<&'a dyn CmykConverter>,
+    /// Bleed and marks around the trim size (`spec.size`); renderers draw
+    /// the artwork at trim size and `compose` it, so the file is `layout.size()`
+    pub layout: Option<&'a PrintLayout>,
+    /// Press sheet for imposed exports (never set together with `layout`);
+    /// renderers draw one trim-size unit and `compose` it onto the sheet
+    pub sheet: Option<&'a SheetLayout>,
+    /// Output profile to embed; the pipeline has checked that it describes
+    /// the export's pixels (RGB for Png, the print color space otherwise)
+    pub icc_profile: Option<&'a IccProfile>,
+    /// Encode Rec. 709 luma (`color::luma_709`): set for print exports
+    /// whose spec is Grayscale, and Png/Svg exports when the template-level
+    /// spec is. Svg renderers bake `svg::bake_grayscale` instead.
+    pub grayscale: bool,
+    /// PDF/X standard for Pdf exports whose print block names one. The
+    /// pipeline has checked `icc_profile` is set and, for PDF/X-1a, that
+    /// `cmyk` or `grayscale` is; renderers write the file with
+    /// `pdf::encode_pdf`, flattening or refusing transparency per the policy.
+    pub pdf: Option<PdfConformance>,
+    /// Set for guide proofs: renderers draw them over the composed layout
+    pub guides: Option<Guides>,
+}
+
+/// Renderer trait - turns an export spec into file bytes
+pub trait Renderer: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError>;
+
+    /// Whether rendering uses nothing but the job: no filesystem, network
+    /// or other ambient state. Sandboxed pipelines refuse other renderers.
+    fn sandbox_safe(&self) -> bool {
+        false
+    }
+}
+
+/// Placeholder renderer - emits minimal valid files per format
+///
+/// PNG exports are transparent canvases at the spec size, encoded with the
+/// pipeline's pinned encoding profile. TIFF and JPEG exports are the same
+/// canvas with bleed and marks (or imposed on its press sheet), on white
+/// paper, in the print color space. All three embed the job's ICC profile.
+/// Soft-proof PNGs are the canvas as printed, opaque. Grayscale PNGs drop
+/// alpha when the canvas is opaque.
+pub struct PlaceholderRenderer;
+
+impl Renderer for PlaceholderRenderer {
+    fn name(&self) -> &'static str { "placeholder" }
+
+    fn sandbox_safe(&self) -> bool { true }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        // Placeholder: In real implementation, this would:
+        // 1. Take the SVG master
+        // 2. Render to the target format at target size
+        // For now, return a minimal valid placeholder
+        let spec = job.spec;
+        match spec.format {
+            ExportFormat::Svg => {
+                let rendered = match job.source.filter(|s| svg::looks_like_svg(s)) {
+                    Some(master) => render_svg_master(master, job.font)?,
+                    None => format!(
+                        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}"></svg>"#,
+                        spec.size[0], spec.size[1]
+                    ).into_bytes(),
+                };
+                if !job.grayscale {
+                    return Ok(rendered);
+                }
+                let text = String::from_utf8(rendered).map_err(|_| RenderError::new("SVG output is not UTF-8"))?;
+                svg::bake_grayscale(&text)
+                    .map(String::into_bytes)
+                    .map_err(|e| RenderError::new(e.to_string()))
+            }
+            ExportFormat::Png if job.grayscale => {
+                let canvas = placeholder_canvas(job);
+                // Alpha only when the canvas has some transparency to keep
+                let (png_color, pixels) = if canvas.pixels.chunks_exact(4).all(|pixel| pixel[3] == 255) {
+                    (PngColor::Gray, color::to_gray(&canvas))
+                } else {
+                    (PngColor::GrayAlpha, color::to_gray_alpha(&canvas))
+                };
+                Ok(encode_png_pixels(
+                    canvas.width, canvas.height, png_color, &pixels, job.encoding, job.metadata, job.icc_profile,
+                ))
+            }
+            ExportFormat::Png => {
+                let mut canvas = placeholder_canvas(job);
+                if let Some(converter) = job.proof {
+                    canvas = color::soft_proof(&canvas, converter);
+                }
+                Ok(encode_png(&canvas, job.encoding, job.metadata, job.icc_profile))
+            }
+            ExportFormat::Tiff => {
+                let canvas = print_canvas(job);
+                let dpi = job.print.map_or(300, |print| print.dpi);
+                let (photometric, pixels) = match job.cmyk {
+                    Some(converter) => (TiffPhotometric::Cmyk, color::to_cmyk(&canvas, converter)),
+                    None if job.grayscale => (TiffPhotometric::Gray, color::to_gray(&canvas)),
+                    None => (TiffPhotometric::Rgb, color::flatten_on_white(&canvas)),
+                };
+                Ok(encode_tiff(canvas.width, canvas.height, photometric, &pixels, dpi, job.icc_profile))
+            }
+            ExportFormat::Jpg => {
+                let canvas = print_canvas(job);
+                let dpi = job.print.map_or(300, |print| print.dpi);
+                let (jpeg_color, pixels) = match job.cmyk {
+                    Some(converter) => (JpegColor::Cmyk, color::to_cmyk(&canvas, converter)),
+                    None if job.grayscale => (JpegColor::Luma, color::to_gray(&canvas)),
+                    None => (JpegColor::Rgb, color::flatten_on_white(&canvas)),
+                };
+                encode_jpeg_pixels(canvas.width, canvas.height, jpeg_color, &pixels, dpi, job.icc_profile)
+                    .map_err(RenderError::new)
+            }
+            ExportFormat::Pdf => match (job.pdf, job.icc_profile) {
+                (Some(pdf), Some(icc)) => render_pdf(job, pdf, icc),
+                _ => Ok(b"placeholder".to_vec()),
+            },
+            _ => {
+                Ok(b"placeholder".to_vec())
+            }
+        }
+    }
+}
+
+/// What `PlaceholderRenderer` writes for a format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoder {
+    /// Real files, decodable by any reader of the format
+    Native,
+    /// Real files for PDF/X exports, placeholder bytes for other PDFs
+    PdfXOnly,
+    /// Placeholder bytes, not a file of the format
+    Placeholder,
+}
+
+impl PlaceholderRenderer {
+    pub fn encoder(format: &ExportFormat) -> Encoder {
+        match format {
+            ExportFormat::Svg | ExportFormat::Png | ExportFormat::Jpg | ExportFormat::Tiff => Encoder::Native,
+            ExportFormat::Pdf => Encoder::PdfXOnly,
+            ExportFormat::Ico => Encoder::Placeholder,
+        }
+    }
+}
+
+/// The placeholder, `delay` slower per export; lets tests stop a compile
+/// mid-render
+#[cfg(feature = "test-hooks")]
+pub struct SlowRenderer {
+    pub delay: Duration,
+}
+
+#[cfg(feature = "test-hooks")]
+impl Renderer for SlowRenderer {
+    fn name(&self) -> &'static str { "slow-placeholder" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        std::thread::sleep(self.delay);
+        PlaceholderRenderer.render(job)
+    }
+}
+
+/// Background (if any) with an empty source layer over it. Real renderers
+/// rasterize the master here before compositing.
+fn placeholder_canvas(job: &RenderJob<'_>) -> Raster {
+    let [width, height] = job.spec.size;
+    let source = Raster::new(width, height);
+    let mut canvas = match job.background {
+        Some(background) => background.clone(),
+        None => Raster::new(width, height),
+    };
+    canvas.draw_over(&source);
+    canvas
+}
+
+/// The placeholder canvas with the job's bleed and marks, or imposed on
+/// its press sheet, and any guides drawn over it
+fn print_canvas(job: &RenderJob<'_>) -> Raster {
+    let mut canvas = placeholder_canvas(job);
+    if let Some(layout) = job.layout {
+        canvas = layout.compose(&canvas);
+    }
+    if let Some(sheet) = job.sheet {
+        canvas = sheet.compose(&canvas);
+    }
+    if let Some(guides) = job.guides {
+        guides.draw(&mut canvas);
+    }
+    canvas
+}
+
+/// PDF/X page: the canvas composed like a Tiff export, then flattened
+/// (or refused, per the transparency policy) into the output color space
+fn render_pdf(job: &RenderJob<'_>, pdf: PdfConformance, icc: &IccProfile) -> Result<Vec<u8>, RenderError> {
+    let canvas = print_canvas(job);
+    let transparent = canvas.pixels.chunks_exact(4).filter(|pixel| pixel[3] < 255).count();
+    if transparent > 0 && pdf.transparency == TransparencyPolicy::Error {
+        return Err(RenderError::new(format!(
+            "{} requirements not met: no transparency ({} partly transparent pixels, transparencyPolicy is error)",
+            pdf.standard.label(), transparent,
+        )));
+    }
+    let (color, pixels) = match job.cmyk {
+        Some(converter) => (PdfColor::Cmyk, color::to_cmyk(&canvas, converter)),
+        None if job.grayscale => (PdfColor::Gray, color::to_gray(&canvas)),
+        None => (PdfColor::Rgb, color::flatten_on_white(&canvas)),
+    };
+    let page = PdfPage {
+        width: canvas.width,
+        height: canvas.height,
+        color,
+        pixels: &pixels,
+        dpi: job.print.map_or(300, |print| print.dpi),
+        trim: job.layout.map(PrintLayout::trim_box),
+        bleed: job.layout.map(PrintLayout::bleed_box),
+        title: &job.template.name,
+    };
+    Ok(pdf::encode_pdf(&page, pdf.standard, icc))
+}
+
+/// SVG exports are the master itself, with text slots outlined to paths
+fn render_svg_master(master: &[u8], font: Option<&Font>) -> Result<Vec<u8>, RenderError> {
+    let Some(font) = font else {
+        return Ok(master.to_vec());
+    };
+    let text = std::str::from_utf8(master)
+        .map_err(|_| RenderError::new("SVG master is not UTF-8"))?;
+    svg::outline_slots(text, font)
+        .map(String::into_bytes)
+        .map_err(|e| RenderError::new(e.to_string()))
+}
@@ -0,0 +1,134 @@
+//! Reproduction - Recompiling a Manifest (Law 5)
+//!
+//! A manifest records the request it compiled (`CompiledAsset::request`),
+//! minus the source bytes. Given the source again, `reproduce` rebuilds
+//! the request, recompiles under the manifest's id, timestamp, config
+//! hash, hash algorithm, encoding and normalization, and compares the two.
+//! The config hash is carried over, not recomputed: the recompiling
+//! pipeline is configured from the manifest, not the original config. Equal
+//! manifest hashes mean a byte-exact reproduction: every export hash, and
+//! so every export byte, is the same.
+//!
+//! Signed manifests and keyed job hashes need the original keys, which
+//! this does not take; their recompiles differ in `signer` or `job_hash`.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::diff::{diff_manifests, ManifestDiff};
+use crate::hashing::HashingError;
+use crate::pipeline::{CompilationPipeline, CompiledAsset, ManifestStamp, PipelineError, RecordedSource, SourceKind};
+use crate::templates::TemplateRegistry;
+
+#[derive(Debug, Error)]
+pub enum ReproduceError {
+    #[error("Manifest records no request; it predates reproducible manifests")]
+    NoRequest,
+
+    #[error("Manifest was compiled from a source; pass that source to reproduce it")]
+    SourceRequired,
+
+    #[error("{0}: {1}")]
+    Source(PathBuf, std::io::Error),
+
+    #[error(transparent)]
+    Pipeline(#[from] PipelineError),
+}
+
+impl From<HashingError> for ReproduceError {
+    fn from(error: HashingError) -> Self {
+        Self::Pipeline(error.into())
+    }
+}
+
+/// The loaded template is not the one the manifest was compiled with
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateChange {
+    pub template_id: String,
+    pub recorded_version: String,
+    pub recorded_hash: String,
+    pub loaded_version: String,
+    pub loaded_hash: String,
+}
+
+/// The given source is not the one the manifest was compiled from
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceChange {
+    pub recorded_hash: Option<String>,
+    pub given_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reproduction {
+    /// Whether the recompiled manifest hash equals the recorded one
+    pub reproduced: bool,
+    pub recorded_manifest_hash: String,
+    pub manifest_hash: String,
+    /// Set when the loaded template's content hash differs from the recorded one
+    pub template_changed: Option<TemplateChange>,
+    pub source_changed: Option<SourceChange>,
+    /// Recorded manifest as `a`, recompiled as `b`
+    pub diff: ManifestDiff,
+}
+
+/// Recompile `manifest` with templates from `registry` and the source at
+/// `source` (required when the manifest records one)
+pub fn reproduce(
+    registry: TemplateRegistry,
+    manifest: &CompiledAsset,
+    source: Option<&Path>,
+) -> Result<Reproduction, ReproduceError> {
+    let record = manifest.request.as_ref().ok_or(ReproduceError::NoRequest)?;
+    let source = match (record.source, source) {
+        (Some(_), None) => return Err(ReproduceError::SourceRequired),
+        (_, Some(path)) => Some((path, std::fs::read(path).map_err(|e| ReproduceError::Source(path.to_path_buf(), e))?)),
+        (None, None) => None,
+    };
+
+    let algorithm = manifest.hash_algorithm;
+    let template_changed = registry.get(&manifest.template_id)
+        .map(|template| template.content_hash_with(algorithm).map(|hash| (template, hash)))
+        .transpose()?
+        .filter(|(_, hash)| *hash != manifest.template_hash)
+        .map(|(template, loaded_hash)| TemplateChange {
+            template_id: manifest.template_id.clone(),
+            recorded_version: manifest.template_version.clone(),
+            recorded_hash: manifest.template_hash.clone(),
+            loaded_version: template.template_version.clone(),
+            loaded_hash,
+        });
+    let source_changed = source.as_ref()
+        .map(|(_, bytes)| algorithm.digest(bytes))
+        .filter(|given| Some(given) != manifest.source_hash.as_ref())
+        .map(|given_hash| SourceChange { recorded_hash: manifest.source_hash.clone(), given_hash });
+
+    let mut builder = CompilationPipeline::builder(registry)
+        .hash_algorithm(algorithm)
+        .encoding(manifest.encoding.clone())
+        .normalize_source(manifest.normalized_source_hash.is_some());
+    let recorded_source = match (&source, record.source) {
+        (Some((path, _)), Some(SourceKind::Path)) => {
+            let (root, name) = match (path.parent(), path.file_name()) {
+                (Some(root), Some(name)) => (root, Path::new(name)),
+                _ => (Path::new("."), *path),
+            };
+            builder = builder.source_root(root);
+            Some(RecordedSource::Path(name))
+        }
+        (Some((_, bytes)), _) => Some(RecordedSource::Inline(bytes)),
+        (None, _) => None,
+    };
+    let request = record.request(&manifest.template_id, recorded_source);
+    let recompiled = builder.build().compile_asset_stamped(&request, &ManifestStamp::of(manifest))?;
+
+    Ok(Reproduction {
+        reproduced: recompiled.manifest_hash == manifest.manifest_hash,
+        recorded_manifest_hash: manifest.manifest_hash.clone(),
+        manifest_hash: recompiled.manifest_hash.clone(),
+        template_changed,
+        source_changed,
+        diff: diff_manifests(manifest, &recompiled),
+    })
+}
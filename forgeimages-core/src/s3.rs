@@ -0,0 +1,115 @@
+//! S3 Export Sink - Exports Straight to an Object Store
+//!
+//! An `ExportSink` over Amazon S3 or any S3-compatible store (MinIO, R2,
+//! Ceph...), built on `object_store`'s async client and driven from the
+//! pipeline's synchronous compile by a runtime the sink owns.
+//!
+//! Objects go to `{prefix}/{asset id}/{filename}` with the export format's
+//! Content-Type, and carry `x-amz-checksum-sha256`, so the store rejects
+//! bytes that arrive damaged. Deletes are the sink's half of the rollback
+//! described in `sink`.
+
+use object_store::aws::{AmazonS3, AmazonS3Builder, Checksum};
+use object_store::path::Path as ObjectPath;
+use object_store::{Attribute, Attributes, ObjectStore, PutOptions, PutPayload};
+
+use crate::sink::{ExportSink, ExportUpload, SinkError, StoredObject};
+
+/// Where and as whom to store exports
+#[derive(Debug, Clone, Default)]
+pub struct S3Config {
+    pub bucket: String,
+    /// Key prefix; surrounding slashes are ignored
+    pub prefix: String,
+    pub region: String,
+    /// S3-compatible endpoint (path-style requests); AWS's own when `None`
+    pub endpoint: Option<String>,
+    /// Static credentials; without them the client asks the instance
+    /// metadata service
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+}
+
+impl S3Config {
+    /// `FORGEIMAGES_S3_BUCKET` (required) and `FORGEIMAGES_S3_PREFIX`, with
+    /// the standard `AWS_REGION` (or `AWS_DEFAULT_REGION`, else us-east-1),
+    /// `AWS_ENDPOINT_URL_S3` (or `AWS_ENDPOINT_URL`), `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`
+    pub fn from_env() -> Result<Self, SinkError> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Ok(Self {
+            bucket: var("FORGEIMAGES_S3_BUCKET")
+                .ok_or_else(|| SinkError::Config("FORGEIMAGES_S3_BUCKET is not set".to_string()))?,
+            prefix: var("FORGEIMAGES_S3_PREFIX").unwrap_or_default(),
+            region: var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")).unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: var("AWS_ENDPOINT_URL_S3").or_else(|| var("AWS_ENDPOINT_URL")),
+            access_key_id: var("AWS_ACCESS_KEY_ID"),
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY"),
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+/// `ExportSink` storing each export as one S3 object
+pub struct S3Sink {
+    store: AmazonS3,
+    prefix: String,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl S3Sink {
+    pub fn new(config: S3Config) -> Result<Self, SinkError> {
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_checksum_algorithm(Checksum::SHA256);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint).with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let (Some(id), Some(secret)) = (&config.access_key_id, &config.secret_access_key) {
+            builder = builder.with_access_key_id(id).with_secret_access_key(secret);
+        }
+        if let Some(token) = &config.session_token {
+            builder = builder.with_token(token);
+        }
+        let store = builder.build().map_err(|e| SinkError::Config(e.to_string()))?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|e| SinkError::Config(format!("cannot start the S3 client runtime: {}", e)))?;
+        Ok(Self { store, prefix: config.prefix.trim_matches('/').to_string(), runtime })
+    }
+
+    /// `S3Sink::new(S3Config::from_env()?)`
+    pub fn from_env() -> Result<Self, SinkError> {
+        Self::new(S3Config::from_env()?)
+    }
+
+    fn path(&self, key: &str) -> Result<ObjectPath, SinkError> {
+        ObjectPath::parse(key).map_err(|e| SinkError::Store(format!("invalid key {:?}: {}", key, e)))
+    }
+}
+
+impl ExportSink for S3Sink {
+    fn put(&self, upload: &ExportUpload<'_>) -> Result<StoredObject, SinkError> {
+        let key = match self.prefix.as_str() {
+            "" => upload.name.to_string(),
+            prefix => format!("{}/{}", prefix, upload.name),
+        };
+        let path = self.path(&key)?;
+        let mut attributes = Attributes::new();
+        attributes.insert(Attribute::ContentType, upload.content_type.into());
+        let options = PutOptions { attributes, ..PutOptions::default() };
+        let payload = PutPayload::from(upload.data.to_vec());
+        let result = self.runtime.block_on(self.store.put_opts(&path, payload, options))
+            .map_err(|e| SinkError::Store(format!("PUT {}: {}", key, e)))?;
+        Ok(upload.stored(key, result.e_tag))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), SinkError> {
+        let path = self.path(key)?;
+        self.runtime.block_on(self.store.delete(&path)).map_err(|e| SinkError::Store(format!("DELETE {}: {}", key, e)))
+    }
+}
@@ -0,0 +1,288 @@
+//! HTTP API - The Pipeline Over JSON
+//!
+//! `GET /healthz`, `GET /config` (the effective `PipelineConfig`, secrets
+//! redacted, with its hash), `GET /templates`, `GET /templates/{id}`,
+//! `POST /validate/{template_id}` (an `AssetInput`) and `POST /compile`
+//! (a `CompileRequest`). Bodies are the library's serde types, parsed
+//! strictly like CLI payloads. Validation failures are 422 with the
+//! `ValidationResult` in the body (under `validation` for compiles); other
+//! errors are the `ErrorEnvelope` the CLI prints, with a status from its
+//! exit code.
+//!
+//! Bodies sent as `Content-Type: application/msgpack` are read as
+//! MessagePack (the `msgpack` feature; 415 without it) and answered in
+//! kind; they decode to the same values, and job hashes, as their JSON.
+//! Error bodies are always JSON.
+//!
+//! Validation and compiles run on the blocking pool, at most `concurrency`
+//! at a time; further requests wait for a slot.
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+
+use crate::error::{ErrorEnvelope, ErrorKind};
+use crate::exit_code;
+use crate::hashing::parse_strict;
+use crate::pipeline::{CompilationPipeline, CompileRequest, PipelineError};
+use crate::validation::AssetInput;
+use crate::ENGINE_VERSION;
+
+/// Limits every server is started with
+#[derive(Debug, Clone, Copy)]
+pub struct ServerOptions {
+    /// Validations and compiles in flight at once
+    pub concurrency: usize,
+    /// Largest request body accepted, in bytes
+    pub max_body_bytes: usize,
+}
+
+#[derive(Clone)]
+struct AppState {
+    pipeline: Arc<CompilationPipeline>,
+    slots: Arc<Semaphore>,
+}
+
+/// The API's routes over a shared pipeline
+pub fn router(pipeline: Arc<CompilationPipeline>, options: ServerOptions) -> Router {
+    let state = AppState { pipeline, slots: Arc::new(Semaphore::new(options.concurrency.max(1))) };
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/config", get(config))
+        .route("/templates", get(list_templates))
+        .route("/templates/{id}", get(get_template))
+        .route("/validate/{template_id}", post(validate))
+        .route("/compile", post(compile))
+        .layer(DefaultBodyLimit::max(options.max_body_bytes))
+        .with_state(state)
+}
+
+/// Serve until the listener fails
+pub async fn serve(listener: TcpListener, pipeline: Arc<CompilationPipeline>, options: ServerOptions) -> std::io::Result<()> {
+    axum::serve(listener, router(pipeline, options)).await
+}
+
+/// An `ErrorEnvelope`, with a status from its exit code
+struct ApiError {
+    status: StatusCode,
+    envelope: ErrorEnvelope,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { status, envelope: ErrorEnvelope::new(kind, message) }
+    }
+}
+
+impl From<ErrorEnvelope> for ApiError {
+    fn from(envelope: ErrorEnvelope) -> Self {
+        let status = match envelope.exit_code() {
+            exit_code::VALIDATION_FAILED => StatusCode::UNPROCESSABLE_ENTITY,
+            exit_code::TEMPLATE_NOT_FOUND => StatusCode::NOT_FOUND,
+            exit_code::VERSION_MISMATCH => StatusCode::CONFLICT,
+            exit_code::INVALID_PAYLOAD => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, envelope }
+    }
+}
+
+impl From<PipelineError> for ApiError {
+    fn from(error: PipelineError) -> Self {
+        ErrorEnvelope::from(&error).into()
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.envelope)).into_response()
+    }
+}
+
+const MSGPACK: &str = "application/msgpack";
+
+/// How a request body is encoded, and so its response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFormat {
+    Json,
+    Msgpack,
+}
+
+impl BodyFormat {
+    fn of(headers: &HeaderMap) -> Self {
+        let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        if essence.eq_ignore_ascii_case(MSGPACK) || essence.eq_ignore_ascii_case("application/x-msgpack") {
+            BodyFormat::Msgpack
+        } else {
+            BodyFormat::Json
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::Msgpack => MSGPACK,
+        }
+    }
+
+    fn encode(self, value: &impl Serialize) -> Result<Vec<u8>, ApiError> {
+        match self {
+            BodyFormat::Json => serde_json::to_vec(value).map_err(|e| PipelineError::from(e).into()),
+            BodyFormat::Msgpack => encode_msgpack(value),
+        }
+    }
+
+    fn respond(self, status: StatusCode, value: &impl Serialize) -> Result<Response, ApiError> {
+        Ok((status, [(header::CONTENT_TYPE, self.content_type())], self.encode(value)?).into_response())
+    }
+}
+
+fn invalid_payload(e: impl std::fmt::Display) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, ErrorKind::InvalidPayload, format!("Invalid payload: {}", e))
+}
+
+/// A request body, rejecting duplicate keys as CLI payloads do
+fn parse_body<T: DeserializeOwned>(format: BodyFormat, body: &[u8]) -> Result<T, ApiError> {
+    let value = match format {
+        BodyFormat::Json => {
+            let text = std::str::from_utf8(body).map_err(invalid_payload)?;
+            parse_strict(text).map_err(invalid_payload)?
+        }
+        BodyFormat::Msgpack => decode_msgpack(body)?,
+    };
+    serde_json::from_value(value).map_err(invalid_payload)
+}
+
+#[cfg(feature = "msgpack")]
+fn decode_msgpack(body: &[u8]) -> Result<Value, ApiError> {
+    crate::msgpack::parse_strict_msgpack(body).map_err(invalid_payload)
+}
+
+#[cfg(feature = "msgpack")]
+fn encode_msgpack(value: &impl Serialize) -> Result<Vec<u8>, ApiError> {
+    crate::msgpack::to_msgpack(value)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorKind::SerializationError, e.to_string()))
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn msgpack_unsupported() -> ApiError {
+    ApiError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, ErrorKind::UnsupportedMediaType, "MessagePack bodies need the msgpack feature")
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn decode_msgpack(_body: &[u8]) -> Result<Value, ApiError> {
+    Err(msgpack_unsupported())
+}
+
+#[cfg(not(feature = "msgpack"))]
+fn encode_msgpack(_value: &impl Serialize) -> Result<Vec<u8>, ApiError> {
+    Err(msgpack_unsupported())
+}
+
+/// Run pipeline work on the blocking pool once a slot is free
+async fn run<T: Send + 'static>(
+    state: &AppState,
+    work: impl FnOnce(&CompilationPipeline) -> T + Send + 'static,
+) -> Result<T, ApiError> {
+    let internal = |e: String| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, ErrorKind::Internal, e);
+    let slot = state.slots.clone().acquire_owned().await.map_err(|e| internal(e.to_string()))?;
+    let pipeline = state.pipeline.clone();
+    tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        work(&pipeline)
+    })
+    .await
+    .map_err(|e| internal(e.to_string()))
+}
+
+async fn healthz(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "engine_version": ENGINE_VERSION,
+        "template_count": state.pipeline.registry().list().len(),
+    }))
+}
+
+/// The effective pipeline config, as `config_hash` in manifests hashes it
+async fn config(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let config = state.pipeline.config();
+    let hash = config.hash(state.pipeline.hash_algorithm()).map_err(PipelineError::from)?;
+    Ok(Json(json!({ "config": config, "config_hash": hash })))
+}
+
+/// Every template, sorted by id
+async fn list_templates(State(state): State<AppState>) -> Response {
+    let mut templates = state.pipeline.list_templates();
+    templates.sort_by(|a, b| a.id.cmp(&b.id));
+    Json(templates).into_response()
+}
+
+async fn get_template(State(state): State<AppState>, Path(id): Path<String>) -> Result<Response, ApiError> {
+    match state.pipeline.get_template(&id) {
+        Some(template) => Ok(Json(template).into_response()),
+        None => Err(PipelineError::TemplateNotFound(id).into()),
+    }
+}
+
+async fn validate(
+    State(state): State<AppState>,
+    Path(template_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let format = BodyFormat::of(&headers);
+    let input: AssetInput = parse_body(format, &body)?;
+    let result = run(&state, move |pipeline| pipeline.validate_asset(&template_id, &input)).await??;
+    let status = if result.valid { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+    format.respond(status, &result)
+}
+
+#[derive(Deserialize)]
+struct CompileQuery {
+    /// `false` drops each export's `data_base64`, keeping hashes and metadata
+    #[serde(default = "default_true")]
+    include_data: bool,
+}
+
+fn default_true() -> bool { true }
+
+async fn compile(
+    State(state): State<AppState>,
+    Query(query): Query<CompileQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let format = BodyFormat::of(&headers);
+    let request: CompileRequest = parse_body(format, &body)?;
+    let manifest = run(&state, move |pipeline| {
+        let asset = match pipeline.compile_asset(&request) {
+            Ok(asset) => asset,
+            Err(e) => return Err(pipeline.error_envelope(&request, &e).into()),
+        };
+        match (query.include_data, format) {
+            (true, BodyFormat::Json) => {
+                return asset.canonical_json().map(|json| json.into_owned().into_bytes()).map_err(|e| PipelineError::from(e).into());
+            }
+            (true, BodyFormat::Msgpack) => return format.encode(&asset),
+            (false, _) => {}
+        }
+        let mut manifest = serde_json::to_value(&asset).map_err(PipelineError::from)?;
+        for export in manifest["exports"].as_array_mut().into_iter().flatten().filter_map(Value::as_object_mut) {
+            export.remove("data_base64");
+        }
+        format.encode(&manifest)
+    })
+    .await??;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], manifest).into_response())
+}
@@ -0,0 +1,147 @@
+//! Export Sinks - Exports Stored as They Compile
+//!
+//! A pipeline built with an `ExportSink` hands each rendered export to it
+//! before hashing the manifest, so the manifest's `stored_objects` records
+//! where every export landed (key, ETag and sha256) under the manifest
+//! hash. Nothing is written to local disk first.
+//!
+//! A required export that fails to store aborts the compile; the objects
+//! already stored for it are then deleted. That rollback is best effort:
+//! a delete that fails is logged and the compile reports the original
+//! error, so a store without versioning may keep orphans under the asset
+//! id's prefix. An optional export that fails to store is dropped from the
+//! manifest and listed in `export_errors`, with a warning, as when it
+//! fails to render.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::hashing::sha256_hex;
+use crate::pipeline::{ExportError, ExportedFile, PipelineError};
+use crate::templates::{ExportFormat, Template};
+
+#[derive(Debug, Error)]
+pub enum SinkError {
+    #[error("Export sink configuration error: {0}")]
+    Config(String),
+
+    #[error("Export sink error: {0}")]
+    Store(String),
+}
+
+/// One export as handed to a sink
+#[derive(Debug, Clone, Copy)]
+pub struct ExportUpload<'a> {
+    /// `{asset id}/{filename}`; a sink may place it under its own prefix
+    pub name: &'a str,
+    pub export_id: &'a str,
+    pub content_type: &'static str,
+    pub data: &'a [u8],
+    /// Hex sha256 of `data`, whatever the manifest's hash algorithm
+    pub sha256: &'a str,
+}
+
+impl ExportUpload<'_> {
+    /// The manifest record for this export once stored under `key`
+    pub fn stored(&self, key: impl Into<String>, etag: Option<String>) -> StoredObject {
+        StoredObject {
+            export_id: self.export_id.to_string(),
+            key: key.into(),
+            etag,
+            sha256: self.sha256.to_string(),
+        }
+    }
+}
+
+/// Where an export was stored, as recorded in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StoredObject {
+    pub export_id: String,
+    pub key: String,
+    /// The store's entity tag, when it returns one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// Hex sha256 of the stored bytes
+    pub sha256: String,
+}
+
+/// Destination for compiled exports
+pub trait ExportSink: Send + Sync {
+    /// Store one export
+    fn put(&self, upload: &ExportUpload<'_>) -> Result<StoredObject, SinkError>;
+
+    /// Remove an object `put` stored, when its compile is rolled back
+    fn delete(&self, key: &str) -> Result<(), SinkError>;
+}
+
+/// Content-Type for an export format
+pub fn media_type(format: &ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Svg => "image/svg+xml",
+        ExportFormat::Png => "image/png",
+        ExportFormat::Ico => "image/vnd.microsoft.icon",
+        ExportFormat::Pdf => "application/pdf",
+        ExportFormat::Jpg => "image/jpeg",
+        ExportFormat::Tiff => "image/tiff",
+    }
+}
+
+/// Exports that stored, where they went, and the optional ones that did not
+pub(crate) struct StoredExports {
+    pub exports: Vec<ExportedFile>,
+    pub objects: Vec<StoredObject>,
+    pub errors: Vec<ExportError>,
+}
+
+/// Store every export of `asset_id` in template order. `checkpoint` runs
+/// before each upload; its error, like a required export's, rolls back.
+pub(crate) fn store_exports(
+    sink: &dyn ExportSink,
+    template: &Template,
+    asset_id: &str,
+    exports: Vec<ExportedFile>,
+    checkpoint: impl Fn(&str) -> Result<(), PipelineError>,
+) -> Result<StoredExports, PipelineError> {
+    let mut stored = StoredExports { exports: vec![], objects: vec![], errors: vec![] };
+    for export in exports {
+        let spec = template.exports.iter().find(|spec| spec.id == export.id)
+            .expect("exports are rendered from template specs");
+        let result = checkpoint(&export.id).and_then(|()| {
+            let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &export.data_base64)
+                .map_err(|e| PipelineError::OutputFailed(format!("{}: {}", export.filename, e)))?;
+            let upload = ExportUpload {
+                name: &format!("{}/{}", asset_id, export.filename),
+                export_id: &export.id,
+                content_type: media_type(&spec.format),
+                data: &data,
+                sha256: &sha256_hex(&data),
+            };
+            sink.put(&upload).map_err(|e| PipelineError::OutputFailed(format!("{}: {}", export.filename, e)))
+        });
+        match result {
+            Ok(object) => {
+                stored.objects.push(object);
+                stored.exports.push(export);
+            }
+            Err(PipelineError::OutputFailed(message)) if !spec.required || spec.is_proof() => {
+                stored.errors.push(ExportError { export_id: export.id, message, spec: spec.clone() });
+            }
+            Err(e) => {
+                discard(sink, &stored.objects);
+                return Err(e);
+            }
+        }
+    }
+    Ok(stored)
+}
+
+/// Best-effort delete of `objects`; failures are logged, not returned
+pub(crate) fn discard(sink: &dyn ExportSink, objects: &[StoredObject]) {
+    for object in objects {
+        if let Err(e) = sink.delete(&object.key) {
+            tracing::warn!(key = %object.key, error = %e, "could not delete stored export");
+        }
+    }
+}
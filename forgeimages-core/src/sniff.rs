@@ -0,0 +1,225 @@
+//! Source Sniffing - Format and Dimensions From File Headers
+//!
+//! Reads just enough of a PNG (IHDR), JPEG (first SOF segment) or SVG
+//! (root viewBox, else width/height) to fill an `AssetInput`. Nothing is
+//! decoded: a file that sniffs cleanly can still fail to render.
+
+use thiserror::Error;
+
+use crate::svg::{self, Token};
+use crate::validation::AssetInput;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    Png,
+    Jpeg,
+    Svg,
+}
+
+impl SourceFormat {
+    /// Value for `AssetInput.format`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceFormat::Png => "png",
+            SourceFormat::Jpeg => "jpeg",
+            SourceFormat::Svg => "svg",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SourceFormat::Png => "PNG",
+            SourceFormat::Jpeg => "JPEG",
+            SourceFormat::Svg => "SVG",
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SniffError {
+    #[error("Unrecognized format: expected PNG, JPEG or SVG")]
+    Unrecognized,
+
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SniffedSource {
+    pub format: SourceFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SniffedSource {
+    pub fn asset_input(&self) -> AssetInput {
+        AssetInput {
+            width: self.width,
+            height: self.height,
+            color_count: None,
+            format: Some(self.format.as_str().to_string()),
+        }
+    }
+}
+
+/// Format and pixel dimensions of `data`
+pub fn sniff(data: &[u8]) -> Result<SniffedSource, SniffError> {
+    let (format, [width, height]) = if data.starts_with(&PNG_SIGNATURE) {
+        (SourceFormat::Png, png_size(data)?)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        (SourceFormat::Jpeg, jpeg_size(data)?)
+    } else if svg::looks_like_svg(data) {
+        (SourceFormat::Svg, svg_size(data)?)
+    } else {
+        return Err(SniffError::Unrecognized);
+    };
+    if width == 0 || height == 0 {
+        return Err(SniffError::Invalid(format.label(), format!("zero size {}x{}", width, height)));
+    }
+    Ok(SniffedSource { format, width, height })
+}
+
+/// IHDR must be the first chunk
+fn png_size(data: &[u8]) -> Result<[u32; 2], SniffError> {
+    let invalid = |message: &str| SniffError::Invalid("PNG", message.to_string());
+    let header = data.get(8..24).ok_or_else(|| invalid("truncated before IHDR"))?;
+    if &header[4..8] != b"IHDR" {
+        return Err(invalid("first chunk is not IHDR"));
+    }
+    let be = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap());
+    Ok([be(&header[8..12]), be(&header[12..16])])
+}
+
+/// Walk the marker segments up to the first start-of-frame
+fn jpeg_size(data: &[u8]) -> Result<[u32; 2], SniffError> {
+    let invalid = |message: String| SniffError::Invalid("JPEG", message);
+    let truncated = || invalid("truncated before the frame header".to_string());
+    let mut pos = 2;
+    loop {
+        if *data.get(pos).ok_or_else(truncated)? != 0xFF {
+            return Err(invalid(format!("expected a marker at byte {}", pos)));
+        }
+        // Any number of 0xFF fill bytes may precede the marker code
+        while data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *data.get(pos + 1).ok_or_else(truncated)?;
+        pos += 2;
+        match marker {
+            // Standalone markers carry no length
+            0x01 | 0xD0..=0xD7 => continue,
+            0xD9 | 0xDA => return Err(invalid("no frame header before the image data".to_string())),
+            _ => {}
+        }
+        let segment = data.get(pos..pos + 2).ok_or_else(truncated)?;
+        let length = u16::from_be_bytes([segment[0], segment[1]]) as usize;
+        if length < 2 {
+            return Err(invalid(format!("segment length {} at byte {}", length, pos)));
+        }
+        // SOF0-SOF15, minus DHT (C4), JPG (C8) and DAC (CC)
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let frame = data.get(pos + 2..pos + 7).ok_or_else(truncated)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]) as u32;
+            let width = u16::from_be_bytes([frame[3], frame[4]]) as u32;
+            return Ok([width, height]);
+        }
+        pos += length;
+    }
+}
+
+/// viewBox size, else the root's width/height; rounded to whole pixels
+fn svg_size(data: &[u8]) -> Result<[u32; 2], SniffError> {
+    let invalid = |message: String| SniffError::Invalid("SVG", message);
+    let text = std::str::from_utf8(data).map_err(|e| invalid(e.to_string()))?;
+    let tokens = svg::tokenize(text).map_err(|e| invalid(e.to_string()))?;
+    let attrs = tokens.iter()
+        .find_map(|token| match token {
+            Token::Start { name: "svg", attrs, .. } => Some(attrs),
+            _ => None,
+        })
+        .ok_or_else(|| invalid("no <svg> element".to_string()))?;
+    let attr = |key: &str| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.trim());
+
+    let size = match attr("viewBox") {
+        Some(view) => {
+            let numbers: Vec<f64> = view.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|n| !n.is_empty())
+                .map(|n| n.parse::<f64>().map_err(|_| invalid(format!("viewBox {:?}", view))))
+                .collect::<Result<_, _>>()?;
+            match numbers[..] {
+                [_, _, w, h] => [w, h],
+                _ => return Err(invalid(format!("viewBox {:?}", view))),
+            }
+        }
+        None => {
+            let length = |key: &str| {
+                let value = attr(key).ok_or_else(|| invalid("no viewBox or width/height".to_string()))?;
+                value.trim_end_matches("px").parse::<f64>()
+                    .map_err(|_| invalid(format!("{} {:?} is not a pixel length", key, value)))
+            };
+            [length("width")?, length("height")?]
+        }
+    };
+    let pixels = |v: f64| {
+        if v.is_finite() && v > 0.0 && v.round() <= u32::MAX as f64 {
+            Ok(v.round() as u32)
+        } else {
+            Err(invalid(format!("size {} x {}", size[0], size[1])))
+        }
+    };
+    Ok([pixels(size[0])?, pixels(size[1])?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend_from_slice(&13u32.to_be_bytes());
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 2, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn test_png_reads_ihdr() {
+        assert_eq!(sniff(&png(300, 200)).unwrap(), SniffedSource { format: SourceFormat::Png, width: 300, height: 200 });
+        assert!(matches!(sniff(&png(300, 200)[..20]), Err(SniffError::Invalid("PNG", _))));
+        assert!(matches!(sniff(&png(0, 200)), Err(SniffError::Invalid("PNG", _))));
+    }
+
+    #[test]
+    fn test_jpeg_skips_to_the_frame_header() {
+        let mut data = vec![0xFF, 0xD8];
+        // APP0 with padding, then fill bytes before SOF2
+        data.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x06, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0xFF, 0xFF, 0xC2, 0x00, 0x0B, 8, 0x01, 0x2C, 0x02, 0x58, 1, 1, 0x11, 0]);
+        assert_eq!(sniff(&data).unwrap(), SniffedSource { format: SourceFormat::Jpeg, width: 600, height: 300 });
+
+        // DHT (C4) is not a frame header
+        let dht = [0xFF, 0xD8, 0xFF, 0xC4, 0x00, 0x02, 0xFF, 0xDA, 0x00, 0x02];
+        assert!(matches!(sniff(&dht), Err(SniffError::Invalid("JPEG", m)) if m.contains("no frame header")));
+        assert!(matches!(sniff(&data[..12]), Err(SniffError::Invalid("JPEG", m)) if m.contains("truncated")));
+    }
+
+    #[test]
+    fn test_svg_prefers_the_viewbox() {
+        let svg = br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" width="10" height="10" viewBox="0,0 640.4 480"/>"#;
+        assert_eq!(sniff(svg).unwrap(), SniffedSource { format: SourceFormat::Svg, width: 640, height: 480 });
+        let sized = br#"<svg xmlns="http://www.w3.org/2000/svg" width="64px" height="32"></svg>"#;
+        assert_eq!(sniff(sized).unwrap().width, 64);
+        let physical = br#"<svg xmlns="http://www.w3.org/2000/svg" width="2in" height="1in"></svg>"#;
+        assert!(matches!(sniff(physical), Err(SniffError::Invalid("SVG", m)) if m.contains("pixel length")));
+    }
+
+    #[test]
+    fn test_anything_else_is_unrecognized() {
+        assert_eq!(sniff(b"GIF89a"), Err(SniffError::Unrecognized));
+        assert_eq!(sniff(b""), Err(SniffError::Unrecognized));
+    }
+}
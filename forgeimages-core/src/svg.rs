@@ -0,0 +1,472 @@
+//! SVG Master Handling
+//!
+//! Law 1 (SVG Is Truth): the master is edited surgically, never re-serialized.
+//! A small tokenizer records byte spans so slot substitution and text
+//! outlining replace exactly the affected elements and copy everything else
+//! through untouched.
+//!
+//! Text slot convention: any element carrying `data-fi-slot="<id>"` is a slot.
+//! Its text content is replaced from `CompileRequest.params[<id>]`, and at
+//! render time `<text>` slots are converted to `<path>` outlines using the
+//! template's bundled font.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::font::Font;
+
+pub const SLOT_ATTRIBUTE: &str = "data-fi-slot";
+
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+
+/// Filter id `bake_grayscale` adds to the master
+pub const GRAYSCALE_FILTER_ID: &str = "fi-grayscale";
+
+/// Attributes that only make sense on `<text>` and are dropped when outlining
+const TEXT_ONLY_ATTRIBUTES: [&str; 5] = ["x", "y", "font-size", "font-family", "text-anchor"];
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Invalid SVG at byte {offset}: {message}")]
+pub struct SvgError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl SvgError {
+    pub(crate) fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self { offset, message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    Start {
+        name: &'a str,
+        /// Raw (still escaped) attribute values in source order
+        attrs: Vec<(&'a str, &'a str)>,
+        self_closing: bool,
+        span: Range<usize>,
+    },
+    End { name: &'a str, span: Range<usize> },
+    Text { span: Range<usize> },
+    Comment { span: Range<usize> },
+    /// XML declaration, processing instruction, DOCTYPE or CDATA
+    Other { span: Range<usize> },
+}
+
+impl Token<'_> {
+    pub(crate) fn span(&self) -> Range<usize> {
+        match self {
+            Token::Start { span, .. }
+            | Token::End { span, .. }
+            | Token::Text { span }
+            | Token::Comment { span }
+            | Token::Other { span } => span.clone(),
+        }
+    }
+}
+
+/// Cheap sniff: does this look like an SVG document?
+pub fn looks_like_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(1024)];
+    String::from_utf8_lossy(head).contains("<svg")
+}
+
+pub(crate) fn tokenize(svg: &str) -> Result<Vec<Token<'_>>, SvgError> {
+    let bytes = svg.as_bytes();
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    let find = |from: usize, needle: &str| svg[from..].find(needle).map(|i| from + i);
+
+    while pos < bytes.len() {
+        if bytes[pos] != b'<' {
+            let end = find(pos, "<").unwrap_or(bytes.len());
+            tokens.push(Token::Text { span: pos..end });
+            pos = end;
+            continue;
+        }
+
+        let rest = &svg[pos..];
+        if rest.starts_with("<!--") {
+            let end = find(pos + 4, "-->").ok_or_else(|| SvgError::new(pos, "unterminated comment"))? + 3;
+            tokens.push(Token::Comment { span: pos..end });
+            pos = end;
+        } else if rest.starts_with("<![CDATA[") {
+            let end = find(pos, "]]>").ok_or_else(|| SvgError::new(pos, "unterminated CDATA"))? + 3;
+            tokens.push(Token::Other { span: pos..end });
+            pos = end;
+        } else if rest.starts_with("<?") {
+            let end = find(pos, "?>").ok_or_else(|| SvgError::new(pos, "unterminated declaration"))? + 2;
+            tokens.push(Token::Other { span: pos..end });
+            pos = end;
+        } else if rest.starts_with("<!") {
+            let end = find(pos, ">").ok_or_else(|| SvgError::new(pos, "unterminated declaration"))? + 1;
+            tokens.push(Token::Other { span: pos..end });
+            pos = end;
+        } else if rest.starts_with("</") {
+            let end = find(pos, ">").ok_or_else(|| SvgError::new(pos, "unterminated end tag"))? + 1;
+            let name = svg[pos + 2..end - 1].trim();
+            tokens.push(Token::End { name, span: pos..end });
+            pos = end;
+        } else {
+            let (token, end) = start_tag(svg, pos)?;
+            tokens.push(token);
+            pos = end;
+        }
+    }
+    Ok(tokens)
+}
+
+fn start_tag(svg: &str, start: usize) -> Result<(Token<'_>, usize), SvgError> {
+    let bytes = svg.as_bytes();
+    let is_name = |b: u8| !b.is_ascii_whitespace() && b != b'>' && b != b'/' && b != b'=';
+    let skip_ws = |mut p: usize| {
+        while p < bytes.len() && bytes[p].is_ascii_whitespace() {
+            p += 1;
+        }
+        p
+    };
+
+    let mut pos = start + 1;
+    while pos < bytes.len() && is_name(bytes[pos]) {
+        pos += 1;
+    }
+    let name = &svg[start + 1..pos];
+    if name.is_empty() {
+        return Err(SvgError::new(start, "empty tag name"));
+    }
+
+    let mut attrs = vec![];
+    loop {
+        pos = skip_ws(pos);
+        match bytes.get(pos) {
+            None => return Err(SvgError::new(start, "unterminated start tag")),
+            Some(b'>') => {
+                let token = Token::Start { name, attrs, self_closing: false, span: start..pos + 1 };
+                return Ok((token, pos + 1));
+            }
+            Some(b'/') if bytes.get(pos + 1) == Some(&b'>') => {
+                let token = Token::Start { name, attrs, self_closing: true, span: start..pos + 2 };
+                return Ok((token, pos + 2));
+            }
+            Some(_) => {}
+        }
+
+        let name_start = pos;
+        while pos < bytes.len() && is_name(bytes[pos]) {
+            pos += 1;
+        }
+        if pos == name_start {
+            return Err(SvgError::new(pos, "malformed attribute"));
+        }
+        let attr_name = &svg[name_start..pos];
+        pos = skip_ws(pos);
+        if bytes.get(pos) != Some(&b'=') {
+            return Err(SvgError::new(pos, format!("attribute '{}' has no value", attr_name)));
+        }
+        pos = skip_ws(pos + 1);
+        let quote = match bytes.get(pos) {
+            Some(&q) if q == b'"' || q == b'\'' => q,
+            _ => return Err(SvgError::new(pos, "attribute value must be quoted")),
+        };
+        let value_start = pos + 1;
+        let value_end = svg[value_start..].find(quote as char)
+            .map(|i| value_start + i)
+            .ok_or_else(|| SvgError::new(pos, "unterminated attribute value"))?;
+        attrs.push((attr_name, &svg[value_start..value_end]));
+        pos = value_end + 1;
+    }
+}
+
+/// Index of the end token matching the start token at `start_index`
+pub(crate) fn matching_end(tokens: &[Token<'_>], start_index: usize) -> Result<usize, SvgError> {
+    let Token::Start { name, span, .. } = &tokens[start_index] else {
+        unreachable!("matching_end called on non-start token");
+    };
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(start_index + 1) {
+        match token {
+            Token::Start { name: n, self_closing: false, .. } if n == name => depth += 1,
+            Token::End { name: n, .. } if n == name => {
+                if depth == 0 {
+                    return Ok(i);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    Err(SvgError::new(span.start, format!("element <{}> is never closed", name)))
+}
+
+fn slot_of<'a>(attrs: &[(&'a str, &'a str)]) -> Option<&'a str> {
+    attrs.iter().find(|(k, _)| *k == SLOT_ATTRIBUTE).map(|(_, v)| *v)
+}
+
+/// All slot ids declared in the master
+pub fn slot_ids(svg: &str) -> Result<BTreeSet<String>, SvgError> {
+    Ok(tokenize(svg)?
+        .iter()
+        .filter_map(|t| match t {
+            Token::Start { attrs, .. } => slot_of(attrs).map(str::to_string),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Paint properties whose `#RGB` / `#RRGGBB` values `paint_colors` collects
+const PAINT_PROPERTIES: [&str; 5] = ["fill", "stroke", "stop-color", "flood-color", "color"];
+
+/// Hex colors painted by the master: paint attributes and their `style`
+/// declarations. Named colors, `url(...)` paints and CSS in `<style>`
+/// elements are not inspected.
+pub fn paint_colors(svg: &str) -> Result<BTreeSet<[u8; 3]>, SvgError> {
+    let mut colors = BTreeSet::new();
+    for token in tokenize(svg)? {
+        let Token::Start { attrs, .. } = token else { continue };
+        for (name, value) in attrs {
+            let values: Vec<&str> = if name == "style" {
+                value.split(';')
+                    .filter_map(|decl| decl.split_once(':'))
+                    .filter(|(property, _)| PAINT_PROPERTIES.contains(&property.trim()))
+                    .map(|(_, value)| value)
+                    .collect()
+            } else if PAINT_PROPERTIES.contains(&name) {
+                vec![value]
+            } else {
+                vec![]
+            };
+            colors.extend(values.into_iter().filter_map(|v| parse_hex_paint(v.trim())));
+        }
+    }
+    Ok(colors)
+}
+
+fn parse_hex_paint(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+    match hex.len() {
+        3 => Some([digit(0)? * 17, digit(1)? * 17, digit(2)? * 17]),
+        6 => Some([0, 2, 4].map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0))),
+        _ => None,
+    }
+}
+
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Copy `svg`, substituting the given byte ranges (must be sorted, disjoint)
+fn splice(svg: &str, edits: Vec<(Range<usize>, String)>) -> String {
+    let mut out = String::with_capacity(svg.len());
+    let mut pos = 0;
+    for (range, replacement) in edits {
+        out.push_str(&svg[pos..range.start]);
+        out.push_str(&replacement);
+        pos = range.end;
+    }
+    out.push_str(&svg[pos..]);
+    out
+}
+
+/// Replace the text content of every slot element named in `values`
+pub fn fill_slots(svg: &str, values: &BTreeMap<String, String>) -> Result<String, SvgError> {
+    let tokens = tokenize(svg)?;
+    let mut edits = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Start { name, attrs, self_closing, span } = token else { continue };
+        let Some(value) = slot_of(attrs).and_then(|slot| values.get(slot)) else { continue };
+        let text = escape_text(value);
+        if *self_closing {
+            let open = svg[span.start..span.end - 2].trim_end();
+            edits.push((span.clone(), format!("{}>{}</{}>", open, text, name)));
+        } else {
+            let end = matching_end(&tokens, i)?;
+            edits.push((span.end..tokens[end].span().start, text));
+        }
+    }
+    // Nested slots would produce overlapping edits; keep the outermost
+    edits.sort_by_key(|(r, _)| r.start);
+    let mut disjoint: Vec<(Range<usize>, String)> = vec![];
+    for edit in edits {
+        if disjoint.last().is_none_or(|(r, _)| edit.0.start >= r.end) {
+            disjoint.push(edit);
+        }
+    }
+    Ok(splice(svg, disjoint))
+}
+
+fn parse_length(value: Option<&str>, default: f64) -> f64 {
+    value
+        .map(|v| v.trim().trim_end_matches("px"))
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| v.is_finite())
+        .unwrap_or(default)
+}
+
+/// Convert every `<text>` slot into a `<path>` outline using `font`
+pub fn outline_slots(svg: &str, font: &Font) -> Result<String, SvgError> {
+    let tokens = tokenize(svg)?;
+    let mut edits = vec![];
+    let mut skip_until = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Start { name, attrs, self_closing, span } = token else { continue };
+        if span.start < skip_until || *name != "text" || slot_of(attrs).is_none() {
+            continue;
+        }
+        let (content, end) = if *self_closing {
+            (String::new(), span.end)
+        } else {
+            let end_index = matching_end(&tokens, i)?;
+            let content: String = tokens[i + 1..end_index].iter()
+                .filter_map(|t| match t {
+                    Token::Text { span } => Some(unescape(&svg[span.clone()])),
+                    _ => None,
+                })
+                .collect();
+            (content, tokens[end_index].span().end)
+        };
+
+        let attr = |key: &str| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        let size = parse_length(attr("font-size"), DEFAULT_FONT_SIZE);
+        let mut x = parse_length(attr("x"), 0.0);
+        let y = parse_length(attr("y"), 0.0);
+        let width = font.text_advance(&content) as f64 * size / font.units_per_em() as f64;
+        match attr("text-anchor") {
+            Some("middle") => x -= width / 2.0,
+            Some("end") => x -= width,
+            _ => {}
+        }
+
+        let d = font.text_to_path(&content, size, x, y)
+            .map_err(|e| SvgError::new(span.start, e.to_string()))?;
+        let mut path = String::from("<path");
+        for (k, v) in attrs.iter().filter(|(k, _)| !TEXT_ONLY_ATTRIBUTES.contains(k)) {
+            path.push_str(&format!(" {}=\"{}\"", k, v));
+        }
+        path.push_str(&format!(" d=\"{}\"/>", d));
+        edits.push((span.start..end, path));
+        skip_until = end;
+    }
+    Ok(splice(svg, edits))
+}
+
+/// Wrap the root element's content in a Rec. 709 luma filter (the
+/// `filter: grayscale(1)` equivalent, computed on sRGB values). Colors in
+/// the master are untouched; viewers apply the filter.
+pub fn bake_grayscale(svg: &str) -> Result<String, SvgError> {
+    let tokens = tokenize(svg)?;
+    let root = tokens.iter()
+        .position(|t| matches!(t, Token::Start { name: "svg", .. }))
+        .ok_or_else(|| SvgError::new(0, "no <svg> root element"))?;
+    let Token::Start { self_closing: false, span, .. } = &tokens[root] else {
+        return Ok(svg.to_string());
+    };
+    let end = tokens[matching_end(&tokens, root)?].span();
+    let row = "0.2126 0.7152 0.0722 0 0";
+    let open = format!(
+        r#"<defs><filter id="{}" color-interpolation-filters="sRGB"><feColorMatrix type="matrix" values="{} {} {} 0 0 0 1 0"/></filter></defs><g filter="url(#{})">"#,
+        GRAYSCALE_FILTER_ID, row, row, row, GRAYSCALE_FILTER_ID,
+    );
+    Ok(splice(svg, vec![(span.end..span.end, open), (end.start..end.start, "</g>".to_string())]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = r#"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 50">
+  <!-- cover -->
+  <text data-fi-slot="title" x="10" y="40" font-size="24">Title</text>
+  <text data-fi-slot='author' x="10" y="48"/>
+</svg>"#;
+
+    #[test]
+    fn test_bake_grayscale_wraps_root_content() {
+        let baked = bake_grayscale(MASTER).unwrap();
+        assert!(baked.starts_with("<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 50\"><defs>"));
+        assert!(baked.contains(r#"values="0.2126 0.7152 0.0722 0 0 0.2126 0.7152 0.0722 0 0 0.2126 0.7152 0.0722 0 0 0 0 0 1 0""#));
+        assert!(baked.ends_with("\n</g></svg>"));
+        // Still well-formed, slots intact
+        assert_eq!(slot_ids(&baked).unwrap(), slot_ids(MASTER).unwrap());
+        assert!(bake_grayscale("<p/>").is_err());
+    }
+
+    #[test]
+    fn test_paint_colors() {
+        let svg = r##"<svg><rect fill="#E4002B" stroke="#fff"/><path style="fill: #00205B; opacity: 0.5" fill="red"/>
+            <stop stop-color="#e4002b"/><g fill="url(#grad)" data-color="#123456"/></svg>"##;
+        let colors: Vec<_> = paint_colors(svg).unwrap().into_iter().collect();
+        assert_eq!(colors, vec![[0, 32, 91], [228, 0, 43], [255, 255, 255]]);
+    }
+
+    #[test]
+    fn test_slot_ids() {
+        let ids = slot_ids(MASTER).unwrap();
+        assert_eq!(ids.into_iter().collect::<Vec<_>>(), vec!["author", "title"]);
+    }
+
+    #[test]
+    fn test_fill_slots_escapes_and_preserves_rest() {
+        let values = BTreeMap::from([
+            ("title".to_string(), "Rock & <Roll>".to_string()),
+            ("author".to_string(), "Ada".to_string()),
+        ]);
+        let filled = fill_slots(MASTER, &values).unwrap();
+        assert!(filled.contains(r#"font-size="24">Rock &amp; &lt;Roll&gt;</text>"#));
+        assert!(filled.contains(r#"<text data-fi-slot='author' x="10" y="48">Ada</text>"#));
+        assert!(filled.starts_with(r#"<?xml version="1.0"?>"#));
+        assert!(filled.contains("<!-- cover -->"));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a &amp; b &#65;&#x42; &bogus;"), "a & b AB &bogus;");
+    }
+
+    #[test]
+    fn test_unclosed_element_is_error() {
+        assert!(fill_slots(r#"<svg><text data-fi-slot="t">x</svg>"#,
+            &BTreeMap::from([("t".to_string(), "y".to_string())])).is_err());
+    }
+}
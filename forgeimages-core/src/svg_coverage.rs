@@ -0,0 +1,693 @@
+//! SVG Coverage - Analysis Rasterizer
+//!
+//! Validation needs to know where a master puts ink, not what it looks
+//! like, so this rasterizer produces a coverage mask (0-255 per pixel) at
+//! an analysis size. It fills geometry only:
+//! - `rect`, `circle`, `ellipse`, `polygon`, `polyline`, `path` (every
+//!   command; curves and arcs flattened) and `image` bounds
+//! - `<text>` not yet outlined as a box of 0.6 em per character
+//! - `transform`, `opacity`, `fill-opacity`, `fill-rule`, `fill="none"` and
+//!   `display="none"`, as attributes or `style` declarations
+//!
+//! Strokes, clipping, masks, `<use>` and gradients are not modelled.
+//! Subtrees marked `data-fi-bleed` (artwork meant to run off the edge) and
+//! shapes covering the whole canvas (backgrounds) are not content.
+
+use crate::svg::{tokenize, unescape, SvgError, Token};
+
+/// Marks artwork that runs into the bleed on purpose
+pub const BLEED_ATTRIBUTE: &str = "data-fi-bleed";
+
+/// Elements whose content is never painted directly
+const NON_RENDERED: [&str; 13] = [
+    "defs", "clipPath", "mask", "symbol", "pattern", "marker", "linearGradient",
+    "radialGradient", "filter", "style", "title", "desc", "metadata",
+];
+
+/// Sub-scanlines per pixel row
+const SUBSAMPLES: u32 = 4;
+/// Line segments per flattened curve
+const CURVE_SEGMENTS: usize = 16;
+
+/// Per-pixel coverage, rows top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn get(&self, x: u32, y: u32) -> u8 {
+        self.values[(y * self.width + x) as usize]
+    }
+
+    /// Percentage of each edge band `band` pixels deep (left, top, right,
+    /// bottom) whose coverage is above `threshold`; corners count for both
+    /// edges they touch
+    pub fn edge_intrusion(&self, band: u32, threshold: u8) -> [f64; 4] {
+        let band_x = band.min(self.width);
+        let band_y = band.min(self.height);
+        let bands = [
+            (0..band_x, 0..self.height),
+            (0..self.width, 0..band_y),
+            (self.width - band_x..self.width, 0..self.height),
+            (0..self.width, self.height - band_y..self.height),
+        ];
+        bands.map(|(xs, ys)| {
+            let total = xs.len() * ys.len();
+            if total == 0 {
+                return 0.0;
+            }
+            let covered = ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .filter(|&(x, y)| self.get(x, y) > threshold)
+                .count();
+            covered as f64 * 100.0 / total as f64
+        })
+    }
+}
+
+/// Coverage of `svg`'s fills with its viewport scaled to `size`
+pub fn rasterize(svg: &str, size: [u32; 2]) -> Result<Coverage, SvgError> {
+    let tokens = tokenize(svg)?;
+    let [width, height] = size;
+    let mut canvas = vec![0f32; width as usize * height as usize];
+    let mut root = None;
+    // Transform, opacity, inherited fill state per open element
+    let mut stack: Vec<State> = vec![];
+    let mut skip_depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Start { name, attrs, self_closing, .. } => {
+                if skip_depth > 0 || NON_RENDERED.contains(name) || property(attrs, "display") == Some("none")
+                    || attrs.iter().any(|(k, _)| *k == BLEED_ATTRIBUTE)
+                {
+                    if !self_closing {
+                        skip_depth += 1;
+                    }
+                    continue;
+                }
+                let parent = match (stack.last(), *name) {
+                    (Some(parent), _) => *parent,
+                    (None, "svg") => {
+                        let state = root_state(attrs, size);
+                        root = Some(state);
+                        state
+                    }
+                    (None, _) => continue,
+                };
+                let state = parent.child(attrs);
+                if !self_closing {
+                    stack.push(state);
+                }
+                let chars = || text_length(svg, &tokens[i + 1..]);
+                if let Some(polygons) = shape(name, attrs, chars) {
+                    let device: Vec<Vec<Point>> = polygons.into_iter()
+                        .map(|poly| poly.into_iter().map(|p| state.transform.apply(p)).collect())
+                        .collect();
+                    let alpha = state.opacity * state.fill_opacity;
+                    if state.filled && alpha > 0.0 && !covers_canvas(&device, size) {
+                        fill(&mut canvas, size, &device, state.even_odd, alpha);
+                    }
+                }
+            }
+            Token::End { .. } if skip_depth > 0 => skip_depth -= 1,
+            Token::End { .. } => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    if root.is_none() {
+        return Err(SvgError::new(0, "no <svg> root element"));
+    }
+    Ok(Coverage {
+        width,
+        height,
+        values: canvas.into_iter().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8).collect(),
+    })
+}
+
+type Point = (f64, f64);
+
+/// Affine `[a b c d e f]`: `x' = a x + c y + e`, `y' = b x + d y + f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transform([f64; 6]);
+
+impl Transform {
+    const IDENTITY: Transform = Transform([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn apply(&self, (x, y): Point) -> Point {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// `self` applied after `inner`
+    fn then(&self, inner: &Transform) -> Transform {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = inner.0;
+        Transform([
+            a * a2 + c * b2,
+            b * a2 + d * b2,
+            a * c2 + c * d2,
+            b * c2 + d * d2,
+            a * e2 + c * f2 + e,
+            b * e2 + d * f2 + f,
+        ])
+    }
+
+    /// A `transform` attribute; unknown functions are ignored
+    fn parse(text: &str) -> Transform {
+        let mut result = Transform::IDENTITY;
+        for item in text.split(')') {
+            let Some((name, args)) = item.split_once('(') else { continue };
+            let v = numbers(args);
+            let arg = |i: usize, default: f64| v.get(i).copied().unwrap_or(default);
+            let next = match name.trim().trim_start_matches(',').trim() {
+                "matrix" if v.len() == 6 => Transform([v[0], v[1], v[2], v[3], v[4], v[5]]),
+                "translate" => Transform([1.0, 0.0, 0.0, 1.0, arg(0, 0.0), arg(1, 0.0)]),
+                "scale" => Transform([arg(0, 1.0), 0.0, 0.0, arg(1, arg(0, 1.0)), 0.0, 0.0]),
+                "rotate" => {
+                    let (sin, cos) = arg(0, 0.0).to_radians().sin_cos();
+                    let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+                    Transform([1.0, 0.0, 0.0, 1.0, cx, cy])
+                        .then(&Transform([cos, sin, -sin, cos, 0.0, 0.0]))
+                        .then(&Transform([1.0, 0.0, 0.0, 1.0, -cx, -cy]))
+                }
+                "skewX" => Transform([1.0, 0.0, arg(0, 0.0).to_radians().tan(), 1.0, 0.0, 0.0]),
+                "skewY" => Transform([1.0, arg(0, 0.0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+                _ => continue,
+            };
+            result = result.then(&next);
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct State {
+    transform: Transform,
+    opacity: f32,
+    fill_opacity: f32,
+    filled: bool,
+    even_odd: bool,
+}
+
+impl State {
+    fn child(&self, attrs: &[(&str, &str)]) -> State {
+        let attr = |key: &str| property(attrs, key);
+        let transform = match attr("transform") {
+            Some(text) => self.transform.then(&Transform::parse(text)),
+            None => self.transform,
+        };
+        let unit = |key: &str| attr(key).and_then(|v| v.trim().parse::<f32>().ok()).map(|v| v.clamp(0.0, 1.0));
+        State {
+            transform,
+            opacity: self.opacity * unit("opacity").unwrap_or(1.0),
+            fill_opacity: unit("fill-opacity").unwrap_or(self.fill_opacity),
+            filled: match attr("fill").map(str::trim) {
+                Some("none" | "transparent") => false,
+                Some(_) => true,
+                None => self.filled,
+            },
+            even_odd: attr("fill-rule").map_or(self.even_odd, |rule| rule.trim() == "evenodd"),
+        }
+    }
+}
+
+/// Maps the root's viewBox (else its width/height) onto `size`
+fn root_state(attrs: &[(&str, &str)], size: [u32; 2]) -> State {
+    let attr = |key: &str| property(attrs, key);
+    let view = attr("viewBox").map(numbers).filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0);
+    let [min_x, min_y, w, h] = match view {
+        Some(v) => [v[0], v[1], v[2], v[3]],
+        None => {
+            let length = |key: &str, default: u32| {
+                attr(key).and_then(|v| v.trim().trim_end_matches("px").parse::<f64>().ok())
+                    .filter(|v| *v > 0.0)
+                    .unwrap_or(default as f64)
+            };
+            [0.0, 0.0, length("width", size[0]), length("height", size[1])]
+        }
+    };
+    let (sx, sy) = (size[0] as f64 / w, size[1] as f64 / h);
+    State {
+        transform: Transform([sx, 0.0, 0.0, sy, -min_x * sx, -min_y * sy]),
+        opacity: 1.0,
+        fill_opacity: 1.0,
+        filled: true,
+        even_odd: false,
+    }
+}
+
+/// `style` declaration, else attribute
+fn property<'a>(attrs: &[(&'a str, &'a str)], key: &str) -> Option<&'a str> {
+    let styled = attrs.iter()
+        .filter(|(k, _)| *k == "style")
+        .flat_map(|(_, style)| style.split(';'))
+        .filter_map(|decl| decl.split_once(':'))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim());
+    styled.or_else(|| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v))
+}
+
+/// Numbers in an attribute, separated by whitespace, commas or signs
+fn numbers(text: &str) -> Vec<f64> {
+    let mut scanner = Scanner { text: text.as_bytes(), pos: 0 };
+    std::iter::from_fn(|| scanner.number()).collect()
+}
+
+/// Characters of text content up to the end of the enclosing element
+fn text_length(svg: &str, rest: &[Token<'_>]) -> usize {
+    let mut depth = 0usize;
+    let mut chars = 0;
+    for token in rest {
+        match token {
+            Token::Start { self_closing: false, .. } => depth += 1,
+            Token::End { .. } if depth == 0 => break,
+            Token::End { .. } => depth -= 1,
+            Token::Text { span } => chars += unescape(&svg[span.clone()]).trim().chars().count(),
+            _ => {}
+        }
+    }
+    chars
+}
+
+fn shape(name: &str, attrs: &[(&str, &str)], text_chars: impl Fn() -> usize) -> Option<Vec<Vec<Point>>> {
+    let attr = |key: &str| property(attrs, key);
+    let num = |key: &str| attr(key).and_then(|v| v.trim().trim_end_matches("px").parse::<f64>().ok()).unwrap_or(0.0);
+    match name {
+        "rect" | "image" => {
+            let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+            (w > 0.0 && h > 0.0).then(|| vec![vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)]])
+        }
+        "circle" => Some(vec![ellipse(num("cx"), num("cy"), num("r"), num("r"))]),
+        "ellipse" => Some(vec![ellipse(num("cx"), num("cy"), num("rx"), num("ry"))]),
+        "polygon" | "polyline" => {
+            let v = numbers(attr("points")?);
+            Some(vec![v.chunks_exact(2).map(|p| (p[0], p[1])).collect()])
+        }
+        "path" => Some(path(attr("d")?)),
+        "text" => {
+            // Outlined slots are paths by now; anything else is estimated
+            let size = attr("font-size").and_then(|v| v.trim().trim_end_matches("px").parse().ok()).unwrap_or(16.0);
+            let (x, y) = (num("x"), num("y"));
+            let w = text_chars() as f64 * 0.6 * size;
+            let x = match attr("text-anchor") {
+                Some("middle") => x - w / 2.0,
+                Some("end") => x - w,
+                _ => x,
+            };
+            Some(vec![vec![(x, y - 0.8 * size), (x + w, y - 0.8 * size), (x + w, y + 0.2 * size), (x, y + 0.2 * size)]])
+        }
+        _ => None,
+    }
+}
+
+fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<Point> {
+    if rx <= 0.0 || ry <= 0.0 {
+        return vec![];
+    }
+    (0..4 * CURVE_SEGMENTS)
+        .map(|i| {
+            let t = i as f64 * std::f64::consts::TAU / (4 * CURVE_SEGMENTS) as f64;
+            (cx + rx * t.cos(), cy + ry * t.sin())
+        })
+        .collect()
+}
+
+struct Scanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn skip_separators(&mut self) {
+        while self.pos < self.text.len() && (self.text[self.pos].is_ascii_whitespace() || self.text[self.pos] == b',') {
+            self.pos += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let c = *self.text.get(self.pos)?;
+        c.is_ascii_alphabetic().then(|| {
+            self.pos += 1;
+            c
+        })
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut end = start;
+        let bytes = self.text;
+        if end < bytes.len() && matches!(bytes[end], b'+' | b'-') {
+            end += 1;
+        }
+        let mut dot = false;
+        while end < bytes.len() && (bytes[end].is_ascii_digit() || (bytes[end] == b'.' && !dot)) {
+            dot |= bytes[end] == b'.';
+            end += 1;
+        }
+        if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+            let mut exp = end + 1;
+            if exp < bytes.len() && matches!(bytes[exp], b'+' | b'-') {
+                exp += 1;
+            }
+            if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+                end = exp;
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+            }
+        }
+        let value = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+        self.pos = end;
+        Some(value)
+    }
+
+    /// Arc flags may be written without separators (`a1 1 0 011 1`)
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.text.get(self.pos)? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(flag)
+    }
+}
+
+/// Subpaths of a path's `d`, curves and arcs flattened; parsing stops at
+/// the first error, keeping what came before (as SVG renderers do)
+fn path(d: &str) -> Vec<Vec<Point>> {
+    let mut s = Scanner { text: d.as_bytes(), pos: 0 };
+    let mut subpaths: Vec<Vec<Point>> = vec![];
+    let mut current: Vec<Point> = vec![];
+    let (mut pen, mut start) = ((0.0, 0.0), (0.0, 0.0));
+    // Reflected control point for S/T
+    let mut last_control: Option<(u8, Point)> = None;
+    let mut command = None;
+
+    loop {
+        let explicit = s.command();
+        let cmd = match explicit.or(command) {
+            Some(c) => c,
+            None => break,
+        };
+        // Repeated implicit M is L
+        command = Some(match cmd {
+            b'M' => b'L',
+            b'm' => b'l',
+            c => c,
+        });
+        let relative = cmd.is_ascii_lowercase();
+        let origin = if relative { pen } else { (0.0, 0.0) };
+        let point = |s: &mut Scanner<'_>| -> Option<Point> {
+            let x = s.number()?;
+            let y = s.number()?;
+            Some((origin.0 + x, origin.1 + y))
+        };
+        let before = s.pos;
+        let mut control = None;
+        let ok = match cmd.to_ascii_uppercase() {
+            b'M' => point(&mut s).map(|p| {
+                if current.len() > 1 {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                current = vec![p];
+                pen = p;
+                start = p;
+            }),
+            b'L' => point(&mut s).map(|p| {
+                current.push(p);
+                pen = p;
+            }),
+            b'H' => s.number().map(|x| {
+                pen = (if relative { pen.0 + x } else { x }, pen.1);
+                current.push(pen);
+            }),
+            b'V' => s.number().map(|y| {
+                pen = (pen.0, if relative { pen.1 + y } else { y });
+                current.push(pen);
+            }),
+            b'C' | b'S' => {
+                let c1 = if cmd.eq_ignore_ascii_case(&b'C') {
+                    point(&mut s)
+                } else {
+                    Some(reflect(last_control, b'C', pen))
+                };
+                c1.and_then(|c1| Some((c1, point(&mut s)?, point(&mut s)?))).map(|(c1, c2, p)| {
+                    current.extend(cubic(pen, c1, c2, p));
+                    control = Some((b'C', c2));
+                    pen = p;
+                })
+            }
+            b'Q' | b'T' => {
+                let c = if cmd.eq_ignore_ascii_case(&b'Q') {
+                    point(&mut s)
+                } else {
+                    Some(reflect(last_control, b'Q', pen))
+                };
+                c.and_then(|c| Some((c, point(&mut s)?))).map(|(c, p)| {
+                    current.extend(quadratic(pen, c, p));
+                    control = Some((b'Q', c));
+                    pen = p;
+                })
+            }
+            b'A' => arc_args(&mut s).map(|(rx, ry, angle, large, sweep, (x, y))| {
+                let p = (origin.0 + x, origin.1 + y);
+                current.extend(arc(pen, rx, ry, angle, large, sweep, p));
+                pen = p;
+            }),
+            b'Z' => {
+                if current.len() > 1 {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                current = vec![start];
+                pen = start;
+                command = None;
+                Some(())
+            }
+            _ => None,
+        };
+        last_control = control;
+        if ok.is_none() || (explicit.is_none() && s.pos == before) {
+            break;
+        }
+    }
+    if current.len() > 1 {
+        subpaths.push(current);
+    }
+    subpaths
+}
+
+/// `rx ry angle large-arc sweep x y`
+fn arc_args(s: &mut Scanner<'_>) -> Option<(f64, f64, f64, bool, bool, Point)> {
+    let (rx, ry, angle) = (s.number()?, s.number()?, s.number()?);
+    let (large, sweep) = (s.flag()?, s.flag()?);
+    Some((rx, ry, angle, large, sweep, (s.number()?, s.number()?)))
+}
+
+fn reflect(last: Option<(u8, Point)>, kind: u8, pen: Point) -> Point {
+    match last {
+        Some((k, c)) if k == kind => (2.0 * pen.0 - c.0, 2.0 * pen.1 - c.1),
+        _ => pen,
+    }
+}
+
+fn cubic(p0: Point, c1: Point, c2: Point, p: Point) -> Vec<Point> {
+    (1..=CURVE_SEGMENTS)
+        .map(|i| {
+            let t = i as f64 / CURVE_SEGMENTS as f64;
+            let u = 1.0 - t;
+            let w = [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t];
+            (
+                w[0] * p0.0 + w[1] * c1.0 + w[2] * c2.0 + w[3] * p.0,
+                w[0] * p0.1 + w[1] * c1.1 + w[2] * c2.1 + w[3] * p.1,
+            )
+        })
+        .collect()
+}
+
+fn quadratic(p0: Point, c: Point, p: Point) -> Vec<Point> {
+    (1..=CURVE_SEGMENTS)
+        .map(|i| {
+            let t = i as f64 / CURVE_SEGMENTS as f64;
+            let u = 1.0 - t;
+            (
+                u * u * p0.0 + 2.0 * u * t * c.0 + t * t * p.0,
+                u * u * p0.1 + 2.0 * u * t * c.1 + t * t * p.1,
+            )
+        })
+        .collect()
+}
+
+/// Endpoint arc (SVG implementation notes, F.6.5), radii scaled up when
+/// too small to reach
+fn arc(p0: Point, rx: f64, ry: f64, angle: f64, large: bool, sweep: bool, p: Point) -> Vec<Point> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 || p0 == p {
+        return vec![p];
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (dx, dy) = ((p0.0 - p.0) / 2.0, (p0.1 - p.1) / 2.0);
+    let x1 = cos * dx + sin * dy;
+    let y1 = -sin * dx + cos * dy;
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = (rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1).max(0.0);
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut k = (numerator / denominator).sqrt();
+    if large == sweep {
+        k = -k;
+    }
+    let (cx1, cy1) = (k * rx * y1 / ry, -k * ry * x1 / rx);
+    let (cx, cy) = (cos * cx1 - sin * cy1 + (p0.0 + p.0) / 2.0, sin * cx1 + cos * cy1 + (p0.1 + p.1) / 2.0);
+    let angle_of = |ux: f64, uy: f64| uy.atan2(ux);
+    let theta = angle_of((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle_of((-x1 - cx1) / rx, (-y1 - cy1) / ry) - theta;
+    if sweep && delta < 0.0 {
+        delta += std::f64::consts::TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= std::f64::consts::TAU;
+    }
+    (1..=CURVE_SEGMENTS)
+        .map(|i| {
+            let t = theta + delta * i as f64 / CURVE_SEGMENTS as f64;
+            let (x, y) = (rx * t.cos(), ry * t.sin());
+            (cos * x - sin * y + cx, sin * x + cos * y + cy)
+        })
+        .collect()
+}
+
+/// A shape whose bounds take in the whole canvas is a background
+fn covers_canvas(polygons: &[Vec<Point>], [width, height]: [u32; 2]) -> bool {
+    let points = || polygons.iter().flatten();
+    let min_x = points().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let min_y = points().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_x = points().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let max_y = points().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    min_x <= 0.0 && min_y <= 0.0 && max_x >= width as f64 && max_y >= height as f64
+}
+
+/// Composite the shape's coverage (times `alpha`) over `canvas`
+fn fill(canvas: &mut [f32], [width, height]: [u32; 2], polygons: &[Vec<Point>], even_odd: bool, alpha: f32) {
+    // Closed edges as (x0, y0, x1, y1, winding)
+    let edges: Vec<(f64, f64, f64, f64, i32)> = polygons.iter()
+        .filter(|poly| poly.len() > 2)
+        .flat_map(|poly| poly.iter().zip(poly.iter().cycle().skip(1)))
+        .filter(|(a, b)| a.1 != b.1)
+        .map(|(a, b)| if a.1 < b.1 { (a.0, a.1, b.0, b.1, 1) } else { (b.0, b.1, a.0, a.1, -1) })
+        .collect();
+    if edges.is_empty() {
+        return;
+    }
+    let top = edges.iter().map(|e| e.1).fold(f64::INFINITY, f64::min).floor().max(0.0) as u32;
+    let bottom = edges.iter().map(|e| e.3).fold(f64::NEG_INFINITY, f64::max).ceil().clamp(0.0, height as f64) as u32;
+    let mut row = vec![0f32; width as usize];
+    let mut crossings: Vec<(f64, i32)> = vec![];
+
+    for y in top..bottom {
+        row.iter_mut().for_each(|c| *c = 0.0);
+        for sub in 0..SUBSAMPLES {
+            let sy = y as f64 + (sub as f64 + 0.5) / SUBSAMPLES as f64;
+            crossings.clear();
+            crossings.extend(edges.iter()
+                .filter(|e| e.1 <= sy && sy < e.3)
+                .map(|&(x0, y0, x1, y1, winding)| (x0 + (sy - y0) * (x1 - x0) / (y1 - y0), winding)));
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = if even_odd { winding % 2 != 0 } else { winding != 0 };
+                if inside {
+                    add_span(&mut row, pair[0].0, pair[1].0, 1.0 / SUBSAMPLES as f32);
+                }
+            }
+        }
+        let line = &mut canvas[(y * width) as usize..((y + 1) * width) as usize];
+        for (dst, &src) in line.iter_mut().zip(&row) {
+            let a = src.min(1.0) * alpha;
+            *dst += a * (1.0 - *dst);
+        }
+    }
+}
+
+/// Add `weight` times the horizontal overlap of `[x0, x1)` with each pixel
+fn add_span(row: &mut [f32], x0: f64, x1: f64, weight: f32) {
+    let x0 = x0.clamp(0.0, row.len() as f64);
+    let x1 = x1.clamp(0.0, row.len() as f64);
+    if x1 <= x0 {
+        return;
+    }
+    let first = x0.floor() as usize;
+    let last = (x1.ceil() as usize).min(row.len());
+    for (px, cell) in row.iter_mut().enumerate().take(last).skip(first) {
+        let overlap = x1.min(px as f64 + 1.0) - x0.max(px as f64);
+        *cell += overlap as f32 * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(body: &str) -> String {
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">{}</svg>"#, body)
+    }
+
+    #[test]
+    fn test_rect_coverage_is_exact_on_pixel_edges() {
+        let coverage = rasterize(&svg(r#"<rect x="10" y="20" width="30" height="40"/>"#), [10, 10]).unwrap();
+        assert_eq!(coverage.get(1, 2), 255);
+        assert_eq!(coverage.get(3, 5), 255);
+        assert_eq!(coverage.get(0, 2), 0);
+        assert_eq!(coverage.get(4, 2), 0);
+        assert_eq!(coverage.get(1, 6), 0);
+        // Half a pixel
+        let coverage = rasterize(&svg(r#"<rect x="5" y="0" width="10" height="10"/>"#), [10, 10]).unwrap();
+        assert_eq!((coverage.get(0, 0), coverage.get(1, 0)), (128, 128));
+    }
+
+    #[test]
+    fn test_paths_transforms_and_fill_rules() {
+        // The same square as a path, moved by a group transform
+        let coverage = rasterize(&svg(r#"<g transform="translate(50 0)"><path d="M0 0h10v10H0z"/></g>"#), [10, 10]).unwrap();
+        assert_eq!((coverage.get(5, 0), coverage.get(0, 0)), (255, 0));
+        // Arc-drawn circle covers its center, not its bounding box corner
+        let coverage = rasterize(&svg(r#"<path d="M10 50a40 40 0 1 0 80 0a40 40 0 1 0-80 0z"/>"#), [10, 10]).unwrap();
+        assert_eq!((coverage.get(5, 5), coverage.get(1, 1)), (255, 0));
+        // An even-odd hole is empty; non-zero fills it
+        let ring = "M0 0H100V99H0zM25 25H75V75H25z";
+        let even_odd = rasterize(&svg(&format!(r#"<path fill-rule="evenodd" d="{}"/>"#, ring)), [4, 4]).unwrap();
+        assert_eq!((even_odd.get(0, 0), even_odd.get(1, 1)), (255, 0));
+        let non_zero = rasterize(&svg(&format!(r#"<path d="{}"/>"#, ring)), [4, 4]).unwrap();
+        assert_eq!(non_zero.get(1, 1), 255);
+    }
+
+    #[test]
+    fn test_backgrounds_bleed_art_and_unfilled_shapes_are_not_content() {
+        let coverage = rasterize(&svg(concat!(
+            r##"<rect width="100" height="100" fill="#fff"/>"##,
+            r#"<g data-fi-bleed=""><rect x="0" y="0" width="50" height="10"/></g>"#,
+            r#"<rect x="0" y="90" width="100" height="10" fill="none"/>"#,
+            r#"<defs><rect id="r" x="0" y="50" width="10" height="10"/></defs>"#,
+            r#"<rect x="90" y="50" width="10" height="10" style="display: none"/>"#,
+        )), [10, 10]).unwrap();
+        assert!(coverage.values.iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_edge_intrusion_per_side() {
+        // Content in the left 1 px column, rows 0-4 of 10
+        let coverage = rasterize(&svg(r#"<rect x="0" y="0" width="10" height="50"/>"#), [10, 10]).unwrap();
+        assert_eq!(coverage.edge_intrusion(2, 127), [25.0, 10.0, 0.0, 0.0]);
+    }
+}
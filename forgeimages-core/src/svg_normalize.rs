@@ -0,0 +1,240 @@
+//! SVG Normalization - Canonical Form for Hashing
+//!
+//! Opt-in exception to Law 1: when a pipeline enables `normalize_source`,
+//! the master is re-serialized so that semantically identical files hash
+//! the same. The canonical form:
+//! - drops the XML declaration, processing instructions and comments
+//! - drops editor namespaces (`inkscape:*`, `sodipodi:*`) - elements,
+//!   attributes and their `xmlns:` declarations
+//! - sorts attributes by name and double-quotes every value
+//! - rounds numbers in attribute values to `NUMBER_PRECISION` decimals
+//! - removes whitespace between tags and collapses it elsewhere, except
+//!   inside text content elements, which are copied verbatim
+//! - writes empty elements as `<name/>`
+//!
+//! Normalizing a normalized document returns it unchanged.
+
+use crate::svg::{matching_end, tokenize, SvgError, Token};
+
+/// Decimal places kept for numbers in attribute values
+pub const NUMBER_PRECISION: usize = 3;
+
+const EDITOR_NAMESPACES: [&str; 2] = ["inkscape", "sodipodi"];
+
+/// Elements whose character data is content, not formatting
+const TEXT_ELEMENTS: [&str; 5] = ["text", "tspan", "textPath", "title", "desc"];
+
+/// Attributes whose values are names, never numbers
+const OPAQUE_ATTRIBUTES: [&str; 4] = ["id", "class", "href", "xlink:href"];
+
+enum Node {
+    Open { name: String, attrs: Vec<(String, String)>, empty: bool },
+    Close(String),
+    Raw(String),
+}
+
+fn is_editor_name(name: &str) -> bool {
+    let prefix = name.strip_prefix("xmlns:").unwrap_or(name);
+    EDITOR_NAMESPACES.iter().any(|ns| {
+        prefix == *ns || prefix.strip_prefix(ns).is_some_and(|rest| rest.starts_with(':'))
+    })
+}
+
+/// Canonicalize an SVG document
+pub fn normalize(svg: &str) -> Result<String, SvgError> {
+    let tokens = tokenize(svg)?;
+    let mut nodes = vec![];
+    let mut text_depth = 0usize;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Start { name, self_closing, .. } if is_editor_name(name) => {
+                if !*self_closing {
+                    i = matching_end(&tokens, i)?;
+                }
+            }
+            Token::Start { name, attrs, self_closing, .. } => {
+                let mut attrs: Vec<(String, String)> = attrs.iter()
+                    .filter(|(k, _)| !is_editor_name(k))
+                    .map(|(k, v)| (k.to_string(), normalize_value(k, v)))
+                    .collect();
+                attrs.sort();
+                if !*self_closing && TEXT_ELEMENTS.contains(name) {
+                    text_depth += 1;
+                }
+                nodes.push(Node::Open { name: name.to_string(), attrs, empty: *self_closing });
+            }
+            Token::End { name, .. } => {
+                if TEXT_ELEMENTS.contains(name) {
+                    text_depth = text_depth.saturating_sub(1);
+                }
+                nodes.push(Node::Close(name.to_string()));
+            }
+            Token::Text { span } => {
+                let text = &svg[span.clone()];
+                if text_depth > 0 {
+                    nodes.push(Node::Raw(text.to_string()));
+                } else if !text.trim().is_empty() {
+                    nodes.push(Node::Raw(collapse_whitespace(text)));
+                }
+            }
+            Token::Comment { .. } => {}
+            Token::Other { span } => {
+                let raw = &svg[span.clone()];
+                if !raw.starts_with("<?") {
+                    nodes.push(Node::Raw(raw.to_string()));
+                }
+            }
+        }
+        i += 1;
+    }
+
+    Ok(serialize(nodes))
+}
+
+fn serialize(nodes: Vec<Node>) -> String {
+    let mut out = String::new();
+    let mut nodes = nodes.into_iter().peekable();
+    while let Some(node) = nodes.next() {
+        match node {
+            Node::Open { name, attrs, mut empty } => {
+                if !empty && matches!(nodes.peek(), Some(Node::Close(n)) if *n == name) {
+                    nodes.next();
+                    empty = true;
+                }
+                out.push('<');
+                out.push_str(&name);
+                for (k, v) in attrs {
+                    out.push_str(&format!(" {}=\"{}\"", k, v));
+                }
+                out.push_str(if empty { "/>" } else { ">" });
+            }
+            Node::Close(name) => out.push_str(&format!("</{}>", name)),
+            Node::Raw(raw) => out.push_str(&raw),
+        }
+    }
+    out
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalize_value(name: &str, raw: &str) -> String {
+    // Values were single-quoted or double-quoted; output always uses double quotes
+    let value = collapse_whitespace(raw).replace('"', "&quot;");
+    if OPAQUE_ATTRIBUTES.contains(&name) || name.starts_with("data-") {
+        return value;
+    }
+    round_numbers(&value)
+}
+
+/// Rewrite every number token at a word boundary to fixed precision.
+///
+/// A number starts a token after punctuation, after another number, or after
+/// a lone letter such as a path command (`M10`), but not inside names like
+/// `grad2` or colors like `#ff0000`.
+fn round_numbers(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = String::with_capacity(value.len());
+    let mut pos = 0;
+    let mut after_number = false;
+    let mut after_lone_letter = false;
+
+    while pos < bytes.len() {
+        let at_boundary = after_number || pos == 0 || {
+            let prev = bytes[pos - 1];
+            !(prev.is_ascii_alphanumeric() || matches!(prev, b'#' | b'_' | b'.' | b'&'))
+        };
+        if at_boundary || after_lone_letter {
+            if let Some(end) = number_end(bytes, pos) {
+                match value[pos..end].parse::<f64>() {
+                    Ok(n) if n.is_finite() => out.push_str(&format_number(n)),
+                    _ => out.push_str(&value[pos..end]),
+                }
+                pos = end;
+                after_number = true;
+                after_lone_letter = false;
+                continue;
+            }
+        }
+        let c = value[pos..].chars().next().unwrap_or_default();
+        out.push(c);
+        pos += c.len_utf8();
+        after_number = false;
+        after_lone_letter = at_boundary && c.is_ascii_alphabetic();
+    }
+    out
+}
+
+/// End of a number starting at `pos`: [+-]? (digits [. digits?] | . digits) ([eE] [+-]? digits)?
+fn number_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    let digits = |mut p: usize| {
+        let start = p;
+        while p < bytes.len() && bytes[p].is_ascii_digit() {
+            p += 1;
+        }
+        (p, p > start)
+    };
+    let mut p = pos;
+    if matches!(bytes.get(p), Some(b'+' | b'-')) {
+        p += 1;
+    }
+    let (after_int, has_int) = digits(p);
+    p = after_int;
+    let mut has_frac = false;
+    if bytes.get(p) == Some(&b'.') {
+        let (after_frac, frac) = digits(p + 1);
+        if frac || has_int {
+            p = after_frac;
+            has_frac = frac;
+        }
+    }
+    if !has_int && !has_frac {
+        return None;
+    }
+    if matches!(bytes.get(p), Some(b'e' | b'E')) {
+        let mut q = p + 1;
+        if matches!(bytes.get(q), Some(b'+' | b'-')) {
+            q += 1;
+        }
+        let (after_exp, has_exp) = digits(q);
+        if has_exp {
+            p = after_exp;
+        }
+    }
+    Some(p)
+}
+
+fn format_number(n: f64) -> String {
+    let mut s = format!("{:.*}", NUMBER_PRECISION, n);
+    if s.contains('.') {
+        s = s.trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+    if s == "-0" {
+        s = "0".to_string();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_formatting() {
+        assert_eq!(round_numbers("M10.00000 20.5-3.14159L.5,1e2"), "M10 20.5-3.142L0.5,100");
+        assert_eq!(round_numbers("translate(-0.0001 7)"), "translate(0 7)");
+        assert_eq!(round_numbers("#ff0000"), "#ff0000");
+        assert_eq!(round_numbers("url(#grad2)"), "url(#grad2)");
+    }
+
+    #[test]
+    fn test_editor_names() {
+        assert!(is_editor_name("inkscape:label"));
+        assert!(is_editor_name("xmlns:sodipodi"));
+        assert!(!is_editor_name("inkscapeish"));
+        assert!(!is_editor_name("xlink:href"));
+    }
+}
@@ -1,13 +1,22 @@
 //! Template System - Enforceable Contracts
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+use crate::background::BackgroundGenerator;
+use crate::hashing::{canonical_json, parse_strict, HashAlgorithm, HashingError, StrictJsonError};
+use crate::imposition::Imposition;
+use crate::output::is_plain_file_name;
+use crate::print::{self, presets, PrintSpec, ProfileRegistry, TemplatePrint, PROFILE_SUFFIX};
+use crate::validation::ViolationSeverity;
 
 pub type TemplateId = String;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Template {
     pub id: TemplateId,
@@ -15,10 +24,16 @@ pub struct Template {
     pub description: String,
     pub template_version: String,
     pub engine_min_version: String,
+    /// First engine version this template is too old for (exclusive)
+    #[serde(default)]
+    pub engine_max_version: Option<String>,
     #[serde(default)]
     pub deprecated: bool,
     #[serde(default)]
     pub superseded_by: Option<String>,
+    /// Free-form labels for finding templates; not enforced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     pub asset_class: AssetClass,
     pub aspect_ratio: [u32; 2],
     pub canonical_size: [u32; 2],
@@ -28,11 +43,477 @@ pub struct Template {
     pub validation: ValidationConfig,
     #[serde(default)]
     pub exports: Vec<ExportSpec>,
+    /// Embed manifest-derived text chunks in raster exports
+    #[serde(default)]
+    pub embed_metadata: bool,
+    /// Seeded background composited beneath the source in raster exports
+    #[serde(default)]
+    pub background_generator: BackgroundGenerator,
+    /// SVG text slots fillable from `CompileRequest.params`
+    #[serde(default)]
+    pub text_slots: Vec<TextSlot>,
+    /// Font used to outline text slots, relative to the templates directory
+    #[serde(default)]
+    pub font: Option<FontRef>,
+    /// Print defaults for Pdf/Jpg/Tiff exports (Template authority)
+    #[serde(default)]
+    pub print: Option<TemplatePrint>,
+    /// What to do when an export is larger than a raster source
+    #[serde(default)]
+    pub scaling_policy: ScalingPolicy,
 }
 
 fn default_true() -> bool { true }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Template {
+    /// sha256 digest of the template's canonical JSON, as loaded (defaults
+    /// filled in), so formatting-only edits to the file do not change it
+    pub fn content_hash(&self) -> Result<String, HashingError> {
+        self.content_hash_with(HashAlgorithm::Sha256)
+    }
+
+    /// `content_hash` under a chosen algorithm
+    pub fn content_hash_with(&self, algorithm: HashAlgorithm) -> Result<String, HashingError> {
+        Ok(algorithm.digest(canonical_json(self)?.as_bytes()))
+    }
+
+    /// Set each preset export's size from its physical preset, at the dpi
+    /// of the print block in effect (300 without one). An explicit size must
+    /// agree; exports without a preset need a size.
+    pub fn resolve_physical_sizes(&mut self) -> Result<(), String> {
+        let template_dpi = self.print.as_ref().map(|p| p.dpi);
+        for spec in &mut self.exports {
+            let Some(name) = &spec.physical_preset else {
+                if spec.size.contains(&0) {
+                    return Err(format!("exports[{}]: size or physicalPreset is required", spec.id));
+                }
+                continue;
+            };
+            let size = presets::get(name)
+                .ok_or_else(|| format!("exports[{}]: unknown physicalPreset '{}'", spec.id, name))?;
+            let dpi = spec.print.as_ref().map(|p| p.dpi).or(template_dpi).unwrap_or(PrintSpec::default().dpi);
+            let pixels = print::raster_dimensions(size, dpi)
+                .map_err(|e| format!("exports[{}]: physicalPreset '{}' at {} dpi: {}", spec.id, name, dpi, e))?;
+            if spec.size != [0, 0] && spec.size != pixels {
+                return Err(format!(
+                    "exports[{}]: size {:?} conflicts with physicalPreset '{}' at {} dpi ({:?})",
+                    spec.id, spec.size, name, dpi, pixels,
+                ));
+            }
+            spec.size = pixels;
+        }
+        Ok(())
+    }
+
+    /// Export ids name the exported files (`{id}.{ext}`), so each must be
+    /// a plain file name; anything else could write outside the output
+    /// directory
+    pub fn validate_export_ids(&self) -> Result<(), TemplateLoadError> {
+        match self.exports.iter().find(|spec| !is_plain_file_name(&spec.id)) {
+            Some(spec) => Err(TemplateLoadError::UnsafeExportId(spec.id.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the template and per-export print blocks against the bounds
+    /// user overrides are held to; export blocks, impositions and guides
+    /// are for print formats only, soft proofs for optional Png exports
+    pub fn validate_print(&self) -> Result<(), String> {
+        if let Some(print) = &self.print {
+            print.validate().map_err(|e| format!("print: {}", e))?;
+        }
+        for spec in &self.exports {
+            if spec.soft_proof && spec.format != ExportFormat::Png {
+                return Err(format!("exports[{}].softProof: proofs are Png exports, not {:?}", spec.id, spec.format));
+            }
+            if spec.soft_proof && spec.required {
+                return Err(format!("exports[{}].softProof: a proof cannot be a required export", spec.id));
+            }
+            if spec.guides && !spec.format.is_print() {
+                return Err(format!("exports[{}].guides: guides are drawn on print exports, not {:?}", spec.id, spec.format));
+            }
+            if spec.guides && spec.required {
+                return Err(format!("exports[{}].guides: a proof cannot be a required export", spec.id));
+            }
+            if spec.guides && spec.imposition.is_some() {
+                return Err(format!("exports[{}].guides: imposed sheets carry no guides", spec.id));
+            }
+            if let Some(imposition) = &spec.imposition {
+                if !spec.format.is_print() {
+                    return Err(format!("exports[{}].imposition: {:?} exports cannot be imposed", spec.id, spec.format));
+                }
+                imposition.validate().map_err(|e| format!("exports[{}].imposition: {}", spec.id, e))?;
+            }
+            let Some(print) = &spec.print else { continue };
+            if !spec.format.is_print() {
+                return Err(format!("exports[{}].print: {:?} exports take no print settings", spec.id, spec.format));
+            }
+            print.validate().map_err(|e| format!("exports[{}].print: {}", spec.id, e))?;
+        }
+        Ok(())
+    }
+
+    /// Read one template file strictly: every problem is an error, where
+    /// `TemplateRegistry::load_from_dir` skips files that do not parse
+    pub fn from_file(path: &Path, profiles: &ProfileRegistry) -> Result<Template, TemplateLoadError> {
+        Self::from_json(&fs::read_to_string(path)?, profiles)
+    }
+
+    /// `from_file` for a template already in memory
+    pub fn from_json(content: &str, profiles: &ProfileRegistry) -> Result<Template, TemplateLoadError> {
+        let mut value = parse_strict(content).map_err(|e| match e {
+            StrictJsonError::Syntax(message) => TemplateLoadError::Syntax(message),
+            StrictJsonError::DuplicateKey(key, pointer) => TemplateLoadError::DuplicateKey(key, pointer),
+        })?;
+        profiles.apply_to_template(&mut value).map_err(|e| TemplateLoadError::Profile(e.to_string()))?;
+        let mut template: Template = serde_json::from_value(value).map_err(|e| TemplateLoadError::Schema(e.to_string()))?;
+        template.validate_export_ids()?;
+        template.resolve_physical_sizes()
+            .and_then(|()| template.validate_print())
+            .map_err(TemplateLoadError::invalid)?;
+        Ok(template)
+    }
+
+    /// Problems that load fine but make a poor contract: bad versions,
+    /// duplicate ids, a canonical size off the aspect ratio
+    pub fn lint(&self) -> Vec<LintIssue> {
+        let mut issues = vec![];
+        let mut issue = |code, severity, field: String, message: String| {
+            issues.push(LintIssue { code, severity, field, message });
+        };
+        use ViolationSeverity::{Error, Warning};
+
+        if self.id.trim().is_empty() {
+            issue("empty_id", Error, "id".into(), "template id is empty".into());
+        }
+        if semver::Version::parse(&self.template_version).is_err() {
+            issue("template_version_invalid", Error, "templateVersion".into(), format!("{:?} is not a semver version", self.template_version));
+        }
+        let min = semver::Version::parse(&self.engine_min_version);
+        if min.is_err() {
+            issue("engine_version_invalid", Error, "engineMinVersion".into(), format!("{:?} is not a semver version", self.engine_min_version));
+        }
+        if let Some(max) = &self.engine_max_version {
+            match (semver::Version::parse(max), &min) {
+                (Err(_), _) => issue("engine_version_invalid", Error, "engineMaxVersion".into(), format!("{:?} is not a semver version", max)),
+                (Ok(max), Ok(min)) if max <= *min => issue(
+                    "engine_range_empty", Error, "engineMaxVersion".into(),
+                    format!("no engine is >= {} and < {}", min, max),
+                ),
+                _ => {}
+            }
+        }
+        if self.superseded_by.as_deref() == Some(self.id.as_str()) {
+            issue("superseded_by_self", Error, "supersededBy".into(), "a template cannot supersede itself".into());
+        } else if self.superseded_by.is_some() && !self.deprecated {
+            issue("superseded_not_deprecated", Warning, "supersededBy".into(), "supersededBy is set but deprecated is false".into());
+        }
+        let mut tags = BTreeSet::new();
+        for tag in &self.tags {
+            if !tags.insert(tag) {
+                issue("duplicate_tag", Warning, "tags".into(), format!("tag {:?} is listed twice", tag));
+            }
+        }
+
+        if self.aspect_ratio.contains(&0) {
+            issue("aspect_ratio_zero", Error, "aspectRatio".into(), format!("{:?} has a zero term", self.aspect_ratio));
+        } else if !self.canonical_size.contains(&0) {
+            let expected = self.aspect_ratio[0] as f64 / self.aspect_ratio[1] as f64;
+            let actual = self.canonical_size[0] as f64 / self.canonical_size[1] as f64;
+            if (expected - actual).abs() > self.validation.rules.aspect_ratio.tolerance {
+                issue(
+                    "canonical_size_aspect", Warning, "canonicalSize".into(),
+                    format!("{:?} is not {}:{}", self.canonical_size, self.aspect_ratio[0], self.aspect_ratio[1]),
+                );
+            }
+        }
+        if self.canonical_size.contains(&0) {
+            issue("canonical_size_zero", Error, "canonicalSize".into(), format!("{:?} has a zero side", self.canonical_size));
+        }
+
+        if self.exports.is_empty() {
+            issue("no_exports", Warning, "exports".into(), "the template produces nothing".into());
+        } else if !self.exports.iter().any(|e| e.required) {
+            issue("no_required_export", Warning, "exports".into(), "no export is required, so a compile can succeed empty".into());
+        }
+        let mut ids = BTreeSet::new();
+        for spec in &self.exports {
+            if !ids.insert(&spec.id) {
+                issue("duplicate_export_id", Error, format!("exports[{}]", spec.id), format!("export id {:?} is used twice", spec.id));
+            }
+            if !is_plain_file_name(&spec.id) {
+                let error = TemplateLoadError::UnsafeExportId(spec.id.clone());
+                issue(error.code(), Error, format!("exports[{}].id", spec.id), error.to_string());
+            }
+        }
+        let mut slots = BTreeSet::new();
+        for slot in &self.text_slots {
+            if !slots.insert(&slot.id) {
+                issue("duplicate_text_slot", Error, format!("textSlots[{}]", slot.id), format!("text slot {:?} is declared twice", slot.id));
+            }
+        }
+        issues
+    }
+
+    /// Start a new template with class-appropriate exports and every
+    /// validation setting spelled out
+    pub fn builder(id: impl Into<String>, asset_class: AssetClass, canonical_size: [u32; 2]) -> TemplateBuilder {
+        let id = id.into();
+        TemplateBuilder {
+            template: Template {
+                name: id.clone(),
+                description: String::new(),
+                id,
+                template_version: "1.0.0".to_string(),
+                engine_min_version: crate::ENGINE_VERSION.to_string(),
+                engine_max_version: None,
+                deprecated: false,
+                superseded_by: None,
+                tags: vec![],
+                vector_master: matches!(asset_class, AssetClass::Icon | AssetClass::Logo),
+                asset_class,
+                aspect_ratio: reduced_ratio(canonical_size),
+                canonical_size,
+                validation: ValidationConfig::for_canonical_size(canonical_size),
+                exports: vec![],
+                embed_metadata: false,
+                background_generator: BackgroundGenerator::default(),
+                text_slots: vec![],
+                font: None,
+                print: None,
+                scaling_policy: ScalingPolicy::default(),
+            },
+            exports: None,
+        }
+    }
+}
+
+/// Builds a `Template` for authors to start from; what `build` returns
+/// lints clean unless a setter made it otherwise
+pub struct TemplateBuilder {
+    template: Template,
+    /// `None` until an export is added: the class defaults are used
+    exports: Option<Vec<ExportSpec>>,
+}
+
+impl TemplateBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.template.name = name.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.template.description = description.into();
+        self
+    }
+
+    /// Override the ratio reduced from the canonical size, e.g. 16:9 for 1366x768
+    pub fn aspect_ratio(mut self, aspect_ratio: [u32; 2]) -> Self {
+        self.template.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.template.tags.push(tag.into());
+        self
+    }
+
+    /// Add an export; the first one replaces the class defaults
+    pub fn export(mut self, spec: ExportSpec) -> Self {
+        self.exports.get_or_insert_with(Vec::new).push(spec);
+        self
+    }
+
+    pub fn validation(mut self, validation: ValidationConfig) -> Self {
+        self.template.validation = validation;
+        self
+    }
+
+    pub fn build(mut self) -> Template {
+        let template = &mut self.template;
+        template.exports = self.exports.unwrap_or_else(|| default_exports(&template.asset_class, template.canonical_size));
+        self.template
+    }
+}
+
+/// A size's aspect ratio in lowest terms
+fn reduced_ratio([width, height]: [u32; 2]) -> [u32; 2] {
+    let (mut a, mut b) = (width, height);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    match (width.checked_div(a), height.checked_div(a)) {
+        (Some(w), Some(h)) => [w, h],
+        _ => [width, height],
+    }
+}
+
+/// What a fresh template of each class produces: a master at the
+/// canonical size plus the renditions the class is usually shipped in
+fn default_exports(asset_class: &AssetClass, canonical_size: [u32; 2]) -> Vec<ExportSpec> {
+    // Scaled so the longer side is `side`, never above the canonical size
+    let scaled = |side: u32| {
+        let longest = canonical_size[0].max(canonical_size[1]).max(1);
+        let side = side.min(longest) as u64;
+        canonical_size.map(|n| ((n as u64 * side).div_ceil(longest as u64)).max(1) as u32)
+    };
+    let export = |id: &str, description: &str, size, format| ExportSpec {
+        id: id.to_string(),
+        description: description.to_string(),
+        size,
+        physical_preset: None,
+        format,
+        required: true,
+        scaling_policy: None,
+        print: None,
+        imposition: None,
+        soft_proof: false,
+        guides: false,
+    };
+    match asset_class {
+        AssetClass::Icon => vec![
+            export("master", "SVG master", canonical_size, ExportFormat::Svg),
+            export("png-512", "PNG 512px", scaled(512), ExportFormat::Png),
+            export("png-192", "PNG 192px", scaled(192), ExportFormat::Png),
+            export("png-32", "PNG 32px", scaled(32), ExportFormat::Png),
+        ],
+        AssetClass::Logo => vec![
+            export("master", "SVG master", canonical_size, ExportFormat::Svg),
+            export("png", "PNG at canonical size", canonical_size, ExportFormat::Png),
+            export("png-small", "PNG for small placements", scaled(256), ExportFormat::Png),
+        ],
+        AssetClass::Banner | AssetClass::Cover => vec![
+            export("master", "PNG master", canonical_size, ExportFormat::Png),
+            export("jpg", "JPEG at canonical size", canonical_size, ExportFormat::Jpg),
+            export("preview", "PNG preview", scaled(640), ExportFormat::Png),
+        ],
+    }
+}
+
+/// A problem `Template::lint` or `Template::from_file` found.
+///
+/// `code` is stable across releases; tooling should key on it, not on
+/// `message`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub code: &'static str,
+    pub severity: ViolationSeverity,
+    /// Path of the offending field, e.g. `exports[card].size`; empty for
+    /// the file as a whole
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Error)]
+pub enum TemplateLoadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid JSON: {0}")]
+    Syntax(String),
+
+    /// Key, then the JSON Pointer path of its second occurrence
+    #[error("Duplicate key {0:?} at {1:?}")]
+    DuplicateKey(String, String),
+
+    #[error("{0}")]
+    Profile(String),
+
+    #[error("Not a template: {0}")]
+    Schema(String),
+
+    /// Field path, message
+    #[error("{0}: {1}")]
+    Invalid(String, String),
+
+    /// An export id with a path separator, `..` or nothing at all
+    #[error("export id {0:?} is not a plain file name")]
+    UnsafeExportId(String),
+}
+
+/// A template file `TemplateRegistry::load_from_dir` refused; the inner
+/// error of the `InvalidData` error it returns
+#[derive(Debug, Error)]
+#[error("{}: {error}", path.display())]
+pub struct RejectedTemplate {
+    pub path: PathBuf,
+    pub error: TemplateLoadError,
+}
+
+/// Why `TemplateRegistry::from_json_array` refused its input
+#[derive(Debug, Error)]
+pub enum TemplateArrayError {
+    #[error("{0}")]
+    Json(#[from] StrictJsonError),
+
+    #[error("expected a JSON array of templates")]
+    NotArray,
+
+    /// Index in the array, then why that template did not load
+    #[error("templates[{0}]: {1}")]
+    Template(usize, TemplateLoadError),
+}
+
+impl TemplateLoadError {
+    /// Split a `field: message` error from the load-time checks
+    fn invalid(error: String) -> Self {
+        match error.split_once(": ") {
+            Some((field, message)) => Self::Invalid(field.to_string(), message.to_string()),
+            None => Self::Invalid("template".to_string(), error),
+        }
+    }
+
+    /// Stable code, as in `LintIssue::code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "unreadable",
+            Self::Syntax(_) => "json_syntax",
+            Self::DuplicateKey(..) => "duplicate_key",
+            Self::Profile(_) => "print_profile",
+            Self::Schema(_) => "schema",
+            Self::Invalid(..) => "invalid_field",
+            Self::UnsafeExportId(_) => "unsafe_export_id",
+        }
+    }
+
+    pub fn to_issue(&self) -> LintIssue {
+        let (field, message) = match self {
+            Self::DuplicateKey(_, pointer) => (pointer.clone(), self.to_string()),
+            Self::Invalid(field, message) => (field.clone(), message.clone()),
+            Self::UnsafeExportId(id) => (format!("exports[{}].id", id), self.to_string()),
+            _ => (String::new(), self.to_string()),
+        };
+        LintIssue { code: self.code(), severity: ViolationSeverity::Error, field, message }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TextSlot {
+    pub id: String,
+    #[serde(default = "default_slot_max_length")]
+    pub max_length: usize,
+}
+
+fn default_slot_max_length() -> usize { 256 }
+
+/// Font bundled with a template; the hash pins the exact file
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FontRef {
+    pub path: String,
+    /// Bare hex sha256, or an algorithm-prefixed digest
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum AssetClass {
     Icon,
@@ -42,6 +523,7 @@ pub enum AssetClass {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ValidationConfig {
     #[serde(default = "default_true")]
@@ -52,7 +534,29 @@ pub struct ValidationConfig {
     pub rules: ValidationRules,
 }
 
+impl ValidationConfig {
+    /// The settings a template file gets for an empty `validation` block,
+    /// with the minimum resolution at half the canonical size
+    pub fn for_canonical_size(canonical_size: [u32; 2]) -> Self {
+        Self {
+            required: true,
+            failure_mode: FailureMode::Block,
+            rules: ValidationRules {
+                aspect_ratio: RuleConfig { enabled: true, tolerance: default_tolerance() },
+                resolution: ResolutionRule {
+                    enabled: true,
+                    min_width: canonical_size[0].div_ceil(2),
+                    min_height: canonical_size[1].div_ceil(2),
+                },
+                color_count: ColorCountRule { enabled: false, max: default_max_colors() },
+                print: PrintRules::default(),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum FailureMode {
     #[default]
@@ -62,6 +566,7 @@ pub enum FailureMode {
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ValidationRules {
     #[serde(default)]
@@ -70,9 +575,12 @@ pub struct ValidationRules {
     pub resolution: ResolutionRule,
     #[serde(default)]
     pub color_count: ColorCountRule,
+    #[serde(default, skip_serializing_if = "PrintRules::is_default")]
+    pub print: PrintRules,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RuleConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -83,6 +591,7 @@ pub struct RuleConfig {
 fn default_tolerance() -> f64 { 0.01 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ResolutionRule {
     #[serde(default = "default_true")]
@@ -97,6 +606,7 @@ fn default_min_width() -> u32 { 1024 }
 fn default_min_height() -> u32 { 1024 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ColorCountRule {
     #[serde(default)]
     pub enabled: bool,
@@ -106,18 +616,170 @@ pub struct ColorCountRule {
 
 fn default_max_colors() -> u32 { 16 }
 
+/// Rules for templates destined for print
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct PrintRules {
+    #[serde(default)]
+    pub effective_dpi: EffectiveDpiRule,
+    #[serde(default)]
+    pub safe_margin: SafeMarginRule,
+}
+
+impl PrintRules {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Raster sources must reach the print dpi at the physical export size
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveDpiRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Fraction below the print dpi that is a Warning rather than an Error
+    #[serde(default = "default_dpi_warning_band")]
+    pub warning_band: f64,
+    /// Templates the rule applies to
+    #[serde(default = "default_dpi_asset_classes")]
+    pub asset_classes: Vec<AssetClass>,
+}
+
+impl Default for EffectiveDpiRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            warning_band: default_dpi_warning_band(),
+            asset_classes: default_dpi_asset_classes(),
+        }
+    }
+}
+
+fn default_dpi_warning_band() -> f64 { 0.10 }
+fn default_dpi_asset_classes() -> Vec<AssetClass> { vec![AssetClass::Banner, AssetClass::Cover] }
+
+/// SVG content must stay out of the safe margin of print exports whose
+/// print block sets `safeMargin`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SafeMarginRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Pixels covered by more than this fraction count as content
+    #[serde(default = "default_coverage_threshold")]
+    pub coverage_threshold: f64,
+    /// Resolution the master is rasterized at for the check
+    #[serde(default = "default_analysis_dpi")]
+    pub analysis_dpi: u32,
+}
+
+impl Default for SafeMarginRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            coverage_threshold: default_coverage_threshold(),
+            analysis_dpi: default_analysis_dpi(),
+        }
+    }
+}
+
+fn default_coverage_threshold() -> f64 { 0.5 }
+fn default_analysis_dpi() -> u32 { 72 }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ExportSpec {
     pub id: String,
     pub description: String,
+    /// Pixel size; filled in from `physical_preset` on load when that is set
+    #[serde(default)]
     pub size: [u32; 2],
+    /// Named physical size (`print::presets`), converted at the export's dpi
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_preset: Option<String>,
     pub format: ExportFormat,
     #[serde(default)]
     pub required: bool,
+    /// Overrides the template's scaling policy for this export
+    #[serde(default)]
+    pub scaling_policy: Option<ScalingPolicy>,
+    /// Stands in for the template's print block for this export (print formats only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub print: Option<TemplatePrint>,
+    /// Repeat the trim-size artwork n-up on a press sheet (print formats only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imposition: Option<Imposition>,
+    /// Png preview of the artwork after CMYK conversion and back
+    /// (`color::soft_proof`); never a deliverable, so never required
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub soft_proof: bool,
+    /// Draw bleed, trim and safe-margin outlines over the print export
+    /// (`bleed::Guides`); a proof like `soft_proof`, never required
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub guides: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingPolicy {
+    /// Validation error when an export exceeds the source resolution
+    ForbidUpscale,
+    /// Upscale, but record a Warning violation
+    AllowUpscaleWithWarning,
+    #[default]
+    Allow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingDecision {
+    /// Vector source; scales freely
+    VectorSource,
+    /// Export fits within the source resolution
+    WithinSource,
+    Upscaled,
+}
+
+/// Effective scaling policy and outcome for one export (recorded in the manifest)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ScalingRecord {
+    pub policy: ScalingPolicy,
+    pub decision: ScalingDecision,
+}
+
+impl ExportSpec {
+    /// Soft proofs and guide proofs: previews, never deliverables
+    pub fn is_proof(&self) -> bool {
+        self.soft_proof || self.guides
+    }
+
+    /// Scaling outcome given the raster source size (`None` for vector sources)
+    pub fn scaling(&self, template_policy: ScalingPolicy, source_size: Option<[u32; 2]>) -> ScalingRecord {
+        let policy = self.scaling_policy.unwrap_or(template_policy);
+        let decision = match source_size {
+            None => ScalingDecision::VectorSource,
+            Some([w, h]) if self.size[0] > w || self.size[1] > h => ScalingDecision::Upscaled,
+            Some(_) => ScalingDecision::WithinSource,
+        };
+        ScalingRecord { policy, decision }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum ExportFormat {
     Svg,
@@ -125,47 +787,243 @@ pub enum ExportFormat {
     Ico,
     Pdf,
     Jpg,
+    Tiff,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 6] = [
+        ExportFormat::Svg,
+        ExportFormat::Png,
+        ExportFormat::Ico,
+        ExportFormat::Pdf,
+        ExportFormat::Jpg,
+        ExportFormat::Tiff,
+    ];
+
+    /// Formats that receive the resolved print spec
+    pub fn is_print(&self) -> bool {
+        matches!(self, ExportFormat::Pdf | ExportFormat::Jpg | ExportFormat::Tiff)
+    }
 }
 
 /// Template registry - loads and caches templates
 pub struct TemplateRegistry {
     templates: HashMap<TemplateId, Template>,
+    /// Directory template assets (fonts) are resolved against
+    asset_dir: Option<PathBuf>,
+    /// Directory each loaded template came from; its assets resolve there
+    sources: HashMap<TemplateId, PathBuf>,
+    /// Assets registered in memory; consulted before `asset_dir`
+    assets: HashMap<String, Vec<u8>>,
 }
 
 impl TemplateRegistry {
     pub fn new() -> Self {
-        Self { templates: HashMap::new() }
+        Self { templates: HashMap::new(), asset_dir: None, sources: HashMap::new(), assets: HashMap::new() }
     }
 
     pub fn load_from_dir(dir: &Path) -> Result<Self, std::io::Error> {
+        Self::load_from_dir_with_profiles(dir, &ProfileRegistry::new())
+    }
+
+    /// `load_from_dir`, applying the print profiles templates name; naming
+    /// a profile `profiles` lacks is an error
+    pub fn load_from_dir_with_profiles(dir: &Path, profiles: &ProfileRegistry) -> Result<Self, std::io::Error> {
         let mut registry = Self::new();
-        if dir.exists() {
+        registry.asset_dir = Some(dir.to_path_buf());
+        if !dir.exists() {
+            tracing::info!(dir = %dir.display(), "templates directory does not exist; no templates loaded");
+        } else {
             for entry in fs::read_dir(dir)? {
                 let entry = entry?;
                 let path = entry.path();
-                if path.extension().map_or(false, |e| e == "json") {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        if let Ok(template) = serde_json::from_str::<Template>(&content) {
+                let is_profile = path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(PROFILE_SUFFIX));
+                if path.extension().is_some_and(|e| e == "json") && !is_profile {
+                    match Template::from_file(&path, profiles) {
+                        Ok(template) => {
+                            tracing::debug!(path = %path.display(), id = %template.id, version = %template.template_version, "loaded template");
+                            registry.sources.insert(template.id.clone(), dir.to_path_buf());
                             registry.templates.insert(template.id.clone(), template);
                         }
+                        // Not a template (or not readable): skipped, as before strict loading
+                        Err(e @ (TemplateLoadError::Io(_) | TemplateLoadError::Syntax(_) | TemplateLoadError::Schema(_))) => {
+                            tracing::info!(path = %path.display(), error = %e, "skipped file: not a template");
+                        }
+                        // The file parses, but not to what its author wrote; bad sizes or
+                        // print settings would otherwise surface only at compile
+                        // as do export ids that would write outside the output directory
+                        Err(error) => {
+                            tracing::info!(path = %path.display(), error = %error, "rejected template");
+                            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, RejectedTemplate { path, error }));
+                        }
                     }
                 }
             }
+            tracing::info!(dir = %dir.display(), templates = registry.templates.len(), "loaded templates directory");
+        }
+        Ok(registry)
+    }
+
+    /// Templates given inline as a JSON array, for hosts with no templates
+    /// directory (FFI, Node); each loads as strictly as `Template::from_json`
+    pub fn from_json_array(json: &str) -> Result<Self, TemplateArrayError> {
+        let serde_json::Value::Array(templates) = parse_strict(json)? else {
+            return Err(TemplateArrayError::NotArray);
+        };
+        let mut registry = Self::new();
+        for (index, template) in templates.iter().enumerate() {
+            let template = Template::from_json(&template.to_string(), &ProfileRegistry::new())
+                .map_err(|e| TemplateArrayError::Template(index, e))?;
+            registry.register(template);
+        }
+        Ok(registry)
+    }
+
+    /// Load each directory in order, later ones overlaid on earlier ones
+    /// (see `overlay`); a conflict is an `InvalidData` error
+    pub fn load_layered(dirs: &[PathBuf], allow_downgrade: bool) -> Result<Self, std::io::Error> {
+        let mut registry = Self::new();
+        for dir in dirs {
+            registry.overlay(Self::load_from_dir(dir)?, allow_downgrade)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
         }
+        registry.asset_dir = dirs.last().cloned();
         Ok(registry)
     }
 
+    /// Add `overlay`'s templates, replacing ones with the same id when the
+    /// overlay's version is greater, or equal with the same content; a
+    /// lower version replaces only with `allow_downgrade`
+    pub fn overlay(&mut self, overlay: TemplateRegistry, allow_downgrade: bool) -> Result<(), Box<OverlayConflict>> {
+        let TemplateRegistry { templates, mut sources, assets, .. } = overlay;
+        for (id, template) in templates {
+            if let Some(base) = self.templates.get(&id) {
+                let source = |sources: &HashMap<TemplateId, PathBuf>| {
+                    sources.get(&id).map_or("memory".to_string(), |dir| dir.display().to_string())
+                };
+                let conflict = OverlayConflict {
+                    id: id.clone(),
+                    base_version: base.template_version.clone(),
+                    base_dir: source(&self.sources),
+                    version: template.template_version.clone(),
+                    dir: source(&sources),
+                    kind: OverlayConflictKind::Downgrade,
+                };
+                let version = |v: &str| semver::Version::parse(v).ok();
+                let (Some(base_version), Some(version)) = (version(&base.template_version), version(&template.template_version)) else {
+                    return Err(Box::new(OverlayConflict { kind: OverlayConflictKind::InvalidVersion, ..conflict }));
+                };
+                if version < base_version && !allow_downgrade {
+                    return Err(Box::new(conflict));
+                }
+                if version == base_version && base.content_hash().ok() != template.content_hash().ok() {
+                    return Err(Box::new(OverlayConflict { kind: OverlayConflictKind::SameVersionDiffers, ..conflict }));
+                }
+            }
+            match sources.remove(&id) {
+                Some(dir) => self.sources.insert(id.clone(), dir),
+                None => self.sources.remove(&id),
+            };
+            self.templates.insert(id, template);
+        }
+        self.assets.extend(assets);
+        Ok(())
+    }
+
     pub fn get(&self, id: &str) -> Option<&Template> {
         self.templates.get(id)
     }
 
+    /// Directory a template was loaded from; `None` for registered ones
+    pub fn source_dir(&self, id: &str) -> Option<&Path> {
+        self.sources.get(id).map(PathBuf::as_path)
+    }
+
     pub fn list(&self) -> Vec<&Template> {
         self.templates.values().collect()
     }
 
     pub fn register(&mut self, template: Template) {
+        self.sources.remove(&template.id);
         self.templates.insert(template.id.clone(), template);
     }
+
+    /// Set the directory template assets are resolved against
+    pub fn set_asset_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.asset_dir = Some(dir.into());
+    }
+
+    /// Register an asset in memory under the relative path templates use
+    pub fn register_asset(&mut self, relative: impl Into<String>, bytes: Vec<u8>) {
+        self.assets.insert(relative.into(), bytes);
+    }
+
+    /// An asset registered in memory, without falling back to disk
+    pub fn registered_asset(&self, relative: &str) -> Option<&[u8]> {
+        self.assets.get(relative).map(Vec::as_slice)
+    }
+
+    /// Read a template asset by relative path; paths may not leave the asset dir
+    pub fn read_asset(&self, relative: &str) -> Result<Vec<u8>, std::io::Error> {
+        self.read_asset_in(self.asset_dir.as_deref(), relative)
+    }
+
+    /// `read_asset`, resolved against the directory `template_id` came from
+    pub fn read_template_asset(&self, template_id: &str, relative: &str) -> Result<Vec<u8>, std::io::Error> {
+        self.read_asset_in(self.source_dir(template_id).or(self.asset_dir.as_deref()), relative)
+    }
+
+    fn read_asset_in(&self, dir: Option<&Path>, relative: &str) -> Result<Vec<u8>, std::io::Error> {
+        use std::io::{Error, ErrorKind};
+
+        if let Some(bytes) = self.registered_asset(relative) {
+            return Ok(bytes.to_vec());
+        }
+
+        let dir = dir
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "registry has no asset directory"))?;
+        let path = Path::new(relative);
+        if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("asset path escapes the templates directory: {}", relative),
+            ));
+        }
+        fs::read(dir.join(path))
+    }
+}
+
+/// A template `TemplateRegistry::overlay` would not replace
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("template {id} {version} in {dir} {kind} {base_version} in {base_dir}")]
+pub struct OverlayConflict {
+    pub id: TemplateId,
+    pub base_version: String,
+    /// Directory of the template already loaded, or `memory`
+    pub base_dir: String,
+    pub version: String,
+    pub dir: String,
+    pub kind: OverlayConflictKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayConflictKind {
+    /// Lower version, without `allow_downgrade`
+    Downgrade,
+    /// Same version, different content
+    SameVersionDiffers,
+    /// A version that is not semver, so cannot be ordered
+    InvalidVersion,
+}
+
+impl std::fmt::Display for OverlayConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Downgrade => "would downgrade",
+            Self::SameVersionDiffers => "differs from",
+            Self::InvalidVersion => "cannot be ordered against",
+        })
+    }
 }
 
 impl Default for TemplateRegistry {
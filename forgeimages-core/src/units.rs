@@ -0,0 +1,274 @@
+//! Units - Physical Lengths
+//!
+//! `Length` holds a whole number of micrometres, so print sizes compare,
+//! hash and key caches exactly however they were written. Constructors
+//! round to the nearest micrometre (halves away from zero); converters
+//! return the nearest `f64` of the exact quotient.
+//!
+//! In JSON a length is a string with a unit: `"3mm"`, `"0.125in"`,
+//! `"9pt"`. Fields that predate `Length` (`bleed_inches`, `widthMm`, ...)
+//! go through `inches` or `millimetres` instead: they still write a bare
+//! number in their old unit, so template and manifest hashes do not move,
+//! and read either that number or a unit string.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+pub const MICROMETRES_PER_MM: i64 = 1_000;
+pub const MICROMETRES_PER_INCH: i64 = 25_400;
+pub const POINTS_PER_INCH: i64 = 72;
+
+/// A physical length in whole micrometres
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Length {
+    micrometres: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid length '{0}': expected a number followed by mm, in or pt")]
+pub struct LengthError(pub String);
+
+impl Length {
+    pub const ZERO: Length = Length { micrometres: 0 };
+
+    pub const fn from_micrometres(micrometres: i64) -> Self {
+        Self { micrometres }
+    }
+
+    /// Nearest micrometre; non-finite input saturates (NaN to zero)
+    pub fn mm(mm: f64) -> Self {
+        Self::from_micrometres((mm * MICROMETRES_PER_MM as f64).round() as i64)
+    }
+
+    pub fn inches(inches: f64) -> Self {
+        Self::from_micrometres((inches * MICROMETRES_PER_INCH as f64).round() as i64)
+    }
+
+    /// Points of 1/72 in
+    pub fn points(points: f64) -> Self {
+        Self::from_micrometres((points * MICROMETRES_PER_INCH as f64 / POINTS_PER_INCH as f64).round() as i64)
+    }
+
+    /// `pixels` at `dpi`, to the nearest micrometre (halves up)
+    pub fn from_pixels(pixels: u32, dpi: u32) -> Self {
+        let dpi = dpi.max(1) as i64;
+        Self::from_micrometres((pixels as i64 * MICROMETRES_PER_INCH + dpi / 2) / dpi)
+    }
+
+    pub const fn micrometres(&self) -> i64 {
+        self.micrometres
+    }
+
+    pub fn as_mm(&self) -> f64 {
+        self.micrometres as f64 / MICROMETRES_PER_MM as f64
+    }
+
+    pub fn as_inches(&self) -> f64 {
+        self.micrometres as f64 / MICROMETRES_PER_INCH as f64
+    }
+
+    pub fn as_points(&self) -> f64 {
+        (self.micrometres * POINTS_PER_INCH) as f64 / MICROMETRES_PER_INCH as f64
+    }
+
+    /// Whole pixels at `dpi`: `floor((µm * dpi + 12700) / 25400)`, exact
+    /// rounding with halves up, in integers. Negative lengths are 0 px;
+    /// past `u32::MAX` saturates (see `checked_pixels`).
+    pub fn to_pixels(&self, dpi: u32) -> u32 {
+        self.checked_pixels(dpi).unwrap_or(u32::MAX)
+    }
+
+    /// `to_pixels`, or `None` when the result does not fit a `u32`
+    pub fn checked_pixels(&self, dpi: u32) -> Option<u32> {
+        let micrometres = self.micrometres.max(0) as u128;
+        let pixels = (micrometres * dpi as u128 + MICROMETRES_PER_INCH as u128 / 2) / MICROMETRES_PER_INCH as u128;
+        u32::try_from(pixels).ok()
+    }
+
+    /// `"3mm"`, `"0.125in"`, `"9pt"`; whitespace before the unit is allowed
+    pub fn parse(text: &str) -> Result<Self, LengthError> {
+        let text = text.trim();
+        let error = || LengthError(text.to_string());
+        let split = text.len().checked_sub(2).filter(|&at| text.is_char_boundary(at)).ok_or_else(error)?;
+        let (number, unit) = text.split_at(split);
+        let value: f64 = number.trim_end().parse().map_err(|_| error())?;
+        if !value.is_finite() {
+            return Err(error());
+        }
+        match unit {
+            "mm" => Ok(Self::mm(value)),
+            "in" => Ok(Self::inches(value)),
+            "pt" => Ok(Self::points(value)),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Millimetres, without trailing zeros: `"3.175mm"`, `"210mm"`
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.micrometres < 0 { "-" } else { "" };
+        let whole = self.micrometres.unsigned_abs() / MICROMETRES_PER_MM as u64;
+        let fraction = self.micrometres.unsigned_abs() % MICROMETRES_PER_MM as u64;
+        if fraction == 0 {
+            return write!(f, "{}{}mm", sign, whole);
+        }
+        let digits = format!("{:03}", fraction);
+        write!(f, "{}{}.{}mm", sign, whole, digits.trim_end_matches('0'))
+    }
+}
+
+impl Serialize for Length {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Length {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Length::parse(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// What `Length::parse` accepts: a decimal number, then mm, in or pt
+#[cfg(feature = "schema")]
+const PATTERN: &str = r"^\s*[+-]?([0-9]+\.?[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?\s*(mm|in|pt)\s*$";
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Length {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Length".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({ "type": "string", "pattern": PATTERN })
+    }
+}
+
+/// Schema of the `inches` and `millimetres` fields: the old number, or a
+/// unit string
+#[cfg(feature = "schema")]
+pub fn legacy_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({ "anyOf": [{ "type": "number" }, generator.subschema_for::<Length>()] })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyLength {
+    Number(f64),
+    Text(String),
+}
+
+fn legacy<'de, D: Deserializer<'de>>(deserializer: D, number: fn(f64) -> Length) -> Result<Length, D::Error> {
+    match LegacyLength::deserialize(deserializer)? {
+        LegacyLength::Number(value) if value.is_finite() => Ok(number(value)),
+        LegacyLength::Number(value) => Err(serde::de::Error::custom(format!("invalid length {}", value))),
+        LegacyLength::Text(text) => Length::parse(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+/// `#[serde(with)]` for fields that were a number of inches
+pub mod inches {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(length: &Length, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(length.as_inches())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Length, D::Error> {
+        legacy(deserializer, Length::inches)
+    }
+}
+
+/// `#[serde(with)]` for fields that were a number of millimetres
+pub mod millimetres {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(length: &Length, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(length.as_mm())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Length, D::Error> {
+        legacy(deserializer, Length::mm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mm_inches_mm_round_trip_within_a_micrometre() {
+        for mm in [0.0, 0.001, 3.0, 3.175, 25.4, 85.0, 88.9, 215.9, 297.0, 1016.0, 1234.567] {
+            let back = Length::inches(Length::mm(mm).as_inches()).as_mm();
+            assert!((back - mm).abs() <= 0.001, "{} mm came back as {} mm", mm, back);
+        }
+        assert_eq!(Length::inches(0.125), Length::mm(3.175));
+        assert_eq!(Length::points(72.0), Length::inches(1.0));
+        assert_eq!(Length::inches(3.0 / 25.4), Length::mm(3.0));
+    }
+
+    #[test]
+    fn test_constructors_round_to_the_nearest_micrometre() {
+        assert_eq!(Length::mm(0.0004).micrometres(), 0);
+        assert_eq!(Length::mm(0.0005).micrometres(), 1);
+        assert_eq!(Length::mm(-0.0005).micrometres(), -1);
+        assert_eq!(Length::points(1.0).micrometres(), 353);
+        assert_eq!(Length::mm(f64::NAN), Length::ZERO);
+        assert_eq!(Length::from_pixels(18, 72), Length::mm(6.35));
+        assert_eq!(Length::from_pixels(1, 72).micrometres(), 353);
+    }
+
+    #[test]
+    fn test_pixels_saturate_instead_of_wrapping() {
+        let huge = Length::from_micrometres(i64::MAX);
+        assert_eq!(huge.checked_pixels(1200), None);
+        assert_eq!(huge.to_pixels(1200), u32::MAX);
+        assert_eq!(Length::mm(-3.0).checked_pixels(300), Some(0));
+    }
+
+    #[test]
+    fn test_pixels_round_halves_up() {
+        // 0.5 in at 1 dpi is exactly half a pixel
+        assert_eq!(Length::mm(12.7).to_pixels(1), 1);
+        assert_eq!(Length::mm(12.699).to_pixels(1), 0);
+        assert_eq!(Length::mm(210.0).to_pixels(300), 2480);
+        assert_eq!(Length::mm(-1.0).to_pixels(300), 0);
+    }
+
+    #[test]
+    fn test_text_form() {
+        assert_eq!(Length::mm(3.175).to_string(), "3.175mm");
+        assert_eq!(Length::mm(210.0).to_string(), "210mm");
+        assert_eq!(Length::mm(0.05).to_string(), "0.05mm");
+        assert_eq!(Length::mm(-1.5).to_string(), "-1.5mm");
+        assert_eq!(Length::parse("0.125in"), Ok(Length::mm(3.175)));
+        assert_eq!(Length::parse(" 9 pt"), Ok(Length::points(9.0)));
+        assert_eq!(Length::parse(&Length::mm(88.9).to_string()), Ok(Length::mm(88.9)));
+        for bad in ["", "mm", "3", "3cm", "inf in", "3 µm"] {
+            assert!(Length::parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Legacy {
+            #[serde(with = "inches")]
+            bleed: Length,
+            #[serde(with = "millimetres")]
+            width: Length,
+        }
+        let parsed: Legacy = serde_json::from_str(r#"{"bleed": 0.125, "width": 210}"#).unwrap();
+        assert_eq!(parsed, Legacy { bleed: Length::mm(3.175), width: Length::mm(210.0) });
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), r#"{"bleed":0.125,"width":210.0}"#);
+        let parsed: Legacy = serde_json::from_str(r#"{"bleed": "3mm", "width": "8.5in"}"#).unwrap();
+        assert_eq!(parsed, Legacy { bleed: Length::mm(3.0), width: Length::mm(215.9) });
+        assert_eq!(serde_json::to_string(&Length::mm(215.9)).unwrap(), r#""215.9mm""#);
+        assert!(serde_json::from_str::<Length>("3").is_err());
+        assert!(serde_json::from_str::<Legacy>(r#"{"bleed": "3", "width": 1}"#).is_err());
+    }
+}
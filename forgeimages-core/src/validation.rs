@@ -3,10 +3,23 @@
 //! Rules produce structured violations.
 //! Policy maps violations to actions.
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
 use serde::{Deserialize, Serialize};
-use crate::templates::{Template, FailureMode};
+use crate::color::SpotColors;
+use crate::font::LoadedFont;
+use crate::pipeline::{slot_values, CompileRequest};
+use crate::print::{self, presets::PhysicalSize};
+use crate::svg;
+use crate::svg_coverage;
+use crate::units::Length;
+use crate::templates::{Template, FailureMode, ScalingDecision, ScalingPolicy};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ViolationSeverity {
     Error,
@@ -15,6 +28,8 @@ pub enum ViolationSeverity {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ValidationViolation {
     pub rule: String,
     pub severity: ViolationSeverity,
@@ -25,6 +40,8 @@ pub struct ValidationViolation {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ValidationResult {
     pub valid: bool,
     pub violations: Vec<ValidationViolation>,
@@ -54,16 +71,84 @@ impl ValidationResult {
     pub fn has_errors(&self) -> bool {
         self.violations.iter().any(|v| v.severity == ViolationSeverity::Error)
     }
+
+    pub fn warning_count(&self) -> u32 {
+        self.violations.iter().filter(|v| v.severity == ViolationSeverity::Warning).count() as u32
+    }
+
+    /// What changed from `self` to `other`; violations are the same when
+    /// rule, severity and message are
+    pub fn diff(&self, other: &ValidationResult) -> ValidationDiff {
+        let same = |a: &ValidationViolation, b: &ValidationViolation| {
+            a.rule == b.rule && a.severity == b.severity && a.message == b.message
+        };
+        // Each violation pairs with at most one on the other side
+        let mut unmatched: Vec<&ValidationViolation> = other.violations.iter().collect();
+        let mut removed = vec![];
+        for violation in &self.violations {
+            match unmatched.iter().position(|v| same(v, violation)) {
+                Some(i) => {
+                    unmatched.remove(i);
+                }
+                None => removed.push(violation.clone()),
+            }
+        }
+        ValidationDiff {
+            valid: (self.valid != other.valid).then_some([self.valid, other.valid]),
+            added: unmatched.into_iter().cloned().collect(),
+            removed,
+        }
+    }
+}
+
+/// `ValidationResult::diff`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationDiff {
+    /// Before and after, when validity changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid: Option<[bool; 2]>,
+    pub added: Vec<ValidationViolation>,
+    pub removed: Vec<ValidationViolation>,
+}
+
+impl ValidationDiff {
+    pub fn is_empty(&self) -> bool {
+        self.valid.is_none() && self.added.is_empty() && self.removed.is_empty()
+    }
 }
 
 /// Validation rule trait - produces violations
-pub trait ValidationRule {
+pub trait ValidationRule: Send + Sync {
     fn name(&self) -> &'static str;
     fn validate(&self, input: &AssetInput, template: &Template) -> Vec<ValidationViolation>;
 }
 
+/// What request-level rules can inspect beyond the asset dimensions
+pub struct RequestContext<'a> {
+    pub request: &'a CompileRequest,
+    /// Decoded source bytes, when the request carries a source
+    pub source: Option<&'a [u8]>,
+    /// Template font, or why it could not be loaded; `None` if none declared
+    pub font: Option<&'a Result<LoadedFont, String>>,
+}
+
+/// Request-level rule - needs the full compile request, not just dimensions
+pub trait RequestRule: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation>;
+}
+
+/// Raster source size for scaling decisions; `None` when the source is SVG
+pub(crate) fn raster_source_size(input: &AssetInput, source: Option<&[u8]>) -> Option<[u32; 2]> {
+    let vector = source.is_some_and(svg::looks_like_svg)
+        || input.format.as_deref().is_some_and(|f| f.eq_ignore_ascii_case("svg"));
+    (!vector).then_some([input.width, input.height])
+}
+
 /// Input for validation
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(optional_fields = nullable))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct AssetInput {
     pub width: u32,
     pub height: u32,
@@ -93,7 +178,7 @@ impl ValidationRule for AspectRatioRule {
             vec![ValidationViolation {
                 rule: self.name().to_string(),
                 severity: ViolationSeverity::Error,
-                message: format!("Aspect ratio mismatch"),
+                message: "Aspect ratio mismatch".to_string(),
                 expected: Some(format!("{}:{}", template.aspect_ratio[0], template.aspect_ratio[1])),
                 actual: Some(format!("{:.3}", actual)),
                 remediation: vec!["Crop or resize to match template aspect ratio".to_string()],
@@ -160,9 +245,449 @@ impl ValidationRule for ColorCountRule {
     }
 }
 
+pub struct SeedRequiredRule;
+
+impl RequestRule for SeedRequiredRule {
+    fn name(&self) -> &'static str { "seed_required" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        if template.background_generator.is_none() || ctx.request.seed.is_some() {
+            return vec![];
+        }
+        vec![ValidationViolation {
+            rule: self.name().to_string(),
+            severity: ViolationSeverity::Error,
+            message: "Template generates a background but no seed was supplied".to_string(),
+            expected: Some("seed".to_string()),
+            actual: None,
+            remediation: vec!["Pass a seed so the background is reproducible".to_string()],
+        }]
+    }
+}
+
+pub struct TextSlotRule;
+
+impl TextSlotRule {
+    fn violation(&self, rule: &str, message: String, expected: Option<String>, actual: Option<String>, fix: &str) -> ValidationViolation {
+        ValidationViolation {
+            rule: rule.to_string(),
+            severity: ViolationSeverity::Error,
+            message,
+            expected,
+            actual,
+            remediation: vec![fix.to_string()],
+        }
+    }
+}
+
+impl RequestRule for TextSlotRule {
+    fn name(&self) -> &'static str { "text_slots" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let mut violations = vec![];
+
+        if !template.text_slots.is_empty() || template.font.is_some() {
+            let problem = match ctx.font {
+                None => Some("template declares no font".to_string()),
+                Some(Err(e)) => Some(e.clone()),
+                Some(Ok(_)) => None,
+            };
+            if let Some(problem) = problem {
+                violations.push(self.violation(
+                    "font_missing",
+                    "Template font is unavailable".to_string(),
+                    template.font.as_ref().map(|f| f.path.clone()),
+                    Some(problem),
+                    "Bundle the font file referenced by the template",
+                ));
+            }
+        }
+
+        let params = &ctx.request.params;
+        if params.is_empty() {
+            return violations;
+        }
+
+        let master_slots = ctx.source
+            .filter(|s| svg::looks_like_svg(s))
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .map(svg::slot_ids);
+
+        for (key, value) in params {
+            let Some(slot) = template.text_slots.iter().find(|s| &s.id == key) else {
+                violations.push(self.violation(
+                    "unknown_param",
+                    format!("Parameter '{}' is not declared by the template", key),
+                    Some(template.text_slots.iter().map(|s| s.id.as_str()).collect::<Vec<_>>().join(", ")),
+                    Some(key.clone()),
+                    "Remove the parameter or use a declared slot id",
+                ));
+                continue;
+            };
+
+            let Some(text) = value.as_str() else {
+                violations.push(self.violation(
+                    "slot_value_type",
+                    format!("Slot '{}' expects a string", key),
+                    Some("string".to_string()),
+                    Some(value.to_string()),
+                    "Pass slot values as strings",
+                ));
+                continue;
+            };
+
+            let length = text.chars().count();
+            if length > slot.max_length {
+                violations.push(self.violation(
+                    "slot_too_long",
+                    format!("Text for slot '{}' is too long", key),
+                    Some(format!("{} characters max", slot.max_length)),
+                    Some(format!("{} characters", length)),
+                    "Shorten the text",
+                ));
+            }
+
+            let present = match &master_slots {
+                Some(Ok(ids)) => ids.contains(key),
+                _ => false,
+            };
+            if !present {
+                let actual = match &master_slots {
+                    None => "no SVG source".to_string(),
+                    Some(Err(e)) => e.to_string(),
+                    Some(Ok(_)) => "slot not found in SVG master".to_string(),
+                };
+                violations.push(self.violation(
+                    "slot_missing",
+                    format!("SVG master has no element for slot '{}'", key),
+                    Some(format!("element with {}=\"{}\"", svg::SLOT_ATTRIBUTE, key)),
+                    Some(actual),
+                    "Add the slot attribute to the SVG master",
+                ));
+            }
+        }
+
+        violations
+    }
+}
+
+pub struct PrintOverrideRule;
+
+impl RequestRule for PrintOverrideRule {
+    fn name(&self) -> &'static str { "print_override" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let Some(user) = &ctx.request.print_spec else {
+            return vec![];
+        };
+        let violation = |message: String| ValidationViolation {
+            rule: self.name().to_string(),
+            severity: ViolationSeverity::Error,
+            message,
+            expected: None,
+            actual: Some(format!("{} dpi, {:?}, {} bleed", user.dpi, user.color_space, user.bleed)),
+            remediation: vec!["Remove print_spec to use the template print settings".to_string()],
+        };
+        if let Err(message) = print::PrintSpec::check_bounds(user.dpi, user.bleed) {
+            return vec![violation(message.to_string())];
+        }
+        let closed = print::closed_blocks(template);
+        if !closed.is_empty() {
+            return vec![violation(format!("Template does not allow user print overrides ({})", closed.join(", ")))];
+        }
+
+        // Refused fields, with a color space outside the block's allowlist
+        // (rather than locked) reported on its own, naming the allowed set
+        let refused = |resolved: print::Resolved, block: Option<&print::TemplatePrint>| -> Vec<String> {
+            let policy = block.map_or(print::OverridePolicy::ALL, print::TemplatePrint::override_policy);
+            let (listed, locked): (Vec<_>, Vec<_>) = resolved.refused(user).into_iter()
+                .partition(|&field| field == print::PrintField::ColorSpace && policy.allows(field));
+            let locked: Vec<_> = locked.iter().map(print::PrintField::name).collect();
+            let locked = (!locked.is_empty()).then(|| format!("Template does not allow user overrides of {}", locked.join(", ")));
+            let listed = (!listed.is_empty()).then(|| format!(
+                "Template does not allow user color space {:?} (allowed: {})",
+                user.color_space,
+                policy.user_color_spaces.iter().map(|c| format!("{:?}", c)).collect::<Vec<_>>().join(", "),
+            ));
+            locked.into_iter().chain(listed).collect()
+        };
+        // Exports without their own block resolve exactly as the template does
+        let template_level = refused(print::resolve_block(Some(user), template.print.as_ref()), template.print.as_ref());
+        let export_level = template.exports.iter()
+            .filter(|spec| spec.print.is_some())
+            .flat_map(|spec| {
+                let refusals = print::resolve_export(Some(user), template.print.as_ref(), spec)
+                    .map_or(vec![], |resolved| refused(resolved, spec.print.as_ref()));
+                refusals.into_iter().map(|message| format!("exports[{}]: {}", spec.id, message))
+            });
+        let mut violations: Vec<_> = template_level.into_iter().chain(export_level).map(violation).collect();
+
+        // What the user changed, for the record (the manifest lists it too)
+        let overrides = print::resolve_block(Some(user), template.print.as_ref()).overrides;
+        if !overrides.is_empty() {
+            let list = |value: fn(&print::PrintOverride) -> &serde_json::Value| overrides.iter()
+                .map(|o| format!("{} {}", o.field, value(o)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            violations.push(ValidationViolation {
+                rule: self.name().to_string(),
+                severity: ViolationSeverity::Info,
+                message: format!(
+                    "User print spec overrides {}",
+                    overrides.iter().map(|o| o.field.as_str()).collect::<Vec<_>>().join(", "),
+                ),
+                expected: Some(list(|o| &o.template_value)),
+                actual: Some(list(|o| &o.user_value)),
+                remediation: vec![],
+            });
+        }
+        violations
+    }
+}
+
+pub struct ScalingRule;
+
+impl RequestRule for ScalingRule {
+    fn name(&self) -> &'static str { "scaling_policy" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let input = &ctx.request.asset_input;
+        let source_size = raster_source_size(input, ctx.source);
+        template.exports.iter()
+            .filter_map(|spec| {
+                let record = spec.scaling(template.scaling_policy, source_size);
+                if record.decision != ScalingDecision::Upscaled {
+                    return None;
+                }
+                let (rule, severity) = match record.policy {
+                    ScalingPolicy::ForbidUpscale => ("upscale_forbidden", ViolationSeverity::Error),
+                    ScalingPolicy::AllowUpscaleWithWarning => ("upscale", ViolationSeverity::Warning),
+                    ScalingPolicy::Allow => return None,
+                };
+                Some(ValidationViolation {
+                    rule: rule.to_string(),
+                    severity,
+                    message: format!("Export {} is larger than the raster source", spec.id),
+                    expected: Some(format!("{}x{} or smaller", input.width, input.height)),
+                    actual: Some(format!("{}x{}", spec.size[0], spec.size[1])),
+                    remediation: vec![
+                        "Provide a higher resolution or SVG source".to_string(),
+                    ],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Raster sources must reach the resolved print dpi at the physical size
+/// of every print export: effective dpi is source pixels per trim inch,
+/// per axis. Short by at most `warning_band` of the dpi is a Warning,
+/// beyond that an Error; one violation names the export the source
+/// falls furthest short for (the largest, when dpi agree). SVG sources
+/// are exempt.
+pub struct EffectiveDpiRule;
+
+impl RequestRule for EffectiveDpiRule {
+    fn name(&self) -> &'static str { "effective_dpi" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let config = &template.validation.rules.print.effective_dpi;
+        if !config.enabled || !config.asset_classes.contains(&template.asset_class) {
+            return vec![];
+        }
+        let input = &ctx.request.asset_input;
+        let Some(source) = raster_source_size(input, ctx.source) else {
+            return vec![];
+        };
+
+        let user = ctx.request.print_spec.as_ref();
+        let worst = template.exports.iter()
+            .filter_map(|spec| {
+                let dpi = print::resolve_export(user, template.print.as_ref(), spec)?.spec.dpi;
+                let inches = spec.size.map(|px| px as f64 / dpi as f64);
+                let effective = [0, 1].map(|axis| source[axis] as f64 / inches[axis]);
+                let ratio = effective[0].min(effective[1]) / dpi as f64;
+                Some((ratio, spec, dpi, inches, effective))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let Some((ratio, spec, dpi, inches, effective)) = worst else {
+            return vec![];
+        };
+        if ratio >= 1.0 {
+            return vec![];
+        }
+
+        let severity = if ratio >= 1.0 - config.warning_band { ViolationSeverity::Warning } else { ViolationSeverity::Error };
+        vec![ValidationViolation {
+            rule: self.name().to_string(),
+            severity,
+            message: format!("Source is below {} dpi at the physical size of export {}", dpi, spec.id),
+            expected: Some(format!("{} dpi at {:.2}x{:.2} in", dpi, inches[0], inches[1])),
+            actual: Some(format!(
+                "{:.1}x{:.1} dpi from a {}x{} source",
+                effective[0], effective[1], source[0], source[1],
+            )),
+            remediation: vec![
+                "Provide a higher resolution or SVG source".to_string(),
+                "Lower the print dpi or the export's physical size".to_string(),
+            ],
+        }]
+    }
+}
+
+/// SVG content must keep clear of the safe margin (`safeMargin`) inside
+/// the trim of print exports. The master, with text slots filled (and
+/// outlined when the font loads), is rasterized at the trim size at
+/// `analysisDpi`; pixels covered above `coverageThreshold` within the
+/// margin band intrude. One Error per distinct analysis size and margin,
+/// naming its exports and the intruding share of each edge, so designers
+/// know which side to fix. Raster sources are not inspected.
+pub struct SafeMarginRule;
+
+const EDGES: [&str; 4] = ["left", "top", "right", "bottom"];
+
+/// (analysis size, band pixels) -> (margin, export ids)
+type MarginChecks<'a> = BTreeMap<([u32; 2], u32), (Length, Vec<&'a str>)>;
+
+impl RequestRule for SafeMarginRule {
+    fn name(&self) -> &'static str { "safe_margin" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let config = &template.validation.rules.print.safe_margin;
+        if !config.enabled {
+            return vec![];
+        }
+        let analysis_dpi = config.analysis_dpi.max(1);
+        let user = ctx.request.print_spec.as_ref();
+        let mut checks = MarginChecks::new();
+        for spec in &template.exports {
+            let block = spec.print.as_ref().or(template.print.as_ref());
+            let Some(margin) = block.and_then(|b| b.safe_margin).filter(|m| *m > Length::ZERO) else { continue };
+            let Some(resolved) = print::resolve_export(user, template.print.as_ref(), spec) else { continue };
+            let [width, height] = spec.size.map(|px| Length::from_pixels(px, resolved.spec.dpi));
+            let Ok(size) = print::raster_dimensions(PhysicalSize::new(width, height), analysis_dpi) else { continue };
+            let size = size.map(|px| px.max(1));
+            checks.entry((size, margin.to_pixels(analysis_dpi)))
+                .or_insert_with(|| (margin, vec![]))
+                .1
+                .push(&spec.id);
+        }
+        if checks.is_empty() {
+            return vec![];
+        }
+        let Some(master) = ctx.source
+            .filter(|s| svg::looks_like_svg(s))
+            .and_then(|s| std::str::from_utf8(s).ok())
+        else {
+            return vec![];
+        };
+        let master = analysis_master(master, ctx, template);
+        // c / 255 > t exactly when c > floor(t * 255)
+        let threshold = (config.coverage_threshold.clamp(0.0, 1.0) * 255.0).floor() as u8;
+
+        let mut violations = vec![];
+        for ((size, band), (margin, ids)) in checks {
+            // Masters that do not parse are reported by the text slot rule
+            let Ok(coverage) = svg_coverage::rasterize(&master, size) else {
+                return vec![];
+            };
+            let intrusion = coverage.edge_intrusion(band, threshold);
+            let intruding: Vec<&str> = EDGES.iter().zip(intrusion)
+                .filter(|(_, share)| *share > 0.0)
+                .map(|(edge, _)| *edge)
+                .collect();
+            if intruding.is_empty() {
+                continue;
+            }
+            violations.push(ValidationViolation {
+                rule: self.name().to_string(),
+                severity: ViolationSeverity::Error,
+                message: format!(
+                    "Content intrudes into the {} safe margin of export {} ({})",
+                    margin, ids.join(", "), intruding.join(", "),
+                ),
+                expected: Some(format!("no content within {} of the trim", margin)),
+                actual: Some(EDGES.iter().zip(intrusion)
+                    .map(|(edge, share)| format!("{} {:.1}%", edge, share))
+                    .collect::<Vec<_>>()
+                    .join(", ")),
+                remediation: vec![
+                    format!("Move content on the {} edge inward", intruding.join(", ")),
+                    format!("Mark artwork meant to run into the bleed with {}", svg_coverage::BLEED_ATTRIBUTE),
+                ],
+            });
+        }
+        violations
+    }
+}
+
+/// The master as it renders: slots filled, outlined when the font loaded
+fn analysis_master<'a>(master: &'a str, ctx: &RequestContext<'_>, template: &Template) -> Cow<'a, str> {
+    let values = slot_values(template, ctx.request);
+    let mut master = Cow::Borrowed(master);
+    if !values.is_empty() {
+        if let Ok(filled) = svg::fill_slots(&master, &values) {
+            master = Cow::Owned(filled);
+        }
+    }
+    if let Some(Ok(font)) = ctx.font {
+        if let Ok(outlined) = svg::outline_slots(&master, &font.font) {
+            master = Cow::Owned(outlined);
+        }
+    }
+    master
+}
+
+/// Colors the SVG master paints that no spot color maps, as Info, for
+/// templates that declare spot colors on any print block. Raster sources
+/// are not inspected.
+pub struct SpotColorRule;
+
+impl RequestRule for SpotColorRule {
+    fn name(&self) -> &'static str { "spot_color_unmapped" }
+
+    fn validate(&self, ctx: &RequestContext<'_>, template: &Template) -> Vec<ValidationViolation> {
+        let blocks = template.print.iter().chain(template.exports.iter().filter_map(|spec| spec.print.as_ref()));
+        // Invalid tables are reported when the template loads or compiles
+        let tables: Vec<_> = blocks
+            .filter(|block| !block.spot_colors.is_empty())
+            .filter_map(|block| SpotColors::new(&block.spot_colors).ok())
+            .collect();
+        if tables.is_empty() {
+            return vec![];
+        }
+        let Some(Ok(colors)) = ctx.source
+            .filter(|s| svg::looks_like_svg(s))
+            .and_then(|s| std::str::from_utf8(s).ok())
+            .map(svg::paint_colors)
+        else {
+            return vec![];
+        };
+
+        colors.into_iter()
+            .filter(|&rgb| !tables.iter().any(|table| table.contains(rgb)))
+            .map(|[r, g, b]| {
+                let hex = format!("#{:02X}{:02X}{:02X}", r, g, b);
+                ValidationViolation {
+                    rule: self.name().to_string(),
+                    severity: ViolationSeverity::Info,
+                    message: format!("Source color {} has no spot color mapping", hex),
+                    expected: Some("a spotColors entry".to_string()),
+                    actual: Some(hex),
+                    remediation: vec![
+                        "Add a spot color mapping if this is a brand color".to_string(),
+                    ],
+                }
+            })
+            .collect()
+    }
+}
+
 /// Validator orchestrates rules and applies policy
 pub struct Validator {
     rules: Vec<Box<dyn ValidationRule>>,
+    request_rules: Vec<Box<dyn RequestRule>>,
+    fail_on_warnings: bool,
 }
 
 impl Validator {
@@ -173,17 +698,61 @@ impl Validator {
                 Box::new(ResolutionRule),
                 Box::new(ColorCountRule),
             ],
+            request_rules: vec![
+                Box::new(SeedRequiredRule),
+                Box::new(TextSlotRule),
+                Box::new(PrintOverrideRule),
+                Box::new(ScalingRule),
+                Box::new(EffectiveDpiRule),
+                Box::new(SafeMarginRule),
+                Box::new(SpotColorRule),
+            ],
+            fail_on_warnings: false,
         }
     }
 
+    /// Treat any Warning as blocking, whatever the template's failure mode.
+    /// This only tightens policy: errors block exactly as before.
+    pub fn fail_on_warnings(mut self, fail: bool) -> Self {
+        self.fail_on_warnings = fail;
+        self
+    }
+
     pub fn validate(&self, input: &AssetInput, template: &Template) -> ValidationResult {
+        let violations = self.input_violations(input, template);
+        self.apply_policy(template, violations)
+    }
+
+    /// Validate a full compile request: asset input rules plus request rules
+    pub fn validate_request(&self, ctx: &RequestContext<'_>, template: &Template) -> ValidationResult {
+        let mut violations = self.input_violations(&ctx.request.asset_input, template);
+        for rule in &self.request_rules {
+            violations.extend(timed(rule.name(), || rule.validate(ctx, template)));
+        }
+        self.apply_policy(template, violations)
+    }
+
+    /// Only the `print_override` rule, under the template's failure mode
+    pub fn validate_print_override(&self, ctx: &RequestContext<'_>, template: &Template) -> ValidationResult {
+        self.apply_policy(template, PrintOverrideRule.validate(ctx, template))
+    }
+
+    fn input_violations(&self, input: &AssetInput, template: &Template) -> Vec<ValidationViolation> {
         let mut all_violations = vec![];
 
         for rule in &self.rules {
-            let violations = rule.validate(input, template);
+            let violations = timed(rule.name(), || rule.validate(input, template));
             all_violations.extend(violations);
         }
 
+        all_violations
+    }
+
+    fn apply_policy(&self, template: &Template, all_violations: Vec<ValidationViolation>) -> ValidationResult {
+        if self.fail_on_warnings && all_violations.iter().any(|v| v.severity == ViolationSeverity::Warning) {
+            return ValidationResult::failure(template, all_violations);
+        }
+
         // Apply failure mode policy
         let has_errors = all_violations.iter()
             .any(|v| v.severity == ViolationSeverity::Error);
@@ -216,6 +785,18 @@ impl Validator {
     }
 }
 
+/// Run one rule, logging how long it took. The clock is only read when
+/// the timing is logged: wasm32 has none.
+fn timed(rule: &'static str, validate: impl FnOnce() -> Vec<ValidationViolation>) -> Vec<ValidationViolation> {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return validate();
+    }
+    let started = Instant::now();
+    let violations = validate();
+    tracing::debug!(rule, violations = violations.len(), elapsed_us = started.elapsed().as_micros() as u64, "validation rule");
+    violations
+}
+
 impl Default for Validator {
     fn default() -> Self {
         Self::new()
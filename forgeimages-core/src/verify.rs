@@ -0,0 +1,459 @@
+//! Verification - Checking Written Manifests
+//!
+//! Works on what is on disk, not on in-memory assets, so third parties can
+//! run the same checks on a delivered directory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::hashing::{parse_digest, sha256_hex, verify_digest, verify_hex, HashAlgorithm, HashScheme};
+use crate::merkle::root_of;
+use crate::pipeline::{CompilationPipeline, CompiledAsset, CompileRequest, ManifestHashView};
+use crate::output::{is_plain_file_name, sidecar_name, CHECKSUMS_FILE, MANIFEST_FILE, MANIFEST_HASH_FILE, SIGNATURE_FILE};
+use crate::templates::TemplateRegistry;
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed manifest: {0}")]
+    Malformed(String),
+
+    #[error("Manifest hash mismatch: recorded {0}, computed {1}")]
+    ManifestHashMismatch(String, String),
+
+    #[error("manifest.json does not match {MANIFEST_HASH_FILE}")]
+    FileHashMismatch,
+
+    #[error("Export file does not match manifest: {0}")]
+    ExportHashMismatch(String),
+
+    #[error("exports_root does not match the listed exports: recorded {0}, computed {1}")]
+    ExportsRootMismatch(String, String),
+
+    #[error("Template {0} is not loaded")]
+    TemplateMissing(String),
+
+    /// Template id, recorded hash, hash of the loaded template
+    #[error("Template {0} changed since compile: recorded {1}, loaded {2}")]
+    TemplateDrift(String, String, String),
+
+    #[error("Job hash is keyed with {0}; verifying it needs that key")]
+    JobHashKeyRequired(String),
+
+    /// Key id the manifest records, key id of the verifier (`unkeyed` for none)
+    #[error("Job hash key mismatch: manifest {0}, verifier {1}")]
+    JobHashKeyMismatch(String, String),
+
+    #[error("Job hash mismatch: recorded {0}, computed {1}")]
+    JobHashMismatch(String, String),
+
+    #[error("Could not recompute job hash: {0}")]
+    JobHashUnavailable(String),
+
+    #[error("Manifest mixes hash algorithms: {0}")]
+    MixedAlgorithms(String),
+
+    #[error("Signature missing: {0}")]
+    SignatureMissing(String),
+
+    #[error("Signature invalid")]
+    SignatureInvalid,
+}
+
+/// Check manifest.json against its sidecar and its own manifest hash.
+///
+/// Returns the verified manifest hash.
+pub fn verify_manifest(manifest_path: &Path) -> Result<String, VerifyError> {
+    let content = fs::read(manifest_path)?;
+
+    // The sidecar names the file it covers; copies beside it (a signed copy, say) are not that file
+    let sidecar = manifest_path.with_file_name(MANIFEST_HASH_FILE);
+    if sidecar.exists() {
+        let line = fs::read_to_string(&sidecar)?;
+        let mut fields = line.split_whitespace();
+        let recorded = fields.next().unwrap_or_default();
+        let covers = fields.next().is_none_or(|name| manifest_path.file_name().is_some_and(|own| own == name));
+        if covers {
+            verify_hex(recorded, &content).map_err(|_| VerifyError::FileHashMismatch)?;
+        }
+    }
+
+    let manifest: Value = serde_json::from_slice(&content)
+        .map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    let recorded = manifest.get("manifest_hash")
+        .and_then(Value::as_str)
+        .ok_or_else(|| VerifyError::Malformed("manifest_hash missing".into()))?
+        .to_string();
+    let (algorithm, _) = parse_digest(&recorded).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    check_algorithms(&manifest, algorithm)?;
+    let scheme = match manifest.get("hash_scheme") {
+        Some(declared) => declared.as_str()
+            .and_then(HashScheme::from_name)
+            .ok_or_else(|| VerifyError::Malformed(format!("unknown hash_scheme {}", declared)))?,
+        None => HashScheme::V0,
+    };
+    let computed = ManifestHashView::from_json(&manifest).hash(scheme, algorithm)
+        .map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    if verify_digest(&recorded, &computed).is_err() {
+        return Err(VerifyError::ManifestHashMismatch(recorded, computed));
+    }
+    check_exports_root(&manifest)?;
+    Ok(recorded)
+}
+
+/// A recorded `exports_root` must be the root over the listed exports
+fn check_exports_root(manifest: &Value) -> Result<(), VerifyError> {
+    let Some(recorded) = manifest.get("exports_root").and_then(Value::as_str) else { return Ok(()) };
+    let leaves = manifest.get("exports").and_then(Value::as_array).into_iter().flatten()
+        .map(|e| match (e.get("id").and_then(Value::as_str), e.get("hash").and_then(Value::as_str)) {
+            (Some(id), Some(hash)) => Ok((id, hash)),
+            _ => Err(VerifyError::Malformed("export without id or hash".into())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let computed = root_of(leaves).map_err(|e| VerifyError::Malformed(e.to_string()))?.unwrap_or_default();
+    if verify_digest(recorded, &computed).is_err() {
+        return Err(VerifyError::ExportsRootMismatch(recorded.to_string(), computed));
+    }
+    Ok(())
+}
+
+/// Every digest in a manifest must use the manifest hash's algorithm
+fn check_algorithms(manifest: &Value, algorithm: HashAlgorithm) -> Result<(), VerifyError> {
+    let mixed = |field: &str| VerifyError::MixedAlgorithms(format!("{} is not {}", field, algorithm.prefix()));
+
+    if let Some(declared) = manifest.get("hash_algorithm") {
+        if serde_json::from_value::<HashAlgorithm>(declared.clone()).ok() != Some(algorithm) {
+            return Err(mixed("hash_algorithm"));
+        }
+    }
+    // Keyed job hashes are HMAC-SHA256 whatever the manifest's algorithm
+    let keyed = manifest.get("job_hash_keyed").and_then(Value::as_bool).unwrap_or(false);
+    let exports = manifest.get("exports").and_then(Value::as_array).into_iter().flatten();
+    let digests = ["job_hash", "template_hash", "config_hash", "source_hash", "normalized_source_hash", "font_hash", "exports_root"]
+        .into_iter()
+        .filter(|field| !(keyed && *field == "job_hash"))
+        .map(|field| (field.to_string(), manifest.get(field)))
+        .chain(exports.enumerate().map(|(i, e)| (format!("exports[{}].hash", i), e.get("hash"))));
+    for (field, digest) in digests {
+        let Some(digest) = digest.and_then(Value::as_str) else { continue };
+        match parse_digest(digest) {
+            Ok((found, _)) if found == algorithm => {}
+            _ => return Err(mixed(&field)),
+        }
+    }
+    Ok(())
+}
+
+/// Verify a directory written by `compile_to_dir`: the manifest, then every
+/// export file it lists against its recorded hash.
+///
+/// Returns the verified manifest hash.
+pub fn verify_exports(dir: &Path) -> Result<String, VerifyError> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let manifest_hash = verify_manifest(&manifest_path)?;
+
+    let manifest: Value = serde_json::from_slice(&fs::read(&manifest_path)?)
+        .map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    for (filename, hash) in listed_exports(&manifest)? {
+        let (algorithm, _) = parse_digest(&hash).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+        let computed = algorithm.digest_reader(fs::File::open(dir.join(&filename))?)?;
+        verify_digest(&hash, &computed).map_err(|e| VerifyError::ExportHashMismatch(format!("{}: {}", filename, e)))?;
+    }
+    Ok(manifest_hash)
+}
+
+/// `(filename, hash)` of every export a manifest lists; filenames must be plain names
+fn listed_exports(manifest: &Value) -> Result<Vec<(String, String)>, VerifyError> {
+    let exports = manifest.get("exports")
+        .and_then(Value::as_array)
+        .ok_or_else(|| VerifyError::Malformed("exports missing".into()))?;
+    exports.iter()
+        .map(|export| {
+            let (Some(filename), Some(hash)) = (
+                export.get("filename").and_then(Value::as_str),
+                export.get("hash").and_then(Value::as_str),
+            ) else {
+                return Err(VerifyError::Malformed("export without filename or hash".into()));
+            };
+            if !is_plain_file_name(filename) {
+                return Err(VerifyError::Malformed(format!("export filename is not a plain name: {}", filename)));
+            }
+            Ok((filename.to_string(), hash.to_string()))
+        })
+        .collect()
+}
+
+/// Which record a file was checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumSource {
+    Manifest,
+    /// The file's own `.sha256` sidecar
+    Sidecar,
+    /// `checksums.txt`
+    ChecksumsFile,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMismatch {
+    pub filename: String,
+    pub source: ChecksumSource,
+    /// Digest as recorded (sidecars and checksums.txt record bare sha256 hex)
+    pub expected: String,
+    pub actual: String,
+}
+
+/// What `verify_directory` found on disk, once the manifest itself verified
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirVerificationReport {
+    pub manifest_hash: String,
+    /// Exports the manifest lists, sidecars `checksums.txt` implies, and
+    /// files `checksums.txt` lists, that are not on disk
+    pub missing: Vec<String>,
+    /// Files on disk that nothing lists
+    pub extra: Vec<String>,
+    pub mismatches: Vec<FileMismatch>,
+}
+
+impl DirVerificationReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// Verify an asset directory before publishing it: the manifest, then every
+/// file on disk against the manifest, its `.sha256` sidecar and
+/// `checksums.txt` (see `output`).
+///
+/// A manifest that fails to verify is an error, since nothing it lists can
+/// be trusted; everything else lands in the report. Sidecars are expected
+/// for every export when `checksums.txt` is present.
+pub fn verify_directory(dir: &Path) -> Result<DirVerificationReport, VerifyError> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let mut report = DirVerificationReport { manifest_hash: verify_manifest(&manifest_path)?, ..Default::default() };
+    let manifest_bytes = fs::read(&manifest_path)?;
+    let manifest: Value = serde_json::from_slice(&manifest_bytes).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    let exports = listed_exports(&manifest)?;
+
+    let checksums_path = dir.join(CHECKSUMS_FILE);
+    let mut checksums = if checksums_path.exists() {
+        Some(read_checksums(&fs::read_to_string(&checksums_path)?, CHECKSUMS_FILE)?)
+    } else {
+        None
+    };
+    let mut mismatch = |filename: &str, source, expected: &str, actual: &str| {
+        report.mismatches.push(FileMismatch {
+            filename: filename.to_string(),
+            source,
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        });
+    };
+
+    let mut missing = vec![];
+    for (filename, hash) in &exports {
+        let path = dir.join(filename);
+        if !path.exists() {
+            missing.push(filename.clone());
+            continue;
+        }
+        let (algorithm, _) = parse_digest(hash).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+        let computed = algorithm.digest_reader(fs::File::open(&path)?)?;
+        if verify_digest(hash, &computed).is_err() {
+            mismatch(filename, ChecksumSource::Manifest, hash, &computed);
+        }
+        let sha256 = if algorithm == HashAlgorithm::Sha256 {
+            computed
+        } else {
+            HashAlgorithm::Sha256.digest_reader(fs::File::open(&path)?)?
+        };
+        let sha256 = sha256.split_once(':').map_or(sha256.as_str(), |(_, hex)| hex);
+
+        let sidecar = dir.join(sidecar_name(filename));
+        if sidecar.exists() {
+            let recorded = read_checksums(&fs::read_to_string(&sidecar)?, &sidecar_name(filename))?;
+            match recorded.get(filename.as_str()) {
+                Some(expected) if recorded.len() == 1 => {
+                    if verify_digest(expected, sha256).is_err() {
+                        mismatch(filename, ChecksumSource::Sidecar, expected, sha256);
+                    }
+                }
+                _ => return Err(VerifyError::Malformed(format!("{} does not name {}", sidecar_name(filename), filename))),
+            }
+        } else if checksums.is_some() {
+            missing.push(sidecar_name(filename));
+        }
+        if let Some(expected) = checksums.as_mut().and_then(|c| c.remove(filename.as_str())) {
+            if verify_digest(&expected, sha256).is_err() {
+                mismatch(filename, ChecksumSource::ChecksumsFile, &expected, sha256);
+            }
+        }
+    }
+    if let Some(checksums) = &mut checksums {
+        if let Some(expected) = checksums.remove(MANIFEST_FILE) {
+            if verify_hex(&expected, &manifest_bytes).is_err() {
+                mismatch(MANIFEST_FILE, ChecksumSource::ChecksumsFile, &expected, &sha256_hex(&manifest_bytes));
+            }
+        }
+        // Listed in checksums.txt only: missing, or reported as extra below
+        missing.extend(checksums.keys().filter(|name| !dir.join(name).exists()).cloned());
+    }
+
+    let mut known: BTreeSet<String> = [MANIFEST_FILE, MANIFEST_HASH_FILE, SIGNATURE_FILE, CHECKSUMS_FILE]
+        .map(String::from)
+        .into();
+    for (filename, _) in &exports {
+        known.insert(sidecar_name(filename));
+        known.insert(filename.clone());
+    }
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if !known.contains(&name) {
+            report.extra.push(name);
+        }
+    }
+
+    missing.sort();
+    missing.dedup();
+    report.missing = missing;
+    report.extra.sort();
+    Ok(report)
+}
+
+/// Parse `sha256sum` output (`<hex>  <name>`, or `<hex> *<name>` for binary mode)
+fn read_checksums(content: &str, source: &str) -> Result<BTreeMap<String, String>, VerifyError> {
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_once(' ')
+                .and_then(|(hex, name)| Some((hex, name.strip_prefix(' ').or_else(|| name.strip_prefix('*'))?)))
+                .filter(|(hex, name)| !hex.is_empty() && !name.is_empty())
+                .map(|(hex, name)| (name.to_string(), hex.to_string()))
+                .ok_or_else(|| VerifyError::Malformed(format!("{}: not a sha256sum line: {}", source, line)))
+        })
+        .collect()
+}
+
+/// Recompute a compiled asset's job hash from its request with `pipeline`.
+///
+/// A keyed job hash can only be checked by a pipeline holding the same key
+/// (matched by key id, then by the recomputed hash).
+pub fn verify_job_hash(
+    asset: &CompiledAsset,
+    request: &CompileRequest,
+    pipeline: &CompilationPipeline,
+) -> Result<(), VerifyError> {
+    let recorded_key = asset.job_hash_key_id.as_deref().filter(|_| asset.job_hash_keyed);
+    match (recorded_key, pipeline.job_hash_key_id()) {
+        (Some(recorded), None) => return Err(VerifyError::JobHashKeyRequired(recorded.to_string())),
+        (recorded, held) if recorded != held => {
+            return Err(VerifyError::JobHashKeyMismatch(
+                recorded.unwrap_or("unkeyed").to_string(),
+                held.unwrap_or("unkeyed").to_string(),
+            ));
+        }
+        _ => {}
+    }
+    let computed = pipeline.job_hash(request).map_err(|e| VerifyError::JobHashUnavailable(e.to_string()))?;
+    if verify_digest(&asset.job_hash, &computed).is_err() {
+        return Err(VerifyError::JobHashMismatch(asset.job_hash.clone(), computed));
+    }
+    Ok(())
+}
+
+/// Verify manifest integrity, then compare its `template_hash` against the
+/// template currently loaded under the same id.
+///
+/// Manifests written before template hashes were recorded pass the second
+/// check. Returns the verified manifest hash.
+pub fn verify_template(manifest_path: &Path, registry: &TemplateRegistry) -> Result<String, VerifyError> {
+    let manifest_hash = verify_manifest(manifest_path)?;
+    let manifest: Value = serde_json::from_slice(&fs::read(manifest_path)?)
+        .map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    let recorded = manifest.get("template_hash").and_then(Value::as_str).unwrap_or_default();
+    if recorded.is_empty() {
+        return Ok(manifest_hash);
+    }
+    let template_id = manifest.get("template_id")
+        .and_then(Value::as_str)
+        .ok_or_else(|| VerifyError::Malformed("template_id missing".into()))?;
+    let template = registry.get(template_id)
+        .ok_or_else(|| VerifyError::TemplateMissing(template_id.to_string()))?;
+
+    let (algorithm, _) = parse_digest(recorded).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    let current = template.content_hash_with(algorithm).map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    if verify_digest(recorded, &current).is_err() {
+        return Err(VerifyError::TemplateDrift(template_id.to_string(), recorded.to_string(), current));
+    }
+    Ok(manifest_hash)
+}
+
+/// Verify manifest integrity, then the `manifest.sig` beside it
+#[cfg(feature = "signing")]
+pub fn verify_signature(
+    manifest_path: &Path,
+    public_key: &crate::signing::VerifyingKey,
+) -> Result<(), VerifyError> {
+    verify_signature_file(manifest_path, &manifest_path.with_file_name(SIGNATURE_FILE), public_key)
+}
+
+/// Verify manifest integrity, then a detached signature file in the
+/// `manifest.sig` form: the base64 signature over the manifest hash
+#[cfg(feature = "signing")]
+pub fn verify_signature_file(
+    manifest_path: &Path,
+    signature_path: &Path,
+    public_key: &crate::signing::VerifyingKey,
+) -> Result<(), VerifyError> {
+    let manifest_hash = verify_manifest(manifest_path)?;
+    if !signature_path.exists() {
+        return Err(VerifyError::SignatureMissing(signature_path.display().to_string()));
+    }
+    let encoded = fs::read_to_string(signature_path)?;
+    check_signature(&manifest_hash, encoded.trim(), public_key)
+}
+
+/// Verify manifest integrity, then the `signature` block embedded in it
+/// (outside the manifest hash, so embedding leaves the hash alone).
+///
+/// Returns the block, whose `key_id` names the key it claims.
+#[cfg(feature = "signing")]
+pub fn verify_embedded_signature(
+    manifest_path: &Path,
+    public_key: &crate::signing::VerifyingKey,
+) -> Result<crate::signing::ManifestSignature, VerifyError> {
+    let manifest_hash = verify_manifest(manifest_path)?;
+    let manifest: Value = serde_json::from_slice(&fs::read(manifest_path)?)
+        .map_err(|e| VerifyError::Malformed(e.to_string()))?;
+    let block = manifest.get("signature")
+        .ok_or_else(|| VerifyError::SignatureMissing(format!("{} has no signature block", manifest_path.display())))?;
+    let signature: crate::signing::ManifestSignature = serde_json::from_value(block.clone())
+        .map_err(|e| VerifyError::Malformed(format!("signature: {}", e)))?;
+    if signature.algorithm != crate::signing::ALGORITHM {
+        return Err(VerifyError::Malformed(format!("unsupported signature algorithm {}", signature.algorithm)));
+    }
+    if verify_digest(&signature.signed_hash, &manifest_hash).is_err() {
+        return Err(VerifyError::ManifestHashMismatch(signature.signed_hash, manifest_hash));
+    }
+    check_signature(&manifest_hash, &signature.signature_b64, public_key)?;
+    Ok(signature)
+}
+
+#[cfg(feature = "signing")]
+fn check_signature(manifest_hash: &str, encoded: &str, public_key: &crate::signing::VerifyingKey) -> Result<(), VerifyError> {
+    let signature: [u8; 64] = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(VerifyError::SignatureInvalid)?;
+
+    if public_key.verify(manifest_hash.as_bytes(), &signature) {
+        Ok(())
+    } else {
+        Err(VerifyError::SignatureInvalid)
+    }
+}
@@ -0,0 +1,173 @@
+//! Webhook Notifier - Compile Events POSTed to an Orchestrator
+//!
+//! Each event is POSTed as canonical JSON with a `SIGNATURE_HEADER` (see
+//! `notify`). Deliveries run on a background thread in order, so a slow or
+//! unreachable receiver never holds up a compile: 5xx answers and
+//! transport failures (the per-request timeout included) are retried with
+//! doubling backoff, other answers are final, and an event that is not
+//! delivered is logged and dropped. The queue is bounded: events arriving
+//! while it is full are logged and dropped too. Dropping the notifier waits
+//! up to `shutdown_timeout` for queued deliveries, then abandons the rest.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::audit::AuditEvent;
+use crate::hashing::canonical_json;
+use crate::notify::{signature, Notifier, SIGNATURE_HEADER};
+use crate::render::RetryPolicy;
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("Webhook client error: {0}")]
+    Client(#[from] reqwest::Error),
+
+    #[error("Cannot start the webhook thread: {0}")]
+    Thread(#[from] io::Error),
+}
+
+/// Where to POST events, and how hard to try
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// HMAC-SHA256 key for the signature header
+    pub secret: Vec<u8>,
+    /// Limit on each delivery attempt, connecting included
+    pub timeout: Duration,
+    /// Attempts per event; only 5xx answers and transport failures retry
+    pub retry: RetryPolicy,
+    /// Events waiting for delivery; more are dropped while it is full
+    pub queue_capacity: usize,
+    /// How long dropping the notifier waits for queued deliveries
+    pub shutdown_timeout: Duration,
+}
+
+impl WebhookConfig {
+    /// 10s per attempt, 4 attempts 0.5s, 1s and 2s apart; 1024 queued
+    /// events, 30s to drain them on shutdown
+    pub fn new(url: impl Into<String>, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            url: url.into(),
+            secret: secret.into(),
+            timeout: Duration::from_secs(10),
+            retry: RetryPolicy { max_attempts: 4, backoff: Duration::from_millis(500) },
+            queue_capacity: 1024,
+            shutdown_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// `Notifier` POSTing each event to one URL
+pub struct WebhookNotifier {
+    queue: Option<SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+    /// Set when shutdown gives up waiting; the worker stops at the next event
+    abandoned: Arc<AtomicBool>,
+    shutdown_timeout: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Result<Self, WebhookError> {
+        let (queue, events) = mpsc::sync_channel::<Vec<u8>>(config.queue_capacity);
+        let (started, start) = mpsc::sync_channel(1);
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let shutdown_timeout = config.shutdown_timeout;
+        let stop = Arc::clone(&abandoned);
+        // The blocking client owns a runtime, so it is built, used and
+        // dropped on the worker: never inside a host's async context
+        let worker = std::thread::Builder::new()
+            .name("forgeimages-webhook".to_string())
+            .spawn(move || {
+                let built = reqwest::blocking::Client::builder()
+                    .timeout(config.timeout)
+                    .connect_timeout(config.timeout)
+                    .build();
+                let client = match built {
+                    Ok(client) => {
+                        let _ = started.send(Ok(()));
+                        client
+                    }
+                    Err(e) => return drop(started.send(Err(e))),
+                };
+                for body in events {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    deliver(&client, &config, &stop, body);
+                }
+            })?;
+        match start.recv() {
+            Ok(Err(e)) => Err(e.into()),
+            _ => Ok(Self { queue: Some(queue), worker: Some(worker), abandoned, shutdown_timeout }),
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, event: &AuditEvent) {
+        let body = match canonical_json(event) {
+            Ok(body) => body.into_bytes(),
+            Err(e) => return tracing::warn!(error = %e, "webhook event not serializable"),
+        };
+        if let Some(queue) = &self.queue {
+            match queue.try_send(body) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => tracing::warn!("webhook queue full, event dropped"),
+                // Only once the worker is gone, when nothing can be sent
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+    }
+}
+
+impl Drop for WebhookNotifier {
+    fn drop(&mut self) {
+        drop(self.queue.take());
+        let Some(worker) = self.worker.take() else { return };
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                self.abandoned.store(true, Ordering::Relaxed);
+                return tracing::warn!(timeout = ?self.shutdown_timeout, "webhook deliveries abandoned at shutdown");
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = worker.join();
+    }
+}
+
+/// POST `body` until it is accepted, refused, attempts run out, or
+/// shutdown abandons it
+fn deliver(client: &reqwest::blocking::Client, config: &WebhookConfig, abandoned: &AtomicBool, body: Vec<u8>) {
+    let signed = signature(&config.secret, &body);
+    let mut attempt = 1;
+    loop {
+        let sent = client.post(&config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signed)
+            .body(body.clone())
+            .send();
+        let failure = match sent {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) if !response.status().is_server_error() => {
+                return tracing::warn!(url = %config.url, status = %response.status(), "webhook refused the event");
+            }
+            Ok(response) => response.status().to_string(),
+            Err(e) => e.to_string(),
+        };
+        if abandoned.load(Ordering::Relaxed) {
+            return;
+        }
+        if attempt >= config.retry.max_attempts {
+            return tracing::warn!(url = %config.url, attempts = attempt, error = %failure, "webhook event not delivered");
+        }
+        std::thread::sleep(config.retry.backoff.saturating_mul(1 << (attempt - 1).min(16)));
+        attempt += 1;
+    }
+}
@@ -0,0 +1,106 @@
+//! Audit Log - every attempt is recorded and the chain detects tampering
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline,
+    audit::{verify_chain, AuditError, AuditOutcome, AuditRecord, FileAuditSink, GENESIS_HASH},
+    hashing::sha256_hex,
+    templates::TemplateRegistry,
+};
+
+fn audited_pipeline(log: &Path) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::builder(registry)
+        .audit_sink(FileAuditSink::open(log).unwrap().with_fsync(true))
+        .build()
+}
+
+fn records(log: &Path) -> Vec<AuditRecord> {
+    fs::read_to_string(log).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_success_and_blocked_compiles_are_logged() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("audit.jsonl");
+    let pipeline = audited_pipeline(&log);
+
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert!(pipeline.compile_asset(&compile_request("test-icon", 100, 100)).is_err());
+    assert!(pipeline.compile_asset(&compile_request("missing", 1024, 1024)).is_err());
+
+    let records = records(&log);
+    assert_eq!(records.len(), 3);
+
+    let ok = &records[0].event;
+    assert_eq!(ok.outcome, AuditOutcome::Success);
+    assert_eq!(ok.manifest_hash.as_deref(), Some(asset.manifest_hash.as_str()));
+    assert_eq!(ok.job_hash.as_deref(), Some(asset.job_hash.as_str()));
+    assert_eq!(records[0].prev_hash, GENESIS_HASH);
+
+    let blocked = &records[1].event;
+    assert_eq!(blocked.outcome, AuditOutcome::ValidationFailed);
+    assert_eq!(blocked.violations, vec!["resolution".to_string()]);
+    assert!(blocked.job_hash.is_some());
+    assert!(blocked.manifest_hash.is_none());
+
+    let missing = &records[2].event;
+    assert_eq!(missing.outcome, AuditOutcome::Error);
+    assert!(missing.template_version.is_none());
+
+    assert_eq!(verify_chain(&log).unwrap(), 3);
+}
+
+#[test]
+fn test_records_are_canonical_and_chained() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("audit.jsonl");
+    let pipeline = audited_pipeline(&log);
+    pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let content = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(forgeimages_core::canonical_json(&value).unwrap(), lines[0]);
+    assert_eq!(records(&log)[1].prev_hash, sha256_hex(lines[0].as_bytes()));
+}
+
+#[test]
+fn test_reopened_log_continues_chain() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("audit.jsonl");
+    audited_pipeline(&log).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    audited_pipeline(&log).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    assert_eq!(verify_chain(&log).unwrap(), 2);
+}
+
+#[test]
+fn test_tampering_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("audit.jsonl");
+    let pipeline = audited_pipeline(&log);
+    for _ in 0..3 {
+        pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    }
+
+    let content = fs::read_to_string(&log).unwrap();
+    let tampered = content.replacen("\"success\"", "\"error\"", 1);
+    fs::write(&log, tampered).unwrap();
+    assert!(matches!(verify_chain(&log), Err(AuditError::ChainBroken(2))));
+
+    let mut lines: Vec<&str> = content.lines().collect();
+    lines.remove(1);
+    fs::write(&log, lines.join("\n")).unwrap();
+    assert!(matches!(verify_chain(&log), Err(AuditError::ChainBroken(2))));
+}
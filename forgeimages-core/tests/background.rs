@@ -0,0 +1,101 @@
+//! Seeded Background Generation
+//!
+//! Backgrounds are a pure function of (seed, size, config).
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest,
+    background::{generate, BackgroundGenerator, PatternStyle},
+    raster::Raster,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn gradient_config() -> BackgroundGenerator {
+    BackgroundGenerator::Gradient {
+        palette: vec!["#ff6600".to_string(), "#1a1a2e".to_string(), "#e0e0ff".to_string()],
+    }
+}
+
+fn diff_ratio(a: &Raster, b: &Raster) -> f64 {
+    let differing = a.pixels.chunks_exact(4)
+        .zip(b.pixels.chunks_exact(4))
+        .filter(|(x, y)| x != y)
+        .count();
+    differing as f64 / (a.width * a.height) as f64
+}
+
+fn background_pipeline(config: BackgroundGenerator) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.background_generator = config;
+    template.exports.push(export("card", [128, 128], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry)
+}
+
+#[test]
+fn same_seed_same_pixels() {
+    for config in [gradient_config(), BackgroundGenerator::Pattern { style: PatternStyle::Dots, palette: vec![] }] {
+        let a = generate(&config, 42, [96, 64]).unwrap().unwrap();
+        let b = generate(&config, 42, [96, 64]).unwrap().unwrap();
+        assert_eq!(diff_ratio(&a, &b), 0.0);
+    }
+}
+
+#[test]
+fn different_seeds_differ() {
+    for style in [PatternStyle::Stripes, PatternStyle::Checker, PatternStyle::Dots] {
+        let config = BackgroundGenerator::Pattern { style, palette: vec![] };
+        let a = generate(&config, 1, [128, 128]).unwrap().unwrap();
+        let b = generate(&config, 2, [128, 128]).unwrap().unwrap();
+        assert!(diff_ratio(&a, &b) > 0.05, "{style:?} backgrounds too similar");
+    }
+
+    let a = generate(&gradient_config(), 1, [128, 128]).unwrap().unwrap();
+    let b = generate(&gradient_config(), 2, [128, 128]).unwrap().unwrap();
+    assert!(diff_ratio(&a, &b) > 0.05);
+}
+
+#[test]
+fn compiled_exports_depend_only_on_seed() {
+    let pipeline = background_pipeline(gradient_config());
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(7);
+
+    let card = |request: &CompileRequest| {
+        let asset = pipeline.compile_asset(request).unwrap();
+        let card = asset.exports.iter().find(|e| e.id == "card").unwrap().clone();
+        let master = asset.exports.iter().find(|e| e.id == "master").unwrap().clone();
+        (card.hash, master.hash)
+    };
+
+    let (first, master_first) = card(&request);
+    let (second, _) = card(&request);
+    assert_eq!(first, second);
+
+    request.seed = Some(8);
+    let (other, master_other) = card(&request);
+    assert_ne!(first, other);
+    // The SVG master never gets a generated background
+    assert_eq!(master_first, master_other);
+}
+
+#[test]
+fn missing_seed_fails_validation() {
+    let pipeline = background_pipeline(gradient_config());
+    let err = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap_err();
+    assert!(err.to_string().contains("seed_required"));
+}
+
+#[test]
+fn template_json_configures_generator() {
+    let config: BackgroundGenerator = serde_json::from_str(
+        r##"{"type": "pattern", "style": "checker", "palette": ["#000000", "#ffffff"]}"##
+    ).unwrap();
+    assert_eq!(config, BackgroundGenerator::Pattern {
+        style: PatternStyle::Checker,
+        palette: vec!["#000000".to_string(), "#ffffff".to_string()],
+    });
+}
@@ -0,0 +1,127 @@
+//! Checkpointed batch runs resume instead of starting over
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    BatchOutcome, CompilationPipeline, CompileRequest, Renderer, RenderError, RenderJob,
+    render::PlaceholderRenderer,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+struct CountingRenderer(Arc<AtomicU32>);
+
+impl Renderer for CountingRenderer {
+    fn name(&self) -> &'static str { "counting" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        PlaceholderRenderer.render(job)
+    }
+}
+
+fn pipeline(renders: &Arc<AtomicU32>) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("icon", [32, 32], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .renderer(CountingRenderer(Arc::clone(renders)))
+        .build()
+}
+
+fn requests(count: u64) -> Vec<CompileRequest> {
+    (0..count)
+        .map(|seed| {
+            let mut request = compile_request("test-icon", 1024, 1024);
+            request.seed = Some(seed);
+            request
+        })
+        .collect()
+}
+
+fn outcomes(pipeline: &CompilationPipeline, requests: &[CompileRequest], out: &Path) -> Vec<BatchOutcome> {
+    pipeline.compile_batch(requests, out, Some(&out.join("checkpoint.jsonl")))
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|item| item.outcome)
+        .collect()
+}
+
+#[test]
+fn test_restart_skips_completed_items() {
+    use BatchOutcome::*;
+    let dir = tempfile::tempdir().unwrap();
+    let renders = Arc::new(AtomicU32::new(0));
+    let pipeline = pipeline(&renders);
+
+    // First run dies after two items
+    assert_eq!(outcomes(&pipeline, &requests(2), dir.path()), [Compiled, Compiled]);
+    assert_eq!(renders.swap(0, Ordering::SeqCst), 4);
+
+    assert_eq!(outcomes(&pipeline, &requests(3), dir.path()), [Skipped, Skipped, Compiled]);
+    assert_eq!(renders.load(Ordering::SeqCst), 2);
+
+    let lines = fs::read_to_string(dir.path().join("checkpoint.jsonl")).unwrap();
+    assert_eq!(lines.lines().count(), 3);
+}
+
+#[test]
+fn test_skipped_items_are_reverified_on_disk() {
+    use BatchOutcome::*;
+    let dir = tempfile::tempdir().unwrap();
+    let renders = Arc::new(AtomicU32::new(0));
+    let pipeline = pipeline(&renders);
+
+    let first = pipeline.compile_batch(&requests(2), dir.path(), Some(&dir.path().join("checkpoint.jsonl"))).unwrap();
+    let tampered = dir.path().join(first.items[1].job_hash.as_ref().unwrap()).join("icon.png");
+    fs::write(&tampered, b"not the compiled export").unwrap();
+
+    assert_eq!(outcomes(&pipeline, &requests(2), dir.path()), [Skipped, Compiled]);
+    assert_ne!(fs::read(&tampered).unwrap(), b"not the compiled export");
+}
+
+#[test]
+fn test_corrupt_checkpoint_line_only_loses_that_item() {
+    use BatchOutcome::*;
+    let dir = tempfile::tempdir().unwrap();
+    let renders = Arc::new(AtomicU32::new(0));
+    let pipeline = pipeline(&renders);
+    outcomes(&pipeline, &requests(3), dir.path());
+
+    let path = dir.path().join("checkpoint.jsonl");
+    let content = fs::read_to_string(&path).unwrap();
+    let mut lines: Vec<&str> = content.lines().collect();
+    lines[1] = "{\"job_hash\":\"garbled";
+    let torn = &lines[2][..lines[2].len() / 2];
+    lines[2] = torn;
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    assert_eq!(outcomes(&pipeline, &requests(3), dir.path()), [Skipped, Compiled, Compiled]);
+    assert_eq!(outcomes(&pipeline, &requests(3), dir.path()), [Skipped, Skipped, Skipped]);
+}
+
+#[test]
+fn test_failed_items_do_not_stop_the_batch() {
+    use BatchOutcome::*;
+    let dir = tempfile::tempdir().unwrap();
+    let renders = Arc::new(AtomicU32::new(0));
+    let pipeline = pipeline(&renders);
+    let mut batch = requests(2);
+    batch.insert(1, compile_request("missing-template", 1024, 1024));
+    batch.push(compile_request("test-icon", 16, 16));
+
+    let result = pipeline.compile_batch(&batch, dir.path(), None).unwrap();
+    let outcomes: Vec<_> = result.items.iter().map(|i| i.outcome).collect();
+    assert_eq!(outcomes, [Compiled, Failed, Compiled, Failed]);
+    assert_eq!(result.items[1].job_hash, None);
+    assert!(result.items[3].error.as_deref().unwrap().contains("Validation failed"));
+    assert_eq!(result.count(Compiled), 2);
+    assert!(!dir.path().join("checkpoint.jsonl").exists());
+}
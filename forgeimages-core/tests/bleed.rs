@@ -0,0 +1,163 @@
+//! Bleed and marks on print exports: file size, recorded trim box, pixels
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    canonical_json, CompilationPipeline, CompiledAsset, PipelineError,
+    bleed::TrimBox,
+    pdf::TransparencyPolicy,
+    print::{BleedFill, ColorSpace, Marks, PrintSpec, TemplatePrint},
+    templates::{ExportFormat, TemplateRegistry},
+    units::Length,
+};
+
+/// 3.5 x 2 in at 300 dpi with 0.125 in bleed
+fn card_print(bleed_fill: BleedFill, marks: Marks) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space: ColorSpace::Rgb,
+        bleed: Length::inches(0.125),
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill,
+        marks,
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+fn compile(print: TemplatePrint) -> CompiledAsset {
+    let mut template = create_test_template();
+    template.print = Some(print);
+    template.exports.push(export("card", [1050, 600], ExportFormat::Tiff, true));
+    template.exports.push(export("web", [512, 512], ExportFormat::Png, false));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap()
+}
+
+fn card(asset: &CompiledAsset) -> &forgeimages_core::pipeline::ExportedFile {
+    asset.exports.iter().find(|e| e.id == "card").unwrap()
+}
+
+/// (width, height, RGB strip) of the placeholder's little-endian TIFF
+fn tiff_rgb(asset: &CompiledAsset) -> (u32, u32, Vec<u8>) {
+    let tiff = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &card(asset).data_base64).unwrap();
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = |wanted: u16| (0..u16_at(ifd) as usize)
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&pos| u16_at(pos) == wanted)
+        .map(|pos| u32_at(pos + 8))
+        .unwrap();
+    let (offset, len) = (tag(273) as usize, tag(279) as usize);
+    (tag(256), tag(257), tiff[offset..offset + len].to_vec())
+}
+
+#[test]
+fn test_business_card_bleed_dimensions() {
+    let asset = compile(card_print(BleedFill::Mirror, Marks::default()));
+
+    // 1050 x 600 trim, 37.5 px of bleed per side
+    assert_eq!(card(&asset).size, [1125, 675]);
+    assert_eq!(card(&asset).trim, Some(TrimBox { size: [1050, 600], offset: [37, 37] }));
+    let (width, height, pixels) = tiff_rgb(&asset);
+    assert_eq!((width, height), (1125, 675));
+    assert_eq!(pixels.len(), 1125 * 675 * 3);
+
+    // Non-print exports keep their spec size and record no trim
+    let web = asset.exports.iter().find(|e| e.id == "web").unwrap();
+    assert_eq!((web.size, web.trim), ([512, 512], None));
+}
+
+#[test]
+fn test_manifest_records_trim_separately() {
+    let asset = compile(card_print(BleedFill::Mirror, Marks::legacy()));
+    let manifest: serde_json::Value = serde_json::from_str(&canonical_json(&asset).unwrap()).unwrap();
+    let card = manifest["exports"].as_array().unwrap().iter().find(|e| e["id"] == "card").unwrap();
+
+    // Marks add a 0.25 in slug per side
+    assert_eq!(card["size"], serde_json::json!([1275, 825]));
+    assert_eq!(card["trim"], serde_json::json!({"size": [1050, 600], "offset": [112, 112]}));
+    // The full marks configuration, legacy `marks: true` spelled out
+    assert_eq!(card["marks"], serde_json::json!({
+        "crop": true, "registration": true, "colorBars": false, "markLength": "3.175mm", "markOffset": "3.175mm",
+    }));
+    let master = manifest["exports"].as_array().unwrap().iter().find(|e| e["id"] == "master").unwrap();
+    assert!(master.get("trim").is_none());
+    assert!(master.get("marks").is_none());
+}
+
+#[test]
+fn test_color_fill_and_marks_in_output() {
+    let fill = BleedFill::Color { color: "#00FF00".to_string() };
+    let asset = compile(card_print(fill, Marks::legacy()));
+    let (width, _, pixels) = tiff_rgb(&asset);
+    let pixel = |x: u32, y: u32| {
+        let i = ((y * width + x) * 3) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2]]
+    };
+
+    // Slug corner is paper, bleed is the fill, trim area is the (empty) art
+    assert_eq!(pixel(0, 0), [255, 255, 255]);
+    assert_eq!(pixel(75, 75), [0, 255, 0]);
+    assert_eq!(pixel(112, 112), [255, 255, 255]);
+    // Crop mark on the left trim line, 38 to 76 px above the trim
+    assert_eq!(pixel(112, 40), [0, 0, 0]);
+    assert_eq!(pixel(112, 20), [255, 255, 255]);
+}
+
+#[test]
+fn test_marks_outside_the_canvas_are_refused() {
+    let marks = Marks { crop: true, mark_offset: Length::mm(5.0), mark_length: Length::mm(5.0), ..Marks::default() };
+    let mut template = create_test_template();
+    template.print = Some(card_print(BleedFill::Mirror, marks));
+    template.exports.push(export("card", [1050, 600], ExportFormat::Tiff, true));
+    assert_eq!(
+        template.validate_print().unwrap_err(),
+        "print: marks need 10mm outside the trim (markOffset 5mm + markLength 5mm) \
+         but bleed 3.175mm and slug 6.35mm leave 9.525mm: 0.475mm short",
+    );
+
+    // A user bleed override can shrink the canvas after the template loaded
+    let fitting = Marks { mark_offset: Length::mm(3.0), ..marks };
+    template.print = Some(card_print(BleedFill::Mirror, fitting));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { bleed: Length::ZERO, ..PrintSpec::default() });
+    let Err(PipelineError::ValidationFailed(message)) = CompilationPipeline::new(registry).compile_asset(&request) else {
+        panic!("compiled");
+    };
+    assert!(message.starts_with("print: exports[card]: marks need 8mm outside the trim"), "{}", message);
+    assert!(message.ends_with("leave 6.35mm: 1.65mm short"), "{}", message);
+}
+
+#[test]
+fn test_layout_changes_defeat_deduplication() {
+    let mut template = create_test_template();
+    let mut marked = export("marked", [1050, 600], ExportFormat::Tiff, true);
+    marked.print = Some(card_print(BleedFill::Mirror, Marks::legacy()));
+    template.exports.push(export("plain", [1050, 600], ExportFormat::Tiff, true));
+    template.exports.push(marked);
+    template.print = Some(card_print(BleedFill::Mirror, Marks::default()));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+
+    let asset = CompilationPipeline::builder(registry)
+        .deduplicate_exports(true)
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    let marked = asset.exports.iter().find(|e| e.id == "marked").unwrap();
+    assert_eq!(marked.deduplicated_from, None);
+    assert_eq!(marked.size, [1275, 825]);
+}
@@ -0,0 +1,87 @@
+//! Cancellation: compiles and batches stop at the next checkpoint once
+//! their token fires, naming the export that was rendering
+
+mod common;
+
+use std::time::Duration;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    exit_code, exit_code_for, CancelReason, CancelToken, CompilationPipeline, PipelineError,
+    templates::TemplateRegistry,
+};
+
+fn pipeline(token: &CancelToken) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::builder(registry).cancel_token(token.clone()).build()
+}
+
+#[test]
+fn test_first_reason_sticks() {
+    let token = CancelToken::new();
+    assert_eq!(token.reason(), None);
+    token.clone().cancel(CancelReason::Interrupted);
+    token.cancel(CancelReason::TimedOut);
+    assert_eq!(token.reason(), Some(CancelReason::Interrupted));
+
+    let expired = CancelToken::with_timeout(Duration::from_nanos(1));
+    std::thread::sleep(Duration::from_millis(1));
+    assert_eq!(expired.reason(), Some(CancelReason::TimedOut));
+}
+
+#[test]
+fn test_cancelled_compile_stops_before_rendering() {
+    let token = CancelToken::new();
+    let pipeline = pipeline(&token);
+    assert!(pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).is_ok());
+
+    token.cancel(CancelReason::Interrupted);
+    let error = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap_err();
+    assert!(matches!(error, PipelineError::Cancelled(CancelReason::Interrupted, None)), "{:?}", error);
+    assert_eq!((error.kind(), exit_code_for(&error)), ("cancelled", exit_code::CANCELLED));
+    assert_eq!(error.to_string(), "Compile interrupted");
+}
+
+#[test]
+fn test_validation_failures_win_over_cancellation() {
+    let token = CancelToken::new();
+    token.cancel(CancelReason::Interrupted);
+    let result = pipeline(&token).compile_asset(&compile_request("test-icon", 100, 100));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(_))));
+}
+
+#[test]
+fn test_cancelled_batch_writes_nothing_further() {
+    let token = CancelToken::new();
+    token.cancel(CancelReason::Interrupted);
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint = dir.path().join("checkpoint.ndjson");
+    let requests = [compile_request("test-icon", 1024, 1024)];
+    let result = pipeline(&token).compile_batch(&requests, &dir.path().join("out"), Some(&checkpoint));
+    assert!(matches!(result, Err(PipelineError::Cancelled(CancelReason::Interrupted, None))));
+    assert!(!dir.path().join("out").exists());
+    assert_eq!(std::fs::read_to_string(&checkpoint).unwrap(), "");
+}
+
+#[cfg(feature = "test-hooks")]
+#[test]
+fn test_timeout_names_the_export_in_flight() {
+    use forgeimages_core::render::SlowRenderer;
+
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    let template_exports: Vec<String> = create_test_template().exports.into_iter().map(|e| e.id).collect();
+    let pipeline = CompilationPipeline::builder(registry)
+        .renderer(SlowRenderer { delay: Duration::from_millis(100) })
+        .cancel_token(CancelToken::with_timeout(Duration::from_millis(50)))
+        .build();
+
+    match pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)) {
+        Err(PipelineError::Cancelled(CancelReason::TimedOut, Some(export))) => {
+            // The deadline passes during the first render
+            assert_eq!(export, template_exports[0]);
+        }
+        other => panic!("expected a timeout, got {:?}", other.map(|asset| asset.manifest_hash)),
+    }
+}
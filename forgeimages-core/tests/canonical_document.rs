@@ -0,0 +1,81 @@
+//! Canonical manifest text kept from hashing
+
+mod common;
+
+use common::{compile_request, create_pipeline, create_test_template, export};
+use forgeimages_core::{
+    canonical_json, CompilationPipeline, CompiledAsset, HashScheme,
+    pipeline::{CanonicalDocument, ManifestHashView},
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn compile(deduplicate: bool) -> CompiledAsset {
+    let mut template = create_test_template();
+    template.exports.push(export("icon-a", [64, 64], ExportFormat::Png, true));
+    template.exports.push(export("icon-b", [64, 64], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .deduplicate_exports(deduplicate)
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap()
+}
+
+#[test]
+fn test_fresh_asset_keeps_the_text_it_hashed() {
+    let asset = compile(false);
+    assert!(asset.canonical.is_cached());
+    assert_eq!(asset.canonical_json().unwrap(), canonical_json(&asset).unwrap());
+    assert_eq!(
+        asset.manifest_hash,
+        ManifestHashView::of(&asset).unwrap().hash(HashScheme::CURRENT, asset.hash_algorithm).unwrap(),
+    );
+}
+
+#[test]
+fn test_deduplicated_exports_are_serialized_on_demand() {
+    // deduplicated_from is outside the hash, so the hashed text is not the manifest
+    let asset = compile(true);
+    assert!(asset.exports.iter().any(|e| e.deduplicated_from.is_some()));
+    assert!(!asset.canonical.is_cached());
+    assert_eq!(asset.canonical_json().unwrap(), canonical_json(&asset).unwrap());
+}
+
+#[test]
+fn test_deserialized_asset_has_no_cache() {
+    let asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let read: CompiledAsset = serde_json::from_str(&asset.canonical_json().unwrap()).unwrap();
+    assert!(!read.canonical.is_cached());
+    assert_eq!(read.canonical_json().unwrap(), asset.canonical_json().unwrap());
+}
+
+#[test]
+fn test_tampered_manifest_cannot_carry_a_stale_text() {
+    let asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let tampered = asset.canonical_json().unwrap().replace(r#""warning_count":0"#, r#""warning_count":7"#);
+    let read: CompiledAsset = serde_json::from_str(&tampered).unwrap();
+    assert_eq!(read.canonical_json().unwrap(), tampered);
+
+    let recomputed = ManifestHashView::of(&read).unwrap().hash(read.hash_scheme, read.hash_algorithm).unwrap();
+    assert_ne!(recomputed, read.manifest_hash);
+}
+
+#[test]
+fn test_changed_manifest_hash_invalidates_the_text() {
+    let mut asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    asset.manifest_hash = "sha256:edited".to_string();
+    assert!(asset.canonical_json().unwrap().contains(r#""manifest_hash":"sha256:edited""#));
+
+    // Clearing the cache is how other edits are picked up
+    asset.warning_count = 7;
+    asset.canonical = CanonicalDocument::default();
+    assert!(asset.canonical_json().unwrap().contains(r#""warning_count":7"#));
+}
+
+#[test]
+fn test_debug_output_does_not_dump_the_text() {
+    let asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let debug = format!("{:?}", asset.canonical);
+    assert!(debug.starts_with("CanonicalDocument(") && debug.ends_with(" bytes)"), "{}", debug);
+}
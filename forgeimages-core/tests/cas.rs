@@ -0,0 +1,92 @@
+//! Content-addressable export store
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_pipeline};
+use forgeimages_core::{
+    HashAlgorithm,
+    cas::{put_exports, CasError, CasStore, FsCasStore, HashRef},
+};
+
+fn object_count(store: &FsCasStore) -> usize {
+    fs::read_dir(store.root().join("objects")).map_or(0, |fan_outs| {
+        fan_outs.map(|d| fs::read_dir(d.unwrap().path()).unwrap().count()).sum()
+    })
+}
+
+#[test]
+fn test_put_get_and_layout() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsCasStore::new(dir.path());
+
+    let hash_ref = store.put(b"forge").unwrap();
+    assert_eq!(hash_ref.as_str(), HashAlgorithm::Sha256.digest(b"forge"));
+    let hex = hash_ref.as_str().strip_prefix("sha256:").unwrap();
+    assert_eq!(store.object_path(&hash_ref), dir.path().join("objects").join(&hex[..2]).join(&hex[2..]));
+
+    assert!(store.contains(&hash_ref).unwrap());
+    assert_eq!(store.get(&hash_ref).unwrap().as_deref(), Some(&b"forge"[..]));
+
+    let absent = HashRef::parse(&HashAlgorithm::Sha256.digest(b"absent")).unwrap();
+    assert!(!store.contains(&absent).unwrap());
+    assert_eq!(store.get(&absent).unwrap(), None);
+}
+
+#[test]
+fn test_identical_bytes_are_stored_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsCasStore::new(dir.path());
+    assert_eq!(store.put(b"same").unwrap(), store.put(b"same").unwrap());
+    assert_eq!(object_count(&store), 1);
+}
+
+#[test]
+fn test_corrupt_object_is_reported_on_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsCasStore::new(dir.path());
+    let hash_ref = store.put(b"original").unwrap();
+    fs::write(store.object_path(&hash_ref), b"tampered").unwrap();
+
+    assert!(matches!(store.get(&hash_ref), Err(CasError::Corrupt(r)) if r == hash_ref));
+}
+
+#[test]
+fn test_retain_removes_unreachable_objects() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsCasStore::new(dir.path());
+    let keep = store.put(b"keep").unwrap();
+    let drop = store.put(b"drop").unwrap();
+
+    assert_eq!(store.retain(std::slice::from_ref(&keep)).unwrap(), 1);
+    assert!(store.contains(&keep).unwrap());
+    assert!(!store.contains(&drop).unwrap());
+    assert_eq!(store.retain(&[]).unwrap(), 1);
+    assert_eq!(object_count(&store), 0);
+}
+
+#[test]
+fn test_shared_exports_are_stored_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsCasStore::new(dir.path());
+    let pipeline = create_pipeline();
+    let first = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let second = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let refs = put_exports(&store, &first).unwrap();
+    assert_eq!(put_exports(&store, &second).unwrap(), refs);
+    assert_eq!(object_count(&store), 1);
+    assert_eq!(refs[0].as_str(), first.exports[0].hash);
+}
+
+#[test]
+fn test_hash_ref_normalizes_and_round_trips() {
+    let digest = HashAlgorithm::Sha256.digest(b"forge");
+    let hex = digest.strip_prefix("sha256:").unwrap();
+    assert_eq!(HashRef::parse(&hex.to_uppercase()).unwrap().as_str(), digest);
+
+    let json = serde_json::to_string(&HashRef::parse(&digest).unwrap()).unwrap();
+    assert_eq!(json, format!("\"{}\"", digest));
+    assert!(serde_json::from_str::<HashRef>("\"sha256:xyz\"").is_err());
+}
@@ -0,0 +1,141 @@
+//! Per-export checksum sidecars and directory verification
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset,
+    hashing::sha256_hex,
+    output::{sidecar_name, CHECKSUMS_FILE, MANIFEST_FILE},
+    templates::{ExportFormat, TemplateRegistry},
+    verify::{verify_directory, ChecksumSource, VerifyError},
+};
+
+fn compile_to(dir: &Path, emit_checksums: bool) -> CompiledAsset {
+    let mut template = create_test_template();
+    template.exports.push(export("icon-64", [64, 64], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .emit_checksums(emit_checksums)
+        .build()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir)
+        .unwrap()
+}
+
+#[test]
+fn test_writes_sidecars_and_checksums() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compile_to(dir.path(), true);
+
+    let mut expected = vec![];
+    for export in &asset.exports {
+        let hex = sha256_hex(&fs::read(dir.path().join(&export.filename)).unwrap());
+        let line = format!("{}  {}\n", hex, export.filename);
+        assert_eq!(fs::read_to_string(dir.path().join(sidecar_name(&export.filename))).unwrap(), line);
+        expected.push((export.filename.clone(), line));
+    }
+    let manifest_hex = sha256_hex(&fs::read(dir.path().join(MANIFEST_FILE)).unwrap());
+    expected.push((MANIFEST_FILE.to_string(), format!("{}  {}\n", manifest_hex, MANIFEST_FILE)));
+    expected.sort();
+    let checksums: String = expected.into_iter().map(|(_, line)| line).collect();
+    assert_eq!(fs::read_to_string(dir.path().join(CHECKSUMS_FILE)).unwrap(), checksums);
+
+    let report = verify_directory(dir.path()).unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+    assert_eq!(report.manifest_hash, asset.manifest_hash);
+}
+
+#[test]
+fn test_sha256sum_accepts_the_sidecars() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compile_to(dir.path(), true);
+    let mut files: Vec<String> = asset.exports.iter().map(|e| sidecar_name(&e.filename)).collect();
+    files.push(CHECKSUMS_FILE.to_string());
+    for file in files {
+        // Skipped where coreutils is unavailable
+        let Ok(status) = Command::new("sha256sum").arg("-c").arg("--quiet").arg(&file).current_dir(dir.path()).status() else {
+            return;
+        };
+        assert!(status.success(), "sha256sum -c {}", file);
+    }
+}
+
+#[test]
+fn test_without_checksums_no_sidecars_are_written_or_left() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    let asset = compile_to(dir.path(), false);
+
+    assert!(!dir.path().join(CHECKSUMS_FILE).exists());
+    for export in &asset.exports {
+        assert!(!dir.path().join(sidecar_name(&export.filename)).exists());
+    }
+    assert!(verify_directory(dir.path()).unwrap().is_ok());
+}
+
+#[test]
+fn test_modified_export_mismatches_every_record() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    fs::write(dir.path().join("icon-64.png"), b"tampered").unwrap();
+
+    let report = verify_directory(dir.path()).unwrap();
+    let sources: Vec<_> = report.mismatches.iter().map(|m| (m.filename.as_str(), m.source)).collect();
+    assert_eq!(sources, [
+        ("icon-64.png", ChecksumSource::Manifest),
+        ("icon-64.png", ChecksumSource::Sidecar),
+        ("icon-64.png", ChecksumSource::ChecksumsFile),
+    ]);
+    assert!(report.missing.is_empty() && report.extra.is_empty());
+    assert_eq!(report.mismatches[1].actual, sha256_hex(b"tampered"));
+}
+
+#[test]
+fn test_edited_sidecar_is_a_sidecar_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    let sidecar = dir.path().join(sidecar_name("icon-64.png"));
+    fs::write(&sidecar, format!("{}  icon-64.png\n", sha256_hex(b"other"))).unwrap();
+
+    let report = verify_directory(dir.path()).unwrap();
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].source, ChecksumSource::Sidecar);
+    assert_eq!(report.mismatches[0].expected, sha256_hex(b"other"));
+}
+
+#[test]
+fn test_missing_and_extra_files_are_reported_separately() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    fs::remove_file(dir.path().join("icon-64.png")).unwrap();
+    fs::remove_file(dir.path().join(sidecar_name("master.svg"))).unwrap();
+    fs::write(dir.path().join("notes.txt"), b"stray").unwrap();
+
+    let report = verify_directory(dir.path()).unwrap();
+    assert_eq!(report.missing, ["icon-64.png", "master.svg.sha256"]);
+    assert_eq!(report.extra, ["notes.txt"]);
+    assert!(report.mismatches.is_empty());
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn test_tampered_manifest_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    let path = dir.path().join(MANIFEST_FILE);
+    fs::write(&path, fs::read_to_string(&path).unwrap().replace("test-icon", "evil-icon")).unwrap();
+    assert!(matches!(verify_directory(dir.path()), Err(VerifyError::FileHashMismatch)));
+}
+
+#[test]
+fn test_malformed_sidecar_is_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), true);
+    fs::write(dir.path().join(sidecar_name("master.svg")), "not a checksum\n").unwrap();
+    assert!(matches!(verify_directory(dir.path()), Err(VerifyError::Malformed(_))));
+}
@@ -0,0 +1,330 @@
+//! CMYK conversion of print exports, and what the manifest records about it
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    canonical_json, CompilationPipeline, CompiledAsset,
+    color::{CmykConverter, ColorConversion, ConversionMethod},
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, ExportSpec, TemplateRegistry},
+    units::Length,
+};
+
+/// Stands in for an ICC-based converter: fixed output, recorded profile
+struct FixedProfile;
+
+const PROFILE_DIGEST: &str = "sha256:0000000000000000000000000000000000000000000000000000000000000001";
+
+impl CmykConverter for FixedProfile {
+    fn conversion(&self) -> ColorConversion {
+        ColorConversion { method: ConversionMethod::Icc, profile: Some(PROFILE_DIGEST.to_string()) }
+    }
+
+    fn convert(&self, _rgb: [u8; 3]) -> [u8; 4] {
+        [1, 2, 3, 4]
+    }
+}
+
+/// No bleed, so files stay at the 4x4 spec size
+fn print_block(color_space: ColorSpace) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space,
+        bleed: Length::ZERO,
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// "press" is CMYK; "proof" overrides it with RGB
+fn registry() -> TemplateRegistry {
+    let mut template = create_test_template();
+    template.print = Some(print_block(ColorSpace::Cmyk));
+    template.exports.push(export("press", [4, 4], ExportFormat::Tiff, true));
+    template.exports.push(ExportSpec {
+        print: Some(print_block(ColorSpace::Rgb)),
+        ..export("proof", [4, 4], ExportFormat::Tiff, true)
+    });
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry
+}
+
+fn decoded(asset: &CompiledAsset, id: &str) -> Vec<u8> {
+    let file = asset.exports.iter().find(|e| e.id == id).unwrap();
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap()
+}
+
+/// (PhotometricInterpretation, strip bytes) of a little-endian TIFF
+fn tiff_pixels(tiff: &[u8]) -> (u32, Vec<u8>) {
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = |wanted: u16| (0..u16_at(ifd) as usize)
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&pos| u16_at(pos) == wanted)
+        .map(|pos| u32_at(pos + 8))
+        .unwrap();
+    let (offset, len) = (tag(273) as usize, tag(279) as usize);
+    (tag(262), tiff[offset..offset + len].to_vec())
+}
+
+#[test]
+fn test_cmyk_exports_are_converted_naively_by_default() {
+    let asset = CompilationPipeline::new(registry()).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    // Empty placeholder canvas: white paper, so no ink at all
+    let (photometric, pixels) = tiff_pixels(&decoded(&asset, "press"));
+    assert_eq!(photometric, 5);
+    assert_eq!(pixels, vec![0; 4 * 4 * 4]);
+
+    let (photometric, pixels) = tiff_pixels(&decoded(&asset, "proof"));
+    assert_eq!(photometric, 2);
+    assert_eq!(pixels, vec![255; 4 * 4 * 3]);
+}
+
+#[test]
+fn test_manifest_records_method_and_profile() {
+    let naive = CompilationPipeline::new(registry()).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let conversion_of = |asset: &CompiledAsset, id: &str| {
+        asset.exports.iter().find(|e| e.id == id).unwrap().color_conversion.clone()
+    };
+    assert_eq!(conversion_of(&naive, "press"), Some(ColorConversion { method: ConversionMethod::Naive, profile: None }));
+    assert_eq!(conversion_of(&naive, "proof"), None);
+    assert_eq!(conversion_of(&naive, "master"), None);
+
+    let manifest: serde_json::Value = serde_json::from_str(&canonical_json(&naive).unwrap()).unwrap();
+    let press = manifest["exports"].as_array().unwrap().iter().find(|e| e["id"] == "press").unwrap();
+    assert_eq!(press["color_conversion"], serde_json::json!({"method": "naive", "profile": null}));
+
+    let icc = CompilationPipeline::builder(registry())
+        .cmyk_converter(FixedProfile)
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    let conversion = conversion_of(&icc, "press").unwrap();
+    assert_eq!(conversion.method, ConversionMethod::Icc);
+    assert_eq!(conversion.profile.as_deref(), Some(PROFILE_DIGEST));
+    assert_eq!(tiff_pixels(&decoded(&icc, "press")).1, [1, 2, 3, 4].repeat(16));
+}
+
+#[test]
+fn test_conversion_is_deterministic() {
+    let pipeline = CompilationPipeline::new(registry());
+    let hashes = |asset: CompiledAsset| asset.exports.into_iter().map(|e| e.hash).collect::<Vec<_>>();
+    let first = hashes(pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap());
+    let second = hashes(pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap());
+    assert_eq!(first, second);
+}
+
+#[cfg(feature = "icc")]
+mod icc {
+    use super::*;
+    use forgeimages_core::{
+        color::{CmykConverter, IccCmyk, PROCESS_INKS},
+        icc::{IccProfile, IccProfileStore},
+    };
+    use moxcms::{
+        ColorProfile, DataColorSpace, LutMultidimensionalType, LutStore, LutWarehouse, Matrix3d, ProfileClass,
+        ToneReprCurve, Vector3d,
+    };
+
+    /// D50 white, the ICC profile connection space's
+    const WHITE: [f64; 3] = [0.9642, 1.0, 0.8249];
+    /// Linear sRGB to XYZ, Bradford-adapted to D50
+    const RGB_TO_XYZ: [[f64; 3]; 3] = [
+        [0.4360747, 0.3850649, 0.1430804],
+        [0.2225045, 0.7168786, 0.0606169],
+        [0.0139322, 0.0971045, 0.7141733],
+    ];
+    const XYZ_TO_RGB: [[f64; 3]; 3] = [
+        [3.1338561, -1.6168667, -0.4906146],
+        [-0.9787684, 1.9161415, 0.0334540],
+        [0.0719453, -0.2289914, 1.4052427],
+    ];
+
+    fn apply(matrix: [[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+        matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+    }
+
+    fn srgb_to_lab(rgb: [f64; 3]) -> [f64; 3] {
+        let linear = rgb.map(|c| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) });
+        let xyz = apply(RGB_TO_XYZ, linear);
+        let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+        let [fx, fy, fz] = [0, 1, 2].map(|i| f(xyz[i] / WHITE[i]));
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    fn lab_to_srgb([l, a, b]: [f64; 3]) -> [f64; 3] {
+        let fy = (l + 16.0) / 116.0;
+        let f = |t: f64| if t > 6.0 / 29.0 { t.powi(3) } else { 108.0 / 841.0 * (t - 4.0 / 29.0) };
+        let xyz = [f(fy + a / 500.0) * WHITE[0], f(fy) * WHITE[1], f(fy - b / 200.0) * WHITE[2]];
+        apply(XYZ_TO_RGB, xyz).map(|c| {
+            let c = c.clamp(0.0, 1.0);
+            if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+        })
+    }
+
+    /// Each ink the complement of its channel, no black: smooth, unlike
+    /// `NaiveCmyk` near black, so a CLUT reproduces it closely
+    fn complement([r, g, b]: [f64; 3]) -> [f64; 4] {
+        [1.0 - r, 1.0 - g, 1.0 - b, 0.0]
+    }
+
+    /// `simulate_press` without the 8-bit rounding
+    fn press(cmyk: [f64; 4]) -> [f64; 3] {
+        [0, 1, 2].map(|channel| {
+            cmyk.iter().zip(PROCESS_INKS).map(|(coverage, ink)| 1.0 - coverage * (1.0 - ink[channel] as f64 / 255.0)).product()
+        })
+    }
+
+    /// Every node of a `grid`-point CLUT with `inputs` channels, first
+    /// channel slowest, as coordinates in 0..=1
+    fn nodes(grid: usize, inputs: u32) -> impl Iterator<Item = Vec<f64>> {
+        (0..grid.pow(inputs)).map(move |index| {
+            (0..inputs).rev().map(|channel| (index / grid.pow(channel) % grid) as f64 / (grid - 1) as f64).collect()
+        })
+    }
+
+    /// mAB/mBA tables: identity curves around a 16-bit CLUT (CMYK always
+    /// on the A side)
+    fn lut(inputs: u8, outputs: u8, grid: u8, table: Vec<u16>) -> LutWarehouse {
+        let identity = |n: u8| vec![ToneReprCurve::Parametric(vec![1.0]); n as usize];
+        let mut grid_points = [0; 16];
+        grid_points[..inputs as usize].fill(grid);
+        LutWarehouse::Multidimensional(LutMultidimensionalType {
+            num_input_channels: inputs,
+            num_output_channels: outputs,
+            grid_points,
+            clut: Some(LutStore::Store16(table)),
+            a_curves: identity(4),
+            b_curves: identity(3),
+            m_curves: vec![],
+            matrix: Matrix3d::IDENTITY,
+            bias: Vector3d::default(),
+        })
+    }
+
+    /// A CMYK output profile (v4, Lab connection space) that separates
+    /// sRGB by `complement` and prints the inks the way `simulate_press`
+    /// does, so known swatches have known separations
+    fn complement_press_profile() -> Vec<u8> {
+        let encode = |v: f64| (v.clamp(0.0, 1.0) * 65535.0).round() as u16;
+        let to_cmyk = nodes(33, 3)
+            .flat_map(|lab| complement(lab_to_srgb([lab[0] * 100.0, lab[1] * 255.0 - 128.0, lab[2] * 255.0 - 128.0])))
+            .map(encode)
+            .collect();
+        let to_lab = nodes(9, 4)
+            .flat_map(|cmyk| {
+                let [l, a, b] = srgb_to_lab(press([cmyk[0], cmyk[1], cmyk[2], cmyk[3]]));
+                [l / 100.0, (a + 128.0) / 255.0, (b + 128.0) / 255.0]
+            })
+            .map(encode)
+            .collect();
+        let mut profile = ColorProfile::default();
+        profile.profile_class = ProfileClass::OutputDevice;
+        profile.color_space = DataColorSpace::Cmyk;
+        profile.pcs = DataColorSpace::Lab;
+        profile.lut_b_to_a_perceptual = Some(lut(3, 4, 33, to_cmyk));
+        profile.lut_a_to_b_perceptual = Some(lut(4, 3, 9, to_lab));
+        profile.encode().unwrap()
+    }
+
+    fn converter() -> (IccCmyk, IccProfile) {
+        let profile = IccProfile::new("Complement Press", complement_press_profile()).unwrap();
+        (IccCmyk::new(&profile).unwrap(), profile)
+    }
+
+    fn assert_close<const N: usize>(actual: [u8; N], expected: [u8; N], tolerance: u8, what: &str) {
+        let off = actual.iter().zip(expected).any(|(&a, e)| a.abs_diff(e) > tolerance);
+        assert!(!off, "{}: {:?}, expected {:?} within {}", what, actual, expected, tolerance);
+    }
+
+    #[test]
+    fn test_known_swatches_separate_through_the_profile() {
+        let (icc, profile) = converter();
+        // Neutrals and muted colors sit inside the table's gamut and land
+        // close; full primaries sit on its edge, where the CLUT
+        // interpolates toward clipped neighbors
+        let swatches = [
+            ("white", [255, 255, 255], [0, 0, 0, 0], 2),
+            ("black", [0, 0, 0], [255, 255, 255, 0], 2),
+            ("mid gray", [128, 128, 128], [127, 127, 127, 0], 2),
+            ("dusty rose", [192, 128, 128], [63, 127, 127, 0], 3),
+            ("sage", [128, 160, 128], [127, 95, 127, 0], 3),
+            ("red", [255, 0, 0], [0, 255, 255, 0], 24),
+            ("green", [0, 255, 0], [255, 0, 255, 0], 24),
+            ("blue", [0, 0, 255], [255, 255, 0, 0], 24),
+        ];
+        for (name, rgb, cmyk, tolerance) in swatches {
+            assert_close(icc.convert(rgb), cmyk, tolerance, name);
+        }
+        assert_eq!(icc.conversion(), ColorConversion { method: ConversionMethod::Icc, profile: Some(profile.sha256().to_string()) });
+
+        // Soft proofs print the inks through the profile's own tables
+        assert_close(icc.to_rgb([0, 0, 0, 0]), [255, 255, 255], 2, "paper");
+        for (ink, appearance) in PROCESS_INKS.into_iter().enumerate() {
+            let mut cmyk = [0; 4];
+            cmyk[ink] = 255;
+            assert_close(icc.to_rgb(cmyk), appearance, 4, "process ink");
+        }
+    }
+
+    #[test]
+    fn test_profile_conversion_is_deterministic() {
+        let (first, _) = converter();
+        let (second, _) = converter();
+        for value in (0..=255).step_by(17) {
+            for rgb in [[value, 0, 255 - value], [value, value, 0], [255 - value, value, value]] {
+                assert_eq!(first.convert(rgb), second.convert(rgb));
+                assert_eq!(first.convert(rgb), first.convert(rgb));
+            }
+        }
+    }
+
+    #[test]
+    fn test_only_cmyk_profiles_with_tables_convert() {
+        let mut srgb = ColorProfile::new_srgb();
+        srgb.profile_class = ProfileClass::DisplayDevice;
+        let rgb = IccProfile::new("sRGB", srgb.encode().unwrap()).unwrap();
+        let error = IccCmyk::new(&rgb).err().unwrap();
+        assert!(error.to_string().contains("not a CMYK output profile"), "{}", error);
+
+        // Header only: registers, but has nothing to convert with
+        let mut header = complement_press_profile()[..128].to_vec();
+        header[..4].copy_from_slice(&128u32.to_be_bytes());
+        let bare = IccProfile::new("Bare", header).unwrap();
+        assert!(IccCmyk::new(&bare).is_err());
+    }
+
+    #[test]
+    fn test_manifest_records_the_profile_used() {
+        let mut store = IccProfileStore::new();
+        let profile = store.register("Complement Press", complement_press_profile()).unwrap().clone();
+        let asset = CompilationPipeline::builder(registry())
+            .icc_profiles(store)
+            .cmyk_converter(IccCmyk::new(&profile).unwrap())
+            .build()
+            .compile_asset(&compile_request("test-icon", 1024, 1024))
+            .unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(&canonical_json(&asset).unwrap()).unwrap();
+        let press = manifest["exports"].as_array().unwrap().iter().find(|e| e["id"] == "press").unwrap();
+        assert_eq!(press["color_conversion"], serde_json::json!({"method": "icc", "profile": profile.sha256()}));
+        // White paper takes next to no ink through this profile either
+        let (_, pixels) = tiff_pixels(&decoded(&asset, "press"));
+        assert!(pixels.iter().all(|&ink| ink <= 2), "{:?}", pixels);
+    }
+}
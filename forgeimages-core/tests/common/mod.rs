@@ -0,0 +1,201 @@
+//! Shared fixtures for integration tests
+
+#![allow(dead_code)]
+
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest,
+    templates::{Template, TemplateRegistry, AssetClass, ValidationConfig, ValidationRules, RuleConfig, ResolutionRule, FailureMode, ExportSpec, ExportFormat},
+    validation::AssetInput,
+};
+
+pub fn create_test_template() -> Template {
+    Template {
+        id: "test-icon".to_string(),
+        name: "Test Icon".to_string(),
+        description: "Test template".to_string(),
+        template_version: "1.0.0".to_string(),
+        engine_min_version: "1.0.0".to_string(),
+        engine_max_version: None,
+        deprecated: false,
+        superseded_by: None,
+        tags: vec![],
+        asset_class: AssetClass::Icon,
+        aspect_ratio: [1, 1],
+        canonical_size: [1024, 1024],
+        vector_master: true,
+        validation: ValidationConfig {
+            required: true,
+            failure_mode: FailureMode::Block,
+            rules: ValidationRules {
+                aspect_ratio: RuleConfig {
+                    enabled: true,
+                    tolerance: 0.01,
+                },
+                resolution: ResolutionRule {
+                    enabled: true,
+                    min_width: 512,
+                    min_height: 512,
+                },
+                color_count: Default::default(),
+                print: Default::default(),
+            },
+        },
+        exports: vec![
+            export("master", [1024, 1024], ExportFormat::Svg, true),
+        ],
+        embed_metadata: false,
+        background_generator: Default::default(),
+        text_slots: vec![],
+        font: None,
+        print: None,
+        scaling_policy: Default::default(),
+    }
+}
+
+pub fn export(id: &str, size: [u32; 2], format: ExportFormat, required: bool) -> ExportSpec {
+    ExportSpec {
+        id: id.to_string(),
+        description: format!("{} export", id),
+        size,
+        physical_preset: None,
+        format,
+        required,
+        scaling_policy: None,
+        print: None,
+        imposition: None,
+        soft_proof: false,
+        guides: false,
+    }
+}
+
+pub fn create_pipeline() -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::new(registry)
+}
+
+pub fn asset_input(width: u32, height: u32) -> AssetInput {
+    AssetInput {
+        width,
+        height,
+        color_count: None,
+        format: None,
+    }
+}
+
+pub fn compile_request(template_id: &str, width: u32, height: u32) -> CompileRequest {
+    CompileRequest {
+        template_id: template_id.to_string(),
+        asset_input: asset_input(width, height),
+        source_data: None,
+        source_path: None,
+        seed: None,
+        prompt: None,
+        params: Default::default(),
+        print_spec: None,
+    }
+}
+
+/// Minimal TrueType font: 'A' maps to a house shape with one quadratic
+/// control point; everything else maps to an empty .notdef.
+pub fn tiny_font() -> Vec<u8> {
+    fn be16(out: &mut Vec<u8>, v: u16) { out.extend_from_slice(&v.to_be_bytes()); }
+    fn be32(out: &mut Vec<u8>, v: u32) { out.extend_from_slice(&v.to_be_bytes()); }
+
+    let mut head = vec![0u8; 54];
+    head[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+    head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+
+    let mut maxp = vec![];
+    be32(&mut maxp, 0x0000_5000);
+    be16(&mut maxp, 2);
+
+    let mut hhea = vec![0u8; 36];
+    hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    hhea[34..36].copy_from_slice(&2u16.to_be_bytes());
+
+    let mut hmtx = vec![];
+    for (advance, lsb) in [(500u16, 0u16), (600, 50)] {
+        be16(&mut hmtx, advance);
+        be16(&mut hmtx, lsb);
+    }
+
+    let mut glyf = vec![];
+    for v in [1i16, 50, 0, 550, 900] { be16(&mut glyf, v as u16); }
+    be16(&mut glyf, 4); // end point of the only contour
+    be16(&mut glyf, 0); // no instructions
+    glyf.extend_from_slice(&[1, 1, 1, 0, 1]); // on, on, on, off, on
+    for dx in [50i16, 500, 0, -250, -250] { be16(&mut glyf, dx as u16); }
+    for dy in [0i16, 0, 700, 200, -200] { be16(&mut glyf, dy as u16); }
+    glyf.push(0); // pad to even length for short loca
+
+    let mut loca = vec![];
+    for offset in [0u16, 0, (glyf.len() / 2) as u16] { be16(&mut loca, offset); }
+
+    let mut cmap = vec![];
+    be16(&mut cmap, 0);
+    be16(&mut cmap, 1);
+    be16(&mut cmap, 3);
+    be16(&mut cmap, 1);
+    be32(&mut cmap, 12);
+    for v in [4u16, 32, 0, 4, 4, 1, 0, 0x41, 0xFFFF, 0, 0x41, 0xFFFF, 1u16.wrapping_sub(0x41), 1, 0, 0] {
+        be16(&mut cmap, v);
+    }
+
+    let tables: [(&[u8; 4], Vec<u8>); 7] = [
+        (b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea),
+        (b"hmtx", hmtx), (b"loca", loca), (b"maxp", maxp),
+    ];
+    let mut font = vec![];
+    be32(&mut font, 0x0001_0000);
+    be16(&mut font, tables.len() as u16);
+    font.extend_from_slice(&[0; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        font.extend_from_slice(*tag);
+        be32(&mut font, 0);
+        be32(&mut font, offset as u32);
+        be32(&mut font, data.len() as u32);
+        offset += data.len();
+    }
+    for (_, data) in &tables {
+        font.extend_from_slice(data);
+    }
+    font
+}
+
+/// (chunk type, chunk data) of every chunk in a PNG, in file order
+pub fn png_chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let (mut pos, mut chunks) = (8, vec![]);
+    while pos < png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        chunks.push((&png[pos + 4..pos + 8], &png[pos + 8..pos + 8 + len]));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+/// Bytes of a zlib stream written with `PngCompression::Stored`
+pub fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    // zlib header, then stored blocks of (final flag, LEN, NLEN, bytes)
+    let mut zlib = &zlib[2..];
+    let mut raw = vec![];
+    loop {
+        let block_len = u16::from_le_bytes([zlib[1], zlib[2]]) as usize;
+        raw.extend_from_slice(&zlib[5..5 + block_len]);
+        if zlib[0] & 1 == 1 {
+            return raw;
+        }
+        zlib = &zlib[5 + block_len..];
+    }
+}
+
+/// (color type, scanlines with their filter bytes) of a PNG written with
+/// `PngCompression::Stored`
+pub fn stored_png(png: &[u8]) -> (u8, Vec<u8>) {
+    let chunks = png_chunks(png);
+    let color_type = chunks.iter().find(|(kind, _)| kind == b"IHDR").unwrap().1[9];
+    let idat: Vec<u8> = chunks.iter().filter(|(kind, _)| kind == b"IDAT").flat_map(|(_, data)| data.iter().copied()).collect();
+    (color_type, inflate_stored(&idat))
+}
@@ -0,0 +1,97 @@
+//! Compile Sets - fan-out of one source across templates
+
+mod common;
+
+use common::{asset_input, compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequestCommon, SourceArtifact, Template,
+    compile_set::SetEntryStatus,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn template(id: &str, min_size: u32) -> Template {
+    let mut template = create_test_template();
+    template.id = id.to_string();
+    template.validation.rules.resolution.min_width = min_size;
+    template.validation.rules.resolution.min_height = min_size;
+    template.exports.push(export("png", [32, 32], ExportFormat::Png, true));
+    template
+}
+
+fn pipeline() -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(template("icon", 512));
+    registry.register(template("poster", 4096));
+    registry.register(template("favicon", 16));
+    CompilationPipeline::new(registry)
+}
+
+const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"/>"#;
+
+#[test]
+fn test_failure_does_not_abort_other_templates() {
+    let source = SourceArtifact::from_bytes(asset_input(1024, 1024), SVG.to_vec());
+    let result = pipeline()
+        .compile_set(source, &["icon", "poster", "favicon"], CompileRequestCommon::default())
+        .unwrap();
+
+    let statuses: Vec<_> = result.manifest.entries.iter().map(|e| e.status).collect();
+    assert_eq!(statuses, vec![SetEntryStatus::Compiled, SetEntryStatus::Failed, SetEntryStatus::Compiled]);
+    assert_eq!(result.results.len(), 3);
+    assert!(result.results[1].result.is_err());
+    assert!(!result.all_succeeded());
+
+    let hashes: Vec<_> = result.results.iter()
+        .filter_map(|m| m.result.as_ref().ok())
+        .map(|a| Some(a.manifest_hash.clone()))
+        .collect();
+    assert_eq!(hashes, vec![result.manifest.entries[0].manifest_hash.clone(), result.manifest.entries[2].manifest_hash.clone()]);
+}
+
+#[test]
+fn test_fail_fast_skips_remaining_templates() {
+    let shared = CompileRequestCommon { fail_fast: true, ..Default::default() };
+    let result = pipeline()
+        .compile_set(SourceArtifact::new(asset_input(1024, 1024)), &["poster", "icon"], shared)
+        .unwrap();
+
+    assert_eq!(result.results.len(), 1);
+    assert_eq!(result.manifest.entries[1].status, SetEntryStatus::Skipped);
+}
+
+#[test]
+fn test_set_members_match_single_compiles() {
+    let pipeline = pipeline();
+    let source = SourceArtifact::from_bytes(asset_input(1024, 1024), SVG.to_vec());
+    let result = pipeline
+        .compile_set(source, &["icon", "favicon"], CompileRequestCommon::default())
+        .unwrap();
+
+    for member in &result.results {
+        let mut request = compile_request(&member.template_id, 1024, 1024);
+        request.source_data = Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, SVG));
+        let single = pipeline.compile_asset(&request).unwrap();
+        let from_set = member.result.as_ref().unwrap();
+        assert_eq!(from_set.job_hash, single.job_hash);
+        let hashes = |a: &forgeimages_core::CompiledAsset| a.exports.iter().map(|e| e.hash.clone()).collect::<Vec<_>>();
+        assert_eq!(hashes(from_set), hashes(&single));
+    }
+}
+
+#[test]
+fn test_set_hash_is_deterministic() {
+    let pipeline = pipeline();
+    let run = || {
+        pipeline
+            .compile_set(SourceArtifact::new(asset_input(1024, 1024)), &["poster"], CompileRequestCommon::default())
+            .unwrap()
+            .manifest
+            .set_hash
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_invalid_base64_rejected_once() {
+    assert!(SourceArtifact::from_base64(asset_input(1024, 1024), "not base64!".to_string()).is_err());
+}
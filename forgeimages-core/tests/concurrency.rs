@@ -0,0 +1,81 @@
+//! One pipeline shared across threads
+
+mod common;
+
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use common::{compile_request, create_pipeline, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, PipelineBuilder,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+const THREADS: u64 = 16;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_pipeline_is_send_and_sync() {
+    assert_send_sync::<CompilationPipeline>();
+    assert_send_sync::<PipelineBuilder>();
+    assert_send_sync::<TemplateRegistry>();
+}
+
+#[test]
+fn test_concurrent_compiles_match_sequential_results() {
+    let pipeline = Arc::new(create_pipeline());
+    let request = |seed: u64| {
+        let mut request = compile_request("test-icon", 1024, 1024);
+        request.seed = Some(seed);
+        request
+    };
+    let expected: Vec<_> = (0..THREADS)
+        .map(|seed| {
+            let asset = pipeline.compile_asset(&request(seed)).unwrap();
+            (asset.job_hash, asset.exports[0].hash.clone())
+        })
+        .collect();
+
+    let barrier = Arc::new(Barrier::new(THREADS as usize));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|seed| {
+            let pipeline = Arc::clone(&pipeline);
+            let barrier = Arc::clone(&barrier);
+            let request = request(seed);
+            thread::spawn(move || {
+                barrier.wait();
+                (0..4).map(|_| pipeline.compile_asset(&request).unwrap()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    for (seed, handle) in handles.into_iter().enumerate() {
+        let assets = handle.join().expect("compile thread panicked");
+        for asset in assets {
+            assert_eq!((asset.job_hash, asset.exports[0].hash.clone()), expected[seed]);
+        }
+    }
+}
+
+#[test]
+fn test_registry_can_be_replaced_while_shared() {
+    let pipeline = Arc::new(create_pipeline());
+    assert_eq!(pipeline.get_template("test-icon").unwrap().exports.len(), 1);
+
+    let mut template = create_test_template();
+    template.exports.push(export("png", [64, 64], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+
+    let snapshot = pipeline.registry();
+    let reloader = {
+        let pipeline = Arc::clone(&pipeline);
+        thread::spawn(move || pipeline.replace_registry(registry))
+    };
+    reloader.join().unwrap();
+
+    assert_eq!(snapshot.get("test-icon").unwrap().exports.len(), 1);
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.exports.len(), 2);
+}
@@ -0,0 +1,80 @@
+//! Export deduplication for specs that render identical bytes
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset,
+    hashing::compute_manifest_hash,
+    output::MANIFEST_FILE,
+    templates::{ExportFormat, TemplateRegistry},
+    verify::verify_manifest,
+};
+
+fn pipeline(deduplicate: bool) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("icon-a", [64, 64], ExportFormat::Png, true));
+    template.exports.push(export("icon-b", [64, 64], ExportFormat::Png, true));
+    template.exports.push(export("icon-small", [32, 32], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .deduplicate_exports(deduplicate)
+        .build()
+}
+
+/// Manifest hash with the per-compile id and timestamp pinned, and the
+/// config hash, which records the `deduplicate_exports` setting itself
+fn stable_hash(asset: &CompiledAsset) -> String {
+    let mut manifest = serde_json::to_value(asset).unwrap();
+    manifest["id"] = "pinned".into();
+    manifest["created_at"] = "pinned".into();
+    manifest["config_hash"] = "pinned".into();
+    manifest["manifest_hash"] = "".into();
+    for export in manifest["exports"].as_array_mut().unwrap() {
+        export.as_object_mut().unwrap().remove("deduplicated_from");
+    }
+    compute_manifest_hash(&manifest).unwrap()
+}
+
+#[test]
+fn test_identical_exports_share_one_render() {
+    let asset = pipeline(true).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let a = asset.exports.iter().find(|e| e.id == "icon-a").unwrap();
+    let b = asset.exports.iter().find(|e| e.id == "icon-b").unwrap();
+    let small = asset.exports.iter().find(|e| e.id == "icon-small").unwrap();
+    assert_eq!(a.hash, b.hash);
+    assert_eq!(a.deduplicated_from, None);
+    assert_eq!(b.deduplicated_from.as_deref(), Some("icon-a"));
+    assert_eq!(b.filename, "icon-b.png");
+    assert_eq!(small.deduplicated_from, None);
+
+    assert_eq!(asset.exports.len(), 4);
+    assert_eq!(asset.metrics.deduplicated, 1);
+    assert_eq!(asset.metrics.rendered(), 3);
+    assert_eq!(asset.metrics.attempts("icon-b"), Some(0));
+}
+
+#[test]
+fn test_deduplication_does_not_change_manifest_hash() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let deduplicated = pipeline(true).compile_asset(&request).unwrap();
+    let rendered = pipeline(false).compile_asset(&request).unwrap();
+
+    assert_eq!(rendered.metrics.deduplicated, 0);
+    assert_eq!(rendered.metrics.rendered(), 4);
+    assert!(rendered.exports.iter().all(|e| e.deduplicated_from.is_none()));
+    assert_eq!(stable_hash(&deduplicated), stable_hash(&rendered));
+}
+
+#[test]
+fn test_deduplicated_manifest_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = pipeline(true)
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+
+    assert!(dir.path().join("icon-b.png").exists());
+    assert_eq!(verify_manifest(&dir.path().join(MANIFEST_FILE)).unwrap(), asset.manifest_hash);
+}
@@ -0,0 +1,113 @@
+//! Effective print dpi of raster sources at the physical export size
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    AssetClass, CompilationPipeline, CompileRequest, PipelineError, Template,
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, FailureMode, TemplateRegistry},
+    units::Length,
+    validation::{ValidationViolation, ViolationSeverity},
+};
+
+/// Cover with a 10 x 10 in print export at 300 dpi, and a 5 x 5 in one
+fn cover() -> Template {
+    let mut template = create_test_template();
+    template.asset_class = AssetClass::Cover;
+    template.print = Some(TemplatePrint {
+        dpi: 300,
+        color_space: ColorSpace::Rgb,
+        bleed: Length::ZERO,
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    });
+    template.exports.push(export("small", [1500, 1500], ExportFormat::Pdf, true));
+    template.exports.push(export("large", [3000, 3000], ExportFormat::Pdf, true));
+    template
+}
+
+fn violations(template: Template, request: &CompileRequest) -> Result<Vec<ValidationViolation>, PipelineError> {
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let asset = CompilationPipeline::new(registry).compile_asset(request)?;
+    Ok(asset.validation.violations.into_iter().filter(|v| v.rule == "effective_dpi").collect())
+}
+
+fn warn(mut template: Template) -> Template {
+    template.validation.failure_mode = FailureMode::Warn;
+    template
+}
+
+#[test]
+fn test_source_reaching_the_dpi_passes() {
+    let found = violations(warn(cover()), &compile_request("test-icon", 3000, 3000)).unwrap();
+    assert!(found.is_empty(), "{:?}", found);
+}
+
+#[test]
+fn test_shortfall_within_the_band_warns() {
+    // 2800 px over 10 in is 280 dpi, 6.7% short
+    let found = violations(warn(cover()), &compile_request("test-icon", 2800, 2800)).unwrap();
+    assert_eq!(found.len(), 1);
+    let violation = &found[0];
+    assert_eq!(violation.severity, ViolationSeverity::Warning);
+    assert!(violation.message.contains("export large"), "{}", violation.message);
+    assert_eq!(violation.expected.as_deref(), Some("300 dpi at 10.00x10.00 in"));
+    assert_eq!(violation.actual.as_deref(), Some("280.0x280.0 dpi from a 2800x2800 source"));
+}
+
+#[test]
+fn test_shortfall_beyond_the_band_blocks() {
+    let result = violations(cover(), &compile_request("test-icon", 2000, 2000));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("effective_dpi")));
+
+    // A wider band turns the same shortfall into a warning
+    let mut template = warn(cover());
+    template.validation.rules.print.effective_dpi.warning_band = 0.5;
+    let found = violations(template, &compile_request("test-icon", 2000, 2000)).unwrap();
+    assert_eq!(found[0].severity, ViolationSeverity::Warning);
+    assert_eq!(found[0].actual.as_deref(), Some("200.0x200.0 dpi from a 2000x2000 source"));
+}
+
+#[test]
+fn test_svg_sources_and_other_asset_classes_are_exempt() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.asset_input.format = Some("svg".to_string());
+    assert!(violations(cover(), &request).unwrap().is_empty());
+
+    let mut icon = cover();
+    icon.asset_class = AssetClass::Icon;
+    assert!(violations(icon, &compile_request("test-icon", 1024, 1024)).unwrap().is_empty());
+
+    let mut disabled = cover();
+    disabled.validation.rules.print.effective_dpi.enabled = false;
+    assert!(violations(disabled, &compile_request("test-icon", 1024, 1024)).unwrap().is_empty());
+}
+
+#[test]
+fn test_default_rule_config_is_not_serialized() {
+    let json = serde_json::to_value(cover()).unwrap();
+    assert!(json["validation"]["rules"].get("print").is_none());
+
+    let mut template = cover();
+    template.validation.rules.print.effective_dpi.asset_classes = vec![AssetClass::Banner];
+    let json = serde_json::to_value(&template).unwrap();
+    assert_eq!(
+        json["validation"]["rules"]["print"]["effectiveDpi"],
+        serde_json::json!({"enabled": true, "warningBand": 0.1, "assetClasses": ["banner"]}),
+    );
+    let parsed: Template = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.validation.rules.print, template.validation.rules.print);
+}
@@ -0,0 +1,116 @@
+//! Encoder Determinism
+//!
+//! PNG bytes are pinned: any change to filter, deflate, or chunk layout
+//! must show up here as a golden mismatch.
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, EncodingProfile,
+    encoding::{PngCompression, PngFilter, encode_png},
+    hashing::sha256_hex,
+    raster::Raster,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn gradient() -> Raster {
+    let mut raster = Raster::new(16, 16);
+    for y in 0..16 {
+        for x in 0..16 {
+            raster.set(x, y, [(x * 16) as u8, (y * 16) as u8, 128, 255]);
+        }
+    }
+    raster
+}
+
+fn png_pipeline(embed_metadata: bool) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.embed_metadata = embed_metadata;
+    template.exports.push(export("pwa-192", [192, 192], ExportFormat::Png, true));
+    template.exports.push(export("favicon-16", [16, 16], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry)
+}
+
+#[test]
+fn golden_png_bytes() {
+    let png = encode_png(&gradient(), &EncodingProfile::default(), &vec![], None);
+    assert_eq!(
+        sha256_hex(&png),
+        "eb4bea4b79d00d24cc3730e4278e06696d7a5c33cde41fb1977b88df4fbf73ef"
+    );
+}
+
+/// The goldens pin bytes, not validity: a real decoder must read every
+/// profile back to the pixels it was given
+#[test]
+fn decoder_reads_every_profile() {
+    let raster = gradient();
+    let filters = [PngFilter::None, PngFilter::Sub, PngFilter::Up, PngFilter::Average, PngFilter::Paeth];
+    for png_compression in [PngCompression::Stored, PngCompression::FixedHuffman] {
+        for png_filter in filters {
+            let profile = EncodingProfile { png_filter, png_compression };
+            let png = encode_png(&raster, &profile, &vec![], None);
+            let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut pixels).unwrap();
+            assert_eq!((info.width, info.height, info.color_type), (16, 16, png::ColorType::Rgba), "{:?}", profile);
+            assert_eq!(pixels, raster.pixels, "{:?}", profile);
+        }
+    }
+}
+
+#[test]
+fn decoder_reads_compiled_exports() {
+    let asset = png_pipeline(true).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    for export in asset.exports.iter().filter(|e| e.filename.ends_with(".png")) {
+        let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &export.data_base64).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!([info.width, info.height], export.size, "{}", export.id);
+    }
+}
+
+#[test]
+fn manifest_records_encoding_profile() {
+    let asset = png_pipeline(false).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.encoding, EncodingProfile::default());
+
+    let json = serde_json::to_value(&asset).unwrap();
+    assert_eq!(json["encoding"]["png_filter"], "up");
+    assert_eq!(json["encoding"]["png_compression"], "fixed_huffman");
+}
+
+#[test]
+fn concurrent_compiles_are_byte_identical() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let hashes: Vec<Vec<String>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..2)
+            .map(|_| scope.spawn(|| {
+                let asset = png_pipeline(true).compile_asset(&request).unwrap();
+                asset.exports.iter()
+                    .map(|e| format!("{}:{}:{}", e.id, e.hash, e.data_base64.len()))
+                    .collect()
+            }))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert_eq!(hashes[0], hashes[1]);
+    assert_eq!(hashes[0].len(), 3);
+}
+
+#[test]
+fn embedded_metadata_never_uses_the_clock() {
+    let asset = png_pipeline(true).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let favicon = asset.exports.iter().find(|e| e.id == "favicon-16").unwrap();
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &favicon.data_base64).unwrap();
+
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"tEXt"));
+    assert!(contains(asset.job_hash.as_bytes()));
+    assert!(!contains(b"tIME"));
+}
@@ -0,0 +1,118 @@
+//! forgeimages-cli exit codes, one failure class each; the bridge relies
+//! on these, so a change here is a breaking change
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assert_cmd::Command;
+use forgeimages_core::exit_code;
+
+const PAYLOAD: &str = r#"{"template_id":"pwa-icon","asset_input":{"width":1024,"height":1024}}"#;
+
+fn shipped_templates() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("templates")
+}
+
+fn cli(templates_dir: &Path) -> Command {
+    let mut command = Command::cargo_bin("forgeimages-cli").unwrap();
+    command.arg("--templates-dir").arg(templates_dir);
+    command
+}
+
+#[test]
+fn test_success() {
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "-p", PAYLOAD])
+        .assert()
+        .code(exit_code::SUCCESS as i32);
+}
+
+#[test]
+fn test_usage_and_other_errors() {
+    cli(&shipped_templates()).args(["compile", "--no-such-flag"])
+        .assert()
+        .code(1);
+    cli(&shipped_templates()).args(["templates", "--asset-class", "poster"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_validation_failure() {
+    cli(&shipped_templates()).args(["validate", "-t", "pwa-icon", "-p", r#"{"width":100,"height":100}"#])
+        .assert()
+        .code(2);
+    let small = r#"{"template_id":"pwa-icon","asset_input":{"width":100,"height":100}}"#;
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "-p", small])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_template_not_found() {
+    cli(&shipped_templates()).args(["compile", "-t", "missing", "-p", PAYLOAD])
+        .assert()
+        .code(3);
+    cli(&shipped_templates()).args(["templates", "show", "missing"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_version_mismatch() {
+    let temp = tempfile::tempdir().unwrap();
+    let template = fs::read_to_string(shipped_templates().join("pwa-icon.json")).unwrap()
+        .replace(r#""engineMinVersion": "1.0.0""#, r#""engineMinVersion": "99.0.0""#);
+    fs::write(temp.path().join("pwa-icon.json"), template).unwrap();
+    cli(temp.path()).args(["compile", "-t", "pwa-icon", "-p", PAYLOAD])
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn test_invalid_payload() {
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "-p", "{not json"])
+        .assert()
+        .code(5);
+    let notes = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sources/notes.txt");
+    cli(&shipped_templates()).args(["validate", "-t", "pwa-icon", "--file"]).arg(notes)
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn test_io_errors() {
+    let temp = tempfile::tempdir().unwrap();
+    fs::write(temp.path().join("notes.txt"), "keep me").unwrap();
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "-p", PAYLOAD, "--output-dir"]).arg(temp.path())
+        .assert()
+        .code(6);
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "--file"]).arg(temp.path().join("missing.png"))
+        .assert()
+        .code(6);
+}
+
+#[test]
+fn test_verification_failure() {
+    let temp = tempfile::tempdir().unwrap();
+    cli(&shipped_templates()).args(["compile", "-t", "pwa-icon", "-p", PAYLOAD, "--output-dir"]).arg(temp.path())
+        .assert()
+        .success();
+    let manifest = temp.path().join("manifest.json");
+    let tampered = fs::read_to_string(&manifest).unwrap().replacen("pwa-icon", "pwa-icOn", 1);
+    fs::write(&manifest, tampered).unwrap();
+    cli(&shipped_templates()).args(["verify", "--dir"]).arg(temp.path())
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_failed_on_warnings() {
+    let temp = tempfile::tempdir().unwrap();
+    let template = fs::read_to_string(shipped_templates().join("pwa-icon.json")).unwrap()
+        .replace(r#""failureMode": "block""#, r#""failureMode": "warn""#);
+    fs::write(temp.path().join("pwa-icon.json"), template).unwrap();
+    let warned = r#"{"width":1024,"height":1024,"color_count":300}"#;
+    cli(temp.path()).args(["validate", "-t", "pwa-icon", "-p", warned, "--fail-on-warning"])
+        .assert()
+        .code(9);
+}
@@ -0,0 +1,64 @@
+//! Parallel export hashing produces the same exports as serial hashing
+
+mod common;
+
+use base64::Engine;
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, HashAlgorithm,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn compile(parallelism: usize, deduplicate: bool, algorithm: HashAlgorithm) -> CompiledAsset {
+    let mut template = create_test_template();
+    template.exports.push(export("png-64", [64, 64], ExportFormat::Png, true));
+    template.exports.push(export("png-64-copy", [64, 64], ExportFormat::Png, true));
+    template.exports.push(export("png-32", [32, 32], ExportFormat::Png, true));
+    template.exports.push(export("jpg-48", [48, 48], ExportFormat::Jpg, false));
+    template.exports.push(export("ico-16", [16, 16], ExportFormat::Ico, false));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .export_parallelism(parallelism)
+        .deduplicate_exports(deduplicate)
+        .hash_algorithm(algorithm)
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap()
+}
+
+#[test]
+fn test_parallel_exports_match_serial_exports() {
+    for deduplicate in [false, true] {
+        let serial = compile(1, deduplicate, HashAlgorithm::Sha256);
+        assert_eq!(serial.exports.len(), 6);
+        for parallelism in [2, 4, 16] {
+            let parallel = compile(parallelism, deduplicate, HashAlgorithm::Sha256);
+            assert_eq!(
+                serde_json::to_value(&parallel.exports).unwrap(),
+                serde_json::to_value(&serial.exports).unwrap(),
+                "parallelism {} deduplicate {}", parallelism, deduplicate,
+            );
+            assert_eq!(parallel.exports_root, serial.exports_root);
+        }
+    }
+}
+
+#[test]
+fn test_streamed_hash_and_base64_match_the_rendered_bytes() {
+    for export in compile(4, false, HashAlgorithm::Sha256).exports {
+        let data = base64::engine::general_purpose::STANDARD.decode(&export.data_base64).unwrap();
+        assert_eq!(export.hash, HashAlgorithm::Sha256.digest(&data), "{}", export.id);
+        assert_eq!(base64::engine::general_purpose::STANDARD.encode(&data), export.data_base64);
+    }
+}
+
+#[test]
+fn test_zero_parallelism_is_serial() {
+    let serial = compile(1, false, HashAlgorithm::Sha256);
+    let zero = compile(0, false, HashAlgorithm::Sha256);
+    assert_eq!(
+        serde_json::to_value(&zero.exports).unwrap(),
+        serde_json::to_value(&serial.exports).unwrap(),
+    );
+}
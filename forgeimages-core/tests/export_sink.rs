@@ -0,0 +1,219 @@
+//! Export Sinks
+//!
+//! Stored exports are recorded in the manifest; a required export that
+//! cannot be stored rolls back its siblings.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, HashScheme, PipelineError,
+    hashing::sha256_hex,
+    pipeline::ManifestHashView,
+    sink::{ExportSink, ExportUpload, SinkError, StoredObject},
+    templates::{ExportFormat, TemplateRegistry},
+    validation::ViolationSeverity,
+};
+
+/// Content-Type and bytes by key
+type Objects = BTreeMap<String, (&'static str, Vec<u8>)>;
+
+/// Keeps objects in memory; refuses exports whose id starts with "refused"
+#[derive(Clone, Default)]
+struct MemorySink(Arc<Mutex<Objects>>);
+
+impl ExportSink for MemorySink {
+    fn put(&self, upload: &ExportUpload<'_>) -> Result<StoredObject, SinkError> {
+        if upload.export_id.starts_with("refused") {
+            return Err(SinkError::Store("simulated upload failure".to_string()));
+        }
+        self.0.lock().unwrap().insert(upload.name.to_string(), (upload.content_type, upload.data.to_vec()));
+        Ok(upload.stored(upload.name, Some(format!("\"etag-{}\"", upload.export_id))))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), SinkError> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+fn compile(sink: &MemorySink, refused_required: Option<bool>) -> Result<CompiledAsset, PipelineError> {
+    let mut template = create_test_template();
+    template.exports.push(export("icon", [64, 64], ExportFormat::Png, true));
+    if let Some(required) = refused_required {
+        template.exports.push(export("refused-pdf", [64, 64], ExportFormat::Pdf, required));
+    }
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .export_sink(sink.clone())
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+}
+
+#[test]
+fn test_stored_objects_enter_the_manifest() {
+    let sink = MemorySink::default();
+    let asset = compile(&sink, None).unwrap();
+
+    let objects = sink.0.lock().unwrap();
+    assert_eq!(asset.stored_objects.len(), 2);
+    for (export, object) in asset.exports.iter().zip(&asset.stored_objects) {
+        assert_eq!(object.export_id, export.id);
+        assert_eq!(object.key, format!("{}/{}", asset.id, export.filename));
+        assert_eq!(object.etag.as_deref(), Some(format!("\"etag-{}\"", export.id).as_str()));
+        let (_, data) = &objects[&object.key];
+        assert_eq!(object.sha256, sha256_hex(data));
+    }
+    assert_eq!(objects[&asset.stored_objects[0].key].0, "image/svg+xml");
+    assert_eq!(objects[&asset.stored_objects[1].key].0, "image/png");
+
+    assert!(asset.canonical_json().unwrap().contains(r#""stored_objects":[{"#));
+    let mut moved = asset.clone();
+    moved.stored_objects[0].key = "elsewhere".to_string();
+    let rehash = |asset: &CompiledAsset| ManifestHashView::of(asset).unwrap().hash(HashScheme::CURRENT, asset.hash_algorithm).unwrap();
+    assert_eq!(rehash(&asset), asset.manifest_hash);
+    assert_ne!(rehash(&moved), asset.manifest_hash);
+}
+
+#[test]
+fn test_manifests_without_a_sink_are_unchanged() {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    let asset = CompilationPipeline::new(registry).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert!(asset.stored_objects.is_empty());
+    assert!(!asset.canonical_json().unwrap().contains("stored_objects"));
+}
+
+#[test]
+fn test_required_upload_failure_rolls_back() {
+    let sink = MemorySink::default();
+    match compile(&sink, Some(true)).unwrap_err() {
+        PipelineError::OutputFailed(message) => assert!(message.contains("refused-pdf.pdf"), "{}", message),
+        other => panic!("unexpected error: {other}"),
+    }
+    assert!(sink.0.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_optional_upload_failure_is_a_warning() {
+    let sink = MemorySink::default();
+    let asset = compile(&sink, Some(false)).unwrap();
+
+    let ids: Vec<_> = asset.exports.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, ["master", "icon"]);
+    assert_eq!(asset.stored_objects.len(), 2);
+    assert_eq!(asset.export_errors[0].export_id, "refused-pdf");
+    let warning = asset.validation.violations.iter().find(|v| v.rule == "export_store_failed").unwrap();
+    assert_eq!(warning.severity, ViolationSeverity::Warning);
+    assert_eq!(sink.0.lock().unwrap().len(), 2);
+}
+
+/// A fake S3 endpoint: records each request, answers PUT with an ETag and
+/// refuses keys containing "refused" with 403
+#[cfg(feature = "s3")]
+#[test]
+fn test_s3_sink_puts_and_rolls_back() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use forgeimages_core::s3::{S3Config, S3Sink};
+
+    #[derive(Debug)]
+    struct Request {
+        method: String,
+        path: String,
+        headers: BTreeMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::<Request>::new()));
+    let recorded = Arc::clone(&requests);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let recorded = Arc::clone(&recorded);
+            std::thread::spawn(move || {
+                let mut writer = stream.unwrap();
+                let mut reader = BufReader::new(writer.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    let mut parts = line.split_whitespace();
+                    let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+                    let mut headers = BTreeMap::new();
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        match header.trim_end().split_once(':') {
+                            Some((name, value)) => headers.insert(name.to_ascii_lowercase(), value.trim().to_string()),
+                            None => break,
+                        };
+                    }
+                    let length = headers.get("content-length").map_or(0, |n| n.parse().unwrap());
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    let response = match method.as_str() {
+                        "PUT" if path.contains("refused") => "HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n".to_string(),
+                        "PUT" => format!("HTTP/1.1 200 OK\r\netag: \"{}\"\r\ncontent-length: 0\r\n\r\n", sha256_hex(&body)),
+                        _ => "HTTP/1.1 204 No Content\r\n\r\n".to_string(),
+                    };
+                    recorded.lock().unwrap().push(Request { method, path, headers, body });
+                    writer.write_all(response.as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+
+    let pipeline = |required: bool| {
+        let mut template = create_test_template();
+        template.exports.push(export("icon", [64, 64], ExportFormat::Png, true));
+        template.exports.push(export("refused-pdf", [64, 64], ExportFormat::Pdf, required));
+        let mut registry = TemplateRegistry::new();
+        registry.register(template);
+        let sink = S3Sink::new(S3Config {
+            bucket: "assets".to_string(),
+            prefix: "/compiled/".to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: Some(endpoint.clone()),
+            access_key_id: Some("AKIDEXAMPLE".to_string()),
+            secret_access_key: Some("secret".to_string()),
+            session_token: None,
+        }).unwrap();
+        CompilationPipeline::builder(registry).export_sink(sink).build()
+    };
+
+    let asset = pipeline(false).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    {
+        let requests = requests.lock().unwrap();
+        let puts: Vec<_> = requests.iter().filter(|r| r.method == "PUT" && !r.path.contains("refused")).collect();
+        assert_eq!(puts.len(), 2);
+        let stored = asset.exports.iter().zip(&asset.stored_objects);
+        for ((put, (export, object)), content_type) in puts.iter().zip(stored).zip(["image/svg+xml", "image/png"]) {
+            assert_eq!(object.key, format!("compiled/{}/{}", asset.id, export.filename));
+            assert_eq!(put.path, format!("/assets/{}", object.key));
+            assert_eq!(put.headers["content-type"], content_type);
+            assert_eq!(object.etag.as_deref(), Some(format!("\"{}\"", sha256_hex(&put.body)).as_str()));
+            assert_eq!(object.sha256, sha256_hex(&put.body));
+            assert!(put.headers.contains_key("x-amz-checksum-sha256"));
+            assert!(put.headers["authorization"].starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        }
+        assert!(!requests.iter().any(|r| r.method == "DELETE"));
+    }
+    assert_eq!(asset.export_errors[0].export_id, "refused-pdf");
+
+    requests.lock().unwrap().clear();
+    let error = pipeline(true).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap_err();
+    assert_eq!(error.kind(), "output_failed");
+    let requests = requests.lock().unwrap();
+    let stored: Vec<_> = requests.iter().filter(|r| r.method == "PUT" && !r.path.contains("refused")).map(|r| &r.path).collect();
+    let deleted: Vec<_> = requests.iter().filter(|r| r.method == "DELETE").map(|r| &r.path).collect();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(deleted, stored);
+}
@@ -0,0 +1,86 @@
+//! Export Failure Semantics
+//!
+//! Required exports abort the compile; optional exports degrade gracefully.
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, PipelineError, Renderer, RenderError, RenderJob,
+    compute_manifest_hash,
+    render::PlaceholderRenderer,
+    templates::{ExportFormat, TemplateRegistry},
+    validation::ViolationSeverity,
+};
+
+/// Fails every export whose id starts with "broken"
+struct FailingRenderer;
+
+impl Renderer for FailingRenderer {
+    fn name(&self) -> &'static str { "failing" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        if job.spec.id.starts_with("broken") {
+            return Err(RenderError::new("simulated render failure"));
+        }
+        PlaceholderRenderer.render(job)
+    }
+}
+
+fn pipeline_with_exports(required_broken: bool) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("broken-png", [64, 64], ExportFormat::Png, required_broken));
+    template.exports.push(export("favicon", [32, 32], ExportFormat::Png, false));
+
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .renderer(FailingRenderer)
+        .build()
+}
+
+#[test]
+fn optional_export_failure_is_recorded() {
+    let pipeline = pipeline_with_exports(false);
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let ids: Vec<_> = asset.exports.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, vec!["master", "favicon"]);
+
+    assert_eq!(asset.export_errors.len(), 1);
+    assert_eq!(asset.export_errors[0].export_id, "broken-png");
+    assert_eq!(asset.export_errors[0].spec.size, [64, 64]);
+
+    let warning = asset.validation.violations.iter()
+        .find(|v| v.rule == "export_failed")
+        .expect("warning violation for failed export");
+    assert_eq!(warning.severity, ViolationSeverity::Warning);
+    assert!(asset.validation.valid);
+}
+
+#[test]
+fn required_export_failure_aborts_compile() {
+    let pipeline = pipeline_with_exports(true);
+    let err = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap_err();
+
+    match err {
+        PipelineError::ExportFailed(id, message) => {
+            assert_eq!(id, "broken-png");
+            assert!(message.contains("simulated"));
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[test]
+fn manifest_hash_covers_export_errors() {
+    let pipeline = pipeline_with_exports(false);
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let mut unhashed = asset.clone();
+    unhashed.manifest_hash = String::new();
+    assert_eq!(compute_manifest_hash(&unhashed).unwrap(), asset.manifest_hash);
+
+    unhashed.export_errors.clear();
+    assert_ne!(compute_manifest_hash(&unhashed).unwrap(), asset.manifest_hash);
+}
@@ -0,0 +1,178 @@
+//! Grayscale print specs: single-channel output, luma values, manifest record
+
+mod common;
+
+use common::{compile_request, create_test_template, export, stored_png};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, EncodingProfile, PipelineError,
+    background::{BackgroundGenerator, PatternStyle},
+    color::ConversionMethod,
+    encoding::{PngCompression, PngFilter},
+    pdf::TransparencyPolicy,
+    print::{BleedFill, ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, TemplateRegistry},
+    units::Length,
+};
+
+fn gray_print(bleed_fill: BleedFill) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 72,
+        color_space: ColorSpace::Grayscale,
+        bleed: Length::inches(0.125),
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill,
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// Red/blue checker background; "scan" and "photo" are print exports, "web"
+/// a PNG, "master" (from the common template) an SVG
+fn registry(print: TemplatePrint) -> TemplateRegistry {
+    let mut template = create_test_template();
+    template.print = Some(print);
+    template.background_generator = BackgroundGenerator::Pattern {
+        style: PatternStyle::Checker,
+        palette: vec!["#FF0000".to_string(), "#0000FF".to_string()],
+    };
+    template.exports.push(export("scan", [8, 8], ExportFormat::Tiff, true));
+    template.exports.push(export("web", [8, 8], ExportFormat::Png, true));
+    template.exports.push(export("photo", [8, 8], ExportFormat::Jpg, false));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry
+}
+
+fn compile(registry: TemplateRegistry) -> Result<CompiledAsset, PipelineError> {
+    // Unfiltered, stored PNG data can be read back without an inflater
+    let encoding = EncodingProfile { png_filter: PngFilter::None, png_compression: PngCompression::Stored };
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(7);
+    CompilationPipeline::builder(registry).encoding(encoding).build().compile_asset(&request)
+}
+
+fn decoded(asset: &CompiledAsset, id: &str) -> Vec<u8> {
+    let file = asset.exports.iter().find(|e| e.id == id).unwrap();
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap()
+}
+
+/// (tag value lookup, strip bytes) of a little-endian TIFF
+fn tiff(data: &[u8]) -> (impl Fn(u16) -> u32 + '_, Vec<u8>) {
+    let u16_at = move |pos: usize| u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap());
+    let u32_at = move |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = move |wanted: u16| {
+        let pos = (0..u16_at(ifd) as usize).map(|i| ifd + 2 + 12 * i).find(|&pos| u16_at(pos) == wanted).unwrap();
+        // SHORT values are inline in the low bytes
+        if u16_at(pos + 2) == 3 && u32_at(pos + 4) == 1 { u16_at(pos + 8) as u32 } else { u32_at(pos + 8) }
+    };
+    let (offset, len) = (tag(273) as usize, tag(279) as usize);
+    (tag, data[offset..offset + len].to_vec())
+}
+
+#[test]
+fn test_tiff_is_single_channel_luma() {
+    let fill = BleedFill::Color { color: "#00FF00".to_string() };
+    let asset = compile(registry(gray_print(fill))).unwrap();
+    let data = decoded(&asset, "scan");
+    let (tag, pixels) = tiff(&data);
+
+    // BlackIsZero, one 8-bit sample; 8x8 trim plus 9 px of bleed per side
+    assert_eq!((tag(262), tag(277), tag(258)), (1, 1, 8));
+    assert_eq!((tag(256), tag(257)), (26, 26));
+    assert_eq!(pixels.len(), 26 * 26);
+    // Bleed is the green fill; the trim area is the checker
+    assert_eq!(pixels[0], 182);
+    let trim: Vec<u8> = (9..17).flat_map(|y| pixels[y * 26 + 9..y * 26 + 17].to_vec()).collect();
+    assert!(trim.iter().all(|&y| y == 54 || y == 18), "{:?}", trim);
+    assert!(trim.contains(&54) && trim.contains(&18));
+}
+
+#[test]
+fn test_opaque_png_is_gray() {
+    let asset = compile(registry(gray_print(BleedFill::Mirror))).unwrap();
+    let (color_type, raw) = stored_png(&decoded(&asset, "web"));
+    assert_eq!(color_type, 0);
+
+    // Rows of (filter byte, 8 luma bytes)
+    assert_eq!(raw.len(), 8 * (1 + 8));
+    for row in raw.chunks_exact(1 + 8) {
+        assert_eq!(row[0], 0);
+        assert!(row[1..].iter().all(|&y| y == 54 || y == 18), "{:?}", row);
+    }
+}
+
+#[test]
+fn test_transparent_png_keeps_alpha() {
+    let mut registry = registry(gray_print(BleedFill::Mirror));
+    let mut template = registry.get("test-icon").unwrap().clone();
+    template.background_generator = BackgroundGenerator::None;
+    registry.register(template);
+    let (color_type, raw) = stored_png(&decoded(&compile(registry).unwrap(), "web"));
+    assert_eq!(color_type, 4);
+
+    // Rows of (filter byte, 8 x (luma, alpha)), all of it transparent
+    assert_eq!(raw.len(), 8 * (1 + 8 * 2));
+    for row in raw.chunks_exact(1 + 8 * 2) {
+        assert!(row[1..].iter().all(|&b| b == 0), "{:?}", row);
+    }
+}
+
+#[test]
+fn test_jpeg_is_single_channel_luma() {
+    let asset = compile(registry(gray_print(BleedFill::Mirror))).unwrap();
+    let data = decoded(&asset, "photo");
+    let mut decoder = jpeg_decoder::Decoder::new(&data[..]);
+    let pixels = decoder.decode().unwrap();
+    let info = decoder.info().unwrap();
+    // Bleed included, like the TIFF
+    assert_eq!((info.pixel_format, info.width, info.height), (jpeg_decoder::PixelFormat::L8, 26, 26));
+
+    // Lossy (ringing at the checker edges), so within 8 levels of the TIFF's exact luma
+    let (_, exact) = tiff(&decoded(&asset, "scan"));
+    for (&got, &want) in pixels.iter().zip(&exact) {
+        assert!(got.abs_diff(want) <= 8, "{} vs {}", got, want);
+    }
+}
+
+#[test]
+fn test_manifest_records_the_conversion() {
+    let asset = compile(registry(gray_print(BleedFill::Mirror))).unwrap();
+
+    let method = |id: &str| {
+        asset.exports.iter().find(|e| e.id == id).unwrap().color_conversion.as_ref().map(|c| c.method)
+    };
+    assert_eq!(method("scan"), Some(ConversionMethod::Rec709Luma));
+    assert_eq!(method("web"), Some(ConversionMethod::Rec709Luma));
+    assert_eq!(method("photo"), Some(ConversionMethod::Rec709Luma));
+    assert_eq!(method("master"), Some(ConversionMethod::Rec709LumaFilter));
+
+    let svg = String::from_utf8(decoded(&asset, "master")).unwrap();
+    assert!(svg.contains(r#"<g filter="url(#fi-grayscale)">"#), "{}", svg);
+
+    let json = serde_json::to_value(&asset).unwrap();
+    let scan = json["exports"].as_array().unwrap().iter().find(|e| e["id"] == "scan").unwrap();
+    assert_eq!(scan["color_conversion"]["method"], "rec709_luma");
+}
+
+#[test]
+fn test_true_grayscale_vectors_refuse_svg_exports() {
+    let print = TemplatePrint { true_grayscale_vectors: true, ..gray_print(BleedFill::Mirror) };
+    // Without SVG exports the template compiles
+    let mut registry = registry(print.clone());
+    let mut template = registry.get("test-icon").unwrap().clone();
+    template.exports.retain(|e| e.format != ExportFormat::Svg);
+    registry.register(template);
+    assert!(compile(registry).is_ok());
+
+    let result = compile(self::registry(print));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("trueGrayscaleVectors")));
+}
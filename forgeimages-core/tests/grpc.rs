@@ -0,0 +1,245 @@
+//! gRPC API: lossless conversions, job hashes that match JSON requests,
+//! and the service end to end over a real socket
+
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use forgeimages_core::{
+    canonical_json, compute_manifest_hash, CompilationPipeline, CompileRequest, CompiledAsset, ErrorEnvelope, ErrorKind,
+    color::{ColorConversion, ConversionMethod, SpotColorUsage},
+    grpc::{self, proto, GrpcOptions},
+    icc::EmbeddedProfile,
+    imposition::{CellRecord, ImpositionRecord},
+    bleed::TrimBox,
+    pdf::PdfStandard,
+    pipeline::{SignerInfo, SourceKind},
+    print::{Marks, PrintOverride},
+    sink::StoredObject,
+    ExportError,
+};
+use common::{compile_request, create_pipeline, create_test_template, export};
+use prost::Message;
+use tonic::Code;
+
+/// Every optional part of a manifest filled in
+fn rich_asset() -> CompiledAsset {
+    let mut asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let request = asset.request.as_mut().unwrap();
+    request.source = Some(SourceKind::Path);
+    request.seed = Some(u64::MAX);
+    request.prompt = Some("a lighthouse".to_string());
+    request.params.insert("title".to_string(), "Forge".into());
+    request.params.insert("scale".to_string(), serde_json::json!({"x": 0.1, "y": [1, null, true]}));
+    request.print_spec = Some(Default::default());
+
+    asset.created_at = Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap();
+    asset.job_hash_keyed = true;
+    asset.job_hash_key_id = Some("key-1".to_string());
+    asset.font_hash = Some("sha256:f0".to_string());
+    asset.signer = Some(SignerInfo { key_id: "signer".to_string(), algorithm: "ed25519".to_string() });
+    asset.source_hash = Some("sha256:50".to_string());
+    asset.normalized_source_hash = Some("sha256:51".to_string());
+    asset.print_overrides.push(PrintOverride {
+        field: "dpi".to_string(),
+        template_value: 300.into(),
+        user_value: serde_json::json!(600),
+    });
+    asset.print_profile = Some("offset".to_string());
+    asset.export_errors.push(ExportError {
+        export_id: "poster".to_string(),
+        message: "render failed".to_string(),
+        spec: export("poster", [2048, 1024], forgeimages_core::templates::ExportFormat::Pdf, false),
+    });
+    asset.stored_objects.push(StoredObject {
+        export_id: "master".to_string(),
+        key: "assets/master.svg".to_string(),
+        etag: Some("\"e1\"".to_string()),
+        sha256: "ab".repeat(32),
+    });
+
+    let mut print = asset.exports[0].clone();
+    print.id = "print".to_string();
+    print.trim = Some(TrimBox { size: [1000, 1000], offset: [12, 12] });
+    print.marks = Some(Marks { color_bars: true, ..Marks::legacy() });
+    print.print = Some(asset.print.clone());
+    print.color_conversion = Some(ColorConversion { method: ConversionMethod::Rec709Luma, profile: Some("sha256:cc".to_string()) });
+    print.icc_profile = Some(EmbeddedProfile { name: "FOGRA39".to_string(), sha256: "cd".repeat(32) });
+    print.spot_colors.push(SpotColorUsage { name: "Gold".to_string(), rgb_hex: "#d4af37".to_string(), cmyk: [0, 30, 255, 12], pixels: 1 << 40 });
+    print.pdf_standard = Some(PdfStandard::X1a);
+    print.proof = true;
+    print.deliverable = false;
+    print.imposition = Some(ImpositionRecord {
+        sheet_mm: [320.0, 450.5],
+        rows: 1,
+        columns: 2,
+        gutter_mm: 3.175,
+        crop_marks: true,
+        cells: vec![
+            CellRecord { row: 0, column: 0, x_pt: 0.1, y_pt: 1.0 / 3.0, width_pt: 100.0, height_pt: 200.0 },
+            CellRecord { row: 0, column: 1, x_pt: 109.0, y_pt: 1.0 / 3.0, width_pt: 100.0, height_pt: 200.0 },
+        ],
+    });
+    print.deduplicated_from = Some("master".to_string());
+    asset.exports.push(print);
+    asset
+}
+
+/// Over the wire and back
+fn round_trip<M: Message + Default>(message: M) -> M {
+    M::decode(message.encode_to_vec().as_slice()).unwrap()
+}
+
+#[test]
+fn test_compiled_assets_round_trip() {
+    let asset = rich_asset();
+    let message = proto::CompiledAsset::try_from(&asset).unwrap();
+    assert_eq!(message.exports[0].data, base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &asset.exports[0].data_base64).unwrap());
+    assert_eq!(message.exports[1].marks.unwrap().mark_length_um, Marks::legacy().mark_length.micrometres());
+
+    let back = CompiledAsset::try_from(round_trip(message)).unwrap();
+    assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&asset).unwrap());
+    assert_eq!(canonical_json(&back).unwrap(), canonical_json(&asset).unwrap());
+    assert_eq!(back.created_at, asset.created_at);
+    assert_eq!(back.exports[1].marks, asset.exports[1].marks);
+}
+
+#[test]
+fn test_requests_and_errors_round_trip() {
+    let request: CompileRequest = serde_json::from_str(
+        r#"{"template_id":"test-icon","asset_input":{"width":1024,"height":1024,"color_count":3,"format":"svg"},
+            "source_data":"PHN2Zy8+","seed":"18446744073709551615","prompt":"p","params":{"a":[1.5,"x"]},
+            "print_spec":{"dpi":600,"color_space":"CMYK","bleed_inches":0.125,"icc_profile":"FOGRA39"}}"#,
+    ).unwrap();
+    let back = CompileRequest::try_from(round_trip(proto::CompileRequest::try_from(&request).unwrap())).unwrap();
+    assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&request).unwrap());
+
+    let pipeline = create_pipeline();
+    let envelope = ErrorEnvelope::from(pipeline.compile_asset(&compile_request("test-icon", 100, 50)).unwrap_err())
+        .with_template_id("test-icon")
+        .with_validation(pipeline.validate_asset("test-icon", &common::asset_input(100, 50)).unwrap())
+        .detail("export_id", "master");
+    let back = ErrorEnvelope::try_from(round_trip(proto::Error::from(&envelope))).unwrap();
+    assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&envelope).unwrap());
+}
+
+#[test]
+fn test_malformed_messages_name_the_field() {
+    let missing = proto::CompileRequest { template_id: "test-icon".to_string(), ..Default::default() };
+    assert_eq!(CompileRequest::try_from(missing).unwrap_err().to_string(), "asset_input: missing");
+
+    let mut message = proto::CompiledAsset::try_from(&rich_asset()).unwrap();
+    message.exports[1].spot_colors[0].cmyk[2] = 256;
+    message.exports[0].scaling.as_mut().unwrap().policy = "sometimes".to_string();
+    let error = CompiledAsset::try_from(message.clone()).unwrap_err();
+    assert_eq!(error.field, "exports[0].scaling.policy");
+    message.exports[0] = proto::CompiledAsset::try_from(&rich_asset()).unwrap().exports.remove(0);
+    let error = CompiledAsset::try_from(message).unwrap_err();
+    assert_eq!(error.to_string(), "exports[1].spot_colors[0].cmyk: 256 is over 255");
+
+    let envelope = ErrorEnvelope::from(CompileRequest::try_from(proto::CompileRequest::default()).unwrap_err());
+    assert_eq!(envelope.kind, ErrorKind::InvalidPayload);
+}
+
+#[test]
+fn test_proto_requests_hash_like_json() {
+    let svg = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sources/logo.svg")).unwrap();
+    let json = format!(
+        r#"{{"template_id":"test-icon","asset_input":{{"width":1024,"height":1024}},"source_data":"{}",
+            "seed":"9007199254740993","params":{{"title":"Forge","n":0.1}},
+            "print_spec":{{"dpi":300,"color_space":"CMYK","bleed":"3mm"}}}}"#,
+        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &svg),
+    );
+    let from_json: CompileRequest = serde_json::from_value(forgeimages_core::parse_strict(&json).unwrap()).unwrap();
+
+    let message = proto::CompileRequest {
+        template_id: "test-icon".to_string(),
+        asset_input: Some(proto::AssetInput { width: 1024, height: 1024, color_count: None, format: None }),
+        source_data: Some(svg),
+        seed: Some(9_007_199_254_740_993),
+        params: [("n".to_string(), "0.1".to_string()), ("title".to_string(), "\"Forge\"".to_string())].into(),
+        print_spec: Some(proto::PrintSpec { authority: "system".to_string(), dpi: 300, color_space: "CMYK".to_string(), bleed_um: 3000, icc_profile: None }),
+        ..Default::default()
+    };
+    let from_proto = CompileRequest::try_from(round_trip(message)).unwrap();
+
+    let pipeline = create_pipeline();
+    assert_eq!(pipeline.job_hash(&from_proto).unwrap(), pipeline.job_hash(&from_json).unwrap());
+}
+
+/// A served pipeline and a client connected to it
+async fn connect(pipeline: CompilationPipeline) -> proto::forge_images_client::ForgeImagesClient<tonic::transport::Channel> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let options = GrpcOptions { concurrency: 2, max_message_bytes: 16 * 1024 * 1024 };
+    tokio::spawn(grpc::serve(listener, Arc::new(pipeline), options));
+    proto::forge_images_client::ForgeImagesClient::connect(format!("http://{}", addr)).await.unwrap()
+}
+
+fn request(width: u32, height: u32) -> proto::CompileRequest {
+    proto::CompileRequest::try_from(&compile_request("test-icon", width, height)).unwrap()
+}
+
+#[test]
+fn test_service_end_to_end() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut client = connect(create_pipeline()).await;
+
+        let templates = client.list_templates(proto::ListTemplatesRequest {}).await.unwrap().into_inner().templates;
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].id, "test-icon");
+        assert_eq!(templates[0].content_hash, create_test_template().content_hash().unwrap());
+
+        let invalid = proto::ValidateAssetRequest { template_id: "test-icon".to_string(), input: request(100, 50).asset_input };
+        let result = client.validate_asset(invalid).await.unwrap().into_inner();
+        assert!(!result.valid);
+
+        let asset = client.compile_asset(request(1024, 1024)).await.unwrap().into_inner();
+        assert_eq!(asset.job_hash, create_pipeline().job_hash(&compile_request("test-icon", 1024, 1024)).unwrap());
+        // The manifest still hashes to its manifest_hash
+        let mut unhashed = CompiledAsset::try_from(asset.clone()).unwrap();
+        unhashed.manifest_hash = String::new();
+        assert_eq!(compute_manifest_hash(&unhashed).unwrap(), asset.manifest_hash);
+
+        let status = client.compile_asset(request(100, 50)).await.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        let error = grpc::error_of(&status).unwrap();
+        assert_eq!(error.error_kind, "validation_failed");
+        assert_eq!(error.template_id.as_deref(), Some("test-icon"));
+        assert_eq!(error.validation, Some(result));
+
+        let missing = proto::ValidateAssetRequest { template_id: "missing".to_string(), input: request(1, 1).asset_input };
+        let status = client.validate_asset(missing).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(grpc::error_of(&status).unwrap().error_kind, "template_not_found");
+        let status = client.compile_asset(proto::CompileRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    });
+}
+
+#[test]
+fn test_batches_stream_in_request_order() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut client = connect(create_pipeline()).await;
+        let requests = vec![request(1024, 1024), request(100, 50), request(2048, 2048), proto::CompileRequest::default()];
+        let mut stream = client.compile_batch(proto::CompileBatchRequest { requests }).await.unwrap().into_inner();
+        let mut items = vec![];
+        while let Some(item) = stream.message().await.unwrap() {
+            items.push(item);
+        }
+
+        assert_eq!(items.iter().map(|item| item.index).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        let kinds: Vec<_> = items.iter()
+            .map(|item| match item.result.as_ref().unwrap() {
+                proto::compile_batch_item::Result::Asset(_) => "asset".to_string(),
+                proto::compile_batch_item::Result::Error(error) => error.error_kind.clone(),
+            })
+            .collect();
+        assert_eq!(kinds, ["asset", "validation_failed", "asset", "invalid_payload"]);
+    });
+}
@@ -0,0 +1,127 @@
+//! Algorithm-prefixed digests and legacy sha256 manifests
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, HashAlgorithm, HashScheme,
+    hashing::{canonical_json, parse_digest, sha256_hex},
+    output::{MANIFEST_FILE, MANIFEST_HASH_FILE},
+    templates::{ExportFormat, TemplateRegistry},
+    verify::{verify_exports, VerifyError},
+};
+use serde_json::Value;
+
+fn pipeline(algorithm: HashAlgorithm) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("icon", [32, 32], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry).hash_algorithm(algorithm).build()
+}
+
+fn compile_to(dir: &Path, algorithm: HashAlgorithm) -> CompiledAsset {
+    pipeline(algorithm).compile_to_dir(&compile_request("test-icon", 1024, 1024), dir).unwrap()
+}
+
+fn rewrite_manifest(dir: &Path, edit: impl FnOnce(&mut Value)) {
+    let path = dir.join(MANIFEST_FILE);
+    let mut manifest: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    edit(&mut manifest);
+    fs::write(&path, canonical_json(&manifest).unwrap()).unwrap();
+    fs::remove_file(dir.join(MANIFEST_HASH_FILE)).unwrap();
+}
+
+fn strip_prefix(value: &mut Value) {
+    if let Some((_, hex)) = value.as_str().and_then(|s| s.split_once(':')) {
+        *value = hex.into();
+    }
+}
+
+#[test]
+fn test_digests_are_prefixed_and_algorithm_is_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compile_to(dir.path(), HashAlgorithm::Sha256);
+
+    assert_eq!(asset.hash_algorithm, HashAlgorithm::Sha256);
+    for digest in [&asset.manifest_hash, &asset.job_hash, &asset.exports[1].hash] {
+        assert!(digest.starts_with("sha256:"), "{}", digest);
+    }
+    let png = fs::read(dir.path().join("icon.png")).unwrap();
+    assert_eq!(asset.exports[1].hash, format!("sha256:{}", sha256_hex(&png)));
+    assert_eq!(verify_exports(dir.path()).unwrap(), asset.manifest_hash);
+}
+
+#[test]
+fn test_unprefixed_legacy_manifest_verifies_as_sha256() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), HashAlgorithm::Sha256);
+
+    rewrite_manifest(dir.path(), |manifest| {
+        for field in ["hash_algorithm", "hash_scheme", "exports_root"] {
+            manifest.as_object_mut().unwrap().remove(field);
+        }
+        strip_prefix(&mut manifest["job_hash"]);
+        for export in manifest["exports"].as_array_mut().unwrap() {
+            strip_prefix(&mut export["hash"]);
+        }
+        manifest["manifest_hash"] = "".into();
+        manifest["manifest_hash"] = sha256_hex(canonical_json(&*manifest).unwrap().as_bytes()).into();
+    });
+
+    let recorded = verify_exports(dir.path()).unwrap();
+    assert!(!recorded.contains(':'));
+    assert_eq!(parse_digest(&recorded).unwrap().0, HashAlgorithm::Sha256);
+}
+
+#[test]
+fn test_declared_scheme_selects_the_manifest_domain() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compile_to(dir.path(), HashAlgorithm::Sha256);
+    assert_eq!(asset.hash_scheme, HashScheme::CURRENT);
+
+    // Relabeling a domain-separated manifest as fi-hash-0 breaks its hash
+    rewrite_manifest(dir.path(), |manifest| manifest["hash_scheme"] = "fi-hash-0".into());
+    assert!(matches!(verify_exports(dir.path()), Err(VerifyError::ManifestHashMismatch(..))));
+
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), HashAlgorithm::Sha256);
+    rewrite_manifest(dir.path(), |manifest| manifest["hash_scheme"] = "fi-hash-9".into());
+    assert!(matches!(verify_exports(dir.path()), Err(VerifyError::Malformed(_))));
+}
+
+#[test]
+fn test_mixed_algorithms_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    compile_to(dir.path(), HashAlgorithm::Sha256);
+
+    rewrite_manifest(dir.path(), |manifest| {
+        let hex = manifest["exports"][1]["hash"].as_str().unwrap().replace("sha256:", "md5:");
+        manifest["exports"][1]["hash"] = hex.into();
+    });
+    assert!(matches!(
+        verify_exports(dir.path()),
+        Err(VerifyError::MixedAlgorithms(field)) if field.contains("exports[1].hash")
+    ));
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn test_blake3_pipeline_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compile_to(dir.path(), HashAlgorithm::Blake3);
+
+    assert_eq!(asset.hash_algorithm, HashAlgorithm::Blake3);
+    for digest in [&asset.manifest_hash, &asset.job_hash, &asset.exports[1].hash] {
+        assert!(digest.starts_with("blake3:"), "{}", digest);
+    }
+    let png = fs::read(dir.path().join("icon.png")).unwrap();
+    assert_eq!(asset.exports[1].hash, HashAlgorithm::Blake3.digest(&png));
+    assert_eq!(verify_exports(dir.path()).unwrap(), asset.manifest_hash);
+
+    let sha = compile_to(tempfile::tempdir().unwrap().path(), HashAlgorithm::Sha256);
+    assert_ne!(sha.job_hash, asset.job_hash);
+}
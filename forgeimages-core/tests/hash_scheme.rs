@@ -0,0 +1,67 @@
+//! Golden manifests, one per hash scheme
+//!
+//! The files under tests/golden are never regenerated: if one stops
+//! verifying, a released scheme changed behavior.
+
+use std::fs;
+use std::path::PathBuf;
+
+use forgeimages_core::{
+    CompiledAsset, HashAlgorithm, HashScheme,
+    verify::verify_manifest,
+};
+use serde_json::Value;
+
+fn golden(scheme: HashScheme) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("manifest-{}.json", scheme.name()))
+}
+
+fn without_hash(scheme: HashScheme) -> Value {
+    let mut manifest: Value = serde_json::from_slice(&fs::read(golden(scheme)).unwrap()).unwrap();
+    manifest["manifest_hash"] = "".into();
+    manifest
+}
+
+#[test]
+fn test_golden_manifests_verify() {
+    for (scheme, expected) in [
+        (HashScheme::V0, "8e48cc4e7bd40df7c36eb5843ec6dab7f028cc17903e84d72d451fb7d197498c"),
+        (HashScheme::V1, "sha256:743ec62540b56a24de136dd16c6f18465f5d55e6c0012c3a826515902a2e9bcb"),
+    ] {
+        assert_eq!(verify_manifest(&golden(scheme)).unwrap(), expected, "{}", scheme.name());
+    }
+}
+
+#[test]
+fn test_schemes_hash_differently() {
+    // fi-hash-0 writes `0.0`, fi-hash-1 writes `0`
+    let legacy = without_hash(HashScheme::V0);
+    assert!(HashScheme::V0.canonicalize(&legacy).unwrap().contains(r#""bleed_inches":0.0"#));
+    assert!(HashScheme::V1.canonicalize(&legacy).unwrap().contains(r#""bleed_inches":0,"#));
+
+    let current = without_hash(HashScheme::V1);
+    assert_ne!(
+        HashScheme::V0.manifest_hash(HashAlgorithm::Sha256, &current).unwrap(),
+        HashScheme::V1.manifest_hash(HashAlgorithm::Sha256, &current).unwrap(),
+    );
+}
+
+#[test]
+fn test_manifest_without_scheme_reads_as_fi_hash_0() {
+    let legacy: CompiledAsset = serde_json::from_slice(&fs::read(golden(HashScheme::V0)).unwrap()).unwrap();
+    assert_eq!(legacy.hash_scheme, HashScheme::V0);
+
+    let current: CompiledAsset = serde_json::from_slice(&fs::read(golden(HashScheme::V1)).unwrap()).unwrap();
+    assert_eq!(current.hash_scheme, HashScheme::CURRENT);
+}
+
+#[test]
+fn test_scheme_names_round_trip() {
+    for scheme in [HashScheme::V0, HashScheme::V1] {
+        assert_eq!(HashScheme::from_name(scheme.name()), Some(scheme));
+        assert_eq!(serde_json::to_value(scheme).unwrap(), scheme.name());
+    }
+    assert_eq!(HashScheme::from_name("fi-hash-9"), None);
+}
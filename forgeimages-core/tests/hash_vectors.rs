@@ -0,0 +1,64 @@
+//! Hash test vectors: the engine reproduces them, and they never drift
+
+use std::collections::HashSet;
+
+use forgeimages_core::{
+    canonical_json, parse_strict, CompiledAsset, HashAlgorithm, HashScheme, ManifestHashView,
+    hashing::{sha256_hex, test_vectors},
+};
+
+/// sha256 of `serde_json::to_string(&test_vectors())`. Changing any vector
+/// changes this; that takes a new hash scheme, not a new pin.
+const VECTORS_PIN: &str = "74265eeebd368b0e2e71603af9916cadfd9b0dd93b9712a21dc5cf4f113d5709";
+
+#[test]
+fn test_vectors_are_pinned() {
+    let vectors = test_vectors();
+    assert_eq!(
+        sha256_hex(serde_json::to_string(&vectors).unwrap().as_bytes()),
+        VECTORS_PIN,
+        "hash test vectors changed; expected values are fixed per scheme, add a HashScheme instead",
+    );
+    assert!(vectors.iter().all(|v| v.scheme == HashScheme::V1));
+    let descriptions: HashSet<_> = vectors.iter().map(|v| v.description).collect();
+    assert_eq!(descriptions.len(), vectors.len());
+}
+
+#[test]
+fn test_engine_reproduces_every_vector() {
+    for vector in test_vectors() {
+        let value = parse_strict(vector.input).unwrap();
+        let canonical = vector.scheme.canonicalize(&value).unwrap();
+        assert_eq!(canonical, vector.canonical, "{}", vector.description);
+        assert_eq!(canonical_json(&value).unwrap(), vector.canonical, "{}", vector.description);
+        assert_eq!(HashAlgorithm::Sha256.digest(canonical.as_bytes()), vector.sha256, "{}", vector.description);
+        // Canonical text is a fixed point
+        assert_eq!(canonical_json(&parse_strict(vector.canonical).unwrap()).unwrap(), vector.canonical);
+    }
+}
+
+#[test]
+fn test_manifest_vector_is_a_real_manifest() {
+    let vectors: Vec<_> = test_vectors().into_iter().filter(|v| v.manifest_hash.is_some()).collect();
+    assert_eq!(vectors.len(), 1);
+    let vector = &vectors[0];
+
+    let asset: CompiledAsset = serde_json::from_str(vector.input).unwrap();
+    assert_eq!(asset.hash_scheme, vector.scheme);
+    assert_eq!(Some(asset.manifest_hash.as_str()), vector.manifest_hash);
+    // Every field of the input survives a round trip through the manifest type
+    assert_eq!(canonical_json(&asset).unwrap(), vector.canonical);
+    assert_eq!(
+        ManifestHashView::of(&asset).unwrap().hash(vector.scheme, HashAlgorithm::Sha256).unwrap(),
+        vector.manifest_hash.unwrap(),
+    );
+    // The recorded hash ignores deduplication bookkeeping
+    assert!(asset.exports.iter().any(|e| e.deduplicated_from.is_some()));
+}
+
+#[test]
+fn test_utf8_key_order_is_recorded() {
+    // Diverges from RFC 8785 (UTF-16 order); verifiers must follow the vector
+    let vector = test_vectors().into_iter().find(|v| v.description.starts_with("keys sort by UTF-8")).unwrap();
+    assert!(vector.canonical.find('\u{ff61}').unwrap() < vector.canonical.find('\u{1f600}').unwrap());
+}
@@ -0,0 +1,183 @@
+//! ICC output profiles: registration, embedding in PNG/JPEG/TIFF, manifest record
+
+mod common;
+
+use common::{compile_request, create_test_template, export, inflate_stored, png_chunks};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, EncodingProfile, PipelineError,
+    encoding::PngCompression,
+    hashing::HashAlgorithm,
+    icc::IccProfileStore,
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, PrintSpec, TemplatePrint},
+    templates::{ExportFormat, ExportSpec, TemplateRegistry},
+    units::Length,
+};
+
+/// Minimal well-formed profile: header plus an empty tag table, with a
+/// distinguishing byte so profiles differ
+fn profile_bytes(space: &[u8; 4], marker: u8) -> Vec<u8> {
+    let mut data = vec![0; 133];
+    data[0..4].copy_from_slice(&133u32.to_be_bytes());
+    data[16..20].copy_from_slice(space);
+    data[36..40].copy_from_slice(b"acsp");
+    data[132] = marker;
+    data
+}
+
+fn store() -> IccProfileStore {
+    let mut store = IccProfileStore::new();
+    store.register("Coated FOGRA39", profile_bytes(b"CMYK", 1)).unwrap();
+    store.register("sRGB proof", profile_bytes(b"RGB ", 2)).unwrap();
+    store
+}
+
+fn print_block(color_space: ColorSpace, icc_profile: &str) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space,
+        bleed: Length::ZERO,
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: Some(icc_profile.to_string()),
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// "press" and "press-jpg" inherit the template's CMYK profile; "proof"
+/// names the RGB one
+fn registry() -> TemplateRegistry {
+    let mut template = create_test_template();
+    template.print = Some(print_block(ColorSpace::Cmyk, "Coated FOGRA39"));
+    template.exports.push(export("press", [4, 4], ExportFormat::Tiff, true));
+    template.exports.push(ExportSpec {
+        print: Some(print_block(ColorSpace::Rgb, "sRGB proof")),
+        ..export("proof", [4, 4], ExportFormat::Tiff, true)
+    });
+    template.exports.push(export("web", [4, 4], ExportFormat::Png, true));
+    template.exports.push(export("press-jpg", [4, 4], ExportFormat::Jpg, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry
+}
+
+fn compile(registry: TemplateRegistry, store: IccProfileStore) -> Result<CompiledAsset, PipelineError> {
+    CompilationPipeline::builder(registry)
+        .icc_profiles(store)
+        // Stored deflate keeps the iCCP payload readable without an inflater
+        .encoding(EncodingProfile { png_compression: PngCompression::Stored, ..EncodingProfile::default() })
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+}
+
+fn decoded(asset: &CompiledAsset, id: &str) -> Vec<u8> {
+    let file = asset.exports.iter().find(|e| e.id == id).unwrap();
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap()
+}
+
+/// Bytes of TIFF tag 34675, if present
+fn tiff_profile(tiff: &[u8]) -> Option<Vec<u8>> {
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let entry = (0..u16_at(ifd) as usize).map(|i| ifd + 2 + 12 * i).find(|&pos| u16_at(pos) == 34675)?;
+    let (count, offset) = (u32_at(entry + 4) as usize, u32_at(entry + 8) as usize);
+    Some(tiff[offset..offset + count].to_vec())
+}
+
+/// (profile name, profile bytes) from a PNG iCCP chunk written with stored deflate
+fn png_profile(png: &[u8]) -> Option<(String, Vec<u8>)> {
+    let (_, data) = png_chunks(png).into_iter().find(|(kind, _)| kind == b"iCCP")?;
+    let name_end = data.iter().position(|&b| b == 0).unwrap();
+    assert_eq!(data[name_end + 1], 0, "compression method");
+    Some((String::from_utf8(data[..name_end].to_vec()).unwrap(), inflate_stored(&data[name_end + 2..])))
+}
+
+/// (pixel layout, APP2 profile bytes) of a JPEG, decoded in full
+fn jpeg_profile(jpeg: &[u8]) -> (jpeg_decoder::PixelFormat, Option<Vec<u8>>) {
+    let mut decoder = jpeg_decoder::Decoder::new(jpeg);
+    let pixels = decoder.decode().unwrap();
+    let info = decoder.info().unwrap();
+    assert_eq!(pixels.len(), info.width as usize * info.height as usize * info.pixel_format.pixel_bytes());
+    (info.pixel_format, decoder.icc_profile())
+}
+
+#[test]
+fn test_profiles_are_embedded_byte_for_byte() {
+    let asset = compile(registry(), store()).unwrap();
+
+    assert_eq!(tiff_profile(&decoded(&asset, "press")), Some(profile_bytes(b"CMYK", 1)));
+    assert_eq!(tiff_profile(&decoded(&asset, "proof")), Some(profile_bytes(b"RGB ", 2)));
+    // The template-level profile is CMYK, so the PNG carries none
+    assert_eq!(png_profile(&decoded(&asset, "web")), None);
+    assert_eq!(
+        jpeg_profile(&decoded(&asset, "press-jpg")),
+        (jpeg_decoder::PixelFormat::CMYK32, Some(profile_bytes(b"CMYK", 1))),
+    );
+
+    let mut registry = registry();
+    let mut template = registry.get("test-icon").unwrap().clone();
+    template.print = Some(print_block(ColorSpace::Rgb, "sRGB proof"));
+    registry.register(template);
+    let asset = compile(registry, store()).unwrap();
+    assert_eq!(
+        png_profile(&decoded(&asset, "web")),
+        Some(("sRGB proof".to_string(), profile_bytes(b"RGB ", 2))),
+    );
+    assert_eq!(
+        jpeg_profile(&decoded(&asset, "press-jpg")),
+        (jpeg_decoder::PixelFormat::RGB24, Some(profile_bytes(b"RGB ", 2))),
+    );
+}
+
+#[test]
+fn test_manifest_records_profile_digests() {
+    let asset = compile(registry(), store()).unwrap();
+    let profile_of = |id: &str| asset.exports.iter().find(|e| e.id == id).unwrap().icc_profile.clone();
+
+    let press = profile_of("press").unwrap();
+    assert_eq!(press.name, "Coated FOGRA39");
+    assert_eq!(press.sha256, HashAlgorithm::Sha256.digest(&profile_bytes(b"CMYK", 1)));
+    assert_eq!(profile_of("proof").unwrap().name, "sRGB proof");
+    assert_eq!(profile_of("web"), None);
+    assert_eq!(profile_of("press-jpg").unwrap(), press);
+    assert_eq!(profile_of("master"), None);
+    assert_eq!(asset.print.icc_profile.as_deref(), Some("Coated FOGRA39"));
+
+    let json = serde_json::to_value(&asset).unwrap();
+    let master = json["exports"].as_array().unwrap().iter().find(|e| e["id"] == "master").unwrap();
+    assert!(master.get("icc_profile").is_none());
+}
+
+#[test]
+fn test_missing_profile_is_a_hard_error() {
+    let mut rgb_only = IccProfileStore::new();
+    rgb_only.register("sRGB proof", profile_bytes(b"RGB ", 2)).unwrap();
+    let result = compile(registry(), rgb_only);
+    assert!(matches!(result, Err(PipelineError::IccProfileNotFound(name)) if name == "Coated FOGRA39"));
+
+    // A user naming an unregistered profile fails the same way
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { icc_profile: Some("GRACoL".to_string()), ..PrintSpec::default() });
+    let result = CompilationPipeline::builder(registry()).icc_profiles(store()).build().compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::IccProfileNotFound(name)) if name == "GRACoL"));
+}
+
+#[test]
+fn test_profile_must_match_the_export_color_space() {
+    let mut registry = registry();
+    let mut template = registry.get("test-icon").unwrap().clone();
+    template.print = Some(print_block(ColorSpace::Cmyk, "sRGB proof"));
+    registry.register(template);
+
+    let result = compile(registry, store());
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("exports[press]")));
+}
@@ -0,0 +1,184 @@
+//! N-up imposition of print exports: sheet size, cell positions, bleed in
+//! gutters, crop marks, fit errors
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    canonical_json, CompilationPipeline, CompiledAsset, PipelineError,
+    imposition::{Imposition, Sheet},
+    pipeline::ExportedFile,
+    pdf::TransparencyPolicy,
+    print::{BleedFill, ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, ExportSpec, TemplateRegistry},
+    units::Length,
+};
+
+/// 72 dpi keeps sheets small: 1 px per point, business card 252 x 144,
+/// us-letter 612 x 792, 9 px of green bleed
+fn card_print() -> TemplatePrint {
+    TemplatePrint {
+        dpi: 72,
+        color_space: ColorSpace::Rgb,
+        bleed: Length::inches(0.125),
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: BleedFill::Color { color: "#00FF00".to_string() },
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// `rows` x 2 business cards on us-letter with a 3 mm (9 px) gutter
+fn cards(rows: u32, crop_marks: bool) -> Imposition {
+    Imposition { sheet: Sheet::Preset("us-letter".to_string()), rows, columns: 2, gutter: Length::mm(3.0), crop_marks }
+}
+
+fn compile(imposition: Imposition) -> Result<CompiledAsset, PipelineError> {
+    let mut template = create_test_template();
+    template.print = Some(card_print());
+    template.exports.push(ExportSpec {
+        imposition: Some(imposition),
+        ..export("sheet", [252, 144], ExportFormat::Tiff, true)
+    });
+    template.exports.push(export("card", [252, 144], ExportFormat::Tiff, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .deduplicate_exports(true)
+        .build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+}
+
+fn file<'a>(asset: &'a CompiledAsset, id: &str) -> &'a ExportedFile {
+    asset.exports.iter().find(|e| e.id == id).unwrap()
+}
+
+/// RGB pixel lookup into the placeholder's little-endian TIFF
+fn tiff_pixels(file: &ExportedFile) -> impl Fn(u32, u32) -> [u8; 3] {
+    let tiff = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap();
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = |wanted: u16| (0..u16_at(ifd) as usize)
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&pos| u16_at(pos) == wanted)
+        .map(|pos| u32_at(pos + 8))
+        .unwrap();
+    let (width, offset) = (tag(256), tag(273) as usize);
+    move |x, y| {
+        let i = offset + ((y * width + x) * 3) as usize;
+        [tiff[i], tiff[i + 1], tiff[i + 2]]
+    }
+}
+
+const PAPER: [u8; 3] = [255, 255, 255];
+const BLEED: [u8; 3] = [0, 255, 0];
+const MARK: [u8; 3] = [0, 0, 0];
+
+#[test]
+fn test_ten_up_business_cards_on_letter() {
+    let asset = compile(cards(5, false)).unwrap();
+    let sheet = file(&asset, "sheet");
+    assert_eq!(sheet.size, [612, 792]);
+    assert_eq!(sheet.trim, None);
+    // The single card still carries its own bleed
+    assert_eq!(file(&asset, "card").size, [270, 162]);
+    assert_eq!(sheet.deduplicated_from, None);
+
+    // Grid with outer bleed is 531 x 774, centered
+    let record = sheet.imposition.as_ref().unwrap();
+    assert_eq!((record.rows, record.columns, record.cells.len()), (5, 2, 10));
+    assert_eq!(record.sheet_mm, [215.9, 279.4]);
+    let boxes: Vec<_> = record.cells.iter().map(|c| (c.row, c.column, c.x_pt, c.y_pt)).collect();
+    assert_eq!(&boxes[..3], &[(0, 0, 49.0, 18.0), (0, 1, 310.0, 18.0), (1, 0, 49.0, 171.0)]);
+    assert!(record.cells.iter().all(|c| (c.width_pt, c.height_pt) == (252.0, 144.0)));
+}
+
+#[test]
+fn test_bleed_fills_gutters_and_stops_at_the_grid_edge() {
+    let asset = compile(cards(5, false)).unwrap();
+    let pixel = tiff_pixels(file(&asset, "sheet"));
+
+    // Column gutter [301, 310) and row gutter [162, 171) are all bleed
+    assert!((301..310).all(|x| pixel(x, 100) == BLEED));
+    assert!((162..171).all(|y| pixel(100, y) == BLEED));
+    // Trim areas are the (empty) art
+    assert_eq!((pixel(49, 18), pixel(300, 161), pixel(310, 171)), (PAPER, PAPER, PAPER));
+    // Outer bleed is 9 px, then paper
+    assert_eq!((pixel(40, 100), pixel(39, 100)), (BLEED, PAPER));
+    assert_eq!((pixel(100, 9), pixel(100, 8)), (BLEED, PAPER));
+    assert_eq!((pixel(570, 100), pixel(571, 100)), (BLEED, PAPER));
+}
+
+#[test]
+fn test_crop_marks_and_fit_errors() {
+    // Marks add an 18 px slug per side: ten cards no longer fit
+    let result = compile(cards(5, true));
+    let Err(PipelineError::ValidationFailed(msg)) = result else { panic!("compiled") };
+    assert!(msg.starts_with("print: exports[sheet]: imposition: "), "{}", msg);
+    assert!(msg.contains("needs 567x810 px but the sheet is 612x792 px at 72 dpi (short by 0x18 px, 0.00x6.35 mm)"), "{}", msg);
+
+    // Eight do; grid with bleed is 531 x 621, slug included 567 x 657
+    let asset = compile(cards(4, true)).unwrap();
+    let sheet = file(&asset, "sheet");
+    assert!(sheet.imposition.as_ref().unwrap().crop_marks);
+    let cell = &sheet.imposition.as_ref().unwrap().cells[0];
+    let (x, y) = (cell.x_pt as u32, cell.y_pt as u32);
+    assert_eq!((x, y), (49, 94));
+    let pixel = tiff_pixels(sheet);
+    // Top marks on both trim lines of the first column, above the outer bleed
+    assert_eq!((pixel(x, y - 27), pixel(x + 251, y - 27)), (MARK, MARK));
+    assert_eq!(pixel(x, y - 10), PAPER);
+    assert_eq!(pixel(x, y - 9), BLEED);
+    // Left marks on the first row's trim lines
+    assert_eq!((pixel(x - 27, y), pixel(x - 27, y + 143)), (MARK, MARK));
+
+    let manifest: serde_json::Value = serde_json::from_str(&canonical_json(&asset).unwrap()).unwrap();
+    let exports = manifest["exports"].as_array().unwrap();
+    let sheet = exports.iter().find(|e| e["id"] == "sheet").unwrap();
+    assert_eq!(sheet["imposition"]["cells"][7]["y_pt"].as_f64(), Some((y + 3 * 153) as f64));
+    assert!(exports.iter().find(|e| e["id"] == "card").unwrap().get("imposition").is_none());
+}
+
+#[test]
+fn test_load_validates_imposition_blocks() {
+    let load = |format: &str, imposition: serde_json::Value| {
+        let mut template = serde_json::to_value(create_test_template()).unwrap();
+        template["print"] = serde_json::json!({"dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.125});
+        template["exports"].as_array_mut().unwrap().push(serde_json::json!({
+            "id": "sheet", "description": "Press sheet", "format": format, "required": true,
+            "physicalPreset": "us-business-card", "imposition": imposition,
+        }));
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+        TemplateRegistry::load_from_dir(dir.path())
+    };
+
+    let registry = load("pdf", serde_json::json!({"sheet": "us-letter", "rows": 5, "columns": 2, "gutterMm": 3})).unwrap();
+    let imposition = registry.get("test-icon").unwrap().exports[1].imposition.clone().unwrap();
+    assert_eq!((imposition.rows, imposition.columns, imposition.crop_marks), (5, 2, false));
+    let explicit = load("tiff", serde_json::json!({"sheet": {"widthMm": 320, "heightMm": 450}, "rows": 1, "columns": 1}));
+    assert!(explicit.is_ok());
+
+    let cases = [
+        ("png", serde_json::json!({"sheet": "us-letter", "rows": 1, "columns": 1}), "Png exports cannot be imposed"),
+        ("pdf", serde_json::json!({"sheet": "a10", "rows": 1, "columns": 1}), "unknown sheet preset 'a10'"),
+        ("pdf", serde_json::json!({"sheet": "a4", "rows": 0, "columns": 1}), "rows and columns"),
+        ("pdf", serde_json::json!({"sheet": "a4", "rows": 1, "columns": 1, "gutterMm": -1}), "gutter must be zero or positive"),
+    ];
+    for (format, imposition, expected) in cases {
+        let Err(e) = load(format, imposition) else { panic!("loaded") };
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(expected), "{}", e);
+    }
+}
@@ -2,60 +2,15 @@
 //!
 //! These tests verify the non-negotiable guarantees.
 
+mod common;
+
+use common::create_pipeline;
 use forgeimages_core::{
-    CompilationPipeline, CompileRequest,
-    templates::{Template, TemplateRegistry, AssetClass, ValidationConfig, ValidationRules, RuleConfig, ResolutionRule, FailureMode, ExportSpec, ExportFormat},
+    CompileRequest,
     validation::AssetInput,
     hashing::canonical_json,
 };
 
-fn create_test_template() -> Template {
-    Template {
-        id: "test-icon".to_string(),
-        name: "Test Icon".to_string(),
-        description: "Test template".to_string(),
-        template_version: "1.0.0".to_string(),
-        engine_min_version: "1.0.0".to_string(),
-        deprecated: false,
-        superseded_by: None,
-        asset_class: AssetClass::Icon,
-        aspect_ratio: [1, 1],
-        canonical_size: [1024, 1024],
-        vector_master: true,
-        validation: ValidationConfig {
-            required: true,
-            failure_mode: FailureMode::Block,
-            rules: ValidationRules {
-                aspect_ratio: RuleConfig {
-                    enabled: true,
-                    tolerance: 0.01,
-                },
-                resolution: ResolutionRule {
-                    enabled: true,
-                    min_width: 512,
-                    min_height: 512,
-                },
-                color_count: Default::default(),
-            },
-        },
-        exports: vec![
-            ExportSpec {
-                id: "master".to_string(),
-                description: "SVG master".to_string(),
-                size: [1024, 1024],
-                format: ExportFormat::Svg,
-                required: true,
-            }
-        ],
-    }
-}
-
-fn create_pipeline() -> CompilationPipeline {
-    let mut registry = TemplateRegistry::new();
-    registry.register(create_test_template());
-    CompilationPipeline::new(registry)
-}
-
 #[test]
 fn invariant_compile_calls_validate() {
     // This test verifies that compile_asset internally calls validate_asset
@@ -73,8 +28,11 @@ fn invariant_compile_calls_validate() {
             format: None,
         },
         source_data: None,
+        source_path: None,
         seed: None,
         prompt: None,
+        params: Default::default(),
+        print_spec: None,
     };
 
     let result = pipeline.compile_asset(&request);
@@ -98,8 +56,11 @@ fn invariant_valid_asset_compiles() {
             format: None,
         },
         source_data: None,
+        source_path: None,
         seed: None,
         prompt: None,
+        params: Default::default(),
+        print_spec: None,
     };
 
     let result = pipeline.compile_asset(&request);
@@ -124,8 +85,11 @@ fn invariant_manifest_hash_stable() {
             format: None,
         },
         source_data: None,
+        source_path: None,
         seed: Some(42),  // Fixed seed for determinism
         prompt: Some("test".to_string()),
+        params: Default::default(),
+        print_spec: None,
     };
 
     // Note: In a real implementation with true determinism,
@@ -169,8 +133,11 @@ fn invariant_template_not_found_error() {
             format: None,
         },
         source_data: None,
+        source_path: None,
         seed: None,
         prompt: None,
+        params: Default::default(),
+        print_spec: None,
     };
 
     let result = pipeline.compile_asset(&request);
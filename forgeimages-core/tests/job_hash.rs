@@ -0,0 +1,255 @@
+//! Job hash input layout and pinned request → hash pairs
+//!
+//! A failure here means job hashes drifted: existing checkpoints and audit
+//! entries would stop matching. Change the pins only together with
+//! `JOB_HASH_VERSION` (or an engine version bump, which is hashed too).
+
+mod common;
+
+use common::{compile_request, create_pipeline, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, JobHashInput,
+    canonical_json,
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, PrintAuthority, PrintField, PrintSpec, TemplatePrint},
+    templates::{ExportFormat, FailureMode, TemplateRegistry},
+    units::Length,
+};
+
+fn detailed_request() -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(42);
+    request.prompt = Some("a lighthouse".to_string());
+    request.params.insert("title".to_string(), "Forge".into());
+    request.print_spec = Some(PrintSpec {
+        authority: PrintAuthority::User,
+        dpi: 300,
+        color_space: ColorSpace::Cmyk,
+        bleed: Length::inches(0.125),
+        icc_profile: None,
+    });
+    request
+}
+
+#[test]
+fn test_absent_fields_are_omitted() {
+    let request = compile_request("test-icon", 1024, 1024);
+    assert_eq!(
+        canonical_json(&JobHashInput::new(&request, &create_test_template(), None)).unwrap(),
+        concat!(
+            r#"{"height":1024,"job_hash_version":2,"#,
+            r#""print":{"bleed_inches":0.125,"color_space":"RGB","dpi":300},"template_id":"test-icon","width":1024}"#,
+        ),
+    );
+}
+
+#[test]
+fn test_detailed_input_layout() {
+    let request = detailed_request();
+    assert_eq!(
+        canonical_json(&JobHashInput::new(&request, &create_test_template(), None)).unwrap(),
+        concat!(
+            r#"{"height":1024,"job_hash_version":2,"params":{"title":"Forge"},"#,
+            r#""print":{"bleed_inches":0.125,"color_space":"CMYK","dpi":300},"#,
+            r#""prompt":"a lighthouse","seed":42,"template_id":"test-icon","width":1024}"#,
+        ),
+    );
+}
+
+#[test]
+fn test_content_hash_replaces_source_data() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_data = Some("PHN2Zy8+".to_string());
+    let input = canonical_json(&JobHashInput::new(&request, &create_test_template(), Some("sha256:ab"))).unwrap();
+    assert!(input.contains(r#""source_hash":"sha256:ab""#));
+    assert!(!input.contains("source_data"));
+}
+
+#[test]
+fn test_print_authority_does_not_change_the_hash() {
+    let pipeline = create_pipeline();
+    let request = detailed_request();
+    let mut system = request.clone();
+    system.print_spec.as_mut().unwrap().authority = PrintAuthority::System;
+    assert_eq!(pipeline.job_hash(&request).unwrap(), pipeline.job_hash(&system).unwrap());
+}
+
+#[test]
+fn test_pinned_job_hashes() {
+    let pipeline = create_pipeline();
+    let mut with_source = compile_request("test-icon", 1024, 1024);
+    with_source.source_data = Some("PHN2ZyB3aWR0aD0iMTAyNCIgaGVpZ2h0PSIxMDI0Ii8+".to_string());
+
+    for (request, expected) in [
+        (compile_request("test-icon", 1024, 1024), "sha256:5ed515bfbaa2d82427004433750b3687a20ccbfd0ce98fabe1f3f3554a270271"),
+        (detailed_request(), "sha256:a758a0f441eb764a0b201ed80bce6373a8a71944d9fd456e6f925c45c36a972c"),
+        (with_source, "sha256:b10e787595d2311d87cfc9145721d4c3da0d95ec3c170966f8e8fe6a2b57059f"),
+    ] {
+        assert_eq!(pipeline.job_hash(&request).unwrap(), expected);
+    }
+}
+
+/// The same seed as a JSON number, as a decimal string, and as the Python
+/// bridge re-serializes a request it received from us
+#[test]
+fn test_seed_representations_share_a_job_hash() {
+    let pipeline = create_pipeline();
+    let base = r#""template_id":"test-icon","asset_input":{"width":1024,"height":1024}"#;
+    for (seed, expected) in [
+        ("9007199254740991", "sha256:1cf75408d9d950ac3fa54cf50a3fa31c3897f03d38ff7fc8f986d849786e44dd"),
+        ("18446744073709551615", "sha256:772cf631a3334ef01fdbf6dae93d07f2cd33509cff623e43e36cb2f6b750ebb7"),
+    ] {
+        let as_number: CompileRequest = serde_json::from_str(&format!("{{{},\"seed\":{}}}", base, seed)).unwrap();
+        let as_string: CompileRequest = serde_json::from_str(&format!("{{{},\"seed\":\"{}\"}}", base, seed)).unwrap();
+        let round_tripped: CompileRequest =
+            serde_json::from_value(serde_json::to_value(&as_number).unwrap()).unwrap();
+        for request in [as_number, as_string, round_tripped] {
+            assert_eq!(pipeline.job_hash(&request).unwrap(), expected, "seed {}", seed);
+        }
+    }
+}
+
+/// A request with a source as JSON, as MessagePack with the same strings,
+/// and as MessagePack carrying the source as raw `bin` bytes
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_msgpack_requests_hash_as_their_json() {
+    use forgeimages_core::msgpack;
+
+    /// Serializes as a byte string, which rmp-serde writes as `bin`
+    struct Bin<'a>(&'a [u8]);
+
+    impl serde::Serialize for Bin<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[derive(serde::Serialize)]
+    struct BinRequest<'a> {
+        #[serde(flatten)]
+        request: serde_json::Value,
+        source_data: Bin<'a>,
+    }
+
+    let pipeline = create_pipeline();
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(u64::MAX);
+    request.source_data = Some("PHN2ZyB3aWR0aD0iMTAyNCIgaGVpZ2h0PSIxMDI0Ii8+".to_string());
+    let json = serde_json::to_string(&request).unwrap();
+
+    let mut without_source = serde_json::to_value(&request).unwrap();
+    without_source.as_object_mut().unwrap().remove("source_data");
+    let bin = rmp_serde::to_vec_named(&BinRequest { request: without_source, source_data: Bin(br#"<svg width="1024" height="1024"/>"#) }).unwrap();
+    let strings = msgpack::to_msgpack(&request).unwrap();
+    assert!(bin.len() < strings.len());
+
+    let expected = pipeline.job_hash(&serde_json::from_str(&json).unwrap()).unwrap();
+    for encoded in [strings, bin] {
+        let decoded = CompileRequest::from_msgpack(&encoded).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        assert_eq!(pipeline.job_hash(&decoded).unwrap(), expected);
+        assert_eq!(pipeline.compile_asset(&decoded).unwrap().job_hash, expected);
+    }
+}
+
+#[test]
+fn test_large_seeds_are_written_as_strings() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(u64::MAX);
+    assert_eq!(serde_json::to_value(&request).unwrap()["seed"], "18446744073709551615");
+    assert!(canonical_json(&JobHashInput::new(&request, &create_test_template(), None)).unwrap().contains(r#""seed":"18446744073709551615""#));
+
+    request.seed = Some(42);
+    assert_eq!(serde_json::to_value(&request).unwrap()["seed"], 42);
+}
+
+#[test]
+fn test_unsafe_integer_params_are_rejected() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.params.insert("count".to_string(), u64::MAX.into());
+    let error = create_pipeline().job_hash(&request).unwrap_err().to_string();
+    assert!(error.contains(r#""/params/count""#), "{}", error);
+}
+
+/// A pipeline over the test template with a print block and a print export
+fn print_pipeline(print: TemplatePrint) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.print = Some(print);
+    template.validation.failure_mode = FailureMode::Warn;
+    template.exports.push(export("flyer", [64, 64], ExportFormat::Tiff, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry)
+}
+
+/// 300 dpi CMYK with 3mm bleed; users may not change the color space
+fn press() -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space: ColorSpace::Cmyk,
+        bleed: Length::mm(3.0),
+        allow_user_print_overrides: true,
+        locked_fields: vec![PrintField::ColorSpace],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+fn with_print(spec: PrintSpec) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(spec);
+    request
+}
+
+#[test]
+fn test_resolved_dpi_changes_the_job_hash() {
+    let pipeline = print_pipeline(press());
+    let spec = PrintSpec { color_space: ColorSpace::Cmyk, bleed: Length::mm(3.0), ..PrintSpec::default() };
+    let at_300 = pipeline.job_hash(&with_print(PrintSpec { dpi: 300, ..spec.clone() })).unwrap();
+    let at_600 = pipeline.job_hash(&with_print(PrintSpec { dpi: 600, ..spec })).unwrap();
+    assert_ne!(at_300, at_600);
+}
+
+#[test]
+fn test_identical_resolved_specs_share_a_job_hash() {
+    // Template authority (no user spec), User authority repeating the
+    // template's values, and a refused color space override that resolves
+    // back to the template's CMYK all render the same files
+    let pipeline = print_pipeline(press());
+    let template_only = compile_request("test-icon", 1024, 1024);
+    let repeated = with_print(PrintSpec { color_space: ColorSpace::Cmyk, bleed: Length::mm(3.0), ..PrintSpec::default() });
+    let refused = with_print(PrintSpec { color_space: ColorSpace::Rgb, bleed: Length::mm(3.0), ..PrintSpec::default() });
+
+    let expected = pipeline.job_hash(&template_only).unwrap();
+    let baseline = pipeline.compile_asset(&template_only).unwrap();
+    let flyer = |asset: &forgeimages_core::CompiledAsset| asset.exports.iter().find(|e| e.id == "flyer").unwrap().hash.clone();
+    for request in [repeated, refused] {
+        assert_eq!(pipeline.job_hash(&request).unwrap(), expected);
+        let asset = pipeline.compile_asset(&request).unwrap();
+        assert_eq!(asset.job_hash, expected);
+        assert_eq!(flyer(&asset), flyer(&baseline));
+    }
+}
+
+#[test]
+fn test_export_print_blocks_enter_the_input() {
+    let mut template = create_test_template();
+    let mut flyer = export("flyer", [64, 64], ExportFormat::Tiff, true);
+    flyer.print = Some(TemplatePrint { dpi: 150, ..press() });
+    template.exports.push(flyer);
+    template.exports.push(export("scan", [64, 64], ExportFormat::Tiff, true));
+
+    let request = with_print(PrintSpec { dpi: 600, ..PrintSpec::default() });
+    let input = canonical_json(&JobHashInput::new(&request, &template, None)).unwrap();
+    assert!(input.contains(r#""export_prints":{"flyer":{"bleed_inches":0.125,"color_space":"CMYK","dpi":600}}"#), "{}", input);
+    assert!(input.contains(r#""print":{"bleed_inches":0.125,"color_space":"RGB","dpi":600}"#), "{}", input);
+}
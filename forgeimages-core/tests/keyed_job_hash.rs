@@ -0,0 +1,110 @@
+//! HMAC-keyed job hashes for deployments that share job hashes across tenants
+
+mod common;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, JobHashKey,
+    output::MANIFEST_FILE,
+    templates::TemplateRegistry,
+    verify::{verify_job_hash, verify_manifest, VerifyError},
+};
+
+fn pipeline(key: Option<JobHashKey>) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    let builder = CompilationPipeline::builder(registry);
+    match key {
+        Some(key) => builder.job_hash_key(key),
+        None => builder,
+    }.build()
+}
+
+fn tenant(key_id: &str, secret: &str) -> Option<JobHashKey> {
+    Some(JobHashKey::new(key_id, secret))
+}
+
+#[test]
+fn test_keyed_job_hash_is_pinned() {
+    // HMAC-SHA256(b"tenant-a-secret", JOB_HASH_DOMAIN || preimage), computed independently
+    let request = compile_request("test-icon", 1024, 1024);
+    assert_eq!(
+        pipeline(tenant("tenant-a", "tenant-a-secret")).job_hash(&request).unwrap(),
+        "sha256:5d8fa4a68ff98f05ec5571b8ffbc890f61a1011ed7b4074ccc474f3bc0614882",
+    );
+}
+
+#[test]
+fn test_keys_separate_identical_inputs() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let unkeyed = pipeline(None).job_hash(&request).unwrap();
+    let a = pipeline(tenant("tenant-a", "tenant-a-secret")).job_hash(&request).unwrap();
+    let b = pipeline(tenant("tenant-b", "tenant-b-secret")).job_hash(&request).unwrap();
+    assert_ne!(a, b);
+    assert_ne!(a, unkeyed);
+    assert_ne!(b, unkeyed);
+}
+
+#[test]
+fn test_manifest_records_the_key_id_but_not_the_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = pipeline(tenant("tenant-a", "tenant-a-secret"))
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+    assert!(asset.job_hash_keyed);
+    assert_eq!(asset.job_hash_key_id.as_deref(), Some("tenant-a"));
+
+    let manifest = std::fs::read_to_string(dir.path().join(MANIFEST_FILE)).unwrap();
+    assert!(manifest.contains(r#""job_hash_keyed":true"#));
+    assert!(!manifest.contains("tenant-a-secret"));
+    verify_manifest(&dir.path().join(MANIFEST_FILE)).unwrap();
+}
+
+#[test]
+fn test_unkeyed_manifests_omit_the_key_fields() {
+    let asset = pipeline(None).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert!(!asset.job_hash_keyed);
+    let manifest = serde_json::to_value(&asset).unwrap();
+    assert!(manifest.get("job_hash_keyed").is_none());
+    assert!(manifest.get("job_hash_key_id").is_none());
+}
+
+#[test]
+fn test_verification_requires_the_right_key() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let keyed = pipeline(tenant("tenant-a", "tenant-a-secret"));
+    let asset = keyed.compile_asset(&request).unwrap();
+
+    verify_job_hash(&asset, &request, &keyed).unwrap();
+    assert!(matches!(
+        verify_job_hash(&asset, &request, &pipeline(None)),
+        Err(VerifyError::JobHashKeyRequired(id)) if id == "tenant-a"
+    ));
+    assert!(matches!(
+        verify_job_hash(&asset, &request, &pipeline(tenant("tenant-b", "tenant-b-secret"))),
+        Err(VerifyError::JobHashKeyMismatch(recorded, held)) if recorded == "tenant-a" && held == "tenant-b"
+    ));
+    // Same id, wrong secret
+    assert!(matches!(
+        verify_job_hash(&asset, &request, &pipeline(tenant("tenant-a", "guessed"))),
+        Err(VerifyError::JobHashMismatch(..))
+    ));
+}
+
+#[test]
+fn test_unkeyed_hash_does_not_verify_under_a_key() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let unkeyed = pipeline(None);
+    let asset = unkeyed.compile_asset(&request).unwrap();
+    verify_job_hash(&asset, &request, &unkeyed).unwrap();
+    assert!(matches!(
+        verify_job_hash(&asset, &request, &pipeline(tenant("tenant-a", "tenant-a-secret"))),
+        Err(VerifyError::JobHashKeyMismatch(recorded, _)) if recorded == "unkeyed"
+    ));
+}
+
+#[test]
+fn test_key_debug_output_hides_the_secret() {
+    let key = JobHashKey::new("tenant-a", "tenant-a-secret");
+    assert!(!format!("{:?}", key).contains("tenant-a-secret"));
+}
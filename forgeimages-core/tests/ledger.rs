@@ -0,0 +1,139 @@
+//! Job ledger - hash-chained entries and the ledger audit sink
+
+mod common;
+
+use std::fs;
+use std::io::BufReader;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline,
+    audit::GENESIS_HASH,
+    hashing::{Ledger, LedgerError, LedgerSink},
+    templates::TemplateRegistry,
+};
+
+fn ledger_of(count: usize) -> Ledger {
+    let mut ledger = Ledger::new();
+    for i in 0..count {
+        ledger.append(
+            &format!("sha256:{:064x}", i),
+            &format!("sha256:{:064x}", i + 100),
+            &format!("2026-01-01T00:00:{:02}+00:00", i),
+        ).unwrap();
+    }
+    ledger
+}
+
+fn broken_at(ledger: &Ledger) -> Option<usize> {
+    match ledger.verify_chain() {
+        Ok(()) => None,
+        Err(LedgerError::ChainBroken(index)) => Some(index),
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+}
+
+/// Round-trip through JSONL after editing the entries
+fn edited(ledger: &Ledger, edit: impl FnOnce(&mut Vec<serde_json::Value>)) -> Ledger {
+    let mut buffer = vec![];
+    ledger.write_jsonl(&mut buffer).unwrap();
+    let mut lines: Vec<serde_json::Value> = String::from_utf8(buffer).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    edit(&mut lines);
+    let jsonl: String = lines.iter().map(|v| format!("{}\n", v)).collect();
+    Ledger::read_jsonl(BufReader::new(jsonl.as_bytes())).unwrap()
+}
+
+#[test]
+fn test_entries_chain_from_genesis() {
+    let ledger = ledger_of(3);
+    let entries = ledger.entries();
+    assert_eq!(entries[0].seq, 0);
+    assert_eq!(entries[0].prev_entry_hash, GENESIS_HASH);
+    assert_eq!(entries[1].prev_entry_hash, entries[0].entry_hash);
+    assert_eq!(entries[2].prev_entry_hash, entries[1].entry_hash);
+    assert_eq!(ledger.head_hash(), entries[2].entry_hash);
+    assert_eq!(broken_at(&ledger), None);
+}
+
+#[test]
+fn test_jsonl_round_trip() {
+    let ledger = ledger_of(4);
+    assert_eq!(edited(&ledger, |_| {}), ledger);
+}
+
+#[test]
+fn test_modified_middle_entry_is_detected() {
+    let ledger = ledger_of(5);
+    let tampered = edited(&ledger, |lines| lines[2]["manifest_hash"] = format!("sha256:{:064x}", 999).into());
+    assert_eq!(broken_at(&tampered), Some(2));
+
+    // Re-deriving the entry's own hash moves the break to its successor
+    let mut forger = Ledger::new();
+    for entry in &ledger.entries()[..2] {
+        forger.append(&entry.job_hash, &entry.manifest_hash, &entry.timestamp).unwrap();
+    }
+    let forged = forger.append("sha256:forged", "sha256:forged", "2026-01-01T00:00:02+00:00").unwrap().clone();
+    let rehashed = edited(&ledger, |lines| lines[2] = serde_json::to_value(&forged).unwrap());
+    assert_eq!(broken_at(&rehashed), Some(3));
+}
+
+#[test]
+fn test_deleted_middle_entry_is_detected() {
+    let tampered = edited(&ledger_of(5), |lines| { lines.remove(2); });
+    assert_eq!(broken_at(&tampered), Some(2));
+}
+
+#[test]
+fn test_reordered_entries_are_detected() {
+    let tampered = edited(&ledger_of(5), |lines| lines.swap(1, 3));
+    assert_eq!(broken_at(&tampered), Some(1));
+}
+
+#[test]
+fn test_malformed_line_is_reported() {
+    let jsonl = "not json\n";
+    assert!(matches!(
+        Ledger::read_jsonl(BufReader::new(jsonl.as_bytes())),
+        Err(LedgerError::Malformed(1, _)),
+    ));
+}
+
+#[test]
+fn test_sink_records_compiles_with_manifests() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ledger.jsonl");
+    let build = || {
+        let mut registry = TemplateRegistry::new();
+        registry.register(create_test_template());
+        CompilationPipeline::builder(registry).audit_sink(LedgerSink::open(&path).unwrap()).build()
+    };
+
+    let pipeline = build();
+    let first = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert!(pipeline.compile_asset(&compile_request("test-icon", 100, 100)).is_err());
+    drop(pipeline);
+
+    // Reopening continues the chain
+    let second = build().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+
+    let ledger = Ledger::read_jsonl(BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+    ledger.verify_chain().unwrap();
+    let hashes: Vec<_> = ledger.entries().iter().map(|e| e.manifest_hash.clone()).collect();
+    assert_eq!(hashes, [first.manifest_hash, second.manifest_hash]);
+    assert_eq!(ledger.entries()[1].job_hash, second.job_hash);
+}
+
+#[test]
+fn test_sink_refuses_a_broken_ledger() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ledger.jsonl");
+    let tampered = edited(&ledger_of(3), |lines| lines.swap(0, 1));
+    let mut buffer = vec![];
+    tampered.write_jsonl(&mut buffer).unwrap();
+    fs::write(&path, buffer).unwrap();
+
+    assert!(matches!(LedgerSink::open(&path), Err(LedgerError::ChainBroken(0))));
+}
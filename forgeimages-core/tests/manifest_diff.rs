@@ -0,0 +1,97 @@
+//! Manifest diffs: rebuilds compare identical, exports match by id, and
+//! validation changes come from `ValidationResult::diff`
+
+mod common;
+
+use common::{compile_request, create_pipeline, export};
+use forgeimages_core::{
+    CompiledAsset,
+    diff::{diff_manifests, ExportDiff, FieldChange},
+    templates::ExportFormat,
+    validation::{ValidationViolation, ViolationSeverity},
+};
+use serde_json::Value;
+
+fn compiled() -> CompiledAsset {
+    create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap()
+}
+
+fn violation(rule: &str, severity: ViolationSeverity) -> ValidationViolation {
+    ValidationViolation {
+        rule: rule.to_string(),
+        severity,
+        message: format!("{} failed", rule),
+        expected: None,
+        actual: None,
+        remediation: vec![],
+    }
+}
+
+#[test]
+fn test_a_rebuild_is_identical() {
+    let (a, b) = (compiled(), compiled());
+    assert_ne!(a.manifest_hash, b.manifest_hash, "runs differ in id and created_at");
+    let diff = diff_manifests(&a, &b);
+    assert!(diff.identical, "{:?}", diff);
+    assert!(diff.fields.is_empty() && diff.exports.is_empty());
+}
+
+#[test]
+fn test_exports_match_by_id() {
+    let a = compiled();
+    let mut b = a.clone();
+    b.engine_version = "9.9.9".to_string();
+    b.exports[0].hash = "sha256:00".to_string();
+    b.exports[0].size = [512, 512];
+    let mut extra = b.exports[0].clone();
+    extra.id = "extra".to_string();
+    b.exports.push(extra);
+
+    let diff = diff_manifests(&a, &b);
+    assert!(!diff.identical);
+    assert_eq!(diff.fields, [FieldChange { field: "engine_version", a: Value::from(a.engine_version.as_str()), b: Value::from("9.9.9") }]);
+    let ExportDiff::Changed { id, changes } = &diff.exports[0] else { panic!("{:?}", diff.exports) };
+    assert_eq!(id, "master");
+    let fields: Vec<&str> = changes.iter().map(|c| c.field).collect();
+    assert_eq!(fields, ["hash", "size"]);
+    assert_eq!(changes[0].a, Value::from(a.exports[0].hash.as_str()));
+    assert_eq!(diff.exports[1], ExportDiff::OnlyInB { id: "extra".to_string() });
+
+    // Reordering is not a difference
+    let mut template = common::create_test_template();
+    template.exports.push(export("png", [64, 64], ExportFormat::Png, true));
+    let mut registry = forgeimages_core::templates::TemplateRegistry::new();
+    registry.register(template);
+    let two = forgeimages_core::CompilationPipeline::new(registry)
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    let mut reversed = two.clone();
+    reversed.exports.reverse();
+    assert!(diff_manifests(&two, &reversed).identical);
+    let mut missing = two.clone();
+    missing.exports.pop();
+    assert_eq!(diff_manifests(&two, &missing).exports, [ExportDiff::OnlyInA { id: "png".to_string() }]);
+}
+
+#[test]
+fn test_validation_diff_pairs_violations() {
+    let a = compiled();
+    let mut b = a.clone();
+    b.validation.violations = vec![violation("color_count", ViolationSeverity::Warning)];
+    let diff = a.validation.diff(&b.validation);
+    assert_eq!(diff.valid, None);
+    assert_eq!(diff.added.len(), 1);
+    assert!(diff.removed.is_empty());
+
+    let mut c = b.clone();
+    c.validation.valid = false;
+    c.validation.violations = vec![
+        violation("color_count", ViolationSeverity::Warning),
+        violation("resolution", ViolationSeverity::Error),
+    ];
+    let diff = b.validation.diff(&c.validation);
+    assert_eq!(diff.valid, Some([true, false]));
+    assert_eq!(diff.added.iter().map(|v| v.rule.as_str()).collect::<Vec<_>>(), ["resolution"]);
+    assert!(c.validation.diff(&c.validation).is_empty());
+    assert!(!diff_manifests(&b, &c).identical);
+}
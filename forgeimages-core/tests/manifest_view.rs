@@ -0,0 +1,92 @@
+//! ManifestHashView - what the manifest hash covers
+
+mod common;
+
+use common::{compile_request, create_pipeline};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, HashAlgorithm, HashScheme, ManifestHashView,
+    pipeline::CompileMetrics,
+    print::PrintSpec,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+fn compiled() -> CompiledAsset {
+    create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap()
+}
+
+fn hash(view: &ManifestHashView) -> String {
+    view.hash(HashScheme::CURRENT, HashAlgorithm::Sha256).unwrap()
+}
+
+#[test]
+fn test_view_hash_is_the_manifest_hash() {
+    let asset = compiled();
+    assert_eq!(hash(&ManifestHashView::of(&asset).unwrap()), asset.manifest_hash);
+}
+
+#[test]
+fn test_metrics_present_or_absent_hash_the_same() {
+    let asset = compiled();
+    assert!(!asset.metrics.exports.is_empty());
+    let mut without = asset.clone();
+    without.metrics = CompileMetrics::default();
+
+    assert_eq!(ManifestHashView::of(&asset).unwrap(), ManifestHashView::of(&without).unwrap());
+    assert_eq!(hash(&ManifestHashView::of(&without).unwrap()), asset.manifest_hash);
+}
+
+#[test]
+fn test_signature_and_recorded_hash_are_outside_the_view() {
+    let asset = compiled();
+    let mut manifest = serde_json::to_value(&asset).unwrap();
+    let unsigned = ManifestHashView::from_json(&manifest);
+
+    manifest["signature"] = "c2lnbmF0dXJl".into();
+    manifest["manifest_hash"] = "sha256:anything".into();
+    manifest["exports"][0]["deduplicated_from"] = "other".into();
+    assert_eq!(ManifestHashView::from_json(&manifest), unsigned);
+
+    manifest.as_object_mut().unwrap().remove("signature");
+    manifest.as_object_mut().unwrap().remove("manifest_hash");
+    assert_eq!(hash(&ManifestHashView::from_json(&manifest)), asset.manifest_hash);
+}
+
+#[test]
+fn test_covered_fields_change_the_hash() {
+    let asset = compiled();
+    let mut edited = asset.clone();
+    edited.template_version = "2.0.0".to_string();
+    assert_ne!(hash(&ManifestHashView::of(&edited).unwrap()), asset.manifest_hash);
+}
+
+type Edit = fn(&mut serde_json::Value);
+
+#[test]
+fn test_print_fields_are_covered() {
+    let mut template = common::create_test_template();
+    template.exports.push(common::export("flyer", [64, 64], ExportFormat::Tiff, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, ..PrintSpec::default() });
+    let asset = CompilationPipeline::new(registry).compile_asset(&request).unwrap();
+    let manifest = serde_json::to_value(&asset).unwrap();
+    assert_eq!(manifest["print"]["dpi"], 150);
+    assert_eq!(manifest["print_overrides"][0]["field"], "dpi");
+
+    // The resolved spec, the overrides and every per-export print field
+    let edits: [(&str, Edit); 5] = [
+        ("print", |m| m["print"]["dpi"] = 300.into()),
+        ("print_overrides", |m| m["print_overrides"][0]["user_value"] = 300.into()),
+        ("exports[].print", |m| m["exports"][1]["print"]["color_space"] = "CMYK".into()),
+        ("exports[].trim", |m| m["exports"][1]["trim"]["offset"] = serde_json::json!([0, 0])),
+        ("exports[].size", |m| m["exports"][1]["size"] = serde_json::json!([64, 64])),
+    ];
+    let recorded = hash(&ManifestHashView::from_json(&manifest));
+    assert_eq!(recorded, asset.manifest_hash);
+    for (field, edit) in edits {
+        let mut edited = manifest.clone();
+        edit(&mut edited);
+        assert_ne!(hash(&ManifestHashView::from_json(&edited)), recorded, "{} is not covered", field);
+    }
+}
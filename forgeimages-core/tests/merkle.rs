@@ -0,0 +1,110 @@
+//! Merkle root over exports and per-export inclusion proofs
+
+mod common;
+
+use common::{compile_request, create_pipeline, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, HashingError,
+    hashing::{exports_root, merkle_proof, verify_merkle_proof, Side},
+    templates::{ExportFormat, TemplateRegistry},
+    verify::verify_exports,
+};
+
+/// Five exports, so the tree has an odd level to duplicate
+fn compile_five() -> CompiledAsset {
+    let mut template = create_test_template();
+    for (id, size) in [("e-large", 256), ("a-tiny", 16), ("c-mid", 64), ("b-small", 32)] {
+        template.exports.push(export(id, [size, size], ExportFormat::Png, true));
+    }
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry).build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap()
+}
+
+fn hash_of<'a>(asset: &'a CompiledAsset, export_id: &str) -> &'a str {
+    &asset.exports.iter().find(|e| e.id == export_id).unwrap().hash
+}
+
+fn assert_proves(asset: &CompiledAsset, export_id: &str, leaf_index: usize) {
+    let root = asset.exports_root.as_deref().unwrap();
+    let proof = merkle_proof(&asset.exports, export_id).unwrap();
+    assert_eq!(proof.leaf_index, leaf_index);
+    assert_eq!(proof.leaf_count, asset.exports.len());
+    assert!(verify_merkle_proof(root, &proof, hash_of(asset, export_id)));
+}
+
+#[test]
+fn test_root_is_recorded_and_matches_exports() {
+    let asset = compile_five();
+    let root = asset.exports_root.clone().unwrap();
+    assert!(root.starts_with("sha256:"));
+    assert_eq!(exports_root(&asset.exports).unwrap(), Some(root));
+}
+
+#[test]
+fn test_proof_for_first_leaf() {
+    // Leaves are ordered by export id, not by template order
+    assert_proves(&compile_five(), "a-tiny", 0);
+}
+
+#[test]
+fn test_proof_for_middle_leaf() {
+    assert_proves(&compile_five(), "c-mid", 2);
+}
+
+#[test]
+fn test_proof_for_last_leaf() {
+    let asset = compile_five();
+    assert_proves(&asset, "master", 4);
+
+    // The odd fifth leaf is paired with itself at the bottom level
+    let proof = merkle_proof(&asset.exports, "master").unwrap();
+    assert_eq!(proof.steps.len(), 3);
+    assert_eq!(proof.steps[0].side, Side::Right);
+}
+
+#[test]
+fn test_proof_for_single_export() {
+    let asset = create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.exports.len(), 1);
+
+    let proof = merkle_proof(&asset.exports, "master").unwrap();
+    assert!(proof.steps.is_empty());
+    assert!(verify_merkle_proof(asset.exports_root.as_deref().unwrap(), &proof, &asset.exports[0].hash));
+}
+
+#[test]
+fn test_proof_rejects_other_leaf_and_tampered_sibling() {
+    let asset = compile_five();
+    let root = asset.exports_root.as_deref().unwrap();
+    let mut proof = merkle_proof(&asset.exports, "c-mid").unwrap();
+
+    assert!(!verify_merkle_proof(root, &proof, hash_of(&asset, "b-small")));
+
+    proof.steps[1].sibling = hash_of(&asset, "a-tiny").to_string();
+    assert!(!verify_merkle_proof(root, &proof, hash_of(&asset, "c-mid")));
+}
+
+#[test]
+fn test_root_ignores_export_order() {
+    let asset = compile_five();
+    let mut reversed = asset.exports.clone();
+    reversed.reverse();
+    assert_eq!(exports_root(&reversed).unwrap(), asset.exports_root);
+}
+
+#[test]
+fn test_unknown_export_id() {
+    let asset = compile_five();
+    assert!(matches!(merkle_proof(&asset.exports, "missing"), Err(HashingError::UnknownLeaf(_))));
+}
+
+#[test]
+fn test_written_manifest_with_root_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = create_pipeline().compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path()).unwrap();
+    assert!(asset.exports_root.is_some());
+    assert_eq!(verify_exports(dir.path()).unwrap(), asset.manifest_hash);
+}
@@ -0,0 +1,73 @@
+//! SVG source normalization
+
+mod common;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, HashAlgorithm,
+    svg_normalize::normalize,
+    templates::TemplateRegistry,
+};
+
+const ORIGINAL: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with Inkscape -->
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape"
+     viewBox="0 0 100.000 100" width="100">
+  <sodipodi:namedview id="base" pagecolor="#ffffff"><inkscape:grid type="xygrid"/></sodipodi:namedview>
+  <g inkscape:label="Layer 1" fill='#336699'>
+    <rect height="50.0"   width="50" x="10.00001" y="0"></rect>
+    <text x="5" y="90">  Spaced   title </text>
+  </g>
+</svg>
+"##;
+
+const REFORMATTED: &str = r##"<svg viewBox="0 0 100 100" width="100" xmlns="http://www.w3.org/2000/svg"><g fill="#336699"><rect x="10" y="0" width="50" height="50"/><text y="90" x="5">  Spaced   title </text></g></svg>"##;
+
+fn pipeline(normalize: bool) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::builder(registry).normalize_source(normalize).build()
+}
+
+fn request(svg: &str) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_data = Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, svg));
+    request
+}
+
+#[test]
+fn test_canonical_form() {
+    assert_eq!(
+        normalize(ORIGINAL).unwrap(),
+        r##"<svg viewBox="0 0 100 100" width="100" xmlns="http://www.w3.org/2000/svg"><g fill="#336699"><rect height="50" width="50" x="10" y="0"/><text x="5" y="90">  Spaced   title </text></g></svg>"##
+    );
+}
+
+#[test]
+fn test_normalization_is_idempotent() {
+    for svg in [ORIGINAL, REFORMATTED, "<svg><path d='M 1.23456 -0.5e1 L.5-.5z'/></svg>"] {
+        let once = normalize(svg).unwrap();
+        assert_eq!(normalize(&once).unwrap(), once);
+    }
+}
+
+#[test]
+fn test_equivalent_sources_share_job_hash_when_normalized() {
+    let a = pipeline(true).compile_asset(&request(ORIGINAL)).unwrap();
+    let b = pipeline(true).compile_asset(&request(REFORMATTED)).unwrap();
+    assert_eq!(a.job_hash, b.job_hash);
+    assert_eq!(a.normalized_source_hash, b.normalized_source_hash);
+    assert_ne!(a.source_hash, b.source_hash);
+    assert_eq!(a.source_hash, Some(HashAlgorithm::Sha256.digest(ORIGINAL.as_bytes())));
+
+    let master = |asset: &forgeimages_core::CompiledAsset| asset.exports[0].hash.clone();
+    assert_eq!(master(&a), master(&b));
+}
+
+#[test]
+fn test_normalization_is_opt_in() {
+    let a = pipeline(false).compile_asset(&request(ORIGINAL)).unwrap();
+    let b = pipeline(false).compile_asset(&request(REFORMATTED)).unwrap();
+    assert_ne!(a.job_hash, b.job_hash);
+    assert!(a.normalized_source_hash.is_none());
+}
@@ -0,0 +1,202 @@
+//! Compile Notifications
+//!
+//! Every compile attempt reaches the notifier, and webhook deliveries are
+//! signed, retried and never able to fail a compile.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, PipelineBuilder,
+    audit::{AuditEvent, AuditOutcome},
+    notify::Notifier,
+    templates::TemplateRegistry,
+};
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<AuditEvent>>>);
+
+impl Notifier for Recorder {
+    fn notify(&self, event: &AuditEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+fn builder() -> PipelineBuilder {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::builder(registry)
+}
+
+#[test]
+fn test_every_attempt_is_notified() {
+    let recorder = Recorder::default();
+    let pipeline = builder().notifier(recorder.clone()).build();
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    pipeline.compile_asset(&compile_request("test-icon", 100, 50)).unwrap_err();
+    pipeline.compile_asset(&compile_request("missing", 1024, 1024)).unwrap_err();
+
+    let events = recorder.0.lock().unwrap();
+    let outcomes: Vec<_> = events.iter().map(|e| e.outcome).collect();
+    assert_eq!(outcomes, [AuditOutcome::Success, AuditOutcome::ValidationFailed, AuditOutcome::Error]);
+    assert_eq!(events[0].job_hash.as_deref(), Some(asset.job_hash.as_str()));
+    assert_eq!(events[0].manifest_hash.as_deref(), Some(asset.manifest_hash.as_str()));
+    assert!(events[1].violations.contains(&"aspect_ratio".to_string()), "{:?}", events[1].violations);
+    assert_eq!(events[2].template_id, "missing");
+}
+
+#[cfg(feature = "webhooks")]
+mod webhook {
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::{Duration, Instant};
+
+    use forgeimages_core::{
+        canonical_json,
+        notify::{verify_signature, SIGNATURE_HEADER},
+        webhook::{WebhookConfig, WebhookNotifier},
+        RetryPolicy,
+    };
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    struct Delivery {
+        headers: BTreeMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// A webhook receiver answering each request, after `delay`, with the
+    /// next status in `statuses` (then 200), handing the test what it received
+    fn receiver(statuses: &'static [u16], delay: Duration) -> (String, Receiver<Delivery>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/compile", listener.local_addr().unwrap());
+        let (sender, deliveries) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut statuses = statuses.iter();
+            for stream in listener.incoming() {
+                let mut writer = stream.unwrap();
+                let mut reader = BufReader::new(writer.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut headers = BTreeMap::new();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    match header.trim_end().split_once(':') {
+                        Some((name, value)) => headers.insert(name.to_ascii_lowercase(), value.trim().to_string()),
+                        None => break,
+                    };
+                }
+                let mut body = vec![0; headers["content-length"].parse().unwrap()];
+                reader.read_exact(&mut body).unwrap();
+                let _ = sender.send(Delivery { headers, body });
+                std::thread::sleep(delay);
+                let status = statuses.next().copied().unwrap_or(200);
+                let _ = write!(writer, "HTTP/1.1 {} X\r\nconnection: close\r\ncontent-length: 0\r\n\r\n", status);
+            }
+        });
+        (url, deliveries)
+    }
+
+    /// HMAC-SHA256 (RFC 2104) of `body`, written out independently of the engine's
+    fn hmac_hex(key: &[u8], body: &[u8]) -> String {
+        let mut block = [0u8; 64];
+        block[..key.len()].copy_from_slice(key);
+        let inner = Sha256::new().chain_update(block.map(|b| b ^ 0x36)).chain_update(body).finalize();
+        let outer = Sha256::new().chain_update(block.map(|b| b ^ 0x5c)).chain_update(inner).finalize();
+        outer.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn config(url: &str, max_attempts: u32, timeout: Duration) -> WebhookConfig {
+        WebhookConfig {
+            timeout,
+            retry: RetryPolicy { max_attempts, backoff: Duration::from_millis(10) },
+            ..WebhookConfig::new(url, "hook-secret")
+        }
+    }
+
+    #[test]
+    fn test_signed_events_are_retried_after_5xx() {
+        let (url, deliveries) = receiver(&[503, 502], Duration::ZERO);
+        let notifier = WebhookNotifier::new(config(&url, 4, Duration::from_secs(5))).unwrap();
+        let pipeline = builder().notifier(notifier).build();
+        let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+        drop(pipeline);
+
+        let received: Vec<_> = deliveries.try_iter().collect();
+        assert_eq!(received.len(), 3);
+        for delivery in &received {
+            assert_eq!(delivery.body, received[0].body);
+            assert_eq!(delivery.headers["content-type"], "application/json");
+            let signature = &delivery.headers[&SIGNATURE_HEADER.to_ascii_lowercase()];
+            assert_eq!(*signature, format!("sha256={}", hmac_hex(b"hook-secret", &delivery.body)));
+            assert!(verify_signature(b"hook-secret", &delivery.body, signature));
+        }
+
+        let event: serde_json::Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(canonical_json(&event).unwrap().as_bytes(), received[0].body);
+        assert_eq!(event["outcome"], "success");
+        assert_eq!(event["template_id"], "test-icon");
+        assert_eq!(event["job_hash"], asset.job_hash.as_str());
+        assert_eq!(event["manifest_hash"], asset.manifest_hash.as_str());
+        assert_eq!(event["violations"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_4xx_is_not_retried() {
+        let (url, deliveries) = receiver(&[401], Duration::ZERO);
+        let pipeline = builder().notifier(WebhookNotifier::new(config(&url, 4, Duration::from_secs(5))).unwrap()).build();
+        pipeline.compile_asset(&compile_request("test-icon", 100, 50)).unwrap_err();
+        drop(pipeline);
+
+        let received: Vec<_> = deliveries.try_iter().collect();
+        assert_eq!(received.len(), 1);
+        let event: serde_json::Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(event["outcome"], "validation_failed");
+    }
+
+    #[test]
+    fn test_slow_or_missing_receivers_never_fail_a_compile() {
+        // Answers after a second, past the 100ms delivery timeout
+        let (url, deliveries) = receiver(&[], Duration::from_secs(1));
+        let pipeline = builder().notifier(WebhookNotifier::new(config(&url, 2, Duration::from_millis(100))).unwrap()).build();
+        let started = Instant::now();
+        pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(100), "{:?}", started.elapsed());
+        drop(pipeline);
+        assert_eq!(deliveries.iter().take(2).count(), 2);
+
+        // Nothing listening at all
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let notifier = WebhookNotifier::new(config(&format!("http://{}/", closed), 2, Duration::from_millis(100))).unwrap();
+        let pipeline = builder().notifier(notifier).build();
+        pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    }
+
+    #[test]
+    fn test_full_queues_drop_events_and_shutdown_is_bounded() {
+        // Each delivery takes a second; one waits behind it, the rest are dropped
+        let (url, deliveries) = receiver(&[], Duration::from_secs(1));
+        let config = WebhookConfig {
+            queue_capacity: 1,
+            shutdown_timeout: Duration::from_millis(200),
+            ..config(&url, 1, Duration::from_secs(5))
+        };
+        let pipeline = builder().notifier(WebhookNotifier::new(config).unwrap()).build();
+        for _ in 0..4 {
+            pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+        }
+        let started = Instant::now();
+        drop(pipeline);
+        assert!(started.elapsed() < Duration::from_millis(800), "{:?}", started.elapsed());
+
+        // The one in flight lands; the queued one is abandoned
+        std::thread::sleep(Duration::from_millis(1500));
+        assert_eq!(deliveries.try_iter().count(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+//! PDF/X exports: output intent, identifiers and boxes read back from the
+//! file structure; unmet requirements; transparency policy
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, PipelineBuilder, PipelineError, Renderer, RenderError, RenderJob,
+    icc::IccProfileStore,
+    pdf::{PdfStandard, TransparencyPolicy},
+    print::{ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, TemplateRegistry},
+    units::Length,
+};
+
+fn profile_bytes(space: &[u8; 4]) -> Vec<u8> {
+    let mut data = vec![0; 132];
+    data[0..4].copy_from_slice(&132u32.to_be_bytes());
+    data[16..20].copy_from_slice(space);
+    data[36..40].copy_from_slice(b"acsp");
+    data
+}
+
+fn store() -> IccProfileStore {
+    let mut store = IccProfileStore::new();
+    store.register("Coated FOGRA39", profile_bytes(b"CMYK")).unwrap();
+    store
+}
+
+/// 72 dpi, so PDF points equal pixels; 0.125 in of bleed is 9 px
+fn print_block(color_space: ColorSpace, icc_profile: Option<&str>, transparency_policy: TransparencyPolicy) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 72,
+        color_space,
+        bleed: Length::inches(0.125),
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: icc_profile.map(str::to_string),
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: Some(PdfStandard::X1a),
+        transparency_policy,
+        profile: None,
+    }
+}
+
+fn compile_with(print: TemplatePrint, configure: impl FnOnce(PipelineBuilder) -> PipelineBuilder) -> Result<CompiledAsset, PipelineError> {
+    let mut template = create_test_template();
+    template.print = Some(print);
+    template.exports.push(export("press", [8, 8], ExportFormat::Pdf, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    configure(CompilationPipeline::builder(registry).icc_profiles(store()))
+        .build().compile_asset(&compile_request("test-icon", 1024, 1024))
+}
+
+fn compile(print: TemplatePrint) -> Result<CompiledAsset, PipelineError> {
+    compile_with(print, |builder| builder)
+}
+
+fn press_pdf(asset: &CompiledAsset) -> Vec<u8> {
+    let file = asset.exports.iter().find(|e| e.id == "press").unwrap();
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap()
+}
+
+/// A PDF read through its cross-reference table
+struct Pdf {
+    objects: BTreeMap<u32, Vec<u8>>,
+    trailer: String,
+}
+
+impl Pdf {
+    fn parse(bytes: &[u8]) -> Pdf {
+        let startxref = bytes.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+        let tail = std::str::from_utf8(&bytes[startxref + 10..]).unwrap();
+        let xref: usize = tail.lines().next().unwrap().parse().unwrap();
+        let table = std::str::from_utf8(&bytes[xref..]).unwrap();
+        assert!(table.starts_with("xref\n0 "), "xref table at startxref");
+        let mut lines = table.lines().skip(1);
+        let count: u32 = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        let mut objects = BTreeMap::new();
+        for (number, entry) in (0..count).zip(lines.by_ref()) {
+            if !entry.ends_with("n ") {
+                continue;
+            }
+            let offset: usize = entry[..10].parse().unwrap();
+            let header = format!("{} 0 obj\n", number);
+            assert_eq!(&bytes[offset..offset + header.len()], header.as_bytes(), "object {} offset", number);
+            let body = &bytes[offset + header.len()..];
+            let end = body.windows(8).position(|w| w == b"\nendobj\n").unwrap();
+            objects.insert(number, body[..end].to_vec());
+        }
+        let trailer = table[table.find("trailer\n").unwrap()..].to_string();
+        Pdf { objects, trailer }
+    }
+
+    /// Dictionary text of an object (a stream's dictionary, without its data)
+    fn dict(&self, number: u32) -> String {
+        let body = &self.objects[&number];
+        let end = body.windows(7).position(|w| w == b"stream\n").unwrap_or(body.len());
+        String::from_utf8(body[..end].to_vec()).unwrap()
+    }
+
+    fn stream(&self, number: u32) -> &[u8] {
+        let body = &self.objects[&number];
+        let length: usize = value(&self.dict(number), "/Length").parse().unwrap();
+        let start = body.windows(7).position(|w| w == b"stream\n").unwrap() + 7;
+        &body[start..start + length]
+    }
+}
+
+/// First token after `key` in a dictionary
+fn value<'a>(dict: &'a str, key: &str) -> &'a str {
+    let at = dict.find(&format!("{} ", key)).unwrap_or_else(|| panic!("{} missing from {}", key, dict));
+    dict[at + key.len() + 1..].split(' ').next().unwrap()
+}
+
+/// Object number of an indirect reference `key N 0 R`
+fn reference(dict: &str, key: &str) -> u32 {
+    value(dict, key).trim_start_matches('[').parse().unwrap()
+}
+
+/// Contents of a `key [a b c d]` rectangle
+fn rect(dict: &str, key: &str) -> Vec<String> {
+    let at = dict.find(&format!("{} [", key)).unwrap() + key.len() + 2;
+    dict[at..at + dict[at..].find(']').unwrap()].split(' ').map(str::to_string).collect()
+}
+
+#[test]
+fn test_x1a_output_intent_and_identifiers() {
+    let asset = compile(print_block(ColorSpace::Cmyk, Some("Coated FOGRA39"), TransparencyPolicy::Flatten)).unwrap();
+    let bytes = press_pdf(&asset);
+    assert!(bytes.starts_with(b"%PDF-1.3\n"));
+    let pdf = Pdf::parse(&bytes);
+
+    let root = reference(&pdf.trailer, "/Root");
+    let catalog = pdf.dict(root);
+    let intent = pdf.dict(reference(&catalog, "/OutputIntents"));
+    assert_eq!(value(&intent, "/Type"), "/OutputIntent");
+    assert_eq!(value(&intent, "/S"), "/GTS_PDFX");
+    assert!(intent.contains("/OutputConditionIdentifier (Coated FOGRA39)"), "{}", intent);
+    let profile = reference(&intent, "/DestOutputProfile");
+    assert_eq!(value(&pdf.dict(profile), "/N"), "4");
+    assert_eq!(pdf.stream(profile), &profile_bytes(b"CMYK")[..]);
+
+    let info = pdf.dict(reference(&pdf.trailer, "/Info"));
+    for entry in ["/GTS_PDFXVersion (PDF/X-1:2001)", "/GTS_PDFXConformance (PDF/X-1a:2001)", "/Trapped /False", "/CreationDate (D:19700101000000Z)", "/Title (Test Icon)"] {
+        assert!(info.contains(entry), "{} missing from {}", entry, info);
+    }
+    let xmp = String::from_utf8(pdf.stream(reference(&catalog, "/Metadata")).to_vec()).unwrap();
+    assert!(xmp.contains("<pdfxid:GTS_PDFXVersion>PDF/X-1:2001</pdfxid:GTS_PDFXVersion>"), "{}", xmp);
+    assert!(xmp.contains("<pdfx:GTS_PDFXConformance>PDF/X-1a:2001</pdfx:GTS_PDFXConformance>"), "{}", xmp);
+
+    // The trailer id and the XMP document id are the same digest
+    let id = value(&pdf.trailer, "/ID").trim_start_matches("[<").trim_end_matches('>');
+    assert_eq!(id.len(), 32);
+    assert!(xmp.contains(&format!("<xmpMM:DocumentID>uuid:{}-", &id[..8])), "{}", xmp);
+
+    // Trim inside 9 px (9 pt at 72 dpi) of bleed; the image is CMYK
+    let pages = pdf.dict(reference(&catalog, "/Pages"));
+    let page = pdf.dict(reference(&pages, "/Kids"));
+    assert_eq!(rect(&page, "/MediaBox"), ["0", "0", "26", "26"]);
+    assert_eq!(rect(&page, "/TrimBox"), ["9", "9", "17", "17"]);
+    assert_eq!(rect(&page, "/BleedBox"), ["0", "0", "26", "26"]);
+    assert!(!bytes.windows(10).any(|w| w == b"/DeviceRGB"));
+
+    let manifest = serde_json::to_value(&asset).unwrap();
+    let press = manifest["exports"].as_array().unwrap().iter().find(|e| e["id"] == "press").unwrap();
+    assert_eq!(press["pdf_standard"], "x1a");
+
+    // Identifiers are deterministic
+    let again = compile(print_block(ColorSpace::Cmyk, Some("Coated FOGRA39"), TransparencyPolicy::Flatten)).unwrap();
+    assert_eq!(press_pdf(&again), bytes);
+}
+
+#[test]
+fn test_unmet_requirements_are_listed() {
+    let Err(PipelineError::ValidationFailed(message)) = compile(print_block(ColorSpace::Rgb, None, TransparencyPolicy::Flatten)) else {
+        panic!("compiled");
+    };
+    assert_eq!(
+        message,
+        "print: exports[press]: PDF/X-1a requirements not met: an output intent ICC profile (iccProfile); \
+         CMYK or grayscale color (colorSpace is RGB)",
+    );
+}
+
+#[test]
+fn test_transparency_policy() {
+    // The test template has no background, so the artwork, and the bleed
+    // mirrored from it, is transparent: all 26 x 26 pixels
+    let Err(PipelineError::ExportFailed(id, message)) = compile(print_block(ColorSpace::Cmyk, Some("Coated FOGRA39"), TransparencyPolicy::Error)) else {
+        panic!("compiled");
+    };
+    assert_eq!(id, "press");
+    assert!(message.starts_with("PDF/X-1a requirements not met: no transparency (676 partly transparent pixels"), "{}", message);
+
+    let asset = compile(print_block(ColorSpace::Cmyk, Some("Coated FOGRA39"), TransparencyPolicy::Flatten)).unwrap();
+    assert!(press_pdf(&asset).starts_with(b"%PDF-1.3"));
+}
+
+/// Writes a bare PDF with no PDF/X structure
+struct PlainPdfRenderer;
+
+impl Renderer for PlainPdfRenderer {
+    fn name(&self) -> &'static str { "plain-pdf" }
+
+    fn render(&self, _job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        Ok(b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n%%EOF\n".to_vec())
+    }
+}
+
+#[test]
+fn test_non_conformant_renderer_output_is_refused() {
+    let result = compile_with(
+        print_block(ColorSpace::Cmyk, Some("Coated FOGRA39"), TransparencyPolicy::Flatten),
+        |builder| builder.renderer(PlainPdfRenderer),
+    );
+    let Err(PipelineError::ExportFailed(id, message)) = result else {
+        panic!("compiled");
+    };
+    assert_eq!(id, "press");
+    assert!(message.starts_with("PDF/X-1a requirements not met: an output intent (/OutputIntents);"), "{}", message);
+    assert!(message.contains("a trim box (/TrimBox)"), "{}", message);
+}
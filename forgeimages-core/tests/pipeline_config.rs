@@ -0,0 +1,176 @@
+//! Pipeline configs: loading, validation, the effective config and its hash
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, ConfigError, HashAlgorithm, JobHashKey, PipelineConfig, SandboxMode,
+    config::{JobHashKeyConfig, Secret, REDACTED},
+    output::MANIFEST_FILE,
+    templates::TemplateRegistry,
+    verify::verify_manifest,
+};
+
+fn registry() -> TemplateRegistry {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    registry
+}
+
+fn invalid_field(config: &PipelineConfig) -> String {
+    match config.validate() {
+        Err(ConfigError::Invalid { field, .. }) => field,
+        other => panic!("expected an invalid field, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_toml_and_json_load_the_same_config() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("pipeline.toml"), r#"
+        normalize_source = true
+        sandbox = "enforced"
+        source_root = "sources"
+
+        [limits]
+        export_parallelism = 4
+        render_attempts = 3
+        retry_backoff_ms = 250
+    "#).unwrap();
+    fs::write(dir.path().join("pipeline.json"), r#"{
+        "normalize_source": true,
+        "sandbox": "enforced",
+        "source_root": "sources",
+        "limits": {"export_parallelism": 4, "render_attempts": 3, "retry_backoff_ms": 250}
+    }"#).unwrap();
+
+    let toml = PipelineConfig::load(&dir.path().join("pipeline.toml")).unwrap();
+    let json = PipelineConfig::load(&dir.path().join("pipeline.json")).unwrap();
+    assert_eq!(toml, json);
+    assert_eq!(toml.sandbox, SandboxMode::Enforced);
+    assert_eq!(toml.limits.render_attempts, 3);
+    // Relative paths are relative to the config file
+    assert_eq!(toml.source_root.as_deref(), Some(dir.path().join("sources").as_path()));
+    assert_eq!(PipelineConfig::from_toml("").unwrap(), PipelineConfig::default());
+}
+
+#[test]
+fn test_unreadable_configs_are_errors() {
+    let error = PipelineConfig::from_toml("renderer = \"placeholder\"\nworkers = 4\n").unwrap_err();
+    assert!(matches!(&error, ConfigError::Parse(message) if message.starts_with("line 2:") && message.contains("workers")), "{}", error);
+    assert!(matches!(PipelineConfig::from_json(r#"{"limits": {"threads": 2}}"#), Err(ConfigError::Parse(_))));
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("pipeline.yaml"), "").unwrap();
+    assert!(matches!(PipelineConfig::load(&dir.path().join("pipeline.yaml")), Err(ConfigError::Format)));
+    assert!(matches!(PipelineConfig::load(&dir.path().join("missing.toml")), Err(ConfigError::Io(_))));
+}
+
+#[test]
+fn test_validation_names_the_broken_setting() {
+    let mut config = PipelineConfig { renderer: "skia".to_string(), ..PipelineConfig::default() };
+    assert_eq!(invalid_field(&config), "renderer");
+    assert!(config.validate().unwrap_err().to_string().contains(r#"unknown renderer "skia" (known: placeholder)"#));
+
+    config = PipelineConfig::default();
+    config.limits.export_parallelism = 0;
+    assert_eq!(invalid_field(&config), "limits.export_parallelism");
+
+    config = PipelineConfig::default();
+    config.limits.render_attempts = 0;
+    assert_eq!(invalid_field(&config), "limits.render_attempts");
+    assert!(config.builder(registry()).is_err());
+
+    config = PipelineConfig::default();
+    config.signing.enabled = true;
+    let field = if cfg!(feature = "signing") { "signing.key_path" } else { "signing.enabled" };
+    assert_eq!(invalid_field(&config), field);
+
+    config = PipelineConfig::default();
+    config.job_hash_key = Some(JobHashKeyConfig { key_id: String::new(), secret: Secret::Value("s".to_string()) });
+    assert_eq!(invalid_field(&config), "job_hash_key.key_id");
+    PipelineConfig::default().validate().unwrap();
+}
+
+#[test]
+fn test_missing_files_fail_the_builder() {
+    let config = PipelineConfig::from_toml(r#"icc_profiles = { "Coated FOGRA39" = "/nonexistent/fogra39.icc" }"#).unwrap();
+    config.validate().unwrap();
+    match config.builder(registry()) {
+        Err(ConfigError::Invalid { field, .. }) => assert_eq!(field, "icc_profiles.Coated FOGRA39"),
+        _ => panic!("expected the missing profile to fail"),
+    }
+}
+
+#[test]
+fn test_effective_config_reports_the_pipeline_with_secrets_redacted() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = PipelineConfig {
+        deduplicate_exports: true,
+        emit_checksums: true,
+        source_root: Some(dir.path().to_path_buf()),
+        audit_log: Some(dir.path().join("audit.log")),
+        job_hash_key: Some(JobHashKeyConfig { key_id: "tenant-a".to_string(), secret: Secret::Value("tenant-a-secret".to_string()) }),
+        ..PipelineConfig::default()
+    };
+    config.limits.export_parallelism = 2;
+    let pipeline = config.builder(registry()).unwrap().build();
+
+    let effective = pipeline.config();
+    assert_eq!(effective, config.redacted());
+    assert_eq!(effective.job_hash_key.as_ref().unwrap().secret, Secret::Value(REDACTED.to_string()));
+    assert!(!serde_json::to_string(&effective).unwrap().contains("tenant-a-secret"));
+
+    // Settings changed on the builder afterwards are what the pipeline runs with
+    let overridden = config.builder(registry()).unwrap().deduplicate_exports(false).build();
+    assert!(!overridden.config().deduplicate_exports);
+
+    // A pipeline built without a config still reports itself
+    let keyed = CompilationPipeline::builder(registry()).job_hash_key(JobHashKey::new("tenant-b", "tenant-b-secret")).build();
+    let reported = keyed.config().job_hash_key.unwrap();
+    assert_eq!((reported.key_id.as_str(), reported.secret), ("tenant-b", Secret::Value(REDACTED.to_string())));
+    assert_eq!(CompilationPipeline::new(registry()).config(), PipelineConfig::default());
+}
+
+#[test]
+fn test_manifests_record_the_config_hash() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let default = CompilationPipeline::new(registry());
+    let asset = default.compile_asset(&request).unwrap();
+    assert_eq!(asset.config_hash, PipelineConfig::default().hash(HashAlgorithm::Sha256).unwrap());
+
+    let keyed = |secret: &str| PipelineConfig {
+        job_hash_key: Some(JobHashKeyConfig { key_id: "tenant-a".to_string(), secret: Secret::Value(secret.to_string()) }),
+        ..PipelineConfig::default()
+    };
+    // Secrets never reach the hash; settings do
+    assert_eq!(keyed("one").hash(HashAlgorithm::Sha256).unwrap(), keyed("two").hash(HashAlgorithm::Sha256).unwrap());
+    let normalizing = PipelineConfig { normalize_source: true, ..PipelineConfig::default() };
+    let other = normalizing.builder(registry()).unwrap().build().compile_asset(&request).unwrap();
+    assert_ne!(other.config_hash, asset.config_hash);
+
+    let dir = tempfile::tempdir().unwrap();
+    default.compile_to_dir(&request, dir.path()).unwrap();
+    let manifest = fs::read_to_string(dir.path().join(MANIFEST_FILE)).unwrap();
+    assert!(manifest.contains(&format!(r#""config_hash":"{}""#, asset.config_hash)));
+    verify_manifest(&dir.path().join(MANIFEST_FILE)).unwrap();
+}
+
+#[cfg(feature = "signing")]
+#[test]
+fn test_signing_key_is_read_from_the_config() {
+    use forgeimages_core::signing::SigningKey;
+
+    let dir = tempfile::tempdir().unwrap();
+    let key = SigningKey::from_seed([7; 32]);
+    fs::write(dir.path().join("signing.pem"), key.to_pkcs8_pem()).unwrap();
+    fs::write(dir.path().join("pipeline.toml"), "[signing]\nenabled = true\nkey_path = \"signing.pem\"\n").unwrap();
+
+    let config = PipelineConfig::load(&dir.path().join("pipeline.toml")).unwrap();
+    let effective = config.builder(registry()).unwrap().build().config();
+    assert!(effective.signing.enabled);
+    assert_eq!(effective.signing.key_path.as_deref(), Some(dir.path().join("signing.pem").as_path()));
+    assert_eq!(effective.signing.key_id, Some(key.verifying_key().key_id()));
+}
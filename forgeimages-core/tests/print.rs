@@ -0,0 +1,531 @@
+//! Print spec resolution through the compile pipeline
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    canonical_json,
+    CompilationPipeline, PipelineError, PrintAuthority, Renderer, RenderError, RenderJob,
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, PrintField, PrintSpec, TemplatePrint},
+    templates::{ExportFormat, ExportSpec, TemplateRegistry},
+    units::Length,
+};
+
+/// Writes the print dpi it was handed, or "none"
+struct PrintEchoRenderer;
+
+impl Renderer for PrintEchoRenderer {
+    fn name(&self) -> &'static str { "print-echo" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        Ok(match job.print {
+            Some(spec) => format!("{}", spec.dpi).into_bytes(),
+            None => b"none".to_vec(),
+        })
+    }
+}
+
+fn pipeline(print: Option<TemplatePrint>) -> CompilationPipeline {
+    pipeline_with(print, None)
+}
+
+/// Like `pipeline`, with a print block of its own on the "flyer" export
+fn pipeline_with(print: Option<TemplatePrint>, flyer: Option<TemplatePrint>) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(ExportSpec { print: flyer, ..export("flyer", [1024, 1024], ExportFormat::Pdf, true) });
+    template.exports.push(export("scan", [1024, 1024], ExportFormat::Tiff, true));
+    template.print = print;
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry).renderer(PrintEchoRenderer).deduplicate_exports(true).build()
+}
+
+/// CMYK at 300 dpi with 3mm bleed
+fn press_print() -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space: ColorSpace::Cmyk,
+        bleed: Length::mm(3.0),
+        allow_user_print_overrides: false,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+fn template_print(allow_user_print_overrides: bool) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 600,
+        color_space: ColorSpace::Cmyk,
+        bleed: Length::inches(0.25),
+        allow_user_print_overrides,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+fn decoded(asset: &forgeimages_core::CompiledAsset, id: &str) -> String {
+    let file = asset.exports.iter().find(|e| e.id == id).unwrap();
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_system_defaults_without_template_or_user_spec() {
+    let asset = pipeline(None).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.print, PrintSpec::default());
+    assert_eq!(asset.print.authority, PrintAuthority::System);
+}
+
+#[test]
+fn test_template_print_block_applies_to_print_exports_only() {
+    let asset = pipeline(Some(template_print(true)))
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+
+    assert_eq!(asset.print.authority, PrintAuthority::Template);
+    assert_eq!(asset.print.color_space, ColorSpace::Cmyk);
+    assert_eq!(decoded(&asset, "flyer"), "600");
+    assert_eq!(decoded(&asset, "scan"), "600");
+    assert_eq!(decoded(&asset, "master"), "none");
+}
+
+#[test]
+fn test_user_spec_overrides_template() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, ..PrintSpec::default() });
+
+    let asset = pipeline(Some(template_print(true))).compile_asset(&request).unwrap();
+    assert_eq!(asset.print.authority, PrintAuthority::User);
+    assert_eq!(asset.print.dpi, 150);
+    assert_eq!(decoded(&asset, "flyer"), "150");
+}
+
+#[test]
+fn test_user_spec_is_validated() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 5000, ..PrintSpec::default() });
+
+    let result = pipeline(None).compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("DPI")));
+}
+
+#[test]
+fn test_user_override_rejected_when_template_forbids_it() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec::default());
+
+    let result = pipeline(Some(template_print(false))).compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("print_override")));
+
+    // Even under warn, and even when the values match the template's
+    let mut template = create_test_template();
+    template.print = Some(template_print(false));
+    template.validation.failure_mode = forgeimages_core::templates::FailureMode::Warn;
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    request.print_spec = Some(template_print(false).spec());
+    let result = CompilationPipeline::new(registry).compile_asset(&request);
+    assert!(matches!(
+        result,
+        Err(PipelineError::ValidationFailed(msg)) if msg.contains("Template does not allow user print overrides (print)")
+    ));
+}
+
+/// `pipeline` under the Log failure mode, which keeps Info findings
+fn logging_pipeline(print: TemplatePrint) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("flyer", [1024, 1024], ExportFormat::Pdf, true));
+    template.print = Some(print);
+    template.validation.failure_mode = forgeimages_core::templates::FailureMode::Log;
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry).renderer(PrintEchoRenderer).build()
+}
+
+fn overrides_of(asset: &forgeimages_core::CompiledAsset) -> Vec<(&str, serde_json::Value, serde_json::Value)> {
+    asset.print_overrides.iter()
+        .map(|o| (o.field.as_str(), o.template_value.clone(), o.user_value.clone()))
+        .collect()
+}
+
+#[test]
+fn test_partial_override_records_only_the_user_field() {
+    // Color space and bleed are locked; the user repeats them and changes dpi
+    let block = TemplatePrint { locked_fields: vec![PrintField::ColorSpace, PrintField::Bleed], ..template_print(true) };
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, ..block.spec() });
+
+    let asset = logging_pipeline(block).compile_asset(&request).unwrap();
+    assert_eq!(overrides_of(&asset), vec![("dpi", serde_json::json!(600), serde_json::json!(150))]);
+    let info: Vec<_> = asset.validation.violations.iter().filter(|v| v.rule == "print_override").collect();
+    assert_eq!(info.len(), 1);
+    assert_eq!(info[0].severity, forgeimages_core::ViolationSeverity::Info);
+    assert_eq!(info[0].message, "User print spec overrides dpi");
+    assert_eq!((info[0].expected.as_deref(), info[0].actual.as_deref()), (Some("dpi 600"), Some("dpi 150")));
+}
+
+#[test]
+fn test_full_override_records_every_supplied_field() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Rgb, bleed: Length::inches(0.5), ..PrintSpec::default() });
+
+    let asset = logging_pipeline(template_print(true)).compile_asset(&request).unwrap();
+    assert_eq!(overrides_of(&asset), vec![
+        ("dpi", serde_json::json!(600), serde_json::json!(150)),
+        ("color_space", serde_json::json!("CMYK"), serde_json::json!("RGB")),
+        ("bleed_inches", serde_json::json!(0.25), serde_json::json!(0.5)),
+    ]);
+    let info = asset.validation.violations.iter().find(|v| v.rule == "print_override").unwrap();
+    assert_eq!(info.message, "User print spec overrides dpi, color_space, bleed_inches");
+
+    let manifest = serde_json::to_value(&asset).unwrap();
+    assert_eq!(manifest["print_overrides"][1], serde_json::json!({
+        "field": "color_space", "template_value": "CMYK", "user_value": "RGB",
+    }));
+
+    // Without a user spec there is nothing to record
+    let asset = logging_pipeline(template_print(true)).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert!(asset.print_overrides.is_empty());
+    assert!(serde_json::to_value(&asset).unwrap().get("print_overrides").is_none());
+    assert!(asset.validation.violations.iter().all(|v| v.rule != "print_override"));
+}
+
+#[test]
+fn test_export_print_block_stands_in_for_template_block() {
+    let asset = pipeline_with(Some(template_print(true)), Some(press_print()))
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    let print_of = |id: &str| asset.exports.iter().find(|e| e.id == id).unwrap().print.clone();
+
+    let flyer = print_of("flyer").unwrap();
+    assert_eq!((flyer.authority, flyer.dpi, flyer.color_space), (PrintAuthority::Template, 300, ColorSpace::Cmyk));
+    assert_eq!(flyer.bleed, Length::mm(3.0));
+    assert_eq!(decoded(&asset, "flyer"), "300");
+    assert_eq!(print_of("scan").unwrap().dpi, 600);
+    assert_eq!(decoded(&asset, "scan"), "600");
+    assert_eq!(print_of("master"), None);
+    // The template-level spec is still what the manifest reports overall
+    assert_eq!(asset.print.dpi, 600);
+}
+
+#[test]
+fn test_export_print_is_recorded_in_the_manifest() {
+    let asset = pipeline_with(None, Some(press_print()))
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&canonical_json(&asset).unwrap()).unwrap();
+    let exports = manifest["exports"].as_array().unwrap();
+
+    let flyer = exports.iter().find(|e| e["id"] == "flyer").unwrap();
+    assert_eq!(flyer["print"]["color_space"], "CMYK");
+    assert_eq!(flyer["print"]["dpi"], 300);
+    let scan = exports.iter().find(|e| e["id"] == "scan").unwrap();
+    assert_eq!(scan["print"]["authority"], "system");
+    let master = exports.iter().find(|e| e["id"] == "master").unwrap();
+    assert!(master.get("print").is_none());
+}
+
+#[test]
+fn test_exports_with_different_print_specs_are_not_deduplicated() {
+    let same = template_print(true);
+    let mut template = create_test_template();
+    template.exports.push(ExportSpec { print: Some(press_print()), ..export("a", [1024, 1024], ExportFormat::Pdf, true) });
+    template.exports.push(ExportSpec { print: Some(same.clone()), ..export("b", [1024, 1024], ExportFormat::Pdf, true) });
+    template.exports.push(ExportSpec { print: Some(same), ..export("c", [1024, 1024], ExportFormat::Pdf, true) });
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let pipeline = CompilationPipeline::builder(registry).renderer(PrintEchoRenderer).deduplicate_exports(true).build();
+
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    let deduplicated_from = |id: &str| asset.exports.iter().find(|e| e.id == id).unwrap().deduplicated_from.clone();
+    assert_eq!(deduplicated_from("b"), None);
+    assert_eq!(deduplicated_from("c").as_deref(), Some("b"));
+}
+
+#[test]
+fn test_export_block_can_forbid_user_overrides() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec::default());
+
+    let result = pipeline_with(Some(template_print(true)), Some(press_print())).compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("exports[flyer]")));
+}
+
+#[test]
+fn test_load_rejects_out_of_bounds_or_misplaced_print_blocks() {
+    let valid = serde_json::to_value(create_test_template()).unwrap();
+    let cases = [
+        ("/print", serde_json::json!({"dpi": 5000, "colorSpace": "RGB", "bleedInches": 0.1}), "DPI"),
+        ("/exports/0/print", serde_json::json!({"dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.1}), "exports[master].print"),
+    ];
+    for (pointer, print, expected) in cases {
+        let mut template = valid.clone();
+        let (parent, field) = pointer.rsplit_once('/').unwrap();
+        template.pointer_mut(parent).unwrap()[field] = print;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+        let Err(e) = TemplateRegistry::load_from_dir(dir.path()) else { panic!("{} loaded", pointer) };
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(expected), "{}", e);
+    }
+
+    // In bounds, on a print export
+    let mut template = valid;
+    template["exports"][0]["format"] = "pdf".into();
+    template["exports"][0]["print"] = serde_json::json!({"dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.1});
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+    let registry = TemplateRegistry::load_from_dir(dir.path()).unwrap();
+    assert_eq!(registry.get("test-icon").unwrap().exports[0].print.as_ref().unwrap().dpi, 300);
+}
+
+fn locked_color_space() -> TemplatePrint {
+    TemplatePrint { locked_fields: vec![PrintField::ColorSpace], ..template_print(true) }
+}
+
+#[test]
+fn test_user_overrides_unlocked_fields_only() {
+    // Asks for the template's own color space, so nothing is refused
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Cmyk, bleed: Length::inches(0.5), ..PrintSpec::default() });
+
+    let asset = pipeline(Some(locked_color_space())).compile_asset(&request).unwrap();
+    assert_eq!(asset.print.authority, PrintAuthority::User);
+    assert_eq!((asset.print.dpi, asset.print.bleed), (150, Length::inches(0.5)));
+    assert_eq!(asset.print.color_space, ColorSpace::Cmyk);
+    assert_eq!(decoded(&asset, "flyer"), "150");
+}
+
+#[test]
+fn test_locked_field_override_is_a_violation() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Rgb, ..PrintSpec::default() });
+
+    let result = pipeline(Some(locked_color_space())).compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("overrides of color_space")));
+}
+
+#[test]
+fn test_warn_mode_keeps_template_value_for_locked_fields() {
+    let mut template = create_test_template();
+    template.exports.push(export("flyer", [1024, 1024], ExportFormat::Pdf, true));
+    template.print = Some(locked_color_space());
+    template.validation.failure_mode = forgeimages_core::templates::FailureMode::Warn;
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let pipeline = CompilationPipeline::builder(registry).renderer(PrintEchoRenderer).build();
+
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Rgb, ..PrintSpec::default() });
+    let asset = pipeline.compile_asset(&request).unwrap();
+    assert!(asset.validation.violations.iter().any(|v| v.rule == "print_override"));
+    assert_eq!(asset.print.dpi, 150);
+    assert_eq!(asset.print.color_space, ColorSpace::Cmyk);
+
+    // Out-of-bounds values never get through, whatever the failure mode
+    request.print_spec = Some(PrintSpec { dpi: 5000, ..PrintSpec::default() });
+    assert!(matches!(pipeline.compile_asset(&request), Err(PipelineError::ValidationFailed(msg)) if msg.contains("DPI")));
+}
+
+/// A web-only block: RGB itself, and users may not force CMYK
+fn rgb_only() -> TemplatePrint {
+    TemplatePrint {
+        color_space: ColorSpace::Rgb,
+        allowed_user_color_spaces: vec![ColorSpace::Rgb, ColorSpace::Grayscale],
+        ..template_print(true)
+    }
+}
+
+#[test]
+fn test_user_color_space_outside_the_allowlist_is_an_error() {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Cmyk, ..PrintSpec::default() });
+    let Err(PipelineError::ValidationFailed(message)) = pipeline(Some(rgb_only())).compile_asset(&request) else {
+        panic!("compiled");
+    };
+    assert!(message.starts_with("print_override: Template does not allow user color space Cmyk (allowed: Rgb, Grayscale);"), "{}", message);
+
+    // A listed space is an ordinary override
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Grayscale, ..PrintSpec::default() });
+    let asset = pipeline(Some(rgb_only())).compile_asset(&request).unwrap();
+    assert_eq!((asset.print.authority, asset.print.color_space), (PrintAuthority::User, ColorSpace::Grayscale));
+}
+
+#[test]
+fn test_allowlist_does_not_restrict_the_template_itself() {
+    // The template's CMYK stands even though users may only ask for RGB
+    let block = TemplatePrint { allowed_user_color_spaces: vec![ColorSpace::Rgb], ..template_print(true) };
+    let asset = pipeline(Some(block.clone())).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!((asset.print.authority, asset.print.color_space), (PrintAuthority::Template, ColorSpace::Cmyk));
+
+    // An export block's own list applies to that export only; asking for
+    // the block's own CMYK would refuse nothing
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Grayscale, ..PrintSpec::default() });
+    let Err(PipelineError::ValidationFailed(message)) = pipeline_with(Some(template_print(true)), Some(block)).compile_asset(&request) else {
+        panic!("compiled");
+    };
+    assert!(message.starts_with("print_override: exports[flyer]: Template does not allow user color space Grayscale (allowed: Rgb);"), "{}", message);
+}
+
+#[test]
+fn test_locked_fields_load_from_template_json() {
+    let mut template = serde_json::to_value(create_test_template()).unwrap();
+    template["print"] = serde_json::json!({
+        "dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.125, "lockedFields": ["colorSpace", "bleedInches"],
+    });
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+    let registry = TemplateRegistry::load_from_dir(dir.path()).unwrap();
+    let print = registry.get("test-icon").unwrap().print.clone().unwrap();
+    assert_eq!(print.locked_fields, vec![PrintField::ColorSpace, PrintField::Bleed]);
+    assert!(print.allowed_user_color_spaces.is_empty());
+
+    template["print"]["allowedUserColorSpaces"] = serde_json::json!(["RGB"]);
+    fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+    let registry = TemplateRegistry::load_from_dir(dir.path()).unwrap();
+    let print = registry.get("test-icon").unwrap().print.clone().unwrap();
+    assert_eq!(print.allowed_user_color_spaces, vec![ColorSpace::Rgb]);
+}
+
+#[test]
+fn test_bleed_reads_as_a_length_and_writes_as_inches() {
+    let mut template = serde_json::to_value(create_test_template()).unwrap();
+    template["print"] = serde_json::json!({"dpi": 300, "colorSpace": "CMYK", "bleed": "3mm", "lockedFields": ["bleed"]});
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+    let registry = TemplateRegistry::load_from_dir(dir.path()).unwrap();
+    let loaded = registry.get("test-icon").unwrap();
+    let print = loaded.print.clone().unwrap();
+    assert_eq!((print.bleed, print.locked_fields.clone()), (Length::mm(3.0), vec![PrintField::Bleed]));
+
+    // Written the way templates always were, so content hashes do not move
+    let json = serde_json::to_value(loaded).unwrap();
+    assert_eq!(json["print"]["bleedInches"].as_f64(), Some(Length::mm(3.0).as_inches()));
+    assert!(json["print"].get("bleed").is_none());
+}
+
+#[test]
+fn test_physical_presets_resolve_on_load() {
+    let load = |card: serde_json::Value| {
+        let mut template = serde_json::to_value(create_test_template()).unwrap();
+        template["print"] = serde_json::json!({"dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.125});
+        template["exports"].as_array_mut().unwrap().push(card);
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+        TemplateRegistry::load_from_dir(dir.path())
+    };
+    let card = |extra: serde_json::Value| {
+        let mut card = serde_json::json!({
+            "id": "card", "description": "Business card", "format": "pdf", "required": true,
+        });
+        card.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        card
+    };
+
+    // At the template's 300 dpi, and at an export block's own dpi
+    let registry = load(card(serde_json::json!({"physicalPreset": "us-business-card"}))).unwrap();
+    assert_eq!(registry.get("test-icon").unwrap().exports[1].size, [1050, 600]);
+    let registry = load(card(serde_json::json!({
+        "physicalPreset": "us-business-card",
+        "print": {"dpi": 600, "colorSpace": "CMYK", "bleedInches": 0.125},
+    })))
+    .unwrap();
+    assert_eq!(registry.get("test-icon").unwrap().exports[1].size, [2100, 1200]);
+
+    // A matching explicit size is fine; a conflicting one is not
+    assert!(load(card(serde_json::json!({"physicalPreset": "us-business-card", "size": [1050, 600]}))).is_ok());
+    let cases = [
+        (serde_json::json!({"physicalPreset": "us-business-card", "size": [1000, 600]}), "conflicts"),
+        (serde_json::json!({"physicalPreset": "a10"}), "unknown physicalPreset 'a10'"),
+        (serde_json::json!({}), "size or physicalPreset"),
+    ];
+    for (extra, expected) in cases {
+        let Err(e) = load(card(extra)) else { panic!("loaded") };
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(expected), "{}", e);
+    }
+}
+
+#[test]
+fn test_print_plan_matches_the_compile() {
+    // The locked color space stays the template's
+    let user = PrintSpec { dpi: 150, color_space: ColorSpace::Cmyk, bleed: Length::mm(3.0), ..PrintSpec::default() };
+    let pipeline = pipeline(Some(locked_color_space()));
+    let plan = pipeline.print_plan("test-icon", Some(&user)).unwrap();
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(user);
+    let asset = pipeline.compile_asset(&request).unwrap();
+
+    assert!(plan.validation.valid);
+    let print = plan.print.unwrap();
+    assert_eq!(print.spec, asset.print);
+    assert_eq!(print.overrides, asset.print_overrides);
+    assert_eq!(print.provenance["dpi"], PrintAuthority::User);
+    assert_eq!(print.provenance["color_space"], PrintAuthority::Template);
+
+    // Print exports only, each sized as the compile sized it
+    assert_eq!(plan.exports.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["flyer", "scan"]);
+    for planned in &plan.exports {
+        let file = asset.exports.iter().find(|e| e.id == planned.id).unwrap();
+        assert_eq!((Some(&planned.print.spec), planned.size), (file.print.as_ref(), file.size));
+        assert_eq!(planned.trim, [1024, 1024]);
+    }
+}
+
+#[test]
+fn test_compile_plan_matches_the_compile() {
+    let pipeline = pipeline(Some(locked_color_space()));
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 150, color_space: ColorSpace::Cmyk, bleed: Length::mm(3.0), ..PrintSpec::default() });
+    let plan = pipeline.compile_plan(&request).unwrap();
+    let asset = pipeline.compile_asset(&request).unwrap();
+
+    assert!(plan.validation.valid);
+    assert_eq!(plan.job_hash, asset.job_hash);
+    assert_eq!(plan.print.unwrap().spec, asset.print);
+    // Every export, named and sized as the manifest records it
+    assert_eq!(plan.exports.len(), asset.exports.len());
+    for (planned, file) in plan.exports.iter().zip(&asset.exports) {
+        assert_eq!((&planned.id, &planned.filename, &planned.format), (&file.id, &file.filename, &file.format));
+        assert_eq!((planned.size, planned.trim, planned.print.as_ref()), (file.size, file.trim, file.print.as_ref()));
+    }
+    assert!(plan.exports.iter().any(|planned| planned.trim.is_some()));
+
+    // Invalid requests stop at validation, as the compile does
+    let plan = pipeline.compile_plan(&compile_request("test-icon", 100, 100)).unwrap();
+    assert!(!plan.validation.valid && plan.print.is_none() && plan.exports.is_empty());
+}
+
+#[test]
+fn test_print_plan_reports_refused_overrides() {
+    let user = PrintSpec { color_space: ColorSpace::Rgb, ..PrintSpec::default() };
+    let plan = pipeline(Some(locked_color_space())).print_plan("test-icon", Some(&user)).unwrap();
+    assert!(!plan.validation.valid);
+    assert!(plan.validation.violations.iter().any(|v| v.message.contains("overrides of color_space")));
+    assert!(plan.print.is_none() && plan.exports.is_empty());
+
+    assert!(matches!(pipeline(None).print_plan("missing", None), Err(PipelineError::TemplateNotFound(_))));
+}
@@ -0,0 +1,109 @@
+//! Named print profiles: loading, template references with overrides,
+//! missing profiles, effective values in the manifest
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, PrintAuthority,
+    print::{BleedFill, ColorSpace, Marks, ProfileRegistry},
+    templates::TemplateRegistry,
+    units::Length,
+};
+
+fn write_profiles(dir: &Path) {
+    fs::write(dir.join("offset-coated.printprofile.json"), serde_json::json!({
+        "name": "offset-coated", "dpi": 300, "colorSpace": "CMYK", "bleed": "3mm", "marks": true,
+    }).to_string()).unwrap();
+    fs::write(dir.join("newsprint.printprofile.json"), serde_json::json!({
+        "name": "newsprint", "dpi": 200, "colorSpace": "GRAYSCALE", "bleedInches": 0.0,
+    }).to_string()).unwrap();
+}
+
+/// Template JSON with `print` as the template block and a Tiff "flyer"
+/// export carrying `flyer` as its own block
+fn write_template(dir: &Path, print: serde_json::Value, flyer: Option<serde_json::Value>) {
+    let mut template = serde_json::to_value(create_test_template()).unwrap();
+    template["print"] = print;
+    let mut export = serde_json::json!({
+        "id": "flyer", "description": "Flyer", "size": [64, 64], "format": "tiff", "required": true,
+    });
+    if let Some(flyer) = flyer {
+        export["print"] = flyer;
+    }
+    template["exports"].as_array_mut().unwrap().push(export);
+    fs::write(dir.join("test-icon.json"), template.to_string()).unwrap();
+}
+
+#[test]
+fn test_profiles_load_from_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    write_profiles(dir.path());
+    fs::write(dir.path().join("notes.json"), "{}").unwrap();
+
+    let profiles = ProfileRegistry::load_from_dir(dir.path()).unwrap();
+    assert_eq!(profiles.names().collect::<Vec<_>>(), vec!["newsprint", "offset-coated"]);
+    let offset = profiles.get("offset-coated").unwrap();
+    assert_eq!((offset.dpi, offset.bleed, offset.marks), (300, Length::mm(3.0), Marks::legacy()));
+    assert_eq!(offset.bleed_fill, BleedFill::Mirror);
+    assert_eq!(profiles.get("newsprint").unwrap().color_space, ColorSpace::Grayscale);
+
+    // Profile files that do not parse or are out of bounds are errors
+    fs::write(dir.path().join("bad.printprofile.json"), r#"{"name": "bad", "dpi": 5000, "colorSpace": "RGB", "bleed": "0mm"}"#).unwrap();
+    let e = ProfileRegistry::load_from_dir(dir.path()).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+    assert!(e.to_string().contains("DPI must be between"), "{}", e);
+}
+
+#[test]
+fn test_template_fields_override_the_profile() {
+    let profiles_dir = tempfile::tempdir().unwrap();
+    write_profiles(profiles_dir.path());
+    let profiles = ProfileRegistry::load_from_dir(profiles_dir.path()).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    write_template(
+        dir.path(),
+        serde_json::json!({"profile": "offset-coated", "dpi": 600}),
+        Some(serde_json::json!({"profile": "newsprint", "bleed": "0.125in"})),
+    );
+    let registry = TemplateRegistry::load_from_dir_with_profiles(dir.path(), &profiles).unwrap();
+    let template = registry.get("test-icon").unwrap();
+    let print = template.print.clone().unwrap();
+    assert_eq!(print.profile.as_deref(), Some("offset-coated"));
+    assert_eq!((print.dpi, print.color_space, print.bleed, print.marks), (600, ColorSpace::Cmyk, Length::mm(3.0), Marks::legacy()));
+    let flyer = template.exports.iter().find(|e| e.id == "flyer").unwrap().print.clone().unwrap();
+    assert_eq!((flyer.dpi, flyer.color_space, flyer.bleed), (200, ColorSpace::Grayscale, Length::inches(0.125)));
+
+    // The manifest holds the effective values, so it does not depend on the profile files
+    let asset = CompilationPipeline::new(registry).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.print_profile.as_deref(), Some("offset-coated"));
+    let manifest = serde_json::to_value(&asset).unwrap();
+    assert_eq!(manifest["print"], serde_json::json!({
+        "authority": "template", "dpi": 600, "color_space": "CMYK", "bleed_inches": Length::mm(3.0).as_inches(),
+    }));
+    let flyer = asset.exports.iter().find(|e| e.id == "flyer").unwrap();
+    assert_eq!(flyer.print.as_ref().map(|p| (p.authority, p.dpi)), Some((PrintAuthority::Template, 200)));
+}
+
+#[test]
+fn test_missing_profile_is_a_load_error() {
+    let dir = tempfile::tempdir().unwrap();
+    write_template(dir.path(), serde_json::json!({"dpi": 300, "colorSpace": "RGB", "bleed": "0mm"}), Some(serde_json::json!({"profile": "gloss"})));
+    write_profiles(dir.path());
+    let profiles = ProfileRegistry::load_from_dir(dir.path()).unwrap();
+
+    for registry in [TemplateRegistry::load_from_dir(dir.path()), TemplateRegistry::load_from_dir_with_profiles(dir.path(), &profiles)] {
+        let Err(e) = registry else { panic!("loaded") };
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("exports[flyer].print: unknown print profile 'gloss'"), "{}", e);
+    }
+
+    // Profiles in the templates directory are not mistaken for templates
+    write_template(dir.path(), serde_json::json!({"profile": "newsprint"}), None);
+    let registry = TemplateRegistry::load_from_dir_with_profiles(dir.path(), &profiles).unwrap();
+    assert_eq!(registry.list().len(), 1);
+}
@@ -0,0 +1,111 @@
+//! Reproducing manifests from the request they record: byte-exact
+//! rebuilds, and named causes when a rebuild cannot match
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, CompileRequest,
+    pipeline::SourceKind,
+    reproduce::{reproduce, ReproduceError},
+    templates::TemplateRegistry,
+};
+
+const SVG: &[u8] = br##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><rect width="10" height="10" fill="#E4002B"/></svg>"##;
+
+fn registry() -> TemplateRegistry {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    registry
+}
+
+fn request(source: &[u8]) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_data = Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, source));
+    request.seed = Some(7);
+    request.prompt = Some("red square".to_string());
+    request
+}
+
+/// The compile of `request(SVG)`, and the source written where reproduce can read it
+fn compiled(dir: &Path) -> CompiledAsset {
+    fs::write(dir.join("logo.svg"), SVG).unwrap();
+    CompilationPipeline::new(registry()).compile_asset(&request(SVG)).unwrap()
+}
+
+#[test]
+fn test_manifests_record_their_request() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compiled(dir.path());
+    let record = asset.request.as_ref().unwrap();
+    assert_eq!((record.source, record.seed, record.prompt.as_deref()), (Some(SourceKind::Inline), Some(7), Some("red square")));
+    assert_eq!(record.asset_input.width, 1024);
+}
+
+#[test]
+fn test_a_recompile_reproduces_every_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compiled(dir.path());
+    let reproduction = reproduce(registry(), &asset, Some(&dir.path().join("logo.svg"))).unwrap();
+    assert!(reproduction.reproduced, "{:?}", reproduction);
+    assert_eq!(reproduction.manifest_hash, asset.manifest_hash);
+    assert!(reproduction.diff.identical && reproduction.template_changed.is_none() && reproduction.source_changed.is_none());
+}
+
+#[test]
+fn test_path_sources_reproduce_as_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("logo.svg"), SVG).unwrap();
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_path = Some("logo.svg".into());
+    let asset = CompilationPipeline::builder(registry()).source_root(dir.path()).build().compile_asset(&request).unwrap();
+    assert_eq!(asset.request.as_ref().unwrap().source, Some(SourceKind::Path));
+
+    // Only the content identifies it, so another name reproduces too
+    fs::write(dir.path().join("renamed.svg"), SVG).unwrap();
+    assert!(reproduce(registry(), &asset, Some(&dir.path().join("renamed.svg"))).unwrap().reproduced);
+}
+
+#[test]
+fn test_a_changed_template_is_named() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compiled(dir.path());
+    let mut template = create_test_template();
+    template.description = "Edited after the compile".to_string();
+    let mut edited = TemplateRegistry::new();
+    edited.register(template);
+
+    let reproduction = reproduce(edited, &asset, Some(&dir.path().join("logo.svg"))).unwrap();
+    assert!(!reproduction.reproduced);
+    let change = reproduction.template_changed.unwrap();
+    assert_eq!((change.template_id.as_str(), change.recorded_hash.as_str()), ("test-icon", asset.template_hash.as_str()));
+    assert_ne!(change.loaded_hash, change.recorded_hash);
+    assert_eq!(reproduction.diff.fields.iter().map(|f| f.field).collect::<Vec<_>>(), ["template_hash"]);
+}
+
+#[test]
+fn test_a_different_source_is_named() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compiled(dir.path());
+    fs::write(dir.path().join("other.svg"), b"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 10 10\"/>").unwrap();
+
+    let reproduction = reproduce(registry(), &asset, Some(&dir.path().join("other.svg"))).unwrap();
+    assert!(!reproduction.reproduced);
+    assert_eq!(reproduction.source_changed.unwrap().recorded_hash, asset.source_hash);
+    assert!(reproduction.diff.fields.iter().any(|f| f.field == "job_hash"));
+}
+
+#[test]
+fn test_what_cannot_be_reproduced() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = compiled(dir.path());
+    assert!(matches!(reproduce(registry(), &asset, None), Err(ReproduceError::SourceRequired)));
+    assert!(matches!(reproduce(registry(), &asset, Some(&dir.path().join("missing.svg"))), Err(ReproduceError::Source(..))));
+
+    let mut old = asset.clone();
+    old.request = None;
+    assert!(matches!(reproduce(registry(), &old, None), Err(ReproduceError::NoRequest)));
+}
@@ -0,0 +1,96 @@
+//! Retry policy for transient render failures
+
+mod common;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, PipelineError, Renderer, RenderError, RenderJob, RetryPolicy,
+    render::PlaceholderRenderer,
+    templates::{ExportFormat, TemplateRegistry},
+};
+
+/// Fails the first `failures` renders of the "flaky" export
+struct FlakyRenderer {
+    failures: u32,
+    retryable: bool,
+    calls: AtomicU32,
+}
+
+impl FlakyRenderer {
+    fn new(failures: u32, retryable: bool) -> Self {
+        Self { failures, retryable, calls: AtomicU32::new(0) }
+    }
+}
+
+impl Renderer for FlakyRenderer {
+    fn name(&self) -> &'static str { "flaky" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        if job.spec.id == "flaky" && self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(if self.retryable {
+                RenderError::retryable("GPU context lost")
+            } else {
+                RenderError::new("unsupported feature")
+            });
+        }
+        PlaceholderRenderer.render(job)
+    }
+}
+
+fn pipeline(renderer: impl Renderer + 'static, max_attempts: u32) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.exports.push(export("flaky", [32, 32], ExportFormat::Png, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry)
+        .renderer(renderer)
+        .retry_policy(RetryPolicy { max_attempts, backoff: Duration::from_millis(1) })
+        .build()
+}
+
+#[test]
+fn test_transient_failures_are_retried_and_output_is_unchanged() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let flaky = pipeline(FlakyRenderer::new(2, true), 3).compile_asset(&request).unwrap();
+    let clean = pipeline(FlakyRenderer::new(0, true), 3).compile_asset(&request).unwrap();
+
+    assert_eq!(flaky.metrics.attempts("flaky"), Some(3));
+    assert_eq!(flaky.metrics.attempts("master"), Some(1));
+    assert_eq!(clean.metrics.attempts("flaky"), Some(1));
+
+    let hashes = |a: &forgeimages_core::CompiledAsset| a.exports.iter().map(|e| e.hash.clone()).collect::<Vec<_>>();
+    assert_eq!(hashes(&flaky), hashes(&clean));
+    assert_eq!(flaky.job_hash, clean.job_hash);
+}
+
+#[test]
+fn test_metrics_stay_out_of_the_manifest() {
+    let request = compile_request("test-icon", 1024, 1024);
+    let asset = pipeline(FlakyRenderer::new(2, true), 3).compile_asset(&request).unwrap();
+    let json = serde_json::to_value(&asset).unwrap();
+    assert!(json.get("metrics").is_none());
+}
+
+#[test]
+fn test_retries_are_bounded() {
+    let result = pipeline(FlakyRenderer::new(5, true), 3)
+        .compile_asset(&compile_request("test-icon", 1024, 1024));
+    assert!(matches!(result, Err(PipelineError::ExportFailed(id, _)) if id == "flaky"));
+}
+
+#[test]
+fn test_deterministic_errors_are_not_retried() {
+    let renderer = FlakyRenderer::new(1, false);
+    let result = pipeline(renderer, 5).compile_asset(&compile_request("test-icon", 1024, 1024));
+    assert!(matches!(result, Err(PipelineError::ExportFailed(..))));
+}
+
+#[test]
+fn test_validation_failures_are_not_retried() {
+    let result = pipeline(FlakyRenderer::new(0, true), 5)
+        .compile_asset(&compile_request("test-icon", 100, 100));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(_))));
+}
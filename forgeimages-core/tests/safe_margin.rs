@@ -0,0 +1,154 @@
+//! Safe margins on print exports: intrusion per edge, bleed-marked art,
+//! guide proofs
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, CompileRequest, PipelineError,
+    bleed::{GUIDE_BLEED, GUIDE_SAFE, GUIDE_TRIM},
+    pdf::TransparencyPolicy,
+    print::{BleedFill, ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, FailureMode, TemplateRegistry},
+    units::Length,
+    validation::ViolationSeverity,
+};
+
+/// 72 dpi with 0.125 in (9 px) of bleed and a 0.25 in (18 px) safe margin
+fn print_block() -> TemplatePrint {
+    TemplatePrint {
+        dpi: 72,
+        color_space: ColorSpace::Rgb,
+        bleed: Length::inches(0.125),
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: BleedFill::Color { color: "#FFFFFF".to_string() },
+        marks: Marks::default(),
+        safe_margin: Some(Length::inches(0.25)),
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors: vec![],
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// A 2 x 2 in card from a 144 x 144 SVG master
+fn compile(svg_body: &str, failure_mode: FailureMode, guides: bool) -> Result<CompiledAsset, PipelineError> {
+    let mut template = create_test_template();
+    template.validation.failure_mode = failure_mode;
+    template.print = Some(print_block());
+    template.exports.push(export("card", [144, 144], ExportFormat::Tiff, true));
+    if guides {
+        let mut proof = export("card-guides", [144, 144], ExportFormat::Tiff, false);
+        proof.guides = true;
+        template.exports.push(proof);
+    }
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry).compile_asset(&request(svg_body))
+}
+
+fn request(svg_body: &str) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_data = Some(base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 144 144">{}</svg>"#, svg_body),
+    ));
+    request
+}
+
+#[test]
+fn test_content_inside_the_safe_area_passes() {
+    let asset = compile(r#"<rect x="20" y="20" width="104" height="104"/>"#, FailureMode::Log, false).unwrap();
+    assert!(asset.validation.violations.iter().all(|v| v.rule != "safe_margin"));
+}
+
+#[test]
+fn test_intrusion_is_reported_per_edge() {
+    // 30 px wide, 64 of the 144 rows: 44.4% of the 18 px left band
+    let body = r#"<rect x="0" y="40" width="30" height="64"/>"#;
+    let asset = compile(body, FailureMode::Log, false).unwrap();
+    let found: Vec<_> = asset.validation.violations.iter().filter(|v| v.rule == "safe_margin").collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].severity, ViolationSeverity::Error);
+    assert!(found[0].message.contains("safe margin of export card (left)"), "{}", found[0].message);
+    assert_eq!(found[0].actual.as_deref(), Some("left 44.4%, top 0.0%, right 0.0%, bottom 0.0%"));
+
+    let Err(PipelineError::ValidationFailed(message)) = compile(body, FailureMode::Block, false) else {
+        panic!("compiled");
+    };
+    assert!(message.contains("safe_margin"), "{}", message);
+}
+
+#[test]
+fn test_backgrounds_and_bleed_art_are_ignored() {
+    let body = r##"<rect width="144" height="144" fill="#F0F0F0"/>
+        <g data-fi-bleed=""><rect x="-9" y="-9" width="162" height="20" fill="#E4002B"/></g>
+        <rect x="20" y="20" width="104" height="104"/>"##;
+    let asset = compile(body, FailureMode::Log, false).unwrap();
+    assert!(asset.validation.violations.iter().all(|v| v.rule != "safe_margin"));
+}
+
+#[test]
+fn test_guide_proof_outlines_bleed_trim_and_safe_area() {
+    let asset = compile(r#"<rect x="20" y="20" width="104" height="104"/>"#, FailureMode::Block, true).unwrap();
+    let proof = asset.exports.iter().find(|e| e.id == "card-guides").unwrap();
+    let card = asset.exports.iter().find(|e| e.id == "card").unwrap();
+    assert!(proof.proof && !proof.deliverable);
+    assert!(card.deliverable);
+    assert_ne!(proof.data_base64, card.data_base64);
+
+    let tiff = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &proof.data_base64).unwrap();
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = |wanted: u16| (0..u16_at(ifd) as usize)
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&pos| u16_at(pos) == wanted)
+        .map(|pos| u32_at(pos + 8))
+        .unwrap();
+    let (width, offset) = (tag(256), tag(273) as usize);
+    assert_eq!(width, 162);
+    let pixel = |x: u32, y: u32| {
+        let i = offset + ((y * width + x) * 3) as usize;
+        [tiff[i], tiff[i + 1], tiff[i + 2], 255]
+    };
+
+    // One-pixel outlines: bleed at the file edge, trim 9 px in, safe 18 px further
+    assert_eq!(pixel(0, 80), GUIDE_BLEED);
+    assert_eq!(pixel(9, 80), GUIDE_TRIM);
+    assert_eq!(pixel(27, 80), GUIDE_SAFE);
+    assert_eq!(pixel(80, 152), GUIDE_TRIM);
+    assert_eq!(pixel(80, 134), GUIDE_SAFE);
+}
+
+#[test]
+fn test_guides_are_refused_where_they_cannot_be_drawn() {
+    let mut template = create_test_template();
+    template.print = Some(print_block());
+    let mut web = export("web", [144, 144], ExportFormat::Png, false);
+    web.guides = true;
+    template.exports.push(web);
+    let message = template.validate_print().unwrap_err();
+    assert!(message.contains("exports[web].guides"), "{}", message);
+
+    let mut template = create_test_template();
+    template.print = Some(print_block());
+    let mut required = export("card", [144, 144], ExportFormat::Tiff, true);
+    required.guides = true;
+    template.exports.push(required);
+    let message = template.validate_print().unwrap_err();
+    assert!(message.contains("a proof cannot be a required export"), "{}", message);
+}
+
+#[test]
+fn test_safe_margin_round_trips_as_a_length() {
+    let json = serde_json::to_value(print_block()).unwrap();
+    assert_eq!(json["safeMargin"], "6.35mm");
+    let mut negative = print_block();
+    negative.safe_margin = Some(Length::inches(-0.1));
+    assert!(negative.validate().unwrap_err().contains("safeMargin"));
+}
@@ -0,0 +1,115 @@
+//! Sandboxed compiles touch nothing beyond their declared inputs
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use common::{compile_request, create_test_template, tiny_font};
+use forgeimages_core::{
+    CompilationPipeline, HashAlgorithm, PipelineError, Renderer, RenderError, RenderJob, SandboxMode,
+    hashing::sha256_hex,
+    render::PlaceholderRenderer,
+    templates::{FontRef, TemplateRegistry},
+};
+
+/// Reads a file from disk on every render and never claims to be sandbox-safe
+struct DiskRenderer {
+    path: PathBuf,
+    renders: Arc<AtomicU32>,
+}
+
+impl Renderer for DiskRenderer {
+    fn name(&self) -> &'static str { "disk" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        self.renders.fetch_add(1, Ordering::SeqCst);
+        fs::read(&self.path).map_err(|e| RenderError::new(e.to_string()))?;
+        PlaceholderRenderer.render(job)
+    }
+}
+
+fn registry() -> TemplateRegistry {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    registry
+}
+
+fn sandboxed(registry: TemplateRegistry) -> CompilationPipeline {
+    CompilationPipeline::builder(registry).sandbox(SandboxMode::Enforced).build()
+}
+
+fn font_registry(dir: &std::path::Path) -> TemplateRegistry {
+    let font = tiny_font();
+    fs::create_dir_all(dir.join("fonts")).unwrap();
+    fs::write(dir.join("fonts/tiny.ttf"), &font).unwrap();
+
+    let mut template = create_test_template();
+    template.font = Some(FontRef { path: "fonts/tiny.ttf".to_string(), sha256: sha256_hex(&font) });
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry.set_asset_dir(dir);
+    registry
+}
+
+#[test]
+fn test_placeholder_renderer_compiles_in_sandbox() {
+    let asset = sandboxed(registry()).compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.exports.len(), 1);
+}
+
+#[test]
+fn test_renderer_without_sandbox_declaration_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let secret = dir.path().join("secret.txt");
+    fs::write(&secret, "not for templates").unwrap();
+    let request = compile_request("test-icon", 1024, 1024);
+
+    let renders = Arc::new(AtomicU32::new(0));
+    let pipeline = CompilationPipeline::builder(registry())
+        .renderer(DiskRenderer { path: secret.clone(), renders: Arc::clone(&renders) })
+        .sandbox(SandboxMode::Enforced)
+        .build();
+    let err = pipeline.compile_asset(&request).unwrap_err();
+    assert!(matches!(&err, PipelineError::SandboxViolation(m) if m.contains("renderer disk")), "{}", err);
+    assert_eq!(renders.load(Ordering::SeqCst), 0);
+
+    // The same renderer is allowed outside the sandbox
+    let pipeline = CompilationPipeline::builder(registry())
+        .renderer(DiskRenderer { path: secret, renders: Arc::clone(&renders) })
+        .build();
+    assert!(pipeline.compile_asset(&request).is_ok());
+    assert_eq!(renders.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_source_path_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("logo.svg"), "<svg/>").unwrap();
+    let pipeline = CompilationPipeline::builder(registry())
+        .source_root(dir.path())
+        .sandbox(SandboxMode::Enforced)
+        .build();
+
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_path = Some("logo.svg".into());
+    let err = pipeline.compile_asset(&request).unwrap_err();
+    assert!(matches!(&err, PipelineError::SandboxViolation(m) if m.contains("logo.svg")), "{}", err);
+}
+
+#[test]
+fn test_template_assets_must_be_registered_in_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let request = compile_request("test-icon", 1024, 1024);
+
+    let err = sandboxed(font_registry(dir.path())).compile_asset(&request).unwrap_err();
+    assert!(matches!(&err, PipelineError::SandboxViolation(m) if m.contains("fonts/tiny.ttf")), "{}", err);
+
+    let mut registry = font_registry(dir.path());
+    registry.register_asset("fonts/tiny.ttf", tiny_font());
+    fs::remove_dir_all(dir.path().join("fonts")).unwrap();
+    let asset = sandboxed(registry).compile_asset(&request).unwrap();
+    assert_eq!(asset.font_hash, Some(HashAlgorithm::Sha256.digest(&tiny_font())));
+}
@@ -0,0 +1,132 @@
+//! Upscaling policy for exports larger than a raster source
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, PipelineError,
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, PrintSpec, TemplatePrint},
+    templates::{ExportFormat, ScalingDecision, ScalingPolicy, TemplateRegistry},
+    units::Length,
+    validation::ViolationSeverity,
+};
+
+fn pipeline(policy: ScalingPolicy, configure: impl FnOnce(&mut forgeimages_core::Template)) -> CompilationPipeline {
+    let mut template = create_test_template();
+    template.scaling_policy = policy;
+    template.validation.rules.resolution.min_width = 64;
+    template.validation.rules.resolution.min_height = 64;
+    template.exports = vec![
+        export("small", [128, 128], ExportFormat::Png, true),
+        export("large", [512, 512], ExportFormat::Png, true),
+    ];
+    configure(&mut template);
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry)
+}
+
+fn svg_request() -> CompileRequest {
+    let mut request = compile_request("test-icon", 256, 256);
+    request.asset_input.format = Some("svg".to_string());
+    request
+}
+
+#[test]
+fn test_forbid_upscale_rejects_raster_source() {
+    let result = pipeline(ScalingPolicy::ForbidUpscale, |_| {})
+        .compile_asset(&compile_request("test-icon", 256, 256));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("upscale_forbidden")));
+}
+
+#[test]
+fn test_svg_sources_are_exempt() {
+    let asset = pipeline(ScalingPolicy::ForbidUpscale, |_| {})
+        .compile_asset(&svg_request())
+        .unwrap();
+    assert!(asset.exports.iter().all(|e| e.scaling.decision == ScalingDecision::VectorSource));
+
+    let mut request = compile_request("test-icon", 256, 256);
+    request.source_data = Some(base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        br#"<svg xmlns="http://www.w3.org/2000/svg"/>"#,
+    ));
+    assert!(pipeline(ScalingPolicy::ForbidUpscale, |_| {}).compile_asset(&request).is_ok());
+}
+
+#[test]
+fn test_warning_policy_records_decision() {
+    let asset = pipeline(ScalingPolicy::AllowUpscaleWithWarning, |_| {})
+        .compile_asset(&compile_request("test-icon", 256, 256))
+        .unwrap();
+
+    let large = asset.exports.iter().find(|e| e.id == "large").unwrap();
+    assert_eq!(large.scaling.decision, ScalingDecision::Upscaled);
+    assert_eq!(large.scaling.policy, ScalingPolicy::AllowUpscaleWithWarning);
+    let small = asset.exports.iter().find(|e| e.id == "small").unwrap();
+    assert_eq!(small.scaling.decision, ScalingDecision::WithinSource);
+
+}
+
+#[test]
+fn test_warning_policy_emits_warning_violation() {
+    // Warn failure mode keeps warnings in the result (Block drops them)
+    let pipeline = pipeline(ScalingPolicy::AllowUpscaleWithWarning, |t| {
+        t.validation.failure_mode = forgeimages_core::templates::FailureMode::Warn;
+    });
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 256, 256)).unwrap();
+    let warning = asset.validation.violations.iter().find(|v| v.rule == "upscale").unwrap();
+    assert_eq!(warning.severity, ViolationSeverity::Warning);
+
+    let asset = self::pipeline(ScalingPolicy::Allow, |t| {
+        t.validation.failure_mode = forgeimages_core::templates::FailureMode::Warn;
+    }).compile_asset(&compile_request("test-icon", 256, 256)).unwrap();
+    assert!(asset.validation.violations.is_empty());
+}
+
+#[test]
+fn test_per_export_policy_overrides_template() {
+    let pipeline = pipeline(ScalingPolicy::ForbidUpscale, |t| {
+        t.exports[1].scaling_policy = Some(ScalingPolicy::Allow);
+    });
+    let asset = pipeline.compile_asset(&compile_request("test-icon", 256, 256)).unwrap();
+    assert_eq!(asset.exports[1].scaling.policy, ScalingPolicy::Allow);
+    assert_eq!(asset.exports[1].scaling.decision, ScalingDecision::Upscaled);
+}
+
+#[test]
+fn test_print_dpi_does_not_change_pixel_based_decision() {
+    // Print exports declare pixel sizes; the dpi only describes physical
+    // output, so a 512px flyer upscales a 256px source at any dpi.
+    let configure = |t: &mut forgeimages_core::Template| {
+        t.exports.pop();
+        t.exports.push(export("flyer", [512, 512], ExportFormat::Pdf, true));
+        t.print = Some(TemplatePrint {
+            dpi: 72,
+            color_space: ColorSpace::Cmyk,
+            bleed: Length::ZERO,
+            allow_user_print_overrides: true,
+            locked_fields: vec![],
+            allowed_user_color_spaces: vec![],
+            bleed_fill: Default::default(),
+            marks: Marks::default(),
+            safe_margin: None,
+            icc_profile: None,
+            true_grayscale_vectors: false,
+            spot_colors: vec![],
+            pdf_standard: None,
+            transparency_policy: TransparencyPolicy::Flatten,
+            profile: None,
+        });
+    };
+    let result = pipeline(ScalingPolicy::ForbidUpscale, configure)
+        .compile_asset(&compile_request("test-icon", 256, 256));
+    assert!(matches!(result, Err(PipelineError::ValidationFailed(msg)) if msg.contains("upscale_forbidden")));
+
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.print_spec = Some(PrintSpec { dpi: 1200, ..PrintSpec::default() });
+    let asset = pipeline(ScalingPolicy::ForbidUpscale, configure).compile_asset(&request).unwrap();
+    let flyer = asset.exports.iter().find(|e| e.id == "flyer").unwrap();
+    assert_eq!(flyer.scaling.decision, ScalingDecision::WithinSource);
+}
@@ -0,0 +1,72 @@
+//! forgeimages-cli schema: the emitted JSON Schemas accept what the engine
+//! reads and writes, under the same serde names
+
+#![cfg(feature = "schema")]
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use forgeimages_core::{CompilationPipeline, Template, templates::TemplateRegistry};
+use serde_json::Value;
+
+fn schema(name: &str) -> jsonschema::Validator {
+    let output = Command::new(env!("CARGO_BIN_EXE_forgeimages-cli")).args(["schema", name]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let schema: Value = serde_json::from_slice(&output.stdout).unwrap();
+    jsonschema::validator_for(&schema).unwrap()
+}
+
+fn assert_valid(validator: &jsonschema::Validator, instance: &Value) {
+    let errors: Vec<_> = validator.iter_errors(instance).map(|e| format!("{} at {}", e, e.instance_path())).collect();
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn test_shipped_template_round_trips_through_the_schema() {
+    let validator = schema("Template");
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates/pwa-icon.json");
+    let file: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_valid(&validator, &file);
+
+    let template: Template = serde_json::from_value(file).unwrap();
+    assert_valid(&validator, &serde_json::to_value(&template).unwrap());
+}
+
+#[test]
+fn test_template_schema_uses_the_camel_case_names() {
+    let validator = schema("Template");
+    let mut template = serde_json::to_value(common::create_test_template()).unwrap();
+    template["print"] = serde_json::json!({
+        "dpi": 300, "colorSpace": "CMYK", "bleedInches": "3mm", "marks": true, "lockedFields": ["colorSpace"],
+    });
+    assert_valid(&validator, &template);
+    template["print"]["bleedInches"] = 0.125.into();
+    assert_valid(&validator, &template);
+
+    // The Rust names are not what templates are read by
+    let mut snake = template.clone();
+    let asset_class = snake.as_object_mut().unwrap().remove("assetClass").unwrap();
+    snake["asset_class"] = asset_class;
+    assert!(!validator.is_valid(&snake));
+    template["print"]["bleedInches"] = "3 furlongs".into();
+    assert!(!validator.is_valid(&template));
+}
+
+#[test]
+fn test_requests_and_manifests_match_their_schemas() {
+    let request = serde_json::json!({
+        "template_id": "test-icon",
+        "asset_input": {"width": 1024, "height": 1024, "format": "svg"},
+        "seed": "18446744073709551615",
+    });
+    assert_valid(&schema("CompileRequest"), &request);
+    assert_valid(&schema("AssetInput"), &request["asset_input"]);
+    assert!(!schema("AssetInput").is_valid(&serde_json::json!({"width": "wide"})));
+
+    let mut registry = TemplateRegistry::new();
+    registry.register(common::create_test_template());
+    let asset = CompilationPipeline::new(registry).compile_asset(&serde_json::from_value(request).unwrap()).unwrap();
+    assert_valid(&schema("CompiledAsset"), &serde_json::to_value(&asset).unwrap());
+}
@@ -0,0 +1,177 @@
+//! forgeimages-cli serve: the HTTP API end to end, over a real socket
+
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use serde_json::Value;
+
+const PAYLOAD: &str = r#"{"template_id":"pwa-icon","asset_input":{"width":1024,"height":1024}}"#;
+
+/// A running server, killed on drop
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(extra: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_forgeimages-cli"))
+            .arg("--templates-dir")
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("templates"))
+            .args(["serve", "--addr", "127.0.0.1:0"])
+            .args(extra)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+        let listening: Value = serde_json::from_str(&line).unwrap();
+        Self { child, addr: listening["listening"].as_str().unwrap().to_string() }
+    }
+
+    /// Status and JSON body of one request
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let (status, _, body) = self.exchange(method, path, "application/json", body.as_bytes());
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// Status, Content-Type and raw body of one request
+    fn exchange(&self, method: &str, path: &str, content_type: &str, body: &[u8]) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method, path, self.addr, content_type, body.len(),
+        ).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        let content_type = head.lines()
+            .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-type")))
+            .map_or(String::new(), |(_, value)| value.trim().to_string());
+        (status, content_type, response[split + 4..].to_vec())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_health_and_templates() {
+    let server = Server::start(&[]);
+    let (status, health) = server.request("GET", "/healthz", "");
+    assert_eq!(status, 200);
+    assert_eq!(health["engine_version"], forgeimages_core::ENGINE_VERSION);
+    assert_eq!(health["template_count"], 1);
+
+    let (status, templates) = server.request("GET", "/templates", "");
+    assert_eq!(status, 200);
+    assert_eq!(templates[0]["id"], "pwa-icon");
+    let (status, template) = server.request("GET", "/templates/pwa-icon", "");
+    assert_eq!((status, &template["id"]), (200, &Value::from("pwa-icon")));
+    let (status, error) = server.request("GET", "/templates/missing", "");
+    assert_eq!((status, &error["error_kind"]), (404, &Value::from("template_not_found")));
+}
+
+#[test]
+fn test_config_reports_the_pipeline_config() {
+    let temp = tempfile::tempdir().unwrap();
+    let config = temp.path().join("pipeline.json");
+    std::fs::write(&config, r#"{"deduplicate_exports": true, "job_hash_key": {"key_id": "tenant-a", "secret": {"value": "hunter2"}}}"#).unwrap();
+    let server = Server::start(&["--pipeline-config", config.to_str().unwrap()]);
+
+    let (status, body) = server.request("GET", "/config", "");
+    assert_eq!(status, 200);
+    assert_eq!(body["config"]["deduplicate_exports"], true);
+    assert_eq!(body["config"]["job_hash_key"]["secret"]["value"], "<redacted>");
+    assert!(!body.to_string().contains("hunter2"));
+
+    let (_, compiled) = server.request("POST", "/compile", PAYLOAD);
+    assert_eq!(compiled["config_hash"], body["config_hash"]);
+}
+
+#[test]
+fn test_validate() {
+    let server = Server::start(&[]);
+    let (status, result) = server.request("POST", "/validate/pwa-icon", r#"{"width":1024,"height":1024}"#);
+    assert_eq!((status, &result["valid"]), (200, &Value::Bool(true)));
+
+    let (status, result) = server.request("POST", "/validate/pwa-icon", r#"{"width":100,"height":100}"#);
+    assert_eq!(status, 422);
+    assert_eq!(result["valid"], false);
+    assert!(!result["violations"].as_array().unwrap().is_empty());
+
+    let (status, error) = server.request("POST", "/validate/pwa-icon", r#"{"width":1,"width":2,"height":1}"#);
+    assert_eq!((status, &error["error_kind"]), (400, &Value::from("invalid_payload")));
+}
+
+#[test]
+fn test_compile() {
+    let server = Server::start(&[]);
+    let (status, asset) = server.request("POST", "/compile", PAYLOAD);
+    assert_eq!(status, 200);
+    assert!(asset["exports"][0]["data_base64"].is_string());
+
+    let (status, slim) = server.request("POST", "/compile?include_data=false", PAYLOAD);
+    assert_eq!(status, 200);
+    assert!(slim["exports"][0].get("data_base64").is_none());
+    assert_eq!(slim["exports"][0]["hash"], asset["exports"][0]["hash"]);
+    assert_eq!(slim["job_hash"], asset["job_hash"]);
+
+    let small = r#"{"template_id":"pwa-icon","asset_input":{"width":100,"height":100}}"#;
+    let (status, error) = server.request("POST", "/compile", small);
+    assert_eq!(status, 422);
+    assert_eq!(error["error_kind"], "validation_failed");
+    assert_eq!(error["validation"]["valid"], false);
+}
+
+#[test]
+fn test_body_limit() {
+    let server = Server::start(&["--max-body-bytes", "16"]);
+    let (status, _) = server.request("POST", "/compile", PAYLOAD);
+    assert_eq!(status, 413);
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_msgpack_bodies() {
+    use forgeimages_core::msgpack::{parse_strict_msgpack, to_msgpack};
+
+    // Fixed clock, so both compiles write the same manifest
+    let server = Server::start(&["--timestamp", "2026-01-01T00:00:00Z"]);
+    let (_, json) = server.request("POST", "/compile", PAYLOAD);
+    let request = to_msgpack(&serde_json::from_str::<Value>(PAYLOAD).unwrap()).unwrap();
+    let (status, content_type, body) = server.exchange("POST", "/compile", "application/msgpack", &request);
+    assert_eq!((status, content_type.as_str()), (200, "application/msgpack"));
+    assert_eq!(parse_strict_msgpack(&body).unwrap(), json);
+
+    let input = to_msgpack(&serde_json::json!({"width": 100, "height": 100})).unwrap();
+    let (status, content_type, body) = server.exchange("POST", "/validate/pwa-icon", "application/msgpack", &input);
+    assert_eq!((status, content_type.as_str()), (422, "application/msgpack"));
+    assert_eq!(parse_strict_msgpack(&body).unwrap()["valid"], false);
+
+    // Errors stay JSON
+    let (status, content_type, body) = server.exchange("POST", "/compile", "application/msgpack", PAYLOAD.as_bytes());
+    assert_eq!((status, content_type.as_str()), (400, "application/json"));
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["error_kind"], "invalid_payload");
+}
+
+#[cfg(not(feature = "msgpack"))]
+#[test]
+fn test_msgpack_needs_the_feature() {
+    let server = Server::start(&[]);
+    let (status, _, body) = server.exchange("POST", "/compile", "application/msgpack", &[0x80]);
+    assert_eq!(status, 415);
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["error_kind"], "unsupported_media_type");
+}
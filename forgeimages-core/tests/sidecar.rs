@@ -0,0 +1,106 @@
+//! Sidecar manifests written by compile_to_dir
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_pipeline};
+use forgeimages_core::{
+    hashing::sha256_hex,
+    output::{MANIFEST_FILE, MANIFEST_HASH_FILE, SIGNATURE_FILE},
+    verify::{verify_manifest, VerifyError},
+};
+
+#[test]
+fn test_writes_exports_and_manifest_sidecars() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = create_pipeline()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+
+    assert!(dir.path().join("master.svg").exists());
+    let manifest = fs::read_to_string(dir.path().join(MANIFEST_FILE)).unwrap();
+    assert_eq!(manifest, forgeimages_core::canonical_json(&asset).unwrap());
+    let sidecar = fs::read_to_string(dir.path().join(MANIFEST_HASH_FILE)).unwrap();
+    assert_eq!(sidecar, format!("{}  manifest.json\n", sha256_hex(manifest.as_bytes())));
+    assert!(!dir.path().join(SIGNATURE_FILE).exists());
+    assert!(asset.signer.is_none());
+
+    assert_eq!(verify_manifest(&dir.path().join(MANIFEST_FILE)).unwrap(), asset.manifest_hash);
+
+    // The sidecar names manifest.json; a reformatted copy beside it is checked on its hash alone
+    let copy = dir.path().join("manifest.pretty.json");
+    fs::write(&copy, serde_json::to_string_pretty(&asset).unwrap()).unwrap();
+    assert_eq!(verify_manifest(&copy).unwrap(), asset.manifest_hash);
+}
+
+#[test]
+fn test_edited_manifest_fails_verification() {
+    let dir = tempfile::tempdir().unwrap();
+    create_pipeline()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+    let path = dir.path().join(MANIFEST_FILE);
+    let edited = fs::read_to_string(&path).unwrap().replace("test-icon", "evil-icon");
+    fs::write(&path, &edited).unwrap();
+
+    assert!(matches!(verify_manifest(&path), Err(VerifyError::FileHashMismatch)));
+
+    // Rewriting the sidecar too still trips the manifest hash
+    fs::write(dir.path().join(MANIFEST_HASH_FILE), format!("{}  manifest.json\n", sha256_hex(edited.as_bytes()))).unwrap();
+    assert!(matches!(verify_manifest(&path), Err(VerifyError::ManifestHashMismatch(..))));
+}
+
+#[cfg(feature = "signing")]
+mod signed {
+    use super::*;
+    use forgeimages_core::{
+        CompilationPipeline,
+        signing::{SigningConfig, SigningKey, ALGORITHM},
+        templates::TemplateRegistry,
+        verify::verify_signature,
+    };
+
+    fn signed_pipeline(seed: u8) -> CompilationPipeline {
+        let mut registry = TemplateRegistry::new();
+        registry.register(common::create_test_template());
+        CompilationPipeline::builder(registry)
+            .signing(SigningConfig { key: SigningKey::from_seed([seed; 32]), key_id: "build-2026".to_string() })
+            .build()
+    }
+
+    #[test]
+    fn test_signed_manifest_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let asset = signed_pipeline(1)
+            .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+            .unwrap();
+
+        let signer = asset.signer.as_ref().unwrap();
+        assert_eq!(signer.key_id, "build-2026");
+        assert_eq!(signer.algorithm, ALGORITHM);
+
+        let public = SigningKey::from_seed([1; 32]).verifying_key();
+        verify_signature(&dir.path().join(MANIFEST_FILE), &public).unwrap();
+
+        let manifest = fs::read_to_string(dir.path().join(MANIFEST_FILE)).unwrap();
+        let signature = fs::read_to_string(dir.path().join(SIGNATURE_FILE)).unwrap();
+        assert!(!manifest.contains(signature.trim()));
+    }
+
+    #[test]
+    fn test_wrong_key_or_missing_signature_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        signed_pipeline(1)
+            .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+            .unwrap();
+        let manifest = dir.path().join(MANIFEST_FILE);
+
+        let other = SigningKey::from_seed([2; 32]).verifying_key();
+        assert!(matches!(verify_signature(&manifest, &other), Err(VerifyError::SignatureInvalid)));
+
+        fs::remove_file(dir.path().join(SIGNATURE_FILE)).unwrap();
+        let public = SigningKey::from_seed([1; 32]).verifying_key();
+        assert!(matches!(verify_signature(&manifest, &public), Err(VerifyError::SignatureMissing(_))));
+    }
+}
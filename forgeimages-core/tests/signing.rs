@@ -0,0 +1,76 @@
+//! Detached manifest signatures for in-memory assets
+
+#![cfg(feature = "signing")]
+
+mod common;
+
+use base64::Engine;
+use common::{compile_request, create_pipeline};
+use forgeimages_core::{
+    CompiledAsset,
+    signing::{self, sign_manifest, SigningKey, ALGORITHM},
+    verify::VerifyError,
+};
+
+fn compiled() -> CompiledAsset {
+    create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap()
+}
+
+fn key() -> SigningKey {
+    SigningKey::from_seed([7; 32])
+}
+
+#[test]
+fn test_signature_verifies_and_carries_no_key_material() {
+    let asset = compiled();
+    let key = key();
+    let signature = sign_manifest(&asset, &key);
+
+    assert_eq!(signature.algorithm, ALGORITHM);
+    assert_eq!(signature.key_id, key.verifying_key().key_id());
+    assert_eq!(signature.signed_hash, asset.manifest_hash);
+    signing::verify(&asset, &signature, &key.verifying_key()).unwrap();
+
+    let serialized = serde_json::to_string(&signature).unwrap();
+    let public = base64::engine::general_purpose::STANDARD.encode(key.verifying_key().to_bytes());
+    assert!(!serialized.contains(&public));
+}
+
+#[test]
+fn test_flipped_export_byte_fails_at_hash_stage() {
+    let mut asset = compiled();
+    let signature = sign_manifest(&asset, &key());
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut data = engine.decode(&asset.exports[0].data_base64).unwrap();
+    data[0] ^= 0x01;
+    asset.exports[0].data_base64 = engine.encode(&data);
+
+    assert!(matches!(
+        signing::verify(&asset, &signature, &key().verifying_key()),
+        Err(VerifyError::ExportHashMismatch(_)),
+    ));
+}
+
+#[test]
+fn test_edited_manifest_or_foreign_signature_fails() {
+    let asset = compiled();
+    let signature = sign_manifest(&asset, &key());
+
+    let mut edited = asset.clone();
+    edited.template_version = "9.9.9".to_string();
+    assert!(matches!(
+        signing::verify(&edited, &signature, &key().verifying_key()),
+        Err(VerifyError::ManifestHashMismatch(..)),
+    ));
+
+    // A valid signature over some other manifest
+    let other = sign_manifest(&compiled(), &key());
+    assert!(matches!(
+        signing::verify(&asset, &other, &key().verifying_key()),
+        Err(VerifyError::ManifestHashMismatch(..)),
+    ));
+
+    let stranger = SigningKey::from_seed([8; 32]).verifying_key();
+    assert!(matches!(signing::verify(&asset, &signature, &stranger), Err(VerifyError::SignatureInvalid)));
+}
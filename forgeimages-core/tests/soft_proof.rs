@@ -0,0 +1,134 @@
+//! Soft-proof exports: CMYK round trip, manifest flags, never required
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template, export, stored_png};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, EncodingProfile, Renderer, RenderError, RenderJob,
+    background::{BackgroundGenerator, PatternStyle},
+    color::{simulate_press, CmykConverter, NaiveCmyk},
+    encoding::{PngCompression, PngFilter},
+    render::PlaceholderRenderer,
+    templates::{ExportFormat, ExportSpec, Template, TemplateRegistry},
+};
+
+/// Pure green and blue checker, outside the press gamut; "web" is the
+/// plain render, "proof" the same artwork soft-proofed
+fn template() -> Template {
+    let mut template = create_test_template();
+    template.background_generator = BackgroundGenerator::Pattern {
+        style: PatternStyle::Checker,
+        palette: vec!["#00FF00".to_string(), "#0000FF".to_string()],
+    };
+    template.exports.push(export("web", [8, 8], ExportFormat::Png, true));
+    template.exports.push(ExportSpec { soft_proof: true, ..export("proof", [8, 8], ExportFormat::Png, false) });
+    template
+}
+
+fn compile(template: Template, renderer: impl Renderer + 'static) -> CompiledAsset {
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(7);
+    CompilationPipeline::builder(registry)
+        .renderer(renderer)
+        .deduplicate_exports(true)
+        // Unfiltered, stored PNG data can be read back without an inflater
+        .encoding(EncodingProfile { png_filter: PngFilter::None, png_compression: PngCompression::Stored })
+        .build()
+        .compile_asset(&request)
+        .unwrap()
+}
+
+/// RGBA pixels of a PNG written unfiltered and stored
+fn png_pixels(asset: &CompiledAsset, id: &str) -> Vec<[u8; 4]> {
+    let file = asset.exports.iter().find(|e| e.id == id).unwrap();
+    let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap();
+    let (_, raw) = stored_png(&data);
+    // Rows of (filter byte, 8 RGBA pixels)
+    raw.chunks_exact(1 + 8 * 4)
+        .flat_map(|row| row[1..].chunks_exact(4).map(|px| [px[0], px[1], px[2], px[3]]).collect::<Vec<_>>())
+        .collect()
+}
+
+#[test]
+fn test_proof_differs_from_the_rgb_render_for_out_of_gamut_colors() {
+    let asset = compile(template(), PlaceholderRenderer);
+    let web = png_pixels(&asset, "web");
+    let proof = png_pixels(&asset, "proof");
+    assert_eq!((web.len(), proof.len()), (64, 64));
+
+    for (plain, proofed) in web.iter().zip(&proof) {
+        let rgb = [plain[0], plain[1], plain[2]];
+        let [r, g, b] = simulate_press(NaiveCmyk.convert(rgb));
+        assert_eq!(*proofed, [r, g, b, 255]);
+        assert_ne!(plain, proofed);
+    }
+    // Green prints as a duller green, blue as a duller violet-blue
+    assert!(proof.contains(&[0, 165, 0, 255]), "{:?}", proof);
+    assert!(web.contains(&[0, 255, 0, 255]));
+}
+
+#[test]
+fn test_manifest_flags_proofs_as_not_deliverable() {
+    let asset = compile(template(), PlaceholderRenderer);
+    let proof = asset.exports.iter().find(|e| e.id == "proof").unwrap();
+    assert!(proof.proof && !proof.deliverable);
+    assert_eq!(proof.deduplicated_from, None);
+    let web = asset.exports.iter().find(|e| e.id == "web").unwrap();
+    assert!(!web.proof && web.deliverable);
+
+    let json = serde_json::to_value(&asset).unwrap();
+    let exports = json["exports"].as_array().unwrap();
+    let proof = exports.iter().find(|e| e["id"] == "proof").unwrap();
+    assert_eq!((&proof["proof"], &proof["deliverable"]), (&serde_json::json!(true), &serde_json::json!(false)));
+    let web = exports.iter().find(|e| e["id"] == "web").unwrap();
+    assert!(web.get("proof").is_none() && web.get("deliverable").is_none());
+}
+
+/// Fails every soft proof, renders everything else as the placeholder
+struct ProofFails;
+
+impl Renderer for ProofFails {
+    fn name(&self) -> &'static str { "proof-fails" }
+
+    fn render(&self, job: &RenderJob<'_>) -> Result<Vec<u8>, RenderError> {
+        match job.proof {
+            Some(_) => Err(RenderError::new("no proofing today")),
+            None => PlaceholderRenderer.render(job),
+        }
+    }
+}
+
+#[test]
+fn test_proofs_never_satisfy_or_block_required_exports() {
+    // Even marked required (which loading refuses), a failed proof is optional
+    let mut template = template();
+    template.exports.iter_mut().find(|e| e.id == "proof").unwrap().required = true;
+    let asset = compile(template, ProofFails);
+    assert_eq!(asset.export_errors.iter().map(|e| e.export_id.as_str()).collect::<Vec<_>>(), vec!["proof"]);
+    assert!(asset.exports.iter().all(|e| e.deliverable));
+
+    let load = |proof: serde_json::Value| {
+        let mut template = serde_json::to_value(create_test_template()).unwrap();
+        template["exports"].as_array_mut().unwrap().push(proof);
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+        TemplateRegistry::load_from_dir(dir.path())
+    };
+    let proof = |format: &str, required: bool| serde_json::json!({
+        "id": "proof", "description": "Soft proof", "size": [64, 64],
+        "format": format, "required": required, "softProof": true,
+    });
+    assert!(load(proof("png", false)).is_ok());
+    for (proof, expected) in [
+        (proof("png", true), "a proof cannot be a required export"),
+        (proof("tiff", false), "proofs are Png exports, not Tiff"),
+    ] {
+        let Err(e) = load(proof) else { panic!("loaded") };
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(expected), "{}", e);
+    }
+}
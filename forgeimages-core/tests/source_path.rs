@@ -0,0 +1,107 @@
+//! Compiling from source files under an allowlisted root
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, HashAlgorithm, PipelineError,
+    templates::TemplateRegistry,
+};
+
+const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"/>"#;
+
+fn pipeline(root: &Path) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(create_test_template());
+    CompilationPipeline::builder(registry).source_root(root).build()
+}
+
+fn path_request(path: impl Into<PathBuf>) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_path = Some(path.into());
+    request
+}
+
+#[test]
+fn test_compiles_from_file_and_records_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("logo.svg"), SVG).unwrap();
+
+    let asset = pipeline(dir.path()).compile_asset(&path_request("logo.svg")).unwrap();
+    assert_eq!(asset.source_hash, Some(HashAlgorithm::Sha256.digest(SVG)));
+}
+
+#[test]
+fn test_job_hash_ignores_filesystem_layout() {
+    let a = tempfile::tempdir().unwrap();
+    let b = tempfile::tempdir().unwrap();
+    fs::write(a.path().join("logo.svg"), SVG).unwrap();
+    fs::create_dir(b.path().join("nested")).unwrap();
+    fs::write(b.path().join("nested/other-name.svg"), SVG).unwrap();
+
+    let first = pipeline(a.path()).compile_asset(&path_request("logo.svg")).unwrap();
+    let second = pipeline(b.path()).compile_asset(&path_request("nested/other-name.svg")).unwrap();
+    assert_eq!(first.job_hash, second.job_hash);
+
+    fs::write(a.path().join("changed.svg"), b"<svg/>").unwrap();
+    let changed = pipeline(a.path()).compile_asset(&path_request("changed.svg")).unwrap();
+    assert_ne!(first.job_hash, changed.job_hash);
+}
+
+#[test]
+fn test_source_data_and_path_are_exclusive() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("logo.svg"), SVG).unwrap();
+    let mut request = path_request("logo.svg");
+    request.source_data = Some("PHN2Zy8+".to_string());
+
+    let result = pipeline(dir.path()).compile_asset(&request);
+    assert!(matches!(result, Err(PipelineError::InvalidSource(msg)) if msg.contains("mutually exclusive")));
+}
+
+#[test]
+fn test_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let result = pipeline(dir.path()).compile_asset(&path_request("absent.svg"));
+    assert!(matches!(result, Err(PipelineError::InvalidSource(_))));
+}
+
+#[test]
+fn test_unreadable_file() {
+    // A directory exists and resolves but cannot be read as a file
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("folder.svg")).unwrap();
+    let result = pipeline(dir.path()).compile_asset(&path_request("folder.svg"));
+    assert!(matches!(result, Err(PipelineError::InvalidSource(_))));
+}
+
+#[test]
+fn test_path_escape_attempts_rejected() {
+    let outer = tempfile::tempdir().unwrap();
+    let root = outer.path().join("root");
+    fs::create_dir(&root).unwrap();
+    let secret = outer.path().join("secret.svg");
+    fs::write(&secret, SVG).unwrap();
+    let pipeline = pipeline(&root);
+
+    for attempt in [PathBuf::from("../secret.svg"), secret.clone()] {
+        let result = pipeline.compile_asset(&path_request(attempt));
+        assert!(matches!(result, Err(PipelineError::SourceNotAllowed(_))));
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&secret, root.join("link.svg")).unwrap();
+        let result = pipeline.compile_asset(&path_request("link.svg"));
+        assert!(matches!(result, Err(PipelineError::SourceNotAllowed(_))));
+    }
+}
+
+#[test]
+fn test_path_requests_need_a_source_root() {
+    let result = common::create_pipeline().compile_asset(&path_request("logo.svg"));
+    assert!(matches!(result, Err(PipelineError::SourceNotAllowed(_))));
+}
@@ -0,0 +1,154 @@
+//! Spot color mappings: exact CMYK for brand colors, manifest record,
+//! unmapped source colors
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, CompiledAsset, CompileRequest,
+    background::{BackgroundGenerator, PatternStyle},
+    color::{CmykConverter, NaiveCmyk, SpotColorMapping, SpotColorUsage},
+    pdf::TransparencyPolicy,
+    print::{ColorSpace, Marks, TemplatePrint},
+    templates::{ExportFormat, FailureMode, TemplateRegistry},
+    units::Length,
+    validation::ViolationSeverity,
+};
+
+const RED: [u8; 3] = [0xE4, 0x00, 0x2B];
+const NAVY: [u8; 3] = [0x00, 0x20, 0x5B];
+const RED_INK: [u8; 4] = [0, 255, 196, 8];
+const NAVY_INK: [u8; 4] = [255, 173, 0, 112];
+
+fn mapping(name: &str, rgb_hex: &str, cmyk: [u8; 4]) -> SpotColorMapping {
+    SpotColorMapping { name: name.to_string(), rgb_hex: rgb_hex.to_string(), cmyk }
+}
+
+fn press(spot_colors: Vec<SpotColorMapping>) -> TemplatePrint {
+    TemplatePrint {
+        dpi: 300,
+        color_space: ColorSpace::Cmyk,
+        bleed: Length::ZERO,
+        allow_user_print_overrides: true,
+        locked_fields: vec![],
+        allowed_user_color_spaces: vec![],
+        bleed_fill: Default::default(),
+        marks: Marks::default(),
+        safe_margin: None,
+        icc_profile: None,
+        true_grayscale_vectors: false,
+        spot_colors,
+        pdf_standard: None,
+        transparency_policy: TransparencyPolicy::Flatten,
+        profile: None,
+    }
+}
+
+/// Two-color logo: a red/navy checker, exported as an 8 x 8 CMYK TIFF
+fn compile(spot_colors: Vec<SpotColorMapping>, request: CompileRequest) -> CompiledAsset {
+    let mut template = create_test_template();
+    template.validation.failure_mode = FailureMode::Warn;
+    template.print = Some(press(spot_colors));
+    template.background_generator = BackgroundGenerator::Pattern {
+        style: PatternStyle::Checker,
+        palette: vec!["#E4002B".to_string(), "#00205B".to_string()],
+    };
+    template.exports.push(export("logo", [8, 8], ExportFormat::Tiff, true));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::new(registry).compile_asset(&request).unwrap()
+}
+
+fn request() -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.seed = Some(7);
+    request
+}
+
+/// CMYK pixels of the placeholder's little-endian TIFF
+fn cmyk_pixels(asset: &CompiledAsset) -> Vec<[u8; 4]> {
+    let file = asset.exports.iter().find(|e| e.id == "logo").unwrap();
+    let tiff = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &file.data_base64).unwrap();
+    let u16_at = |pos: usize| u16::from_le_bytes(tiff[pos..pos + 2].try_into().unwrap());
+    let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap());
+    let ifd = u32_at(4) as usize;
+    let tag = |wanted: u16| (0..u16_at(ifd) as usize)
+        .map(|i| ifd + 2 + 12 * i)
+        .find(|&pos| u16_at(pos) == wanted)
+        .map(|pos| u32_at(pos + 8))
+        .unwrap();
+    let (offset, len) = (tag(273) as usize, tag(279) as usize);
+    tiff[offset..offset + len].chunks_exact(4).map(|px| [px[0], px[1], px[2], px[3]]).collect()
+}
+
+fn spot_colors(asset: &CompiledAsset) -> &[SpotColorUsage] {
+    &asset.exports.iter().find(|e| e.id == "logo").unwrap().spot_colors
+}
+
+#[test]
+fn test_mapped_colors_get_the_exact_ink_values() {
+    let asset = compile(vec![mapping("Brand Red", "#E4002B", RED_INK), mapping("Brand Navy", "#00205b", NAVY_INK)], request());
+    let pixels = cmyk_pixels(&asset);
+    assert_eq!(pixels.len(), 64);
+    assert!(pixels.iter().all(|&px| px == RED_INK || px == NAVY_INK), "{:?}", pixels);
+
+    let usage = spot_colors(&asset);
+    let names: Vec<_> = usage.iter().map(|u| (u.name.as_str(), u.rgb_hex.as_str(), u.cmyk)).collect();
+    assert_eq!(names, vec![("Brand Red", "#E4002B", RED_INK), ("Brand Navy", "#00205B", NAVY_INK)]);
+    let red = pixels.iter().filter(|&&px| px == RED_INK).count() as u64;
+    assert_eq!((usage[0].pixels, usage[1].pixels), (red, 64 - red));
+    assert!(red > 0 && red < 64);
+}
+
+#[test]
+fn test_unmapped_colors_use_the_converter() {
+    let asset = compile(vec![mapping("Brand Red", "#E4002B", RED_INK)], request());
+    let navy = NaiveCmyk.convert(NAVY);
+    assert!(cmyk_pixels(&asset).iter().all(|&px| px == RED_INK || px == navy));
+    assert_eq!(spot_colors(&asset).len(), 1);
+
+    // Mappings nothing matched are not listed; without any, the field is omitted
+    let asset = compile(vec![mapping("Gold", "#FFD100", [0, 40, 255, 0])], request());
+    assert!(spot_colors(&asset).is_empty());
+    let json = serde_json::to_value(&asset).unwrap();
+    assert!(json["exports"].as_array().unwrap().iter().all(|e| e.get("spot_colors").is_none()));
+    let generic = [NaiveCmyk.convert(RED), NaiveCmyk.convert(NAVY)];
+    assert!(cmyk_pixels(&asset).iter().all(|px| generic.contains(px)));
+}
+
+#[test]
+fn test_unmapped_source_colors_are_reported_as_info() {
+    let mut request = request();
+    request.source_data = Some(base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        r##"<svg xmlns="http://www.w3.org/2000/svg"><rect fill="#E4002B"/><rect fill="#00205B"/><circle style="fill:#FFD100"/></svg>"##,
+    ));
+    let asset = compile(vec![mapping("Brand Red", "#E4002B", RED_INK), mapping("Brand Navy", "#00205B", NAVY_INK)], request.clone());
+    let found: Vec<_> = asset.validation.violations.iter().filter(|v| v.rule == "spot_color_unmapped").collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].severity, ViolationSeverity::Info);
+    assert_eq!(found[0].actual.as_deref(), Some("#FFD100"));
+
+    // Templates without spot colors are not checked
+    let asset = compile(vec![], request);
+    assert!(asset.validation.violations.iter().all(|v| v.rule != "spot_color_unmapped"));
+}
+
+#[test]
+fn test_load_rejects_invalid_tables() {
+    let mut template = serde_json::to_value(create_test_template()).unwrap();
+    template["print"] = serde_json::json!({
+        "dpi": 300, "colorSpace": "CMYK", "bleedInches": 0.125,
+        "spotColors": [
+            {"name": "Brand Red", "rgbHex": "#E4002B", "cmyk": [0, 255, 196, 8]},
+            {"name": "Red Again", "rgbHex": "#e4002b", "cmyk": [0, 255, 196, 8]},
+        ],
+    });
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("test-icon.json"), template.to_string()).unwrap();
+    let Err(e) = TemplateRegistry::load_from_dir(dir.path()) else { panic!("loaded") };
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+    assert!(e.to_string().contains("spotColors: Red Again duplicates spot color Brand Red"), "{}", e);
+}
@@ -0,0 +1,89 @@
+//! Strict JSON parsing: duplicate keys are rejected, not resolved
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    canonical_json, parse_strict, ErrorEnvelope, StrictJsonError,
+    templates::{TemplateArrayError, TemplateLoadError, TemplateRegistry},
+};
+use serde_json::Value;
+
+#[test]
+fn test_round_trips_manifests_and_templates() {
+    let asset = common::create_pipeline().compile_asset(&compile_request("test-icon", 1024, 1024)).unwrap();
+    for value in [serde_json::to_value(&asset).unwrap(), serde_json::to_value(create_test_template()).unwrap()] {
+        let canonical = canonical_json(&value).unwrap();
+        assert_eq!(parse_strict(&canonical).unwrap(), value);
+        assert_eq!(canonical_json(&parse_strict(&canonical).unwrap()).unwrap(), canonical);
+        let pretty = serde_json::to_string_pretty(&value).unwrap();
+        assert_eq!(parse_strict(&pretty).unwrap(), value);
+    }
+}
+
+#[test]
+fn test_nested_duplicate_in_a_template_is_named() {
+    let template = canonical_json(&create_test_template()).unwrap();
+    // Repeat the first export's format
+    let duplicated = template.replacen(r#""format":"svg""#, r#""format":"png","format":"svg""#, 1);
+    assert_ne!(duplicated, template);
+
+    // serde_json alone would accept it and keep the last value
+    assert_eq!(serde_json::from_str::<Value>(&duplicated).unwrap(), serde_json::from_str::<Value>(&template).unwrap());
+    match parse_strict(&duplicated) {
+        Err(StrictJsonError::DuplicateKey(key, path)) => {
+            assert_eq!(key, "format");
+            assert_eq!(path, "/exports/0/format");
+        }
+        other => panic!("expected a duplicate key, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_template_with_duplicate_keys_fails_to_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = canonical_json(&create_test_template()).unwrap();
+    fs::write(dir.path().join("icon.json"), &template).unwrap();
+    assert!(TemplateRegistry::load_from_dir(dir.path()).unwrap().get("test-icon").is_some());
+
+    let duplicated = template.replacen(r#""id":"test-icon""#, r#""id":"test-icon","id":"other-icon""#, 1);
+    fs::write(dir.path().join("icon.json"), duplicated).unwrap();
+    let Err(error) = TemplateRegistry::load_from_dir(dir.path()) else { panic!("loaded a template with duplicate keys") };
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains(r#"Duplicate key "id" at "/id""#), "{}", error);
+}
+
+#[test]
+fn test_unparseable_template_files_are_still_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("broken.json"), "{").unwrap();
+    assert!(TemplateRegistry::load_from_dir(dir.path()).unwrap().list().is_empty());
+}
+
+#[test]
+fn test_inline_template_arrays_load_strictly() {
+    let template = canonical_json(&create_test_template()).unwrap();
+    let registry = TemplateRegistry::from_json_array(&format!("[{}]", template)).unwrap();
+    assert!(registry.get("test-icon").is_some());
+
+    let Err(error) = TemplateRegistry::from_json_array(&format!("[{},{{}}]", template)) else {
+        panic!("an empty object loaded as a template");
+    };
+    assert!(matches!(error, TemplateArrayError::Template(1, TemplateLoadError::Schema(_))), "{}", error);
+    let envelope = ErrorEnvelope::from(&error);
+    assert_eq!((envelope.kind.name(), &envelope.details["index"]), ("invalid_template", &Value::from(1)));
+
+    // The whole array parses strictly, so the pointer names the template
+    let duplicated = template.replacen(r#""format":"svg""#, r#""format":"png","format":"svg""#, 1);
+    match TemplateRegistry::from_json_array(&format!("[{},{}]", template, duplicated)) {
+        Err(TemplateArrayError::Json(StrictJsonError::DuplicateKey(key, pointer))) => {
+            assert_eq!((key.as_str(), pointer.as_str()), ("format", "/1/exports/0/format"));
+        }
+        _ => panic!("a duplicate key loaded"),
+    }
+
+    assert!(matches!(TemplateRegistry::from_json_array(&template), Err(TemplateArrayError::NotArray)));
+    assert!(matches!(TemplateRegistry::from_json_array("[{}"), Err(TemplateArrayError::Json(_))));
+}
@@ -0,0 +1,96 @@
+//! Template content hashes recorded in manifests, and drift against them
+
+mod common;
+
+use std::fs;
+
+use common::{compile_request, create_test_template};
+use forgeimages_core::{
+    CompilationPipeline, HashAlgorithm,
+    output::MANIFEST_FILE,
+    templates::TemplateRegistry,
+    verify::{verify_template, VerifyError},
+};
+
+fn registry_with(template: forgeimages_core::templates::Template) -> TemplateRegistry {
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry
+}
+
+#[test]
+fn test_manifest_records_the_template_hash() {
+    let template = create_test_template();
+    let asset = CompilationPipeline::builder(registry_with(template.clone())).build()
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+
+    assert_eq!(asset.template_hash, template.content_hash().unwrap());
+    assert!(asset.template_hash.starts_with("sha256:"));
+    assert_eq!(template.content_hash_with(HashAlgorithm::Sha256).unwrap(), asset.template_hash);
+}
+
+#[test]
+fn test_content_hash_ignores_file_formatting() {
+    let template = create_test_template();
+    let compact: forgeimages_core::templates::Template =
+        serde_json::from_str(&serde_json::to_string(&template).unwrap()).unwrap();
+    let pretty: forgeimages_core::templates::Template =
+        serde_json::from_str(&serde_json::to_string_pretty(&template).unwrap()).unwrap();
+    assert_eq!(compact.content_hash().unwrap(), pretty.content_hash().unwrap());
+}
+
+#[test]
+fn test_unchanged_template_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = CompilationPipeline::builder(registry_with(create_test_template())).build()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+
+    let registry = registry_with(create_test_template());
+    assert_eq!(verify_template(&dir.path().join(MANIFEST_FILE), &registry).unwrap(), asset.manifest_hash);
+}
+
+#[test]
+fn test_edit_without_version_bump_is_reported_as_drift() {
+    let dir = tempfile::tempdir().unwrap();
+    let asset = CompilationPipeline::builder(registry_with(create_test_template())).build()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+
+    let mut edited = create_test_template();
+    edited.validation.rules.resolution.min_width = 256;
+    let current = edited.content_hash().unwrap();
+    match verify_template(&dir.path().join(MANIFEST_FILE), &registry_with(edited)) {
+        Err(VerifyError::TemplateDrift(id, recorded, loaded)) => {
+            assert_eq!(id, "test-icon");
+            assert_eq!(recorded, asset.template_hash);
+            assert_eq!(loaded, current);
+        }
+        other => panic!("expected drift, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_missing_template_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    CompilationPipeline::builder(registry_with(create_test_template())).build()
+        .compile_to_dir(&compile_request("test-icon", 1024, 1024), dir.path())
+        .unwrap();
+
+    assert!(matches!(
+        verify_template(&dir.path().join(MANIFEST_FILE), &TemplateRegistry::new()),
+        Err(VerifyError::TemplateMissing(id)) if id == "test-icon"
+    ));
+}
+
+#[test]
+fn test_manifest_without_template_hash_skips_the_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(MANIFEST_FILE);
+    fs::copy(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/manifest-fi-hash-1.json"),
+        &path,
+    ).unwrap();
+    verify_template(&path, &TemplateRegistry::new()).unwrap();
+}
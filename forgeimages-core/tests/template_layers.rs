@@ -0,0 +1,101 @@
+//! Layered template directories: overlays replace base templates only
+//! upward in version, and each template remembers where it came from
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::create_test_template;
+use forgeimages_core::{
+    templates::{OverlayConflictKind, Template, TemplateRegistry},
+    CompilationPipeline,
+};
+
+fn write(dir: &Path, template: &Template) {
+    fs::write(dir.join(format!("{}.json", template.id)), serde_json::to_vec(template).unwrap()).unwrap();
+}
+
+fn versioned(version: &str, description: &str) -> Template {
+    let mut template = create_test_template();
+    template.template_version = version.to_string();
+    template.description = description.to_string();
+    template
+}
+
+/// A base pack with test-icon 1.0.0 and another template, and an empty overlay
+fn layers() -> (tempfile::TempDir, PathBuf, PathBuf) {
+    let temp = tempfile::tempdir().unwrap();
+    let (base, overlay) = (temp.path().join("base"), temp.path().join("overlay"));
+    fs::create_dir_all(&base).unwrap();
+    fs::create_dir_all(&overlay).unwrap();
+    write(&base, &versioned("1.0.0", "base"));
+    let mut other = create_test_template();
+    other.id = "base-only".to_string();
+    write(&base, &other);
+    (temp, base, overlay)
+}
+
+fn conflict(dirs: &[PathBuf]) -> String {
+    match TemplateRegistry::load_layered(dirs, false) {
+        Ok(_) => panic!("layers should conflict"),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn test_clean_layering() {
+    let (_temp, base, overlay) = layers();
+    write(&overlay, &versioned("1.1.0", "overlay"));
+    let mut extra = create_test_template();
+    extra.id = "overlay-only".to_string();
+    write(&overlay, &extra);
+
+    let registry = TemplateRegistry::load_layered(&[base.clone(), overlay.clone()], false).unwrap();
+    assert_eq!(registry.list().len(), 3);
+    assert_eq!(registry.get("test-icon").unwrap().description, "overlay");
+    assert_eq!(registry.source_dir("test-icon"), Some(overlay.as_path()));
+    assert_eq!(registry.source_dir("base-only"), Some(base.as_path()));
+    assert_eq!(registry.source_dir("overlay-only"), Some(overlay.as_path()));
+
+    // The same version with the same content is a no-op, not a conflict
+    write(&overlay, &versioned("1.0.0", "base"));
+    assert!(TemplateRegistry::load_layered(&[base, overlay], false).is_ok());
+}
+
+#[test]
+fn test_same_version_with_different_content_conflicts() {
+    let (_temp, base, overlay) = layers();
+    write(&overlay, &versioned("1.0.0", "changed"));
+    let message = conflict(&[base.clone(), overlay.clone()]);
+    assert!(message.contains("test-icon 1.0.0"), "{}", message);
+    assert!(message.contains("differs from"), "{}", message);
+
+    // --allow-downgrade does not cover this
+    assert!(TemplateRegistry::load_layered(&[base, overlay], true).is_err());
+}
+
+#[test]
+fn test_downgrades_are_rejected_unless_allowed() {
+    let (_temp, base, overlay) = layers();
+    write(&overlay, &versioned("0.9.0", "older"));
+    let message = conflict(&[base.clone(), overlay.clone()]);
+    assert!(message.contains("would downgrade 1.0.0"), "{}", message);
+
+    let registry = TemplateRegistry::load_layered(&[base.clone(), overlay.clone()], true).unwrap();
+    assert_eq!(registry.get("test-icon").unwrap().template_version, "0.9.0");
+
+    let mut upper = TemplateRegistry::load_from_dir(&base).unwrap();
+    let error = upper.overlay(TemplateRegistry::load_from_dir(&overlay).unwrap(), false).unwrap_err();
+    assert_eq!(error.kind, OverlayConflictKind::Downgrade);
+    assert_eq!((error.base_version.as_str(), error.version.as_str()), ("1.0.0", "0.9.0"));
+}
+
+#[test]
+fn test_layered_registries_compile() {
+    let (_temp, base, overlay) = layers();
+    write(&overlay, &versioned("2.0.0", "overlay"));
+    let pipeline = CompilationPipeline::new(TemplateRegistry::load_layered(&[base, overlay], false).unwrap());
+    let asset = pipeline.compile_asset(&common::compile_request("test-icon", 1024, 1024)).unwrap();
+    assert_eq!(asset.template_version, "2.0.0");
+}
@@ -0,0 +1,139 @@
+//! Template lint rules and strict single-file loading
+
+mod common;
+
+use std::fs;
+
+use common::{create_test_template, export};
+use forgeimages_core::{
+    exit_code, CompilationPipeline, PipelineError,
+    print::ProfileRegistry,
+    templates::{AssetClass, ExportFormat, RejectedTemplate, Template, TemplateLoadError, TemplateRegistry},
+    validation::ViolationSeverity,
+};
+
+fn codes(template: &Template) -> Vec<&'static str> {
+    template.lint().iter().map(|issue| issue.code).collect()
+}
+
+#[test]
+fn test_a_sound_template_has_no_issues() {
+    assert!(create_test_template().lint().is_empty());
+}
+
+#[test]
+fn test_builder_templates_lint_clean_for_every_class() {
+    for class in [AssetClass::Icon, AssetClass::Cover, AssetClass::Banner, AssetClass::Logo] {
+        let template = Template::builder("fresh", class.clone(), [1366, 768]).build();
+        assert!(template.lint().is_empty(), "{:?}: {:?}", class, template.lint());
+        assert_eq!(template.aspect_ratio, [683, 384]);
+        assert!(template.exports.iter().all(|e| e.size[0] <= 1366 && e.required));
+    }
+    let icon = Template::builder("icon", AssetClass::Icon, [1024, 1024]).build();
+    let sizes: Vec<[u32; 2]> = icon.exports.iter().map(|e| e.size).collect();
+    assert_eq!(sizes, [[1024, 1024], [512, 512], [192, 192], [32, 32]]);
+
+    let banner = Template::builder("banner", AssetClass::Banner, [1366, 768])
+        .aspect_ratio([16, 9])
+        .export(common::export("only", [1366, 768], ExportFormat::Png, true))
+        .build();
+    assert_eq!(banner.exports.len(), 1);
+    assert!(banner.lint().is_empty(), "1366x768 is 16:9 within tolerance");
+}
+
+#[test]
+fn test_lint_reports_codes_with_field_paths() {
+    let mut template = create_test_template();
+    template.exports.push(export("master", [512, 512], ExportFormat::Png, false));
+    template.engine_max_version = Some("1.0.0".to_string());
+    template.canonical_size = [1024, 512];
+    template.superseded_by = Some("test-icon-2".to_string());
+    assert_eq!(codes(&template), [
+        "engine_range_empty",
+        "superseded_not_deprecated",
+        "canonical_size_aspect",
+        "duplicate_export_id",
+    ]);
+    let duplicate = template.lint().into_iter().find(|i| i.code == "duplicate_export_id").unwrap();
+    assert_eq!(duplicate.field, "exports[master]");
+    assert_eq!(duplicate.severity, ViolationSeverity::Error);
+
+    let mut optional = create_test_template();
+    optional.exports[0].required = false;
+    optional.template_version = "one".to_string();
+    assert_eq!(codes(&optional), ["template_version_invalid", "no_required_export"]);
+}
+
+#[test]
+fn test_from_file_reports_what_load_from_dir_skips() {
+    let dir = tempfile::tempdir().unwrap();
+    let profiles = ProfileRegistry::new();
+    let write = |name: &str, content: &str| {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    };
+
+    let syntax = write("syntax.json", "{\"id\": ");
+    assert!(matches!(Template::from_file(&syntax, &profiles), Err(TemplateLoadError::Syntax(_))));
+    let schema = write("schema.json", r#"{"id": "x"}"#);
+    let error = Template::from_file(&schema, &profiles).unwrap_err();
+    assert_eq!(error.code(), "schema");
+
+    let mut template = create_test_template();
+    let mut web = export("web", [64, 64], ExportFormat::Png, false);
+    web.guides = true;
+    template.exports.push(web);
+    let invalid = write("invalid.json", &serde_json::to_string(&template).unwrap());
+    let issue = Template::from_file(&invalid, &profiles).unwrap_err().to_issue();
+    assert_eq!((issue.code, issue.field.as_str()), ("invalid_field", "exports[web].guides"));
+
+    let duplicate = write("duplicate.json", r#"{"id": "a", "id": "b"}"#);
+    let issue = Template::from_file(&duplicate, &profiles).unwrap_err().to_issue();
+    assert_eq!((issue.code, issue.field.as_str()), ("duplicate_key", "/id"));
+
+    // The lenient loader still skips files that are not templates
+    fs::remove_file(&invalid).unwrap();
+    fs::remove_file(&duplicate).unwrap();
+    assert!(TemplateRegistry::load_from_dir(dir.path()).unwrap().list().is_empty());
+}
+
+#[test]
+fn test_export_ids_must_be_plain_file_names() {
+    for id in ["../escaped", "nested/icon", "back\\slash", "..", ".", ""] {
+        let mut template = create_test_template();
+        template.exports.push(export(id, [64, 64], ExportFormat::Png, false));
+        let issue = template.lint().into_iter().find(|i| i.code == "unsafe_export_id").expect(id);
+        assert_eq!((issue.field, issue.severity), (format!("exports[{}].id", id), ViolationSeverity::Error));
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut template = create_test_template();
+    template.exports.push(export("../escaped", [64, 64], ExportFormat::Png, false));
+    fs::write(dir.path().join("evil.json"), serde_json::to_string(&template).unwrap()).unwrap();
+    let error = Template::from_file(&dir.path().join("evil.json"), &ProfileRegistry::new()).unwrap_err();
+    assert!(matches!(&error, TemplateLoadError::UnsafeExportId(id) if id == "../escaped"));
+
+    // Refused at load time, not skipped
+    let error = TemplateRegistry::load_from_dir(dir.path()).err().unwrap();
+    let rejected = error.get_ref().and_then(|e| e.downcast_ref::<RejectedTemplate>()).unwrap();
+    assert!(matches!(rejected.error, TemplateLoadError::UnsafeExportId(_)));
+    assert!(error.to_string().ends_with(r#"evil.json: export id "../escaped" is not a plain file name"#), "{}", error);
+}
+
+#[test]
+fn test_registered_templates_with_unsafe_export_ids_write_nothing() {
+    let mut template = create_test_template();
+    template.exports.push(export("../escaped", [64, 64], ExportFormat::Svg, false));
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+
+    let root = tempfile::tempdir().unwrap();
+    let out = root.path().join("out");
+    let error = CompilationPipeline::new(registry)
+        .compile_to_dir(&common::compile_request("test-icon", 1024, 1024), &out)
+        .unwrap_err();
+    assert!(matches!(error, PipelineError::ValidationFailed(_)), "{}", error);
+    assert_eq!(exit_code::exit_code_for(&error), exit_code::VALIDATION_FAILED);
+    assert!(fs::read_dir(root.path()).unwrap().next().is_none(), "nothing is written, inside or out");
+}
@@ -0,0 +1,93 @@
+//! Text Slot Substitution
+//!
+//! Slot params are validated against the template and the SVG master, then
+//! outlined with the template font so output never depends on host fonts.
+
+mod common;
+
+use std::path::Path;
+
+use common::{compile_request, create_test_template, tiny_font};
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, HashAlgorithm,
+    hashing::sha256_hex,
+    templates::{FontRef, TemplateRegistry, TextSlot},
+};
+
+const MASTER: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1024 1024"><text data-fi-slot="title" x="100" y="500" font-size="100" fill="#222">Placeholder</text></svg>"##;
+
+fn slot_pipeline(dir: &Path, font_path: &str) -> CompilationPipeline {
+    let font = tiny_font();
+    std::fs::create_dir_all(dir.join("fonts")).unwrap();
+    std::fs::write(dir.join("fonts/tiny.ttf"), &font).unwrap();
+
+    let mut template = create_test_template();
+    template.text_slots = vec![TextSlot { id: "title".to_string(), max_length: 12 }];
+    template.font = Some(FontRef { path: font_path.to_string(), sha256: sha256_hex(&font) });
+
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    registry.set_asset_dir(dir);
+    CompilationPipeline::new(registry)
+}
+
+fn slot_request(title: &str) -> CompileRequest {
+    let mut request = compile_request("test-icon", 1024, 1024);
+    request.source_data = Some(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, MASTER));
+    request.params.insert("title".to_string(), serde_json::json!(title));
+    request
+}
+
+#[test]
+fn slot_text_is_substituted_and_outlined() {
+    let dir = tempfile::tempdir().unwrap();
+    let pipeline = slot_pipeline(dir.path(), "fonts/tiny.ttf");
+    let asset = pipeline.compile_asset(&slot_request("AA")).unwrap();
+
+    let master = asset.exports.iter().find(|e| e.id == "master").unwrap();
+    let svg = String::from_utf8(
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &master.data_base64).unwrap()
+    ).unwrap();
+
+    assert!(!svg.contains("<text"));
+    assert!(!svg.contains("Placeholder"));
+    assert!(svg.contains(r##"<path data-fi-slot="title" fill="#222" d="M105 500 L155 500 L155 430 Q130 410 105 430 Z M165 500 L215 500"##));
+    assert_eq!(asset.font_hash, Some(HashAlgorithm::Sha256.digest(&tiny_font())));
+}
+
+#[test]
+fn slot_text_over_max_length_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let pipeline = slot_pipeline(dir.path(), "fonts/tiny.ttf");
+    let err = pipeline.compile_asset(&slot_request("MUCH TOO LONG TITLE")).unwrap_err();
+    assert!(err.to_string().contains("slot_too_long"));
+}
+
+#[test]
+fn params_for_missing_slots_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let pipeline = slot_pipeline(dir.path(), "fonts/tiny.ttf");
+
+    let mut request = slot_request("AA");
+    request.source_data = Some(base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><text>no slot</text></svg>"#,
+    ));
+    let err = pipeline.compile_asset(&request).unwrap_err().to_string();
+    assert!(err.contains("slot_missing"), "{err}");
+
+    let mut request = slot_request("AA");
+    request.params.insert("subtitle".to_string(), serde_json::json!("x"));
+    let err = pipeline.compile_asset(&request).unwrap_err().to_string();
+    assert!(err.contains("unknown_param"), "{err}");
+}
+
+#[test]
+fn missing_or_escaping_font_is_a_validation_error() {
+    let dir = tempfile::tempdir().unwrap();
+    for path in ["fonts/absent.ttf", "../fonts/tiny.ttf"] {
+        let pipeline = slot_pipeline(dir.path(), path);
+        let err = pipeline.compile_asset(&slot_request("AA")).unwrap_err().to_string();
+        assert!(err.contains("font_missing"), "{path}: {err}");
+    }
+}
@@ -0,0 +1,88 @@
+//! Top-level warning fields and fail_on_warnings
+
+mod common;
+
+use common::{compile_request, create_test_template, export};
+use forgeimages_core::{
+    CompilationPipeline, PipelineError, Template,
+    templates::{ExportFormat, FailureMode, TemplateRegistry},
+};
+
+/// Template whose color_count rule warns above 16 colors
+fn template(mode: FailureMode) -> Template {
+    let mut template = create_test_template();
+    template.validation.failure_mode = mode;
+    template.validation.rules.color_count.enabled = true;
+    template.validation.rules.color_count.max = 16;
+    template
+}
+
+fn pipeline(template: Template, fail_on_warnings: bool) -> CompilationPipeline {
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    CompilationPipeline::builder(registry).fail_on_warnings(fail_on_warnings).build()
+}
+
+fn colorful(width: u32) -> forgeimages_core::CompileRequest {
+    let mut request = compile_request("test-icon", width, width);
+    request.asset_input.color_count = Some(64);
+    request
+}
+
+#[test]
+fn test_warnings_surface_at_top_level() {
+    let asset = pipeline(template(FailureMode::Warn), false).compile_asset(&colorful(1024)).unwrap();
+    assert!(asset.has_warnings);
+    assert_eq!(asset.warning_count, 1);
+
+    let json = serde_json::to_value(&asset).unwrap();
+    assert_eq!(json["warning_count"], 1);
+    assert_eq!(json["has_warnings"], true);
+}
+
+#[test]
+fn test_fail_on_warnings_rejects_warned_compiles_in_every_mode() {
+    for mode in [FailureMode::Block, FailureMode::Warn, FailureMode::Log] {
+        let result = pipeline(template(mode.clone()), true).compile_asset(&colorful(1024));
+        assert!(
+            matches!(result, Err(PipelineError::ValidationFailed(ref msg)) if msg.contains("color_count")),
+            "{:?} mode: {:?}", mode, result.map(|a| a.warning_count),
+        );
+    }
+}
+
+#[test]
+fn test_fail_on_warnings_passes_clean_compiles() {
+    let asset = pipeline(template(FailureMode::Block), true)
+        .compile_asset(&compile_request("test-icon", 1024, 1024))
+        .unwrap();
+    assert!(!asset.has_warnings);
+}
+
+#[test]
+fn test_fail_on_warnings_never_loosens() {
+    // Errors still block under Block mode with or without the flag
+    for fail_on_warnings in [false, true] {
+        let result = pipeline(template(FailureMode::Block), fail_on_warnings).compile_asset(&colorful(100));
+        assert!(matches!(result, Err(PipelineError::ValidationFailed(_))));
+    }
+}
+
+#[test]
+fn test_optional_export_failure_counts_as_warning() {
+    let mut template = template(FailureMode::Block);
+    // Background without a seed fails at render time for this optional PNG
+    template.exports.push(export("optional", [16, 16], ExportFormat::Png, false));
+    template.background_generator = serde_json::from_value(serde_json::json!({
+        "type": "pattern", "style": "dots"
+    })).unwrap();
+    template.validation.failure_mode = FailureMode::Warn;
+
+    let request = compile_request("test-icon", 1024, 1024);
+    let asset = pipeline(template.clone(), false).compile_asset(&request).unwrap();
+    assert!(asset.has_warnings);
+    assert!(matches!(
+        pipeline(template, true).compile_asset(&request),
+        Err(PipelineError::ValidationFailed(_))
+    ));
+}
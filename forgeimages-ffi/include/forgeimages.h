@@ -0,0 +1,82 @@
+/* ForgeImages C API: JSON in, JSON out. See forgeimages-ffi/src/lib.rs. */
+
+#ifndef FORGEIMAGES_H
+#define FORGEIMAGES_H
+
+/* Generated by cbindgen from src/lib.rs; tests/header.rs checks it is current. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A compilation pipeline over the templates given to `fi_pipeline_new`
+ */
+typedef struct FiPipeline FiPipeline;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A pipeline over `templates_json`, a JSON array of template objects as
+ * they would appear in template files (read as strictly, print profiles
+ * aside: templates naming one are rejected). Returns null on failure;
+ * `fi_last_error` says why.
+ *
+ * # Safety
+ * `templates_json` must be null or a NUL-terminated string.
+ */
+struct FiPipeline *fi_pipeline_new(const char *templates_json);
+
+/**
+ * The error envelope for the last `fi_pipeline_new` on this thread that
+ * returned null, or null if it succeeded. Free with `fi_string_free`.
+ */
+char *fi_last_error(void);
+
+/**
+ * Validate `input_json` (an `AssetInput`) against a template. Returns a
+ * `ValidationResult`, valid or not, or an error envelope.
+ *
+ * # Safety
+ * `pipeline` must be null or from `fi_pipeline_new`, not yet freed; the
+ * strings null or NUL-terminated.
+ */
+char *fi_validate(const struct FiPipeline *pipeline,
+                  const char *template_id,
+                  const char *input_json);
+
+/**
+ * Compile `request_json` (a `CompileRequest`). Returns the
+ * `CompiledAsset`, exports embedded, or an error envelope;
+ * `validation_failed`, with the violations, when the request is blocked.
+ *
+ * # Safety
+ * As for `fi_validate`.
+ */
+char *fi_compile(const struct FiPipeline *pipeline, const char *request_json);
+
+/**
+ * Free a string returned by this library; null is ignored.
+ *
+ * # Safety
+ * `string` must be null or returned by this library, and not freed yet.
+ */
+void fi_string_free(char *string);
+
+/**
+ * Free a pipeline; null is ignored.
+ *
+ * # Safety
+ * `pipeline` must be null or from `fi_pipeline_new`, not freed yet, and
+ * no longer in use on any thread.
+ */
+void fi_pipeline_free(struct FiPipeline *pipeline);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FORGEIMAGES_H */
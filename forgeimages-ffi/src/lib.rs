@@ -0,0 +1,202 @@
+//! ForgeImages FFI - C ABI for Non-Rust Hosts
+//!
+//! Validation and compilation for hosts that embed the engine instead of
+//! running `forgeimages-cli`. Every call takes and returns JSON strings:
+//! results serialize the core's own types (`ValidationResult`,
+//! `CompiledAsset`), failures the core's `ErrorEnvelope`, as the CLI prints it:
+//!
+//! ```json
+//! {"success":false,"error_kind":"template_not_found","message":"...","details":{...}}
+//! ```
+//!
+//! Results never have an `error_kind` key, so hosts tell the two apart by it.
+//!
+//! Strings returned by this library are owned by the caller and freed with
+//! `fi_string_free`; pipelines with `fi_pipeline_free`. A pipeline may be
+//! used from several threads at once. Panics are caught at the boundary and
+//! reported as `internal_error` envelopes; none unwind into the host.
+//!
+//! `include/forgeimages.h` is generated by cbindgen (`tests/header.rs`
+//! checks it is current).
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use forgeimages_core::{
+    CompilationPipeline, CompileRequest, ErrorEnvelope, ErrorKind,
+    templates::TemplateRegistry,
+    validation::AssetInput,
+};
+use serde::Serialize;
+
+/// A compilation pipeline over the templates given to `fi_pipeline_new`
+pub struct FiPipeline {
+    pipeline: CompilationPipeline,
+}
+
+/// Envelope for a panic caught at the boundary
+fn panicked(payload: Box<dyn std::any::Any + Send>) -> ErrorEnvelope {
+    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    ErrorEnvelope::new(ErrorKind::Internal, format!("panicked: {}", message))
+}
+
+thread_local! {
+    /// Why the last `fi_pipeline_new` on this thread returned null
+    static LAST_ERROR: RefCell<Option<ErrorEnvelope>> = const { RefCell::new(None) };
+}
+
+/// A pipeline over `templates_json`, a JSON array of template objects as
+/// they would appear in template files (read as strictly, print profiles
+/// aside: templates naming one are rejected). Returns null on failure;
+/// `fi_last_error` says why.
+///
+/// # Safety
+/// `templates_json` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fi_pipeline_new(templates_json: *const c_char) -> *mut FiPipeline {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let templates = read_str(templates_json, "templates_json")?;
+        pipeline_from_json(templates)
+    }))
+    .unwrap_or_else(|panic| Err(panicked(panic)));
+    match result {
+        Ok(pipeline) => {
+            LAST_ERROR.with(|last| last.borrow_mut().take());
+            Box::into_raw(Box::new(FiPipeline { pipeline }))
+        }
+        Err(envelope) => {
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(envelope));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The error envelope for the last `fi_pipeline_new` on this thread that
+/// returned null, or null if it succeeded. Free with `fi_string_free`.
+#[no_mangle]
+pub extern "C" fn fi_last_error() -> *mut c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null_mut(), into_c_string))
+}
+
+/// Validate `input_json` (an `AssetInput`) against a template. Returns a
+/// `ValidationResult`, valid or not, or an error envelope.
+///
+/// # Safety
+/// `pipeline` must be null or from `fi_pipeline_new`, not yet freed; the
+/// strings null or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn fi_validate(
+    pipeline: *const FiPipeline,
+    template_id: *const c_char,
+    input_json: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        let pipeline = read_pipeline(pipeline)?;
+        let template_id = read_str(template_id, "template_id")?;
+        let input: AssetInput = parse_payload(read_str(input_json, "input_json")?)?;
+        pipeline.validate_asset(template_id, &input).map_err(ErrorEnvelope::from)
+    })
+}
+
+/// Compile `request_json` (a `CompileRequest`). Returns the
+/// `CompiledAsset`, exports embedded, or an error envelope;
+/// `validation_failed`, with the violations, when the request is blocked.
+///
+/// # Safety
+/// As for `fi_validate`.
+#[no_mangle]
+pub unsafe extern "C" fn fi_compile(pipeline: *const FiPipeline, request_json: *const c_char) -> *mut c_char {
+    respond(|| {
+        let pipeline = read_pipeline(pipeline)?;
+        let request: CompileRequest = parse_payload(read_str(request_json, "request_json")?)?;
+        pipeline.compile_asset(&request).map_err(|e| pipeline.error_envelope(&request, &e))
+    })
+}
+
+/// Free a string returned by this library; null is ignored.
+///
+/// # Safety
+/// `string` must be null or returned by this library, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fi_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Free a pipeline; null is ignored.
+///
+/// # Safety
+/// `pipeline` must be null or from `fi_pipeline_new`, not freed yet, and
+/// no longer in use on any thread.
+#[no_mangle]
+pub unsafe extern "C" fn fi_pipeline_free(pipeline: *mut FiPipeline) {
+    if !pipeline.is_null() {
+        drop(Box::from_raw(pipeline));
+    }
+}
+
+fn pipeline_from_json(templates: &str) -> Result<CompilationPipeline, ErrorEnvelope> {
+    let registry = TemplateRegistry::from_json_array(templates).map_err(|e| ErrorEnvelope::from(&e))?;
+    Ok(CompilationPipeline::new(registry))
+}
+
+fn parse_payload<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, ErrorEnvelope> {
+    serde_json::from_str(json).map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)))
+}
+
+/// # Safety
+/// `pipeline` must be null or a live pipeline.
+unsafe fn read_pipeline<'a>(pipeline: *const FiPipeline) -> Result<&'a CompilationPipeline, ErrorEnvelope> {
+    pipeline.as_ref()
+        .map(|pipeline| &pipeline.pipeline)
+        .ok_or_else(|| ErrorEnvelope::new(ErrorKind::InvalidArgument, "pipeline is null").detail("argument", "pipeline"))
+}
+
+/// # Safety
+/// `string` must be null or NUL-terminated.
+unsafe fn read_str<'a>(string: *const c_char, argument: &str) -> Result<&'a str, ErrorEnvelope> {
+    if string.is_null() {
+        return Err(ErrorEnvelope::new(ErrorKind::InvalidArgument, format!("{} is null", argument)).detail("argument", argument));
+    }
+    CStr::from_ptr(string).to_str()
+        .map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidArgument, format!("{} is not UTF-8: {}", argument, e)).detail("argument", argument))
+}
+
+/// Run `call`, serializing its result or envelope; panics become envelopes
+fn respond<T: Serialize>(call: impl FnOnce() -> Result<T, ErrorEnvelope>) -> *mut c_char {
+    let json = catch_unwind(AssertUnwindSafe(|| match call() {
+        Ok(result) => serde_json::to_string(&result)
+            .map_err(|e| ErrorEnvelope::new(ErrorKind::SerializationError, e.to_string())),
+        Err(envelope) => Err(envelope),
+    }));
+    match json {
+        Ok(Ok(json)) => CString::new(json).expect("JSON escapes NUL").into_raw(),
+        Ok(Err(envelope)) => into_c_string(&envelope),
+        Err(panic) => into_c_string(&panicked(panic)),
+    }
+}
+
+fn into_c_string(envelope: &ErrorEnvelope) -> *mut c_char {
+    let json = serde_json::to_string(envelope).expect("envelopes serialize");
+    CString::new(json).expect("JSON escapes NUL").into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_panics_become_envelopes() {
+        let json = respond::<()>(|| panic!("renderer exploded"));
+        let envelope: Value = serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+        unsafe { fi_string_free(json) };
+        assert_eq!(envelope["error_kind"], "internal");
+        assert_eq!(envelope["message"], "panicked: renderer exploded");
+    }
+}
@@ -0,0 +1,50 @@
+//! Builds tests/smoke.c against include/forgeimages.h and the cdylib with
+//! the system C compiler (`CC`, default `cc`), and runs it
+
+#![cfg(unix)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where cargo put the cdylib built alongside this test: target/<profile>/deps
+/// (next to the test binary) under `cargo test`, target/<profile> once
+/// `cargo build` has copied it up
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let name = format!("{}forgeimages_ffi{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX);
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join(&name).is_file())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, exe.display()))
+        .to_path_buf()
+}
+
+#[test]
+fn test_c_smoke() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let temp = tempfile::tempdir().unwrap();
+    let template = fs::read_to_string(crate_dir.join("../forgeimages-core/templates/pwa-icon.json")).unwrap();
+    let templates = temp.path().join("templates.json");
+    fs::write(&templates, format!("[{}]", template)).unwrap();
+
+    let lib = library_dir();
+    let smoke = temp.path().join("smoke");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I").arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/smoke.c"))
+        .arg("-L").arg(&lib)
+        .arg(format!("-Wl,-rpath,{}", lib.display()))
+        .arg("-lforgeimages_ffi")
+        .arg("-o").arg(&smoke)
+        .status()
+        .unwrap_or_else(|e| panic!("{}: {}", compiler, e));
+    assert!(status.success(), "compiling tests/smoke.c failed");
+
+    let output = Command::new(&smoke).arg(&templates).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "ok\n");
+}
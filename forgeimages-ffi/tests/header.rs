@@ -0,0 +1,26 @@
+//! include/forgeimages.h is what cbindgen generates from src/lib.rs;
+//! `FORGEIMAGES_UPDATE_GOLDEN=1` regenerates it
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn test_header_is_current() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    let mut generated = vec![];
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let path = crate_dir.join("include/forgeimages.h");
+    if std::env::var_os("FORGEIMAGES_UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    assert_eq!(generated, fs::read_to_string(&path).unwrap(), "FORGEIMAGES_UPDATE_GOLDEN=1 regenerates include/forgeimages.h");
+}
@@ -0,0 +1,88 @@
+/* C smoke test for the ForgeImages C API; run by tests/c_smoke.rs.
+ * Usage: smoke <templates.json>, a JSON array of templates with pwa-icon */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "forgeimages.h"
+
+static int failures = 0;
+
+/* Check `result` contains `needle`, then free it */
+static void expect(const char *what, char *result, const char *needle) {
+    if (result == NULL || strstr(result, needle) == NULL) {
+        fprintf(stderr, "FAIL %s: expected %s in %s\n", what, needle, result ? result : "(null)");
+        failures++;
+    }
+    fi_string_free(result);
+}
+
+static char *read_file(const char *path) {
+    FILE *file = fopen(path, "rb");
+    if (file == NULL) {
+        return NULL;
+    }
+    fseek(file, 0, SEEK_END);
+    long size = ftell(file);
+    rewind(file);
+    char *content = malloc((size_t)size + 1);
+    size_t read = fread(content, 1, (size_t)size, file);
+    content[read] = '\0';
+    fclose(file);
+    return content;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <templates.json>\n", argv[0]);
+        return 2;
+    }
+    char *templates = read_file(argv[1]);
+    if (templates == NULL) {
+        perror(argv[1]);
+        return 2;
+    }
+
+    FiPipeline *broken = fi_pipeline_new("{not json");
+    if (broken != NULL) {
+        fprintf(stderr, "FAIL pipeline from bad JSON\n");
+        failures++;
+    }
+    expect("last error", fi_last_error(), "\"error_kind\":\"invalid_template\"");
+
+    FiPipeline *pipeline = fi_pipeline_new(templates);
+    free(templates);
+    if (pipeline == NULL) {
+        char *error = fi_last_error();
+        fprintf(stderr, "FAIL pipeline: %s\n", error);
+        fi_string_free(error);
+        return 1;
+    }
+    if (fi_last_error() != NULL) {
+        fprintf(stderr, "FAIL last error after success\n");
+        failures++;
+    }
+
+    expect("valid", fi_validate(pipeline, "pwa-icon", "{\"width\":1024,\"height\":1024}"), "\"valid\":true");
+    expect("invalid", fi_validate(pipeline, "pwa-icon", "{\"width\":100,\"height\":100}"), "\"valid\":false");
+    expect("bad input", fi_validate(pipeline, "pwa-icon", "[]"), "\"error_kind\":\"invalid_payload\"");
+    expect("null id", fi_validate(pipeline, NULL, "{}"), "\"error_kind\":\"invalid_argument\"");
+    expect("null pipeline", fi_compile(NULL, "{}"), "\"error_kind\":\"invalid_argument\"");
+
+    const char *request = "{\"template_id\":\"pwa-icon\",\"asset_input\":{\"width\":1024,\"height\":1024}}";
+    expect("compile", fi_compile(pipeline, request), "\"manifest_hash\":\"sha256:");
+    const char *blocked = "{\"template_id\":\"pwa-icon\",\"asset_input\":{\"width\":100,\"height\":100}}";
+    expect("blocked", fi_compile(pipeline, blocked), "\"error_kind\":\"validation_failed\"");
+    const char *missing = "{\"template_id\":\"missing\",\"asset_input\":{\"width\":1024,\"height\":1024}}";
+    expect("missing", fi_compile(pipeline, missing), "\"details\":{\"template_id\":\"missing\"}");
+
+    fi_pipeline_free(pipeline);
+    fi_pipeline_free(NULL);
+    fi_string_free(NULL);
+
+    if (failures == 0) {
+        printf("ok\n");
+    }
+    return failures == 0 ? 0 : 1;
+}
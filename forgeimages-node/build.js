@@ -0,0 +1,8 @@
+// Copies the cdylib cargo built to forgeimages.node, where index.js loads it
+const fs = require("node:fs");
+const path = require("node:path");
+
+const names = { darwin: "libforgeimages_node.dylib", win32: "forgeimages_node.dll" };
+const built = names[process.platform] ?? "libforgeimages_node.so";
+const profile = process.argv[2] ?? "release";
+fs.copyFileSync(path.join(__dirname, "target", profile, built), path.join(__dirname, "forgeimages.node"));
@@ -0,0 +1,414 @@
+// Generated by tests/types.rs from the core's serde types; do not edit
+
+export const ENGINE_VERSION: string
+
+/** What every call throws, or `compile` rejects with, when it fails */
+export interface ForgeImagesError extends Error {
+  code: ErrorKind
+  envelope: ErrorEnvelope
+}
+
+/**
+ * A pipeline over one template directory, or over templates given as JSON
+ * (text or parsed)
+ */
+export function createPipeline(templates: string | Array<object>): Pipeline
+/** The canonical JSON every engine hash is taken over */
+export function canonicalJson(value: unknown): string
+/** A digest of the template, the payload and the engine version (this engine's by default) */
+export function computeJobHash(templateId: string, templateVersion: string, payload: unknown, engineVersion?: string): string
+
+export class Pipeline {
+  private constructor()
+  /** The `ValidationResult` for an `AssetInput`, valid or not */
+  validate(templateId: string, input: AssetInput): ValidationResult
+  /** Compiled on the libuv threadpool; rejects with `validation_failed` when validation blocks it */
+  compile(request: CompileRequest): Promise<CompiledAsset>
+  /** The job hash `compile` would record */
+  jobHash(request: CompileRequest): string
+  /** Template IDs, sorted */
+  templateIds(): Array<string>
+}
+
+export type AssetInput = { width: number, height: number, color_count?: number | null, format?: string | null, };
+
+export type BleedFill = { "type": "mirror" } | { "type": "color", color: string, };
+
+export type CellRecord = { row: number, column: number, x_pt: number, y_pt: number, width_pt: number, height_pt: number, };
+
+export type ColorConversion = { method: ConversionMethod, 
+/**
+ * Digest of the ICC profile; `None` for naive conversion
+ */
+profile: string | null, };
+
+export type ColorSpace = "RGB" | "CMYK" | "GRAYSCALE";
+
+export type CompileRequest = { template_id: string, asset_input: AssetInput, source_data?: string | null, 
+/**
+ * Source file under the pipeline's source root; exclusive with source_data.
+ * Only the file's hash enters the job hash, never the path.
+ */
+source_path?: string | null, 
+/**
+ * A number or a decimal string; written as a string above 2^53 - 1
+ */
+seed?: number | string | null, prompt?: string | null, 
+/**
+ * Template parameters, e.g. text slot values keyed by slot id
+ */
+params: { [key in string]?: JsonValue }, 
+/**
+ * User print override; resolved against the template's print block
+ */
+print_spec?: PrintSpec | null, };
+
+export type CompiledAsset = { id: string, template_id: string, template_version: string, 
+/**
+ * `Template::content_hash` of the template as compiled; empty in older manifests
+ */
+template_hash?: string, engine_version: string, created_at: string, 
+/**
+ * Algorithm behind every digest in this manifest
+ */
+hash_algorithm: HashAlgorithm, 
+/**
+ * How job and manifest hashes were built; absent means `fi-hash-0`
+ */
+hash_scheme: HashScheme, manifest_hash: string, job_hash: string, 
+/**
+ * `job_hash` is HMAC-SHA256 under the key named by `job_hash_key_id`
+ */
+job_hash_keyed?: boolean, job_hash_key_id?: string | null, validation: ValidationResult, 
+/**
+ * Warning violations in `validation`, surfaced so callers can't miss them
+ */
+warning_count: number, has_warnings: boolean, encoding: EncodingProfile, 
+/**
+ * `PipelineConfig::hash` of the compiling pipeline's effective config;
+ * empty in older manifests
+ */
+config_hash?: string, 
+/**
+ * Digest of the template font used to outline text slots
+ */
+font_hash: string | null, 
+/**
+ * Key that signs this manifest; the signature itself is never hashed
+ */
+signer: SignerInfo | null, 
+/**
+ * Digest of the decoded source bytes
+ */
+source_hash: string | null, 
+/**
+ * Digest of the normalized SVG, when the pipeline normalizes sources
+ */
+normalized_source_hash: string | null, 
+/**
+ * The request compiled, minus its source bytes; absent in older manifests
+ */
+request?: RequestRecord | null, 
+/**
+ * Effective print spec, including the authority it came from
+ */
+print: PrintSpec, 
+/**
+ * Fields of `print` the user's spec supplied, with the template values
+ * they replaced
+ */
+print_overrides?: Array<PrintOverride>, 
+/**
+ * Print profile the template block was built from; `print` already
+ * holds its effective values
+ */
+print_profile?: string | null, 
+/**
+ * Merkle root over the export digests (see `merkle`); `None` without exports
+ */
+exports_root: string | null, exports: Array<ExportedFile>, 
+/**
+ * Optional exports that failed to render or store (covered by the manifest hash)
+ */
+export_errors: Array<ExportError>, 
+/**
+ * Where the pipeline's `ExportSink` stored each export (see `sink`)
+ */
+stored_objects?: Array<StoredObject>, };
+
+export type ConversionMethod = "naive" | "icc" | "rec709_luma" | "rec709_luma_filter";
+
+export type EmbeddedProfile = { name: string, sha256: string, };
+
+export type EncodingProfile = { png_filter: PngFilter, png_compression: PngCompression, };
+
+export type ErrorEnvelope = { success: false, error_kind: ErrorKind, message: string, template_id?: string | null, validation?: ValidationResult | null, details: { [key in string]?: JsonValue }, };
+
+export type ErrorKind = "template_not_found" | "validation_failed" | "engine_version_mismatch" | "compilation_error" | "export_failed" | "invalid_source" | "source_not_allowed" | "output_failed" | "audit_failed" | "sandbox_violation" | "icc_profile_not_found" | "serialization_error" | "hashing_error" | "cancelled" | "usage" | "invalid_payload" | "invalid_file" | "invalid_json" | "invalid_template" | "invalid_argument" | "invalid_config" | "invalid_key" | "unsupported_key" | "templates_unavailable" | "output_exists" | "not_reproducible" | "unknown_export" | "unknown_asset_class" | "verification_failed" | "signature_rejected" | "unsupported_media_type" | "server" | "internal";
+
+export type ExportError = { export_id: string, message: string, spec: ExportSpec, };
+
+export type ExportFormat = "svg" | "png" | "ico" | "pdf" | "jpg" | "tiff";
+
+export type ExportSpec = { id: string, description: string, 
+/**
+ * Pixel size; filled in from `physical_preset` on load when that is set
+ */
+size: [number, number], 
+/**
+ * Named physical size (`print::presets`), converted at the export's dpi
+ */
+physicalPreset?: string | null, format: ExportFormat, required: boolean, 
+/**
+ * Overrides the template's scaling policy for this export
+ */
+scalingPolicy: ScalingPolicy | null, 
+/**
+ * Stands in for the template's print block for this export (print formats only)
+ */
+print?: TemplatePrint | null, 
+/**
+ * Repeat the trim-size artwork n-up on a press sheet (print formats only)
+ */
+imposition?: Imposition | null, 
+/**
+ * Png preview of the artwork after CMYK conversion and back
+ * (`color::soft_proof`); never a deliverable, so never required
+ */
+softProof?: boolean, 
+/**
+ * Draw bleed, trim and safe-margin outlines over the print export
+ * (`bleed::Guides`); a proof like `soft_proof`, never required
+ */
+guides?: boolean, };
+
+export type ExportedFile = { id: string, filename: string, format: string, 
+/**
+ * File dimensions, bleed and marks included (see `trim`)
+ */
+size: [number, number], 
+/**
+ * Trim box within the file, when bleed or marks enlarge it past the spec size
+ */
+trim?: TrimBox | null, 
+/**
+ * Printer's marks drawn outside the trim
+ */
+marks?: Marks | null, data_base64: string, hash: string, 
+/**
+ * Effective scaling policy and whether this export upscaled the source
+ */
+scaling: ScalingRecord, 
+/**
+ * Print spec the export was rendered for; Pdf/Jpg/Tiff only
+ */
+print?: PrintSpec | null, 
+/**
+ * How CMYK pixels were produced; set for CMYK print exports
+ */
+color_conversion?: ColorConversion | null, 
+/**
+ * ICC profile embedded in the file
+ */
+icc_profile?: EmbeddedProfile | null, 
+/**
+ * Spot colors mapped during CMYK conversion, with the pixels each matched
+ */
+spot_colors?: Array<SpotColorUsage>, 
+/**
+ * PDF/X standard the file conforms to
+ */
+pdf_standard?: PdfStandard | null, 
+/**
+ * Soft-proof or guide preview (`ExportSpec::is_proof`)
+ */
+proof?: boolean, 
+/**
+ * Whether the file is meant for delivery; false for proofs
+ */
+deliverable?: boolean, 
+/**
+ * N-up layout, when the file is an imposed press sheet (`size` is the sheet's)
+ */
+imposition?: ImpositionRecord | null, 
+/**
+ * Export whose render produced these bytes, when deduplication reused it
+ * (not covered by the manifest hash)
+ */
+deduplicated_from?: string | null, };
+
+export type HashAlgorithm = "sha256";
+
+export type HashScheme = "fi-hash-0" | "fi-hash-1";
+
+export type Imposition = { sheet: Sheet, rows: number, columns: number, 
+/**
+ * Written as `gutterMm`, a number of millimetres; also reads a length
+ * string under either name
+ */
+gutterMm: number, cropMarks?: boolean, };
+
+export type ImpositionRecord = { sheet_mm: [number, number], rows: number, columns: number, gutter_mm: number, crop_marks: boolean, 
+/**
+ * Row-major, top-left first
+ */
+cells: Array<CellRecord>, };
+
+export type JsonValue = number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null;
+
+export type Marks = { crop: boolean, registration: boolean, colorBars: boolean, markLength: string, 
+/**
+ * From the trim; marks closer than the bleed print over it
+ */
+markOffset: string, };
+
+export type PdfStandard = "x1a" | "x4";
+
+export type PhysicalSize = { widthMm: number, heightMm: number, };
+
+export type PngCompression = "stored" | "fixed_huffman";
+
+export type PngFilter = "none" | "sub" | "up" | "average" | "paeth";
+
+export type PrintAuthority = "system" | "template" | "user";
+
+export type PrintField = "dpi" | "colorSpace" | "bleedInches" | "iccProfile";
+
+export type PrintOverride = { 
+/**
+ * `PrintField::name`
+ */
+field: string, 
+/**
+ * The template's value; the system default without a template block
+ */
+template_value: JsonValue, user_value: JsonValue, };
+
+export type PrintSpec = { 
+/**
+ * Ignored on requests: a request-supplied spec is always User authority
+ */
+authority: PrintAuthority, dpi: number, color_space: ColorSpace, 
+/**
+ * Written as `bleed_inches`, a number of inches; also reads a length
+ * string under either name
+ */
+bleed_inches: number, 
+/**
+ * Output profile registered in the pipeline's `IccProfileStore`.
+ * On requests, `None` leaves the template's profile in place.
+ */
+icc_profile?: string | null, };
+
+export type RequestRecord = { asset_input: AssetInput, 
+/**
+ * How the source was supplied; absent without a source
+ */
+source?: SourceKind | null, seed?: number | string | null, prompt?: string | null, params?: { [key in string]?: JsonValue }, print_spec?: PrintSpec | null, };
+
+export type ScalingDecision = "vector_source" | "within_source" | "upscaled";
+
+export type ScalingPolicy = "forbid_upscale" | "allow_upscale_with_warning" | "allow";
+
+export type ScalingRecord = { policy: ScalingPolicy, decision: ScalingDecision, };
+
+export type Sheet = string | PhysicalSize;
+
+export type SignerInfo = { key_id: string, algorithm: string, };
+
+export type SourceKind = "inline" | "path";
+
+export type SpotColorMapping = { name: string, 
+/**
+ * `#RRGGBB` the artwork uses for this color
+ */
+rgbHex: string, 
+/**
+ * 8-bit C, M, Y, K (0 = no ink), as `CmykConverter::convert` produces
+ */
+cmyk: [number, number, number, number], };
+
+export type SpotColorUsage = { name: string, rgb_hex: string, cmyk: [number, number, number, number], 
+/**
+ * Pixels converted to the mapped ink values
+ */
+pixels: number, };
+
+export type StoredObject = { export_id: string, key: string, 
+/**
+ * The store's entity tag, when it returns one
+ */
+etag?: string | null, 
+/**
+ * Hex sha256 of the stored bytes
+ */
+sha256: string, };
+
+export type TemplatePrint = { dpi: number, colorSpace: ColorSpace, 
+/**
+ * Written as `bleedInches`, a number of inches; also reads a length
+ * string under either name
+ */
+bleedInches: number, allowUserPrintOverrides: boolean, 
+/**
+ * Fields users may not override even when overrides are allowed
+ */
+lockedFields?: Array<PrintField>, 
+/**
+ * Color spaces a user spec may ask for; any when empty. The block's
+ * own `colorSpace` is not held to it.
+ */
+allowedUserColorSpaces?: Array<ColorSpace>, 
+/**
+ * How artwork extends into the bleed (see `bleed`)
+ */
+bleedFill?: BleedFill, 
+/**
+ * Printer's marks in a slug outside the bleed (see `Marks`)
+ */
+marks?: Marks | true, 
+/**
+ * Distance inside the trim that critical content must keep clear of;
+ * checked by the `safe_margin` rule, drawn on guide proofs
+ */
+safeMargin?: string | null, 
+/**
+ * Output profile name (see `icc`)
+ */
+iccProfile?: string | null, 
+/**
+ * When Grayscale, refuse SVG exports rather than bake a luma filter
+ * over colored vectors (see `color`)
+ */
+trueGrayscaleVectors?: boolean, 
+/**
+ * Exact RGB to CMYK mappings applied ahead of the converter (see `color`)
+ */
+spotColors?: Array<SpotColorMapping>, 
+/**
+ * PDF/X standard the block's Pdf exports must conform to (see `pdf`)
+ */
+pdfStandard?: PdfStandard | null, 
+/**
+ * Partly transparent pixels in PDF/X exports: flatten or fail
+ */
+transparencyPolicy?: TransparencyPolicy, 
+/**
+ * `PrintProfile` the block was built from; its values are already
+ * applied, so this only records where they came from
+ */
+profile?: string | null, };
+
+export type TransparencyPolicy = "flatten" | "error";
+
+export type TrimBox = { size: [number, number], 
+/**
+ * Top-left corner of the trim box within the file
+ */
+offset: [number, number], };
+
+export type ValidationResult = { valid: boolean, violations: Array<ValidationViolation>, template_id: string, template_version: string, };
+
+export type ValidationViolation = { rule: string, severity: ViolationSeverity, message: string, expected: string | null, actual: string | null, remediation: Array<string>, };
+
+export type ViolationSeverity = "error" | "warning" | "info";
@@ -0,0 +1,183 @@
+//! ForgeImages Node Module - N-API Bindings
+//!
+//! The engine in-process for Node hosts, in place of a `forgeimages-cli`
+//! subprocess per request:
+//!
+//! ```js
+//! const forgeimages = require("forgeimages");
+//!
+//! const pipeline = forgeimages.createPipeline("templates");
+//! const result = pipeline.validate("pwa-icon", { width: 1024, height: 1024 });
+//! try {
+//!   const manifest = await pipeline.compile({ template_id: "pwa-icon", asset_input: { ... } });
+//! } catch (e) {
+//!   console.log(e.code, e.envelope.validation);
+//! }
+//! ```
+//!
+//! Arguments and results are plain objects shaped like the core's serde
+//! types. Failures throw an `Error` whose `code` is the `ErrorKind` and
+//! whose `envelope` is the core's `ErrorEnvelope`, as the CLI prints it.
+//! `compile` runs on the libuv threadpool and returns a promise; the rest
+//! answer synchronously. index.d.ts is generated from the core's types
+//! (`tests/types.rs` checks it is current); build with `npm run build`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use forgeimages_core::{
+    canonical_json as canonical, compute_job_hash as job_hash, CompilationPipeline, CompileRequest,
+    CompiledAsset, ErrorEnvelope, ErrorKind,
+    templates::{TemplateArrayError, TemplateRegistry},
+    validation::AssetInput,
+};
+use napi::{Env, Error, JsUnknown, Result, Status, Task, ValueType};
+use napi::bindgen_prelude::AsyncTask;
+use napi_derive::napi;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// The engine version compiles record
+#[napi(js_name = "ENGINE_VERSION")]
+pub const ENGINE_VERSION: &str = forgeimages_core::ENGINE_VERSION;
+
+/// The `Error` thrown for an envelope
+fn throw(env: Env, envelope: &ErrorEnvelope) -> Error {
+    let error = env.create_error(Error::new(Status::GenericFailure, envelope.message.clone()))
+        .and_then(|mut error| {
+            error.set_named_property("code", envelope.kind.name())?;
+            error.set_named_property("envelope", env.to_js_value(envelope)?)?;
+            Ok(error.into_unknown())
+        });
+    match error {
+        Ok(error) => Error::from(error),
+        Err(e) => e,
+    }
+}
+
+fn error(env: Env, kind: ErrorKind, message: impl ToString) -> Error {
+    throw(env, &ErrorEnvelope::new(kind, message.to_string()))
+}
+
+/// A plain JS value as the serde type `T`
+fn from_js<T: DeserializeOwned>(env: Env, value: JsUnknown) -> Result<T> {
+    env.from_js_value(value).map_err(|e| error(env, ErrorKind::InvalidPayload, format!("Invalid payload: {}", e.reason)))
+}
+
+/// A compilation pipeline over one template directory, or over templates
+/// given as JSON (text or parsed, read as strictly as template files)
+#[napi]
+pub fn create_pipeline(env: Env, templates: JsUnknown) -> Result<Pipeline> {
+    let registry = match templates.get_type()? {
+        ValueType::String => {
+            let text: String = env.from_js_value(templates)?;
+            if text.trim_start().starts_with('[') {
+                registry_from_json(env, &text)?
+            } else {
+                TemplateRegistry::load_layered(&[PathBuf::from(text)], false).map_err(|e| {
+                    error(env, ErrorKind::TemplatesUnavailable, format!("Failed to load templates: {}", e))
+                })?
+            }
+        }
+        _ => {
+            let value: Value = env.from_js_value(templates).map_err(|e| error(env, ErrorKind::InvalidTemplate, e.reason))?;
+            registry_from_json(env, &value.to_string())?
+        }
+    };
+    Ok(Pipeline { pipeline: Arc::new(CompilationPipeline::new(registry)) })
+}
+
+fn registry_from_json(env: Env, templates: &str) -> Result<TemplateRegistry> {
+    TemplateRegistry::from_json_array(templates).map_err(|e| match e {
+        TemplateArrayError::NotArray => {
+            error(env, ErrorKind::InvalidTemplate, "templates must be a directory or an array of templates")
+        }
+        e => throw(env, &ErrorEnvelope::from(&e)),
+    })
+}
+
+/// The canonical JSON every engine hash is taken over
+#[napi]
+pub fn canonical_json(env: Env, value: JsUnknown) -> Result<String> {
+    let value: Value = from_js(env, value)?;
+    canonical(&value).map_err(|e| error(env, ErrorKind::HashingError, e))
+}
+
+/// `compute_job_hash` from the core: a digest of the template, the payload
+/// and the engine version (this engine's by default)
+#[napi]
+pub fn compute_job_hash(
+    env: Env,
+    template_id: String,
+    template_version: String,
+    payload: JsUnknown,
+    engine_version: Option<String>,
+) -> Result<String> {
+    let payload: Value = from_js(env, payload)?;
+    let engine_version = engine_version.as_deref().unwrap_or(ENGINE_VERSION);
+    job_hash(&template_id, &template_version, &payload, engine_version).map_err(|e| error(env, ErrorKind::HashingError, e))
+}
+
+/// A pipeline from `createPipeline`; shareable between concurrent compiles
+#[napi]
+pub struct Pipeline {
+    pipeline: Arc<CompilationPipeline>,
+}
+
+#[napi]
+impl Pipeline {
+    /// The `ValidationResult` for an `AssetInput`, valid or not
+    #[napi]
+    pub fn validate(&self, env: Env, template_id: String, input: JsUnknown) -> Result<JsUnknown> {
+        let input: AssetInput = from_js(env, input)?;
+        let result = self.pipeline.validate_asset(&template_id, &input).map_err(|e| throw(env, &e.into()))?;
+        env.to_js_value(&result)
+    }
+
+    /// Compile a `CompileRequest` to its `CompiledAsset`, exports embedded.
+    /// Rejects with `validation_failed`, the `ValidationResult` in the
+    /// envelope, when validation blocks it.
+    #[napi]
+    pub fn compile(&self, env: Env, request: JsUnknown) -> Result<AsyncTask<Compile>> {
+        let request = from_js(env, request)?;
+        Ok(AsyncTask::new(Compile { pipeline: Arc::clone(&self.pipeline), request }))
+    }
+
+    /// The job hash `compile` would record for a `CompileRequest`
+    #[napi]
+    pub fn job_hash(&self, env: Env, request: JsUnknown) -> Result<String> {
+        let request: CompileRequest = from_js(env, request)?;
+        self.pipeline.job_hash(&request).map_err(|e| throw(env, &e.into()))
+    }
+
+    /// Template IDs, sorted
+    #[napi]
+    pub fn template_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.pipeline.list_templates().into_iter().map(|template| template.id).collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// One `compile` call, off the JS thread
+pub struct Compile {
+    pipeline: Arc<CompilationPipeline>,
+    request: CompileRequest,
+}
+
+impl Task for Compile {
+    type Output = std::result::Result<CompiledAsset, ErrorEnvelope>;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let request = &self.request;
+        Ok(self.pipeline.compile_asset(request).map_err(|e| self.pipeline.error_envelope(request, &e)))
+    }
+
+    fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        match output {
+            Ok(asset) => env.to_js_value(&asset),
+            Err(envelope) => Err(throw(env, &envelope)),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Native Module Tests
+//
+// The module must give the same answers as forgeimages-cli: results are
+// checked against the CLI golden files in forgeimages-core.
+//
+// Run after `npm run build`:  npm test
+
+const assert = require("node:assert/strict");
+const fs = require("node:fs");
+const path = require("node:path");
+const { describe, it } = require("node:test");
+
+const forgeimages = require("..");
+
+const CORE = path.join(__dirname, "..", "..", "forgeimages-core");
+const TEMPLATES = path.join(CORE, "tests", "fixtures", "templates");
+const REQUEST = { template_id: "pwa-icon", asset_input: { width: 1024, height: 1024 } };
+
+const golden = (name) => JSON.parse(fs.readFileSync(path.join(CORE, "tests", "golden", "cli", name), "utf8"));
+const pipeline = forgeimages.createPipeline(TEMPLATES);
+
+describe("answers match the CLI", () => {
+  it("validates", () => {
+    assert.deepEqual(pipeline.validate("pwa-icon", { width: 1024, height: 1024 }), golden("validate-valid.json"));
+    assert.deepEqual(pipeline.validate("pwa-icon", { width: 100, height: 50 }), golden("validate-invalid.json"));
+  });
+
+  it("hashes jobs", () => {
+    assert.equal(pipeline.jobHash(REQUEST), golden("hash-job.json").job_hash);
+  });
+
+  it("compiles on the threadpool", async () => {
+    const assets = await Promise.all(Array.from({ length: 8 }, () => pipeline.compile(REQUEST)));
+    for (const asset of assets) {
+      assert.equal(asset.job_hash, golden("hash-job.json").job_hash);
+      assert.deepEqual(asset.exports.map((e) => e.id), golden("manifest.json").exports.map((e) => e.id));
+    }
+  });
+
+  it("takes templates as JSON", () => {
+    const template = fs.readFileSync(path.join(TEMPLATES, "pwa-icon.json"), "utf8");
+    for (const templates of [`[${template}]`, [JSON.parse(template)]]) {
+      const fromJson = forgeimages.createPipeline(templates);
+      assert.deepEqual(fromJson.templateIds(), ["pwa-icon"]);
+      assert.equal(fromJson.jobHash(REQUEST), golden("hash-job.json").job_hash);
+    }
+  });
+});
+
+describe("errors carry the envelope", () => {
+  it("rejects blocked compiles with their validation", async () => {
+    const request = { template_id: "pwa-icon", asset_input: { width: 100, height: 50 } };
+    await assert.rejects(pipeline.compile(request), (e) => {
+      assert.ok(e instanceof Error);
+      assert.equal(e.code, "validation_failed");
+      assert.equal(e.envelope.success, false);
+      assert.equal(e.envelope.error_kind, "validation_failed");
+      assert.equal(e.envelope.template_id, "pwa-icon");
+      assert.deepEqual(e.envelope.validation, golden("validate-invalid.json"));
+      return true;
+    });
+  });
+
+  it("throws the CLI's envelope", () => {
+    assert.throws(() => pipeline.validate("missing", { width: 1, height: 1 }), (e) => {
+      const { success, error_kind, message, template_id, details } = golden("error-template-not-found.json");
+      assert.deepEqual(e.envelope, { success, error_kind, message, template_id, details });
+      assert.equal(e.message, message);
+      return true;
+    });
+    assert.throws(() => pipeline.jobHash({ template_id: "pwa-icon" }), { code: "invalid_payload" });
+    assert.throws(() => forgeimages.createPipeline(path.join(TEMPLATES, "pwa-icon.json")), { code: "templates_unavailable" });
+    assert.throws(() => forgeimages.createPipeline("[{}]"), { code: "invalid_template" });
+  });
+});
+
+describe("hashing", () => {
+  it("canonicalizes JSON", () => {
+    assert.equal(forgeimages.canonicalJson({ b: [1, 2.5], a: "é" }), '{"a":"é","b":[1,2.5]}');
+  });
+
+  it("defaults job hashes to this engine", () => {
+    const payload = { width: 1024 };
+    assert.equal(
+      forgeimages.computeJobHash("pwa-icon", "1.0.0", payload),
+      forgeimages.computeJobHash("pwa-icon", "1.0.0", payload, forgeimages.ENGINE_VERSION),
+    );
+    assert.match(forgeimages.computeJobHash("pwa-icon", "1.0.0", payload, "0.0.1"), /^sha256:/);
+  });
+});
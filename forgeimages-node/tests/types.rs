@@ -0,0 +1,85 @@
+//! index.d.ts is the module's API over the core's types as ts-rs declares
+//! them; `FORGEIMAGES_UPDATE_GOLDEN=1` regenerates it
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use forgeimages_core::{validation::AssetInput, CompileRequest, CompiledAsset, ErrorEnvelope, ValidationResult};
+use ts_rs::{TypeVisitor, TS};
+
+/// What src/lib.rs exports, in terms of the declarations below
+const API: &str = r#"// Generated by tests/types.rs from the core's serde types; do not edit
+
+export const ENGINE_VERSION: string
+
+/** What every call throws, or `compile` rejects with, when it fails */
+export interface ForgeImagesError extends Error {
+  code: ErrorKind
+  envelope: ErrorEnvelope
+}
+
+/**
+ * A pipeline over one template directory, or over templates given as JSON
+ * (text or parsed)
+ */
+export function createPipeline(templates: string | Array<object>): Pipeline
+/** The canonical JSON every engine hash is taken over */
+export function canonicalJson(value: unknown): string
+/** A digest of the template, the payload and the engine version (this engine's by default) */
+export function computeJobHash(templateId: string, templateVersion: string, payload: unknown, engineVersion?: string): string
+
+export class Pipeline {
+  private constructor()
+  /** The `ValidationResult` for an `AssetInput`, valid or not */
+  validate(templateId: string, input: AssetInput): ValidationResult
+  /** Compiled on the libuv threadpool; rejects with `validation_failed` when validation blocks it */
+  compile(request: CompileRequest): Promise<CompiledAsset>
+  /** The job hash `compile` would record */
+  jobHash(request: CompileRequest): string
+  /** Template IDs, sorted */
+  templateIds(): Array<string>
+}
+"#;
+
+/// Declarations of `T` and everything it refers to, by name
+#[derive(Default)]
+struct Declarations {
+    seen: HashSet<TypeId>,
+    by_name: BTreeMap<String, String>,
+}
+
+impl TypeVisitor for Declarations {
+    fn visit<T: TS + 'static + ?Sized>(&mut self) {
+        // Primitives, `Vec`, `Option` and the like have nothing to declare
+        if T::output_path().is_none() || !self.seen.insert(TypeId::of::<T>()) {
+            return;
+        }
+        self.by_name.insert(T::ident(), format!("export {}", T::decl()));
+        T::visit_dependencies(self);
+    }
+}
+
+#[test]
+fn test_type_definitions_are_current() {
+    let mut declarations = Declarations::default();
+    declarations.visit::<AssetInput>();
+    declarations.visit::<CompileRequest>();
+    declarations.visit::<ValidationResult>();
+    declarations.visit::<CompiledAsset>();
+    declarations.visit::<ErrorEnvelope>();
+    let mut generated = API.to_string();
+    for declaration in declarations.by_name.values() {
+        generated.push('\n');
+        generated.push_str(declaration);
+        generated.push('\n');
+    }
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("index.d.ts");
+    if std::env::var_os("FORGEIMAGES_UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    assert_eq!(generated, fs::read_to_string(&path).unwrap(), "FORGEIMAGES_UPDATE_GOLDEN=1 regenerates index.d.ts");
+}
@@ -0,0 +1,34 @@
+"""Type stubs for the native ForgeImages module (src/lib.rs)."""
+
+from os import PathLike
+from typing import Any, Sequence
+
+ENGINE_VERSION: str
+
+class ForgeImagesError(Exception):
+    """An engine call failed; ``kind`` is the envelope's ``error_kind``."""
+
+    kind: str
+    template_id: str | None
+    details: dict[str, Any]
+
+class ValidationError(ForgeImagesError):
+    """Validation blocked a compile."""
+
+    violations: list[dict[str, Any]]
+    validation: dict[str, Any] | None
+
+class Pipeline:
+    def __init__(self, templates_dir: str | PathLike[str] | Sequence[str | PathLike[str]]) -> None: ...
+    def validate(self, template_id: str, input: dict[str, Any]) -> dict[str, Any]: ...
+    def compile(self, request: dict[str, Any]) -> dict[str, Any]: ...
+    def job_hash(self, request: dict[str, Any]) -> str: ...
+    def template_ids(self) -> list[str]: ...
+
+def canonical_json(obj: Any) -> str: ...
+def compute_job_hash(
+    template_id: str,
+    template_version: str,
+    payload: Any,
+    engine_version: str = ...,
+) -> str: ...
@@ -0,0 +1,173 @@
+//! ForgeImages Python Module - Native Bindings
+//!
+//! The engine in-process for Python hosts, in place of a `forgeimages-cli`
+//! subprocess per call:
+//!
+//! ```python
+//! import forgeimages
+//!
+//! pipeline = forgeimages.Pipeline("templates")
+//! result = pipeline.validate("pwa-icon", {"width": 1024, "height": 1024})
+//! try:
+//!     manifest = pipeline.compile({"template_id": "pwa-icon", "asset_input": {...}})
+//! except forgeimages.ValidationError as e:
+//!     print(e.violations)
+//! ```
+//!
+//! Arguments and results are the core's serde types as plain dicts and
+//! lists, converted through JSON. Failures raise `ForgeImagesError`
+//! carrying the core's `ErrorEnvelope` as `kind`, `template_id` and
+//! `details`; blocked compiles raise its subclass `ValidationError`.
+//! Compiles release the GIL, so Python threads sharing one `Pipeline`
+//! overlap their work. Build with `maturin develop` (see pyproject.toml);
+//! the type stubs are in forgeimages.pyi.
+
+use std::path::PathBuf;
+
+use forgeimages_core::{
+    canonical_json as canonical, compute_job_hash as job_hash, parse_strict, CompilationPipeline, CompileRequest,
+    ErrorEnvelope, ErrorKind, PipelineError, ENGINE_VERSION,
+    templates::TemplateRegistry,
+    validation::AssetInput,
+};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+create_exception!(forgeimages, ForgeImagesError, PyException, "An engine call failed; `kind` names the failure");
+create_exception!(forgeimages, ValidationError, ForgeImagesError, "Validation blocked a compile; `violations` lists why");
+
+/// The exception for a failed call, the envelope's fields as attributes:
+/// `ValidationError` for a blocked compile, else `ForgeImagesError`
+fn raise(py: Python<'_>, envelope: ErrorEnvelope) -> PyErr {
+    let error = match envelope.kind {
+        ErrorKind::ValidationFailed => ValidationError::new_err(envelope.message.clone()),
+        _ => ForgeImagesError::new_err(envelope.message.clone()),
+    };
+    let value = error.value(py);
+    let attributes = || -> PyResult<()> {
+        value.setattr("kind", envelope.kind.name())?;
+        value.setattr("template_id", &envelope.template_id)?;
+        value.setattr("details", to_py(py, &envelope.details)?)?;
+        if envelope.kind == ErrorKind::ValidationFailed {
+            // The violations, as validate reports them; request-level
+            // rules (the source's own) are only in the message
+            let violations = envelope.validation.as_ref().map(|result| result.violations.as_slice()).unwrap_or_default();
+            value.setattr("violations", to_py(py, &violations)?)?;
+            value.setattr("validation", to_py(py, &envelope.validation)?)?;
+        }
+        Ok(())
+    };
+    match attributes() {
+        Ok(()) => error,
+        Err(e) => e,
+    }
+}
+
+fn error(py: Python<'_>, kind: ErrorKind, message: impl ToString) -> PyErr {
+    raise(py, ErrorEnvelope::new(kind, message.to_string()))
+}
+
+fn pipeline_error(py: Python<'_>, e: &PipelineError) -> PyErr {
+    raise(py, e.into())
+}
+
+/// A dict, list or scalar as the serde type `T`, via JSON
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let py = value.py();
+    let json: String = py.import("json")?.call_method1("dumps", (value,))?.extract()?;
+    serde_json::from_str(&json).map_err(|e| error(py, ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)))
+}
+
+/// `value` as plain Python objects, via JSON
+fn to_py<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value).map_err(|e| error(py, ErrorKind::SerializationError, e))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// A compilation pipeline over one or more template directories, layered
+/// as repeated `--templates-dir` flags are
+#[pyclass(frozen, module = "forgeimages")]
+struct Pipeline {
+    pipeline: CompilationPipeline,
+}
+
+#[pymethods]
+impl Pipeline {
+    #[new]
+    fn new(py: Python<'_>, templates_dir: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let dirs: Vec<PathBuf> = match templates_dir.extract::<PathBuf>() {
+            Ok(dir) => vec![dir],
+            Err(_) => templates_dir.extract()?,
+        };
+        let registry = TemplateRegistry::load_layered(&dirs, false)
+            .map_err(|e| error(py, ErrorKind::TemplatesUnavailable, format!("Failed to load templates: {}", e)))?;
+        Ok(Self { pipeline: CompilationPipeline::new(registry) })
+    }
+
+    /// The `ValidationResult` for an `AssetInput`, valid or not
+    fn validate<'py>(&self, py: Python<'py>, template_id: &str, input: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let input: AssetInput = from_py(input)?;
+        let result = self.pipeline.validate_asset(template_id, &input).map_err(|e| pipeline_error(py, &e))?;
+        to_py(py, &result)
+    }
+
+    /// Compile a `CompileRequest` to its `CompiledAsset`, exports embedded.
+    /// Raises `ValidationError` when validation blocks it.
+    fn compile<'py>(&self, py: Python<'py>, request: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let request: CompileRequest = from_py(request)?;
+        match py.detach(|| self.pipeline.compile_asset(&request)) {
+            Ok(asset) => to_py(py, &asset),
+            Err(e) => Err(raise(py, self.pipeline.error_envelope(&request, &e))),
+        }
+    }
+
+    /// The job hash `compile` would record for a `CompileRequest`
+    fn job_hash(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<String> {
+        let request: CompileRequest = from_py(request)?;
+        self.pipeline.job_hash(&request).map_err(|e| pipeline_error(py, &e))
+    }
+
+    /// Template IDs, sorted
+    fn template_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.pipeline.list_templates().into_iter().map(|template| template.id).collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// The canonical JSON every engine hash is taken over
+#[pyfunction]
+fn canonical_json(py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<String> {
+    let json: String = py.import("json")?.call_method1("dumps", (value,))?.extract()?;
+    let value = parse_strict(&json).map_err(|e| error(py, ErrorKind::InvalidPayload, e))?;
+    canonical(&value).map_err(|e| error(py, ErrorKind::HashingError, e))
+}
+
+/// `compute_job_hash` from the core: a digest of the template, the payload
+/// and the engine version
+#[pyfunction]
+#[pyo3(signature = (template_id, template_version, payload, engine_version = ENGINE_VERSION))]
+fn compute_job_hash(
+    py: Python<'_>,
+    template_id: &str,
+    template_version: &str,
+    payload: &Bound<'_, PyAny>,
+    engine_version: &str,
+) -> PyResult<String> {
+    let payload: serde_json::Value = from_py(payload)?;
+    job_hash(template_id, template_version, &payload, engine_version).map_err(|e| error(py, ErrorKind::HashingError, e))
+}
+
+#[pymodule]
+fn forgeimages(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Pipeline>()?;
+    m.add_function(wrap_pyfunction!(canonical_json, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_job_hash, m)?)?;
+    m.add("ForgeImagesError", m.py().get_type::<ForgeImagesError>())?;
+    m.add("ValidationError", m.py().get_type::<ValidationError>())?;
+    m.add("ENGINE_VERSION", ENGINE_VERSION)?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+"""
+Native Module Tests
+
+The module must give the same answers as forgeimages-cli: results are
+checked against the CLI golden files in forgeimages-core.
+
+Run after `maturin develop`:  pytest
+"""
+
+import json
+import threading
+from pathlib import Path
+
+import pytest
+
+import forgeimages
+
+CORE = Path(__file__).resolve().parents[2] / "forgeimages-core"
+TEMPLATES = CORE / "tests" / "fixtures" / "templates"
+REQUEST = {"template_id": "pwa-icon", "asset_input": {"width": 1024, "height": 1024}}
+
+
+def golden(name):
+    return json.loads((CORE / "tests" / "golden" / "cli" / name).read_text())
+
+
+@pytest.fixture(scope="module")
+def pipeline():
+    return forgeimages.Pipeline(TEMPLATES)
+
+
+class TestAnswersMatchTheCli:
+    def test_validate(self, pipeline):
+        assert pipeline.validate("pwa-icon", {"width": 1024, "height": 1024}) == golden("validate-valid.json")
+        assert pipeline.validate("pwa-icon", {"width": 100, "height": 50}) == golden("validate-invalid.json")
+
+    def test_job_hash(self, pipeline):
+        assert pipeline.job_hash(REQUEST) == golden("hash-job.json")["job_hash"]
+
+    def test_compile(self, pipeline):
+        asset = pipeline.compile(REQUEST)
+        assert asset["job_hash"] == golden("hash-job.json")["job_hash"]
+        assert [export["id"] for export in asset["exports"]] == [
+            export["id"] for export in golden("manifest.json")["exports"]
+        ]
+
+    def test_template_ids(self, pipeline):
+        assert pipeline.template_ids() == ["pwa-icon"]
+
+
+class TestErrors:
+    def test_blocked_compile_carries_violations(self, pipeline):
+        request = {"template_id": "pwa-icon", "asset_input": {"width": 100, "height": 50}}
+        with pytest.raises(forgeimages.ValidationError) as raised:
+            pipeline.compile(request)
+        assert raised.value.kind == "validation_failed"
+        assert raised.value.template_id == "pwa-icon"
+        assert raised.value.violations == golden("validate-invalid.json")["violations"]
+        assert raised.value.validation["valid"] is False
+
+    def test_errors_name_their_kind(self, pipeline):
+        with pytest.raises(forgeimages.ForgeImagesError) as raised:
+            pipeline.validate("banner", {"width": 1, "height": 1})
+        assert raised.value.kind == "template_not_found"
+        assert raised.value.template_id == "banner"
+        assert raised.value.details == {"template_id": "banner"}
+        with pytest.raises(forgeimages.ForgeImagesError) as raised:
+            pipeline.compile({"template_id": "pwa-icon"})
+        assert raised.value.kind == "invalid_payload"
+        with pytest.raises(forgeimages.ForgeImagesError) as raised:
+            forgeimages.Pipeline(TEMPLATES / "pwa-icon.json")
+        assert raised.value.kind == "templates_unavailable"
+
+
+class TestHashing:
+    def test_canonical_json(self):
+        assert forgeimages.canonical_json({"b": [1, 2.5], "a": "é"}) == '{"a":"é","b":[1,2.5]}'
+
+    def test_compute_job_hash_defaults_to_this_engine(self):
+        payload = {"width": 1024}
+        assert forgeimages.compute_job_hash("pwa-icon", "1.0.0", payload) == forgeimages.compute_job_hash(
+            "pwa-icon", "1.0.0", payload, forgeimages.ENGINE_VERSION
+        )
+        assert forgeimages.compute_job_hash("pwa-icon", "1.0.0", payload, "0.0.1").startswith("sha256:")
+
+
+def test_threads_share_a_pipeline(pipeline):
+    hashes = []
+
+    def compile_one():
+        hashes.append(pipeline.compile(REQUEST)["job_hash"])
+
+    threads = [threading.Thread(target=compile_one) for _ in range(8)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert hashes == [golden("hash-job.json")["job_hash"]] * 8
@@ -0,0 +1,107 @@
+//! ForgeImages WASM - Validation and Hashing in the Browser
+//!
+//! Lets an upload UI validate an asset, and compute the hashes the server
+//! will record, before sending any bytes. Templates come in as JSON, read
+//! as strictly as template files; nothing touches a filesystem and nothing
+//! is rendered. Build with `wasm-pack build --target web forgeimages-wasm`.
+//!
+//! Every function returns a string and throws an `Error` whose message is
+//! `<error_kind>: <message>` (an `ErrorKind`, as in the CLI's envelope) when
+//! it cannot answer. A failed validation is an answer, not an error.
+
+use forgeimages_core::{
+    canonical_json, parse_strict, CompilationPipeline, CompileRequest, ErrorEnvelope, ErrorKind, Template,
+    print::ProfileRegistry,
+    templates::TemplateRegistry,
+    validation::AssetInput,
+};
+use wasm_bindgen::prelude::*;
+
+/// `validate(templateJson, inputJson)`: the `ValidationResult` JSON for
+/// an `AssetInput` against the template, as `forgeimages-cli validate`
+/// prints it
+#[wasm_bindgen]
+pub fn validate(template_json: &str, input_json: &str) -> Result<String, JsError> {
+    validate_json(template_json, input_json).map_err(throw)
+}
+
+/// `canonicalJson(json)`: the canonical form every engine hash is taken
+/// over; duplicate keys are rejected
+#[wasm_bindgen(js_name = canonicalJson)]
+pub fn canonical(json: &str) -> Result<String, JsError> {
+    canonical_form(json).map_err(throw)
+}
+
+/// `jobHash(templateJson, requestJson)`: the job hash a compile of the
+/// `CompileRequest` will record, as `forgeimages-cli hash --job` prints it
+#[wasm_bindgen(js_name = jobHash)]
+pub fn job_hash(template_json: &str, request_json: &str) -> Result<String, JsError> {
+    job_hash_of(template_json, request_json).map_err(throw)
+}
+
+/// The JS `Error` for a call that could not answer
+fn throw(envelope: ErrorEnvelope) -> JsError {
+    JsError::new(&envelope.to_string())
+}
+
+/// A pipeline whose registry holds just `template_json`
+fn pipeline(template_json: &str) -> Result<(CompilationPipeline, String), ErrorEnvelope> {
+    let template = Template::from_json(template_json, &ProfileRegistry::new())
+        .map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidTemplate, e.to_string()))?;
+    let id = template.id.clone();
+    let mut registry = TemplateRegistry::new();
+    registry.register(template);
+    Ok((CompilationPipeline::new(registry), id))
+}
+
+fn validate_json(template_json: &str, input_json: &str) -> Result<String, ErrorEnvelope> {
+    let (pipeline, id) = pipeline(template_json)?;
+    let input: AssetInput = serde_json::from_str(input_json)
+        .map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)))?;
+    let result = pipeline.validate_asset(&id, &input).map_err(ErrorEnvelope::from)?;
+    serde_json::to_string(&result).map_err(|e| ErrorEnvelope::new(ErrorKind::SerializationError, e.to_string()))
+}
+
+fn canonical_form(json: &str) -> Result<String, ErrorEnvelope> {
+    let value = parse_strict(json).map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidJson, e.to_string()))?;
+    canonical_json(&value).map_err(|e| ErrorEnvelope::new(ErrorKind::HashingError, e.to_string()))
+}
+
+fn job_hash_of(template_json: &str, request_json: &str) -> Result<String, ErrorEnvelope> {
+    let (pipeline, _) = pipeline(template_json)?;
+    let request: CompileRequest = serde_json::from_str(request_json)
+        .map_err(|e| ErrorEnvelope::new(ErrorKind::InvalidPayload, format!("Invalid payload: {}", e)))?;
+    pipeline.job_hash(&request).map_err(ErrorEnvelope::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = include_str!("../../forgeimages-core/tests/fixtures/templates/pwa-icon.json");
+    const REQUEST: &str = r#"{"template_id":"pwa-icon","asset_input":{"width":1024,"height":1024}}"#;
+
+    /// The server's answer for the same request, from the CLI golden files
+    fn golden(name: &str) -> serde_json::Value {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../forgeimages-core/tests/golden/cli").join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_answers_match_the_server() {
+        assert_eq!(job_hash_of(TEMPLATE, REQUEST).unwrap(), golden("hash-job.json")["job_hash"]);
+        let result: serde_json::Value = serde_json::from_str(&validate_json(TEMPLATE, r#"{"width":100,"height":50}"#).unwrap()).unwrap();
+        assert_eq!(result, golden("validate-invalid.json"));
+        assert_eq!(canonical_form(r#"{"b": [1, 2.5], "a": "é"}"#).unwrap(), r#"{"a":"é","b":[1,2.5]}"#);
+    }
+
+    #[test]
+    fn test_failures_name_their_kind() {
+        let kind = |result: Result<String, ErrorEnvelope>| result.unwrap_err().kind;
+        assert_eq!(kind(validate_json("{}", "{}")), ErrorKind::InvalidTemplate);
+        assert_eq!(kind(validate_json(TEMPLATE, "[]")), ErrorKind::InvalidPayload);
+        assert_eq!(kind(canonical_form(r#"{"a":1,"a":2}"#)), ErrorKind::InvalidJson);
+        let other = REQUEST.replace(r#""template_id":"pwa-icon""#, r#""template_id":"banner""#);
+        assert_eq!(kind(job_hash_of(TEMPLATE, &other)), ErrorKind::TemplateNotFound);
+    }
+}
@@ -0,0 +1,36 @@
+//! Headless browser tests: `wasm-pack test --headless --firefox forgeimages-wasm`
+//! (or `--chrome`). The exports answer in the browser as the server does.
+
+#![cfg(target_arch = "wasm32")]
+
+use forgeimages_wasm::{canonical, job_hash, validate};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const TEMPLATE: &str = include_str!("../../forgeimages-core/tests/fixtures/templates/pwa-icon.json");
+const HASH_JOB: &str = include_str!("../../forgeimages-core/tests/golden/cli/hash-job.json");
+const VALIDATE_INVALID: &str = include_str!("../../forgeimages-core/tests/golden/cli/validate-invalid.json");
+
+fn json(text: &str) -> serde_json::Value {
+    serde_json::from_str(text).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_job_hash_matches_the_server() {
+    let request = r#"{"template_id":"pwa-icon","asset_input":{"width":1024,"height":1024}}"#;
+    assert_eq!(job_hash(TEMPLATE, request).unwrap(), json(HASH_JOB)["job_hash"]);
+}
+
+#[wasm_bindgen_test]
+fn test_validate_matches_the_server() {
+    assert_eq!(json(&validate(TEMPLATE, r#"{"width":100,"height":50}"#).unwrap()), json(VALIDATE_INVALID));
+    assert_eq!(json(&validate(TEMPLATE, r#"{"width":1024,"height":1024}"#).unwrap())["valid"], true);
+}
+
+#[wasm_bindgen_test]
+fn test_canonical_json_and_errors() {
+    assert_eq!(canonical(r#"{"b":1,"a":[true,null]}"#).unwrap(), r#"{"a":[true,null],"b":1}"#);
+    assert!(canonical(r#"{"a":1,"a":2}"#).is_err());
+    assert!(validate("{}", "{}").is_err());
+}